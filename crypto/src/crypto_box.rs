@@ -161,6 +161,21 @@ impl PrecomputedKey {
     }
 }
 
+impl CryptoKey for PrecomputedKey {
+    fn from_bytes<B: AsRef<[u8]>>(buf: B) -> Result<Self, CryptoError> {
+        ensure_crypto_key_bytes(buf)
+            .map(|key_bytes| PrecomputedKey(box_::PrecomputedKey(key_bytes)))
+    }
+}
+
+impl FromHex for PrecomputedKey {
+    type Error = CryptoError;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        Self::from_bytes(hex::decode(hex)?)
+    }
+}
+
 impl From<FromHexError> for CryptoError {
     fn from(e: FromHexError) -> Self {
         CryptoError::InvalidKey {
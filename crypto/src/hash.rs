@@ -24,6 +24,9 @@ mod prefix_bytes {
     pub const PUBLIC_KEY_ED25519: [u8; 4] = [13, 15, 37, 217];
     pub const PUBLIC_KEY_SECP256K1: [u8; 4] = [3, 254, 226, 86];
     pub const PUBLIC_KEY_P256: [u8; 4] = [3, 178, 139, 127];
+    pub const SEED_ED25519: [u8; 4] = [13, 15, 58, 7];
+    pub const SIGNATURE_ED25519: [u8; 5] = [9, 245, 205, 134, 18];
+    pub const NONCE_HASH: [u8; 3] = [69, 220, 169];
 }
 
 pub type Hash = Vec<u8>;
@@ -144,6 +147,9 @@ define_hash!(CryptoboxPublicKeyHash);
 define_hash!(PublicKeyEd25519);
 define_hash!(PublicKeySecp256k1);
 define_hash!(PublicKeyP256);
+define_hash!(SeedEd25519);
+define_hash!(SignatureEd25519);
+define_hash!(NonceHash);
 
 /// Note: see Tezos ocaml lib_crypto/base58.ml
 #[derive(Debug, Copy, Clone)]
@@ -182,6 +188,12 @@ pub enum HashType {
     PublicKeySecp256k1,
     // "\003\178\139\127" (* p2pk(55) *)
     PublicKeyP256,
+    // "\013\015\058\007" (* edsk(54) *)
+    SeedEd25519,
+    // "\009\245\205\134\018" (* edsig(99) *)
+    SignatureEd25519,
+    // "\069\220\169" (* nce(53) *)
+    NonceHash,
 }
 
 impl HashType {
@@ -206,6 +218,9 @@ impl HashType {
             HashType::PublicKeyEd25519 => &PUBLIC_KEY_ED25519,
             HashType::PublicKeySecp256k1 => &PUBLIC_KEY_SECP256K1,
             HashType::PublicKeyP256 => &PUBLIC_KEY_P256,
+            HashType::SeedEd25519 => &SEED_ED25519,
+            HashType::SignatureEd25519 => &SIGNATURE_ED25519,
+            HashType::NonceHash => &NONCE_HASH,
         }
     }
 
@@ -221,13 +236,16 @@ impl HashType {
             | HashType::OperationListListHash
             | HashType::OperationMetadataHash
             | HashType::OperationMetadataListListHash
-            | HashType::PublicKeyEd25519 => 32,
+            | HashType::PublicKeyEd25519
+            | HashType::SeedEd25519
+            | HashType::NonceHash => 32,
             HashType::CryptoboxPublicKeyHash => 16,
             HashType::ContractKt1Hash
             | HashType::ContractTz1Hash
             | HashType::ContractTz2Hash
             | HashType::ContractTz3Hash => 20,
             HashType::PublicKeySecp256k1 | HashType::PublicKeyP256 => 33,
+            HashType::SignatureEd25519 => 64,
         }
     }
 
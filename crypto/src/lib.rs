@@ -12,6 +12,7 @@ pub mod crypto_box;
 pub mod nonce;
 pub mod proof_of_work;
 pub mod seeded_step;
+pub mod signature;
 #[macro_use]
 pub mod hash;
 
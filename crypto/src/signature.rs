@@ -0,0 +1,120 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! This module wrapps [`sodiumoxide::crypto::sign::`] stuff,
+//! which is used for signing of blocks and operations with ed25519 keys (edsk/edpk/edsig).
+//!
+//! Like in Tezos, signed data are not signed directly, but signature is created from
+//! the blake2b hash of `watermark + data`.
+
+use sodiumoxide::crypto::sign;
+
+use crate::blake2b;
+use crate::hash::{PublicKeyEd25519, SeedEd25519, SignatureEd25519};
+use crate::CryptoError;
+
+/// Watermark used for signing of block headers (`0x01 + chain_id`)
+pub fn block_header_watermark(chain_id: &[u8]) -> Vec<u8> {
+    let mut watermark = Vec::with_capacity(1 + chain_id.len());
+    watermark.push(0x01);
+    watermark.extend_from_slice(chain_id);
+    watermark
+}
+
+/// Watermark used for signing of generic operations
+pub const GENERIC_OPERATION_WATERMARK: [u8; 1] = [0x03];
+
+fn keypair(seed: &SeedEd25519) -> Result<(sign::PublicKey, sign::SecretKey), CryptoError> {
+    let seed = sign::Seed::from_slice(seed.as_ref()).ok_or(CryptoError::InvalidKeySize {
+        expected: sign::SEEDBYTES,
+        actual: seed.as_ref().len(),
+    })?;
+    Ok(sign::keypair_from_seed(&seed))
+}
+
+/// Returns public key for the secret key (seed)
+pub fn public_key_ed25519(seed: &SeedEd25519) -> Result<PublicKeyEd25519, CryptoError> {
+    let (public_key, _) = keypair(seed)?;
+    Ok(PublicKeyEd25519(public_key.as_ref().to_vec()))
+}
+
+/// Signs blake2b digest of `watermark + data` with the secret key (seed)
+pub fn sign_ed25519(
+    seed: &SeedEd25519,
+    watermark: &[u8],
+    data: &[u8],
+) -> Result<SignatureEd25519, CryptoError> {
+    let (_, secret_key) = keypair(seed)?;
+    // signed message is signature + digest
+    let mut signature = sign::sign(&digest(watermark, data), &secret_key);
+    signature.truncate(sign::SIGNATUREBYTES);
+    Ok(SignatureEd25519(signature))
+}
+
+/// Verifies signature of blake2b digest of `watermark + data`
+pub fn verify_ed25519(
+    public_key: &PublicKeyEd25519,
+    signature: &SignatureEd25519,
+    watermark: &[u8],
+    data: &[u8],
+) -> Result<bool, CryptoError> {
+    let public_key =
+        sign::PublicKey::from_slice(public_key.as_ref()).ok_or(CryptoError::InvalidKeySize {
+            expected: sign::PUBLICKEYBYTES,
+            actual: public_key.as_ref().len(),
+        })?;
+    let mut signed_message = signature.as_ref().clone();
+    signed_message.extend(digest(watermark, data));
+    Ok(sign::verify(&signed_message, &public_key).is_ok())
+}
+
+fn digest(watermark: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut signed = Vec::with_capacity(watermark.len() + data.len());
+    signed.extend_from_slice(watermark);
+    signed.extend_from_slice(data);
+    blake2b::digest_256(&signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_from_seed() -> Result<(), failure::Error> {
+        // sandbox activator key
+        let seed = SeedEd25519::from_base58_check(
+            "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6",
+        )?;
+        let public_key = public_key_ed25519(&seed)?;
+        assert_eq!(
+            "edpkuSLWfVU1Vq7Jg9FucPyKmma6otcMHac9zG4oU1KMHSTBpJuGQ2",
+            public_key.to_base58_check()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_and_verify() -> Result<(), failure::Error> {
+        let seed = SeedEd25519::from_base58_check(
+            "edsk3gUfUPyBSfrS9CCgmCiQsTCHGkviBDusMxDJstFtojtc1zcpsh",
+        )?;
+        let public_key = public_key_ed25519(&seed)?;
+        let watermark = block_header_watermark(&hex::decode("8eceda2f")?);
+
+        let signature = sign_ed25519(&seed, &watermark, b"block header")?;
+        assert!(signature.to_base58_check().starts_with("edsig"));
+        assert!(verify_ed25519(
+            &public_key,
+            &signature,
+            &watermark,
+            b"block header"
+        )?);
+        assert!(!verify_ed25519(
+            &public_key,
+            &signature,
+            &GENERIC_OPERATION_WATERMARK,
+            b"block header"
+        )?);
+        Ok(())
+    }
+}
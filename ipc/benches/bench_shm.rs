@@ -4,6 +4,7 @@
 
 extern crate test;
 
+use std::convert::TryInto;
 use std::io;
 use std::thread;
 use std::time::Duration;
@@ -12,7 +13,18 @@ use test::Bencher;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use ipc::{temp_sock, IpcClient, IpcServer};
+use ipc::{temp_sock, IpcClient, IpcServer, IpcTransport};
+use tezos_api::ffi::{ApplyBlockRequest, ApplyBlockResponse};
+use tezos_messages::p2p::binary_message::BinaryMessage;
+use tezos_messages::p2p::encoding::prelude::*;
+
+const CHAIN_ID: &str = "8eceda2f";
+const HEADER: &str = "0000000301a14f19e0df37d7b71312523305d71ac79e3d989c1c1d4e8e884b6857e4ec1627000000005c017ed604dfcb6b41e91650bb908618b2740a6167d9072c3230e388b24feeef04c98dc27f000000110000000100000000080000000000000005f06879947f3d9959090f27054062ed23dbf9f7bd4b3c8a6e86008daabb07913e000c00000003e5445371002b9745d767d7f164a39e7f373a0f25166794cba491010ab92b0e281b570057efc78120758ff26a33301870f361d780594911549bcb7debbacd8a142e0b76a605";
+const OPERATION: &str = "a14f19e0df37d7b71312523305d71ac79e3d989c1c1d4e8e884b6857e4ec1627000000000236663bacdca76094fdb73150092659d463fec94eda44ba4db10973a1ad057ef53a5b3239a1b9c383af803fc275465bd28057d68f3cab46adfd5b2452e863ff0a";
+/// Count of operations in every validation pass of the apply request
+const OPERATIONS_PER_PASS: usize = 64;
+/// Size of json metadata in apply response (mainnet blocks have hundreds of KB)
+const METADATA_JSON_SIZE: usize = 256 * 1024;
 
 #[derive(Serialize, Deserialize)]
 struct BenchData {
@@ -115,3 +127,100 @@ fn bench_uds(b: &mut Bencher) {
         }
     });
 }
+
+#[derive(Serialize, Deserialize)]
+enum ApplyMessage {
+    Request(ApplyBlockRequest),
+    Response(ApplyBlockResponse),
+}
+
+fn apply_block_request() -> ApplyBlockRequest {
+    let operation = Operation::from_bytes(hex::decode(OPERATION).unwrap()).unwrap();
+    ApplyBlockRequest {
+        chain_id: hex::decode(CHAIN_ID).unwrap().try_into().unwrap(),
+        block_header: BlockHeader::from_bytes(hex::decode(HEADER).unwrap()).unwrap(),
+        pred_header: BlockHeader::from_bytes(hex::decode(HEADER).unwrap()).unwrap(),
+        max_operations_ttl: 60,
+        operations: (0..4)
+            .map(|_| vec![operation.clone(); OPERATIONS_PER_PASS])
+            .collect(),
+        predecessor_block_metadata_hash: None,
+        predecessor_ops_metadata_hash: None,
+    }
+}
+
+fn apply_block_response() -> ApplyBlockResponse {
+    let metadata_json = format!(
+        "{{\"balance_updates\":[{}]}}",
+        "{\"kind\":\"contract\",\"change\":\"-1000\"},".repeat(METADATA_JSON_SIZE / 36)
+    );
+    ApplyBlockResponse {
+        validation_result_message: "lvl 1, fit 1:1, prio 0, 256 ops".to_string(),
+        context_hash: "CoV16kW8WgL51SpcftQKdeqc94D6ekghMgPMmEn7TSZzFA697PeE"
+            .try_into()
+            .unwrap(),
+        block_header_proto_json: "{}".to_string(),
+        block_header_proto_metadata_json: metadata_json.clone(),
+        operations_proto_metadata_json: metadata_json,
+        max_operations_ttl: 60,
+        last_allowed_fork_level: 0,
+        forking_testchain: false,
+        forking_testchain_data: None,
+        block_metadata_hash: None,
+        ops_metadata_hashes: None,
+        ops_metadata_hash: None,
+    }
+}
+
+/// Roundtrip of apply block request/response (like node <-> protocol runner) with selected transport
+fn bench_apply_block(b: &mut Bencher, transport: IpcTransport) {
+    let sock_path = temp_sock();
+
+    let child_pid = fork(|| {
+        // wait for parent to be ready
+        let sock_path = sock_path.as_path();
+        while !sock_path.exists() {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let client: IpcClient<ApplyMessage, ApplyMessage> = IpcClient::new(&sock_path);
+        let (mut rx, mut tx) = client.connect().unwrap();
+        let response = ApplyMessage::Response(apply_block_response());
+        while rx.receive().is_ok() {
+            tx.send(&response).unwrap();
+        }
+    });
+    assert!(child_pid > 0);
+
+    let mut server: IpcServer<ApplyMessage, ApplyMessage> = IpcServer::bind_path(&sock_path)
+        .unwrap()
+        .with_transport(transport);
+    let (mut rx, mut tx) = server.try_accept(Duration::from_secs(3)).unwrap();
+    assert_eq!(
+        matches!(transport, IpcTransport::SharedMemory { .. }),
+        rx.is_shared_memory()
+    );
+
+    let request = ApplyMessage::Request(apply_block_request());
+    b.iter(|| {
+        for _ in 0..10 {
+            tx.send(&request).unwrap();
+            let _ = rx.receive().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn bench_apply_block_uds(b: &mut Bencher) {
+    bench_apply_block(b, IpcTransport::Socket)
+}
+
+#[bench]
+fn bench_apply_block_shm(b: &mut Bencher) {
+    bench_apply_block(
+        b,
+        IpcTransport::SharedMemory {
+            ring_buffer_size: IpcTransport::DEFAULT_RING_BUFFER_SIZE,
+        },
+    )
+}
@@ -6,16 +6,25 @@
 //!
 //! The IPC is implemented as unix domain sockets. Functionality is similar to how network sockets work.
 //!
+//! Optionally, messages can be transferred through shared memory ring buffers (see [IpcTransport]),
+//! transport is negotiated during [IpcServer::try_accept] / [IpcClient::connect] and falls back to unix socket,
+//! if shared memory cannot be used by any side.
+//!
+//! For peers on different hosts, IPC channel can be established over TCP (see [TcpIpcServer] and [TcpIpcClient]),
+//! peers are authenticated with pre-shared key and frames are encrypted.
+//!
 //! TODO: TE-292 - investigate/reimplement
 
+use std::cell::Cell;
 use std::fs;
 use std::io;
 use std::io::prelude::*;
 use std::iter;
 use std::marker::PhantomData;
-use std::net::Shutdown;
+use std::net::{Shutdown, TcpStream};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use std::{env, thread};
 
@@ -24,6 +33,14 @@ use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
+use crate::shm::{ShmBuffers, ShmReceiver, ShmSender};
+use crate::tcp::FrameCipher;
+
+pub use crate::tcp::{load_preshared_key, TcpIpcClient, TcpIpcServer};
+
+mod shm;
+mod tcp;
+
 /// IPC communication errors
 #[derive(Debug, Fail)]
 pub enum IpcError {
@@ -47,25 +64,181 @@ pub enum IpcError {
     SplitError { reason: io::Error },
     #[fail(display = "Socker configuration error: {}", reason)]
     SocketConfigurationError { reason: io::Error },
+    #[fail(display = "Handshake error: {}", reason)]
+    HandshakeError { reason: String },
+    #[fail(display = "Encryption error: {}", reason)]
+    EncryptionError { reason: String },
+    #[fail(display = "Pre-shared key error: {}", reason)]
+    PresharedKeyError { reason: String },
+}
+
+/// Transport used for messages of IPC channel (unix socket is always used for connection and handshake)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum IpcTransport {
+    /// Messages are written to unix socket (default)
+    Socket,
+    /// Messages are written to shared memory ring buffers (one per direction)
+    SharedMemory { ring_buffer_size: u32 },
+}
+
+impl IpcTransport {
+    /// Default size of one shared memory ring buffer
+    pub const DEFAULT_RING_BUFFER_SIZE: u32 = 8 * 1024 * 1024;
+}
+
+impl Default for IpcTransport {
+    fn default() -> Self {
+        IpcTransport::Socket
+    }
+}
+
+impl FromStr for IpcTransport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "socket" => Ok(IpcTransport::Socket),
+            "shm" => Ok(IpcTransport::SharedMemory {
+                ring_buffer_size: IpcTransport::DEFAULT_RING_BUFFER_SIZE,
+            }),
+            _ => Err(format!("Unsupported IPC transport: {}", s)),
+        }
+    }
+}
+
+/// Offer sent by server just after connection is accepted
+#[derive(Serialize, Deserialize, Debug)]
+enum HandshakeOffer {
+    Socket,
+    SharedMemory {
+        server_to_client: PathBuf,
+        client_to_server: PathBuf,
+        ring_buffer_size: u32,
+    },
+}
+
+/// Transport accepted by client
+#[derive(Serialize, Deserialize, Debug)]
+enum HandshakeReply {
+    Socket,
+    SharedMemory,
+}
+
+/// How long server waits for handshake reply from connected client
+const SERVER_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long client waits for handshake offer, (server can accept connection later)
+const CLIENT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often shared memory receiver checks, if peer did not close the connection
+const SHM_PEER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Stream of the IPC channel (TCP is used for peers on different hosts)
+enum IpcStream {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl IpcStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            IpcStream::Unix(stream) => stream.set_read_timeout(timeout),
+            IpcStream::Tcp(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            IpcStream::Unix(stream) => stream.set_write_timeout(timeout),
+            IpcStream::Tcp(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            IpcStream::Unix(stream) => stream.set_nonblocking(nonblocking),
+            IpcStream::Tcp(stream) => stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            IpcStream::Unix(stream) => stream.shutdown(how),
+            IpcStream::Tcp(stream) => stream.shutdown(how),
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<IpcStream> {
+        match self {
+            IpcStream::Unix(stream) => stream.try_clone().map(IpcStream::Unix),
+            IpcStream::Tcp(stream) => stream.try_clone().map(IpcStream::Tcp),
+        }
+    }
+}
+
+impl Read for IpcStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            IpcStream::Unix(stream) => stream.read(buf),
+            IpcStream::Tcp(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for IpcStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            IpcStream::Unix(stream) => stream.write(buf),
+            IpcStream::Tcp(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            IpcStream::Unix(stream) => stream.flush(),
+            IpcStream::Tcp(stream) => stream.flush(),
+        }
+    }
 }
 
 /// Represents sending end of the IPC channel.
-pub struct IpcSender<S>(UnixStream, PhantomData<S>);
+pub struct IpcSender<S> {
+    stream: IpcStream,
+    /// If set, messages are sent through shared memory
+    shm: Option<ShmSender>,
+    /// If set, frames are encrypted (TCP)
+    cipher: Option<FrameCipher>,
+    _phantom: PhantomData<S>,
+}
 
 impl<S> IpcSender<S> {
     /// Close IPC channel and release associated resources.
     ///
     /// This closes only the sending part of the IPC channel.
     fn shutdown(&self) -> Result<(), io::Error> {
-        self.0.shutdown(Shutdown::Write)
+        self.stream.shutdown(Shutdown::Write)
     }
 
     pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
-        self.0.set_write_timeout(timeout)
+        match &self.shm {
+            Some(shm) => {
+                shm.set_write_timeout(timeout);
+                Ok(())
+            }
+            None => self.stream.set_write_timeout(timeout),
+        }
     }
 
+    /// Shared memory sender always blocks (up to write timeout), until there is space in the ring buffer
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        self.0.set_nonblocking(nonblocking)
+        match &self.shm {
+            // socket is shared with receiver, which needs it in non-blocking mode
+            Some(_) => Ok(()),
+            None => self.stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Returns true, if messages are sent through shared memory
+    pub fn is_shared_memory(&self) -> bool {
+        self.shm.is_some()
     }
 }
 
@@ -74,19 +247,10 @@ impl<S: Serialize> IpcSender<S> {
     ///
     /// This is a blocking operation,
     pub fn send(&mut self, value: &S) -> Result<(), IpcError> {
-        let msg_buf = bincode::serialize(value).map_err(|err| IpcError::SerializationError {
-            reason: format!("{:?}", err),
-        })?;
-        let msg_len_buf = msg_buf.len().to_be_bytes();
-        self.0
-            .write_all(&msg_len_buf)
-            .map_err(|err| IpcError::SendError { reason: err })?;
-        self.0
-            .write_all(&msg_buf)
-            .map_err(|err| IpcError::SendError { reason: err })?;
-        self.0
-            .flush()
-            .map_err(|err| IpcError::SendError { reason: err })
+        match &self.shm {
+            Some(shm) => shm.send(value),
+            None => write_frame(&mut self.stream, value, self.cipher.as_mut()),
+        }
     }
 }
 
@@ -97,22 +261,59 @@ impl<S> Drop for IpcSender<S> {
 }
 
 /// Represents receiving end of the IPC channel.
-pub struct IpcReceiver<R>(UnixStream, PhantomData<R>);
+pub struct IpcReceiver<R> {
+    stream: IpcStream,
+    /// If set, messages are received through shared memory
+    shm: Option<ShmReceiver>,
+    /// If set, frames are encrypted (TCP)
+    cipher: Option<FrameCipher>,
+    /// Read timeout and non-blocking mode for shared memory (socket is not used for messages)
+    shm_read_timeout: Cell<Option<Duration>>,
+    shm_nonblocking: Cell<bool>,
+    _phantom: PhantomData<R>,
+}
 
 impl<R> IpcReceiver<R> {
     /// Close IPC channel and release associated resources.
     ///
     /// This closes only the receiving part of the IPC channel.
     fn shutdown(&self) -> Result<(), io::Error> {
-        self.0.shutdown(Shutdown::Read)
+        self.stream.shutdown(Shutdown::Read)
     }
 
     pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
-        self.0.set_read_timeout(timeout)
+        match &self.shm {
+            Some(_) => {
+                self.shm_read_timeout.set(timeout);
+                Ok(())
+            }
+            None => self.stream.set_read_timeout(timeout),
+        }
     }
 
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
-        self.0.set_nonblocking(nonblocking)
+        match &self.shm {
+            Some(_) => {
+                self.shm_nonblocking.set(nonblocking);
+                Ok(())
+            }
+            None => self.stream.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Returns true, if messages are received through shared memory
+    pub fn is_shared_memory(&self) -> bool {
+        self.shm.is_some()
+    }
+
+    /// Socket is not used for messages in shared memory mode, so anything else than `WouldBlock` means, that peer closed connection
+    fn shm_peer_closed(&mut self) -> bool {
+        let mut buf = [0u8; 1];
+        match self.stream.read(&mut buf) {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => false,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => false,
+            _ => true,
+        }
     }
 }
 
@@ -140,25 +341,51 @@ where
 
     /// Read bytes from established IPC channel and deserialize into a rust type.
     pub fn receive(&mut self) -> Result<R, IpcError> {
-        let mut msg_len_buf = [0; 8];
-        self.0.read_exact(&mut msg_len_buf).map_err(|err| {
-            if err.kind() == io::ErrorKind::WouldBlock {
-                IpcError::ReceiveMessageTimeouted
-            } else {
-                IpcError::ReceiveMessageLengthError { reason: err }
-            }
-        })?;
+        if self.shm.is_some() {
+            return self.receive_shm();
+        }
+        read_frame(&mut self.stream, self.cipher.as_mut())
+    }
 
-        let msg_len = usize::from_be_bytes(msg_len_buf);
+    fn receive_shm(&mut self) -> Result<R, IpcError> {
+        let deadline = if self.shm_nonblocking.get() {
+            Some(Instant::now())
+        } else {
+            self.shm_read_timeout
+                .get()
+                .map(|timeout| Instant::now() + timeout)
+        };
 
-        let mut msg_buf = vec![0u8; msg_len];
-        self.0
-            .read_exact(&mut msg_buf)
-            .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+        loop {
+            let wait = match deadline {
+                Some(deadline) => std::cmp::min(
+                    deadline.saturating_duration_since(Instant::now()),
+                    SHM_PEER_CHECK_INTERVAL,
+                ),
+                None => SHM_PEER_CHECK_INTERVAL,
+            };
+
+            if let Some(shm) = self.shm.as_mut() {
+                if let Some(msg) = shm.recv_timeout(wait)? {
+                    return Ok(msg);
+                }
+            }
 
-        bincode::deserialize(&msg_buf).map_err(|err| IpcError::DeserializationError {
-            reason: format!("{:?}", err),
-        })
+            if self.shm_peer_closed() {
+                return Err(IpcError::ReceiveMessageLengthError {
+                    reason: io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "IPC peer closed connection",
+                    ),
+                });
+            }
+
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return Err(IpcError::ReceiveMessageTimeouted);
+                }
+            }
+        }
     }
 }
 
@@ -168,10 +395,65 @@ impl<R> Drop for IpcReceiver<R> {
     }
 }
 
+/// Writes length-prefixed bincode frame to the socket (encrypted, if `cipher` is set)
+fn write_frame<W: Write, S: Serialize>(
+    stream: &mut W,
+    value: &S,
+    cipher: Option<&mut FrameCipher>,
+) -> Result<(), IpcError> {
+    let mut msg_buf = bincode::serialize(value).map_err(|err| IpcError::SerializationError {
+        reason: format!("{:?}", err),
+    })?;
+    if let Some(cipher) = cipher {
+        msg_buf = cipher.encrypt(&msg_buf)?;
+    }
+    let msg_len_buf = msg_buf.len().to_be_bytes();
+    stream
+        .write_all(&msg_len_buf)
+        .map_err(|err| IpcError::SendError { reason: err })?;
+    stream
+        .write_all(&msg_buf)
+        .map_err(|err| IpcError::SendError { reason: err })?;
+    stream
+        .flush()
+        .map_err(|err| IpcError::SendError { reason: err })
+}
+
+/// Reads length-prefixed bincode frame from the socket (decrypted, if `cipher` is set)
+fn read_frame<Rd: Read, R>(stream: &mut Rd, cipher: Option<&mut FrameCipher>) -> Result<R, IpcError>
+where
+    R: for<'de> Deserialize<'de>,
+{
+    let mut msg_len_buf = [0; 8];
+    stream.read_exact(&mut msg_len_buf).map_err(|err| {
+        if err.kind() == io::ErrorKind::WouldBlock {
+            IpcError::ReceiveMessageTimeouted
+        } else {
+            IpcError::ReceiveMessageLengthError { reason: err }
+        }
+    })?;
+
+    let msg_len = usize::from_be_bytes(msg_len_buf);
+
+    let mut msg_buf = vec![0u8; msg_len];
+    stream
+        .read_exact(&mut msg_buf)
+        .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+    if let Some(cipher) = cipher {
+        msg_buf = cipher.decrypt(&msg_buf)?;
+    }
+
+    bincode::deserialize(&msg_buf).map_err(|err| IpcError::DeserializationError {
+        reason: format!("{:?}", err),
+    })
+}
+
 /// Listens for incoming IPC connections.
 pub struct IpcServer<R, S> {
     listener: UnixListener,
     pub path: PathBuf,
+    /// Transport offered to the connected clients
+    transport: IpcTransport,
     _phantom_r: PhantomData<R>,
     _phantom_s: PhantomData<S>,
 }
@@ -205,11 +487,18 @@ where
         Ok(IpcServer {
             listener,
             path: path_buf,
+            transport: IpcTransport::default(),
             _phantom_r: PhantomData,
             _phantom_s: PhantomData,
         })
     }
 
+    /// Sets transport, which is offered to the connected clients (client can fall back to socket)
+    pub fn with_transport(mut self, transport: IpcTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
     /// Try to accept new connection a return sender/receiver for it
     /// In case of timeout, can be IpcError::AcceptTimeout handled
     ///
@@ -265,7 +554,14 @@ where
         // maybe it is enought to set non_blocking to the [`stream`], but we make sure,
         // also On macOS and FreeBSD new sockets inherit flags from accepting fd,
         // but we expect this to be in blocking by default.
-        split(stream.0, false, false)
+        let mut stream = stream.0;
+        stream
+            .set_nonblocking(false)
+            .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+
+        // negotiate transport with client
+        let shm = server_handshake(&mut stream, self.transport)?;
+        split(IpcStream::Unix(stream), shm, None)
     }
 
     /// Create new IpcClient for this server
@@ -307,9 +603,10 @@ where
 
     /// Try to open new connection.
     pub fn connect(&self) -> Result<(IpcReceiver<R>, IpcSender<S>), IpcError> {
-        let stream = UnixStream::connect(&self.path)
+        let mut stream = UnixStream::connect(&self.path)
             .map_err(|err| IpcError::ConnectionError { reason: err })?;
-        split(stream, false, false)
+        let shm = client_handshake(&mut stream)?;
+        split(IpcStream::Unix(stream), shm, None)
     }
 }
 
@@ -325,29 +622,120 @@ pub fn temp_sock() -> PathBuf {
     temp_dir.join(chars + ".sock")
 }
 
+/// Server offers transport and waits for reply of the client, if shared memory cannot be created, socket is offered
+fn server_handshake(
+    stream: &mut UnixStream,
+    transport: IpcTransport,
+) -> Result<Option<(ShmReceiver, ShmSender)>, IpcError> {
+    set_handshake_timeout(stream, Some(SERVER_HANDSHAKE_TIMEOUT))?;
+
+    // files of ring buffers are removed, when this goes out of scope
+    let shm_buffers = match transport {
+        IpcTransport::Socket => None,
+        // fallback to socket
+        IpcTransport::SharedMemory { ring_buffer_size } => {
+            ShmBuffers::create(ring_buffer_size).ok()
+        }
+    };
+    let offer = match &shm_buffers {
+        Some(shm_buffers) => HandshakeOffer::SharedMemory {
+            server_to_client: shm_buffers.server_to_client.clone(),
+            client_to_server: shm_buffers.client_to_server.clone(),
+            ring_buffer_size: shm_buffers.ring_buffer_size(),
+        },
+        None => HandshakeOffer::Socket,
+    };
+    write_frame(stream, &offer, None).map_err(handshake_error)?;
+    let reply: HandshakeReply = read_frame(stream, None).map_err(handshake_error)?;
+
+    set_handshake_timeout(stream, None)?;
+    match (reply, shm_buffers) {
+        (HandshakeReply::SharedMemory, Some(shm_buffers)) => Ok(shm_buffers.into_server_channel()),
+        (HandshakeReply::SharedMemory, None) => Err(IpcError::HandshakeError {
+            reason: "client accepted shared memory, which was not offered".to_string(),
+        }),
+        (HandshakeReply::Socket, _) => Ok(None),
+    }
+}
+
+/// Client accepts offered transport, if shared memory cannot be opened, falls back to socket
+fn client_handshake(stream: &mut UnixStream) -> Result<Option<(ShmReceiver, ShmSender)>, IpcError> {
+    set_handshake_timeout(stream, Some(CLIENT_HANDSHAKE_TIMEOUT))?;
+
+    let offer: HandshakeOffer = read_frame(stream, None).map_err(handshake_error)?;
+    let shm = match offer {
+        HandshakeOffer::Socket => None,
+        HandshakeOffer::SharedMemory {
+            server_to_client,
+            client_to_server,
+            ring_buffer_size,
+        } => shm::open_client_channel(&server_to_client, &client_to_server, ring_buffer_size).ok(),
+    };
+    let reply = match &shm {
+        Some(_) => HandshakeReply::SharedMemory,
+        None => HandshakeReply::Socket,
+    };
+    write_frame(stream, &reply, None).map_err(handshake_error)?;
+
+    set_handshake_timeout(stream, None)?;
+    Ok(shm)
+}
+
+fn set_handshake_timeout(stream: &UnixStream, timeout: Option<Duration>) -> Result<(), IpcError> {
+    stream
+        .set_read_timeout(timeout)
+        .and(stream.set_write_timeout(timeout))
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })
+}
+
+fn handshake_error(err: IpcError) -> IpcError {
+    match err {
+        IpcError::HandshakeError { .. } => err,
+        err => IpcError::HandshakeError {
+            reason: format!("{}", err),
+        },
+    }
+}
+
 fn split<R, S>(
-    stream: UnixStream,
-    receiver_non_blocking: bool,
-    sender_non_blocking: bool,
+    stream: IpcStream,
+    shm: Option<(ShmReceiver, ShmSender)>,
+    ciphers: Option<(FrameCipher, FrameCipher)>,
 ) -> Result<(IpcReceiver<R>, IpcSender<S>), IpcError>
 where
     R: for<'de> Deserialize<'de>,
     S: Serialize,
 {
-    let receiver = IpcReceiver(
-        stream
-            .try_clone()
-            .map_err(|err| IpcError::SplitError { reason: err })?,
-        PhantomData,
-    );
-    receiver
-        .set_nonblocking(receiver_non_blocking)
+    let (shm_receiver, shm_sender) = match shm {
+        Some((shm_receiver, shm_sender)) => (Some(shm_receiver), Some(shm_sender)),
+        None => (None, None),
+    };
+    let (receiver_cipher, sender_cipher) = match ciphers {
+        Some((receiver_cipher, sender_cipher)) => (Some(receiver_cipher), Some(sender_cipher)),
+        None => (None, None),
+    };
+
+    // socket is used just for detection of closed peer in shared memory mode, so it must not block
+    stream
+        .set_nonblocking(shm_receiver.is_some())
         .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
 
-    let sender = IpcSender(stream, PhantomData);
-    sender
-        .set_nonblocking(sender_non_blocking)
-        .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+    let receiver = IpcReceiver {
+        stream: stream
+            .try_clone()
+            .map_err(|err| IpcError::SplitError { reason: err })?,
+        shm: shm_receiver,
+        cipher: receiver_cipher,
+        shm_read_timeout: Cell::new(None),
+        shm_nonblocking: Cell::new(false),
+        _phantom: PhantomData,
+    };
+    let sender = IpcSender {
+        stream,
+        shm: shm_sender,
+        cipher: sender_cipher,
+        _phantom: PhantomData,
+    };
 
     Ok((receiver, sender))
 }
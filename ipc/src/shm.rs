@@ -0,0 +1,272 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Shared-memory transport of the IPC channel.
+//!
+//! Every direction of the channel has its own ring buffer (memory mapped file), messages are serialized directly into the ring buffer,
+//! so there is no copying through the kernel (like with unix socket). Unix socket is still used for the handshake and for detection of closed peer.
+//!
+//! Message larger than a quarter of the ring buffer is serialized and sent in chunks.
+
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ipmpsc::{Receiver, Sender, SharedRingBuffer};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::IpcError;
+
+/// Frame written to the ring buffer, `M` is message and `C` is chunk of serialized message
+#[derive(Serialize, Deserialize)]
+enum ShmFrame<M, C> {
+    Message(M),
+    Chunk(C),
+    LastChunk(C),
+}
+
+/// Sending end of the ring buffer
+pub(crate) struct ShmSender {
+    tx: Sender,
+    max_message_size: u64,
+    write_timeout: Cell<Option<Duration>>,
+}
+
+impl ShmSender {
+    fn new(buffer: SharedRingBuffer, ring_buffer_size: u32) -> Self {
+        ShmSender {
+            tx: Sender::new(buffer),
+            // leave enough space, so writer does not need to wait for reader to read whole ring buffer
+            max_message_size: u64::from(ring_buffer_size / 4),
+            write_timeout: Cell::new(None),
+        }
+    }
+
+    pub(crate) fn set_write_timeout(&self, timeout: Option<Duration>) {
+        self.write_timeout.set(timeout);
+    }
+
+    pub(crate) fn send<S: Serialize>(&self, value: &S) -> Result<(), IpcError> {
+        let message_size =
+            bincode::serialized_size(value).map_err(|err| IpcError::SerializationError {
+                reason: format!("{:?}", err),
+            })?;
+
+        if message_size <= self.max_message_size {
+            return self.send_frame::<&S, &[u8]>(&ShmFrame::Message(value));
+        }
+
+        // too large message is sent in chunks
+        let msg_buf = bincode::serialize(value).map_err(|err| IpcError::SerializationError {
+            reason: format!("{:?}", err),
+        })?;
+        let mut chunks = msg_buf.chunks(self.max_message_size as usize).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() {
+                self.send_frame::<&S, &[u8]>(&ShmFrame::Chunk(chunk))?;
+            } else {
+                self.send_frame::<&S, &[u8]>(&ShmFrame::LastChunk(chunk))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_frame<M: Serialize, C: Serialize>(
+        &self,
+        frame: &ShmFrame<M, C>,
+    ) -> Result<(), IpcError> {
+        match self.write_timeout.get() {
+            Some(timeout) => match self.tx.send_timeout(frame, timeout) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(IpcError::SendError {
+                    reason: io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "shared memory ring buffer is full",
+                    ),
+                }),
+                Err(err) => Err(send_error(err)),
+            },
+            None => self.tx.send(frame).map_err(send_error),
+        }
+    }
+}
+
+/// Receiving end of the ring buffer
+pub(crate) struct ShmReceiver {
+    rx: Receiver,
+    /// Already received chunks of the message
+    chunks: Vec<u8>,
+}
+
+impl ShmReceiver {
+    fn new(buffer: SharedRingBuffer) -> Self {
+        ShmReceiver {
+            rx: Receiver::new(buffer),
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Returns None, if whole message was not received in `timeout`
+    pub(crate) fn recv_timeout<R>(&mut self, timeout: Duration) -> Result<Option<R>, IpcError>
+    where
+        R: for<'de> Deserialize<'de>,
+    {
+        loop {
+            let frame = if timeout == Duration::from_secs(0) {
+                self.rx.try_recv::<ShmFrame<R, Vec<u8>>>()
+            } else {
+                self.rx.recv_timeout::<ShmFrame<R, Vec<u8>>>(timeout)
+            }
+            .map_err(receive_error)?;
+
+            match frame {
+                None => return Ok(None),
+                Some(ShmFrame::Message(msg)) => return Ok(Some(msg)),
+                Some(ShmFrame::Chunk(chunk)) => self.chunks.extend_from_slice(&chunk),
+                Some(ShmFrame::LastChunk(chunk)) => {
+                    self.chunks.extend_from_slice(&chunk);
+                    let msg = bincode::deserialize(&self.chunks).map_err(|err| {
+                        IpcError::DeserializationError {
+                            reason: format!("{:?}", err),
+                        }
+                    });
+                    self.chunks.clear();
+                    return msg.map(Some);
+                }
+            }
+        }
+    }
+}
+
+fn send_error(err: ipmpsc::Error) -> IpcError {
+    match err {
+        ipmpsc::Error::Bincode(err) => IpcError::SerializationError {
+            reason: format!("{:?}", err),
+        },
+        err => IpcError::SendError {
+            reason: io::Error::new(io::ErrorKind::Other, format!("{}", err)),
+        },
+    }
+}
+
+fn receive_error(err: ipmpsc::Error) -> IpcError {
+    match err {
+        ipmpsc::Error::Bincode(err) => IpcError::DeserializationError {
+            reason: format!("{:?}", err),
+        },
+        err => IpcError::ReceiveMessageError {
+            reason: io::Error::new(io::ErrorKind::Other, format!("{}", err)),
+        },
+    }
+}
+
+/// Ring buffers created by server, files are removed on drop
+/// (after both sides mapped them, they are not needed anymore).
+pub(crate) struct ShmBuffers {
+    pub(crate) server_to_client: PathBuf,
+    pub(crate) client_to_server: PathBuf,
+    ring_buffer_size: u32,
+    buffers: Option<(SharedRingBuffer, SharedRingBuffer)>,
+}
+
+impl ShmBuffers {
+    pub(crate) fn create(ring_buffer_size: u32) -> Result<Self, IpcError> {
+        let server_to_client = temp_shm_file();
+        let client_to_server = temp_shm_file();
+        let mut shm_buffers = ShmBuffers {
+            server_to_client,
+            client_to_server,
+            ring_buffer_size,
+            buffers: None,
+        };
+        let server_to_client_buffer =
+            create_buffer(&shm_buffers.server_to_client, ring_buffer_size)?;
+        let client_to_server_buffer =
+            create_buffer(&shm_buffers.client_to_server, ring_buffer_size)?;
+        shm_buffers.buffers = Some((server_to_client_buffer, client_to_server_buffer));
+        Ok(shm_buffers)
+    }
+
+    pub(crate) fn ring_buffer_size(&self) -> u32 {
+        self.ring_buffer_size
+    }
+
+    /// Returns server side of the channel
+    pub(crate) fn into_server_channel(mut self) -> Option<(ShmReceiver, ShmSender)> {
+        let ring_buffer_size = self.ring_buffer_size;
+        self.buffers
+            .take()
+            .map(|(server_to_client, client_to_server)| {
+                (
+                    ShmReceiver::new(client_to_server),
+                    ShmSender::new(server_to_client, ring_buffer_size),
+                )
+            })
+    }
+}
+
+impl Drop for ShmBuffers {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.server_to_client);
+        let _ = fs::remove_file(&self.client_to_server);
+    }
+}
+
+/// Opens client side of the channel created by server
+pub(crate) fn open_client_channel(
+    server_to_client: &Path,
+    client_to_server: &Path,
+    ring_buffer_size: u32,
+) -> Result<(ShmReceiver, ShmSender), IpcError> {
+    let server_to_client = open_buffer(server_to_client)?;
+    let client_to_server = open_buffer(client_to_server)?;
+    Ok((
+        ShmReceiver::new(server_to_client),
+        ShmSender::new(client_to_server, ring_buffer_size),
+    ))
+}
+
+fn create_buffer(path: &Path, ring_buffer_size: u32) -> Result<SharedRingBuffer, IpcError> {
+    SharedRingBuffer::create(&path.to_string_lossy(), ring_buffer_size).map_err(|err| {
+        IpcError::HandshakeError {
+            reason: format!(
+                "failed to create shared memory ring buffer: {}, reason: {}",
+                path.display(),
+                err
+            ),
+        }
+    })
+}
+
+fn open_buffer(path: &Path) -> Result<SharedRingBuffer, IpcError> {
+    SharedRingBuffer::open(&path.to_string_lossy()).map_err(|err| IpcError::HandshakeError {
+        reason: format!(
+            "failed to open shared memory ring buffer: {}, reason: {}",
+            path.display(),
+            err
+        ),
+    })
+}
+
+/// Randomly named file for ring buffer, tmpfs `/dev/shm` is preferred (if exists), so pages are never written to disk
+fn temp_shm_file() -> PathBuf {
+    let dev_shm = Path::new("/dev/shm");
+    let dir = if dev_shm.is_dir() {
+        dev_shm.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    };
+
+    let mut rng = thread_rng();
+    let chars = iter::repeat(())
+        .map(|()| rng.sample(Alphanumeric))
+        .take(7)
+        .collect::<String>();
+
+    dir.join(format!("tezedge_ipc_{}.shm", chars))
+}
@@ -0,0 +1,350 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! TCP transport of the IPC channel, used for protocol runners running on remote hosts.
+//!
+//! Peers are authenticated with pre-shared key (the same key is configured on both sides),
+//! handshake:
+//! 1. client and server exchange hello with random nonce (in plain text),
+//! 2. local/remote nonces are generated from both hellos (the same way as for p2p connections),
+//! 3. client and server exchange auth message encrypted with pre-shared key, which proves, that peer knows the key.
+//!
+//! After handshake, every frame (the same length-prefixed bincode frame as for unix socket) is encrypted with pre-shared key and the next nonce.
+
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hex::FromHex;
+use serde::{Deserialize, Serialize};
+
+use crypto::crypto_box::PrecomputedKey;
+use crypto::nonce::{generate_nonces, Nonce, NONCE_SIZE};
+
+use crate::{split, IpcError, IpcReceiver, IpcSender, IpcStream, SERVER_HANDSHAKE_TIMEOUT};
+
+/// Hello starts with magic bytes (with version), so we do not try to handshake with something else
+const HELLO_MAGIC: &[u8; 8] = b"TZIPC\x00\x00\x01";
+const HELLO_SIZE: usize = HELLO_MAGIC.len() + NONCE_SIZE;
+/// Plain text of auth message
+const AUTH_MAGIC: &[u8; 16] = b"tezedge-ipc-auth";
+/// Encrypted auth message (plain text + MAC)
+const AUTH_SIZE: usize = AUTH_MAGIC.len() + 16;
+
+/// Encrypts/decrypts frames of one direction of the channel
+pub(crate) struct FrameCipher {
+    key: PrecomputedKey,
+    nonce: Nonce,
+}
+
+impl FrameCipher {
+    fn new(key: PrecomputedKey, nonce: Nonce) -> Self {
+        FrameCipher { key, nonce }
+    }
+
+    pub(crate) fn encrypt(&mut self, msg: &[u8]) -> Result<Vec<u8>, IpcError> {
+        let encrypted =
+            self.key
+                .encrypt(msg, &self.nonce)
+                .map_err(|err| IpcError::EncryptionError {
+                    reason: format!("{}", err),
+                })?;
+        self.nonce = self.nonce.increment();
+        Ok(encrypted)
+    }
+
+    pub(crate) fn decrypt(&mut self, msg: &[u8]) -> Result<Vec<u8>, IpcError> {
+        let decrypted =
+            self.key
+                .decrypt(msg, &self.nonce)
+                .map_err(|err| IpcError::EncryptionError {
+                    reason: format!("{}", err),
+                })?;
+        self.nonce = self.nonce.increment();
+        Ok(decrypted)
+    }
+}
+
+/// Loads pre-shared key from file, file contains 32 bytes encoded as hex string (e.g. generated with `openssl rand -hex 32`)
+pub fn load_preshared_key<P: AsRef<Path>>(path: P) -> Result<PrecomputedKey, IpcError> {
+    let content = fs::read_to_string(path.as_ref()).map_err(|err| IpcError::PresharedKeyError {
+        reason: format!(
+            "failed to read pre-shared key file: {}, reason: {}",
+            path.as_ref().display(),
+            err
+        ),
+    })?;
+    PrecomputedKey::from_hex(content.trim()).map_err(|err| IpcError::PresharedKeyError {
+        reason: format!(
+            "invalid pre-shared key in file: {}, reason: {}",
+            path.as_ref().display(),
+            err
+        ),
+    })
+}
+
+/// Listens for incoming IPC connections over TCP, only peers with the same pre-shared key are accepted.
+pub struct TcpIpcServer<R, S> {
+    listener: TcpListener,
+    key: PrecomputedKey,
+    _phantom_r: PhantomData<R>,
+    _phantom_s: PhantomData<S>,
+}
+
+impl<R, S> TcpIpcServer<R, S>
+where
+    R: for<'de> Deserialize<'de>,
+    S: Serialize,
+{
+    /// Bind TcpIpcServer to the address
+    ///
+    /// # Arguments
+    /// * `address` - listening address
+    /// * `key` - pre-shared key, which must be known by clients
+    pub fn bind<A: ToSocketAddrs>(address: A, key: PrecomputedKey) -> Result<Self, IpcError> {
+        let listener =
+            TcpListener::bind(address).map_err(|err| IpcError::ConnectionError { reason: err })?;
+
+        Ok(TcpIpcServer {
+            listener,
+            key,
+            _phantom_r: PhantomData,
+            _phantom_s: PhantomData,
+        })
+    }
+
+    /// Returns listening address (useful, if bound to port 0)
+    pub fn local_addr(&self) -> Result<SocketAddr, IpcError> {
+        self.listener
+            .local_addr()
+            .map_err(|err| IpcError::ConnectionError { reason: err })
+    }
+
+    /// Pre-shared key, which is required for [TcpIpcServer::handshake] of accepted connections
+    pub fn key(&self) -> &PrecomputedKey {
+        &self.key
+    }
+
+    /// Try to accept new connection, client is not authenticated yet (see [TcpIpcServer::handshake]),
+    /// so slow or malicious client cannot block accepting of other connections.
+    /// In case of timeout, can be IpcError::AcceptTimeout handled
+    ///
+    /// Returns `blocking` stream and address of the client
+    pub fn try_accept(&mut self, timeout: Duration) -> Result<(TcpStream, SocketAddr), IpcError> {
+        // the same simple retry logic as for unix socket
+        self.listener
+            .set_nonblocking(true)
+            .map_err(|err| IpcError::ConnectionError { reason: err })?;
+
+        let deadline = Instant::now() + timeout;
+        let (stream, peer_address) = loop {
+            match self.listener.accept() {
+                Ok(connection) => break connection,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    if deadline.checked_duration_since(Instant::now()).is_some() {
+                        thread::sleep(Duration::from_millis(50))
+                    } else {
+                        return Err(IpcError::AcceptTimeout { timeout });
+                    }
+                }
+                Err(e) => {
+                    return Err(IpcError::ConnectionError { reason: e });
+                }
+            }
+        };
+
+        self.listener
+            .set_nonblocking(false)
+            .map_err(|err| IpcError::ConnectionError { reason: err })?;
+        configure_stream(&stream)?;
+
+        Ok((stream, peer_address))
+    }
+
+    /// Authenticates client of the accepted connection with pre-shared key (blocks up to handshake timeout)
+    ///
+    /// Returns `blocking` receiver and `blocking` sender
+    pub fn handshake(
+        mut stream: TcpStream,
+        peer_address: SocketAddr,
+        key: &PrecomputedKey,
+    ) -> Result<(IpcReceiver<R>, IpcSender<S>), IpcError> {
+        let (rx_cipher, tx_cipher) =
+            server_handshake(&mut stream, key).map_err(|err| IpcError::HandshakeError {
+                reason: format!("client: {}, {}", peer_address, handshake_reason(err)),
+            })?;
+        split(IpcStream::Tcp(stream), None, Some((rx_cipher, tx_cipher)))
+    }
+}
+
+/// Connects to a listening TCP IPC endpoint.
+#[derive(Debug)]
+pub struct TcpIpcClient<R, S> {
+    address: SocketAddr,
+    _phantom_r: PhantomData<R>,
+    _phantom_s: PhantomData<S>,
+}
+
+impl<R, S> TcpIpcClient<R, S>
+where
+    R: for<'de> Deserialize<'de>,
+    S: Serialize,
+{
+    /// Create new client instance.
+    ///
+    /// # Arguments
+    /// * `address` - address of the listening [TcpIpcServer]
+    pub fn new(address: SocketAddr) -> Self {
+        TcpIpcClient {
+            address,
+            _phantom_r: PhantomData,
+            _phantom_s: PhantomData,
+        }
+    }
+
+    pub fn address(&self) -> &SocketAddr {
+        &self.address
+    }
+
+    /// Try to open new connection and authenticate with pre-shared `key`.
+    ///
+    /// `timeout` - timeout for connect and handshake
+    pub fn connect(
+        &self,
+        key: &PrecomputedKey,
+        timeout: Duration,
+    ) -> Result<(IpcReceiver<R>, IpcSender<S>), IpcError> {
+        let mut stream = TcpStream::connect_timeout(&self.address, timeout)
+            .map_err(|err| IpcError::ConnectionError { reason: err })?;
+        configure_stream(&stream)?;
+
+        let (rx_cipher, tx_cipher) =
+            client_handshake(&mut stream, key, timeout).map_err(|err| {
+                IpcError::HandshakeError {
+                    reason: format!("server: {}, {}", self.address, handshake_reason(err)),
+                }
+            })?;
+        split(IpcStream::Tcp(stream), None, Some((rx_cipher, tx_cipher)))
+    }
+}
+
+/// Accepted/connected stream is supposed to be blocking, messages are small request/response, so we dont want to wait for Nagle
+fn configure_stream(stream: &TcpStream) -> Result<(), IpcError> {
+    stream
+        .set_nonblocking(false)
+        .and(stream.set_nodelay(true))
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })
+}
+
+/// Server waits for hello and auth of the client, then sends own auth, returns ciphers for (receiving, sending)
+fn server_handshake(
+    stream: &mut TcpStream,
+    key: &PrecomputedKey,
+) -> Result<(FrameCipher, FrameCipher), IpcError> {
+    set_handshake_timeout(stream, Some(SERVER_HANDSHAKE_TIMEOUT))?;
+
+    let client_hello = read_hello(stream)?;
+    let server_hello = write_hello(stream)?;
+    let nonces = generate_nonces(&server_hello, &client_hello, true);
+    let mut rx_cipher = FrameCipher::new(key.clone(), nonces.remote);
+    let mut tx_cipher = FrameCipher::new(key.clone(), nonces.local);
+
+    read_auth(stream, &mut rx_cipher)?;
+    write_auth(stream, &mut tx_cipher)?;
+
+    set_handshake_timeout(stream, None)?;
+    Ok((rx_cipher, tx_cipher))
+}
+
+/// Client sends hello and auth and waits for auth of the server, returns ciphers for (receiving, sending)
+fn client_handshake(
+    stream: &mut TcpStream,
+    key: &PrecomputedKey,
+    timeout: Duration,
+) -> Result<(FrameCipher, FrameCipher), IpcError> {
+    set_handshake_timeout(stream, Some(timeout))?;
+
+    let client_hello = write_hello(stream)?;
+    let server_hello = read_hello(stream)?;
+    let nonces = generate_nonces(&client_hello, &server_hello, false);
+    let mut rx_cipher = FrameCipher::new(key.clone(), nonces.remote);
+    let mut tx_cipher = FrameCipher::new(key.clone(), nonces.local);
+
+    write_auth(stream, &mut tx_cipher)?;
+    read_auth(stream, &mut rx_cipher)?;
+
+    set_handshake_timeout(stream, None)?;
+    Ok((rx_cipher, tx_cipher))
+}
+
+/// Hello and auth have fixed size, so unauthenticated peer cannot force us to allocate anything
+fn write_hello(stream: &mut TcpStream) -> Result<Vec<u8>, IpcError> {
+    let nonce = Nonce::random()
+        .get_bytes()
+        .map_err(|err| IpcError::EncryptionError {
+            reason: format!("{}", err),
+        })?;
+    let mut hello = Vec::with_capacity(HELLO_SIZE);
+    hello.extend_from_slice(HELLO_MAGIC);
+    hello.extend_from_slice(&nonce);
+    stream
+        .write_all(&hello)
+        .and(stream.flush())
+        .map_err(|err| IpcError::SendError { reason: err })?;
+    Ok(hello)
+}
+
+fn read_hello(stream: &mut TcpStream) -> Result<Vec<u8>, IpcError> {
+    let mut hello = vec![0u8; HELLO_SIZE];
+    stream
+        .read_exact(&mut hello)
+        .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+    if &hello[..HELLO_MAGIC.len()] != HELLO_MAGIC {
+        return Err(IpcError::HandshakeError {
+            reason:
+                "unexpected hello (peer is not tezedge IPC endpoint or has incompatible version)"
+                    .to_string(),
+        });
+    }
+    Ok(hello)
+}
+
+fn write_auth(stream: &mut TcpStream, cipher: &mut FrameCipher) -> Result<(), IpcError> {
+    let auth = cipher.encrypt(AUTH_MAGIC)?;
+    stream
+        .write_all(&auth)
+        .and(stream.flush())
+        .map_err(|err| IpcError::SendError { reason: err })
+}
+
+fn read_auth(stream: &mut TcpStream, cipher: &mut FrameCipher) -> Result<(), IpcError> {
+    let mut auth = vec![0u8; AUTH_SIZE];
+    stream
+        .read_exact(&mut auth)
+        .map_err(|err| IpcError::ReceiveMessageError { reason: err })?;
+    match cipher.decrypt(&auth) {
+        Ok(auth) if auth.as_slice() == AUTH_MAGIC => Ok(()),
+        _ => Err(IpcError::HandshakeError {
+            reason: "authentication failed (different pre-shared key)".to_string(),
+        }),
+    }
+}
+
+fn set_handshake_timeout(stream: &TcpStream, timeout: Option<Duration>) -> Result<(), IpcError> {
+    stream
+        .set_read_timeout(timeout)
+        .and(stream.set_write_timeout(timeout))
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })
+}
+
+fn handshake_reason(err: IpcError) -> String {
+    match err {
+        IpcError::HandshakeError { reason } => reason,
+        err => format!("{}", err),
+    }
+}
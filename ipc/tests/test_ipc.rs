@@ -5,8 +5,10 @@ use std::thread;
 use std::time::Duration;
 
 use failure::format_err;
+use hex::FromHex;
 use serial_test::serial;
 
+use crypto::crypto_box::PrecomputedKey;
 use ipc::*;
 
 mod common;
@@ -152,3 +154,161 @@ fn ipc_accept_timeout() -> Result<(), failure::Error> {
         Ok(_) => Err(format_err!("Unexpected result")),
     }
 }
+
+#[test]
+#[serial]
+fn ipc_fork_and_client_exchange_shm() -> Result<(), failure::Error> {
+    let sock_path = temp_sock();
+    assert!(!sock_path.exists());
+
+    // larger than ring buffer, so it is sent in chunks
+    let large_message = "tezedge".repeat(300_000);
+    let child_large_message = large_message.clone();
+
+    let child_pid = common::fork(|| {
+        // wait for socket/bind to be ready
+        let sock_path = sock_path.as_path();
+        while !sock_path.exists() {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        // try connect
+        let client: IpcClient<String, String> = IpcClient::new(&sock_path);
+        let (mut rx, mut tx) = client.connect().unwrap();
+        assert!(rx.is_shared_memory());
+        assert!(tx.is_shared_memory());
+
+        // test send/receive
+        tx.send(&String::from("hello")).unwrap();
+        let recv = rx.receive().unwrap();
+        assert_eq!(recv, "quick");
+
+        tx.send(&child_large_message).unwrap();
+        let recv = rx.receive().unwrap();
+        assert_eq!(recv, child_large_message);
+    });
+    assert!(child_pid > 0);
+
+    // bind and accept server
+    let mut server: IpcServer<String, String> =
+        IpcServer::bind_path(&sock_path)?.with_transport(IpcTransport::SharedMemory {
+            ring_buffer_size: 1024 * 1024,
+        });
+    let (mut rx, mut tx) = server.try_accept(Duration::from_secs(10))?;
+    assert!(rx.is_shared_memory());
+    assert!(tx.is_shared_memory());
+
+    // test send/receive
+    tx.send(&String::from("quick"))?;
+    let recv = rx.receive()?;
+    assert_eq!(recv, "hello");
+
+    let recv = rx.try_receive(Some(Duration::from_secs(10)), None)?;
+    assert_eq!(recv, large_message);
+    tx.send(&large_message)?;
+
+    // child finished, so connection is closed (not timeouted)
+    assert!(common::wait(child_pid));
+    match rx.receive() {
+        Err(IpcError::ReceiveMessageTimeouted { .. }) => {
+            Err(format_err!("Unexpected IpcError::ReceiveMessageTimeouted"))
+        }
+        Err(_) => Ok(()),
+        Ok(_) => Err(format_err!("Unexpected result")),
+    }
+}
+
+#[test]
+fn ipc_tcp_exchange_with_preshared_key() -> Result<(), failure::Error> {
+    let key = PrecomputedKey::from_hex(
+        "5228751a6f5a6494e38e1042f578e3a64ae3462b7899356f49e50be846c9609c",
+    )?;
+    let mut server: TcpIpcServer<String, String> = TcpIpcServer::bind("127.0.0.1:0", key.clone())?;
+    let address = server.local_addr()?;
+
+    // larger than socket buffers, so it is written/read in more parts
+    let large_message = "x".repeat(2_100_000);
+
+    let client_thread = {
+        let large_message = large_message.clone();
+        thread::spawn(move || {
+            let client: TcpIpcClient<String, String> = TcpIpcClient::new(address);
+            let (mut rx, mut tx) = client.connect(&key, Duration::from_secs(10)).unwrap();
+
+            tx.send(&String::from("hello")).unwrap();
+            let recv = rx.receive().unwrap();
+            assert_eq!(recv, "quick");
+
+            tx.send(&large_message).unwrap();
+            let recv = rx.receive().unwrap();
+            assert_eq!(recv, large_message);
+        })
+    };
+
+    let (stream, peer_address) = server.try_accept(Duration::from_secs(10))?;
+    let (mut rx, mut tx) =
+        TcpIpcServer::<String, String>::handshake(stream, peer_address, server.key())?;
+
+    let recv = rx.receive()?;
+    assert_eq!(recv, "hello");
+    tx.send(&String::from("quick"))?;
+
+    let recv = rx.receive()?;
+    assert_eq!(recv, large_message);
+    tx.send(&large_message)?;
+
+    client_thread
+        .join()
+        .map_err(|_| format_err!("Client thread failed"))?;
+
+    // client finished, so connection is closed
+    match rx.receive() {
+        Err(IpcError::ReceiveMessageTimeouted { .. }) => {
+            Err(format_err!("Unexpected IpcError::ReceiveMessageTimeouted"))
+        }
+        Err(_) => Ok(()),
+        Ok(_) => Err(format_err!("Unexpected result")),
+    }
+}
+
+#[test]
+fn ipc_tcp_rejects_different_preshared_key() -> Result<(), failure::Error> {
+    let server_key = PrecomputedKey::from_hex(
+        "5228751a6f5a6494e38e1042f578e3a64ae3462b7899356f49e50be846c9609c",
+    )?;
+    let client_key = PrecomputedKey::from_hex(
+        "96678b88756dd6cfd6c129980247b70a6e44da77823c3672a2ec0eae870d8646",
+    )?;
+    let mut server: TcpIpcServer<String, String> = TcpIpcServer::bind("127.0.0.1:0", server_key)?;
+    let address = server.local_addr()?;
+
+    let client_thread = thread::spawn(move || {
+        let client: TcpIpcClient<String, String> = TcpIpcClient::new(address);
+        client
+            .connect(&client_key, Duration::from_secs(10))
+            .is_err()
+    });
+
+    let (stream, peer_address) = server.try_accept(Duration::from_secs(10))?;
+    assert!(matches!(
+        TcpIpcServer::<String, String>::handshake(stream, peer_address, server.key()),
+        Err(IpcError::HandshakeError { .. })
+    ));
+    assert!(client_thread
+        .join()
+        .map_err(|_| format_err!("Client thread failed"))?);
+
+    Ok(())
+}
+
+#[test]
+fn ipc_transport_from_str() {
+    assert_eq!(Ok(IpcTransport::Socket), "socket".parse::<IpcTransport>());
+    assert_eq!(
+        Ok(IpcTransport::SharedMemory {
+            ring_buffer_size: IpcTransport::DEFAULT_RING_BUFFER_SIZE
+        }),
+        "SHM".parse::<IpcTransport>()
+    );
+    assert!("pipe".parse::<IpcTransport>().is_err());
+}
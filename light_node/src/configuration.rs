@@ -12,15 +12,18 @@ use std::{collections::HashMap, collections::HashSet, fmt::Debug};
 
 use clap::{App, Arg};
 
+use ipc::IpcTransport;
 use rocksdb::ColumnFamilyDescriptor;
 use shell::peer_manager::P2p;
+use shell::state::synchronization_state::DEFAULT_SYNCHRONIZATION_LATENCY;
 use shell::PeerConnectionThreshold;
+use storage::backend::LMDB_DEFAULT_MAP_SIZE;
 use storage::persistent::KeyValueSchema;
-use storage::KeyValueStoreBackend;
+use storage::{Checkpoint, KeyValueStoreBackend};
 use tezos_api::environment;
-use tezos_api::environment::TezosEnvironment;
+use tezos_api::environment::{TezosEnvironment, TezosEnvironmentConfiguration};
 use tezos_api::ffi::PatchContext;
-use tezos_wrapper::TezosApiConnectionPoolConfiguration;
+use tezos_wrapper::{ProtocolRunnerLocation, TezosApiConnectionPoolConfiguration};
 
 #[derive(Debug, Clone)]
 pub struct Rpc {
@@ -34,6 +37,7 @@ pub struct Logging {
     pub level: slog::Level,
     pub format: LogFormat,
     pub file: Option<PathBuf>,
+    pub block_trace_file: Option<PathBuf>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -44,7 +48,15 @@ pub enum ContextActionStoreBackend {
 }
 
 pub trait ColumnFactory {
-    fn create(&self, cache: &rocksdb::Cache) -> Vec<ColumnFamilyDescriptor>;
+    /// Column families of the database together with their names
+    fn columns(&self, cache: &rocksdb::Cache) -> Vec<(&'static str, ColumnFamilyDescriptor)>;
+
+    fn create(&self, cache: &rocksdb::Cache) -> Vec<ColumnFamilyDescriptor> {
+        self.columns(cache)
+            .into_iter()
+            .map(|(_, descriptor)| descriptor)
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -57,40 +69,40 @@ pub struct ContextTableInitializer {}
 pub struct ContextActionsTableInitializer {}
 
 impl ColumnFactory for DBTableInitializer {
-    fn create(&self, cache: &rocksdb::Cache) -> Vec<ColumnFamilyDescriptor> {
+    fn columns(&self, cache: &rocksdb::Cache) -> Vec<(&'static str, ColumnFamilyDescriptor)> {
         vec![
-            storage::block_storage::BlockPrimaryIndex::descriptor(cache),
-            storage::block_storage::BlockByLevelIndex::descriptor(cache),
-            storage::block_storage::BlockByContextHashIndex::descriptor(cache),
-            storage::BlockMetaStorage::descriptor(cache),
-            storage::OperationsStorage::descriptor(cache),
-            storage::OperationsMetaStorage::descriptor(cache),
-            storage::SystemStorage::descriptor(cache),
-            storage::persistent::sequence::Sequences::descriptor(cache),
-            storage::MempoolStorage::descriptor(cache),
-            storage::ChainMetaStorage::descriptor(cache),
-            storage::PredecessorStorage::descriptor(cache),
+            storage::block_storage::BlockPrimaryIndex::named_descriptor(cache),
+            storage::block_storage::BlockByLevelIndex::named_descriptor(cache),
+            storage::block_storage::BlockByContextHashIndex::named_descriptor(cache),
+            storage::BlockMetaStorage::named_descriptor(cache),
+            storage::OperationsStorage::named_descriptor(cache),
+            storage::OperationsMetaStorage::named_descriptor(cache),
+            storage::SystemStorage::named_descriptor(cache),
+            storage::persistent::sequence::Sequences::named_descriptor(cache),
+            storage::MempoolStorage::named_descriptor(cache),
+            storage::ChainMetaStorage::named_descriptor(cache),
+            storage::PredecessorStorage::named_descriptor(cache),
         ]
     }
 }
 
 impl ColumnFactory for ContextTableInitializer {
-    fn create(&self, cache: &rocksdb::Cache) -> Vec<ColumnFamilyDescriptor> {
+    fn columns(&self, cache: &rocksdb::Cache) -> Vec<(&'static str, ColumnFamilyDescriptor)> {
         vec![
-            storage::SystemStorage::descriptor(cache),
-            storage::merkle_storage::MerkleStorage::descriptor(cache),
+            storage::SystemStorage::named_descriptor(cache),
+            storage::merkle_storage::MerkleStorage::named_descriptor(cache),
         ]
     }
 }
 
 impl ColumnFactory for ContextActionsTableInitializer {
-    fn create(&self, cache: &rocksdb::Cache) -> Vec<ColumnFamilyDescriptor> {
+    fn columns(&self, cache: &rocksdb::Cache) -> Vec<(&'static str, ColumnFamilyDescriptor)> {
         vec![
-            storage::SystemStorage::descriptor(cache),
-            storage::context_action_storage::ContextActionByBlockHashIndex::descriptor(cache),
-            storage::context_action_storage::ContextActionByContractIndex::descriptor(cache),
-            storage::context_action_storage::ContextActionByTypeIndex::descriptor(cache),
-            storage::context_action_storage::ContextActionStorage::descriptor(cache),
+            storage::SystemStorage::named_descriptor(cache),
+            storage::context_action_storage::ContextActionByBlockHashIndex::named_descriptor(cache),
+            storage::context_action_storage::ContextActionByContractIndex::named_descriptor(cache),
+            storage::context_action_storage::ContextActionByTypeIndex::named_descriptor(cache),
+            storage::context_action_storage::ContextActionStorage::named_descriptor(cache),
         ]
     }
 }
@@ -115,6 +127,11 @@ pub struct Storage {
     pub kv_store_backend: KeyValueStoreBackend,
     pub compute_context_action_tree_hashes: bool,
     pub patch_context: Option<PatchContext>,
+    pub db_migration_dry_run: bool,
+    pub integrity_check: bool,
+    pub integrity_repair: bool,
+    pub restore_from_backup: Option<PathBuf>,
+    pub checkpoint: Option<Checkpoint>,
 }
 
 impl Storage {
@@ -142,6 +159,15 @@ pub struct Ffi {
     pub tezos_readonly_api_pool: TezosApiConnectionPoolConfiguration,
     pub tezos_readonly_prevalidation_api_pool: TezosApiConnectionPoolConfiguration,
     pub tezos_without_context_api_pool: TezosApiConnectionPoolConfiguration,
+    pub ipc_recording_file: Option<PathBuf>,
+    /// Transport for messages between node and protocol runners
+    pub ipc_transport: IpcTransport,
+    /// Pre-shared key for authentication of remote protocol runners
+    pub remote_runner_psk_file: Option<PathBuf>,
+    /// Write protocol runner, which does not apply block in this timeout, is considered as hung and is restarted
+    pub write_runner_apply_block_timeout: Duration,
+    /// Max count of blocks, which are prepared in advance/stored in the background, while (write) protocol runner applies block (0 disables pipelining)
+    pub write_runner_look_ahead: usize,
 }
 
 impl Ffi {
@@ -150,7 +176,7 @@ impl Ffi {
     const TEZOS_WITHOUT_CONTEXT_API_POOL_DISCRIMINATOR: &'static str = "twcap";
 }
 
-#[derive(Debug, Clone)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum LogFormat {
     Json,
     Simple,
@@ -168,6 +194,94 @@ impl std::str::FromStr for LogFormat {
     }
 }
 
+/// Subset of configuration, which can be reloaded at runtime without node restart (SIGHUP or rpc /dev/configuration/reload)
+#[derive(Debug, Clone)]
+pub struct ReloadableConfiguration {
+    pub peer_threshold: PeerConnectionThreshold,
+    pub bootstrap_peers: Vec<SocketAddr>,
+    pub disable_mempool: bool,
+    pub log_level: slog::Level,
+    pub log_format: LogFormat,
+    pub tezos_readonly_api_pool: TezosApiConnectionPoolConfiguration,
+    pub tezos_readonly_prevalidation_api_pool: TezosApiConnectionPoolConfiguration,
+    pub tezos_without_context_api_pool: TezosApiConnectionPoolConfiguration,
+}
+
+impl ReloadableConfiguration {
+    pub fn from_environment(env: &Environment) -> Self {
+        ReloadableConfiguration {
+            peer_threshold: env.p2p.peer_threshold,
+            bootstrap_peers: env.p2p.bootstrap_peers.clone(),
+            disable_mempool: env.p2p.disable_mempool,
+            log_level: env.logging.level,
+            log_format: env.logging.format.clone(),
+            tezos_readonly_api_pool: env.ffi.tezos_readonly_api_pool.clone(),
+            tezos_readonly_prevalidation_api_pool: env
+                .ffi
+                .tezos_readonly_prevalidation_api_pool
+                .clone(),
+            tezos_without_context_api_pool: env.ffi.tezos_without_context_api_pool.clone(),
+        }
+    }
+
+    /// Resolves reloadable configuration from args, all values are validated (no panic on invalid value)
+    fn try_from_args(args: &clap::ArgMatches) -> Result<Self, String> {
+        let required_value = |arg_name: &str| {
+            args.value_of(arg_name)
+                .ok_or_else(|| format!("required \"{}\" arg is missing", arg_name))
+        };
+        let parse_usize = |arg_name: &str, value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|e| format!("invalid \"{}\" value: {}, reason: {}", arg_name, value, e))
+        };
+
+        let peer_threshold = PeerConnectionThreshold::try_new(
+            parse_usize("peer-thresh-low", required_value("peer-thresh-low")?)?,
+            parse_usize("peer-thresh-high", required_value("peer-thresh-high")?)?,
+            match args.value_of("synchronization-thresh") {
+                Some(value) => Some(parse_usize("synchronization-thresh", value)?),
+                None => None,
+            },
+        )
+        .map_err(|e| format!("invalid peer threshold: {}", e))?;
+
+        let bootstrap_peers = match args.value_of("peers") {
+            Some(peers_str) => peers_str
+                .split(',')
+                .map(|ip_port| {
+                    ip_port
+                        .parse::<SocketAddr>()
+                        .map_err(|e| format!("invalid peer: {}, reason: {}", ip_port, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+
+        let log_level = required_value("log-level")?
+            .parse::<slog::Level>()
+            .map_err(|_| "invalid \"log-level\" value".to_string())?;
+        let log_format = required_value("log-format")?.parse::<LogFormat>()?;
+
+        Ok(ReloadableConfiguration {
+            peer_threshold,
+            bootstrap_peers,
+            disable_mempool: args.is_present("disable-mempool"),
+            log_level,
+            log_format,
+            tezos_readonly_api_pool: pool_cfg(args, Ffi::TEZOS_READONLY_API_POOL_DISCRIMINATOR)?,
+            tezos_readonly_prevalidation_api_pool: pool_cfg(
+                args,
+                Ffi::TEZOS_READONLY_PREVALIDATION_API_POOL_DISCRIMINATOR,
+            )?,
+            tezos_without_context_api_pool: pool_cfg(
+                args,
+                Ffi::TEZOS_WITHOUT_CONTEXT_API_POOL_DISCRIMINATOR,
+            )?,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Environment {
     pub p2p: P2p,
@@ -178,6 +292,8 @@ pub struct Environment {
     pub ffi: Ffi,
 
     pub tezos_network: TezosEnvironment,
+    /// Resolved configuration of tezos_network (hard-coded or loaded from network descriptor file)
+    pub tezos_network_config: TezosEnvironmentConfiguration,
     pub enable_testchain: bool,
     pub tokio_threads: usize,
 
@@ -280,6 +396,37 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("NUM")
             .help("Max number of threads used by database configuration. If not specified, then number of threads equal to CPU cores.")
             .validator(parse_validator_fn!(usize, "Value must be a valid number")))
+        .arg(Arg::with_name("db-migration-dry-run")
+            .long("db-migration-dry-run")
+            .takes_value(false)
+            .help("Validates pending database migrations without modifying the database, than just stops application"))
+        .arg(Arg::with_name("storage-integrity-check")
+            .long("storage-integrity-check")
+            .takes_value(false)
+            .help("Checks consistency of the stored blocks (metadata, additional data, operations, contexts), prints found issues and stops application"))
+        .arg(Arg::with_name("storage-integrity-repair")
+            .long("storage-integrity-repair")
+            .takes_value(false)
+            .requires("storage-integrity-check")
+            .help("Together with --storage-integrity-check, moves current head back to the last consistent applied block, so inconsistent blocks are re-applied on next start"))
+        .arg(Arg::with_name("storage-restore")
+            .long("storage-restore")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Restores storage from backup directory (created by /dev/storage/backup rpc) to empty --bootstrap-db-path before node starts. Chain id and database version of backup are checked first")
+            .validator(|v| {
+                if Path::new(&v).is_dir() {
+                    Ok(())
+                } else {
+                    Err(format!("Backup directory '{}' does not exist", v))
+                }
+            }))
+        .arg(Arg::with_name("checkpoint")
+            .long("checkpoint")
+            .takes_value(true)
+            .value_name("BLOCK_HASH,LEVEL")
+            .help("Trusted checkpoint, until current head reaches the checkpoint level, node ignores branches, which cannot contain this block, downloads headers backward from the checkpoint first and blacklists peers, which send other block at its level. Checkpoint is stored, so it is enforced also after restart")
+            .validator(|v| v.parse::<Checkpoint>().map(|_| ())))
         .arg(Arg::with_name("bootstrap-lookup-address")
             .long("bootstrap-lookup-address")
             .takes_value(true)
@@ -297,6 +444,12 @@ pub fn tezos_app() -> App<'static, 'static> {
             .value_name("PATH")
             .help("Path to the log file. If provided, logs are displayed the log file, otherwise in terminal.
                        In case it starts with ./ or ../, it is relative path to the current dir, otherwise to the --tezos-data-dir"))
+        .arg(Arg::with_name("block-trace-file")
+            .long("block-trace-file")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Path to the block lifecycle trace file. If provided, all spans of the block lifecycle are appended to this file as JSON lines.
+                       In case it starts with ./ or ../, it is relative path to the current dir, otherwise to the --tezos-data-dir"))
         .arg(Arg::with_name("log-format")
             .long("log-format")
             .takes_value(true)
@@ -326,8 +479,15 @@ pub fn tezos_app() -> App<'static, 'static> {
         .arg(Arg::with_name("network")
             .long("network")
             .takes_value(true)
-            .possible_values(&["alphanet", "babylonnet", "babylon", "mainnet", "zeronet", "carthagenet", "carthage", "delphinet", "delphi", "edonet", "edo", "sandbox"])
-            .help("Choose the Tezos environment"))
+            .value_name("NETWORK")
+            .validator(|v| {
+                if v.parse::<TezosEnvironment>().is_ok() || Path::new(&v).is_file() {
+                    Ok(())
+                } else {
+                    Err(format!("Value must be one of [alphanet, babylonnet, babylon, mainnet, zeronet, carthagenet, carthage, delphinet, delphi, edonet, edo, sandbox] or path to existing network descriptor json file, but was '{}'", v))
+                }
+            })
+            .help("Choose the Tezos environment, or path to Octez-style network descriptor json file for custom network"))
         .arg(Arg::with_name("p2p-port")
             .long("p2p-port")
             .takes_value(true)
@@ -383,8 +543,14 @@ pub fn tezos_app() -> App<'static, 'static> {
             .long("synchronization-thresh")
             .takes_value(true)
             .value_name("NUM")
-            .help("Maximal number of peers to connect to")
+            .help("Threshold number of peers with recent head, which is required to consider node as synchronized (bootstrapped)")
             .validator(parse_validator_fn!(usize, "Value must be a valid number")))
+        .arg(Arg::with_name("synchronization-latency")
+            .long("synchronization-latency")
+            .takes_value(true)
+            .value_name("SECONDS")
+            .help("Peer's head older than latency (in seconds) is not considered as recent by synchronization heuristic, default: 150")
+            .validator(parse_validator_fn!(u64, "Value must be a valid number")))
         .arg(Arg::with_name("protocol-runner")
             .long("protocol-runner")
             .takes_value(true)
@@ -416,6 +582,18 @@ pub fn tezos_app() -> App<'static, 'static> {
                     .takes_value(true)
                     .value_name("NUM")
                     .help("Number of seconds to remove unused protocol_runner from pool, default: 1800 means 30 minutes")
+                    .validator(parse_validator_fn!(u64, "Value must be a valid number")),
+                Arg::with_name("ffi-pool-runners")
+                    .long("ffi-pool-runners")
+                    .takes_value(true)
+                    .value_name("LOCATIONS")
+                    .help("Comma separated locations of protocol runners ('local' or IP:PORT of remote protocol-runner-server), new runners are started round-robin, default: local")
+                    .validator(|v| v.split(',').try_for_each(|location| location.parse::<ProtocolRunnerLocation>().map(|_| ()))),
+                Arg::with_name("ffi-pool-max-runner-memory-in-mb")
+                    .long("ffi-pool-max-runner-memory-in-mb")
+                    .takes_value(true)
+                    .value_name("NUM")
+                    .help("Max resident memory (in MB) of one local protocol_runner, runner which exceeds it is evicted from pool (checked on checkout), default: no limit")
                     .validator(parse_validator_fn!(u64, "Value must be a valid number"))
             ])
         .args(
@@ -443,6 +621,18 @@ pub fn tezos_app() -> App<'static, 'static> {
                     .takes_value(true)
                     .value_name("NUM")
                     .help("Number of seconds to remove unused protocol_runner from pool, default: 1800 means 30 minutes")
+                    .validator(parse_validator_fn!(u64, "Value must be a valid number")),
+                Arg::with_name("ffi-trpap-pool-runners")
+                    .long("ffi-trpap-pool-runners")
+                    .takes_value(true)
+                    .value_name("LOCATIONS")
+                    .help("Comma separated locations of protocol runners ('local' or IP:PORT of remote protocol-runner-server), new runners are started round-robin, default: local")
+                    .validator(|v| v.split(',').try_for_each(|location| location.parse::<ProtocolRunnerLocation>().map(|_| ()))),
+                Arg::with_name("ffi-trpap-pool-max-runner-memory-in-mb")
+                    .long("ffi-trpap-pool-max-runner-memory-in-mb")
+                    .takes_value(true)
+                    .value_name("NUM")
+                    .help("Max resident memory (in MB) of one local protocol_runner, runner which exceeds it is evicted from pool (checked on checkout), default: no limit")
                     .validator(parse_validator_fn!(u64, "Value must be a valid number"))
             ])
         .args(
@@ -470,8 +660,53 @@ pub fn tezos_app() -> App<'static, 'static> {
                     .takes_value(true)
                     .value_name("NUM")
                     .help("Number of seconds to remove unused protocol_runner from pool, default: 1800 means 30 minutes")
+                    .validator(parse_validator_fn!(u64, "Value must be a valid number")),
+                Arg::with_name("ffi-twcap-pool-runners")
+                    .long("ffi-twcap-pool-runners")
+                    .takes_value(true)
+                    .value_name("LOCATIONS")
+                    .help("Comma separated locations of protocol runners ('local' or IP:PORT of remote protocol-runner-server), new runners are started round-robin, default: local")
+                    .validator(|v| v.split(',').try_for_each(|location| location.parse::<ProtocolRunnerLocation>().map(|_| ()))),
+                Arg::with_name("ffi-twcap-pool-max-runner-memory-in-mb")
+                    .long("ffi-twcap-pool-max-runner-memory-in-mb")
+                    .takes_value(true)
+                    .value_name("NUM")
+                    .help("Max resident memory (in MB) of one local protocol_runner, runner which exceeds it is evicted from pool (checked on checkout), default: no limit")
                     .validator(parse_validator_fn!(u64, "Value must be a valid number"))
             ])
+        .arg(Arg::with_name("ffi-write-runner-apply-timeout-in-secs")
+            .long("ffi-write-runner-apply-timeout-in-secs")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Number of seconds to wait for apply block result from (write) protocol runner, after timeout runner is considered as hung and is restarted, default: 600")
+            .validator(|v| match v.parse::<u64>() {
+                Ok(secs) if secs > 0 => Ok(()),
+                _ => Err("Value must be a valid positive number".to_string()),
+            }))
+        .arg(Arg::with_name("ffi-write-runner-look-ahead")
+            .long("ffi-write-runner-look-ahead")
+            .takes_value(true)
+            .value_name("NUM")
+            .help("Max count of next blocks, which are prefetched, while (write) protocol runner applies block, results of applied blocks are stored in the background meanwhile, default: 0 (no pipelining, blocks are applied sequentially)")
+            .validator(parse_validator_fn!(usize, "Value must be a valid number")))
+        .arg(Arg::with_name("ffi-ipc-transport")
+            .long("ffi-ipc-transport")
+            .takes_value(true)
+            .value_name("STRING")
+            .possible_values(&["socket", "shm"])
+            .help("Transport for messages between node and protocol runners, 'shm' uses shared memory ring buffers (falls back to socket, if not possible), default: socket"))
+        .arg(Arg::with_name("ffi-remote-runner-psk-file")
+            .long("ffi-remote-runner-psk-file")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Path to the file with pre-shared key (32 bytes as hex string) for authentication of remote protocol runners, required if any pool uses remote runners")
+            .validator(|v| if Path::new(&v).exists() { Ok(()) } else { Err(format!("Pre-shared key file not found at '{}'", v)) }))
+        .arg(Arg::with_name("ffi-ipc-recording-file")
+            .long("ffi-ipc-recording-file")
+            .takes_value(true)
+            .value_name("PATH")
+            .help("Path to the file, where all IPC calls to protocol runners (requests, responses and timing) are recorded. Recording can be replayed with protocol-runner-replay.
+                       In case it starts with ./ or ../, it is relative path to the current dir, otherwise to the --tezos-data-dir"))
         .arg(Arg::with_name("tokio-threads")
             .long("tokio-threads")
             .takes_value(true)
@@ -488,7 +723,17 @@ pub fn tezos_app() -> App<'static, 'static> {
             .long("kv-store-backend")
             .takes_value(true)
             .value_name("STRING")
-            .help("Choose the merkle storege backend - supported backends: 'rocksdb', 'sled', 'inmem', 'btree'"))
+            .possible_values(&["rocksdb", "lmdb", "sled", "inmem", "btree"])
+            .help("Choose the storage backend used for all key-value databases (db, context, context actions) - supported backends: 'rocksdb', 'lmdb', 'sled', 'inmem', 'btree'"))
+        .arg(Arg::with_name("lmdb-map-size")
+            .long("lmdb-map-size")
+            .takes_value(true)
+            .value_name("GiB")
+            .help("Maximal size of every LMDB database in GiB (size of LMDB memory map, disk space is allocated lazily), used only with '--kv-store-backend lmdb'. Default is 1024 GiB")
+            .validator(|v| match v.parse::<usize>() {
+                Ok(size) if size > 0 => Ok(()),
+                _ => Err("Value must be a valid positive number".to_string()),
+            }))
         .arg(Arg::with_name("compute-context-action-tree-hashes")
             .long("compute-context-action-tree-hashes")
             .takes_value(true)
@@ -507,45 +752,90 @@ pub fn tezos_app() -> App<'static, 'static> {
 fn pool_cfg(
     args: &clap::ArgMatches,
     pool_name_discriminator: &str,
-) -> TezosApiConnectionPoolConfiguration {
-    TezosApiConnectionPoolConfiguration {
+) -> Result<TezosApiConnectionPoolConfiguration, String> {
+    Ok(TezosApiConnectionPoolConfiguration {
         min_connections: 0,
         /* 0 means that connections are created on-demand, because of AT_LEAST_ONE_WRITE_PROTOCOL_CONTEXT_WAS_SUCCESS_AT_FIRST_LOCK */
-        max_connections: args
-            .value_of(&format!(
-                "ffi-{}-pool-max-connections",
-                pool_name_discriminator
-            ))
-            .unwrap_or("10")
-            .parse::<u8>()
-            .expect("Provided value cannot be converted to number"),
-        connection_timeout: args
-            .value_of(&format!(
-                "ffi-{}-pool-connection-timeout-in-secs",
-                pool_name_discriminator
+        max_connections: pool_arg_value::<u8>(
+            args,
+            pool_name_discriminator,
+            "max-connections",
+            "10",
+        )?,
+        connection_timeout: pool_arg_value::<u16>(
+            args,
+            pool_name_discriminator,
+            "connection-timeout-in-secs",
+            "60",
+        )
+        .map(|seconds| Duration::from_secs(seconds as u64))?,
+        max_lifetime: pool_arg_value::<u64>(
+            args,
+            pool_name_discriminator,
+            "max-lifetime-in-secs",
+            "21600",
+        )
+        .map(Duration::from_secs)?,
+        idle_timeout: pool_arg_value::<u64>(
+            args,
+            pool_name_discriminator,
+            "idle-timeout-in-secs",
+            "1800",
+        )
+        .map(Duration::from_secs)?,
+        runners: args
+            .value_of(&pool_arg_name(pool_name_discriminator, "runners"))
+            .unwrap_or("local")
+            .split(',')
+            .map(|location| location.parse::<ProtocolRunnerLocation>())
+            .collect::<Result<Vec<_>, _>>()?,
+        max_runner_memory: args
+            .value_of(&pool_arg_name(
+                pool_name_discriminator,
+                "max-runner-memory-in-mb",
             ))
-            .unwrap_or("60")
-            .parse::<u16>()
-            .map(|seconds| Duration::from_secs(seconds as u64))
-            .expect("Provided value cannot be converted to number"),
-        max_lifetime: args
-            .value_of(&format!(
-                "ffi-{}-pool-max-lifetime-in-secs",
-                pool_name_discriminator
-            ))
-            .unwrap_or("21600")
-            .parse::<u16>()
-            .map(|seconds| Duration::from_secs(seconds as u64))
-            .expect("Provided value cannot be converted to number"),
-        idle_timeout: args
-            .value_of(&format!(
-                "ffi-{}-pool-idle-timeout-in-secs",
-                pool_name_discriminator
-            ))
-            .unwrap_or("1800")
-            .parse::<u16>()
-            .map(|seconds| Duration::from_secs(seconds as u64))
-            .expect("Provided value cannot be converted to number"),
+            .map(|mb| {
+                let invalid = |reason: String| {
+                    format!(
+                        "invalid \"{}\" value: {}, reason: {}",
+                        pool_arg_name(pool_name_discriminator, "max-runner-memory-in-mb"),
+                        mb,
+                        reason
+                    )
+                };
+                mb.parse::<u64>()
+                    .map_err(|e| invalid(e.to_string()))?
+                    .checked_mul(1024 * 1024)
+                    .ok_or_else(|| invalid("value is too large".to_string()))
+            })
+            .transpose()?,
+    })
+}
+
+/// Parses pool argument value (or default), error contains the argument name and the invalid value
+fn pool_arg_value<T>(
+    args: &clap::ArgMatches,
+    pool_name_discriminator: &str,
+    name: &str,
+    default: &str,
+) -> Result<T, String>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let arg_name = pool_arg_name(pool_name_discriminator, name);
+    let value = args.value_of(&arg_name).unwrap_or(default);
+    value
+        .parse::<T>()
+        .map_err(|e| format!("invalid \"{}\" value: {}, reason: {}", arg_name, value, e))
+}
+
+/// Name of the pool argument, e.g. ffi-trpap-pool-runners (ffi-pool-runners for default discriminator)
+fn pool_arg_name(pool_name_discriminator: &str, name: &str) -> String {
+    if pool_name_discriminator.is_empty() {
+        format!("ffi-pool-{}", name)
+    } else {
+        format!("ffi-{}-pool-{}", pool_name_discriminator, name)
     }
 }
 
@@ -608,6 +898,23 @@ pub fn get_final_path(tezos_data_dir: &Path, path: PathBuf) -> PathBuf {
     final_path
 }
 
+// Resolves network by name from hard-coded environments, or loads custom network from Octez-style network descriptor json file
+fn resolve_tezos_network(network: &str) -> (TezosEnvironment, TezosEnvironmentConfiguration) {
+    match network.parse::<TezosEnvironment>() {
+        Ok(tezos_network) => match environment::TEZOS_ENV.get(&tezos_network) {
+            None => panic!("No tezos environment configured for: {:?}", tezos_network),
+            Some(cfg) => (tezos_network, cfg.clone()),
+        },
+        Err(_) => match TezosEnvironmentConfiguration::try_from_json_file(Path::new(network)) {
+            Ok(cfg) => (TezosEnvironment::Custom, cfg),
+            Err(e) => panic!(
+                "Cannot load custom network from file: {}, reason: {}",
+                network, e
+            ),
+        },
+    }
+}
+
 // Parses config file and returns vector of OsString representing all argument strings from file
 // All lines that are empty or begin with "#" or "//" are ignored
 pub fn parse_config(config_path: PathBuf) -> Vec<OsString> {
@@ -638,7 +945,47 @@ pub fn parse_config(config_path: PathBuf) -> Vec<OsString> {
     args
 }
 
+// Merges args from config file with cli arguments (cli arguments are placed after config file arguments)
+fn merge_args(
+    config_args: Vec<OsString>,
+    mut cli_args: impl Iterator<Item = OsString>,
+) -> Vec<OsString> {
+    let mut merged_args = config_args;
+    if let Some(bin) = cli_args.next() {
+        merged_args.insert(0, bin);
+    }
+    merged_args.extend(cli_args);
+    merged_args
+}
+
 impl Environment {
+    /// Re-reads cli arguments and config file (the same way as on startup) and returns just the reloadable subset of configuration
+    pub fn reloadable_from_args() -> Result<ReloadableConfiguration, String> {
+        let app = tezos_app();
+        let cli_args: Vec<OsString> = env::args_os().collect();
+
+        let temp_args = app
+            .clone()
+            .get_matches_from_safe(cli_args.clone())
+            .map_err(|e| e.message)?;
+        let args = match temp_args.value_of("config-file") {
+            Some(config_path) => {
+                let config_path = PathBuf::from(config_path);
+                if !config_path.is_file() {
+                    return Err(format!("Config file not found at: {:?}", config_path));
+                }
+                app.get_matches_from_safe(merge_args(
+                    parse_config(config_path),
+                    cli_args.into_iter(),
+                ))
+                .map_err(|e| e.message)?
+            }
+            None => temp_args,
+        };
+
+        ReloadableConfiguration::try_from_args(&args)
+    }
+
     pub fn from_args() -> Self {
         let app = tezos_app();
         let args: clap::ArgMatches;
@@ -653,15 +1000,7 @@ impl Environment {
                 .parse::<PathBuf>()
                 .expect("Provided config-file cannot be converted to path");
 
-            let mut merged_args = parse_config(config_path);
-
-            let mut cli_args = env::args_os();
-            if let Some(bin) = cli_args.next() {
-                merged_args.insert(0, bin);
-            }
-            merged_args.extend(cli_args);
-
-            args = app.get_matches_from(merged_args);
+            args = app.get_matches_from(merge_args(parse_config(config_path), env::args_os()));
         }
         // Otherwise use only cli arguments that are already parsed
         else {
@@ -671,11 +1010,8 @@ impl Environment {
         // Validates required flags of args
         validate_required_args(&args);
 
-        let tezos_network: TezosEnvironment = args
-            .value_of("network")
-            .unwrap_or("")
-            .parse::<TezosEnvironment>()
-            .expect("Was expecting one value from TezosEnvironment");
+        let (tezos_network, tezos_network_config) =
+            resolve_tezos_network(args.value_of("network").unwrap_or(""));
 
         let data_dir: PathBuf = args
             .value_of("tezos-data-dir")
@@ -701,13 +1037,7 @@ impl Environment {
                     })
                     .unwrap_or_else(|| {
                         if !args.is_present("peers") && !args.is_present("private-node") {
-                            match environment::TEZOS_ENV.get(&tezos_network) {
-                                None => panic!(
-                                    "No tezos environment configured for: {:?}",
-                                    tezos_network
-                                ),
-                                Some(cfg) => cfg.bootstrap_lookup_addresses.clone(),
-                            }
+                            tezos_network_config.bootstrap_lookup_addresses.clone()
                         } else {
                             Vec::with_capacity(0)
                         }
@@ -744,6 +1074,14 @@ impl Environment {
                     }),
                 )
                 .expect("Invalid threashold range"),
+                synchronization_latency: args
+                    .value_of("synchronization-latency")
+                    .map(|v| {
+                        v.parse::<u64>()
+                            .map(Duration::from_secs)
+                            .expect("Provided value cannot be converted to number")
+                    })
+                    .unwrap_or(DEFAULT_SYNCHRONIZATION_LATENCY),
                 private_node: args
                     .value_of("private-node")
                     .unwrap_or("false")
@@ -791,6 +1129,13 @@ impl Environment {
                         log_file_path
                     }
                 },
+                block_trace_file: args.value_of("block-trace-file").map(|v| {
+                    get_final_path(
+                        &data_dir,
+                        v.parse::<PathBuf>()
+                            .expect("Provided value cannot be converted to path"),
+                    )
+                }),
             },
             storage: {
                 let path = args
@@ -890,6 +1235,18 @@ impl Environment {
 
                 let kv_store_backend = match args.value_of("kv-store-backend").unwrap_or("rocksdb")
                 {
+                    "lmdb" => KeyValueStoreBackend::Lmdb {
+                        map_size: args
+                            .value_of("lmdb-map-size")
+                            .map(|size| {
+                                size.parse::<usize>()
+                                    .expect("Provided value cannot be converted to number")
+                                    * 1024
+                                    * 1024
+                                    * 1024
+                            })
+                            .unwrap_or(LMDB_DEFAULT_MAP_SIZE),
+                    },
                     "inmem" => KeyValueStoreBackend::InMem,
                     "sled" => KeyValueStoreBackend::Sled,
                     "btree" => KeyValueStoreBackend::BTreeMap,
@@ -905,6 +1262,17 @@ impl Environment {
                     compute_context_action_tree_hashes,
                     action_store_backend,
                     kv_store_backend,
+                    db_migration_dry_run: args.is_present("db-migration-dry-run"),
+                    integrity_check: args.is_present("storage-integrity-check"),
+                    integrity_repair: args.is_present("storage-integrity-repair"),
+                    restore_from_backup: args.value_of("storage-restore").map(|v| {
+                        v.parse::<PathBuf>()
+                            .expect("Provided value cannot be converted to path")
+                    }),
+                    checkpoint: args.value_of("checkpoint").map(|v| {
+                        v.parse::<Checkpoint>()
+                            .expect("Provided value cannot be converted to checkpoint")
+                    }),
                     patch_context: {
                         match args.value_of("sandbox-patch-context-json-file") {
                             Some(path) => {
@@ -936,13 +1304,9 @@ impl Environment {
                             }
                             None => {
                                 // check default configuration, if any
-                                match environment::TEZOS_ENV.get(&tezos_network) {
-                                    None => panic!(
-                                        "No tezos environment configured for: {:?}",
-                                        tezos_network
-                                    ),
-                                    Some(cfg) => cfg.patch_context_genesis_parameters.clone(),
-                                }
+                                tezos_network_config
+                                    .patch_context_genesis_parameters
+                                    .clone()
                             }
                         }
                     },
@@ -972,15 +1336,45 @@ impl Environment {
                 tezos_readonly_api_pool: pool_cfg(
                     &args,
                     Ffi::TEZOS_READONLY_API_POOL_DISCRIMINATOR,
-                ),
+                )
+                .unwrap_or_else(|e| panic!("Invalid ffi pool configuration: {}", e)),
                 tezos_readonly_prevalidation_api_pool: pool_cfg(
                     &args,
                     Ffi::TEZOS_READONLY_PREVALIDATION_API_POOL_DISCRIMINATOR,
-                ),
+                )
+                .unwrap_or_else(|e| panic!("Invalid ffi pool configuration: {}", e)),
                 tezos_without_context_api_pool: pool_cfg(
                     &args,
                     Ffi::TEZOS_WITHOUT_CONTEXT_API_POOL_DISCRIMINATOR,
-                ),
+                )
+                .unwrap_or_else(|e| panic!("Invalid ffi pool configuration: {}", e)),
+                ipc_recording_file: args.value_of("ffi-ipc-recording-file").map(|v| {
+                    get_final_path(
+                        &data_dir,
+                        v.parse::<PathBuf>()
+                            .expect("Provided value cannot be converted to path"),
+                    )
+                }),
+                ipc_transport: args
+                    .value_of("ffi-ipc-transport")
+                    .unwrap_or("socket")
+                    .parse::<IpcTransport>()
+                    .expect("Provided value cannot be converted to IPC transport"),
+                remote_runner_psk_file: args.value_of("ffi-remote-runner-psk-file").map(|v| {
+                    v.parse::<PathBuf>()
+                        .expect("Provided value cannot be converted to path")
+                }),
+                write_runner_apply_block_timeout: args
+                    .value_of("ffi-write-runner-apply-timeout-in-secs")
+                    .unwrap_or("600")
+                    .parse::<u64>()
+                    .map(Duration::from_secs)
+                    .expect("Provided value cannot be converted to number"),
+                write_runner_look_ahead: args
+                    .value_of("ffi-write-runner-look-ahead")
+                    .unwrap_or("0")
+                    .parse::<usize>()
+                    .expect("Provided value cannot be converted to number"),
             },
             tokio_threads: args
                 .value_of("tokio-threads")
@@ -988,6 +1382,7 @@ impl Environment {
                 .parse::<usize>()
                 .expect("Provided value cannot be converted to number"),
             tezos_network,
+            tezos_network_config,
             enable_testchain: args
                 .value_of("enable-testchain")
                 .unwrap_or("false")
@@ -7,12 +7,14 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use riker::actors::*;
-use rocksdb::{Cache, DB};
-use slog::{debug, error, info, Drain, Logger};
+use rocksdb::Cache;
+use slog::{debug, error, info, warn, Drain, Logger};
 
 use configuration::{ColumnFactory, RocksDBConfig};
+use crypto::crypto_box::PrecomputedKey;
 use logging::detailed_json;
 use logging::file::FileAppenderBuilder;
+use logging::reloadable::{BoxedDrain, ReloadableDrain, ReloadableDrainRef};
 use monitoring::{Monitor, WebsocketHandler};
 use networking::p2p::network_channel::NetworkChannel;
 use networking::ShellCompatibilityVersion;
@@ -23,35 +25,46 @@ use shell::chain_feeder_channel::ChainFeederChannel;
 use shell::chain_manager::ChainManager;
 use shell::context_listener::ContextListener;
 use shell::mempool::init_mempool_state_storage;
-use shell::mempool::mempool_prevalidator::MempoolPrevalidator;
 use shell::peer_manager::PeerManager;
+use shell::reload::ConfigurationReloader;
 use shell::shell_channel::{ShellChannel, ShellChannelTopic, ShuttingDown};
+use shell::state::clock_state::init_node_clock;
 use shell::state::head_state::init_current_head_state;
 use shell::state::synchronization_state::init_synchronization_bootstrap_state_storage;
 use shell::stats::apply_block_stats::init_empty_apply_block_stats;
+use shell::stats::block_lifecycle::{init_block_lifecycle_tracer, BLOCK_TIMELINES_CAPACITY};
+use shell::stats::metrics::init_monitor_metrics;
+use shell::stats::write_runner_stats::init_write_runner_stats;
+use storage::backup::restore_storage;
+use storage::integrity::{check_storage_integrity, repair_current_head, IntegrityError};
+use storage::migration::{migrate_database, MigrationError, MigrationRegistry};
 use storage::persistent::{
-    open_cl, open_kv, ActionRecorder, CommitLogSchema, DbConfiguration, NoRecorder,
-    PersistentStorage,
+    open_cl, open_kv_store, ActionRecorder, CommitLogSchema, DbConfiguration, KeyValueStore,
+    NoRecorder, PersistentStorage, StorageType,
 };
 use storage::ActionFileStorage;
 use storage::ContextActionStorage;
 use storage::{
-    check_database_compatibility, context::TezedgeContext, persistent::DBError,
-    resolve_storage_init_chain_data, BlockStorage, StorageInitInfo,
+    check_database_compatibility, context::TezedgeContext, persistent::DBError, resolve_checkpoint,
+    resolve_storage_init_chain_data, BlockStorage, ChainMetaStorage, KeyValueStoreBackend,
+    StorageInitInfo,
 };
 use tezos_api::environment;
 use tezos_api::environment::TezosEnvironmentConfiguration;
 use tezos_api::ffi::TezosRuntimeConfiguration;
 use tezos_identity::Identity;
+use tezos_wrapper::recorder::{IpcRecorder, IpcRecorderRef};
 use tezos_wrapper::service::IpcEvtServer;
 use tezos_wrapper::ProtocolEndpointConfiguration;
-use tezos_wrapper::TezosApiConnectionPoolError;
+use tezos_wrapper::{ProtocolRunnerLocation, TezosApiConnectionPoolError};
 use tezos_wrapper::{TezosApiConnectionPool, TezosApiConnectionPoolConfiguration};
 
 use crate::configuration::LogFormat;
+use crate::reload::NodeConfigurationReloader;
 
 mod configuration;
 mod identity;
+mod reload;
 mod system;
 
 macro_rules! create_terminal_logger {
@@ -100,15 +113,22 @@ macro_rules! create_file_logger {
     }};
 }
 
-fn create_logger(env: &crate::configuration::Environment) -> Logger {
-    let drain = match &env.logging.file {
-        Some(log_file) => create_file_logger!(env.logging.format, log_file),
-        None => create_terminal_logger!(env.logging.format),
+/// Creates underlying log drain with selected format (used also for runtime reload of log format)
+pub(crate) fn create_log_drain(format: &LogFormat, file: Option<&PathBuf>) -> BoxedDrain {
+    match file {
+        Some(log_file) => Box::new(create_file_logger!(format, log_file).fuse()),
+        None => Box::new(create_terminal_logger!(format).fuse()),
     }
-    .filter_level(env.logging.level)
-    .fuse();
+}
+
+/// Creates root logger, returned drain can be used to change log level/format at runtime
+fn create_logger(env: &crate::configuration::Environment) -> (Logger, ReloadableDrainRef) {
+    let drain = ReloadableDrain::new(
+        env.logging.level,
+        create_log_drain(&env.logging.format, env.logging.file.as_ref()),
+    );
 
-    Logger::root(drain, slog::o!())
+    (Logger::root(drain.clone(), slog::o!()), drain)
 }
 
 fn create_tokio_runtime(
@@ -132,6 +152,8 @@ fn create_tezos_readonly_api_pool(
     pool_cfg: TezosApiConnectionPoolConfiguration,
     env: &crate::configuration::Environment,
     tezos_env: TezosEnvironmentConfiguration,
+    ipc_recorder: Option<IpcRecorderRef>,
+    remote_runner_key: Option<PrecomputedKey>,
     log: Logger,
 ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
     TezosApiConnectionPool::new_with_readonly_context(
@@ -149,6 +171,9 @@ fn create_tezos_readonly_api_pool(
             &env.ffi.protocol_runner,
             env.logging.level,
             None,
+            ipc_recorder,
+            env.ffi.ipc_transport,
+            remote_runner_key,
         ),
         log,
     )
@@ -161,6 +186,8 @@ fn create_tezos_without_context_api_pool(
     pool_cfg: TezosApiConnectionPoolConfiguration,
     env: &crate::configuration::Environment,
     tezos_env: TezosEnvironmentConfiguration,
+    ipc_recorder: Option<IpcRecorderRef>,
+    remote_runner_key: Option<PrecomputedKey>,
     log: Logger,
 ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
     TezosApiConnectionPool::new_without_context(
@@ -178,6 +205,9 @@ fn create_tezos_without_context_api_pool(
             &env.ffi.protocol_runner,
             env.logging.level,
             None,
+            ipc_recorder,
+            env.ffi.ipc_transport,
+            remote_runner_key,
         ),
         log,
     )
@@ -189,6 +219,7 @@ fn create_tezos_writeable_api_pool(
     event_server_path: PathBuf,
     env: &crate::configuration::Environment,
     tezos_env: TezosEnvironmentConfiguration,
+    ipc_recorder: Option<IpcRecorderRef>,
     log: Logger,
 ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
     TezosApiConnectionPool::new_without_context(
@@ -201,6 +232,9 @@ fn create_tezos_writeable_api_pool(
             connection_timeout: Duration::from_secs(30),
             min_connections: 0,
             max_connections: 1,
+            // writeable context is always local
+            runners: vec![ProtocolRunnerLocation::Local],
+            max_runner_memory: None,
         },
         ProtocolEndpointConfiguration::new(
             TezosRuntimeConfiguration {
@@ -214,6 +248,9 @@ fn create_tezos_writeable_api_pool(
             &env.ffi.protocol_runner,
             env.logging.level,
             Some(event_server_path),
+            ipc_recorder,
+            env.ffi.ipc_transport,
+            None,
         ),
         log,
     )
@@ -248,6 +285,7 @@ fn block_on_actors(
     identity: Arc<Identity>,
     persistent_storage: PersistentStorage,
     tezedge_context: TezedgeContext,
+    log_drain: ReloadableDrainRef,
     log: Logger,
 ) {
     // if feeding is started, than run chain manager
@@ -261,6 +299,19 @@ fn block_on_actors(
 
     info!(log, "Initializing protocol runners... (3/4)");
 
+    // optional recording of all IPC calls to protocol runners (shared by all pools)
+    let ipc_recorder = env.ffi.ipc_recording_file.as_ref().map(|ipc_recording_file| {
+        info!(log, "Recording IPC calls to protocol runners"; "file" => ipc_recording_file.as_path().display().to_string());
+        IpcRecorder::create(ipc_recording_file, log.clone())
+            .expect("Failed to create IPC recording file")
+    });
+
+    // optional pre-shared key for remote protocol runners (shared by all readonly pools)
+    let remote_runner_key = env.ffi.remote_runner_psk_file.as_ref().map(|psk_file| {
+        ipc::load_preshared_key(psk_file)
+            .expect("Failed to load pre-shared key for remote protocol runners")
+    });
+
     // create pool for ffi protocol runner connections (used just for readonly context)
     let tezos_readonly_api_pool = Arc::new(
         create_tezos_readonly_api_pool(
@@ -268,6 +319,8 @@ fn block_on_actors(
             env.ffi.tezos_readonly_api_pool.clone(),
             &env,
             tezos_env.clone(),
+            ipc_recorder.clone(),
+            remote_runner_key.clone(),
             log.clone(),
         )
         .expect("Failed to initialize read-only API pool"),
@@ -278,6 +331,8 @@ fn block_on_actors(
             env.ffi.tezos_readonly_prevalidation_api_pool.clone(),
             &env,
             tezos_env.clone(),
+            ipc_recorder.clone(),
+            remote_runner_key.clone(),
             log.clone(),
         )
         .expect("Failed to initialize read-only prevalidation API pool"),
@@ -288,6 +343,8 @@ fn block_on_actors(
             env.ffi.tezos_without_context_api_pool.clone(),
             &env,
             tezos_env.clone(),
+            ipc_recorder.clone(),
+            remote_runner_key,
             log.clone(),
         )
         .expect("Failed to initialize API pool without context"),
@@ -301,6 +358,7 @@ fn block_on_actors(
             context_actions_event_server.server_path(),
             &env,
             tezos_env.clone(),
+            ipc_recorder,
             log.clone(),
         )
         .expect("Failed to initialize writable API pool"),
@@ -313,12 +371,23 @@ fn block_on_actors(
     let local_current_head_state = init_current_head_state();
     let remote_current_head_state = init_current_head_state();
     let current_mempool_state_storage = init_mempool_state_storage();
+    let node_clock = init_node_clock();
     let bootstrap_state = init_synchronization_bootstrap_state_storage(
         env.p2p
             .peer_threshold
             .num_of_peers_for_bootstrap_threshold(),
+        env.p2p.synchronization_latency,
+        node_clock.clone(),
     );
     let apply_block_stats = init_empty_apply_block_stats();
+    let monitor_metrics = init_monitor_metrics();
+    let block_lifecycle_tracer = init_block_lifecycle_tracer(
+        BLOCK_TIMELINES_CAPACITY,
+        env.logging.block_trace_file.as_deref(),
+        log.clone(),
+    )
+    .expect("Failed to open block lifecycle trace file");
+    let write_runner_stats = init_write_runner_stats();
 
     // create tokio runtime
     let tokio_runtime = create_tokio_runtime(&env).expect("Failed to create tokio runtime");
@@ -343,6 +412,7 @@ fn block_on_actors(
         &persistent_storage,
         build_recorders(&env, &persistent_storage),
         context_actions_event_server,
+        block_lifecycle_tracer.clone(),
         log.clone(),
     )
     .expect("Failed to create context event listener");
@@ -352,10 +422,10 @@ fn block_on_actors(
         persistent_storage.clone(),
         init_storage_data.clone(),
         local_current_head_state.clone(),
-        remote_current_head_state.clone(),
         current_mempool_state_storage.clone(),
         bootstrap_state.clone(),
         apply_block_stats.clone(),
+        block_lifecycle_tracer.clone(),
     )
     .expect("Failed to create chain current head manager");
     let block_applier = ChainFeeder::actor(
@@ -367,6 +437,10 @@ fn block_on_actors(
         tezos_writeable_api_pool.clone(),
         init_storage_data.clone(),
         tezos_env.clone(),
+        block_lifecycle_tracer.clone(),
+        write_runner_stats.clone(),
+        env.ffi.write_runner_apply_block_timeout,
+        env.ffi.write_runner_look_ahead,
         log.clone(),
     )
     .expect("Failed to create chain feeder");
@@ -383,27 +457,38 @@ fn block_on_actors(
         local_current_head_state,
         remote_current_head_state,
         current_mempool_state_storage.clone(),
-        bootstrap_state,
-        apply_block_stats,
+        bootstrap_state.clone(),
+        apply_block_stats.clone(),
+        block_lifecycle_tracer.clone(),
+        node_clock.clone(),
         env.p2p.disable_mempool,
         identity.clone(),
     )
     .expect("Failed to create chain manager");
 
+    // reloader of runtime configuration (also owns mempool prevalidator, which can be started/stopped on reload)
+    let configuration_reloader = Arc::new(NodeConfigurationReloader::new(
+        &env,
+        log_drain,
+        shell_channel.clone(),
+        bootstrap_state.clone(),
+        tezos_readonly_api_pool.clone(),
+        tezos_readonly_prevalidation_api_pool.clone(),
+        tezos_without_context_api_pool.clone(),
+        actor_system.clone(),
+        persistent_storage.clone(),
+        current_mempool_state_storage.clone(),
+        init_storage_data.clone(),
+        log.clone(),
+    ));
+
     if env.p2p.disable_mempool {
         info!(log, "Mempool disabled");
     } else {
         info!(log, "Mempool enabled");
-        let _ = MempoolPrevalidator::actor(
-            &actor_system,
-            shell_channel.clone(),
-            &persistent_storage,
-            current_mempool_state_storage.clone(),
-            init_storage_data.chain_id.clone(),
-            tezos_readonly_api_pool.clone(),
-            log.clone(),
-        )
-        .expect("Failed to create mempool prevalidator");
+        configuration_reloader
+            .start_mempool_prevalidator()
+            .expect("Failed to create mempool prevalidator");
     }
     let websocket_handler = WebsocketHandler::actor(
         &actor_system,
@@ -417,6 +502,7 @@ fn block_on_actors(
         network_channel.clone(),
         websocket_handler,
         shell_channel.clone(),
+        monitor_metrics.clone(),
     )
     .expect("Failed to create monitor actor");
     let _ = RpcServer::actor(
@@ -426,10 +512,18 @@ fn block_on_actors(
         &tokio_runtime.handle(),
         &persistent_storage,
         current_mempool_state_storage,
+        bootstrap_state,
         &tezedge_context,
         tezos_readonly_api_pool.clone(),
         tezos_readonly_prevalidation_api_pool.clone(),
         tezos_without_context_api_pool.clone(),
+        tezos_writeable_api_pool.clone(),
+        apply_block_stats,
+        block_lifecycle_tracer,
+        monitor_metrics,
+        configuration_reloader.clone(),
+        node_clock,
+        write_runner_stats,
         tezos_env.clone(),
         Arc::new(shell_compatibility_version.to_network_version()),
         &init_storage_data,
@@ -454,6 +548,38 @@ fn block_on_actors(
 
     info!(log, "Actors initialized");
 
+    // reload configuration on SIGHUP
+    let configuration_reload_on_sighup = {
+        let configuration_reloader = configuration_reloader.clone();
+        let log = log.clone();
+        tokio_runtime.spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => hangup,
+                Err(e) => {
+                    warn!(log, "Failed to listen for SIGHUP, configuration reload by signal is disabled"; "reason" => format!("{}", e));
+                    return;
+                }
+            };
+            while hangup.recv().await.is_some() {
+                info!(log, "SIGHUP received, reloading configuration");
+                let configuration_reloader = configuration_reloader.clone();
+                match tokio::task::spawn_blocking(move || configuration_reloader.reload()).await {
+                    Ok(Ok(changes)) => {
+                        info!(log, "Configuration reload by SIGHUP finished"; "changes" => changes.len())
+                    }
+                    Ok(Err(e)) => {
+                        warn!(log, "Configuration reload by SIGHUP failed"; "reason" => format!("{}", e))
+                    }
+                    Err(e) => {
+                        warn!(log, "Configuration reload by SIGHUP failed"; "reason" => format!("{}", e))
+                    }
+                }
+            }
+        })
+    };
+
     tokio_runtime.block_on(async move {
         use tokio::signal;
         use tokio::time::timeout;
@@ -482,6 +608,9 @@ fn block_on_actors(
         };
 
         info!(log, "Shutting down protocol runner pools (3/5)");
+        configuration_reload_on_sighup.abort();
+        let _ = configuration_reload_on_sighup.await;
+        drop(configuration_reloader);
         drop(tezos_readonly_api_pool);
         drop(tezos_readonly_prevalidation_api_pool);
         drop(tezos_without_context_api_pool);
@@ -496,13 +625,37 @@ fn block_on_actors(
     });
 }
 
+/// Applies all pending migrations to the database (in dry-run mode migrations are just validated)
+fn migrate_db<Factory: ColumnFactory>(
+    log: &Logger,
+    cache: &Cache,
+    config: &RocksDBConfig<Factory>,
+    storage_type: StorageType,
+    dry_run: bool,
+) -> Result<(), MigrationError> {
+    migrate_database(
+        &config.db_path,
+        config.columns.columns(cache),
+        &DbConfiguration {
+            max_threads: config.threads,
+        },
+        &MigrationRegistry::for_storage(storage_type),
+        config.expected_db_version,
+        dry_run,
+        log,
+    )
+    .map(|_| ())
+}
+
 fn initialize_db<Factory: ColumnFactory>(
     log: &Logger,
     cache: &Cache,
     config: &RocksDBConfig<Factory>,
+    backend: &KeyValueStoreBackend,
     env: &TezosEnvironmentConfiguration,
-) -> Result<Arc<DB>, DBError> {
-    let db = open_kv(
+) -> Result<Arc<KeyValueStore>, DBError> {
+    let db = open_kv_store(
+        backend,
         &config.db_path,
         config.columns.create(cache),
         &DbConfiguration {
@@ -525,20 +678,41 @@ fn initialize_db<Factory: ColumnFactory>(
     }
 }
 
+/// Checks integrity of the whole storage, logs all found issues and optionally moves current head to the last consistent block
+fn check_integrity(
+    log: &Logger,
+    persistent_storage: &PersistentStorage,
+    tezos_env: &TezosEnvironmentConfiguration,
+    repair: bool,
+) -> Result<(), IntegrityError> {
+    let report = check_storage_integrity(persistent_storage, log)?;
+    for issue in &report.issues {
+        warn!(log, "Storage inconsistency found"; "issue" => format!("{}", issue));
+    }
+
+    if report.is_consistent() {
+        info!(log, "Storage is consistent"; "checked_blocks" => report.checked_blocks);
+    } else if repair {
+        let chain_id = tezos_env
+            .main_chain_id()
+            .map_err(|error| IntegrityError::StorageError {
+                error: error.into(),
+            })?;
+        repair_current_head(persistent_storage, &chain_id, &report, log)?;
+    } else {
+        warn!(log, "Storage is not consistent, run with --storage-integrity-repair to move current head to the last consistent block";
+                   "issues" => report.issues.len());
+    }
+    Ok(())
+}
+
 fn main() {
     // Parses config + cli args
     let env = crate::configuration::Environment::from_args();
-    let tezos_env = environment::TEZOS_ENV
-        .get(&env.tezos_network)
-        .unwrap_or_else(|| {
-            panic!(
-                "No tezos environment version configured for: {:?}",
-                env.tezos_network
-            )
-        });
+    let tezos_env = env.tezos_network_config.clone();
 
     // Creates default logger
-    let log = create_logger(&env);
+    let (log, log_drain) = create_logger(&env);
 
     // Loads tezos identity based on provided identity-file argument. In case it does not exist, it will try to automatically generate it
     info!(log, "Loading identity... (1/4)");
@@ -580,26 +754,96 @@ fn main() {
             .expect("Failed to initialize RocksDB cache (db_context_actions)"),
     ];
 
+    // restore storage from backup, if requested
+    if let Some(backup_dir) = &env.storage.restore_from_backup {
+        let chain_id = tezos_env
+            .main_chain_id()
+            .expect("Failed to resolve main chain id");
+        restore_storage(
+            backup_dir,
+            &env.storage.db_path,
+            &chain_id,
+            env.storage.db.expected_db_version,
+            &log,
+        )
+        .expect("Failed to restore storage from backup");
+    }
+
+    // migrate dbs to expected versions, if needed (migrations are supported for RocksDB only)
+    let dry_run = env.storage.db_migration_dry_run;
+    if env.storage.kv_store_backend == KeyValueStoreBackend::RocksDB {
+        migrate_db(
+            &log,
+            &cache[0],
+            &env.storage.db,
+            StorageType::Database,
+            dry_run,
+        )
+        .expect("Failed to migrate RocksDB database (db)");
+        migrate_db(
+            &log,
+            &cache[1],
+            &env.storage.db_context,
+            StorageType::Context,
+            dry_run,
+        )
+        .expect("Failed to migrate RocksDB database (db_context)");
+        migrate_db(
+            &log,
+            &cache[2],
+            &env.storage.db_context_actions,
+            StorageType::ContextAction,
+            dry_run,
+        )
+        .expect("Failed to migrate RocksDB database (db_context_actions)");
+    } else {
+        info!(log, "Database migrations are skipped, they are not supported by storage backend";
+                   "backend" => format!("{:?}", env.storage.kv_store_backend));
+    }
+    if dry_run {
+        info!(log, "Database migration dry-run finished, stopping node");
+        return;
+    }
+
     // initialize dbs
-    let kv = initialize_db(&log, &cache[0], &env.storage.db, &tezos_env)
-        .expect("Failed to create/initialize RocksDB database (db)");
-    let kv_context = initialize_db(&log, &cache[1], &env.storage.db_context, &tezos_env)
-        .expect("Failed to create/initialize RocksDB database (db_context)");
-    let kv_actions = initialize_db(&log, &cache[2], &env.storage.db_context_actions, &tezos_env)
-        .expect("Failed to create/initialize RocksDB database (db_context_actions)");
+    let backend = &env.storage.kv_store_backend;
+    let kv = initialize_db(&log, &cache[0], &env.storage.db, backend, &tezos_env)
+        .expect("Failed to create/initialize database (db)");
+    let kv_context = initialize_db(
+        &log,
+        &cache[1],
+        &env.storage.db_context,
+        backend,
+        &tezos_env,
+    )
+    .expect("Failed to create/initialize database (db_context)");
+    let kv_actions = initialize_db(
+        &log,
+        &cache[2],
+        &env.storage.db_context_actions,
+        backend,
+        &tezos_env,
+    )
+    .expect("Failed to create/initialize database (db_context_actions)");
     let commit_logs = Arc::new(
         open_cl(&env.storage.db_path, vec![BlockStorage::descriptor()])
             .expect("Failed to open plain block_header storage"),
     );
 
     {
-        let persistent_storage = PersistentStorage::new(
-            kv,
-            kv_context,
-            kv_actions,
-            commit_logs,
-            env.storage.kv_store_backend.clone(),
-        );
+        let persistent_storage = PersistentStorage::new(kv, kv_context, kv_actions, commit_logs);
+
+        if env.storage.integrity_check {
+            check_integrity(
+                &log,
+                &persistent_storage,
+                &tezos_env,
+                env.storage.integrity_repair,
+            )
+            .expect("Failed to check storage integrity");
+            return;
+        }
+
         let tezedge_context = TezedgeContext::new(
             BlockStorage::new(&persistent_storage),
             persistent_storage.merkle(),
@@ -610,17 +854,25 @@ fn main() {
             &env.storage.db_path,
             &env.storage.tezos_data_dir,
             &env.storage.patch_context,
+            &env.storage.checkpoint,
             &log,
         ) {
-            Ok(init_data) => {
+            Ok(mut init_data) => {
+                resolve_checkpoint(
+                    &ChainMetaStorage::new(&persistent_storage),
+                    &mut init_data,
+                    &log,
+                )
+                .expect("Failed to resolve checkpoint");
                 info!(log, "Databases loaded successfully");
                 block_on_actors(
                     env,
-                    tezos_env,
+                    &tezos_env,
                     init_data,
                     Arc::new(tezos_identity),
                     persistent_storage,
                     tezedge_context,
+                    log_drain,
                     log,
                 )
             }
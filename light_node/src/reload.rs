@@ -0,0 +1,350 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Runtime reload of the subset of configuration (see [ReloadableConfiguration]), triggered by SIGHUP or rpc.
+//!
+//! Changes are applied to:
+//! - logger (log level and log format)
+//! - peer manager and chain manager (peer thresholds, bootstrap peers, mempool) through shell channel
+//! - mempool prevalidator (started/stopped according to disable_mempool)
+//! - protocol runner pools (readonly, readonly prevalidation and without context pools are recreated with new configuration)
+
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use riker::actors::*;
+use slog::{info, warn, Logger};
+
+use logging::reloadable::ReloadableDrainRef;
+use shell::mempool::mempool_prevalidator::{MempoolPrevalidator, MempoolPrevalidatorRef};
+use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::reload::{ConfigurationChange, ConfigurationReloadError, ConfigurationReloader};
+use shell::shell_channel::{P2pConfigurationReloaded, ShellChannelRef, ShellChannelTopic};
+use shell::state::synchronization_state::SynchronizationBootstrapStateRef;
+use storage::persistent::PersistentStorage;
+use storage::StorageInitInfo;
+use tezos_wrapper::{TezosApiConnectionPool, TezosApiConnectionPoolConfiguration};
+
+use crate::configuration::{Environment, ReloadableConfiguration};
+
+/// How long to wait for the stopped mempool prevalidator actor to terminate
+const MEMPOOL_PREVALIDATOR_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Applies reloaded configuration to the running node
+pub struct NodeConfigurationReloader {
+    /// Currently applied configuration (lock is held during the whole reload)
+    current: Mutex<ReloadableConfiguration>,
+
+    log_drain: ReloadableDrainRef,
+    log_file: Option<PathBuf>,
+
+    shell_channel: ShellChannelRef,
+    bootstrap_state: SynchronizationBootstrapStateRef,
+
+    tezos_readonly_api_pool: Arc<TezosApiConnectionPool>,
+    tezos_readonly_prevalidation_api_pool: Arc<TezosApiConnectionPool>,
+    tezos_without_context_api_pool: Arc<TezosApiConnectionPool>,
+
+    /// Running mempool prevalidator (if mempool is enabled) and everything needed to start it at runtime
+    mempool_prevalidator: Mutex<Option<MempoolPrevalidatorRef>>,
+    actor_system: ActorSystem,
+    persistent_storage: PersistentStorage,
+    current_mempool_state_storage: CurrentMempoolStateStorageRef,
+    init_storage_data: StorageInitInfo,
+
+    log: Logger,
+}
+
+impl NodeConfigurationReloader {
+    pub fn new(
+        env: &Environment,
+        log_drain: ReloadableDrainRef,
+        shell_channel: ShellChannelRef,
+        bootstrap_state: SynchronizationBootstrapStateRef,
+        tezos_readonly_api_pool: Arc<TezosApiConnectionPool>,
+        tezos_readonly_prevalidation_api_pool: Arc<TezosApiConnectionPool>,
+        tezos_without_context_api_pool: Arc<TezosApiConnectionPool>,
+        actor_system: ActorSystem,
+        persistent_storage: PersistentStorage,
+        current_mempool_state_storage: CurrentMempoolStateStorageRef,
+        init_storage_data: StorageInitInfo,
+        log: Logger,
+    ) -> Self {
+        NodeConfigurationReloader {
+            current: Mutex::new(ReloadableConfiguration::from_environment(env)),
+            log_drain,
+            log_file: env.logging.file.clone(),
+            shell_channel,
+            bootstrap_state,
+            tezos_readonly_api_pool,
+            tezos_readonly_prevalidation_api_pool,
+            tezos_without_context_api_pool,
+            mempool_prevalidator: Mutex::new(None),
+            actor_system,
+            persistent_storage,
+            current_mempool_state_storage,
+            init_storage_data,
+            log,
+        }
+    }
+
+    /// Starts mempool prevalidator actor (if not running)
+    pub fn start_mempool_prevalidator(&self) -> Result<(), ConfigurationReloadError> {
+        let mut mempool_prevalidator = self.lock_mempool_prevalidator()?;
+        if mempool_prevalidator.is_some() {
+            return Ok(());
+        }
+
+        // previous instance could still be stopping (actor stop is asynchronous)
+        self.wait_for_mempool_prevalidator_terminated()?;
+
+        let actor = MempoolPrevalidator::actor(
+            &self.actor_system,
+            self.shell_channel.clone(),
+            &self.persistent_storage,
+            self.current_mempool_state_storage.clone(),
+            self.init_storage_data.chain_id.clone(),
+            self.tezos_readonly_api_pool.clone(),
+            self.log.clone(),
+        )
+        .map_err(|e| ConfigurationReloadError::ApplyError {
+            setting: "disable_mempool".to_string(),
+            reason: format!("Failed to create mempool prevalidator: {:?}", e),
+        })?;
+        *mempool_prevalidator = Some(actor);
+        Ok(())
+    }
+
+    /// Stops mempool prevalidator actor (if running) and waits until it is terminated,
+    /// so the actor name is released and prevalidator can be started again by the next reload
+    fn stop_mempool_prevalidator(&self) -> Result<(), ConfigurationReloadError> {
+        if let Some(actor) = self.lock_mempool_prevalidator()?.take() {
+            self.actor_system.stop(actor);
+            self.wait_for_mempool_prevalidator_terminated()?;
+        }
+        Ok(())
+    }
+
+    fn wait_for_mempool_prevalidator_terminated(&self) -> Result<(), ConfigurationReloadError> {
+        let started = Instant::now();
+        while self
+            .actor_system
+            .user_root()
+            .children()
+            .any(|actor_ref| actor_ref.name() == MempoolPrevalidator::name())
+        {
+            if started.elapsed() > MEMPOOL_PREVALIDATOR_STOP_TIMEOUT {
+                return Err(ConfigurationReloadError::ApplyError {
+                    setting: "disable_mempool".to_string(),
+                    reason: format!(
+                        "Mempool prevalidator was not stopped within {:?}",
+                        MEMPOOL_PREVALIDATOR_STOP_TIMEOUT
+                    ),
+                });
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    fn lock_mempool_prevalidator(
+        &self,
+    ) -> Result<std::sync::MutexGuard<Option<MempoolPrevalidatorRef>>, ConfigurationReloadError>
+    {
+        self.mempool_prevalidator
+            .lock()
+            .map_err(|e| ConfigurationReloadError::ApplyError {
+                setting: "disable_mempool".to_string(),
+                reason: format!("Failed to lock mempool prevalidator: {}", e),
+            })
+    }
+
+    fn apply(
+        &self,
+        current: &mut ReloadableConfiguration,
+        reloaded: ReloadableConfiguration,
+        changes: &mut Vec<ConfigurationChange>,
+    ) -> Result<(), ConfigurationReloadError> {
+        // logging
+        if current.log_level != reloaded.log_level {
+            self.log_drain.set_level(reloaded.log_level);
+            changes.push(change("log_level", &current.log_level, &reloaded.log_level));
+            current.log_level = reloaded.log_level;
+        }
+        if current.log_format != reloaded.log_format {
+            self.log_drain.set_drain(crate::create_log_drain(
+                &reloaded.log_format,
+                self.log_file.as_ref(),
+            ));
+            changes.push(change(
+                "log_format",
+                &current.log_format,
+                &reloaded.log_format,
+            ));
+            current.log_format = reloaded.log_format;
+        }
+
+        // p2p
+        if current.peer_threshold != reloaded.peer_threshold
+            || current.bootstrap_peers != reloaded.bootstrap_peers
+            || current.disable_mempool != reloaded.disable_mempool
+        {
+            if current.peer_threshold != reloaded.peer_threshold {
+                self.bootstrap_state
+                    .write()
+                    .map_err(|e| ConfigurationReloadError::ApplyError {
+                        setting: "peer_threshold".to_string(),
+                        reason: format!("Failed to lock bootstrap state: {}", e),
+                    })?
+                    .set_num_of_peers_for_bootstrap_threshold(
+                        reloaded
+                            .peer_threshold
+                            .num_of_peers_for_bootstrap_threshold(),
+                    );
+                changes.push(change(
+                    "peer_threshold",
+                    &current.peer_threshold,
+                    &reloaded.peer_threshold,
+                ));
+            }
+            if current.bootstrap_peers != reloaded.bootstrap_peers {
+                changes.push(change(
+                    "bootstrap_peers",
+                    &current.bootstrap_peers,
+                    &reloaded.bootstrap_peers,
+                ));
+            }
+            if current.disable_mempool != reloaded.disable_mempool {
+                if reloaded.disable_mempool {
+                    self.stop_mempool_prevalidator()?;
+                } else {
+                    self.start_mempool_prevalidator()?;
+                }
+                changes.push(change(
+                    "disable_mempool",
+                    &current.disable_mempool,
+                    &reloaded.disable_mempool,
+                ));
+            }
+
+            // notify peer manager and chain manager
+            self.shell_channel.tell(
+                Publish {
+                    msg: P2pConfigurationReloaded {
+                        peer_threshold: reloaded.peer_threshold,
+                        bootstrap_peers: reloaded.bootstrap_peers.clone(),
+                        disable_mempool: reloaded.disable_mempool,
+                    }
+                    .into(),
+                    topic: ShellChannelTopic::ShellConfiguration.into(),
+                },
+                None,
+            );
+
+            current.peer_threshold = reloaded.peer_threshold;
+            current.bootstrap_peers = reloaded.bootstrap_peers;
+            current.disable_mempool = reloaded.disable_mempool;
+        }
+
+        // protocol runner pools
+        reconfigure_pool(
+            &self.tezos_readonly_api_pool,
+            &mut current.tezos_readonly_api_pool,
+            reloaded.tezos_readonly_api_pool,
+            changes,
+        )?;
+        reconfigure_pool(
+            &self.tezos_readonly_prevalidation_api_pool,
+            &mut current.tezos_readonly_prevalidation_api_pool,
+            reloaded.tezos_readonly_prevalidation_api_pool,
+            changes,
+        )?;
+        reconfigure_pool(
+            &self.tezos_without_context_api_pool,
+            &mut current.tezos_without_context_api_pool,
+            reloaded.tezos_without_context_api_pool,
+            changes,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl ConfigurationReloader for NodeConfigurationReloader {
+    fn reload(&self) -> Result<Vec<ConfigurationChange>, ConfigurationReloadError> {
+        let mut current = match self.current.try_lock() {
+            Ok(current) => current,
+            Err(std::sync::TryLockError::WouldBlock) => {
+                return Err(ConfigurationReloadError::AlreadyRunning)
+            }
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        };
+
+        // read and validate at first, so nothing is applied, if configuration is invalid
+        let reloaded = Environment::reloadable_from_args()
+            .map_err(|reason| ConfigurationReloadError::InvalidConfiguration { reason })?;
+        for pool_cfg in &[
+            &reloaded.tezos_readonly_api_pool,
+            &reloaded.tezos_readonly_prevalidation_api_pool,
+            &reloaded.tezos_without_context_api_pool,
+        ] {
+            if pool_cfg.max_connections == 0 {
+                return Err(ConfigurationReloadError::InvalidConfiguration {
+                    reason: "ffi pool max connections must be greater than 0".to_string(),
+                });
+            }
+        }
+
+        let mut changes = Vec::new();
+        let result = self.apply(&mut current, reloaded, &mut changes);
+
+        for applied in &changes {
+            info!(self.log, "Configuration changed";
+                            "setting" => applied.setting.clone(),
+                            "previous_value" => applied.previous_value.clone(),
+                            "new_value" => applied.new_value.clone());
+        }
+
+        match result {
+            Ok(()) => {
+                info!(self.log, "Configuration reloaded"; "changes" => changes.len());
+                Ok(changes)
+            }
+            Err(e) => {
+                warn!(self.log, "Configuration reload failed (some changes could be already applied)"; "reason" => format!("{}", e));
+                Err(e)
+            }
+        }
+    }
+}
+
+fn reconfigure_pool(
+    pool: &TezosApiConnectionPool,
+    current: &mut TezosApiConnectionPoolConfiguration,
+    reloaded: TezosApiConnectionPoolConfiguration,
+    changes: &mut Vec<ConfigurationChange>,
+) -> Result<(), ConfigurationReloadError> {
+    if *current == reloaded {
+        return Ok(());
+    }
+
+    pool.reconfigure(reloaded.clone())
+        .map_err(|e| ConfigurationReloadError::ApplyError {
+            setting: pool.pool_name.clone(),
+            reason: format!("{}", e),
+        })?;
+    changes.push(change(&pool.pool_name, current, &reloaded));
+    *current = reloaded;
+    Ok(())
+}
+
+fn change<T: Debug>(setting: &str, previous_value: &T, new_value: &T) -> ConfigurationChange {
+    ConfigurationChange::new(
+        setting,
+        format!("{:?}", previous_value),
+        format!("{:?}", new_value),
+    )
+}
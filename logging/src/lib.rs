@@ -4,3 +4,4 @@
 
 pub mod detailed_json;
 pub mod file;
+pub mod reloadable;
@@ -0,0 +1,110 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Drain, which can be reconfigured at runtime without recreating the logger,
+//! so all already created (child) loggers see the change.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use slog::{Drain, Level, Never, OwnedKVList, Record};
+
+/// Underlying drain, which does the real logging (e.g. async terminal or file drain with selected format)
+pub type BoxedDrain = Box<dyn Drain<Ok = (), Err = Never> + Send + Sync>;
+
+/// Reference to [ReloadableDrain], which is used to change log level or log format at runtime
+pub type ReloadableDrainRef = Arc<ReloadableDrain>;
+
+/// Drain with log level filter and underlying drain, both can be changed at runtime
+pub struct ReloadableDrain {
+    level: AtomicUsize,
+    drain: RwLock<BoxedDrain>,
+}
+
+impl ReloadableDrain {
+    pub fn new(level: Level, drain: BoxedDrain) -> ReloadableDrainRef {
+        Arc::new(ReloadableDrain {
+            level: AtomicUsize::new(level.as_usize()),
+            drain: RwLock::new(drain),
+        })
+    }
+
+    pub fn level(&self) -> Level {
+        Level::from_usize(self.level.load(Ordering::Acquire)).unwrap_or(Level::Info)
+    }
+
+    pub fn set_level(&self, level: Level) {
+        self.level.store(level.as_usize(), Ordering::Release);
+    }
+
+    /// Replaces underlying drain, previous drain is dropped after replace (so async drains are flushed)
+    pub fn set_drain(&self, drain: BoxedDrain) {
+        let previous = match self.drain.write() {
+            Ok(mut current) => std::mem::replace(&mut *current, drain),
+            Err(poisoned) => std::mem::replace(&mut *poisoned.into_inner(), drain),
+        };
+        drop(previous);
+    }
+}
+
+impl Drain for ReloadableDrain {
+    type Ok = ();
+    type Err = Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        if record.level().is_at_least(self.level()) {
+            if let Ok(drain) = self.drain.read() {
+                drain.log(record, values)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_enabled(&self, level: Level) -> bool {
+        level.is_at_least(self.level())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slog::{debug, info, o, Logger};
+
+    use super::*;
+
+    struct CountingDrain(Arc<AtomicUsize>);
+
+    impl Drain for CountingDrain {
+        type Ok = ();
+        type Err = Never;
+
+        fn log(&self, _: &Record, _: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reload_level_and_drain() {
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+
+        let drain = ReloadableDrain::new(Level::Info, Box::new(CountingDrain(first.clone())));
+        let log = Logger::root(drain.clone(), o!());
+
+        info!(log, "counted");
+        debug!(log, "filtered");
+        assert_eq!(1, first.load(Ordering::SeqCst));
+
+        // change level
+        drain.set_level(Level::Debug);
+        assert_eq!(Level::Debug, drain.level());
+        debug!(log, "counted");
+        assert_eq!(2, first.load(Ordering::SeqCst));
+
+        // change drain
+        drain.set_drain(Box::new(CountingDrain(second.clone())));
+        info!(log, "counted by second");
+        assert_eq!(2, first.load(Ordering::SeqCst));
+        assert_eq!(1, second.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,697 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![forbid(unsafe_code)]
+
+//! Deterministic pure-Rust implementation of [ProtocolApi], which does not need OCaml runtime.
+//!
+//! Mock does not run any Tezos protocol, every applied block just writes a few keys (level, hashes, scripted values)
+//! to in-memory [MerkleStorage] on top of predecessor's context and commits it with block's timestamp,
+//! so the context hashes are deterministic and all context actions are sent to the node through the event channel,
+//! exactly like the OCaml protocol runner does.
+//!
+//! Behaviour for selected blocks (errors, delays, additional context values, response fields) can be scripted - see [MockScript].
+
+use std::convert::{TryFrom, TryInto};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::Fail;
+use lazy_static::lazy_static;
+
+use crypto::hash::{BlockHash, ChainId, ContextHash, OperationHash, ProtocolHash};
+use storage::backend::InMemoryBackend;
+use storage::merkle_storage::{ContextKey, ContextValue, EntryHash, MerkleError, MerkleStorage};
+use tezos_api::ffi::*;
+use tezos_context::channel::{context_send, ContextAction, ContextActionMessage};
+use tezos_messages::p2p::binary_message::MessageHash;
+use tezos_messages::p2p::encoding::prelude::BlockHeader;
+use tezos_wrapper::protocol::ProtocolApi;
+
+pub use crate::script::{MockScript, MockScriptError, ScriptedApplyResult, ScriptedBlock};
+
+mod script;
+
+/// Author used for commits of applied blocks
+const MOCK_COMMIT_AUTHOR: &str = "mock";
+
+lazy_static! {
+    static ref SCRIPT: RwLock<MockScript> = RwLock::new(MockScript::default());
+    static ref CONTEXT: Mutex<Option<MockContext>> = Mutex::new(None);
+}
+
+/// Replaces script used by [MockProtocolApi] (by default nothing is scripted)
+pub fn set_script(script: MockScript) {
+    match SCRIPT.write() {
+        Ok(mut current) => *current = script,
+        Err(poisoned) => *poisoned.into_inner() = script,
+    }
+}
+
+fn script() -> RwLockReadGuard<'static, MockScript> {
+    match SCRIPT.read() {
+        Ok(script) => script,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+#[derive(Debug, Fail)]
+enum MockContextError {
+    #[fail(display = "Context is not initialized, call init_protocol_context at first")]
+    NotInitialized,
+    #[fail(display = "Merkle storage error: {}", error)]
+    MerkleStorageError { error: MerkleError },
+    #[fail(display = "Failed to send context action: {}", reason)]
+    SendError { reason: String },
+    #[fail(display = "Invalid data: {}", reason)]
+    InvalidData { reason: String },
+}
+
+impl From<MerkleError> for MockContextError {
+    fn from(error: MerkleError) -> Self {
+        MockContextError::MerkleStorageError { error }
+    }
+}
+
+/// Context of the mock protocol, every runner process has its own in-memory storage
+struct MockContext {
+    merkle: MerkleStorage,
+    genesis: GenesisChain,
+}
+
+impl MockContext {
+    /// Genesis context is always computed (so also readonly runners or restarted runner know it),
+    /// but actions are sent to the node only if `emit` (commit_genesis) is set
+    fn init(
+        genesis: GenesisChain,
+        patch_context: Option<PatchContext>,
+        emit: bool,
+    ) -> Result<(MockContext, ContextHash), MockContextError> {
+        let genesis_hash = BlockHash::from_base58_check(&genesis.block).map_err(|e| {
+            MockContextError::InvalidData {
+                reason: format!(
+                    "invalid genesis block hash: {}, reason: {}",
+                    &genesis.block, e
+                ),
+            }
+        })?;
+        let genesis_protocol = ProtocolHash::from_base58_check(&genesis.protocol).map_err(|e| {
+            MockContextError::InvalidData {
+                reason: format!(
+                    "invalid genesis protocol: {}, reason: {}",
+                    &genesis.protocol, e
+                ),
+            }
+        })?;
+        let date = chrono::DateTime::parse_from_rfc3339(&genesis.time)
+            .map_err(|e| MockContextError::InvalidData {
+                reason: format!("invalid genesis time: {}, reason: {}", &genesis.time, e),
+            })?
+            .timestamp();
+
+        let mut context = MockContext {
+            merkle: MerkleStorage::new(Arc::new(InMemoryBackend::new())),
+            genesis,
+        };
+        let block_hash = genesis_hash.as_ref().clone();
+
+        let mut values = vec![
+            (
+                vec!["protocol".to_string()],
+                genesis_protocol.as_ref().clone(),
+            ),
+            (mock_key("level"), b"0".to_vec()),
+            (mock_key("block_hash"), block_hash.clone()),
+        ];
+        if let Some(PatchContext { key, json }) = patch_context {
+            values.push((split_key(&key), json.into_bytes()));
+        }
+        for (key, value) in values {
+            context.set(None, &block_hash, key, value, emit)?;
+        }
+
+        let context_hash = context.commit(
+            None,
+            &block_hash,
+            date,
+            "Tezos".to_string(),
+            "Genesis".to_string(),
+            emit,
+        )?;
+        Ok((context, context_hash))
+    }
+
+    fn contains(&self, context_hash: &ContextHash) -> Result<bool, MockContextError> {
+        self.merkle
+            .contains_commit(&entry_hash(context_hash)?)
+            .map_err(MockContextError::from)
+    }
+
+    /// Returns false, if context_hash is not known
+    fn checkout(&mut self, context_hash: &ContextHash) -> Result<bool, MockContextError> {
+        let entry_hash = entry_hash(context_hash)?;
+        if !self.merkle.contains_commit(&entry_hash)? {
+            return Ok(false);
+        }
+
+        let start_time = now();
+        self.merkle.checkout(&entry_hash)?;
+        send(ContextAction::Checkout {
+            context_hash: context_hash.as_ref().clone(),
+            start_time,
+            end_time: now(),
+        })?;
+        Ok(true)
+    }
+
+    fn set(
+        &mut self,
+        context_hash: Option<&ContextHash>,
+        block_hash: &[u8],
+        key: ContextKey,
+        value: ContextValue,
+        emit: bool,
+    ) -> Result<(), MockContextError> {
+        let start_time = now();
+        self.merkle.set(&key, &value)?;
+        if emit {
+            send(ContextAction::Set {
+                context_hash: context_hash.map(|h| h.as_ref().clone()),
+                block_hash: Some(block_hash.to_vec()),
+                operation_hash: None,
+                tree_hash: None,
+                new_tree_hash: None,
+                start_time,
+                end_time: now(),
+                key,
+                value,
+                value_as_json: None,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn commit(
+        &mut self,
+        parent_context_hash: Option<&ContextHash>,
+        block_hash: &[u8],
+        date: i64,
+        author: String,
+        message: String,
+        emit: bool,
+    ) -> Result<ContextHash, MockContextError> {
+        let start_time = now();
+        let time = u64::try_from(date).map_err(|_| MockContextError::InvalidData {
+            reason: format!("invalid commit date: {}", date),
+        })?;
+        let new_context_hash = self.merkle.commit(time, author.clone(), message.clone())?;
+        let new_context_hash = ContextHash::try_from(&new_context_hash[..]).map_err(|e| {
+            MockContextError::InvalidData {
+                reason: format!("invalid commit hash, reason: {}", e),
+            }
+        })?;
+        if emit {
+            send(ContextAction::Commit {
+                parent_context_hash: parent_context_hash.map(|h| h.as_ref().clone()),
+                block_hash: Some(block_hash.to_vec()),
+                new_context_hash: new_context_hash.as_ref().clone(),
+                tree_hash: None,
+                start_time,
+                end_time: now(),
+                author,
+                message,
+                date,
+                parents: parent_context_hash
+                    .map(|h| vec![h.as_ref().clone()])
+                    .unwrap_or_default(),
+            })?;
+        }
+        Ok(new_context_hash)
+    }
+}
+
+fn entry_hash(context_hash: &ContextHash) -> Result<EntryHash, MockContextError> {
+    context_hash
+        .as_ref()
+        .as_slice()
+        .try_into()
+        .map_err(|_| MockContextError::InvalidData {
+            reason: format!("invalid context hash: {}", context_hash.to_base58_check()),
+        })
+}
+
+fn send(action: ContextAction) -> Result<(), MockContextError> {
+    context_send(ContextActionMessage {
+        action,
+        record: true,
+        perform: true,
+    })
+    .map_err(|e| MockContextError::SendError {
+        reason: format!("{}", e),
+    })
+}
+
+fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0_f64)
+}
+
+fn mock_key(name: &str) -> ContextKey {
+    vec!["mock".to_string(), name.to_string()]
+}
+
+fn split_key(key: &str) -> ContextKey {
+    key.split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_string())
+        .collect()
+}
+
+fn header_hash(header: &BlockHeader) -> Result<BlockHash, String> {
+    header
+        .message_hash()
+        .map_err(|e| format!("{}", e))
+        .and_then(|hash| BlockHash::try_from(hash).map_err(|e| format!("{}", e)))
+}
+
+fn with_context<T, F>(f: F) -> Result<T, MockContextError>
+where
+    F: FnOnce(&mut MockContext) -> Result<T, MockContextError>,
+{
+    let mut guard = match CONTEXT.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    match guard.as_mut() {
+        Some(context) => f(context),
+        None => Err(MockContextError::NotInitialized),
+    }
+}
+
+fn script_protocol(genesis_protocol: Option<&str>) -> Option<ProtocolHash> {
+    script()
+        .protocol
+        .as_deref()
+        .or(genesis_protocol)
+        .and_then(|protocol| ProtocolHash::from_base58_check(protocol).ok())
+}
+
+/// Pure-Rust protocol api, see module documentation
+pub struct MockProtocolApi;
+
+impl ProtocolApi for MockProtocolApi {
+    fn apply_block(request: ApplyBlockRequest) -> Result<ApplyBlockResponse, ApplyBlockError> {
+        let header = &request.block_header;
+        let block_hash = header_hash(header)
+            .map_err(|message| ApplyBlockError::InvalidRequestResponseData { message })?;
+        let predecessor_hash = header_hash(&request.pred_header)
+            .map_err(|message| ApplyBlockError::InvalidRequestResponseData { message })?;
+        if header.predecessor() != &predecessor_hash {
+            return Err(ApplyBlockError::PredecessorMismatch {
+                message: format!(
+                    "block: {} has predecessor: {}, but pred_header is: {}",
+                    block_hash.to_base58_check(),
+                    header.predecessor().to_base58_check(),
+                    predecessor_hash.to_base58_check()
+                ),
+            });
+        }
+        if request.operations.len() != header.validation_pass() as usize {
+            return Err(ApplyBlockError::IncompleteOperations {
+                expected: header.validation_pass() as usize,
+                actual: request.operations.len(),
+            });
+        }
+
+        let script = script();
+        let scripted = script.find_block(header.level(), &block_hash);
+        if let Some(scripted) = scripted {
+            if scripted.delay_millis > 0 {
+                thread::sleep(Duration::from_millis(scripted.delay_millis));
+            }
+            if let Some(error) = scripted.error() {
+                return Err(error);
+            }
+        }
+        let scripted_result = scripted.map(|scripted| &scripted.result);
+
+        let predecessor_context = request.pred_header.context();
+        let context_hash = with_context(|context| {
+            if !context.checkout(predecessor_context)? {
+                return Ok(None);
+            }
+
+            let operations_count: usize = request.operations.iter().map(|ops| ops.len()).sum();
+            let mut values = vec![
+                (mock_key("level"), header.level().to_string().into_bytes()),
+                (mock_key("block_hash"), block_hash.as_ref().clone()),
+                (mock_key("predecessor"), predecessor_hash.as_ref().clone()),
+                (
+                    mock_key("operations_count"),
+                    operations_count.to_string().into_bytes(),
+                ),
+            ];
+            if let Some(scripted_result) = scripted_result {
+                for (key, value) in &scripted_result.context {
+                    values.push((split_key(key), value.as_bytes().to_vec()));
+                }
+            }
+            for (key, value) in values {
+                context.set(
+                    Some(predecessor_context),
+                    block_hash.as_ref(),
+                    key,
+                    value,
+                    true,
+                )?;
+            }
+
+            context
+                .commit(
+                    Some(predecessor_context),
+                    block_hash.as_ref(),
+                    header.timestamp(),
+                    MOCK_COMMIT_AUTHOR.to_string(),
+                    format!("lvl {}", header.level()),
+                    true,
+                )
+                .map(Some)
+        })
+        .map_err(|e| ApplyBlockError::FailedToApplyBlock {
+            message: format!("{}", e),
+        })?
+        .ok_or_else(|| ApplyBlockError::UnknownPredecessorContext {
+            message: predecessor_context.to_base58_check(),
+        })?;
+
+        let operations_proto_metadata_json = format!(
+            "[{}]",
+            request
+                .operations
+                .iter()
+                .map(|ops| format!("[{}]", vec!["{}"; ops.len()].join(",")))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        let result = scripted_result.cloned().unwrap_or_default();
+
+        Ok(ApplyBlockResponse {
+            validation_result_message: result
+                .validation_result_message
+                .unwrap_or_else(|| format!("lvl {}, mock", header.level())),
+            context_hash,
+            block_header_proto_json: result
+                .block_header_proto_json
+                .unwrap_or_else(|| "{}".to_string()),
+            block_header_proto_metadata_json: result
+                .block_header_proto_metadata_json
+                .unwrap_or_else(|| "{}".to_string()),
+            operations_proto_metadata_json: result
+                .operations_proto_metadata_json
+                .unwrap_or(operations_proto_metadata_json),
+            max_operations_ttl: result
+                .max_operations_ttl
+                .unwrap_or(request.max_operations_ttl),
+            last_allowed_fork_level: result.last_allowed_fork_level.unwrap_or(0),
+            forking_testchain: false,
+            forking_testchain_data: None,
+            block_metadata_hash: None,
+            ops_metadata_hashes: None,
+            ops_metadata_hash: None,
+        })
+    }
+
+    fn begin_application(
+        request: BeginApplicationRequest,
+    ) -> Result<BeginApplicationResponse, BeginApplicationError> {
+        let predecessor_context = request.pred_header.context();
+        let known = with_context(|context| context.contains(predecessor_context)).map_err(|e| {
+            BeginApplicationError::FailedToBeginApplication {
+                message: format!("{}", e),
+            }
+        })?;
+        if !known {
+            return Err(BeginApplicationError::UnknownPredecessorContext {
+                message: predecessor_context.to_base58_check(),
+            });
+        }
+        Ok(BeginApplicationResponse {
+            result: format!("lvl {}, mock", request.block_header.level()),
+        })
+    }
+
+    fn begin_construction(
+        request: BeginConstructionRequest,
+    ) -> Result<PrevalidatorWrapper, BeginConstructionError> {
+        let genesis_protocol = with_context(|context| Ok(context.genesis.protocol.clone())).ok();
+        let protocol = script_protocol(genesis_protocol.as_deref()).ok_or_else(|| {
+            BeginConstructionError::FailedToBeginConstruction {
+                message: "no protocol is known, init context at first".to_string(),
+            }
+        })?;
+        Ok(PrevalidatorWrapper {
+            chain_id: request.chain_id,
+            protocol,
+            context_fitness: Some(request.predecessor.fitness().clone()),
+        })
+    }
+
+    fn validate_operation(
+        request: ValidateOperationRequest,
+    ) -> Result<ValidateOperationResponse, ValidateOperationError> {
+        let hash = request
+            .operation
+            .message_hash()
+            .map_err(|e| format!("{}", e))
+            .and_then(|hash| OperationHash::try_from(hash).map_err(|e| format!("{}", e)))
+            .map_err(|message| ValidateOperationError::InvalidRequestResponseData { message })?;
+
+        Ok(ValidateOperationResponse {
+            prevalidator: request.prevalidator,
+            result: ValidateOperationResult {
+                applied: vec![Applied {
+                    hash,
+                    protocol_data_json: "{}".to_string(),
+                }],
+                refused: vec![],
+                branch_refused: vec![],
+                branch_delayed: vec![],
+            },
+        })
+    }
+
+    fn call_protocol_rpc(
+        request: ProtocolRpcRequest,
+    ) -> Result<ProtocolRpcResponse, ProtocolRpcError> {
+        let script = script();
+        match script.rpc.get(&request.request.context_path) {
+            Some(response) => Ok(ProtocolRpcResponse::RPCOk(response.to_string())),
+            None => Err(ProtocolRpcError::RPCErrorServiceNotFound),
+        }
+    }
+
+    fn helpers_preapply_operations(
+        _request: ProtocolRpcRequest,
+    ) -> Result<HelpersPreapplyResponse, HelpersPreapplyError> {
+        Err(HelpersPreapplyError::FailedToCallProtocolRpc {
+            message: "helpers_preapply_operations is not supported by mock protocol runner"
+                .to_string(),
+        })
+    }
+
+    fn helpers_preapply_block(
+        _request: HelpersPreapplyBlockRequest,
+    ) -> Result<HelpersPreapplyResponse, HelpersPreapplyError> {
+        Err(HelpersPreapplyError::FailedToCallProtocolRpc {
+            message: "helpers_preapply_block is not supported by mock protocol runner".to_string(),
+        })
+    }
+
+    fn change_runtime_configuration(
+        _settings: TezosRuntimeConfiguration,
+    ) -> Result<(), TezosRuntimeConfigurationError> {
+        Ok(())
+    }
+
+    fn init_protocol_context(
+        _storage_data_dir: String,
+        genesis: GenesisChain,
+        _protocol_overrides: ProtocolOverrides,
+        commit_genesis: bool,
+        _enable_testchain: bool,
+        _readonly: bool,
+        patch_context: Option<PatchContext>,
+    ) -> Result<InitProtocolContextResult, TezosStorageInitError> {
+        let genesis_protocol = ProtocolHash::from_base58_check(&genesis.protocol).map_err(|e| {
+            TezosStorageInitError::InitializeError {
+                message: format!(
+                    "invalid genesis protocol: {}, reason: {}",
+                    &genesis.protocol, e
+                ),
+            }
+        })?;
+        let mut supported_protocol_hashes = vec![genesis_protocol];
+        {
+            let script = script();
+            for protocol in script
+                .protocol
+                .iter()
+                .chain(script.supported_protocols.iter())
+            {
+                // script is already validated
+                if let Ok(protocol) = ProtocolHash::from_base58_check(protocol) {
+                    if !supported_protocol_hashes.contains(&protocol) {
+                        supported_protocol_hashes.push(protocol);
+                    }
+                }
+            }
+        }
+
+        let (context, genesis_context_hash) =
+            MockContext::init(genesis, patch_context, commit_genesis).map_err(|e| {
+                TezosStorageInitError::InitializeError {
+                    message: format!("{}", e),
+                }
+            })?;
+        match CONTEXT.lock() {
+            Ok(mut current) => *current = Some(context),
+            Err(poisoned) => *poisoned.into_inner() = Some(context),
+        }
+
+        Ok(InitProtocolContextResult {
+            supported_protocol_hashes,
+            genesis_commit_hash: if commit_genesis {
+                Some(genesis_context_hash)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn genesis_result_data(
+        _genesis_context_hash: &ContextHash,
+        _chain_id: &ChainId,
+        _genesis_protocol_hash: &ProtocolHash,
+        _genesis_max_operations_ttl: u16,
+    ) -> Result<CommitGenesisResult, GetDataError> {
+        Ok(CommitGenesisResult {
+            block_header_proto_json: "{}".to_string(),
+            block_header_proto_metadata_json: "{}".to_string(),
+            operations_proto_metadata_json: "[]".to_string(),
+        })
+    }
+
+    fn compute_path(_request: ComputePathRequest) -> Result<ComputePathResponse, ComputePathError> {
+        Err(ComputePathError::PathError {
+            message: "compute_path is not supported by mock protocol runner".to_string(),
+        })
+    }
+
+    fn assert_encoding_for_protocol_data(
+        _protocol_hash: ProtocolHash,
+        _protocol_data: Vec<u8>,
+    ) -> Result<(), ProtocolDataError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tezos_api::environment::{get_empty_operation_list_list_hash, TezosEnvironment, TEZOS_ENV};
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use super::*;
+
+    fn block_header(
+        level: i32,
+        predecessor: &BlockHeader,
+        context: ContextHash,
+    ) -> Result<BlockHeader, failure::Error> {
+        Ok(BlockHeaderBuilder::default()
+            .level(level)
+            .proto(1)
+            .predecessor(header_hash(predecessor).map_err(failure::err_msg)?)
+            .timestamp(predecessor.timestamp() + 60)
+            .validation_pass(0)
+            .operations_hash(get_empty_operation_list_list_hash()?)
+            .fitness(vec![])
+            .context(context)
+            .protocol_data(vec![])
+            .build()
+            .map_err(failure::err_msg)?)
+    }
+
+    fn apply_request(
+        chain_id: &ChainId,
+        block_header: &BlockHeader,
+        pred_header: &BlockHeader,
+    ) -> ApplyBlockRequest {
+        ApplyBlockRequest {
+            chain_id: chain_id.clone(),
+            block_header: block_header.clone(),
+            pred_header: pred_header.clone(),
+            max_operations_ttl: 0,
+            operations: vec![],
+            predecessor_block_metadata_hash: None,
+            predecessor_ops_metadata_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_blocks_with_script() -> Result<(), failure::Error> {
+        let tezos_env = TEZOS_ENV
+            .get(&TezosEnvironment::Carthagenet)
+            .expect("no environment configuration");
+        let chain_id = tezos_env.main_chain_id()?;
+        set_script(MockScript::from_json(
+            r#"{ "blocks": [ { "level": 3, "error": { "FailedToApplyBlock": { "message": "boom" } } } ] }"#,
+        )?);
+
+        let init_result = MockProtocolApi::init_protocol_context(
+            "".to_string(),
+            tezos_env.genesis.clone(),
+            tezos_env.protocol_overrides.clone(),
+            true,
+            false,
+            false,
+            None,
+        )?;
+        let genesis_context = init_result
+            .genesis_commit_hash
+            .expect("genesis should be committed");
+        let genesis = tezos_env.genesis_header(
+            genesis_context.clone(),
+            get_empty_operation_list_list_hash()?,
+        )?;
+
+        // apply is deterministic
+        let block_1 = block_header(1, &genesis, genesis_context.clone())?;
+        let result_1 = MockProtocolApi::apply_block(apply_request(&chain_id, &block_1, &genesis))?;
+        let result_1_again =
+            MockProtocolApi::apply_block(apply_request(&chain_id, &block_1, &genesis))?;
+        assert_eq!(result_1.context_hash, result_1_again.context_hash);
+        assert_ne!(genesis_context, result_1.context_hash);
+
+        // predecessor's context must be known
+        let block_1 = block_header(1, &genesis, result_1.context_hash)?;
+        let block_2 = block_header(2, &block_1, genesis_context.clone())?;
+        assert!(MockProtocolApi::apply_block(apply_request(&chain_id, &block_2, &block_1)).is_ok());
+        let unknown = block_header(1, &genesis, ContextHash::try_from(vec![0; 32])?)?;
+        let block_2 = block_header(2, &unknown, genesis_context.clone())?;
+        assert!(matches!(
+            MockProtocolApi::apply_block(apply_request(&chain_id, &block_2, &unknown)),
+            Err(ApplyBlockError::UnknownPredecessorContext { .. })
+        ));
+
+        // scripted error
+        let block_3 = block_header(3, &block_1, genesis_context)?;
+        assert!(matches!(
+            MockProtocolApi::apply_block(apply_request(&chain_id, &block_3, &block_1)),
+            Err(ApplyBlockError::FailedToApplyBlock { .. })
+        ));
+
+        Ok(())
+    }
+}
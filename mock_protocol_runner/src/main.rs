@@ -0,0 +1,165 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![forbid(unsafe_code)]
+
+//! Mock protocol runner - drop-in replacement of protocol_runner (same arguments and IPC),
+//! which uses [MockProtocolApi] instead of OCaml Tezos protocol, e.g. for integration tests on machines without OCaml.
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg};
+use slog::*;
+
+use mock_protocol_runner::{set_script, MockProtocolApi, MockScript};
+use tezos_context::channel;
+
+fn create_logger(log_level: Level, endpoint_name: String) -> Logger {
+    let drain = slog_async::Async::new(
+        slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
+            .build()
+            .fuse(),
+    )
+    .build()
+    .filter_level(log_level)
+    .fuse();
+
+    Logger::root(drain, slog::o!("endpoint" => endpoint_name))
+}
+
+fn main() {
+    let matches = App::new("Mock Protocol Runner")
+        .version("1.0")
+        .author("Tomas Sedlak <tomas.sedlak@simplestaking.com>")
+        .about("Tezos Protocol Runner without OCaml protocol")
+        .arg(
+            Arg::with_name("sock-cmd")
+                .short("c")
+                .long("sock-cmd")
+                .value_name("path")
+                .help("Path to a command socket")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("sock-evt")
+                .short("e")
+                .long("sock-evt")
+                .value_name("path")
+                .help("Path to an event socket (not required)")
+                .takes_value(true)
+                .empty_values(false)
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("endpoint")
+                .long("endpoint")
+                .value_name("STRING")
+                .help("Name of the endpoint, which spawned runner")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["critical", "error", "warn", "info", "debug", "trace"])
+                .help("Set log level"),
+        )
+        .arg(
+            Arg::with_name("script")
+                .long("script")
+                .takes_value(true)
+                .value_name("PATH")
+                .env("MOCK_PROTOCOL_RUNNER_SCRIPT")
+                .help("Path to a json file with scripted apply results/errors (node passes only fixed arguments to runners, so use env variable there)"),
+        )
+        .get_matches();
+
+    let cmd_socket_path = matches
+        .value_of("sock-cmd")
+        .expect("Missing sock-cmd value");
+    let evt_socket_path = matches.value_of("sock-evt");
+    let endpoint_name = matches
+        .value_of("endpoint")
+        .expect("Missing endpoint value")
+        .to_string();
+    let log_level = matches
+        .value_of("log-level")
+        .unwrap_or("info")
+        .parse::<slog::Level>()
+        .expect("Was expecting one value from slog::Level");
+
+    let log = create_logger(log_level, endpoint_name);
+
+    if let Some(script_path) = matches.value_of("script") {
+        match MockScript::load(Path::new(script_path)) {
+            Ok(script) => {
+                info!(log, "Mock protocol runner script loaded"; "script" => script_path, "scripted_blocks" => script.blocks.len());
+                set_script(script);
+            }
+            Err(e) => {
+                crit!(log, "Failed to load mock protocol runner script"; "script" => script_path, "reason" => format!("{}", e));
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let shutdown_callback = |log: &Logger| {
+        debug!(log, "Shutting down mock protocol runner");
+    };
+
+    {
+        let log = log.clone();
+        ctrlc::set_handler(move || {
+            shutdown_callback(&log);
+            warn!(log, "Mock protocol runner was terminated/killed/ctrl-c");
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
+
+    // Spawn a new event processing thread (if condigured evt_socket_path).
+    // Context actions are generated by MockProtocolApi and are pushed into a shared channel from which runner
+    // is reading them and then sends them to the Rust node via IPC channel.
+    let event_thread = match evt_socket_path {
+        Some(evt_socket_path) => Some({
+            let evt_socket_path = evt_socket_path.to_string();
+            let log = log.clone();
+            channel::enable_context_channel();
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    match tezos_wrapper::service::process_protocol_events(&evt_socket_path) {
+                        Ok(()) => break,
+                        Err(err) => {
+                            warn!(log, "Error while processing protocol events";  "reason" => format!("{:?}", err));
+                            thread::sleep(Duration::from_secs(1));
+                        }
+                    }
+                }
+            })
+        }),
+        None => None,
+    };
+
+    // Process commands from from the Rust node
+    if let Err(err) = tezos_wrapper::service::process_protocol_commands::<MockProtocolApi, _, _>(
+        cmd_socket_path,
+        &log,
+        shutdown_callback,
+    ) {
+        error!(log, "Error while processing protocol commands"; "reason" => format!("{:?}", err));
+        shutdown_callback(&log);
+    }
+
+    if let Some(event_thread) = event_thread {
+        if let Err(e) = event_thread.join() {
+            error!(log, "Failed to join event thread"; "reason" => format!("{:?}", e));
+        }
+    }
+
+    info!(log, "Mock protocol runner finished gracefully");
+}
@@ -0,0 +1,237 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Script describing, how the mock protocol runner should behave for selected blocks.
+//!
+//! Example:
+//! ```json
+//! {
+//!     "protocol": "PsddFKi32cMJ2qPjf43Qv5GDWLDPZb3T3bF6fLKiF5HtvHNU7aP",
+//!     "blocks": [
+//!         { "level": 3, "delay_millis": 500, "result": { "context": { "data/votes": "yay" } } },
+//!         { "level": 5, "error": { "FailedToApplyBlock": { "message": "scripted failure" } } }
+//!     ],
+//!     "rpc": { "/chains/main/blocks/head/context/constants": { "preserved_cycles": 3 } }
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use failure::Fail;
+use serde::Deserialize;
+
+use crypto::hash::{BlockHash, ProtocolHash};
+use tezos_api::ffi::ApplyBlockError;
+
+#[derive(Debug, Fail)]
+pub enum MockScriptError {
+    #[fail(display = "Failed to read mock script, reason: {}", reason)]
+    IOError { reason: io::Error },
+    #[fail(display = "Invalid mock script, reason: {}", reason)]
+    InvalidScript { reason: String },
+}
+
+impl From<io::Error> for MockScriptError {
+    fn from(reason: io::Error) -> Self {
+        MockScriptError::IOError { reason }
+    }
+}
+
+/// Scripted behaviour of the mock protocol runner
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MockScript {
+    /// Protocol reported by begin_construction (genesis protocol is used, if not set)
+    #[serde(default)]
+    pub protocol: Option<String>,
+    /// Additional protocols reported as supported by init_protocol_context
+    #[serde(default)]
+    pub supported_protocols: Vec<String>,
+    /// Scripted apply results/errors, the first matching entry is used
+    #[serde(default)]
+    pub blocks: Vec<ScriptedBlock>,
+    /// Scripted responses for protocol rpc (context_path -> json)
+    #[serde(default)]
+    pub rpc: HashMap<String, serde_json::Value>,
+}
+
+/// Scripted apply for blocks selected by level and/or block hash
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedBlock {
+    #[serde(default)]
+    pub level: Option<i32>,
+    #[serde(default)]
+    pub block_hash: Option<String>,
+    /// Simulates slow protocol, apply waits before returning result/error
+    #[serde(default)]
+    pub delay_millis: u64,
+    /// If set, apply fails with this error and nothing is written to the context
+    #[serde(default)]
+    pub error: Option<ApplyBlockError>,
+    #[serde(default)]
+    pub result: ScriptedApplyResult,
+}
+
+/// Overrides for the default (generated) apply block response
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptedApplyResult {
+    pub validation_result_message: Option<String>,
+    pub max_operations_ttl: Option<i32>,
+    pub last_allowed_fork_level: Option<i32>,
+    pub block_header_proto_json: Option<String>,
+    pub block_header_proto_metadata_json: Option<String>,
+    pub operations_proto_metadata_json: Option<String>,
+    /// Additional context key-values written by block (key is '/' separated path)
+    #[serde(default)]
+    pub context: BTreeMap<String, String>,
+}
+
+impl MockScript {
+    /// Loads and validates script from json file
+    pub fn load(path: &Path) -> Result<MockScript, MockScriptError> {
+        let content = fs::read_to_string(path)?;
+        Self::from_json(&content)
+    }
+
+    pub fn from_json(content: &str) -> Result<MockScript, MockScriptError> {
+        let script: MockScript =
+            serde_json::from_str(content).map_err(|e| MockScriptError::InvalidScript {
+                reason: format!("{}", e),
+            })?;
+        script.validate()?;
+        Ok(script)
+    }
+
+    fn validate(&self) -> Result<(), MockScriptError> {
+        for protocol in self.protocol.iter().chain(self.supported_protocols.iter()) {
+            ProtocolHash::from_base58_check(protocol).map_err(|e| {
+                MockScriptError::InvalidScript {
+                    reason: format!("invalid protocol hash: {}, reason: {}", protocol, e),
+                }
+            })?;
+        }
+        for (idx, block) in self.blocks.iter().enumerate() {
+            match (&block.level, &block.block_hash) {
+                (None, None) => {
+                    return Err(MockScriptError::InvalidScript {
+                        reason: format!("block[{}] requires `level` or `block_hash`", idx),
+                    })
+                }
+                (_, Some(block_hash)) => {
+                    BlockHash::from_base58_check(block_hash).map_err(|e| {
+                        MockScriptError::InvalidScript {
+                            reason: format!(
+                                "block[{}] has invalid block_hash: {}, reason: {}",
+                                idx, block_hash, e
+                            ),
+                        }
+                    })?;
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the first scripted block matching level and block hash
+    pub fn find_block(&self, level: i32, block_hash: &BlockHash) -> Option<&ScriptedBlock> {
+        let block_hash = block_hash.to_base58_check();
+        self.blocks.iter().find(|block| {
+            block.level.map_or(true, |l| l == level)
+                && block.block_hash.as_ref().map_or(true, |h| h == &block_hash)
+        })
+    }
+}
+
+impl ScriptedBlock {
+    /// ApplyBlockError is not Clone, so we need to construct a new one for every apply
+    pub fn error(&self) -> Option<ApplyBlockError> {
+        self.error.as_ref().map(|error| match error {
+            ApplyBlockError::IncompleteOperations { expected, actual } => {
+                ApplyBlockError::IncompleteOperations {
+                    expected: *expected,
+                    actual: *actual,
+                }
+            }
+            ApplyBlockError::FailedToApplyBlock { message } => {
+                ApplyBlockError::FailedToApplyBlock {
+                    message: message.clone(),
+                }
+            }
+            ApplyBlockError::UnknownPredecessorContext { message } => {
+                ApplyBlockError::UnknownPredecessorContext {
+                    message: message.clone(),
+                }
+            }
+            ApplyBlockError::PredecessorMismatch { message } => {
+                ApplyBlockError::PredecessorMismatch {
+                    message: message.clone(),
+                }
+            }
+            ApplyBlockError::InvalidRequestResponseData { message } => {
+                ApplyBlockError::InvalidRequestResponseData {
+                    message: message.clone(),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_block() -> Result<(), failure::Error> {
+        let script = MockScript::from_json(
+            r#"{
+                "blocks": [
+                    { "level": 2, "error": { "FailedToApplyBlock": { "message": "boom" } } },
+                    { "block_hash": "BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7", "delay_millis": 10 },
+                    { "level": 3, "result": { "context": { "data/votes": "yay" } } }
+                ]
+            }"#,
+        )?;
+
+        let genesis =
+            BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7")?;
+        let other =
+            BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe")?;
+
+        assert!(matches!(
+            script.find_block(2, &other).and_then(|b| b.error()),
+            Some(ApplyBlockError::FailedToApplyBlock { .. })
+        ));
+        assert_eq!(
+            10,
+            script
+                .find_block(7, &genesis)
+                .map(|b| b.delay_millis)
+                .unwrap()
+        );
+        assert_eq!(
+            1,
+            script.find_block(3, &other).unwrap().result.context.len()
+        );
+        assert!(script.find_block(4, &other).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_script() {
+        assert!(matches!(
+            MockScript::from_json(r#"{ "blocks": [ { "delay_millis": 10 } ] }"#),
+            Err(MockScriptError::InvalidScript { .. })
+        ));
+        assert!(matches!(
+            MockScript::from_json(r#"{ "protocol": "xyz" }"#),
+            Err(MockScriptError::InvalidScript { .. })
+        ));
+    }
+}
@@ -8,12 +8,13 @@ use riker::{actor::*, actors::SystemMsg, system::SystemEvent, system::Timer};
 use slog::{warn, Logger};
 
 use networking::p2p::network_channel::{NetworkChannelMsg, NetworkChannelRef, PeerMessageReceived};
+use networking::p2p::traffic::PeerTrafficStats;
 use shell::shell_channel::{ShellChannelMsg, ShellChannelRef};
+use shell::stats::metrics::MonitorMetricsRef;
 use shell::subscription::{
     subscribe_to_actor_terminated, subscribe_to_network_events, subscribe_to_shell_events,
     subscribe_to_shell_new_current_head,
 };
-use tezos_messages::p2p::binary_message::BinaryMessage;
 
 use crate::websocket::handler_messages::HandlerMessage;
 use crate::{
@@ -34,8 +35,12 @@ pub struct Monitor {
     network_channel: NetworkChannelRef,
     shell_channel: ShellChannelRef,
     msg_channel: ActorRef<WebsocketHandlerMsg>,
+    /// Shared snapshot of collected statistics (exported by rpc `/metrics`)
+    monitor_metrics: MonitorMetricsRef,
     // Monitors
     peer_monitors: HashMap<ActorUri, PeerMonitor>,
+    /// Summed traffic of already disconnected peers
+    disconnected_peers_traffic: PeerTrafficStats,
     bootstrap_monitor: BootstrapMonitor,
     blocks_monitor: BlocksMonitor,
     block_application_monitor: ApplicationMonitor,
@@ -52,15 +57,34 @@ impl Monitor {
         event_channel: NetworkChannelRef,
         msg_channel: ActorRef<WebsocketHandlerMsg>,
         shell_channel: ShellChannelRef,
+        monitor_metrics: MonitorMetricsRef,
     ) -> Result<MonitorRef, CreateError> {
         sys.actor_of_props::<Monitor>(
             Self::name(),
-            Props::new_args((event_channel, msg_channel, shell_channel)),
+            Props::new_args((event_channel, msg_channel, shell_channel, monitor_metrics)),
         )
     }
 
-    fn process_peer_message(&mut self, msg: PeerMessageReceived, log: &Logger) {
-        use std::mem::size_of_val;
+    /// Publishes actual totals to shared monitor metrics
+    fn publish_metrics(&self, log: &Logger) {
+        match self.monitor_metrics.write() {
+            Ok(mut metrics) => {
+                metrics.peers = self
+                    .peer_monitors
+                    .values()
+                    .map(PeerMonitor::transfer_metrics)
+                    .collect();
+                metrics.disconnected_peers_traffic = self.disconnected_peers_traffic.clone();
+                metrics.bootstrap = self.bootstrap_monitor.bootstrap_metrics();
+                metrics.bootstrap.applied_blocks = self.blocks_monitor.applied_blocks() as u64;
+            }
+            Err(e) => {
+                warn!(log, "Failed to publish monitor metrics"; "reason" => format!("{}", e))
+            }
+        }
+    }
+
+    fn process_peer_message(&mut self, msg: PeerMessageReceived) {
         use tezos_messages::p2p::encoding::peer::PeerMessage;
 
         for message in msg.message.messages() {
@@ -74,18 +98,6 @@ impl Monitor {
                 _ => (),
             }
         }
-
-        if let Some(monitor) = self.peer_monitors.get_mut(msg.peer.uri()) {
-            // TODO: TE-190 - reimplement correctly, now not all messages are counted in (Ack, Metadata, ConnectionMessage is not involved)
-            let size = if let Ok(msg) = msg.message.as_bytes() {
-                msg.len()
-            } else {
-                size_of_val(&msg.message)
-            };
-            monitor.incoming_bytes(size);
-        } else {
-            warn!(log, "Missing monitor for peer"; "peer" => msg.peer.name());
-        }
     }
 }
 
@@ -94,20 +106,24 @@ impl
         NetworkChannelRef,
         ActorRef<WebsocketHandlerMsg>,
         ShellChannelRef,
+        MonitorMetricsRef,
     )> for Monitor
 {
     fn create_args(
-        (event_channel, msg_channel, shell_channel): (
+        (event_channel, msg_channel, shell_channel, monitor_metrics): (
             NetworkChannelRef,
             ActorRef<WebsocketHandlerMsg>,
             ShellChannelRef,
+            MonitorMetricsRef,
         ),
     ) -> Self {
         Self {
             network_channel: event_channel,
             shell_channel,
             msg_channel,
+            monitor_metrics,
             peer_monitors: HashMap::new(),
+            disconnected_peers_traffic: PeerTrafficStats::default(),
             bootstrap_monitor: BootstrapMonitor::default(),
             blocks_monitor: BlocksMonitor::new(4096, 0),
             block_application_monitor: ApplicationMonitor::new(),
@@ -164,6 +180,7 @@ impl Receive<SystemEvent> for Monitor {
     fn receive(&mut self, ctx: &Context<Self::Msg>, msg: SystemEvent, _sender: Sender) {
         if let SystemEvent::ActorTerminated(evt) = msg {
             if let Some(monitor) = self.peer_monitors.remove(evt.actor.uri()) {
+                self.disconnected_peers_traffic.merge(&monitor.traffic());
                 ctx.myself.tell(
                     BroadcastSignal::PeerUpdate(PeerConnectionStatus::disconnected(
                         monitor.peer_address(),
@@ -201,6 +218,8 @@ impl Receive<BroadcastSignal> for Monitor {
                 let payload = self.chain_monitor.snapshot();
                 self.msg_channel
                     .tell(HandlerMessage::ChainStatus { payload }, ctx.myself().into());
+
+                self.publish_metrics(&ctx.system.log());
             }
             BroadcastSignal::PeerUpdate(msg) => {
                 let msg: HandlerMessage = msg.into();
@@ -219,7 +238,11 @@ impl Receive<NetworkChannelMsg> for Monitor {
                 let key = peer_id.peer_ref.uri();
                 let previous = self.peer_monitors.insert(
                     key.clone(),
-                    PeerMonitor::new(peer_id.peer_address, peer_id.peer_id_marker.clone()),
+                    PeerMonitor::new(
+                        peer_id.peer_address,
+                        peer_id.peer_id_marker.clone(),
+                        peer_id.traffic.clone(),
+                    ),
                 );
                 if let Some(previous) = previous {
                     warn!(ctx.system.log(), "Duplicate monitor found for peer"; "key" => key.to_string(), "peer_address" => previous.peer_address());
@@ -232,9 +255,7 @@ impl Receive<NetworkChannelMsg> for Monitor {
                     );
                 }
             }
-            NetworkChannelMsg::PeerMessageReceived(msg) => {
-                self.process_peer_message(msg, &ctx.system.log())
-            }
+            NetworkChannelMsg::PeerMessageReceived(msg) => self.process_peer_message(msg),
             _ => (),
         }
     }
@@ -41,6 +41,10 @@ impl BlocksMonitor {
         self.applied_blocks += 1;
     }
 
+    pub fn applied_blocks(&self) -> usize {
+        self.applied_blocks
+    }
+
     pub fn snapshot(&mut self) -> Vec<BlockMetrics> {
         use std::cmp::min;
 
@@ -3,6 +3,8 @@
 
 use std::time::Instant;
 
+use shell::stats::metrics::BootstrapMetrics;
+
 use crate::websocket::handler_messages::IncomingTransferMetrics;
 
 /// General statistics about incoming transfer
@@ -91,6 +93,16 @@ impl BootstrapMonitor {
         self.headers_per_session as f32 / self.session_start.elapsed().as_secs_f32()
     }
 
+    /// Returns totals collected from node start, `applied_blocks` are not tracked by this monitor
+    pub fn bootstrap_metrics(&self) -> BootstrapMetrics {
+        BootstrapMetrics {
+            remote_level: self.level as u64,
+            received_headers: self.downloaded_headers as u64,
+            downloaded_operations: self.downloaded_blocks as u64,
+            applied_blocks: 0,
+        }
+    }
+
     pub fn snapshot(&mut self) -> IncomingTransferMetrics {
         use std::f32;
         let snapshot_end = Instant::now();
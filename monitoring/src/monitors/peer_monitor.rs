@@ -3,59 +3,76 @@
 
 use std::{net::SocketAddr, time::Instant};
 
+use networking::p2p::traffic::{PeerTrafficRef, PeerTrafficStats};
+use shell::stats::metrics::PeerTransferMetrics;
+
 use crate::websocket::handler_messages::PeerMetrics;
 
-/// Peer specific details about transfer *FROM* peer.
+/// Peer specific details about transfer *FROM* peer (speeds) and socket level traffic in both directions.
 pub(crate) struct PeerMonitor {
     peer_address: SocketAddr,
     public_key: String,
 
-    total_transferred: usize,
-    current_transferred: usize,
+    /// Traffic counters shared with the peer connection
+    traffic: PeerTrafficRef,
+    /// Total of received bytes at the last snapshot
+    last_update_transferred: u64,
     last_update: Instant,
     first_update: Instant,
 }
 
 impl PeerMonitor {
-    pub fn new(peer_addr: SocketAddr, public_key: String) -> Self {
+    pub fn new(peer_addr: SocketAddr, public_key: String, traffic: PeerTrafficRef) -> Self {
         let now = Instant::now();
         Self {
             peer_address: peer_addr,
             public_key,
-            total_transferred: 0,
-            current_transferred: 0,
+            traffic,
+            last_update_transferred: 0,
             last_update: now,
             first_update: now,
         }
     }
 
-    pub fn avg_speed(&self) -> f32 {
-        self.total_transferred as f32 / self.first_update.elapsed().as_secs_f32()
-    }
-
-    pub fn current_speed(&self) -> f32 {
-        self.current_transferred as f32 / self.last_update.elapsed().as_secs_f32()
+    fn avg_speed(&self, total_transferred: u64) -> f32 {
+        total_transferred as f32 / self.first_update.elapsed().as_secs_f32()
     }
 
-    pub fn incoming_bytes(&mut self, incoming: usize) {
-        self.total_transferred += incoming;
-        self.current_transferred += incoming
+    fn current_speed(&self, total_transferred: u64) -> f32 {
+        total_transferred.saturating_sub(self.last_update_transferred) as f32
+            / self.last_update.elapsed().as_secs_f32()
     }
 
     pub fn snapshot(&mut self) -> PeerMetrics {
+        let traffic = self.traffic.snapshot();
+        let total_transferred = traffic.inbound.total.bytes;
         let ret = PeerMetrics::new(
             self.public_key.clone(),
             self.peer_address(),
-            self.total_transferred,
-            self.avg_speed(),
-            self.current_speed(),
+            total_transferred as usize,
+            self.avg_speed(total_transferred),
+            self.current_speed(total_transferred),
+            &traffic,
         );
 
-        self.current_transferred = 0;
+        self.last_update_transferred = total_transferred;
         self.last_update = Instant::now();
         ret
     }
 
+    /// Actual socket level traffic of the peer connection
+    pub fn traffic(&self) -> PeerTrafficStats {
+        self.traffic.snapshot()
+    }
+
+    pub fn transfer_metrics(&self) -> PeerTransferMetrics {
+        PeerTransferMetrics {
+            peer_id: self.public_key.clone(),
+            address: self.peer_address(),
+            traffic: self.traffic(),
+        }
+    }
+
     pub fn peer_address(&self) -> String {
         self.peer_address.to_string()
     }
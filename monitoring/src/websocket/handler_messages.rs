@@ -6,6 +6,8 @@ use std::iter::FromIterator;
 use serde::Serialize;
 use slog_derive::SerdeValue;
 
+use networking::p2p::traffic::PeerTrafficStats;
+
 use crate::monitors::ChainMonitor;
 use crate::monitors::PeerMonitor;
 
@@ -86,6 +88,9 @@ pub struct PeerMetrics {
     transferred_bytes: usize,
     average_transfer_speed: f32,
     current_transfer_speed: f32,
+    received_messages: u64,
+    sent_bytes: u64,
+    sent_messages: u64,
 }
 
 impl PeerMetrics {
@@ -95,6 +100,7 @@ impl PeerMetrics {
         transferred_bytes: usize,
         average_transfer_speed: f32,
         current_transfer_speed: f32,
+        traffic: &PeerTrafficStats,
     ) -> Self {
         Self {
             public_key,
@@ -102,6 +108,9 @@ impl PeerMetrics {
             transferred_bytes,
             average_transfer_speed,
             current_transfer_speed,
+            received_messages: traffic.inbound.total.messages,
+            sent_bytes: traffic.outbound.total.bytes,
+            sent_messages: traffic.outbound.total.messages,
         }
     }
 }
@@ -13,6 +13,7 @@ use tezos_messages::p2p::encoding::ack::NackMotive;
 use tezos_messages::p2p::encoding::prelude::NetworkVersion;
 
 use crate::p2p::peer::PeerRef;
+use crate::p2p::traffic::PeerTrafficRef;
 
 pub mod p2p;
 
@@ -26,6 +27,8 @@ pub struct PeerId {
     pub peer_id_marker: String,
     /// Peer address
     pub peer_address: SocketAddr,
+    /// Socket level traffic counters of the peer connection
+    pub traffic: PeerTrafficRef,
 }
 
 impl PeerId {
@@ -34,12 +37,14 @@ impl PeerId {
         peer_public_key_hash: CryptoboxPublicKeyHash,
         peer_id_marker: String,
         peer_address: SocketAddr,
+        traffic: PeerTrafficRef,
     ) -> Self {
         Self {
             peer_ref,
             peer_public_key_hash,
             peer_id_marker,
             peer_address,
+            traffic,
         }
     }
 }
@@ -29,6 +29,7 @@ use crate::{LocalPeerInfo, PeerId};
 
 use super::network_channel::{NetworkChannelRef, NetworkChannelTopic, PeerMessageReceived};
 use super::stream::{EncryptedMessageReader, EncryptedMessageWriter, MessageStream, StreamError};
+use super::traffic::{PeerTraffic, PeerTrafficRef, TrafficMessageType};
 
 const IO_TIMEOUT: Duration = Duration::from_secs(6);
 /// There is a 90-second timeout for ping peers with GetCurrentHead
@@ -199,6 +200,7 @@ pub struct Peer {
     peer_id_marker: String,
     peer_metadata: MetadataMessage,
     peer_compatible_network_version: NetworkVersion,
+    peer_traffic: PeerTrafficRef,
 }
 
 impl Peer {
@@ -232,6 +234,7 @@ impl ActorFactoryArgs<(NetworkChannelRef, Handle, BootstrapOutput)> for Peer {
             peer_id_marker: info.3,
             peer_metadata: info.4,
             peer_compatible_network_version: info.5,
+            peer_traffic: info.7,
         }
     }
 }
@@ -252,10 +255,11 @@ impl Actor for Peer {
         let peer_id_marker = self.peer_id_marker.clone();
         let peer_metadata = self.peer_metadata.clone();
         let peer_compatible_network_version = self.peer_compatible_network_version.clone();
+        let peer_traffic = self.peer_traffic.clone();
 
         self.tokio_executor.spawn(async move {
             // prepare PeerId
-            let peer_id = Arc::new(PeerId::new(myself.clone(), peer_public_key_hash, peer_id_marker, net.socket_address.clone()));
+            let peer_id = Arc::new(PeerId::new(myself.clone(), peer_public_key_hash, peer_id_marker, net.socket_address.clone(), peer_traffic));
             let log = {
                 let myself_name = myself.name().to_string();
                 let myself_uri = myself.uri().to_string();
@@ -334,6 +338,7 @@ pub struct BootstrapOutput(
     pub MetadataMessage,
     pub NetworkVersion,
     pub SocketAddr,
+    pub PeerTrafficRef,
 );
 
 impl fmt::Debug for BootstrapOutput {
@@ -346,6 +351,7 @@ impl fmt::Debug for BootstrapOutput {
             peer_metadata,
             peer_compatible_network_version,
             peer_address,
+            peer_traffic,
         ) = self;
         let peer_public_key_hash: &Hash = peer_public_key_hash.as_ref();
         f.debug_tuple("BootstrapOutput")
@@ -354,6 +360,7 @@ impl fmt::Debug for BootstrapOutput {
             .field(peer_metadata)
             .field(peer_compatible_network_version)
             .field(peer_address)
+            .field(&peer_traffic.snapshot())
             .finish()
     }
 }
@@ -375,6 +382,7 @@ pub async fn bootstrap(
     };
 
     let supported_protocol_version = &info.version;
+    let traffic = PeerTraffic::new_ref();
 
     // send connection message
     let connection_message = ConnectionMessage::try_new(
@@ -387,7 +395,13 @@ pub async fn bootstrap(
     let connection_message_sent = {
         let connection_message_bytes = BinaryChunk::from_content(&connection_message.as_bytes()?)?;
         match timeout(IO_TIMEOUT, msg_tx.write_message(&connection_message_bytes)).await? {
-            Ok(_) => connection_message_bytes,
+            Ok(_) => {
+                traffic.record_outbound(
+                    connection_message.traffic_message_type(),
+                    connection_message_bytes.raw().len(),
+                );
+                connection_message_bytes
+            }
             Err(e) => {
                 return Err(PeerError::NetworkError {
                     error: e.into(),
@@ -411,6 +425,10 @@ pub async fn bootstrap(
 
     let connection_message =
         ConnectionMessage::from_bytes(received_connection_message_bytes.content())?;
+    traffic.record_inbound(
+        connection_message.traffic_message_type(),
+        received_connection_message_bytes.raw().len(),
+    );
 
     // generate local and remote nonce
     let NoncePair {
@@ -434,10 +452,20 @@ pub async fn bootstrap(
     let log = log.new(o!("peer_id" => peer_id_marker.clone()));
 
     // from now on all messages will be encrypted
-    let mut msg_tx =
-        EncryptedMessageWriter::new(msg_tx, precomputed_key.clone(), nonce_local, log.clone());
-    let mut msg_rx =
-        EncryptedMessageReader::new(msg_rx, precomputed_key, nonce_remote, log.clone());
+    let mut msg_tx = EncryptedMessageWriter::new(
+        msg_tx,
+        precomputed_key.clone(),
+        nonce_local,
+        traffic.clone(),
+        log.clone(),
+    );
+    let mut msg_rx = EncryptedMessageReader::new(
+        msg_rx,
+        precomputed_key,
+        nonce_remote,
+        traffic.clone(),
+        log.clone(),
+    );
 
     let connecting_to_self = peer_public_key == info.identity.public_key;
     if connecting_to_self {
@@ -508,6 +536,7 @@ pub async fn bootstrap(
                 metadata_received,
                 compatible_network_version,
                 msg.address,
+                traffic,
             ))
         }
         AckMessage::NackV0 => {
@@ -23,6 +23,8 @@ use tezos_messages::p2p::binary_message::{
     BinaryChunk, BinaryChunkError, BinaryMessage, CONTENT_LENGTH_FIELD_BYTES,
 };
 
+use crate::p2p::traffic::{PeerTrafficRef, TrafficMessageType, UNKNOWN_MESSAGE_TYPE};
+
 /// Max allowed content length in bytes when taking into account extra data added by encryption
 pub const CONTENT_LENGTH_MAX: usize =
     tezos_messages::p2p::binary_message::CONTENT_LENGTH_MAX - crypto::crypto_box::BOX_ZERO_BYTES;
@@ -175,6 +177,8 @@ pub struct EncryptedMessageWriter {
     nonce_local: Nonce,
     /// Outgoing message writer
     tx: MessageWriter,
+    /// Counters of outgoing traffic
+    traffic: PeerTrafficRef,
     /// Logger
     log: Logger,
 }
@@ -184,23 +188,40 @@ impl EncryptedMessageWriter {
         tx: MessageWriter,
         precomputed_key: PrecomputedKey,
         nonce_local: Nonce,
+        traffic: PeerTrafficRef,
         log: Logger,
     ) -> Self {
         EncryptedMessageWriter {
             tx,
             precomputed_key,
             nonce_local,
+            traffic,
             log,
         }
     }
 
     pub async fn write_message<'a>(
         &'a mut self,
-        message: &'a impl BinaryMessage,
+        message: &'a (impl BinaryMessage + TrafficMessageType),
     ) -> Result<(), StreamError> {
         let message_bytes = message.as_bytes()?;
         trace!(self.log, "Writing message"; "message" => FnValue(|_| hex::encode(&message_bytes)));
 
+        let mut written_bytes = 0;
+        let result = self.write_chunks(&message_bytes, &mut written_bytes).await;
+        if written_bytes > 0 {
+            self.traffic
+                .record_outbound(message.traffic_message_type(), written_bytes);
+        }
+        result
+    }
+
+    /// Encrypts and sends message bytes in chunks, `written_bytes` counts raw bytes sent to network
+    async fn write_chunks(
+        &mut self,
+        message_bytes: &[u8],
+        written_bytes: &mut usize,
+    ) -> Result<(), StreamError> {
         for chunk_content_bytes in message_bytes.chunks(CONTENT_LENGTH_MAX) {
             // encrypt
             let nonce = self.nonce_fetch_increment();
@@ -213,6 +234,7 @@ impl EncryptedMessageWriter {
             // send
             let chunk = BinaryChunk::from_content(&message_bytes_encrypted)?;
             self.tx.write_message(&chunk).await?;
+            *written_bytes += chunk.raw().len();
         }
 
         Ok(())
@@ -235,6 +257,8 @@ pub struct EncryptedMessageReader {
     nonce_remote: Nonce,
     /// Incoming message reader
     rx: MessageReader,
+    /// Counters of incoming traffic
+    traffic: PeerTrafficRef,
     /// Logger
     log: Logger,
 }
@@ -245,18 +269,37 @@ impl EncryptedMessageReader {
         rx: MessageReader,
         precomputed_key: PrecomputedKey,
         nonce_remote: Nonce,
+        traffic: PeerTrafficRef,
         log: Logger,
     ) -> Self {
         EncryptedMessageReader {
             rx,
             precomputed_key,
             nonce_remote,
+            traffic,
             log,
         }
     }
 
     /// Consume content of inner message reader into specific message
     pub async fn read_message<M>(&mut self) -> Result<M, StreamError>
+    where
+        M: BinaryMessage + TrafficMessageType,
+    {
+        let mut read_bytes = 0;
+        let result = self.read_chunks::<M>(&mut read_bytes).await;
+        if read_bytes > 0 {
+            let message_type = match &result {
+                Ok(message) => message.traffic_message_type(),
+                Err(_) => UNKNOWN_MESSAGE_TYPE,
+            };
+            self.traffic.record_inbound(message_type, read_bytes);
+        }
+        result
+    }
+
+    /// Reads and decrypts chunks until whole message is received, `read_bytes` counts raw bytes read from network
+    async fn read_chunks<M>(&mut self, read_bytes: &mut usize) -> Result<M, StreamError>
     where
         M: BinaryMessage,
     {
@@ -266,6 +309,7 @@ impl EncryptedMessageReader {
         loop {
             // read
             let message_encrypted = self.rx.read_message().await?;
+            *read_bytes += message_encrypted.raw().len();
 
             // decrypt
             let nonce = self.nonce_fetch_increment();
@@ -0,0 +1,236 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Traffic accounting of peer connection.
+//!
+//! Bytes are counted at the socket level (raw chunks including length prefix and encryption overhead),
+//! messages are counted per message type, so also handshake messages (connection, metadata, ack) are included.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use serde::Serialize;
+
+use tezos_messages::p2p::encoding::prelude::*;
+
+/// Message type used for traffic of messages, which could not be decoded
+pub const UNKNOWN_MESSAGE_TYPE: &str = "Unknown";
+
+/// Shareable traffic counters of one peer connection
+pub type PeerTrafficRef = Arc<PeerTraffic>;
+
+#[derive(Clone, Copy, Debug, Default, Serialize, PartialEq)]
+pub struct TrafficCounter {
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+impl TrafficCounter {
+    fn add(&mut self, bytes: usize) {
+        self.messages += 1;
+        self.bytes += bytes as u64;
+    }
+
+    fn merge(&mut self, other: &TrafficCounter) {
+        self.messages += other.messages;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Traffic in one direction
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DirectionTraffic {
+    pub total: TrafficCounter,
+    pub by_message_type: BTreeMap<&'static str, TrafficCounter>,
+}
+
+impl DirectionTraffic {
+    fn add(&mut self, message_type: &'static str, bytes: usize) {
+        self.total.add(bytes);
+        self.by_message_type
+            .entry(message_type)
+            .or_default()
+            .add(bytes);
+    }
+
+    fn merge(&mut self, other: &DirectionTraffic) {
+        self.total.merge(&other.total);
+        for (message_type, counter) in &other.by_message_type {
+            self.by_message_type
+                .entry(*message_type)
+                .or_default()
+                .merge(counter);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PeerTrafficStats {
+    pub inbound: DirectionTraffic,
+    pub outbound: DirectionTraffic,
+}
+
+impl PeerTrafficStats {
+    /// Adds counters of `other` to these counters (e.g. to sum traffic of more peers)
+    pub fn merge(&mut self, other: &PeerTrafficStats) {
+        self.inbound.merge(&other.inbound);
+        self.outbound.merge(&other.outbound);
+    }
+}
+
+/// Traffic counters of one peer connection, shared by reader and writer of the connection
+#[derive(Debug, Default)]
+pub struct PeerTraffic {
+    stats: Mutex<PeerTrafficStats>,
+}
+
+impl PeerTraffic {
+    pub fn new_ref() -> PeerTrafficRef {
+        Arc::new(PeerTraffic::default())
+    }
+
+    pub fn record_inbound(&self, message_type: &'static str, bytes: usize) {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .inbound
+            .add(message_type, bytes);
+    }
+
+    pub fn record_outbound(&self, message_type: &'static str, bytes: usize) {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .outbound
+            .add(message_type, bytes);
+    }
+
+    pub fn snapshot(&self) -> PeerTrafficStats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Name of the message type used for traffic accounting
+pub trait TrafficMessageType {
+    fn traffic_message_type(&self) -> &'static str;
+}
+
+impl TrafficMessageType for ConnectionMessage {
+    fn traffic_message_type(&self) -> &'static str {
+        "Connection"
+    }
+}
+
+impl TrafficMessageType for MetadataMessage {
+    fn traffic_message_type(&self) -> &'static str {
+        "Metadata"
+    }
+}
+
+impl TrafficMessageType for AckMessage {
+    fn traffic_message_type(&self) -> &'static str {
+        "Ack"
+    }
+}
+
+/// Response contains (almost) always just one message, so type of the first message is used
+impl TrafficMessageType for PeerMessageResponse {
+    fn traffic_message_type(&self) -> &'static str {
+        match self.messages().first() {
+            Some(message) => message.traffic_message_type(),
+            None => "Empty",
+        }
+    }
+}
+
+impl TrafficMessageType for PeerMessage {
+    fn traffic_message_type(&self) -> &'static str {
+        match self {
+            PeerMessage::Disconnect => "Disconnect",
+            PeerMessage::Advertise(_) => "Advertise",
+            PeerMessage::SwapRequest(_) => "SwapRequest",
+            PeerMessage::SwapAck(_) => "SwapAck",
+            PeerMessage::Bootstrap => "Bootstrap",
+            PeerMessage::GetCurrentBranch(_) => "GetCurrentBranch",
+            PeerMessage::CurrentBranch(_) => "CurrentBranch",
+            PeerMessage::Deactivate(_) => "Deactivate",
+            PeerMessage::GetCurrentHead(_) => "GetCurrentHead",
+            PeerMessage::CurrentHead(_) => "CurrentHead",
+            PeerMessage::GetBlockHeaders(_) => "GetBlockHeaders",
+            PeerMessage::BlockHeader(_) => "BlockHeader",
+            PeerMessage::GetOperations(_) => "GetOperations",
+            PeerMessage::Operation(_) => "Operation",
+            PeerMessage::GetProtocols(_) => "GetProtocols",
+            PeerMessage::Protocol(_) => "Protocol",
+            PeerMessage::GetOperationHashesForBlocks(_) => "GetOperationHashesForBlocks",
+            PeerMessage::OperationHashesForBlock(_) => "OperationHashesForBlocks",
+            PeerMessage::GetOperationsForBlocks(_) => "GetOperationsForBlocks",
+            PeerMessage::OperationsForBlocks(_) => "OperationsForBlocks",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_traffic_counts_by_message_type() {
+        let traffic = PeerTraffic::new_ref();
+        traffic.record_inbound("Connection", 100);
+        traffic.record_inbound("CurrentHead", 250);
+        traffic.record_inbound("CurrentHead", 50);
+        traffic.record_outbound("Ack", 20);
+
+        let stats = traffic.snapshot();
+        assert_eq!(
+            TrafficCounter {
+                messages: 3,
+                bytes: 400
+            },
+            stats.inbound.total
+        );
+        assert_eq!(
+            Some(&TrafficCounter {
+                messages: 2,
+                bytes: 300
+            }),
+            stats.inbound.by_message_type.get("CurrentHead")
+        );
+        assert_eq!(
+            TrafficCounter {
+                messages: 1,
+                bytes: 20
+            },
+            stats.outbound.total
+        );
+        assert_eq!(1, stats.outbound.by_message_type.len());
+
+        let mut merged = PeerTrafficStats::default();
+        merged.merge(&stats);
+        merged.merge(&stats);
+        assert_eq!(
+            Some(&TrafficCounter {
+                messages: 4,
+                bytes: 600
+            }),
+            merged.inbound.by_message_type.get("CurrentHead")
+        );
+        assert_eq!(
+            TrafficCounter {
+                messages: 2,
+                bytes: 40
+            },
+            merged.outbound.total
+        );
+    }
+
+    #[test]
+    fn test_peer_message_response_type() {
+        let response: PeerMessageResponse = PeerMessage::Bootstrap.into();
+        assert_eq!("Bootstrap", response.traffic_message_type());
+    }
+}
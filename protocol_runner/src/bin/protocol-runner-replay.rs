@@ -0,0 +1,139 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![forbid(unsafe_code)]
+
+//! Replays IPC recording (see light-node `--ffi-ipc-recording-file`) with fresh protocol runners
+//! and reports calls, which responses differ from the recorded ones.
+
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{App, Arg};
+use slog::*;
+
+use tezos_wrapper::recorder::replay_recording;
+
+fn create_logger(log_level: Level) -> Logger {
+    let drain = slog_async::Async::new(
+        slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
+            .build()
+            .fuse(),
+    )
+    .build()
+    .filter_level(log_level)
+    .fuse();
+
+    Logger::root(drain, slog::o!())
+}
+
+fn main() {
+    let matches = App::new("Protocol Runner Replay")
+        .version("1.0")
+        .about("Replays recorded IPC calls with fresh protocol runners and compares responses")
+        .arg(
+            Arg::with_name("recording")
+                .long("recording")
+                .value_name("PATH")
+                .help("Path to the IPC recording file")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true)
+                .validator(|v| {
+                    if Path::new(&v).exists() {
+                        Ok(())
+                    } else {
+                        Err(format!("Recording not found at '{}'", v))
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("protocol-runner")
+                .long("protocol-runner")
+                .value_name("PATH")
+                .help("Path to a tezos protocol runner executable")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true)
+                .validator(|v| {
+                    if Path::new(&v).exists() {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "Tezos protocol runner executable not found at '{}'",
+                            v
+                        ))
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("tezos-data-dir")
+                .long("tezos-data-dir")
+                .value_name("PATH")
+                .help("Empty directory, where replayed context is initialized (recorded data dir is not used)")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["critical", "error", "warn", "info", "debug", "trace"])
+                .help("Set log level"),
+        )
+        .get_matches();
+
+    let recording = PathBuf::from(matches.value_of("recording").expect("Missing recording"));
+    let protocol_runner = PathBuf::from(
+        matches
+            .value_of("protocol-runner")
+            .expect("Missing protocol-runner"),
+    );
+    let data_dir = PathBuf::from(
+        matches
+            .value_of("tezos-data-dir")
+            .expect("Missing tezos-data-dir"),
+    );
+    let log_level = matches
+        .value_of("log-level")
+        .unwrap_or("info")
+        .parse::<slog::Level>()
+        .expect("Was expecting one value from slog::Level");
+
+    let log = create_logger(log_level);
+
+    if let Err(e) = std::fs::create_dir_all(&data_dir) {
+        crit!(log, "Failed to create data dir"; "data_dir" => data_dir.display().to_string(), "reason" => format!("{}", e));
+        drop(log);
+        process::exit(1);
+    }
+
+    let report = match replay_recording(&recording, &protocol_runner, log_level, &data_dir, &log) {
+        Ok(report) => report,
+        Err(e) => {
+            crit!(log, "Replay failed"; "reason" => format!("{}", e));
+            drop(log);
+            process::exit(1);
+        }
+    };
+
+    for mismatch in &report.mismatches {
+        warn!(log, "Response mismatch";
+                   "sequence" => mismatch.sequence,
+                   "connection_id" => mismatch.connection_id,
+                   "call" => mismatch.call,
+                   "recorded" => &mismatch.recorded,
+                   "replayed" => &mismatch.replayed);
+    }
+    info!(log, "Replay finished";
+               "replayed_calls" => report.replayed_calls,
+               "mismatches" => report.mismatches.len());
+
+    // give async logger chance to flush
+    drop(log);
+
+    if !report.mismatches.is_empty() {
+        process::exit(2);
+    }
+}
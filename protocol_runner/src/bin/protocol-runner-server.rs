@@ -0,0 +1,149 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![forbid(unsafe_code)]
+
+//! Serves protocol runners for remote nodes (see light-node `--ffi-*-pool-runners`).
+//!
+//! Every authenticated TCP connection from the node gets its own fresh protocol runner sub-process on this host.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{App, Arg};
+use slog::*;
+
+use tezos_wrapper::remote::{serve_remote_runners, RemoteRunnerServerConfiguration};
+
+fn create_logger(log_level: Level) -> Logger {
+    let drain = slog_async::Async::new(
+        slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
+            .build()
+            .fuse(),
+    )
+    .build()
+    .filter_level(log_level)
+    .fuse();
+
+    Logger::root(drain, slog::o!())
+}
+
+fn main() {
+    let matches = App::new("Protocol Runner Server")
+        .version("1.0")
+        .about("Serves protocol runners for remote tezedge nodes over TCP (authenticated with pre-shared key)")
+        .arg(
+            Arg::with_name("listen")
+                .long("listen")
+                .value_name("IP:PORT")
+                .help("Listening address, e.g. 0.0.0.0:9733")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true)
+                .validator(|v| {
+                    v.parse::<SocketAddr>()
+                        .map(|_| ())
+                        .map_err(|e| format!("Invalid listening address '{}': {}", v, e))
+                }),
+        )
+        .arg(
+            Arg::with_name("psk-file")
+                .long("psk-file")
+                .value_name("PATH")
+                .help("Path to the file with pre-shared key (32 bytes as hex string), the same key must be configured on the node")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("protocol-runner")
+                .long("protocol-runner")
+                .value_name("PATH")
+                .help("Path to a tezos protocol runner executable")
+                .takes_value(true)
+                .empty_values(false)
+                .required(true)
+                .validator(|v| {
+                    if Path::new(&v).exists() {
+                        Ok(())
+                    } else {
+                        Err(format!(
+                            "Tezos protocol runner executable not found at '{}'",
+                            v
+                        ))
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("tezos-data-dir")
+                .long("tezos-data-dir")
+                .value_name("PATH")
+                .help("Directory with (replicated) context on this host, if not set, the node's tezos data dir is used")
+                .takes_value(true)
+                .empty_values(false),
+        )
+        .arg(
+            Arg::with_name("max-runners")
+                .long("max-runners")
+                .value_name("NUM")
+                .help("Max count of concurrently running protocol runners, default: 10")
+                .takes_value(true)
+                .validator(|v| {
+                    v.parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|_| "Value must be a valid number".to_string())
+                }),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("LEVEL")
+                .possible_values(&["critical", "error", "warn", "info", "debug", "trace"])
+                .help("Set log level"),
+        )
+        .get_matches();
+
+    let listen_address = matches
+        .value_of("listen")
+        .expect("Missing listen")
+        .parse::<SocketAddr>()
+        .expect("Invalid listening address");
+    let psk_file = PathBuf::from(matches.value_of("psk-file").expect("Missing psk-file"));
+    let log_level = matches
+        .value_of("log-level")
+        .unwrap_or("info")
+        .parse::<slog::Level>()
+        .expect("Was expecting one value from slog::Level");
+    let configuration = RemoteRunnerServerConfiguration {
+        protocol_runner: PathBuf::from(
+            matches
+                .value_of("protocol-runner")
+                .expect("Missing protocol-runner"),
+        ),
+        runner_log_level: log_level,
+        data_dir: matches.value_of("tezos-data-dir").map(PathBuf::from),
+        max_runners: matches
+            .value_of("max-runners")
+            .unwrap_or("10")
+            .parse::<usize>()
+            .expect("Provided value cannot be converted to number"),
+    };
+
+    let log = create_logger(log_level);
+
+    let key = match ipc::load_preshared_key(&psk_file) {
+        Ok(key) => key,
+        Err(e) => {
+            crit!(log, "Failed to load pre-shared key"; "reason" => format!("{}", e));
+            drop(log);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = serve_remote_runners(listen_address, key, configuration, &log) {
+        crit!(log, "Remote protocol runner server failed"; "reason" => format!("{}", e));
+        drop(log);
+        process::exit(1);
+    }
+}
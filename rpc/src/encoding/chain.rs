@@ -3,9 +3,11 @@
 
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use storage::InvalidBlock;
+
 use crate::helpers::FullBlockInfo;
 
 use super::base_types::*;
@@ -46,3 +48,73 @@ impl From<FullBlockInfo> for BlockInfo {
         }
     }
 }
+
+// GET /chains/<chain_id>/invalid_blocks
+
+/// Block, which was rejected by protocol
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBlockInfo {
+    block: String,
+    level: i32,
+    errors: Vec<String>,
+}
+
+impl InvalidBlockInfo {
+    pub fn new(block: String, level: i32, errors: Vec<String>) -> Self {
+        Self {
+            block,
+            level,
+            errors,
+        }
+    }
+}
+
+impl From<InvalidBlock> for InvalidBlockInfo {
+    fn from(val: InvalidBlock) -> Self {
+        Self {
+            block: val.block_hash().to_base58_check(),
+            level: *val.level(),
+            errors: vec![val.error().clone()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use crypto::hash::BlockHash;
+
+    use crate::encoding::test_helpers::*;
+
+    use super::*;
+
+    #[test]
+    fn invalid_block_encoded_equals_decoded() -> Result<(), serde_json::Error> {
+        let original = InvalidBlockInfo::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".to_string(),
+            10,
+            vec!["Protocol error".to_string()],
+        );
+        let encoded = serde_json::to_string(&original)?;
+        let decoded: InvalidBlockInfo = serde_json::from_str(&encoded)?;
+        assert_eq!(original, decoded);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_block_from_storage() -> Result<(), failure::Error> {
+        let block_hash =
+            BlockHash::try_from("BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe")?;
+        let info = InvalidBlockInfo::from(InvalidBlock::new(
+            block_hash,
+            10,
+            "Protocol error".to_string(),
+        ));
+        custom_encoded(
+            info,
+            "{\"block\":\"BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe\",\"level\":10,\"errors\":[\"Protocol error\"]}",
+        )?;
+        Ok(())
+    }
+}
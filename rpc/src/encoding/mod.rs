@@ -1,6 +1,7 @@
 pub mod base_types;
 pub mod chain;
 pub mod monitor;
+pub mod open_metrics;
 
 #[cfg(test)]
 pub mod test_helpers {
@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use crypto::hash::BlockHash;
+use shell::state::synchronization_state::SyncState;
 use tezos_messages::p2p::encoding::prelude::*;
 
 use super::base_types::*;
@@ -76,6 +77,24 @@ impl BootstrapInfo {
     }
 }
 
+// GET /chains/<chain_id>/is_bootstrapped
+
+/// Bootstrap status with the state of synchronization heuristic
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IsBootstrappedInfo {
+    bootstrapped: bool,
+    sync_state: SyncState,
+}
+
+impl IsBootstrappedInfo {
+    pub fn new(bootstrapped: bool, sync_state: SyncState) -> Self {
+        Self {
+            bootstrapped,
+            sync_state,
+        }
+    }
+}
+
 // GET /monitor/heads/<chain_id>?(next_protocol=<Protocol_hash>)*
 
 pub type OperationListHash = Vec<Vec<String>>;
@@ -159,6 +178,40 @@ mod tests {
         }
     }
 
+    mod is_bootstrapped {
+        use super::*;
+
+        #[test]
+        fn encoded_equals_decoded() -> Result<(), serde_json::Error> {
+            for original in &[
+                IsBootstrappedInfo::new(true, SyncState::Synced),
+                IsBootstrappedInfo::new(false, SyncState::Unsynced),
+                IsBootstrappedInfo::new(true, SyncState::Stuck),
+            ] {
+                let encoded = serde_json::to_string(original)?;
+                let decoded: IsBootstrappedInfo = serde_json::from_str(&encoded)?;
+                assert_eq!(original, &decoded);
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn encoded_custom() -> Result<(), serde_json::Error> {
+            custom_encoded(
+                IsBootstrappedInfo::new(false, SyncState::Unsynced),
+                "{\"bootstrapped\":false,\"sync_state\":\"unsynced\"}",
+            )
+        }
+
+        #[test]
+        fn decoded_custom() -> Result<(), serde_json::Error> {
+            custom_decoded(
+                "{\"bootstrapped\":true,\"sync_state\":\"stuck\"}",
+                IsBootstrappedInfo::new(true, SyncState::Stuck),
+            )
+        }
+    }
+
     mod active_chain {
         use super::*;
 
@@ -0,0 +1,243 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Encoding of metrics to OpenMetrics text exposition format (see https://openmetrics.io),
+//! which is scraped by Prometheus compatible monitoring.
+
+use std::fmt::Write;
+
+/// Content type of the encoded metrics
+pub const OPEN_METRICS_CONTENT_TYPE: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+            MetricType::Summary => "summary",
+        }
+    }
+}
+
+pub type Labels = Vec<(&'static str, String)>;
+
+#[derive(Debug, Clone)]
+struct Sample {
+    suffix: &'static str,
+    labels: Labels,
+    value: f64,
+}
+
+/// Metric family - all samples of one metric with different labels
+#[derive(Debug, Clone)]
+pub struct MetricFamily {
+    name: &'static str,
+    help: &'static str,
+    metric_type: MetricType,
+    samples: Vec<Sample>,
+}
+
+impl MetricFamily {
+    fn new(name: &'static str, help: &'static str, metric_type: MetricType) -> Self {
+        Self {
+            name,
+            help,
+            metric_type,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Counter, `name` is without `_total` suffix (suffix is added to samples)
+    pub fn counter(name: &'static str, help: &'static str) -> Self {
+        Self::new(name, help, MetricType::Counter)
+    }
+
+    pub fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self::new(name, help, MetricType::Gauge)
+    }
+
+    pub fn histogram(name: &'static str, help: &'static str) -> Self {
+        Self::new(name, help, MetricType::Histogram)
+    }
+
+    pub fn summary(name: &'static str, help: &'static str) -> Self {
+        Self::new(name, help, MetricType::Summary)
+    }
+
+    /// Adds sample for counter or gauge
+    pub fn add(&mut self, labels: Labels, value: f64) {
+        debug_assert!(
+            self.metric_type == MetricType::Counter || self.metric_type == MetricType::Gauge,
+            "Sample can be added just to counter or gauge, metric: {}",
+            self.name
+        );
+        let suffix = match self.metric_type {
+            MetricType::Counter => "_total",
+            _ => "",
+        };
+        self.samples.push(Sample {
+            suffix,
+            labels,
+            value,
+        });
+    }
+
+    /// Builder variant of `add` for metric without labels
+    pub fn with_value(mut self, value: f64) -> Self {
+        self.add(Vec::new(), value);
+        self
+    }
+
+    /// Adds histogram samples, `buckets` are pairs (upper bound, cumulative count) and have to end with `+Inf` bucket
+    pub fn add_histogram(&mut self, labels: Labels, buckets: &[(f64, u64)], sum: f64, count: u64) {
+        debug_assert_eq!(MetricType::Histogram, self.metric_type);
+        for (bound, cumulative_count) in buckets {
+            let mut bucket_labels = labels.clone();
+            bucket_labels.push(("le", format_value(*bound)));
+            self.samples.push(Sample {
+                suffix: "_bucket",
+                labels: bucket_labels,
+                value: *cumulative_count as f64,
+            });
+        }
+        self.add_sum_and_count(labels, sum, count);
+    }
+
+    /// Adds summary samples (without quantiles)
+    pub fn add_summary(&mut self, labels: Labels, sum: f64, count: u64) {
+        debug_assert_eq!(MetricType::Summary, self.metric_type);
+        self.add_sum_and_count(labels, sum, count);
+    }
+
+    fn add_sum_and_count(&mut self, labels: Labels, sum: f64, count: u64) {
+        self.samples.push(Sample {
+            suffix: "_sum",
+            labels: labels.clone(),
+            value: sum,
+        });
+        self.samples.push(Sample {
+            suffix: "_count",
+            labels,
+            value: count as f64,
+        });
+    }
+}
+
+/// Encodes metric families to OpenMetrics text format
+pub fn encode_open_metrics(families: &[MetricFamily]) -> String {
+    let mut out = String::new();
+    for family in families {
+        // writing to String cannot fail
+        let _ = writeln!(
+            out,
+            "# TYPE {} {}",
+            family.name,
+            family.metric_type.as_str()
+        );
+        let _ = writeln!(out, "# HELP {} {}", family.name, escape(family.help, false));
+        for sample in &family.samples {
+            out.push_str(family.name);
+            out.push_str(sample.suffix);
+            if !sample.labels.is_empty() {
+                let labels = sample
+                    .labels
+                    .iter()
+                    .map(|(name, value)| format!("{}=\"{}\"", name, escape(value, true)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(out, "{{{}}}", labels);
+            }
+            let _ = writeln!(out, " {}", format_value(sample.value));
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 {
+            "+Inf".to_string()
+        } else {
+            "-Inf".to_string()
+        }
+    } else {
+        format!("{}", value)
+    }
+}
+
+fn escape(value: &str, escape_quotes: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if escape_quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_open_metrics() {
+        let mut peers = MetricFamily::counter(
+            "tezedge_p2p_peer_received_bytes",
+            "Bytes received from connected peer",
+        );
+        peers.add(vec![("address", "127.0.0.1:9732".to_string())], 1024.0);
+        peers.add(vec![("address", "a\"b\\c".to_string())], 0.5);
+
+        let mut latency = MetricFamily::histogram(
+            "tezedge_block_application_duration_seconds",
+            "Duration of block application",
+        );
+        latency.add_histogram(
+            vec![("phase", "validation".to_string())],
+            &[(0.5, 1), (f64::INFINITY, 2)],
+            1.25,
+            2,
+        );
+
+        let encoded = encode_open_metrics(&[
+            MetricFamily::gauge("tezedge_p2p_peers_connected", "Count of connected peers")
+                .with_value(3.0),
+            peers,
+            latency,
+        ]);
+
+        assert_eq!(
+            encoded,
+            "# TYPE tezedge_p2p_peers_connected gauge\n\
+             # HELP tezedge_p2p_peers_connected Count of connected peers\n\
+             tezedge_p2p_peers_connected 3\n\
+             # TYPE tezedge_p2p_peer_received_bytes counter\n\
+             # HELP tezedge_p2p_peer_received_bytes Bytes received from connected peer\n\
+             tezedge_p2p_peer_received_bytes_total{address=\"127.0.0.1:9732\"} 1024\n\
+             tezedge_p2p_peer_received_bytes_total{address=\"a\\\"b\\\\c\"} 0.5\n\
+             # TYPE tezedge_block_application_duration_seconds histogram\n\
+             # HELP tezedge_block_application_duration_seconds Duration of block application\n\
+             tezedge_block_application_duration_seconds_bucket{phase=\"validation\",le=\"0.5\"} 1\n\
+             tezedge_block_application_duration_seconds_bucket{phase=\"validation\",le=\"+Inf\"} 2\n\
+             tezedge_block_application_duration_seconds_sum{phase=\"validation\"} 1.25\n\
+             tezedge_block_application_duration_seconds_count{phase=\"validation\"} 2\n\
+             # EOF\n"
+        );
+    }
+}
@@ -63,6 +63,16 @@ pub(crate) fn make_json_stream_response<
         .body(Body::wrap_stream(content))?)
 }
 
+/// Function to generate response with metrics in OpenMetrics text format
+pub(crate) fn make_open_metrics_response(content: String) -> ServiceResult {
+    Ok(Response::builder()
+        .header(
+            hyper::header::CONTENT_TYPE,
+            encoding::open_metrics::OPEN_METRICS_CONTENT_TYPE,
+        )
+        .body(Body::from(content))?)
+}
+
 /// Returns result as a JSON response.
 pub(crate) fn result_to_json_response<T: serde::Serialize>(
     res: Result<T, failure::Error>,
@@ -11,7 +11,14 @@ use tokio::runtime::Handle;
 
 use crypto::hash::ChainId;
 use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::reload::ConfigurationReloaderRef;
 use shell::shell_channel::{ShellChannelMsg, ShellChannelRef};
+use shell::state::clock_state::NodeClockRef;
+use shell::state::synchronization_state::SynchronizationBootstrapStateRef;
+use shell::stats::apply_block_stats::ApplyBlockStatsRef;
+use shell::stats::block_lifecycle::BlockLifecycleTracerRef;
+use shell::stats::metrics::MonitorMetricsRef;
+use shell::stats::write_runner_stats::WriteRunnerStatsRef;
 use shell::subscription::subscribe_to_shell_new_current_head;
 use storage::context::TezedgeContext;
 use storage::persistent::PersistentStorage;
@@ -57,10 +64,18 @@ impl RpcServer {
         tokio_executor: &Handle,
         persistent_storage: &PersistentStorage,
         current_mempool_state_storage: CurrentMempoolStateStorageRef,
+        bootstrap_state: SynchronizationBootstrapStateRef,
         tezedge_context: &TezedgeContext,
         tezos_readonly_api: Arc<TezosApiConnectionPool>,
         tezos_readonly_prevalidation_api: Arc<TezosApiConnectionPool>,
         tezos_without_context_api: Arc<TezosApiConnectionPool>,
+        tezos_writeable_api: Arc<TezosApiConnectionPool>,
+        apply_block_stats: ApplyBlockStatsRef,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        monitor_metrics: MonitorMetricsRef,
+        configuration_reloader: ConfigurationReloaderRef,
+        node_clock: NodeClockRef,
+        write_runner_stats: WriteRunnerStatsRef,
         tezos_env: TezosEnvironmentConfiguration,
         network_version: Arc<NetworkVersion>,
         init_storage_data: &StorageInitInfo,
@@ -89,10 +104,18 @@ impl RpcServer {
                 network_version,
                 persistent_storage,
                 current_mempool_state_storage,
+                bootstrap_state,
                 tezedge_context,
                 tezos_readonly_api,
                 tezos_readonly_prevalidation_api,
                 tezos_without_context_api,
+                tezos_writeable_api,
+                apply_block_stats,
+                block_lifecycle_tracer,
+                monitor_metrics,
+                configuration_reloader,
+                node_clock,
+                write_runner_stats,
                 init_storage_data.chain_id.clone(),
                 init_storage_data.genesis_block_header_hash.clone(),
                 shared_state,
@@ -2,13 +2,17 @@
 // SPDX-License-Identifier: MIT
 
 use failure::format_err;
-use hyper::{Body, Request};
+use hyper::{Body, Method, Request};
 use slog::warn;
 
+use crate::encoding::open_metrics::encode_open_metrics;
 use crate::helpers::{parse_block_hash, parse_chain_id, SlimBlockData, MAIN_CHAIN_ID};
 use crate::server::{HasSingleValue, Params, Query, RpcServiceEnvironment};
-use crate::services::{base_services, dev_services};
-use crate::{empty, make_json_response, required_param, result_to_json_response, ServiceResult};
+use crate::services::{base_services, dev_services, metrics_services};
+use crate::{
+    empty, make_json_response, make_open_metrics_response, required_param,
+    result_option_to_json_response, result_to_json_response, ServiceResult,
+};
 
 pub async fn dev_blocks(
     _: Request<Body>,
@@ -175,6 +179,55 @@ pub async fn dev_stats_memory_protocol_runners(
     }
 }
 
+pub async fn dev_stats_network(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    result_to_json_response(
+        dev_services::get_network_stats(env.monitor_metrics()),
+        env.log(),
+    )
+}
+
+pub async fn dev_stats_write_protocol_runner(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    result_to_json_response(
+        dev_services::get_write_runner_stats(env.write_runner_stats()),
+        env.log(),
+    )
+}
+
+pub async fn dev_stats_protocol_runners(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    make_json_response(&dev_services::get_protocol_runner_stats(&env))
+}
+
+pub async fn dev_block_timeline(
+    _: Request<Body>,
+    params: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    // TODO: TE-221 - add optional chain_id to params mapping
+    let chain_id_param = MAIN_CHAIN_ID;
+    let chain_id = parse_chain_id(chain_id_param, &env)?;
+    let block_hash = parse_block_hash(&chain_id, required_param!(params, "block_hash")?, &env)?;
+    result_option_to_json_response(
+        Ok(env.block_lifecycle_tracer().timeline(&block_hash)),
+        env.log(),
+    )
+}
+
 pub async fn context_stats(
     _: Request<Body>,
     _: Params,
@@ -187,6 +240,54 @@ pub async fn context_stats(
     )
 }
 
+/// Creates consistent backup of the whole storage to (empty) directory `target_dir` without stopping the node
+pub async fn dev_storage_backup(
+    _: Request<Body>,
+    _: Params,
+    query: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    let target_dir = required_param!(query, "target_dir")?;
+    result_to_json_response(
+        dev_services::backup_storage(target_dir, env.persistent_storage(), env.log()),
+        env.log(),
+    )
+}
+
+/// Re-reads node configuration (the same as SIGHUP) and applies changed reloadable settings without node restart
+pub async fn dev_configuration_reload(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    // reload blocks (e.g. recreates protocol runner pools and waits for actors), so it must not run on the async executor
+    let configuration_reloader = env.configuration_reloader().clone();
+    let result = tokio::task::spawn_blocking(move || configuration_reloader.reload())
+        .await?
+        .map_err(failure::Error::from);
+    result_to_json_response(result, env.log())
+}
+
+/// Returns (GET) or sets, advances or resets (POST) node's notion of "now", which is used by block validation (sandbox only)
+pub async fn dev_sandbox_clock(
+    req: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    if req.method() == Method::GET {
+        return make_json_response(&dev_services::get_node_clock(env.node_clock()));
+    }
+
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let update: dev_services::NodeClockUpdate = serde_json::from_slice(&body)?;
+    result_to_json_response(
+        dev_services::update_node_clock(env.node_clock(), update, env.log()),
+        env.log(),
+    )
+}
+
 /// Get the version string
 pub async fn dev_version(
     _: Request<Body>,
@@ -202,3 +303,15 @@ pub async fn dev_version(
         }
     }
 }
+
+/// Exports node internals in OpenMetrics text format (for Prometheus compatible scrapers)
+pub async fn metrics(
+    _: Request<Body>,
+    _: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    make_open_metrics_response(encode_open_metrics(&metrics_services::collect_metrics(
+        &env,
+    )))
+}
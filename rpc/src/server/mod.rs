@@ -15,7 +15,14 @@ use slog::{error, Logger};
 
 use crypto::hash::{BlockHash, ChainId};
 use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::reload::ConfigurationReloaderRef;
 use shell::shell_channel::ShellChannelRef;
+use shell::state::clock_state::NodeClockRef;
+use shell::state::synchronization_state::SynchronizationBootstrapStateRef;
+use shell::stats::apply_block_stats::ApplyBlockStatsRef;
+use shell::stats::block_lifecycle::BlockLifecycleTracerRef;
+use shell::stats::metrics::MonitorMetricsRef;
+use shell::stats::write_runner_stats::WriteRunnerStatsRef;
 use storage::context::TezedgeContext;
 use storage::persistent::PersistentStorage;
 use tezos_api::environment::TezosEnvironmentConfiguration;
@@ -42,6 +49,8 @@ pub struct RpcServiceEnvironment {
     #[get = "pub(crate)"]
     current_mempool_state_storage: CurrentMempoolStateStorageRef,
     #[get = "pub(crate)"]
+    bootstrap_state: SynchronizationBootstrapStateRef,
+    #[get = "pub(crate)"]
     tezedge_context: TezedgeContext,
     #[get = "pub(crate)"]
     state: RpcCollectedStateRef,
@@ -65,6 +74,21 @@ pub struct RpcServiceEnvironment {
     tezos_readonly_prevalidation_api: Arc<TezosApiConnectionPool>,
     #[get = "pub(crate)"]
     tezos_without_context_api: Arc<TezosApiConnectionPool>,
+    #[get = "pub(crate)"]
+    tezos_writeable_api: Arc<TezosApiConnectionPool>,
+
+    #[get = "pub(crate)"]
+    apply_block_stats: ApplyBlockStatsRef,
+    #[get = "pub(crate)"]
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
+    #[get = "pub(crate)"]
+    monitor_metrics: MonitorMetricsRef,
+    #[get = "pub(crate)"]
+    configuration_reloader: ConfigurationReloaderRef,
+    #[get = "pub(crate)"]
+    node_clock: NodeClockRef,
+    #[get = "pub(crate)"]
+    write_runner_stats: WriteRunnerStatsRef,
 }
 
 impl RpcServiceEnvironment {
@@ -76,10 +100,18 @@ impl RpcServiceEnvironment {
         network_version: Arc<NetworkVersion>,
         persistent_storage: &PersistentStorage,
         current_mempool_state_storage: CurrentMempoolStateStorageRef,
+        bootstrap_state: SynchronizationBootstrapStateRef,
         tezedge_context: &TezedgeContext,
         tezos_readonly_api: Arc<TezosApiConnectionPool>,
         tezos_readonly_prevalidation_api: Arc<TezosApiConnectionPool>,
         tezos_without_context_api: Arc<TezosApiConnectionPool>,
+        tezos_writeable_api: Arc<TezosApiConnectionPool>,
+        apply_block_stats: ApplyBlockStatsRef,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        monitor_metrics: MonitorMetricsRef,
+        configuration_reloader: ConfigurationReloaderRef,
+        node_clock: NodeClockRef,
+        write_runner_stats: WriteRunnerStatsRef,
         main_chain_id: ChainId,
         main_chain_genesis_hash: BlockHash,
         state: RpcCollectedStateRef,
@@ -93,6 +125,7 @@ impl RpcServiceEnvironment {
             network_version,
             persistent_storage: persistent_storage.clone(),
             current_mempool_state_storage,
+            bootstrap_state,
             tezedge_context: tezedge_context.clone(),
             main_chain_id,
             main_chain_genesis_hash,
@@ -101,6 +134,13 @@ impl RpcServiceEnvironment {
             tezos_readonly_api,
             tezos_readonly_prevalidation_api,
             tezos_without_context_api,
+            tezos_writeable_api,
+            apply_block_stats,
+            block_lifecycle_tracer,
+            monitor_metrics,
+            configuration_reloader,
+            node_clock,
+            write_runner_stats,
         }
     }
 }
@@ -67,11 +67,21 @@ pub(crate) fn create_routes(is_sandbox: bool) -> PathTree<MethodHandler> {
         "/chains/:chain_id/chain_id",
         shell_handler::get_chain_id,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/chains/:chain_id/is_bootstrapped",
+        shell_handler::chain_is_bootstrapped,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/chains/:chain_id/blocks",
         shell_handler::blocks,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/chains/:chain_id/invalid_blocks",
+        shell_handler::invalid_blocks,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/chains/:chain_id/blocks/:block_id",
@@ -164,6 +174,11 @@ pub(crate) fn create_routes(is_sandbox: bool) -> PathTree<MethodHandler> {
             "/injection/block",
             shell_handler::inject_block,
         );
+        routes.handle(
+            hash_set![Method::GET, Method::POST],
+            "/dev/sandbox/clock",
+            dev_handler::dev_sandbox_clock,
+        );
     }
 
     // Shell rpcs - routed through ffi calls
@@ -228,6 +243,11 @@ pub(crate) fn create_routes(is_sandbox: bool) -> PathTree<MethodHandler> {
         "/dev/version",
         dev_handler::dev_version,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/dev/blocks/:block_hash/timeline",
+        dev_handler::dev_block_timeline,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/stats/memory",
@@ -238,12 +258,38 @@ pub(crate) fn create_routes(is_sandbox: bool) -> PathTree<MethodHandler> {
         "/stats/memory/protocol_runners",
         dev_handler::dev_stats_memory_protocol_runners,
     );
+    routes.handle(
+        hash_set![Method::GET],
+        "/stats/protocol_runners",
+        dev_handler::dev_stats_protocol_runners,
+    );
+    routes.handle(
+        hash_set![Method::GET],
+        "/stats/protocol_runners/write",
+        dev_handler::dev_stats_write_protocol_runner,
+    );
+    routes.handle(
+        hash_set![Method::GET],
+        "/stats/network",
+        dev_handler::dev_stats_network,
+    );
     routes.handle(
         hash_set![Method::GET],
         "/stats/context",
         dev_handler::context_stats,
     );
     //routes.handle(hash_set![Method::GET], "/stats/storage", dev_handler::dev_stats_storage);
+    routes.handle(hash_set![Method::GET], "/metrics", dev_handler::metrics);
+    routes.handle(
+        hash_set![Method::POST],
+        "/dev/storage/backup",
+        dev_handler::dev_storage_backup,
+    );
+    routes.handle(
+        hash_set![Method::POST],
+        "/dev/configuration/reload",
+        dev_handler::dev_configuration_reload,
+    );
 
     // DEPRECATED in ocaml but still used by python tests
     routes.handle(
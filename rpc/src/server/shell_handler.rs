@@ -11,7 +11,6 @@ use serde::Serialize;
 
 use crypto::hash::{chain_id_to_b58_string, ProtocolHash};
 use tezos_api::ffi::ProtocolRpcError;
-use tezos_messages::ts_to_rfc3339;
 use tezos_wrapper::service::{ProtocolError, ProtocolServiceError};
 
 use crate::helpers::{
@@ -21,12 +20,11 @@ use crate::server::{HResult, HasSingleValue, Params, Query, RpcServiceEnvironmen
 use crate::services::{base_services, stream_services};
 use crate::{
     empty,
-    encoding::{base_types::*, monitor::BootstrapInfo},
+    encoding::{base_types::*, chain::InvalidBlockInfo, monitor::IsBootstrappedInfo},
     helpers, make_json_response, make_json_stream_response, required_param,
     result_option_to_json_response, result_to_empty_json_response, result_to_json_response,
     services, ServiceResult,
 };
-use storage::BlockHeaderWithHash;
 
 #[derive(Serialize)]
 pub struct ErrorMessage {
@@ -39,22 +37,17 @@ pub async fn bootstrapped(
     _: Params,
     _: Query,
     env: RpcServiceEnvironment,
-) -> HResult {
-    let state_read = env.state().read().unwrap();
-
-    let bootstrap_info = match state_read.current_head().as_ref() {
-        Some(current_head) => {
-            let current_head: &BlockHeaderWithHash = &current_head;
-            let timestamp = ts_to_rfc3339(current_head.header.timestamp());
-            Ok(BootstrapInfo::new(
-                &current_head.hash,
-                TimeStamp::Rfc(timestamp),
-            ))
-        }
-        None => Err(format_err!("No current head")),
-    };
+) -> ServiceResult {
+    let RpcServiceEnvironment {
+        state,
+        bootstrap_state,
+        ..
+    } = env;
 
-    result_to_json_response(bootstrap_info, env.log())
+    make_json_stream_response(stream_services::BootstrappedMonitorStream::new(
+        state,
+        bootstrap_state,
+    ))
 }
 
 pub async fn commit_hash(
@@ -169,8 +162,24 @@ pub async fn blocks(
 ) -> ServiceResult {
     let chain_id = parse_chain_id(required_param!(params, "chain_id")?, &env)?;
     let length = query.get_str("length").unwrap_or("0");
+
+    // list branches from all known heads (current head and alternate heads)
+    if query.contains_key("heads") || !query.contains_key("head") {
+        let length = length.parse::<usize>()?.max(1);
+        let heads = base_services::get_known_heads(&chain_id, env.persistent_storage())?;
+        let mut branches = Vec::with_capacity(heads.len());
+        for head in heads {
+            branches.push(base_services::get_branch_block_hashes(
+                head.block_hash(),
+                length,
+                env.persistent_storage(),
+            )?);
+        }
+        return make_json_response(&branches);
+    }
+
     // TODO: mutliparameter
-    let head = parse_block_hash(&chain_id, query.get_str("head").unwrap(), &env)?;
+    let head = parse_block_hash(&chain_id, required_param!(query, "head")?, &env)?;
     // TODO: implement min_date query arg
 
     // TODO: This can be implemented in a more optimised and cleaner way
@@ -383,6 +392,47 @@ pub async fn get_chain_id(
     result_to_json_response(Ok(chain_id_to_b58_string(&chain_id)), env.log())
 }
 
+pub async fn chain_is_bootstrapped(
+    _: Request<Body>,
+    params: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    let _ = parse_chain_id(required_param!(params, "chain_id")?, &env)?;
+
+    let is_bootstrapped_info = env
+        .bootstrap_state()
+        .read()
+        .map(|bootstrap_state| {
+            IsBootstrappedInfo::new(
+                bootstrap_state.is_bootstrapped(),
+                bootstrap_state.sync_state(),
+            )
+        })
+        .map_err(|e| format_err!("Failed to lock bootstrap state: {}", e));
+
+    result_to_json_response(is_bootstrapped_info, env.log())
+}
+
+pub async fn invalid_blocks(
+    _: Request<Body>,
+    params: Params,
+    _: Query,
+    env: RpcServiceEnvironment,
+) -> ServiceResult {
+    let chain_id = parse_chain_id(required_param!(params, "chain_id")?, &env)?;
+
+    result_to_json_response(
+        base_services::get_invalid_blocks(&chain_id, env.persistent_storage()).map(|blocks| {
+            blocks
+                .into_iter()
+                .map(InvalidBlockInfo::from)
+                .collect::<Vec<_>>()
+        }),
+        env.log(),
+    )
+}
+
 pub async fn get_block_operation_hashes(
     _: Request<Body>,
     params: Params,
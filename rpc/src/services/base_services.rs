@@ -4,15 +4,18 @@
 use failure::bail;
 
 use crypto::hash::{BlockHash, ChainId};
+use shell::validation::fitness_comparator::FitnessWrapper;
 use storage::block_storage::BlockJsonData;
+use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::context::ContextApi;
 use storage::merkle_storage::StringTreeEntry;
 use storage::persistent::PersistentStorage;
 use storage::{
     context_key, BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
-    BlockStorageReader,
+    BlockStorageReader, ChainMetaStorage, InvalidBlock,
 };
 use tezos_messages::p2p::encoding::version::NetworkVersion;
+use tezos_messages::Head;
 
 use crate::helpers::{
     get_context_hash, BlockHeaderInfo, BlockHeaderShellInfo, BlockMetadata, FullBlockInfo,
@@ -46,6 +49,54 @@ where
     Ok(blocks)
 }
 
+/// Get known heads (current head and alternate heads), sorted by fitness from the best one
+pub(crate) fn get_known_heads(
+    chain_id: &ChainId,
+    persistent_storage: &PersistentStorage,
+) -> Result<Vec<Head>, failure::Error> {
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+    let mut heads = chain_meta_storage.get_alternate_heads(chain_id)?;
+    if let Some(current_head) = chain_meta_storage.get_current_head(chain_id)? {
+        heads.push(current_head);
+    }
+    heads.sort_by(|h1, h2| {
+        FitnessWrapper::new(h2.fitness()).cmp(&FitnessWrapper::new(h1.fitness()))
+    });
+    Ok(heads)
+}
+
+/// Get block hashes of branch from block down to its predecessors (at most `length` blocks)
+pub(crate) fn get_branch_block_hashes(
+    block_hash: &BlockHash,
+    length: usize,
+    persistent_storage: &PersistentStorage,
+) -> Result<Vec<String>, failure::Error> {
+    let block_storage = BlockStorage::new(persistent_storage);
+    let mut branch = Vec::with_capacity(length);
+    let mut next_block_hash = Some(block_hash.clone());
+    while let Some(block_hash) = next_block_hash.take() {
+        if branch.len() >= length {
+            break;
+        }
+        if let Some(block) = block_storage.get(&block_hash)? {
+            // genesis is its own predecessor
+            if block.header.level() > 0 {
+                next_block_hash = Some(block.header.predecessor().clone());
+            }
+        }
+        branch.push(block_hash.to_base58_check());
+    }
+    Ok(branch)
+}
+
+/// Get blocks, which were rejected by protocol
+pub(crate) fn get_invalid_blocks(
+    chain_id: &ChainId,
+    persistent_storage: &PersistentStorage,
+) -> Result<Vec<InvalidBlock>, failure::Error> {
+    Ok(ChainMetaStorage::new(persistent_storage).get_invalid_blocks(chain_id)?)
+}
+
 /// Get block metadata
 pub(crate) fn get_block_metadata(
     chain_id: &ChainId,
@@ -1,10 +1,17 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use failure::format_err;
+use serde::{Deserialize, Serialize};
 use slog::Logger;
 
 use crypto::hash::BlockHash;
+use networking::p2p::traffic::PeerTrafficStats;
+use shell::state::clock_state::NodeClock;
 use shell::stats::memory::{Memory, MemoryData, MemoryStatsResult};
+use shell::stats::metrics::MonitorMetricsRef;
+use shell::stats::write_runner_stats::{WriteRunnerStats, WriteRunnerStatsRef};
+use storage::backup::BackupManifest;
 use storage::context::{ContextApi, TezedgeContext};
 use storage::context_action_storage::{
     contract_id_to_contract_address_for_index, ContextActionFilters, ContextActionJson,
@@ -14,6 +21,8 @@ use storage::persistent::PersistentStorage;
 use storage::{ContextActionRecordValue, ContextActionStorage};
 use tezos_context::channel::ContextAction;
 use tezos_messages::base::rpc_support::UniversalValue;
+use tezos_wrapper::stats::ProtocolRunnerStats;
+use tezos_wrapper::TezosApiConnectionPool;
 
 use crate::helpers::{get_action_types, PagedResult};
 use crate::server::RpcServiceEnvironment;
@@ -112,12 +121,99 @@ pub(crate) fn get_stats_memory_protocol_runners() -> MemoryStatsResult<Vec<Memor
     memory.get_memory_stats_protocol_runners()
 }
 
+#[derive(Serialize)]
+pub struct PeerNetworkStats {
+    peer_id: String,
+    address: String,
+    traffic: PeerTrafficStats,
+}
+
+#[derive(Serialize)]
+pub struct NetworkStats {
+    /// Traffic of connected peers
+    peers: Vec<PeerNetworkStats>,
+    /// Traffic of all (also disconnected) peers
+    total: PeerTrafficStats,
+}
+
+/// Socket level traffic per peer and message type, as last collected by monitoring
+pub(crate) fn get_network_stats(
+    monitor_metrics: &MonitorMetricsRef,
+) -> Result<NetworkStats, failure::Error> {
+    let metrics = monitor_metrics
+        .read()
+        .map_err(|e| format_err!("Failed to lock monitor metrics: {}", e))?;
+
+    let mut total = metrics.disconnected_peers_traffic.clone();
+    let peers = metrics
+        .peers
+        .iter()
+        .map(|peer| {
+            total.merge(&peer.traffic);
+            PeerNetworkStats {
+                peer_id: peer.peer_id.clone(),
+                address: peer.address.clone(),
+                traffic: peer.traffic.clone(),
+            }
+        })
+        .collect();
+
+    Ok(NetworkStats { peers, total })
+}
+
+/// Restarts/replays of the supervised (write) protocol runner
+pub(crate) fn get_write_runner_stats(
+    write_runner_stats: &WriteRunnerStatsRef,
+) -> Result<WriteRunnerStats, failure::Error> {
+    let stats = write_runner_stats
+        .read()
+        .map_err(|e| format_err!("Failed to lock write runner stats: {}", e))?;
+    Ok(stats.clone())
+}
+
+#[derive(Serialize)]
+pub struct ProtocolRunnerPoolStats {
+    pool: String,
+    runners: Vec<ProtocolRunnerStats>,
+}
+
+/// Alive protocol runners of all pools (age, calls, errors, latency percentiles, memory)
+pub(crate) fn get_protocol_runner_stats(
+    env: &RpcServiceEnvironment,
+) -> Vec<ProtocolRunnerPoolStats> {
+    let pools: [&TezosApiConnectionPool; 4] = [
+        env.tezos_readonly_api(),
+        env.tezos_readonly_prevalidation_api(),
+        env.tezos_without_context_api(),
+        env.tezos_writeable_api(),
+    ];
+    pools
+        .iter()
+        .map(|pool| ProtocolRunnerPoolStats {
+            pool: pool.pool_name.clone(),
+            runners: pool.runner_stats(),
+        })
+        .collect()
+}
+
 pub(crate) fn get_context_stats(
     context: &TezedgeContext,
 ) -> Result<MerkleStorageStats, failure::Error> {
     Ok(context.get_merkle_stats()?)
 }
 
+pub(crate) fn backup_storage(
+    target_dir: &str,
+    persistent_storage: &PersistentStorage,
+    log: &Logger,
+) -> Result<BackupManifest, failure::Error> {
+    Ok(storage::backup::backup_storage(
+        persistent_storage,
+        target_dir,
+        log,
+    )?)
+}
+
 pub(crate) fn get_cycle_length_for_block(
     block_hash: &BlockHash,
     env: &RpcServiceEnvironment,
@@ -143,6 +239,84 @@ pub(crate) fn get_cycle_length_for_block(
     }
 }
 
+#[derive(Serialize)]
+pub struct NodeClockInfo {
+    /// Node's notion of "now" (RFC3339)
+    now: String,
+    /// Offset of node's "now" from the wall clock (in seconds)
+    offset_secs: i64,
+}
+
+/// Change of node's clock, exactly one of the fields is expected
+#[derive(Deserialize)]
+pub struct NodeClockUpdate {
+    /// Sets "now" to the timestamp (RFC3339)
+    timestamp: Option<String>,
+    /// Moves "now" forward to the timestamp (RFC3339), if it is behind
+    advance_to: Option<String>,
+    /// Moves "now" by seconds
+    advance_by: Option<i64>,
+    /// Resets clock back to the wall clock
+    #[serde(default)]
+    reset: bool,
+}
+
+pub(crate) fn get_node_clock(node_clock: &NodeClock) -> NodeClockInfo {
+    NodeClockInfo {
+        now: node_clock.now().to_rfc3339(),
+        offset_secs: node_clock.offset_secs(),
+    }
+}
+
+/// Sets/advances/resets node's notion of "now" (sandbox only)
+pub(crate) fn update_node_clock(
+    node_clock: &NodeClock,
+    update: NodeClockUpdate,
+    log: &Logger,
+) -> Result<NodeClockInfo, failure::Error> {
+    let parse_timestamp = |timestamp: &str| {
+        chrono::DateTime::parse_from_rfc3339(timestamp)
+            .map(|timestamp| timestamp.timestamp())
+            .map_err(|e| format_err!("Invalid timestamp: {}, reason: {}", timestamp, e))
+    };
+
+    match update {
+        NodeClockUpdate {
+            timestamp: Some(timestamp),
+            advance_to: None,
+            advance_by: None,
+            reset: false,
+        } => node_clock.set_now(parse_timestamp(&timestamp)?),
+        NodeClockUpdate {
+            timestamp: None,
+            advance_to: Some(timestamp),
+            advance_by: None,
+            reset: false,
+        } => node_clock.advance_to(parse_timestamp(&timestamp)?),
+        NodeClockUpdate {
+            timestamp: None,
+            advance_to: None,
+            advance_by: Some(secs),
+            reset: false,
+        } => node_clock.advance(secs),
+        NodeClockUpdate {
+            timestamp: None,
+            advance_to: None,
+            advance_by: None,
+            reset: true,
+        } => node_clock.reset(),
+        _ => {
+            return Err(format_err!(
+                "Expected exactly one of: timestamp, advance_to, advance_by, reset"
+            ))
+        }
+    }
+
+    let info = get_node_clock(node_clock);
+    slog::info!(log, "Node clock changed"; "now" => &info.now, "offset_secs" => info.offset_secs);
+    Ok(info)
+}
+
 pub(crate) fn get_dev_version() -> Result<String, failure::Error> {
     let version_env: &'static str = env!("CARGO_PKG_VERSION");
 
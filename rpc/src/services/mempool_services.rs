@@ -208,7 +208,7 @@ pub fn inject_operation(
         &operation_hash,
         &operation,
         env.current_mempool_state_storage().clone(),
-        &env.tezos_readonly_prevalidation_api().pool.get()?.api,
+        &env.tezos_readonly_prevalidation_api().pool().get()?.api,
         &block_storage,
         &block_meta_storage,
     )?;
@@ -346,7 +346,7 @@ pub fn inject_block(
     let paths = if let Some(vps) = validation_passes.as_ref() {
         let response = env
             .tezos_without_context_api()
-            .pool
+            .pool()
             .get()?
             .api
             .compute_path(vps.try_into()?)?;
@@ -0,0 +1,365 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Collects node internals as metrics for `/metrics` endpoint.
+//!
+//! Metric names and labels are part of the api for alerting, so they should not be renamed.
+//! Every source is collected independently, failed source is just logged and skipped.
+
+use slog::{warn, Logger};
+
+use storage::context::ContextApi;
+use storage::persistent::StorageType;
+use storage::storage_backend::StorageBackend;
+use tezos_wrapper::TezosApiConnectionPool;
+
+use crate::encoding::open_metrics::{Labels, MetricFamily};
+use crate::server::RpcServiceEnvironment;
+
+/// Nanoseconds in one second, merkle latencies are measured in nanoseconds
+const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+pub(crate) fn collect_metrics(env: &RpcServiceEnvironment) -> Vec<MetricFamily> {
+    let mut families = Vec::new();
+    collect_monitor_metrics(env, &mut families);
+    collect_chain_metrics(env, &mut families);
+    collect_block_application_metrics(env, &mut families);
+    collect_mempool_metrics(env, &mut families);
+    collect_merkle_metrics(env, &mut families);
+    collect_storage_metrics(env, &mut families);
+    collect_protocol_runner_pool_metrics(env, &mut families);
+    collect_write_runner_metrics(env, &mut families);
+    families
+}
+
+fn labels(labels: &[(&'static str, &str)]) -> Labels {
+    labels
+        .iter()
+        .map(|(name, value)| (*name, value.to_string()))
+        .collect()
+}
+
+fn collect_failed(log: &Logger, source: &str, reason: impl std::fmt::Display) {
+    warn!(log, "Failed to collect metrics"; "source" => source, "reason" => format!("{}", reason));
+}
+
+/// Peers and bootstrap pipeline (collected by monitoring)
+fn collect_monitor_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let metrics = match env.monitor_metrics().read() {
+        Ok(metrics) => metrics,
+        Err(e) => return collect_failed(env.log(), "monitor", e),
+    };
+
+    families.push(
+        MetricFamily::gauge("tezedge_p2p_peers_connected", "Count of connected peers")
+            .with_value(metrics.peers.len() as f64),
+    );
+
+    let mut peer_received = MetricFamily::counter(
+        "tezedge_p2p_peer_received_bytes",
+        "Bytes received from connected peer",
+    );
+    let mut peer_sent = MetricFamily::counter(
+        "tezedge_p2p_peer_sent_bytes",
+        "Bytes sent to connected peer",
+    );
+    let mut total = metrics.disconnected_peers_traffic.clone();
+    for peer in &metrics.peers {
+        let peer_labels = labels(&[
+            ("peer_id", peer.peer_id.as_str()),
+            ("address", peer.address.as_str()),
+        ]);
+        peer_received.add(peer_labels.clone(), peer.traffic.inbound.total.bytes as f64);
+        peer_sent.add(peer_labels, peer.traffic.outbound.total.bytes as f64);
+        total.merge(&peer.traffic);
+    }
+    families.push(peer_received);
+    families.push(peer_sent);
+
+    families.push(
+        MetricFamily::counter(
+            "tezedge_p2p_received_bytes",
+            "Bytes received from all (also disconnected) peers",
+        )
+        .with_value(total.inbound.total.bytes as f64),
+    );
+    families.push(
+        MetricFamily::counter(
+            "tezedge_p2p_sent_bytes",
+            "Bytes sent to all (also disconnected) peers",
+        )
+        .with_value(total.outbound.total.bytes as f64),
+    );
+
+    let mut messages = MetricFamily::counter(
+        "tezedge_p2p_messages",
+        "Messages exchanged with all (also disconnected) peers by message type",
+    );
+    let mut message_bytes = MetricFamily::counter(
+        "tezedge_p2p_message_bytes",
+        "Bytes exchanged with all (also disconnected) peers by message type",
+    );
+    for (direction, traffic) in &[("inbound", &total.inbound), ("outbound", &total.outbound)] {
+        for (message_type, counter) in &traffic.by_message_type {
+            let message_labels =
+                labels(&[("direction", *direction), ("message_type", *message_type)]);
+            messages.add(message_labels.clone(), counter.messages as f64);
+            message_bytes.add(message_labels, counter.bytes as f64);
+        }
+    }
+    families.push(messages);
+    families.push(message_bytes);
+
+    families.push(
+        MetricFamily::gauge(
+            "tezedge_bootstrap_remote_level",
+            "Highest block level announced by peers",
+        )
+        .with_value(metrics.bootstrap.remote_level as f64),
+    );
+    let mut pipeline = MetricFamily::counter(
+        "tezedge_bootstrap_blocks",
+        "Blocks which passed the bootstrap pipeline stage",
+    );
+    pipeline.add(
+        labels(&[("stage", "header_received")]),
+        metrics.bootstrap.received_headers as f64,
+    );
+    pipeline.add(
+        labels(&[("stage", "operations_downloaded")]),
+        metrics.bootstrap.downloaded_operations as f64,
+    );
+    pipeline.add(
+        labels(&[("stage", "applied")]),
+        metrics.bootstrap.applied_blocks as f64,
+    );
+    families.push(pipeline);
+}
+
+fn collect_chain_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let state = match env.state().read() {
+        Ok(state) => state,
+        Err(e) => return collect_failed(env.log(), "chain", e),
+    };
+
+    if let Some(current_head) = state.current_head() {
+        families.push(
+            MetricFamily::gauge(
+                "tezedge_chain_current_head_level",
+                "Level of the local current head",
+            )
+            .with_value(current_head.header.level() as f64),
+        );
+    }
+}
+
+fn collect_block_application_metrics(
+    env: &RpcServiceEnvironment,
+    families: &mut Vec<MetricFamily>,
+) {
+    let stats = match env.apply_block_stats().read() {
+        Ok(stats) => stats,
+        Err(e) => return collect_failed(env.log(), "block_application", e),
+    };
+
+    families.push(
+        MetricFamily::counter("tezedge_block_applied", "Blocks applied by protocol")
+            .with_value(*stats.applied_block_total_count() as f64),
+    );
+    if let Some(level) = stats.applied_block_level() {
+        families.push(
+            MetricFamily::gauge(
+                "tezedge_block_last_applied_level",
+                "Level of the last applied block",
+            )
+            .with_value(*level as f64),
+        );
+    }
+
+    let mut durations = MetricFamily::histogram(
+        "tezedge_block_application_duration_seconds",
+        "Duration of the block application phase",
+    );
+    for (phase, histogram) in stats.applied_block_histograms().phases().iter() {
+        durations.add_histogram(
+            labels(&[("phase", *phase)]),
+            &histogram.cumulative_buckets(),
+            histogram.sum(),
+            histogram.count(),
+        );
+    }
+    families.push(durations);
+}
+
+fn collect_mempool_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let mempool = match env.current_mempool_state_storage().read() {
+        Ok(mempool) => mempool,
+        Err(e) => return collect_failed(env.log(), "mempool", e),
+    };
+
+    let result = mempool.result();
+    let mut operations = MetricFamily::gauge(
+        "tezedge_mempool_operations",
+        "Operations in mempool by validation status",
+    );
+    for (status, count) in &[
+        ("applied", result.applied.len()),
+        ("refused", result.refused.len()),
+        ("branch_refused", result.branch_refused.len()),
+        ("branch_delayed", result.branch_delayed.len()),
+        ("pending", mempool.pending().len()),
+    ] {
+        operations.add(labels(&[("status", *status)]), *count as f64);
+    }
+    families.push(operations);
+}
+
+fn collect_merkle_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let stats = match env.tezedge_context().get_merkle_stats() {
+        Ok(stats) => stats,
+        Err(e) => return collect_failed(env.log(), "merkle", e),
+    };
+
+    let mut durations = MetricFamily::summary(
+        "tezedge_merkle_operation_duration_seconds",
+        "Duration of merkle storage operation",
+    );
+    let mut max_durations = MetricFamily::gauge(
+        "tezedge_merkle_operation_max_duration_seconds",
+        "The longest duration of merkle storage operation",
+    );
+    let mut operations: Vec<_> = stats.perf_stats.global.iter().collect();
+    operations.sort_by(|(op1, _), (op2, _)| op1.cmp(op2));
+    for (operation, latencies) in operations {
+        if latencies.op_exec_times == 0 {
+            continue;
+        }
+        durations.add_summary(
+            labels(&[("operation", operation.as_str())]),
+            latencies.cumul_op_exec_time() / NANOS_PER_SEC,
+            latencies.op_exec_times,
+        );
+        max_durations.add(
+            labels(&[("operation", operation.as_str())]),
+            latencies.op_exec_time_max / NANOS_PER_SEC,
+        );
+    }
+    families.push(durations);
+    families.push(max_durations);
+}
+
+fn collect_storage_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let mut mem_table_total = MetricFamily::gauge(
+        "tezedge_storage_mem_table_total_bytes",
+        "Approximate size of active and unflushed immutable memtables",
+    );
+    let mut mem_table_unflushed = MetricFamily::gauge(
+        "tezedge_storage_mem_table_unflushed_bytes",
+        "Approximate size of unflushed immutable memtables",
+    );
+    let mut mem_table_readers_total = MetricFamily::gauge(
+        "tezedge_storage_mem_table_readers_total_bytes",
+        "Memory used by table readers (indexes and filter blocks)",
+    );
+    let mut cache_total = MetricFamily::gauge(
+        "tezedge_storage_cache_total_bytes",
+        "Memory used by block cache",
+    );
+
+    for (db, storage_type) in vec![
+        ("database", StorageType::Database),
+        ("context", StorageType::Context),
+        ("context_actions", StorageType::ContextAction),
+    ] {
+        let kv = env.persistent_storage().kv(storage_type);
+        let stats = match kv.get_mem_use_stats() {
+            Ok(stats) => stats,
+            Err(e) => {
+                collect_failed(env.log(), db, e);
+                continue;
+            }
+        };
+        let db_labels = labels(&[("db", db), ("backend", kv.name())]);
+        mem_table_total.add(db_labels.clone(), stats.mem_table_total as f64);
+        mem_table_unflushed.add(db_labels.clone(), stats.mem_table_unflushed as f64);
+        mem_table_readers_total.add(db_labels.clone(), stats.mem_table_readers_total as f64);
+        cache_total.add(db_labels, stats.cache_total as f64);
+    }
+
+    families.push(mem_table_total);
+    families.push(mem_table_unflushed);
+    families.push(mem_table_readers_total);
+    families.push(cache_total);
+}
+
+fn collect_protocol_runner_pool_metrics(
+    env: &RpcServiceEnvironment,
+    families: &mut Vec<MetricFamily>,
+) {
+    let mut connections = MetricFamily::gauge(
+        "tezedge_protocol_runner_pool_connections",
+        "Protocol runner connections (sub-processes) by state",
+    );
+    let mut max_connections = MetricFamily::gauge(
+        "tezedge_protocol_runner_pool_max_connections",
+        "Maximal count of protocol runner connections",
+    );
+
+    let pools: [&TezosApiConnectionPool; 4] = [
+        env.tezos_readonly_api(),
+        env.tezos_readonly_prevalidation_api(),
+        env.tezos_without_context_api(),
+        env.tezos_writeable_api(),
+    ];
+    for pool in pools.iter() {
+        let r2d2_pool = pool.pool();
+        let state = r2d2_pool.state();
+        let active = state.connections.saturating_sub(state.idle_connections);
+        connections.add(
+            labels(&[("pool", pool.pool_name.as_str()), ("state", "active")]),
+            active as f64,
+        );
+        connections.add(
+            labels(&[("pool", pool.pool_name.as_str()), ("state", "idle")]),
+            state.idle_connections as f64,
+        );
+        max_connections.add(
+            labels(&[("pool", pool.pool_name.as_str())]),
+            r2d2_pool.max_size() as f64,
+        );
+    }
+
+    families.push(connections);
+    families.push(max_connections);
+}
+
+/// Supervised (write) protocol runner, which applies blocks
+fn collect_write_runner_metrics(env: &RpcServiceEnvironment, families: &mut Vec<MetricFamily>) {
+    let stats = match env.write_runner_stats().read() {
+        Ok(stats) => stats,
+        Err(e) => return collect_failed(env.log(), "write_runner", e),
+    };
+
+    let mut restarts = MetricFamily::counter(
+        "tezedge_protocol_runner_write_restarts",
+        "Restarts of the write protocol runner by reason",
+    );
+    restarts.add(labels(&[("reason", "crash")]), stats.crashes as f64);
+    restarts.add(labels(&[("reason", "hang")]), stats.hangs as f64);
+    families.push(restarts);
+
+    families.push(
+        MetricFamily::counter(
+            "tezedge_protocol_runner_write_replayed_blocks",
+            "Blocks replayed to the restarted write protocol runner",
+        )
+        .with_value(stats.replayed_blocks as f64),
+    );
+    families.push(
+        MetricFamily::counter(
+            "tezedge_protocol_runner_write_abandoned_blocks",
+            "Blocks not applied, because write protocol runner failed too many times",
+        )
+        .with_value(stats.abandoned_blocks as f64),
+    );
+}
@@ -6,6 +6,7 @@
 pub mod base_services;
 pub mod dev_services;
 pub mod mempool_services;
+pub mod metrics_services;
 pub mod protocol;
 pub mod stats_services;
 pub mod stream_services;
@@ -452,7 +452,7 @@ pub(crate) fn call_protocol_rpc(
     // TODO: retry?
     let response = env
         .tezos_readonly_api()
-        .pool
+        .pool()
         .get()?
         .api
         .call_protocol_rpc(request)?;
@@ -473,7 +473,7 @@ pub(crate) fn preapply_operations(
     // TODO: retry?
     let response = env
         .tezos_readonly_api()
-        .pool
+        .pool()
         .get()?
         .api
         .helpers_preapply_operations(request)?;
@@ -515,7 +515,7 @@ pub(crate) fn preapply_block(
     // TODO: retry?
     let response = env
         .tezos_readonly_api()
-        .pool
+        .pool()
         .get()?
         .api
         .helpers_preapply_block(request)?;
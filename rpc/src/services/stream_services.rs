@@ -14,9 +14,13 @@ use tokio::time::{Duration, Instant};
 
 use crypto::hash::{BlockHash, ChainId, ProtocolHash};
 use shell::mempool::CurrentMempoolStateStorageRef;
+use shell::state::synchronization_state::SynchronizationBootstrapStateRef;
 use storage::persistent::PersistentStorage;
 use storage::{BlockHeaderWithHash, BlockStorage, BlockStorageReader};
+use tezos_messages::ts_to_rfc3339;
 
+use crate::encoding::base_types::TimeStamp;
+use crate::encoding::monitor::BootstrapInfo;
 use crate::helpers::{BlockHeaderInfo, FullBlockInfo};
 use crate::rpc_actor::RpcCollectedStateRef;
 use crate::services::mempool_services::get_pending_operations;
@@ -416,3 +420,106 @@ impl Stream for OperationMonitorStream {
 }
 
 // TODO: add tests for both Streams!
+
+/// Streams new current heads till node is bootstrapped, stream ends with the head, on which node is bootstrapped
+pub struct BootstrappedMonitorStream {
+    state: RpcCollectedStateRef,
+    bootstrap_state: SynchronizationBootstrapStateRef,
+    last_checked_head: Option<BlockHash>,
+    delay: Option<Interval>,
+    done: bool,
+}
+
+impl BootstrappedMonitorStream {
+    pub fn new(
+        state: RpcCollectedStateRef,
+        bootstrap_state: SynchronizationBootstrapStateRef,
+    ) -> Self {
+        Self {
+            state,
+            bootstrap_state,
+            last_checked_head: None,
+            delay: None,
+            done: false,
+        }
+    }
+
+    fn yield_head(current_head: &BlockHeaderWithHash) -> Result<String, failure::Error> {
+        let bootstrap_info = BootstrapInfo::new(
+            &current_head.hash,
+            TimeStamp::Rfc(ts_to_rfc3339(current_head.header.timestamp())),
+        );
+
+        // serialize the struct to a json string to yield by the stream
+        let mut head_string = serde_json::to_string(&bootstrap_info)?;
+
+        // push a newline character to the stream
+        head_string.push('\n');
+
+        Ok(head_string)
+    }
+}
+
+impl Stream for BootstrappedMonitorStream {
+    type Item = Result<String, failure::Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<String, failure::Error>>> {
+        // Note: the stream ends, when node is bootstrapped
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        // create or get a delay future, that blocks for MONITOR_TIMER_MILIS
+        let delay = self.delay.get_or_insert_with(|| {
+            interval_at(Instant::now(), Duration::from_millis(MONITOR_TIMER_MILIS))
+        });
+
+        // poll the delay future
+        match delay.poll_tick(cx) {
+            Poll::Pending => Poll::Pending,
+            _ => {
+                // get rid of the used delay
+                self.delay = None;
+
+                let current_head = self.state.read().unwrap().current_head().clone();
+                let is_bootstrapped = match self.bootstrap_state.read() {
+                    Ok(bootstrap_state) => bootstrap_state.is_bootstrapped(),
+                    Err(e) => {
+                        return Poll::Ready(Some(Err(format_err!(
+                            "Failed to lock bootstrap state: {}",
+                            e
+                        ))))
+                    }
+                };
+
+                let current_head = match current_head {
+                    Some(current_head) => current_head,
+                    None => {
+                        // No current head found, storage not ready yet, wait
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                };
+
+                let head_changed = self.last_checked_head.as_ref() != Some(&current_head.hash);
+                if is_bootstrapped {
+                    // last one, if not already yielded
+                    self.done = true;
+                    if !head_changed {
+                        return Poll::Ready(None);
+                    }
+                } else if !head_changed {
+                    // current head not changed, yield nothing
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+
+                self.last_checked_head = Some(current_head.hash.clone());
+                Poll::Ready(Some(Self::yield_head(&current_head)))
+            }
+        }
+    }
+}
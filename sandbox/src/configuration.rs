@@ -9,8 +9,14 @@ pub struct LauncherEnvironment {
     pub light_node_path: PathBuf,
     pub protocol_runner_path: PathBuf,
     pub log_level: slog::Level,
-    pub sandbox_rpc_port: u16,
-    pub tezos_client_path: PathBuf,
+    /// Not required, when just scenarios are run
+    pub sandbox_rpc_port: Option<u16>,
+    /// If not set, blocks are baked natively (without tezos-client)
+    pub tezos_client_path: Option<PathBuf>,
+    /// Scenario files to run (launcher exits after all scenarios are finished)
+    pub scenarios: Vec<PathBuf>,
+    /// JUnit XML report of scenarios
+    pub scenario_report: Option<PathBuf>,
 }
 
 macro_rules! parse_validator_fn {
@@ -65,8 +71,7 @@ fn sandbox_app() -> App<'static, 'static> {
                 .long("tezos-client-path")
                 .takes_value(true)
                 .value_name("PATH")
-                .help("Path to the tezos-client binary")
-                .required(true)
+                .help("Path to the tezos-client binary (optional, if not set, wallets, protocol activation and baking are handled natively by the launcher)")
                 .validator(|v| {
                     if Path::new(&v).exists() {
                         Ok(())
@@ -89,11 +94,35 @@ fn sandbox_app() -> App<'static, 'static> {
                 .takes_value(true)
                 .value_name("PORT")
                 .help("Rust server RPC port for communication with rust node")
-                .required(true)
+                .required_unless("scenario")
                 .validator(parse_validator_fn!(
                     u16,
                     "Value must be a valid port number"
                 )),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PATH")
+                .help("Path to the scenario file (YAML or JSON), if set, scenarios are run and launcher exits (can be used multiple times)")
+                .validator(|v| {
+                    if Path::new(&v).exists() {
+                        Ok(())
+                    } else {
+                        Err(format!("Scenario file not found at '{}'", v))
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("scenario-report")
+                .long("scenario-report")
+                .takes_value(true)
+                .value_name("PATH")
+                .requires("scenario")
+                .help("Path to the JUnit XML report of scenarios"),
         );
     app
 }
@@ -119,16 +148,29 @@ impl LauncherEnvironment {
                 .unwrap_or("")
                 .parse::<slog::Level>()
                 .expect("Was expecting one value from slog::Level"),
-            sandbox_rpc_port: args
-                .value_of("sandbox-rpc-port")
-                .unwrap_or("")
-                .parse::<u16>()
-                .expect("Was expecting value of sandbox-rpc-port"),
-            tezos_client_path: args
-                .value_of("tezos-client-path")
-                .unwrap_or("")
-                .parse::<PathBuf>()
-                .expect("Provided value cannot be converted to path"),
+            sandbox_rpc_port: args.value_of("sandbox-rpc-port").map(|port| {
+                port.parse::<u16>()
+                    .expect("Was expecting value of sandbox-rpc-port")
+            }),
+            tezos_client_path: args.value_of("tezos-client-path").map(|path| {
+                path.parse::<PathBuf>()
+                    .expect("Provided value cannot be converted to path")
+            }),
+            scenarios: args
+                .values_of("scenario")
+                .map(|paths| {
+                    paths
+                        .map(|path| {
+                            path.parse::<PathBuf>()
+                                .expect("Provided value cannot be converted to path")
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            scenario_report: args.value_of("scenario-report").map(|path| {
+                path.parse::<PathBuf>()
+                    .expect("Provided value cannot be converted to path")
+            }),
         }
     }
 }
@@ -9,13 +9,15 @@ use warp::Filter;
 
 use crate::handlers::{
     activate_protocol, bake_block_with_client, bake_block_with_client_arbitrary, get_wallets,
-    handle_rejection, init_client_data, list_nodes, resolve_node_from_request,
-    start_node_with_config, stop_node,
+    handle_rejection, heal_link, init_client_data, kill_node, list_nodes, partition_link,
+    resolve_node_from_request, restart_node, start_node_with_config, start_nodes_in_topology,
+    stop_node, update_clock, NodeQuery,
 };
 use crate::node_runner::{LightNodeRunnerRef, NodeRpcIpPort};
 use crate::tezos_client_runner::{
     BakeRequest, SandboxWallets, TezosClientRunnerRef, TezosProtcolActivationParameters,
 };
+use crate::topology::{LinkRequest, TopologyRequest};
 
 pub fn sandbox(
     log: Logger,
@@ -35,13 +37,23 @@ pub fn sandbox(
         client_runner.clone(),
         peers.clone(),
     )
+    .or(start_topology(
+        log.clone(),
+        runner.clone(),
+        client_runner.clone(),
+        peers.clone(),
+    ))
     .or(stop(
         log.clone(),
-        runner,
+        runner.clone(),
         client_runner.clone(),
         peers.clone(),
     ))
-    .or(list(log.clone(), peers.clone()))
+    .or(kill(log.clone(), runner.clone(), peers.clone()))
+    .or(restart(log.clone(), runner.clone(), peers.clone()))
+    .or(list(log.clone(), runner.clone()))
+    .or(partition(log.clone(), runner.clone()))
+    .or(heal(log.clone(), runner))
     .or(init_client(
         log.clone(),
         client_runner.clone(),
@@ -50,7 +62,8 @@ pub fn sandbox(
     .or(wallets(log.clone(), client_runner.clone(), peers.clone()))
     .or(activate(log.clone(), client_runner.clone(), peers.clone()))
     .or(bake(log.clone(), client_runner.clone(), peers.clone()))
-    .or(bake_random(log.clone(), client_runner, peers))
+    .or(bake_random(log.clone(), client_runner, peers.clone()))
+    .or(clock(log.clone(), peers))
     .recover(move |rejection| handle_rejection(rejection, log.clone()))
     .with(cors)
 }
@@ -71,6 +84,46 @@ pub fn start(
         .and_then(start_node_with_config)
 }
 
+pub fn start_topology(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+    client_runner: TezosClientRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("start_topology")
+        .and(warp::post())
+        .and(topology_json_body())
+        .and(with_log(log))
+        .and(with_runner(runner))
+        .and(with_client_runner(client_runner))
+        .and(with_peers(peers))
+        .and_then(start_nodes_in_topology)
+}
+
+pub fn partition(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("partition")
+        .and(warp::post())
+        .and(link_json_body())
+        .and(with_log(log))
+        .and(with_runner(runner))
+        .and_then(partition_link)
+}
+
+pub fn heal(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("heal")
+        .and(warp::post())
+        .and(link_json_body())
+        .and(with_log(log))
+        .and(with_runner(runner))
+        .and_then(heal_link)
+}
+
 pub fn stop(
     log: Logger,
     runner: LightNodeRunnerRef,
@@ -87,14 +140,40 @@ pub fn stop(
         .and_then(stop_node)
 }
 
-pub fn list(
+pub fn kill(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("kill")
+        .and(warp::get())
+        .and(with_log(log))
+        .and(with_runner(runner))
+        .and(with_peer(peers))
+        .and_then(kill_node)
+}
+
+pub fn restart(
     log: Logger,
+    runner: LightNodeRunnerRef,
     peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("restart")
+        .and(warp::get())
+        .and(with_log(log))
+        .and(with_runner(runner))
+        .and(with_peer(peers))
+        .and_then(restart_node)
+}
+
+pub fn list(
+    log: Logger,
+    runner: LightNodeRunnerRef,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("list_nodes")
         .and(warp::get())
         .and(with_log(log))
-        .and(with_peers(peers))
+        .and(with_runner(runner))
         .and_then(list_nodes)
 }
 
@@ -149,6 +228,7 @@ pub fn bake(
         .and(bake_json_body())
         .and(with_log(log))
         .and(with_client_runner(client_runner))
+        .and(with_peers(peers.clone()))
         .and(with_peer(peers))
         .and_then(bake_block_with_client)
 }
@@ -162,16 +242,42 @@ pub fn bake_random(
         .and(warp::get())
         .and(with_log(log))
         .and(with_client_runner(client_runner))
+        .and(with_peers(peers.clone()))
         .and(with_peer(peers))
         .and_then(bake_block_with_client_arbitrary)
 }
 
+pub fn clock(
+    log: Logger,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("clock")
+        .and(warp::post())
+        .and(json_body())
+        .and(with_log(log))
+        .and(with_peers(peers))
+        .and_then(update_clock)
+}
+
 fn json_body() -> impl Filter<Extract = (serde_json::Value,), Error = warp::Rejection> + Clone {
     // When accepting a body, we want a JSON body
     // (and to reject huge payloads)...
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
 
+fn topology_json_body() -> impl Filter<Extract = (TopologyRequest,), Error = warp::Rejection> + Clone
+{
+    // When accepting a body, we want a JSON body with the deserialized TopologyRequest
+    // (and to reject huge payloads)...
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn link_json_body() -> impl Filter<Extract = (LinkRequest,), Error = warp::Rejection> + Clone {
+    // When accepting a body, we want a JSON body with the deserialized LinkRequest
+    // (and to reject huge payloads)...
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
 fn init_client_json_body(
 ) -> impl Filter<Extract = (SandboxWallets,), Error = warp::Rejection> + Clone {
     // When accepting a body, we want a JSON body
@@ -217,9 +323,12 @@ fn with_peers(
     warp::any().map(move || peers.clone())
 }
 
-// TODO: this should resolve peer from request somehow (header, param,...)
+/// Resolves peer from request (optional query parameter `node` with rpc port)
 fn with_peer(
     peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
-) -> impl Filter<Extract = (Option<NodeRpcIpPort>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || resolve_node_from_request(peers.clone()))
+) -> impl Filter<Extract = (Option<NodeRpcIpPort>,), Error = warp::Rejection> + Clone {
+    warp::query::<NodeQuery>().and_then(move |query| {
+        let peers = peers.clone();
+        async move { resolve_node_from_request(peers, query) }
+    })
 }
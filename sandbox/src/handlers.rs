@@ -6,17 +6,19 @@ use std::fmt;
 use std::sync::{Arc, Mutex};
 use std::{collections::HashSet, sync::PoisonError};
 
-use itertools::Itertools;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use slog::{error, info, Logger};
 use warp::http::StatusCode;
 use warp::{reject, Rejection, Reply};
 
+use crate::native_baker::NativeBaker;
 use crate::node_runner::{LightNodeRunnerError, LightNodeRunnerRef, NodeRpcIpPort};
 use crate::tezos_client_runner::{
-    reply_with_client_output, BakeRequest, SandboxWallets, TezosClientRunnerError,
-    TezosClientRunnerRef, TezosProtcolActivationParameters,
+    parse_secret_key, reply_with_client_output, BakeRequest, SandboxWallets,
+    TezosClientRunnerError, TezosClientRunnerRef, TezosProtcolActivationParameters,
+    ACTIVATOR_SECRET_KEY,
 };
+use crate::topology::{LinkRequest, TopologyRequest};
 
 #[derive(Debug, Serialize, Clone)]
 pub struct ErrorMessage {
@@ -110,6 +112,85 @@ pub async fn start_node_with_config(
     ))
 }
 
+/// Handler for start_topology endpoint
+pub async fn start_nodes_in_topology(
+    request: TopologyRequest,
+    log: Logger,
+    runner: LightNodeRunnerRef,
+    client_runner: TezosClientRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to start light nodes in topology"; "nodes" => request.nodes, "topology" => format!("{:?}", request.topology));
+
+    // aquire a write lock to the runner
+    let mut runner = runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on runner"))?;
+
+    // spawn the nodes
+    info!(log, "Starting light-nodes...");
+    let nodes = runner.spawn_topology(request, &log)?;
+
+    // initialize data for tezos client and store nodes
+    let mut client_runner = client_runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
+    let mut peers = peers
+        .lock()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on peers"))?;
+    for (node_ref, data_dir) in nodes {
+        info!(log, "Initializing tezos-client data  for light-node"; "node_ref" => format!("{}", &node_ref));
+        client_runner.init_sandbox_data(node_ref.clone(), data_dir);
+        peers.insert(node_ref);
+    }
+
+    info!(log, "Light-nodes started successfully!");
+    Ok(warp::reply::with_status(
+        warp::reply::json(&runner.nodes_info()),
+        StatusCode::OK,
+    ))
+}
+
+/// Handler for partition endpoint
+pub async fn partition_link(
+    request: LinkRequest,
+    log: Logger,
+    runner: LightNodeRunnerRef,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to partition link between sandbox nodes"; "node_a" => request.node_a, "node_b" => request.node_b);
+
+    let mut runner = runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on runner"))?;
+    runner.partition(&request)?;
+
+    info!(log, "Link between sandbox nodes partitioned"; "node_a" => request.node_a, "node_b" => request.node_b);
+    Ok(warp::reply::with_status(
+        warp::reply::json(&""),
+        StatusCode::OK,
+    ))
+}
+
+/// Handler for heal endpoint
+pub async fn heal_link(
+    request: LinkRequest,
+    log: Logger,
+    runner: LightNodeRunnerRef,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to heal link between sandbox nodes"; "node_a" => request.node_a, "node_b" => request.node_b);
+
+    let mut runner = runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on runner"))?;
+    runner.heal(&request)?;
+
+    info!(log, "Link between sandbox nodes healed"; "node_a" => request.node_a, "node_b" => request.node_b);
+    Ok(warp::reply::with_status(
+        warp::reply::json(&""),
+        StatusCode::OK,
+    ))
+}
+
 pub async fn stop_node(
     log: Logger,
     runner: LightNodeRunnerRef,
@@ -161,20 +242,59 @@ pub async fn stop_node(
     }
 }
 
+/// Handler for kill endpoint (node is killed without cleanup, so it can be restarted)
+pub async fn kill_node(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+    node_ref: Option<NodeRpcIpPort>,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to kill the sandbox node"; "node_ref" => format!("{:?}", &node_ref));
+
+    let node_ref = ensure_node(node_ref)?;
+    runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on runner"))?
+        .kill(&node_ref)?;
+
+    info!(log, "Sandbox node killed"; "node_ref" => format!("{}", &node_ref));
+    Ok(warp::reply::with_status(
+        warp::reply::json(&""),
+        StatusCode::OK,
+    ))
+}
+
+/// Handler for restart endpoint
+pub async fn restart_node(
+    log: Logger,
+    runner: LightNodeRunnerRef,
+    node_ref: Option<NodeRpcIpPort>,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to restart the sandbox node"; "node_ref" => format!("{:?}", &node_ref));
+
+    let node_ref = ensure_node(node_ref)?;
+    runner
+        .write()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on runner"))?
+        .restart(&node_ref)?;
+
+    info!(log, "Sandbox node restarted"; "node_ref" => format!("{}", &node_ref));
+    Ok(warp::reply::with_status(
+        warp::reply::json(&node_ref),
+        StatusCode::OK,
+    ))
+}
+
 pub async fn list_nodes(
     log: Logger,
-    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+    runner: LightNodeRunnerRef,
 ) -> Result<impl warp::Reply, reject::Rejection> {
     info!(log, "Received request to list sandbox nodes...");
 
     // return all nodes
-    let mut nodes = peers
-        .lock()
-        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on peers"))?
-        .iter()
-        .cloned()
-        .collect_vec();
-    nodes.sort_by_key(|k| k.port);
+    let nodes = runner
+        .read()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on runner"))?
+        .nodes_info();
 
     Ok(warp::reply::with_status(
         warp::reply::json(&nodes),
@@ -222,7 +342,7 @@ pub async fn get_wallets(
 }
 
 pub async fn activate_protocol(
-    activation_parameters: TezosProtcolActivationParameters,
+    mut activation_parameters: TezosProtcolActivationParameters,
     log: Logger,
     client_runner: TezosClientRunnerRef,
     node_ref: Option<NodeRpcIpPort>,
@@ -230,10 +350,29 @@ pub async fn activate_protocol(
     info!(log, "Received request to activate the protocol");
 
     let node_ref = ensure_node(node_ref)?;
-    let client_runner = client_runner
-        .read()
-        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
-    let client_output = client_runner.activate_protocol(activation_parameters, &node_ref)?;
+    {
+        let client_runner = client_runner
+            .read()
+            .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
+        if !client_runner.is_native() {
+            let client_output =
+                client_runner.activate_protocol(activation_parameters, &node_ref)?;
+            return reply_with_client_output(client_output, &log).map_err(|e| e.into());
+        }
+        client_runner.insert_bootstrap_accounts(&mut activation_parameters, &node_ref)?;
+    }
+
+    // lock is released, native activation calls node rpc
+    let activator = parse_secret_key("activator", ACTIVATOR_SECRET_KEY)?;
+    let client_output = NativeBaker::new(node_ref, log.clone())
+        .activate_protocol(
+            &activator,
+            &activation_parameters.protocol_hash,
+            &activation_parameters.timestamp,
+            &activation_parameters.protocol_parameters,
+        )
+        .await
+        .map_err(TezosClientRunnerError::from)?;
 
     reply_with_client_output(client_output, &log).map_err(|e| e.into())
 }
@@ -242,35 +381,112 @@ pub async fn bake_block_with_client(
     request: BakeRequest,
     log: Logger,
     client_runner: TezosClientRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
     node_ref: Option<NodeRpcIpPort>,
 ) -> Result<impl warp::Reply, reject::Rejection> {
     info!(log, "Received request to bake a block");
 
-    let node_ref = ensure_node(node_ref)?;
-    let client_runner = client_runner
-        .read()
-        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
-    let client_output = client_runner.bake_block(Some(request), &node_ref)?;
-
-    reply_with_client_output(client_output, &log).map_err(|e| e.into())
+    bake_block(
+        Some(request),
+        log,
+        client_runner,
+        peers,
+        ensure_node(node_ref)?,
+    )
+    .await
 }
 
 pub async fn bake_block_with_client_arbitrary(
     log: Logger,
     client_runner: TezosClientRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
     node_ref: Option<NodeRpcIpPort>,
 ) -> Result<impl warp::Reply, reject::Rejection> {
     info!(log, "Received request to bake a block");
 
-    let node_ref = ensure_node(node_ref)?;
-    let client_runner = client_runner
-        .read()
-        .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
-    let client_output = client_runner.bake_block(None, &node_ref)?;
+    bake_block(None, log, client_runner, peers, ensure_node(node_ref)?).await
+}
+
+async fn bake_block(
+    request: Option<BakeRequest>,
+    log: Logger,
+    client_runner: TezosClientRunnerRef,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+    node_ref: NodeRpcIpPort,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    let timestamp = request
+        .as_ref()
+        .and_then(|request| request.timestamp.clone());
+    let (delegate, baker) = {
+        let client_runner = client_runner
+            .read()
+            .map_err(|e| LockErrorCause::new(e, "Cannot get write lock on client_runner"))?;
+        if !client_runner.is_native() {
+            let client_output = client_runner.bake_block(request, &node_ref)?;
+            return reply_with_client_output(client_output, &log).map_err(|e| e.into());
+        }
+        let wallet = client_runner.baking_wallet(request, &node_ref)?;
+        (wallet.public_key_hash().to_string(), wallet.secret_key()?)
+    };
+
+    // clocks of all sandbox nodes are kept in sync, so baked block is accepted by all of them
+    let other_nodes = peers
+        .lock()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on peers"))?
+        .iter()
+        .filter(|peer| **peer != node_ref)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // lock is released, native baking calls node rpc
+    let client_output = NativeBaker::new(node_ref, log.clone())
+        .bake_block(&delegate, &baker, timestamp.as_deref(), &other_nodes)
+        .await
+        .map_err(TezosClientRunnerError::from)?;
 
     reply_with_client_output(client_output, &log).map_err(|e| e.into())
 }
 
+/// Sets, advances or resets clock of all running sandbox nodes (request is forwarded to node's `/dev/sandbox/clock`)
+pub async fn update_clock(
+    update: serde_json::Value,
+    log: Logger,
+    peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
+) -> Result<impl warp::Reply, reject::Rejection> {
+    info!(log, "Received request to update clock of sandbox nodes"; "update" => update.to_string());
+
+    let nodes = peers
+        .lock()
+        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on peers"))?
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+    if nodes.is_empty() {
+        return Err(TezosClientRunnerError::UnavailableSandboxNodeError.into());
+    }
+
+    let mut clocks = Vec::with_capacity(nodes.len());
+    for node_ref in nodes {
+        let clock = NativeBaker::new(node_ref.clone(), log.clone())
+            .update_clock(&update)
+            .await
+            .map_err(TezosClientRunnerError::from)?;
+        clocks.push(NodeClock { node_ref, clock });
+    }
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&clocks),
+        StatusCode::OK,
+    ))
+}
+
+/// Clock of the sandbox node as returned by node rpc
+#[derive(Serialize)]
+struct NodeClock {
+    node_ref: NodeRpcIpPort,
+    clock: serde_json::Value,
+}
+
 pub async fn handle_rejection(err: Rejection, log: Logger) -> Result<impl Reply, Infallible> {
     let (code, error_message) = if err.is_not_found() {
         error!(log, "Rpc handle error"; "message" => "rpc not found");
@@ -296,6 +512,8 @@ pub async fn handle_rejection(err: Rejection, log: Logger) -> Result<impl Reply,
             match tcre {
                 TezosClientRunnerError::ProtocolParameterError { .. }
                 | TezosClientRunnerError::NonexistantWallet { .. }
+                | TezosClientRunnerError::InvalidSecretKey { .. }
+                | TezosClientRunnerError::NativeBakingRequired { .. }
                 | TezosClientRunnerError::UnavailableSandboxNodeError
                 | TezosClientRunnerError::IOError { .. }
                 | TezosClientRunnerError::SandboxDataDirNotInitialized { .. }
@@ -322,8 +540,11 @@ pub async fn handle_rejection(err: Rejection, log: Logger) -> Result<impl Reply,
                 LightNodeRunnerError::JsonParsingError { .. }
                 | LightNodeRunnerError::IOError { .. }
                 | LightNodeRunnerError::ConfigurationMissingValidRpcPort { .. }
-                | LightNodeRunnerError::NodeAlreadyRunning
-                | LightNodeRunnerError::NodeNotRunning { .. } => {
+                | LightNodeRunnerError::InvalidTopology { .. }
+                | LightNodeRunnerError::LinkNotFound { .. }
+                | LightNodeRunnerError::NodeAlreadyRunning { .. }
+                | LightNodeRunnerError::NodeNotRunning { .. }
+                | LightNodeRunnerError::UnknownNode { .. } => {
                     let message = format!("{}", lnre);
                     error!(log, "Rpc handle error (light-node)"; "message" => message.clone());
                     (
@@ -395,17 +616,25 @@ fn extract_field_name(message: &str) -> Option<String> {
     }
 }
 
-/// Resolves which peer we want to call
+/// Optional query parameter to select sandbox node by rpc port, e.g. `/bake?node=18733`
+#[derive(Debug, Deserialize)]
+pub struct NodeQuery {
+    node: Option<u16>,
+}
+
+/// Resolves which peer we want to call (selected by query parameter or the one with the lowest rpc port)
 pub fn resolve_node_from_request(
     peers: Arc<Mutex<HashSet<NodeRpcIpPort>>>,
-) -> Option<NodeRpcIpPort> {
-    // TODO: resolve some peer from request
-    peers
+    query: NodeQuery,
+) -> Result<Option<NodeRpcIpPort>, Rejection> {
+    let peers = peers
         .lock()
-        .unwrap() // TODO: reject when TE-213 is resolved, since now this should be infallible
-        .iter()
-        .next()
-        .map(|p| p.clone())
+        .map_err(|e| LockErrorCause::new(e, "Cannot get read lock on peers"))?;
+    let node = match query.node {
+        Some(port) => peers.iter().find(|p| p.port == port).cloned(),
+        None => peers.iter().min_by_key(|p| p.port).cloned(),
+    };
+    Ok(node)
 }
 
 fn ensure_node(node_ref: Option<NodeRpcIpPort>) -> Result<NodeRpcIpPort, TezosClientRunnerError> {
@@ -9,13 +9,16 @@ use std::{env, fs, io, iter};
 
 use rand::distributions::Alphanumeric;
 use rand::Rng;
-use slog::{info, Drain, Level, Logger};
+use slog::{error, info, Drain, Level, Logger};
 
 mod configuration;
 mod filters;
 mod handlers;
+mod native_baker;
 mod node_runner;
+mod scenario;
 mod tezos_client_runner;
+mod topology;
 
 #[tokio::main]
 async fn main() {
@@ -30,22 +33,33 @@ async fn main() {
 
     // create a thread safe reference to the runner struct
     let runner = Arc::new(RwLock::new(node_runner::LightNodeRunner::new(
-        "light-node-0",
+        "light-node",
         env.light_node_path,
         env.protocol_runner_path,
     )));
 
+    if env.tezos_client_path.is_none() {
+        info!(log, "Tezos-client path is not set, using native baking");
+    }
+
     // create a thread safe reference to the client runner struct
     let client_runner = Arc::new(RwLock::new(tezos_client_runner::TezosClientRunner::new(
         "tezos-client",
         env.tezos_client_path,
     )));
 
-    // the port to open the rpc server on
-    let rpc_port = env.sandbox_rpc_port;
-
     // combined warp filter
-    let api = filters::sandbox(log.clone(), runner, client_runner, peers);
+    let api = filters::sandbox(log.clone(), runner.clone(), client_runner, peers);
+
+    if !env.scenarios.is_empty() {
+        let success = run_scenarios(api, runner, &env.scenarios, env.scenario_report, &log).await;
+        std::process::exit(if success { 0 } else { 1 });
+    }
+
+    // the port to open the rpc server on
+    let rpc_port = env
+        .sandbox_rpc_port
+        .expect("Was expecting value of sandbox-rpc-port");
 
     info!(log, "Start to serving Sandbox RPCs");
 
@@ -53,6 +67,47 @@ async fn main() {
     warp::serve(api).run(([0, 0, 0, 0], rpc_port)).await;
 }
 
+/// Runs scenarios one by one and writes JUnit report, returns false, if any scenario failed
+async fn run_scenarios<F>(
+    api: F,
+    runner: node_runner::LightNodeRunnerRef,
+    scenarios: &[PathBuf],
+    report_path: Option<PathBuf>,
+    log: &Logger,
+) -> bool
+where
+    F: warp::Filter + 'static,
+    F::Extract: warp::Reply + Send,
+{
+    let scenario_runner = scenario::ScenarioRunner::new(api, runner, log.clone());
+    let mut reports = Vec::with_capacity(scenarios.len());
+    let mut success = true;
+
+    for path in scenarios {
+        match scenario::Scenario::load(path) {
+            Ok(scenario) => {
+                let report = scenario_runner.run(&scenario).await;
+                info!(log, "Scenario finished"; "scenario" => &report.name, "steps" => report.steps.len(),
+                           "failures" => report.failures(), "skipped" => report.skipped());
+                success &= report.failures() == 0;
+                reports.push(report);
+            }
+            Err(e) => {
+                error!(log, "Failed to load scenario"; "reason" => format!("{}", e));
+                success = false;
+            }
+        }
+    }
+
+    if let Some(report_path) = report_path {
+        if let Err(e) = fs::write(&report_path, scenario::junit_report(&reports)) {
+            error!(log, "Failed to write scenario report"; "path" => report_path.display().to_string(), "reason" => format!("{}", e));
+            success = false;
+        }
+    }
+    success
+}
+
 /// Creates a slog Logger
 fn create_logger(level: Level) -> Logger {
     let drain = slog_async::Async::new(
@@ -0,0 +1,701 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Baking and protocol activation without the tezos-client binary.
+//!
+//! Block is prepared by the node (`helpers/preapply/block`), the header is forged and signed here
+//! with the sandbox wallet key and the block is injected back to the node with `/injection/block`.
+
+use chrono::DateTime;
+use failure::Fail;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use rand::Rng;
+use serde_json::{json, Value};
+use slog::{info, Logger};
+use warp::http::StatusCode;
+
+use crypto::blake2b;
+use crypto::hash::{
+    BlockHash, ChainId, ContextHash, NonceHash, OperationListListHash, ProtocolHash, SeedEd25519,
+    SignatureEd25519,
+};
+use crypto::signature::{block_header_watermark, sign_ed25519};
+use tezos_messages::p2p::binary_message::BinaryMessage;
+use tezos_messages::p2p::encoding::block_header::{BlockHeader, BlockHeaderBuilder, Fitness};
+use tezos_messages::ts_to_rfc3339;
+
+use crate::handlers::ErrorMessage;
+use crate::node_runner::NodeRpcIpPort;
+use crate::tezos_client_runner::{TezosClientReply, TezosClientRunnerError};
+
+/// Protocol, which is used to activate the first real protocol
+const GENESIS_PROTOCOL: &str = "ProtoGenesisGenesisGenesisGenesisGenesisGenesk612im";
+
+/// Fitness of the activation block (the same as `activate protocol ... with fitness 1`)
+const ACTIVATION_FITNESS: [&str; 2] = ["01", "0000000000000001"];
+
+/// Max priority checked for baking rights of the baker
+const MAX_BAKING_PRIORITY: u16 = 64;
+
+/// Count of validation passes of the protocol (endorsements, votes, anonymous, managers)
+const VALIDATION_PASSES: usize = 4;
+
+#[derive(Debug, Fail)]
+pub enum NativeBakerError {
+    #[fail(display = "Node rpc call failed, url: {}, reason: {}", url, reason)]
+    RpcError { url: String, reason: String },
+
+    #[fail(display = "Unexpected node rpc response, reason: {}", reason)]
+    InvalidResponse { reason: String },
+
+    #[fail(
+        display = "No baking rights found for delegate: {} (max_priority: {})",
+        delegate, max_priority
+    )]
+    NoBakingRights { delegate: String, max_priority: u16 },
+
+    #[fail(display = "Failed to forge block header, reason: {}", reason)]
+    ForgeError { reason: String },
+}
+
+impl From<NativeBakerError> for TezosClientRunnerError {
+    fn from(err: NativeBakerError) -> TezosClientRunnerError {
+        TezosClientRunnerError::CallError {
+            message: ErrorMessage::generic(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Native baking call finished with error",
+                format!("{}", err),
+            ),
+        }
+    }
+}
+
+fn invalid_response<E: std::fmt::Display>(what: &str, error: E) -> NativeBakerError {
+    NativeBakerError::InvalidResponse {
+        reason: format!("{} ({})", what, error),
+    }
+}
+
+fn forge_error<E: std::fmt::Display>(error: E) -> NativeBakerError {
+    NativeBakerError::ForgeError {
+        reason: format!("{}", error),
+    }
+}
+
+/// Bakes blocks (and activates protocol) on one sandbox node through its rpc
+pub struct NativeBaker {
+    node_ref: NodeRpcIpPort,
+    client: Client<HttpConnector>,
+    log: Logger,
+}
+
+impl NativeBaker {
+    pub fn new(node_ref: NodeRpcIpPort, log: Logger) -> Self {
+        Self {
+            node_ref,
+            client: Client::new(),
+            log,
+        }
+    }
+
+    /// Activates protocol on the genesis block (the same as `tezos-client activate protocol`)
+    pub async fn activate_protocol(
+        &self,
+        activator: &SeedEd25519,
+        protocol_hash: &str,
+        timestamp: &str,
+        protocol_parameters: &Value,
+    ) -> Result<TezosClientReply, NativeBakerError> {
+        let protocol_hash = ProtocolHash::from_base58_check(protocol_hash)
+            .map_err(|e| invalid_response("invalid protocol hash", e))?;
+        let fitness = ACTIVATION_FITNESS
+            .iter()
+            .map(hex::decode)
+            .collect::<Result<Fitness, _>>()
+            .map_err(forge_error)?;
+        let protocol_parameters = encode_protocol_parameters(protocol_parameters)?;
+
+        let preapplied = self
+            .post(
+                &format!(
+                    "/chains/main/blocks/genesis/helpers/preapply/block?timestamp={}",
+                    timestamp.replace('+', "%2B")
+                ),
+                &json!({
+                    "protocol_data": {
+                        "protocol": GENESIS_PROTOCOL,
+                        "content": {
+                            "command": "activate",
+                            "hash": protocol_hash.to_base58_check(),
+                            "fitness": ACTIVATION_FITNESS,
+                            "protocol_parameters": hex::encode(&protocol_parameters),
+                        },
+                        "signature": zero_signature(),
+                    },
+                    "operations": [],
+                }),
+            )
+            .await?;
+
+        // activate command: tag(0) + protocol hash + fitness + protocol parameters
+        let mut command = vec![0u8];
+        command.extend_from_slice(protocol_hash.as_ref());
+        command.extend(encode_fitness(&fitness));
+        command.extend(protocol_parameters);
+
+        let chain_id = self.chain_id().await?;
+        let header = forge_signed_header(&preapplied["shell_header"], &command, |unsigned| {
+            sign_ed25519(
+                activator,
+                &block_header_watermark(chain_id.as_ref()),
+                unsigned,
+            )
+        })?;
+
+        let block_hash = self.inject_block(&header, Vec::new()).await?;
+        info!(self.log, "Protocol activated"; "node_ref" => format!("{}", &self.node_ref), "protocol" => protocol_hash.to_base58_check(), "block_hash" => block_hash.clone());
+
+        Ok(TezosClientReply::new(
+            format!("Injected {}", block_hash),
+            String::new(),
+        ))
+    }
+
+    /// Bakes block with the pending (applied) mempool operations (the same as `tezos-client bake for`).
+    ///
+    /// Block timestamp is `timestamp` (if set) or the minimal valid timestamp according to node's clock.
+    /// If the block is baked ahead of "now", clocks of the node and of the `other_nodes` are advanced to the block timestamp,
+    /// so the block is not rejected as a block from the future.
+    pub async fn bake_block(
+        &self,
+        delegate: &str,
+        baker: &SeedEd25519,
+        timestamp: Option<&str>,
+        other_nodes: &[NodeRpcIpPort],
+    ) -> Result<TezosClientReply, NativeBakerError> {
+        let head = self.get("/chains/main/blocks/head/header").await?;
+        let level = head["level"]
+            .as_i64()
+            .ok_or_else(|| invalid_response("missing head level", &head))?
+            + 1;
+        let protocol = self.get("/chains/main/blocks/head/protocols").await?["next_protocol"]
+            .as_str()
+            .ok_or_else(|| invalid_response("missing next_protocol", "protocols"))?
+            .to_string();
+        let constants = self
+            .get("/chains/main/blocks/head/context/constants")
+            .await?;
+        let proof_of_work_threshold = constants["proof_of_work_threshold"]
+            .as_str()
+            .and_then(|threshold| threshold.parse::<i64>().ok())
+            .ok_or_else(|| invalid_response("missing proof_of_work_threshold", &constants))?;
+
+        // best priority and its minimal timestamp
+        let rights = self
+            .get(&format!(
+                "/chains/main/blocks/head/helpers/baking_rights?delegate={}&level={}&max_priority={}",
+                delegate, level, MAX_BAKING_PRIORITY
+            ))
+            .await?;
+        let right = rights
+            .as_array()
+            .and_then(|rights| {
+                rights
+                    .iter()
+                    .filter(|right| right["priority"].is_u64())
+                    .min_by_key(|right| right["priority"].as_u64())
+            })
+            .ok_or_else(|| NativeBakerError::NoBakingRights {
+                delegate: delegate.to_string(),
+                max_priority: MAX_BAKING_PRIORITY,
+            })?;
+        let priority = right["priority"]
+            .as_u64()
+            .ok_or_else(|| invalid_response("missing priority", right))?
+            as u16;
+        let timestamp = match timestamp {
+            Some(timestamp) => parse_timestamp(timestamp)?,
+            None => {
+                let now = self.node_now().await?;
+                match right["estimated_time"].as_str() {
+                    Some(estimated_time) => parse_timestamp(estimated_time)?.max(now),
+                    None => now,
+                }
+            }
+        };
+
+        // seed nonce hash is required on commitment levels, nonce is not revealed in sandbox
+        let expected_commitment = self
+            .get("/chains/main/blocks/head/helpers/current_level?offset=1")
+            .await?["expected_commitment"]
+            .as_bool()
+            .unwrap_or(false);
+        let seed_nonce_hash = if expected_commitment {
+            let nonce = rand::thread_rng().gen::<[u8; 32]>();
+            Some(NonceHash(blake2b::digest_256(&nonce)))
+        } else {
+            None
+        };
+
+        let operations = classify_operations(
+            self.get("/chains/main/mempool/pending_operations").await?,
+            &protocol,
+        )?;
+
+        let mut protocol_data = json!({
+            "protocol": protocol,
+            "priority": priority,
+            "proof_of_work_nonce": hex::encode([0u8; 8]),
+            "signature": zero_signature(),
+        });
+        if let Some(seed_nonce_hash) = &seed_nonce_hash {
+            protocol_data["seed_nonce_hash"] = Value::String(seed_nonce_hash.to_base58_check());
+        }
+        let preapplied = self
+            .post(
+                &format!(
+                    "/chains/main/blocks/head/helpers/preapply/block?sort=true&timestamp={}",
+                    ts_to_rfc3339(timestamp)
+                ),
+                &json!({
+                    "protocol_data": protocol_data,
+                    "operations": operations,
+                }),
+            )
+            .await?;
+        let shell_header = &preapplied["shell_header"];
+
+        // find proof of work nonce
+        let mut nonce = rand::thread_rng().gen::<u64>();
+        let contents = loop {
+            let contents = encode_block_contents(priority, nonce, seed_nonce_hash.as_ref());
+            let header = forge_header(shell_header, {
+                let mut protocol_data = contents.clone();
+                protocol_data.extend_from_slice(&[0u8; 64]);
+                protocol_data
+            })?;
+            if check_proof_of_work(
+                &header.as_bytes().map_err(forge_error)?,
+                proof_of_work_threshold,
+            ) {
+                break contents;
+            }
+            nonce = nonce.wrapping_add(1);
+        };
+
+        let chain_id = self.chain_id().await?;
+        let header = forge_signed_header(shell_header, &contents, |unsigned| {
+            sign_ed25519(baker, &block_header_watermark(chain_id.as_ref()), unsigned)
+        })?;
+
+        // operations applied by preapply, per validation pass
+        let operations = preapplied["operations"]
+            .as_array()
+            .ok_or_else(|| invalid_response("missing preapplied operations", &preapplied))?
+            .iter()
+            .map(|validation_pass| {
+                validation_pass["applied"]
+                    .as_array()
+                    .map(|applied| {
+                        applied
+                            .iter()
+                            .map(|op| json!({"branch": op["branch"], "data": op["data"]}))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let operations_count: usize = operations.iter().map(Vec::len).sum();
+
+        // advance_to never moves clock back, so it is safe to call also for blocks from the past
+        for node_ref in std::iter::once(&self.node_ref).chain(other_nodes) {
+            self.call(
+                node_ref,
+                Method::POST,
+                "/dev/sandbox/clock",
+                Body::from(json!({ "advance_to": ts_to_rfc3339(timestamp) }).to_string()),
+            )
+            .await?;
+        }
+
+        let block_hash = self.inject_block(&header, operations).await?;
+        info!(self.log, "Block baked"; "node_ref" => format!("{}", &self.node_ref), "block_hash" => block_hash.clone(), "level" => level,
+                        "priority" => priority, "delegate" => delegate, "operations" => operations_count);
+
+        Ok(TezosClientReply::new(
+            format!("Injected block {}", block_hash),
+            String::new(),
+        ))
+    }
+
+    /// Sets, advances or resets node's clock (see `/dev/sandbox/clock` node rpc)
+    pub async fn update_clock(&self, update: &Value) -> Result<Value, NativeBakerError> {
+        self.post("/dev/sandbox/clock", update).await
+    }
+
+    /// Returns node's notion of "now" (unix seconds)
+    async fn node_now(&self) -> Result<i64, NativeBakerError> {
+        let clock = self.get("/dev/sandbox/clock").await?;
+        clock["now"]
+            .as_str()
+            .ok_or_else(|| invalid_response("missing now", &clock))
+            .and_then(parse_timestamp)
+    }
+
+    async fn chain_id(&self) -> Result<ChainId, NativeBakerError> {
+        let chain_id = self.get("/chains/main/chain_id").await?;
+        chain_id
+            .as_str()
+            .ok_or_else(|| invalid_response("invalid chain_id", &chain_id))
+            .and_then(|chain_id| {
+                ChainId::from_base58_check(chain_id)
+                    .map_err(|e| invalid_response("invalid chain_id", e))
+            })
+    }
+
+    /// Injects block and returns its hash
+    async fn inject_block(
+        &self,
+        header: &BlockHeader,
+        operations: Vec<Vec<Value>>,
+    ) -> Result<String, NativeBakerError> {
+        let block_hash = self
+            .post(
+                "/injection/block?chain_id=main",
+                &json!({
+                    "data": hex::encode(header.as_bytes().map_err(forge_error)?),
+                    "operations": operations,
+                }),
+            )
+            .await?;
+        block_hash
+            .as_str()
+            .map(|block_hash| block_hash.to_string())
+            .ok_or_else(|| invalid_response("invalid injected block hash", &block_hash))
+    }
+
+    async fn get(&self, path: &str) -> Result<Value, NativeBakerError> {
+        self.call(&self.node_ref, Method::GET, path, Body::empty())
+            .await
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<Value, NativeBakerError> {
+        self.call(
+            &self.node_ref,
+            Method::POST,
+            path,
+            Body::from(body.to_string()),
+        )
+        .await
+    }
+
+    async fn call(
+        &self,
+        node_ref: &NodeRpcIpPort,
+        method: Method,
+        path: &str,
+        body: Body,
+    ) -> Result<Value, NativeBakerError> {
+        let url = format!("http://{}:{}{}", node_ref.ip, node_ref.port, path);
+        let rpc_error = |reason: String| NativeBakerError::RpcError {
+            url: url.clone(),
+            reason,
+        };
+
+        let request = Request::builder()
+            .method(method)
+            .uri(&url)
+            .header("content-type", "application/json")
+            .body(body)
+            .map_err(|e| rpc_error(format!("{}", e)))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| rpc_error(format!("{}", e)))?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| rpc_error(format!("{}", e)))?;
+
+        if !status.is_success() {
+            return Err(rpc_error(format!(
+                "{} - {}",
+                status,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        serde_json::from_slice(&body).map_err(|e| rpc_error(format!("{}", e)))
+    }
+}
+
+fn zero_signature() -> String {
+    SignatureEd25519(vec![0u8; 64]).to_base58_check()
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<i64, NativeBakerError> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|timestamp| timestamp.timestamp())
+        .map_err(|e| invalid_response("invalid timestamp", e))
+}
+
+/// Splits applied mempool operations to validation passes according to operation kind
+fn classify_operations(
+    pending_operations: Value,
+    protocol: &str,
+) -> Result<Vec<Vec<Value>>, NativeBakerError> {
+    let mut validation_passes = vec![Vec::new(); VALIDATION_PASSES];
+    let applied = match pending_operations["applied"].as_array() {
+        Some(applied) => applied.clone(),
+        None => return Ok(validation_passes),
+    };
+
+    for mut operation in applied {
+        let validation_pass = match operation["contents"][0]["kind"].as_str() {
+            Some("endorsement") | Some("endorsement_with_slot") => 0,
+            Some("proposals") | Some("ballot") => 1,
+            Some("seed_nonce_revelation")
+            | Some("double_endorsement_evidence")
+            | Some("double_baking_evidence")
+            | Some("activate_account") => 2,
+            Some(_) => 3,
+            None => return Err(invalid_response("operation without kind", &operation)),
+        };
+        if let Some(operation) = operation.as_object_mut() {
+            operation.remove("hash");
+            operation.insert("protocol".to_string(), Value::String(protocol.to_string()));
+        }
+        validation_passes[validation_pass].push(operation);
+    }
+    Ok(validation_passes)
+}
+
+/// Protocol specific block header contents: priority + proof_of_work_nonce + seed_nonce_hash (optional)
+fn encode_block_contents(
+    priority: u16,
+    nonce: u64,
+    seed_nonce_hash: Option<&NonceHash>,
+) -> Vec<u8> {
+    let mut contents = Vec::with_capacity(2 + 8 + 33);
+    contents.extend_from_slice(&priority.to_be_bytes());
+    contents.extend_from_slice(&nonce.to_be_bytes());
+    match seed_nonce_hash {
+        Some(seed_nonce_hash) => {
+            contents.push(0xff);
+            contents.extend_from_slice(seed_nonce_hash.as_ref());
+        }
+        None => contents.push(0x00),
+    }
+    contents
+}
+
+/// Stamp is the first 8 bytes of the header hash (with zero signature), which cannot exceed the threshold
+fn check_proof_of_work(header_bytes: &[u8], threshold: i64) -> bool {
+    if threshold < 0 {
+        return true;
+    }
+    let hash = blake2b::digest_256(header_bytes);
+    let mut stamp = [0u8; 8];
+    stamp.copy_from_slice(&hash[0..8]);
+    u64::from_be_bytes(stamp) <= threshold as u64
+}
+
+/// Fitness is encoded as dynamic list of dynamic bytes
+fn encode_fitness(fitness: &Fitness) -> Vec<u8> {
+    let mut elements = Vec::new();
+    for element in fitness {
+        elements.extend_from_slice(&(element.len() as u32).to_be_bytes());
+        elements.extend_from_slice(element);
+    }
+    let mut encoded = (elements.len() as u32).to_be_bytes().to_vec();
+    encoded.extend(elements);
+    encoded
+}
+
+/// Creates header from preapplied shell header and protocol data
+fn forge_header(
+    shell_header: &Value,
+    protocol_data: Vec<u8>,
+) -> Result<BlockHeader, NativeBakerError> {
+    let field = |name: &str| {
+        shell_header[name]
+            .as_str()
+            .ok_or_else(|| invalid_response("missing shell header field", name))
+    };
+    let number = |name: &str| {
+        shell_header[name]
+            .as_i64()
+            .ok_or_else(|| invalid_response("missing shell header field", name))
+    };
+    let fitness = shell_header["fitness"]
+        .as_array()
+        .ok_or_else(|| invalid_response("missing shell header field", "fitness"))?
+        .iter()
+        .map(|element| {
+            element
+                .as_str()
+                .ok_or_else(|| invalid_response("invalid fitness", element))
+                .and_then(|element| hex::decode(element).map_err(forge_error))
+        })
+        .collect::<Result<Fitness, _>>()?;
+
+    BlockHeaderBuilder::default()
+        .level(number("level")? as i32)
+        .proto(number("proto")? as u8)
+        .predecessor(BlockHash::from_base58_check(field("predecessor")?).map_err(forge_error)?)
+        .timestamp(parse_timestamp(field("timestamp")?)?)
+        .validation_pass(number("validation_pass")? as u8)
+        .operations_hash(
+            OperationListListHash::from_base58_check(field("operations_hash")?)
+                .map_err(forge_error)?,
+        )
+        .fitness(fitness)
+        .context(ContextHash::from_base58_check(field("context")?).map_err(forge_error)?)
+        .protocol_data(protocol_data)
+        .build()
+        .map_err(forge_error)
+}
+
+/// Creates header with signed protocol data (signature is appended to the contents)
+fn forge_signed_header<F>(
+    shell_header: &Value,
+    contents: &[u8],
+    sign: F,
+) -> Result<BlockHeader, NativeBakerError>
+where
+    F: FnOnce(&[u8]) -> Result<SignatureEd25519, crypto::CryptoError>,
+{
+    let unsigned = forge_header(shell_header, contents.to_vec())?
+        .as_bytes()
+        .map_err(forge_error)?;
+    let signature = sign(&unsigned).map_err(forge_error)?;
+
+    let mut protocol_data = contents.to_vec();
+    protocol_data.extend_from_slice(signature.as_ref());
+    forge_header(shell_header, protocol_data)
+}
+
+/// Protocol parameters are passed to genesis protocol as binary json (4 bytes length + bson document)
+fn encode_protocol_parameters(protocol_parameters: &Value) -> Result<Vec<u8>, NativeBakerError> {
+    let mut document = Vec::new();
+    match protocol_parameters.as_object() {
+        Some(_) => encode_bson_document(protocol_parameters, &mut document),
+        None => {
+            return Err(invalid_response(
+                "protocol parameters must be json object",
+                protocol_parameters,
+            ))
+        }
+    }
+
+    let mut encoded = (document.len() as u32).to_be_bytes().to_vec();
+    encoded.extend(document);
+    Ok(encoded)
+}
+
+/// Minimal bson encoder for json values (numbers are encoded as doubles like in ocaml json_repr_bson)
+fn encode_bson_document(value: &Value, out: &mut Vec<u8>) {
+    let start = out.len();
+    // placeholder for document size
+    out.extend_from_slice(&[0u8; 4]);
+
+    let mut encode_element = |key: &str, value: &Value| {
+        let tag = match value {
+            Value::Number(_) => 0x01,
+            Value::String(_) => 0x02,
+            Value::Object(_) => 0x03,
+            Value::Array(_) => 0x04,
+            Value::Bool(_) => 0x08,
+            Value::Null => 0x0A,
+        };
+        out.push(tag);
+        out.extend_from_slice(key.as_bytes());
+        out.push(0x00);
+
+        match value {
+            Value::Number(number) => {
+                out.extend_from_slice(&number.as_f64().unwrap_or_default().to_le_bytes())
+            }
+            Value::String(string) => {
+                out.extend_from_slice(&(string.len() as i32 + 1).to_le_bytes());
+                out.extend_from_slice(string.as_bytes());
+                out.push(0x00);
+            }
+            Value::Object(_) | Value::Array(_) => encode_bson_document(value, out),
+            Value::Bool(boolean) => out.push(*boolean as u8),
+            Value::Null => (),
+        }
+    };
+
+    match value {
+        Value::Object(fields) => fields
+            .iter()
+            .for_each(|(key, value)| encode_element(key.as_str(), value)),
+        Value::Array(elements) => elements
+            .iter()
+            .enumerate()
+            .for_each(|(index, value)| encode_element(index.to_string().as_str(), value)),
+        _ => (),
+    }
+    out.push(0x00);
+
+    let size = (out.len() - start) as i32;
+    out[start..start + 4].copy_from_slice(&size.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_protocol_parameters() -> Result<(), failure::Error> {
+        let encoded = encode_protocol_parameters(&json!({"a": 1, "b": ["x"], "c": true}))?;
+        let expected = hex::decode(concat!(
+            // binary json size
+            "00000025",
+            // bson document size
+            "25000000",
+            // "a": 1.0
+            "016100000000000000f03f",
+            // "b": ["x"]
+            "0462000e000000023000020000007800",
+            "00",
+            // "c": true
+            "08630001",
+            "00",
+        ))?;
+        assert_eq!(expected, encoded);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_block_contents() {
+        assert_eq!(
+            hex::decode("0001000000000000000200").unwrap(),
+            encode_block_contents(1, 2, None)
+        );
+        let seed_nonce_hash = NonceHash(vec![7u8; 32]);
+        let contents = encode_block_contents(0, 0, Some(&seed_nonce_hash));
+        assert_eq!(2 + 8 + 1 + 32, contents.len());
+        assert_eq!(0xff, contents[10]);
+    }
+
+    #[test]
+    fn test_classify_operations() -> Result<(), failure::Error> {
+        let pending_operations = json!({
+            "applied": [
+                {"hash": "oo1", "branch": "BL1", "contents": [{"kind": "transaction"}], "signature": "sig1"},
+                {"hash": "oo2", "branch": "BL1", "contents": [{"kind": "endorsement", "level": 1}]},
+            ],
+            "refused": [],
+        });
+        let validation_passes = classify_operations(pending_operations, "PtEdo2Zk")?;
+        assert_eq!(VALIDATION_PASSES, validation_passes.len());
+        assert_eq!(1, validation_passes[0].len());
+        assert_eq!(1, validation_passes[3].len());
+        assert_eq!("PtEdo2Zk", validation_passes[3][0]["protocol"]);
+        assert!(validation_passes[3][0].get("hash").is_none());
+        Ok(())
+    }
+}
@@ -1,9 +1,11 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::collections::HashMap;
 use std::fmt;
 use std::fs;
 use std::io::{BufReader, Read};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process::{Child, Command, Stdio};
 use std::sync::{Arc, RwLock};
@@ -19,18 +21,26 @@ use slog::{error, info, warn, Logger};
 use wait_timeout::ChildExt;
 use warp::reject;
 
+use crate::topology::{LinkInfo, LinkRequest, SandboxLink, TopologyRequest};
 use crate::{create_temp_dir, rand_chars};
 
 #[derive(Debug, Fail)]
 pub enum LightNodeRunnerError {
     /// Already running error.
-    #[fail(display = "Sandbox light-node is already running")]
-    NodeAlreadyRunning,
+    #[fail(
+        display = "Sandbox light-node is already running, node_ref: {}",
+        node_ref
+    )]
+    NodeAlreadyRunning { node_ref: NodeRpcIpPort },
 
     /// Already running error.
     #[fail(display = "Sandbox light-node is not running, node_ref: {}", node_ref)]
     NodeNotRunning { node_ref: NodeRpcIpPort },
 
+    /// Node was not started by launcher
+    #[fail(display = "Unknown sandbox light-node, node_ref: {}", node_ref)]
+    UnknownNode { node_ref: NodeRpcIpPort },
+
     /// IO Error.
     #[fail(display = "IOError - {}, reason: {}", message, reason)]
     IOError {
@@ -52,6 +62,20 @@ pub enum LightNodeRunnerError {
         value
     )]
     ConfigurationMissingValidRpcPort { value: Option<String> },
+
+    /// Invalid request for nodes topology
+    #[fail(display = "Invalid topology, reason: {}", reason)]
+    InvalidTopology { reason: String },
+
+    /// There is no link between nodes
+    #[fail(
+        display = "There is no link between sandbox nodes, node_a: {}, node_b: {}",
+        node_a, node_b
+    )]
+    LinkNotFound {
+        node_a: NodeRpcIpPort,
+        node_b: NodeRpcIpPort,
+    },
 }
 
 impl reject::Reject for LightNodeRunnerError {}
@@ -61,6 +85,14 @@ pub type LightNodeRunnerRef = Arc<RwLock<LightNodeRunner>>;
 
 const SANDBOX_NODE_IP: &str = "localhost";
 const NODE_CONFIG_RPC_PORT: &str = "rpc_port";
+const NODE_CONFIG_P2P_PORT: &str = "p2p_port";
+const NODE_CONFIG_WEBSOCKET_ADDRESS: &str = "websocket_address";
+const NODE_CONFIG_PEERS: &str = "peers";
+const NODE_CONFIG_PRIVATE_NODE: &str = "private_node";
+const NODE_CONFIG_DISABLE_BOOTSTRAP_LOOKUP: &str = "disable_bootstrap_lookup";
+const NODE_CONFIG_BOOTSTRAP_LOOKUP_ADDRESS: &str = "bootstrap_lookup_address";
+const NODE_CONFIG_PEER_THRESH_LOW: &str = "peer_thresh_low";
+const NODE_CONFIG_PEER_THRESH_HIGH: &str = "peer_thresh_high";
 const NODE_CONFIG_TEZOS_DATA_DIR: &str = "tezos_data_dir";
 const NODE_CONFIG_TEZEDGE_DATA_DIR: &str = "bootstrap_db_path";
 const NODE_CONFIG_IDENTITY_FILE: &str = "identity_file";
@@ -97,10 +129,15 @@ impl NodeRpcIpPort {
                 return Err(LightNodeRunnerError::ConfigurationMissingValidRpcPort { value: None })
             }
         };
-        Ok(Self {
+        Ok(Self::sandbox(port))
+    }
+
+    /// Sandbox node listening for rpc requests on the `port`
+    pub fn sandbox(port: u16) -> Self {
+        Self {
             ip: SANDBOX_NODE_IP.to_string(),
             port,
-        })
+        }
     }
 }
 
@@ -110,15 +147,37 @@ impl fmt::Display for NodeRpcIpPort {
     }
 }
 
-/// Struct that holds info about the running child process
+/// Info about the running sandbox node returned by rpc
+#[derive(Clone, Debug, Serialize)]
+pub struct SandboxNodeInfo {
+    #[serde(flatten)]
+    pub node_ref: NodeRpcIpPort,
+    pub name: String,
+    pub p2p_port: Option<u16>,
+    pub data_dir: PathBuf,
+    pub links: Vec<LinkInfo>,
+}
+
+/// Running (or killed) light-node child process
+struct SandboxNode {
+    name: String,
+    p2p_port: Option<u16>,
+    data_dir: PathBuf,
+    /// Sandboxed configuration, which is used also to restart the node
+    cfg: serde_json::Value,
+    process: Child,
+}
+
+/// Struct that holds info about the running child processes
 pub struct LightNodeRunner {
     executable_path: PathBuf,
     protocol_runner_executable_path: PathBuf,
-    _name: String,
+    name: String,
 
-    // TODO: TE-213 Launch multiple nodes + wrap process with rpc ip/port
-    // TODO: in main we have peers
-    process: Option<Child>,
+    /// Running nodes identified by rpc ip/port
+    nodes: HashMap<NodeRpcIpPort, SandboxNode>,
+    /// Links between nodes started in topology
+    links: Vec<SandboxLink>,
 }
 
 impl LightNodeRunner {
@@ -132,8 +191,9 @@ impl LightNodeRunner {
         Self {
             executable_path,
             protocol_runner_executable_path,
-            _name: name.to_string(),
-            process: None,
+            name: name.to_string(),
+            nodes: HashMap::new(),
+            links: Vec::new(),
         }
     }
 
@@ -148,11 +208,16 @@ impl LightNodeRunner {
         cfg: serde_json::Value,
         log: &Logger,
     ) -> Result<(NodeRpcIpPort, PathBuf), LightNodeRunnerError> {
-        if self.is_running() {
-            Err(LightNodeRunnerError::NodeAlreadyRunning)
+        // parse rpc settings
+        let node = NodeRpcIpPort::new(&cfg)?;
+
+        if self.is_running(&node) {
+            Err(LightNodeRunnerError::NodeAlreadyRunning { node_ref: node })
         } else {
-            // parse rpc settings
-            let node = NodeRpcIpPort::new(&cfg)?;
+            let p2p_port = cfg
+                .get(NODE_CONFIG_P2P_PORT)
+                .and_then(|port| port.as_u64())
+                .map(|port| port as u16);
 
             // one node will have its own temp folder for data (identity, dbs, ...)
             let data_dir =
@@ -210,9 +275,7 @@ impl LightNodeRunner {
                 _ => {
                     // somehow process was not finished, so we need to kill him
                     match Self::send_sigint(process.id()) {
-                        Ok(()) => {
-                            self.process = None;
-                        }
+                        Ok(()) => (),
                         // if for some reason, the SIGINT fails to end the process, kill it with SIGKILL
                         Err(e) => {
                             let error_msg = handle_stderr(&mut process);
@@ -261,42 +324,288 @@ impl LightNodeRunner {
 
             // the process started up OK, but we restart it to enable normal logging (stderr won't be piped)
             // start the process again without piped stdout/stderr, e.g. to have ability log to syslog in docker to tezos-debugger
-            let process = Command::new(&self.executable_path)
-                .args(Self::construct_args(cfg, false)?)
-                .spawn()
-                .map_err(|err| LightNodeRunnerError::IOError {
-                    message: "Failed to start light-node".to_string(),
-                    reason: err,
-                })?;
-
-            self.process = Some(process);
+            let process = self.start_process(&cfg)?;
+
+            self.nodes.insert(
+                node.clone(),
+                SandboxNode {
+                    name: format!("{}-{}", self.name, node.port),
+                    p2p_port,
+                    data_dir: data_dir.clone(),
+                    cfg,
+                    process,
+                },
+            );
             Ok((node, data_dir))
         }
     }
 
+    /// Spawn light-node child processes connected in requested topology
+    ///
+    /// Every node gets its own rpc/p2p/websocket ports (incremented from configuration) and runs as private node,
+    /// which connects just to its neighbours (through link proxies, see [SandboxLink]).
+    pub fn spawn_topology(
+        &mut self,
+        request: TopologyRequest,
+        log: &Logger,
+    ) -> Result<Vec<(NodeRpcIpPort, PathBuf)>, LightNodeRunnerError> {
+        if request.nodes < 2 {
+            return Err(LightNodeRunnerError::InvalidTopology {
+                reason: format!(
+                    "at least 2 nodes are required, requested: {}",
+                    request.nodes
+                ),
+            });
+        }
+        if !request.config.is_object() {
+            return Err(LightNodeRunnerError::JsonParsingError {
+                json: request.config,
+            });
+        }
+
+        // resolve ports for all nodes
+        let rpc_port = NodeRpcIpPort::new(&request.config)?.port;
+        let p2p_port = match request
+            .config
+            .get(NODE_CONFIG_P2P_PORT)
+            .and_then(|port| port.as_u64())
+        {
+            Some(port) if port <= u16::MAX as u64 => port as u16,
+            _ => {
+                return Err(LightNodeRunnerError::InvalidTopology {
+                    reason: format!("missing or invalid u16 `{}`", NODE_CONFIG_P2P_PORT),
+                })
+            }
+        };
+        let websocket_address = match request.config.get(NODE_CONFIG_WEBSOCKET_ADDRESS) {
+            Some(address) => match address
+                .as_str()
+                .map(|address| address.parse::<SocketAddr>())
+            {
+                Some(Ok(address)) => Some(address),
+                _ => {
+                    return Err(LightNodeRunnerError::InvalidTopology {
+                        reason: format!("invalid `{}`", NODE_CONFIG_WEBSOCKET_ADDRESS),
+                    })
+                }
+            },
+            None => None,
+        };
+        let port_for = |base: u16, index: usize| {
+            base.checked_add(index as u16)
+                .ok_or_else(|| LightNodeRunnerError::InvalidTopology {
+                    reason: format!("port {} + {} is out of range", base, index),
+                })
+        };
+        let mut nodes = Vec::with_capacity(request.nodes);
+        for index in 0..request.nodes {
+            let node_ref = NodeRpcIpPort::sandbox(port_for(rpc_port, index)?);
+            if self.is_running(&node_ref) {
+                return Err(LightNodeRunnerError::NodeAlreadyRunning { node_ref });
+            }
+            nodes.push((node_ref, port_for(p2p_port, index)?));
+        }
+        let websocket_addresses = match websocket_address {
+            Some(address) => (0..request.nodes)
+                .map(|index| {
+                    port_for(address.port(), index)
+                        .map(|port| Some(SocketAddr::new(address.ip(), port)))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![None; request.nodes],
+        };
+
+        // open links between nodes
+        let mut links = Vec::new();
+        for (a, b) in request.topology.links(request.nodes) {
+            links.push(
+                SandboxLink::open(nodes[a].clone(), nodes[b].clone(), log).map_err(|err| {
+                    LightNodeRunnerError::IOError {
+                        message: "Failed to open link proxy between sandbox nodes".to_string(),
+                        reason: err,
+                    }
+                })?,
+            );
+        }
+
+        // spawn nodes one by one
+        let mut spawned = Vec::with_capacity(request.nodes);
+        for ((node_ref, p2p_port), websocket_address) in nodes.iter().zip(websocket_addresses) {
+            let peers = links
+                .iter()
+                .filter_map(|link| link.dial_address(node_ref))
+                .map(|address| address.to_string())
+                .collect::<Vec<_>>();
+
+            let mut cfg = request.config.clone();
+            if let Some(map) = cfg.as_object_mut() {
+                map.insert(NODE_CONFIG_RPC_PORT.to_string(), node_ref.port.into());
+                map.insert(NODE_CONFIG_P2P_PORT.to_string(), (*p2p_port).into());
+                if let Some(websocket_address) = websocket_address {
+                    map.insert(
+                        NODE_CONFIG_WEBSOCKET_ADDRESS.to_string(),
+                        websocket_address.to_string().into(),
+                    );
+                }
+                map.insert(NODE_CONFIG_PEERS.to_string(), peers.join(",").into());
+                map.insert(NODE_CONFIG_PRIVATE_NODE.to_string(), true.into());
+                map.insert(NODE_CONFIG_DISABLE_BOOTSTRAP_LOOKUP.to_string(), "".into());
+                map.remove(NODE_CONFIG_BOOTSTRAP_LOOKUP_ADDRESS);
+                // node can have two connections with every neighbour (incoming and outgoing)
+                map.insert(NODE_CONFIG_PEER_THRESH_LOW.to_string(), peers.len().into());
+                map.insert(
+                    NODE_CONFIG_PEER_THRESH_HIGH.to_string(),
+                    (2 * peers.len()).into(),
+                );
+            }
+
+            info!(log, "Starting light-node in topology"; "node_ref" => format!("{}", node_ref), "peers" => peers.join(","));
+            match self.spawn(cfg, log) {
+                Ok(node) => spawned.push(node),
+                Err(e) => {
+                    // stop already started nodes, links are closed on drop
+                    for (node_ref, _) in &spawned {
+                        let _ = self.shutdown(node_ref);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        self.links.extend(links);
+        Ok(spawned)
+    }
+
     /// Shut down the light-node
     pub fn shutdown(&mut self, node_ref: &NodeRpcIpPort) -> Result<(), LightNodeRunnerError> {
-        if self.is_running() {
-            let process = self.process.as_mut().unwrap();
-            // kill with SIGINT (ctr-c)
-            match Self::send_sigint(process.id()) {
-                Ok(()) => {
-                    self.process = None;
-                    Ok(())
-                }
-                // if for some reason, the SIGINT fails to end the process, kill it with SIGKILL
-                Err(_) => {
-                    Self::terminate_ref(process);
-                    Ok(())
+        // close all links of the node
+        self.links.retain(|link| link.neighbour(node_ref).is_none());
+
+        let running = self.is_running(node_ref);
+        match self.nodes.remove(node_ref) {
+            Some(mut node) if running => {
+                // kill with SIGINT (ctr-c)
+                match Self::send_sigint(node.process.id()) {
+                    Ok(()) => Ok(()),
+                    // if for some reason, the SIGINT fails to end the process, kill it with SIGKILL
+                    Err(_) => {
+                        Self::terminate_ref(&mut node.process);
+                        Ok(())
+                    }
                 }
             }
-        } else {
-            Err(LightNodeRunnerError::NodeNotRunning {
+            _ => Err(LightNodeRunnerError::NodeNotRunning {
                 node_ref: node_ref.clone(),
+            }),
+        }
+    }
+
+    /// Kills the light-node (SIGKILL) without any cleanup, so it can be restarted with the same data
+    pub fn kill(&mut self, node_ref: &NodeRpcIpPort) -> Result<(), LightNodeRunnerError> {
+        if !self.is_running(node_ref) {
+            return Err(LightNodeRunnerError::NodeNotRunning {
+                node_ref: node_ref.clone(),
+            });
+        }
+        if let Some(node) = self.nodes.get_mut(node_ref) {
+            node.process
+                .kill()
+                .and_then(|_| node.process.wait())
+                .map_err(|err| LightNodeRunnerError::IOError {
+                    message: "Failed to kill light-node".to_string(),
+                    reason: err,
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Starts again the killed (or crashed) light-node with the same configuration and data
+    pub fn restart(&mut self, node_ref: &NodeRpcIpPort) -> Result<(), LightNodeRunnerError> {
+        if self.is_running(node_ref) {
+            return Err(LightNodeRunnerError::NodeAlreadyRunning {
+                node_ref: node_ref.clone(),
+            });
+        }
+        let process = match self.nodes.get(node_ref) {
+            Some(node) => self.start_process(&node.cfg)?,
+            None => {
+                return Err(LightNodeRunnerError::UnknownNode {
+                    node_ref: node_ref.clone(),
+                })
+            }
+        };
+        if let Some(node) = self.nodes.get_mut(node_ref) {
+            node.process = process;
+        }
+        Ok(())
+    }
+
+    /// Partition link between two nodes started in topology
+    pub fn partition(&mut self, request: &LinkRequest) -> Result<(), LightNodeRunnerError> {
+        self.find_link(request)?.partition();
+        Ok(())
+    }
+
+    /// Heal partitioned link between two nodes started in topology
+    pub fn heal(&mut self, request: &LinkRequest) -> Result<(), LightNodeRunnerError> {
+        self.find_link(request)?
+            .heal()
+            .map_err(|err| LightNodeRunnerError::IOError {
+                message: "Failed to reopen link proxy between sandbox nodes".to_string(),
+                reason: err,
+            })
+    }
+
+    /// Returns info about all running nodes (sorted by rpc port)
+    pub fn nodes_info(&self) -> Vec<SandboxNodeInfo> {
+        self.nodes
+            .iter()
+            .map(|(node_ref, node)| SandboxNodeInfo {
+                node_ref: node_ref.clone(),
+                name: node.name.clone(),
+                p2p_port: node.p2p_port,
+                data_dir: node.data_dir.clone(),
+                links: self
+                    .links
+                    .iter()
+                    .filter_map(|link| {
+                        link.neighbour(node_ref).map(|neighbour| LinkInfo {
+                            node: neighbour.clone(),
+                            partitioned: link.is_partitioned(),
+                        })
+                    })
+                    .collect(),
             })
+            .sorted_by_key(|info| info.node_ref.port)
+            .collect()
+    }
+
+    fn find_link(
+        &mut self,
+        request: &LinkRequest,
+    ) -> Result<&mut SandboxLink, LightNodeRunnerError> {
+        let node_a = NodeRpcIpPort::sandbox(request.node_a);
+        let node_b = NodeRpcIpPort::sandbox(request.node_b);
+        match self
+            .links
+            .iter_mut()
+            .find(|link| link.is_between(&node_a, &node_b))
+        {
+            Some(link) => Ok(link),
+            None => Err(LightNodeRunnerError::LinkNotFound { node_a, node_b }),
         }
     }
 
+    fn start_process(&self, cfg: &serde_json::Value) -> Result<Child, LightNodeRunnerError> {
+        Command::new(&self.executable_path)
+            .args(Self::construct_args(cfg.clone(), false)?)
+            .spawn()
+            .map_err(|err| LightNodeRunnerError::IOError {
+                message: "Failed to start light-node".to_string(),
+                reason: err,
+            })
+    }
+
     fn terminate_ref(process: &mut Child) {
         match process.wait_timeout(Self::PROCESS_WAIT_TIMEOUT).unwrap() {
             Some(_) => (),
@@ -307,9 +616,9 @@ impl LightNodeRunner {
         };
     }
 
-    fn is_running(&mut self) -> bool {
-        if let Some(process) = &mut self.process {
-            match process.try_wait() {
+    fn is_running(&mut self, node_ref: &NodeRpcIpPort) -> bool {
+        if let Some(node) = self.nodes.get_mut(node_ref) {
+            match node.process.try_wait() {
                 Ok(None) => true,
                 _ => false,
             }
@@ -0,0 +1,848 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Declarative sandbox scenarios (YAML or JSON) for integration tests.
+//!
+//! Scenario is a list of steps executed one by one. Steps which manage nodes (start, bake, kill, ...) are handled
+//! by the launcher's own warp filters (the same as the launcher RPCs, see [crate::filters::sandbox]), the other steps
+//! call node RPCs directly. The first failed step stops the scenario (remaining steps are skipped),
+//! all nodes are stopped at the end and results are reported as JUnit XML.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use failure::Fail;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use slog::{error, info, Logger};
+use warp::{Filter, Reply};
+
+use crate::node_runner::{LightNodeRunnerRef, NodeRpcIpPort};
+
+#[derive(Debug, Fail)]
+pub enum ScenarioError {
+    #[fail(
+        display = "Failed to read scenario file: {:?}, reason: {}",
+        path, reason
+    )]
+    IOError {
+        path: PathBuf,
+        reason: std::io::Error,
+    },
+
+    #[fail(display = "Invalid scenario file: {:?}, reason: {}", path, reason)]
+    InvalidScenario { path: PathBuf, reason: String },
+}
+
+/// Scenario loaded from file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub steps: Vec<ScenarioStep>,
+    /// Directory of the scenario file, `file` payloads are resolved relatively to this directory
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+impl Scenario {
+    /// Loads scenario from YAML (`.yaml`, `.yml`) or JSON file
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        let content = fs::read_to_string(path).map_err(|reason| ScenarioError::IOError {
+            path: path.to_path_buf(),
+            reason,
+        })?;
+        let invalid_scenario = |reason: String| ScenarioError::InvalidScenario {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let mut scenario: Scenario = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => {
+                serde_yaml::from_str(&content).map_err(|e| invalid_scenario(format!("{}", e)))?
+            }
+            _ => serde_json::from_str(&content).map_err(|e| invalid_scenario(format!("{}", e)))?,
+        };
+        scenario.base_dir = path
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+        Ok(scenario)
+    }
+}
+
+/// One step of the scenario, `name` is used in report (defaults to the action name)
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScenarioStep {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// Inline json or json file (relative to scenario file)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Payload {
+    File { file: PathBuf },
+    Inline(Value),
+}
+
+impl Payload {
+    fn resolve(&self, base_dir: &Path) -> Result<Value, String> {
+        match self {
+            Payload::Inline(value) => Ok(value.clone()),
+            Payload::File { file } => {
+                let path = base_dir.join(file);
+                let content = fs::read_to_string(&path).map_err(|e| {
+                    format!("Failed to read payload file: {:?}, reason: {}", path, e)
+                })?;
+                serde_json::from_str(&content)
+                    .map_err(|e| format!("Invalid payload file: {:?}, reason: {}", path, e))
+            }
+        }
+    }
+}
+
+/// Actions of the scenario steps, `node` is rpc port of the node (if not set, the node with the lowest rpc port is used)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Starts node (launcher `/start`)
+    Start { config: Payload },
+    /// Starts nodes in topology (launcher `/start_topology`)
+    StartTopology { topology: Payload },
+    /// Initializes wallets (launcher `/init_client`)
+    InitClient {
+        #[serde(default)]
+        node: Option<u16>,
+        wallets: Payload,
+    },
+    /// Activates protocol (launcher `/activate_protocol`)
+    ActivateProtocol {
+        #[serde(default)]
+        node: Option<u16>,
+        parameters: Payload,
+    },
+    /// Bakes `count` blocks (launcher `/bake`), with fixed `timestamp` the next blocks are baked `interval_secs` later
+    Bake {
+        #[serde(default)]
+        node: Option<u16>,
+        #[serde(default)]
+        alias: Option<String>,
+        #[serde(default = "default_bake_count")]
+        count: u32,
+        #[serde(default)]
+        timestamp: Option<String>,
+        #[serde(default = "default_bake_interval_secs")]
+        interval_secs: i64,
+    },
+    /// Injects operation (node `/injection/operation`)
+    InjectOperation {
+        #[serde(default)]
+        node: Option<u16>,
+        /// Signed operation bytes (hex)
+        data: String,
+    },
+    /// Sets/advances/resets clock of all nodes (launcher `/clock`)
+    Clock { update: Value },
+    /// Partitions link between nodes (launcher `/partition`)
+    Partition { node_a: u16, node_b: u16 },
+    /// Heals link between nodes (launcher `/heal`)
+    Heal { node_a: u16, node_b: u16 },
+    /// Kills node without cleanup (launcher `/kill`)
+    Kill {
+        #[serde(default)]
+        node: Option<u16>,
+    },
+    /// Restarts killed node (launcher `/restart`)
+    Restart {
+        #[serde(default)]
+        node: Option<u16>,
+    },
+    /// Stops node and removes its data (launcher `/stop`)
+    Stop {
+        #[serde(default)]
+        node: Option<u16>,
+    },
+    /// Calls node rpc (POST, if `body` is set) and checks the result
+    Rpc {
+        #[serde(default)]
+        node: Option<u16>,
+        path: String,
+        #[serde(default)]
+        body: Option<Value>,
+        #[serde(default)]
+        expect: Option<Condition>,
+    },
+    /// Polls node rpc until it succeeds and the result matches the condition
+    WaitFor {
+        #[serde(default)]
+        node: Option<u16>,
+        path: String,
+        #[serde(default)]
+        condition: Option<Condition>,
+        #[serde(default = "default_wait_timeout_secs")]
+        timeout_secs: u64,
+        #[serde(default = "default_wait_interval_millis")]
+        interval_millis: u64,
+    },
+}
+
+fn default_bake_count() -> u32 {
+    1
+}
+
+fn default_bake_interval_secs() -> i64 {
+    1
+}
+
+fn default_wait_timeout_secs() -> u64 {
+    60
+}
+
+fn default_wait_interval_millis() -> u64 {
+    500
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Start { .. } => "start",
+            Action::StartTopology { .. } => "start_topology",
+            Action::InitClient { .. } => "init_client",
+            Action::ActivateProtocol { .. } => "activate_protocol",
+            Action::Bake { .. } => "bake",
+            Action::InjectOperation { .. } => "inject_operation",
+            Action::Clock { .. } => "clock",
+            Action::Partition { .. } => "partition",
+            Action::Heal { .. } => "heal",
+            Action::Kill { .. } => "kill",
+            Action::Restart { .. } => "restart",
+            Action::Stop { .. } => "stop",
+            Action::Rpc { .. } => "rpc",
+            Action::WaitFor { .. } => "wait_for",
+        }
+    }
+}
+
+/// Condition on the json result of rpc, all set checks must pass
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Condition {
+    /// JSON pointer (e.g. `/level`) to the checked value, the whole result is checked, if not set
+    #[serde(default)]
+    pub pointer: String,
+    #[serde(default)]
+    pub equals: Option<Value>,
+    /// Checked value (number or numeric string) must be greater or equal
+    #[serde(default)]
+    pub gte: Option<f64>,
+    /// Checked value (number or numeric string) must be less or equal
+    #[serde(default)]
+    pub lte: Option<f64>,
+}
+
+impl Condition {
+    pub fn check(&self, result: &Value) -> Result<(), String> {
+        let value = result
+            .pointer(&self.pointer)
+            .ok_or_else(|| format!("Missing value at: '{}', result: {}", self.pointer, result))?;
+
+        if let Some(expected) = &self.equals {
+            if value != expected {
+                return Err(format!(
+                    "Value at: '{}' is {}, expected: {}",
+                    self.pointer, value, expected
+                ));
+            }
+        }
+        if self.gte.is_some() || self.lte.is_some() {
+            let number = match value {
+                Value::Number(number) => number.as_f64(),
+                Value::String(number) => number.parse::<f64>().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| format!("Value at: '{}' is not a number: {}", self.pointer, value))?;
+
+            if let Some(gte) = self.gte {
+                if number < gte {
+                    return Err(format!(
+                        "Value at: '{}' is {}, expected >= {}",
+                        self.pointer, number, gte
+                    ));
+                }
+            }
+            if let Some(lte) = self.lte {
+                if number > lte {
+                    return Err(format!(
+                        "Value at: '{}' is {}, expected <= {}",
+                        self.pointer, number, lte
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of one step
+#[derive(Clone, Debug)]
+pub enum StepOutcome {
+    Passed,
+    Failed(String),
+    Skipped,
+}
+
+#[derive(Clone, Debug)]
+pub struct StepReport {
+    pub name: String,
+    pub duration: Duration,
+    pub outcome: StepOutcome,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScenarioReport {
+    pub name: String,
+    pub steps: Vec<StepReport>,
+}
+
+impl ScenarioReport {
+    pub fn failures(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.outcome, StepOutcome::Failed(_)))
+            .count()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.outcome, StepOutcome::Skipped))
+            .count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+}
+
+/// Runs scenarios against the launcher's warp filter
+pub struct ScenarioRunner<F> {
+    api: F,
+    runner: LightNodeRunnerRef,
+    client: Client<HttpConnector>,
+    log: Logger,
+}
+
+impl<F> ScenarioRunner<F>
+where
+    F: Filter + 'static,
+    F::Extract: Reply + Send,
+{
+    pub fn new(api: F, runner: LightNodeRunnerRef, log: Logger) -> Self {
+        Self {
+            api,
+            runner,
+            client: Client::new(),
+            log,
+        }
+    }
+
+    /// Runs all steps of the scenario (until the first failure) and stops all nodes at the end
+    pub async fn run(&self, scenario: &Scenario) -> ScenarioReport {
+        info!(self.log, "Running scenario"; "scenario" => &scenario.name, "steps" => scenario.steps.len());
+
+        let mut steps = Vec::with_capacity(scenario.steps.len());
+        let mut failed = false;
+        for (index, step) in scenario.steps.iter().enumerate() {
+            let name = format!(
+                "{:02} {}",
+                index + 1,
+                step.name.as_deref().unwrap_or_else(|| step.action.name())
+            );
+            if failed {
+                steps.push(StepReport {
+                    name,
+                    duration: Duration::default(),
+                    outcome: StepOutcome::Skipped,
+                });
+                continue;
+            }
+
+            let started = Instant::now();
+            let outcome = match self.run_action(&step.action, &scenario.base_dir).await {
+                Ok(()) => {
+                    info!(self.log, "Scenario step passed"; "scenario" => &scenario.name, "step" => &name);
+                    StepOutcome::Passed
+                }
+                Err(reason) => {
+                    error!(self.log, "Scenario step failed"; "scenario" => &scenario.name, "step" => &name, "reason" => &reason);
+                    failed = true;
+                    StepOutcome::Failed(reason)
+                }
+            };
+            steps.push(StepReport {
+                name,
+                duration: started.elapsed(),
+                outcome,
+            });
+        }
+
+        self.stop_all_nodes().await;
+
+        ScenarioReport {
+            name: scenario.name.clone(),
+            steps,
+        }
+    }
+
+    async fn run_action(&self, action: &Action, base_dir: &Path) -> Result<(), String> {
+        match action {
+            Action::Start { config } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/start",
+                    None,
+                    Some(config.resolve(base_dir)?),
+                )
+                .await?;
+            }
+            Action::StartTopology { topology } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/start_topology",
+                    None,
+                    Some(topology.resolve(base_dir)?),
+                )
+                .await?;
+            }
+            Action::InitClient { node, wallets } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/init_client",
+                    *node,
+                    Some(wallets.resolve(base_dir)?),
+                )
+                .await?;
+            }
+            Action::ActivateProtocol { node, parameters } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/activate_protocol",
+                    *node,
+                    Some(parameters.resolve(base_dir)?),
+                )
+                .await?;
+            }
+            Action::Bake {
+                node,
+                alias,
+                count,
+                timestamp,
+                interval_secs,
+            } => {
+                let timestamp = match timestamp {
+                    Some(timestamp) => Some(
+                        chrono::DateTime::parse_from_rfc3339(timestamp).map_err(|e| {
+                            format!("Invalid timestamp: {}, reason: {}", timestamp, e)
+                        })?,
+                    ),
+                    None => None,
+                };
+                for index in 0..*count {
+                    let timestamp = timestamp.map(|timestamp| {
+                        (timestamp + chrono::Duration::seconds(index as i64 * interval_secs))
+                            .to_rfc3339()
+                    });
+                    match (alias, timestamp) {
+                        (None, None) => self.call_launcher(Method::GET, "/bake", *node, None),
+                        (Some(alias), timestamp) => self.call_launcher(
+                            Method::POST,
+                            "/bake",
+                            *node,
+                            Some(json!({ "alias": alias, "timestamp": timestamp })),
+                        ),
+                        (None, Some(_)) => {
+                            return Err(
+                                "`alias` is required for baking with fixed timestamp".to_string()
+                            )
+                        }
+                    }
+                    .await?;
+                }
+            }
+            Action::InjectOperation { node, data } => {
+                self.call_node(
+                    *node,
+                    "/injection/operation?chain_id=main",
+                    Some(Value::String(data.clone())),
+                )
+                .await?;
+            }
+            Action::Clock { update } => {
+                self.call_launcher(Method::POST, "/clock", None, Some(update.clone()))
+                    .await?;
+            }
+            Action::Partition { node_a, node_b } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/partition",
+                    None,
+                    Some(json!({ "node_a": node_a, "node_b": node_b })),
+                )
+                .await?;
+            }
+            Action::Heal { node_a, node_b } => {
+                self.call_launcher(
+                    Method::POST,
+                    "/heal",
+                    None,
+                    Some(json!({ "node_a": node_a, "node_b": node_b })),
+                )
+                .await?;
+            }
+            Action::Kill { node } => {
+                self.call_launcher(Method::GET, "/kill", *node, None)
+                    .await?;
+            }
+            Action::Restart { node } => {
+                self.call_launcher(Method::GET, "/restart", *node, None)
+                    .await?;
+            }
+            Action::Stop { node } => {
+                self.call_launcher(Method::GET, "/stop", *node, None)
+                    .await?;
+            }
+            Action::Rpc {
+                node,
+                path,
+                body,
+                expect,
+            } => {
+                let result = self.call_node(*node, path, body.clone()).await?;
+                if let Some(expect) = expect {
+                    expect.check(&result)?;
+                }
+            }
+            Action::WaitFor {
+                node,
+                path,
+                condition,
+                timeout_secs,
+                interval_millis,
+            } => {
+                let deadline = Instant::now() + Duration::from_secs(*timeout_secs);
+                loop {
+                    let result = match self.call_node(*node, path, None).await {
+                        Ok(result) => match condition {
+                            Some(condition) => condition.check(&result),
+                            None => Ok(()),
+                        },
+                        Err(e) => Err(e),
+                    };
+                    match result {
+                        Ok(()) => break,
+                        Err(e) if Instant::now() >= deadline => {
+                            return Err(format!(
+                                "Timeout after {}s, last error: {}",
+                                timeout_secs, e
+                            ))
+                        }
+                        Err(_) => tokio::time::sleep(Duration::from_millis(*interval_millis)).await,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls launcher rpc through its warp filter, `node` is passed as query parameter
+    async fn call_launcher(
+        &self,
+        method: Method,
+        path: &str,
+        node: Option<u16>,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let path = match node {
+            Some(node) => format!("{}?node={}", path, node),
+            None => path.to_string(),
+        };
+        let mut request = warp::test::request().method(method.as_str()).path(&path);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.reply(&self.api).await;
+        if !response.status().is_success() {
+            return Err(format!(
+                "Launcher rpc {} failed: {} - {}",
+                path,
+                response.status(),
+                String::from_utf8_lossy(response.body())
+            ));
+        }
+        serde_json::from_slice(response.body()).or_else(|_| {
+            Ok(Value::String(
+                String::from_utf8_lossy(response.body()).into(),
+            ))
+        })
+    }
+
+    /// Calls node rpc directly (POST, if body is set)
+    async fn call_node(
+        &self,
+        node: Option<u16>,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<Value, String> {
+        let node_ref = self.resolve_node(node)?;
+        let url = format!("http://{}:{}{}", node_ref.ip, node_ref.port, path);
+        let (method, body) = match body {
+            Some(body) => (Method::POST, Body::from(body.to_string())),
+            None => (Method::GET, Body::empty()),
+        };
+
+        let request = Request::builder()
+            .method(method)
+            .uri(&url)
+            .header("content-type", "application/json")
+            .body(body)
+            .map_err(|e| format!("Invalid node rpc request: {}, reason: {}", url, e))?;
+        let response = self
+            .client
+            .request(request)
+            .await
+            .map_err(|e| format!("Node rpc {} failed: {}", url, e))?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| format!("Node rpc {} failed: {}", url, e))?;
+
+        if !status.is_success() {
+            return Err(format!(
+                "Node rpc {} failed: {} - {}",
+                url,
+                status,
+                String::from_utf8_lossy(&body)
+            ));
+        }
+        serde_json::from_slice(&body)
+            .map_err(|e| format!("Node rpc {} returned invalid json: {}", url, e))
+    }
+
+    /// Returns requested node or the node with the lowest rpc port
+    fn resolve_node(&self, node: Option<u16>) -> Result<NodeRpcIpPort, String> {
+        if let Some(node) = node {
+            return Ok(NodeRpcIpPort::sandbox(node));
+        }
+        self.runner
+            .read()
+            .map_err(|e| format!("Cannot get read lock on runner: {}", e))?
+            .nodes_info()
+            .into_iter()
+            .next()
+            .map(|info| info.node_ref)
+            .ok_or_else(|| "There is no running sandbox node".to_string())
+    }
+
+    async fn stop_all_nodes(&self) {
+        let nodes = match self.runner.read() {
+            Ok(runner) => runner.nodes_info(),
+            Err(e) => {
+                error!(self.log, "Cannot get read lock on runner"; "reason" => format!("{}", e));
+                return;
+            }
+        };
+        for info in nodes {
+            // stop also cleans data of killed nodes, so errors are just logged
+            if let Err(e) = self
+                .call_launcher(Method::GET, "/stop", Some(info.node_ref.port), None)
+                .await
+            {
+                error!(self.log, "Failed to stop sandbox node after scenario"; "node_ref" => format!("{}", info.node_ref), "reason" => e);
+            }
+        }
+    }
+}
+
+/// Formats reports as JUnit XML (testsuite per scenario, testcase per step)
+pub fn junit_report(reports: &[ScenarioReport]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" skipped=\"{}\">\n",
+        reports
+            .iter()
+            .map(|report| report.steps.len())
+            .sum::<usize>(),
+        reports.iter().map(ScenarioReport::failures).sum::<usize>(),
+        reports.iter().map(ScenarioReport::skipped).sum::<usize>(),
+    ));
+    for report in reports {
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(&report.name),
+            report.steps.len(),
+            report.failures(),
+            report.skipped(),
+            report.duration().as_secs_f64()
+        ));
+        for step in &report.steps {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                escape_xml(&report.name),
+                escape_xml(&step.name),
+                step.duration.as_secs_f64()
+            ));
+            match &step.outcome {
+                StepOutcome::Passed => xml.push_str("/>\n"),
+                StepOutcome::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
+                StepOutcome::Failed(reason) => xml.push_str(&format!(
+                    ">\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                    escape_xml(reason)
+                )),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenario_deserialize_yaml() {
+        let scenario: Scenario = serde_yaml::from_str(
+            r#"
+name: bake and restart
+steps:
+  - start:
+      config:
+        file: sandbox_start_light_node_args.json
+  - name: wait for rpc
+    wait_for:
+      path: /chains/main/blocks/head/header
+      timeout_secs: 30
+  - bake:
+      alias: bootstrap1
+      count: 3
+      timestamp: "2021-03-01T12:00:00Z"
+  - kill:
+      node: 18732
+  - rpc:
+      path: /chains/main/blocks/head/header
+      expect:
+        pointer: /level
+        gte: 3
+"#,
+        )
+        .unwrap();
+
+        assert_eq!("bake and restart", scenario.name);
+        assert_eq!(5, scenario.steps.len());
+        assert!(matches!(
+            scenario.steps[0].action,
+            Action::Start {
+                config: Payload::File { .. }
+            }
+        ));
+        assert_eq!(Some("wait for rpc".to_string()), scenario.steps[1].name);
+        assert!(matches!(
+            scenario.steps[1].action,
+            Action::WaitFor {
+                timeout_secs: 30,
+                interval_millis: 500,
+                ..
+            }
+        ));
+        assert!(matches!(
+            scenario.steps[2].action,
+            Action::Bake {
+                count: 3,
+                interval_secs: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            scenario.steps[3].action,
+            Action::Kill { node: Some(18732) }
+        ));
+    }
+
+    #[test]
+    fn test_condition_check() {
+        let result = json!({"level": 5, "balance": "4000000000000", "protocol": "PsCARTHAG"});
+
+        let condition =
+            |pointer: &str, equals: Option<Value>, gte: Option<f64>, lte: Option<f64>| Condition {
+                pointer: pointer.to_string(),
+                equals,
+                gte,
+                lte,
+            };
+        assert!(condition("/level", Some(json!(5)), None, None)
+            .check(&result)
+            .is_ok());
+        assert!(condition("/level", None, Some(3.0), Some(5.0))
+            .check(&result)
+            .is_ok());
+        assert!(condition("/level", None, Some(6.0), None)
+            .check(&result)
+            .is_err());
+        assert!(condition("/balance", None, Some(1.0), None)
+            .check(&result)
+            .is_ok());
+        assert!(condition("/protocol", None, Some(1.0), None)
+            .check(&result)
+            .is_err());
+        assert!(condition("/missing", None, None, None)
+            .check(&result)
+            .is_err());
+        assert!(Condition::default().check(&result).is_ok());
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let report = ScenarioReport {
+            name: "scenario <1>".to_string(),
+            steps: vec![
+                StepReport {
+                    name: "01 start".to_string(),
+                    duration: Duration::from_millis(1500),
+                    outcome: StepOutcome::Passed,
+                },
+                StepReport {
+                    name: "02 bake".to_string(),
+                    duration: Duration::from_millis(10),
+                    outcome: StepOutcome::Failed("level is \"1\"".to_string()),
+                },
+                StepReport {
+                    name: "03 stop".to_string(),
+                    duration: Duration::default(),
+                    outcome: StepOutcome::Skipped,
+                },
+            ],
+        };
+
+        let xml = junit_report(&[report]);
+        assert!(xml.contains("<testsuites tests=\"3\" failures=\"1\" skipped=\"1\">"));
+        assert!(xml.contains("<testsuite name=\"scenario &lt;1&gt;\" tests=\"3\" failures=\"1\" skipped=\"1\" time=\"1.510\">"));
+        assert!(xml.contains("name=\"01 start\" time=\"1.500\"/>"));
+        assert!(xml.contains("<failure message=\"level is &quot;1&quot;\"/>"));
+        assert!(xml.contains("<skipped/>"));
+    }
+}
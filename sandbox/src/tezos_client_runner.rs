@@ -15,10 +15,15 @@ use slog::{error, info, warn, Logger};
 use warp::http::StatusCode;
 use warp::reject;
 
+use crypto::hash::SeedEd25519;
+
 use crate::handlers::ErrorMessage;
 use crate::node_runner::NodeRpcIpPort;
 use crate::rand_chars;
 
+/// Secret key of the sandbox activator (its public key is configured as `genesis_pubkey` of the sandbox node)
+pub const ACTIVATOR_SECRET_KEY: &str = "edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6";
+
 #[derive(Debug, Fail)]
 pub enum TezosClientRunnerError {
     /// IO Error.
@@ -36,6 +41,13 @@ pub enum TezosClientRunnerError {
     #[fail(display = "Alias ({}) does not exists among the known wallets", alias)]
     NonexistantWallet { alias: String },
 
+    /// Wallet secret key cannot be used for signing
+    #[fail(
+        display = "Invalid secret key for alias: {}, reason: {}",
+        alias, reason
+    )]
+    InvalidSecretKey { alias: String, reason: String },
+
     /// Serde Error.
     #[fail(display = "Error in serde, reason: {}", reason)]
     SerdeError { reason: serde_json::Error },
@@ -53,6 +65,13 @@ pub enum TezosClientRunnerError {
         node_ref
     )]
     SandboxDataDirNotInitialized { node_ref: NodeRpcIpPort },
+
+    /// Feature is not supported, when baking with tezos-client
+    #[fail(
+        display = "{} is supported just with native baking (launcher started without tezos-client path)",
+        feature
+    )]
+    NativeBakingRequired { feature: String },
 }
 
 impl From<std::io::Error> for TezosClientRunnerError {
@@ -82,10 +101,38 @@ pub struct Wallet {
     initial_balance: String,
 }
 
+impl Wallet {
+    pub fn public_key_hash(&self) -> &str {
+        &self.public_key_hash
+    }
+
+    /// Secret key used for native signing (only unencrypted ed25519 keys are supported)
+    pub fn secret_key(&self) -> Result<SeedEd25519, TezosClientRunnerError> {
+        parse_secret_key(&self.alias, &self.secret_key)
+    }
+}
+
+/// Parses unencrypted ed25519 secret key (with or without the `unencrypted:` prefix)
+pub fn parse_secret_key(
+    alias: &str,
+    secret_key: &str,
+) -> Result<SeedEd25519, TezosClientRunnerError> {
+    let secret_key = secret_key.trim_start_matches("unencrypted:");
+    SeedEd25519::from_base58_check(secret_key).map_err(|e| {
+        TezosClientRunnerError::InvalidSecretKey {
+            alias: alias.to_string(),
+            reason: format!("{}", e),
+        }
+    })
+}
+
 /// The json body incoming with the bake request containing the alias for the wallet to bake with
 #[derive(Clone, Debug, Deserialize)]
 pub struct BakeRequest {
     alias: String,
+    /// Fixed timestamp (RFC3339) of the baked block (supported just with native baking)
+    #[serde(default)]
+    pub timestamp: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -108,7 +155,8 @@ pub type TezosClientRunnerRef = Arc<RwLock<TezosClientRunner>>;
 #[derive(Clone)]
 pub struct TezosClientRunner {
     pub name: String,
-    pub executable_path: PathBuf,
+    /// Path to the tezos-client binary, if None, wallets are kept just by the launcher and blocks are baked natively (see [crate::native_baker])
+    pub executable_path: Option<PathBuf>,
 
     /// Temporary data per node
     sandbox_data: HashMap<NodeRpcIpPort, SandboxData>,
@@ -117,9 +165,9 @@ pub struct TezosClientRunner {
 /// A structure holding all the required parameters to activate an economic protocol
 #[derive(Clone, Debug, Deserialize)]
 pub struct TezosProtcolActivationParameters {
-    timestamp: String,
-    protocol_hash: String,
-    protocol_parameters: serde_json::Value,
+    pub timestamp: String,
+    pub protocol_hash: String,
+    pub protocol_parameters: serde_json::Value,
 }
 
 #[derive(Serialize, Clone, Debug)]
@@ -141,7 +189,7 @@ impl Default for TezosClientReply {
 }
 
 impl TezosClientRunner {
-    pub fn new(name: &str, executable_path: PathBuf) -> Self {
+    pub fn new(name: &str, executable_path: Option<PathBuf>) -> Self {
         Self {
             name: name.to_string(),
             executable_path,
@@ -149,6 +197,11 @@ impl TezosClientRunner {
         }
     }
 
+    /// Returns true, if tezos-client binary is not used
+    pub fn is_native(&self) -> bool {
+        self.executable_path.is_none()
+    }
+
     pub fn init_sandbox_data(&mut self, node_ref: NodeRpcIpPort, data_dir_path: PathBuf) {
         self.sandbox_data.insert(
             node_ref,
@@ -201,26 +254,7 @@ impl TezosClientRunner {
             }
         };
 
-        // get as mutable object, so we can insert the hardcoded bootstrap accounts
-        let params = if let Some(params) = activation_parameters.protocol_parameters.as_object_mut()
-        {
-            params
-        } else {
-            return Err(TezosClientRunnerError::ProtocolParameterError {
-                json: activation_parameters.protocol_parameters,
-            });
-        };
-
-        let wallet_activation: Vec<[String; 2]> = self
-            .wallets(node_ref)?
-            .clone()
-            .into_iter()
-            .map(|(_, w)| [w.public_key, w.initial_balance])
-            .collect();
-
-        // serialize the harcoded accounts as json array and include it in protocol_parameters
-        let sandbox_accounts = serde_json::json!(wallet_activation);
-        params.insert("bootstrap_accounts".to_string(), sandbox_accounts);
+        self.insert_bootstrap_accounts(&mut activation_parameters, node_ref)?;
 
         // create a temporary file, the tezos-client requires the parameters to be passed in a .json file
         let protocol_parameters_json_file =
@@ -262,32 +296,79 @@ impl TezosClientRunner {
         Ok(client_output)
     }
 
-    /// Bake a block with the bootstrap1 account
-    pub fn bake_block(
+    /// Inserts wallets of the node as bootstrap accounts to the protocol parameters
+    pub fn insert_bootstrap_accounts(
         &self,
-        request: Option<BakeRequest>,
+        activation_parameters: &mut TezosProtcolActivationParameters,
         node_ref: &NodeRpcIpPort,
-    ) -> Result<TezosClientReply, TezosClientRunnerError> {
-        let mut client_output: TezosClientReply = Default::default();
+    ) -> Result<(), TezosClientRunnerError> {
+        let wallet_activation: Vec<[String; 2]> = self
+            .wallets(node_ref)?
+            .clone()
+            .into_iter()
+            .map(|(_, w)| [w.public_key, w.initial_balance])
+            .collect();
+
+        // get as mutable object, so we can insert the hardcoded bootstrap accounts
+        let params = if let Some(params) = activation_parameters.protocol_parameters.as_object_mut()
+        {
+            params
+        } else {
+            return Err(TezosClientRunnerError::ProtocolParameterError {
+                json: activation_parameters.protocol_parameters.clone(),
+            });
+        };
 
-        let alias = if let Some(request) = request {
+        // serialize the harcoded accounts as json array and include it in protocol_parameters
+        let sandbox_accounts = serde_json::json!(wallet_activation);
+        params.insert("bootstrap_accounts".to_string(), sandbox_accounts);
+        Ok(())
+    }
+
+    /// Returns wallet requested for baking or an arbitrary wallet, if there is no request (GET)
+    pub fn baking_wallet(
+        &self,
+        request: Option<BakeRequest>,
+        node_ref: &NodeRpcIpPort,
+    ) -> Result<&Wallet, TezosClientRunnerError> {
+        if let Some(request) = request {
             if let Some(wallet) = self.wallets(node_ref)?.get(&request.alias) {
-                &wallet.alias
+                Ok(wallet)
             } else {
-                return Err(TezosClientRunnerError::NonexistantWallet {
+                Err(TezosClientRunnerError::NonexistantWallet {
                     alias: request.alias,
-                });
+                })
             }
         } else {
             // if there is no wallet provided in the request (GET) set the alias to be an arbitrary wallet
             if let Some(wallet) = self.wallets(node_ref)?.values().next() {
-                &wallet.alias
+                Ok(wallet)
             } else {
-                return Err(TezosClientRunnerError::NonexistantWallet {
+                Err(TezosClientRunnerError::NonexistantWallet {
                     alias: "-none-".to_string(),
-                });
+                })
             }
-        };
+        }
+    }
+
+    /// Bake a block with the bootstrap1 account
+    pub fn bake_block(
+        &self,
+        request: Option<BakeRequest>,
+        node_ref: &NodeRpcIpPort,
+    ) -> Result<TezosClientReply, TezosClientRunnerError> {
+        let mut client_output: TezosClientReply = Default::default();
+
+        if request
+            .as_ref()
+            .map_or(false, |request| request.timestamp.is_some())
+        {
+            return Err(TezosClientRunnerError::NativeBakingRequired {
+                feature: "Baking with fixed timestamp".to_string(),
+            });
+        }
+
+        let alias = &self.baking_wallet(request, node_ref)?.alias;
 
         self.run_client(
             node_ref,
@@ -306,6 +387,15 @@ impl TezosClientRunner {
     ) -> Result<TezosClientReply, TezosClientRunnerError> {
         let mut client_output: TezosClientReply = Default::default();
 
+        if self.is_native() {
+            // keys are used directly by the native baker, so just validate them
+            for wallet in requested_wallets {
+                wallet.secret_key()?;
+                self.insert_wallet(node_ref, wallet)?;
+            }
+            return Ok(client_output);
+        }
+
         self.run_client(
             node_ref,
             [
@@ -313,7 +403,7 @@ impl TezosClientRunner {
                 "secret",
                 "key",
                 "activator",
-                "unencrypted:edsk31vznjHSSpGExDMHYASz45VZqXN4DPxvsa4hAyY8dHM28cZzp6",
+                &format!("unencrypted:{}", ACTIVATOR_SECRET_KEY),
             ]
             .to_vec(),
             &mut client_output,
@@ -369,6 +459,17 @@ impl TezosClientRunner {
             }
         };
         let port = node_ref.port.to_string();
+        let executable_path = match &self.executable_path {
+            Some(executable_path) => executable_path,
+            None => {
+                return Err(TezosClientRunnerError::IOError {
+                    reason: std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "tezos-client binary is not configured (native baking is used)",
+                    ),
+                })
+            }
+        };
 
         let mut args = [
             // add base-dir
@@ -384,7 +485,7 @@ impl TezosClientRunner {
         args.extend(command_args);
 
         // call tezos-client
-        let output = Command::new(&self.executable_path)
+        let output = Command::new(executable_path)
             .args(args)
             .output()
             .map_err(|err| TezosClientRunnerError::IOError { reason: err })?;
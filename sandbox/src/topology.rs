@@ -0,0 +1,266 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Multi-node sandbox topologies.
+//!
+//! Nodes are not connected directly, every node dials its neighbours through a [LinkProxy] (tcp proxy owned by the launcher),
+//! so a link between two nodes can be partitioned (proxies are closed and all connections are dropped) and healed later.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+
+use serde::{Deserialize, Serialize};
+use slog::{debug, warn, Logger};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+
+use crate::node_runner::NodeRpcIpPort;
+
+/// Supported topologies of sandbox nodes
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// node_0 - node_1 - ... - node_n
+    Line,
+    /// node_0 is connected to all other nodes
+    Star,
+    /// every node is connected to every other node
+    FullMesh,
+}
+
+impl Topology {
+    /// Returns undirected links as pairs of node indexes (lower index first)
+    pub fn links(&self, nodes_count: usize) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Line => (1..nodes_count).map(|i| (i - 1, i)).collect(),
+            Topology::Star => (1..nodes_count).map(|i| (0, i)).collect(),
+            Topology::FullMesh => (0..nodes_count)
+                .flat_map(|i| (i + 1..nodes_count).map(move |j| (i, j)))
+                .collect(),
+        }
+    }
+}
+
+/// The json body incoming with the request to start nodes in topology
+#[derive(Clone, Debug, Deserialize)]
+pub struct TopologyRequest {
+    /// Count of nodes to start
+    pub nodes: usize,
+    pub topology: Topology,
+    /// Light-node configuration (the same as for the start endpoint) used for all nodes,
+    /// `rpc_port`, `p2p_port` (and `websocket_address`, if present) are incremented for every next node
+    pub config: serde_json::Value,
+}
+
+/// The json body incoming with the request to partition/heal link between two nodes (identified by rpc port)
+#[derive(Clone, Debug, Deserialize)]
+pub struct LinkRequest {
+    pub node_a: u16,
+    pub node_b: u16,
+}
+
+/// Link info returned by rpc
+#[derive(Clone, Debug, Serialize)]
+pub struct LinkInfo {
+    pub node: NodeRpcIpPort,
+    pub partitioned: bool,
+}
+
+/// Link between two sandbox nodes, every node dials the other one through its own proxy
+pub struct SandboxLink {
+    node_a: NodeRpcIpPort,
+    node_b: NodeRpcIpPort,
+    /// proxy used by node_a to connect to node_b
+    proxy_a_to_b: LinkProxy,
+    /// proxy used by node_b to connect to node_a
+    proxy_b_to_a: LinkProxy,
+}
+
+impl SandboxLink {
+    pub fn open(
+        (node_a, p2p_port_a): (NodeRpcIpPort, u16),
+        (node_b, p2p_port_b): (NodeRpcIpPort, u16),
+        log: &Logger,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            proxy_a_to_b: LinkProxy::open(localhost(p2p_port_b), log.clone())?,
+            proxy_b_to_a: LinkProxy::open(localhost(p2p_port_a), log.clone())?,
+            node_a,
+            node_b,
+        })
+    }
+
+    /// Returns address, which should be used by `node` to connect to the other node of this link
+    pub fn dial_address(&self, node: &NodeRpcIpPort) -> Option<SocketAddr> {
+        if *node == self.node_a {
+            Some(localhost(self.proxy_a_to_b.port))
+        } else if *node == self.node_b {
+            Some(localhost(self.proxy_b_to_a.port))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the other node of this link, if `node` is connected by this link
+    pub fn neighbour(&self, node: &NodeRpcIpPort) -> Option<&NodeRpcIpPort> {
+        if *node == self.node_a {
+            Some(&self.node_b)
+        } else if *node == self.node_b {
+            Some(&self.node_a)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_between(&self, node_a: &NodeRpcIpPort, node_b: &NodeRpcIpPort) -> bool {
+        self.neighbour(node_a) == Some(node_b)
+    }
+
+    pub fn is_partitioned(&self) -> bool {
+        !self.proxy_a_to_b.is_open() || !self.proxy_b_to_a.is_open()
+    }
+
+    /// Closes proxies in both directions, so nodes are disconnected and cannot connect to each other
+    pub fn partition(&mut self) {
+        self.proxy_a_to_b.close();
+        self.proxy_b_to_a.close();
+    }
+
+    /// Reopens proxies in both directions, nodes reconnect on their next peer check
+    pub fn heal(&mut self) -> io::Result<()> {
+        self.proxy_a_to_b.reopen()?;
+        self.proxy_b_to_a.reopen()
+    }
+}
+
+/// Tcp proxy listening on localhost and forwarding all connections to the target p2p address
+struct LinkProxy {
+    /// Local port, where proxy listens (preserved after close, so the same address can be used after reopen)
+    port: u16,
+    target: SocketAddr,
+    /// Dropping the sender stops listener and all forwarded connections (None, if closed)
+    shutdown: Option<watch::Sender<()>>,
+    log: Logger,
+}
+
+impl LinkProxy {
+    fn open(target: SocketAddr, log: Logger) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind(localhost(0))?;
+        let mut proxy = Self {
+            port: listener.local_addr()?.port(),
+            target,
+            shutdown: None,
+            log,
+        };
+        proxy.run(listener)?;
+        Ok(proxy)
+    }
+
+    fn is_open(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    fn close(&mut self) {
+        // drop sender, which stops proxy
+        self.shutdown = None;
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        if self.is_open() {
+            return Ok(());
+        }
+        let listener = std::net::TcpListener::bind(localhost(self.port))?;
+        self.run(listener)
+    }
+
+    fn run(&mut self, listener: std::net::TcpListener) -> io::Result<()> {
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+
+        tokio::spawn(accept_connections(
+            listener,
+            self.target,
+            shutdown_rx,
+            self.log.clone(),
+        ));
+        self.shutdown = Some(shutdown_tx);
+        Ok(())
+    }
+}
+
+async fn accept_connections(
+    listener: TcpListener,
+    target: SocketAddr,
+    mut shutdown: watch::Receiver<()>,
+    log: Logger,
+) {
+    loop {
+        tokio::select! {
+            _ = shutdown.changed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((incoming, _)) => {
+                    tokio::spawn(forward_connection(incoming, target, shutdown.clone(), log.clone()));
+                }
+                Err(e) => {
+                    warn!(log, "Link proxy failed to accept connection"; "target" => target, "reason" => format!("{}", e));
+                }
+            }
+        }
+    }
+}
+
+async fn forward_connection(
+    incoming: TcpStream,
+    target: SocketAddr,
+    mut shutdown: watch::Receiver<()>,
+    log: Logger,
+) {
+    let outgoing = match TcpStream::connect(target).await {
+        Ok(outgoing) => outgoing,
+        Err(e) => {
+            debug!(log, "Link proxy failed to connect to target node"; "target" => target, "reason" => format!("{}", e));
+            return;
+        }
+    };
+
+    let (mut incoming_rx, mut incoming_tx) = incoming.into_split();
+    let (mut outgoing_rx, mut outgoing_tx) = outgoing.into_split();
+
+    // forward until one side disconnects or proxy is closed, dropping streams closes both connections
+    tokio::select! {
+        _ = shutdown.changed() => (),
+        _ = tokio::io::copy(&mut incoming_rx, &mut outgoing_tx) => (),
+        _ = tokio::io::copy(&mut outgoing_rx, &mut incoming_tx) => (),
+    }
+}
+
+fn localhost(port: u16) -> SocketAddr {
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topology_links() {
+        assert_eq!(vec![(0, 1), (1, 2), (2, 3)], Topology::Line.links(4));
+        assert_eq!(vec![(0, 1), (0, 2), (0, 3)], Topology::Star.links(4));
+        assert_eq!(
+            vec![(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)],
+            Topology::FullMesh.links(4)
+        );
+        assert!(Topology::Line.links(1).is_empty());
+    }
+
+    #[test]
+    fn test_topology_deserialize() {
+        let request: TopologyRequest = serde_json::from_str(
+            r#"{"nodes": 3, "topology": "full_mesh", "config": {"rpc_port": 18732}}"#,
+        )
+        .unwrap();
+        assert_eq!(3, request.nodes);
+        assert_eq!(Topology::FullMesh, request.topology);
+    }
+}
@@ -25,6 +25,7 @@ use crate::state::head_state::{CurrentHeadRef, HeadResult, HeadState};
 use crate::state::synchronization_state::SynchronizationBootstrapStateRef;
 use crate::state::StateError;
 use crate::stats::apply_block_stats::{ApplyBlockStatsRef, BlockValidationTimer};
+use crate::stats::block_lifecycle::{BlockLifecycleStage, BlockLifecycleTracerRef};
 
 /// Message commands [`ChainCurrentHeadManager`] to process applied block.
 /// Chain_feeder propagates if block successfully validated and applied
@@ -67,11 +68,11 @@ pub struct ChainCurrentHeadManager {
     head_state: HeadState,
     /// Holds bootstrapped state
     current_bootstrap_state: SynchronizationBootstrapStateRef,
-    /// Holds "best" known remote head
-    remote_current_head_state: CurrentHeadRef,
 
     /// Internal stats
     apply_block_stats: ApplyBlockStatsRef,
+    /// Tracing of the block lifecycle
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
 }
 
 /// Reference to [chain manager](ChainManager) actor.
@@ -85,10 +86,10 @@ impl ChainCurrentHeadManager {
         persistent_storage: PersistentStorage,
         init_storage_data: StorageInitInfo,
         local_current_head_state: CurrentHeadRef,
-        remote_current_head_state: CurrentHeadRef,
         current_mempool_state: CurrentMempoolStateStorageRef,
         current_bootstrap_state: SynchronizationBootstrapStateRef,
         apply_block_stats: ApplyBlockStatsRef,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
     ) -> Result<ChainCurrentHeadManagerRef, CreateError> {
         sys.actor_of_props::<ChainCurrentHeadManager>(
             ChainCurrentHeadManager::name(),
@@ -97,10 +98,10 @@ impl ChainCurrentHeadManager {
                 persistent_storage,
                 init_storage_data,
                 local_current_head_state,
-                remote_current_head_state,
                 current_mempool_state,
                 current_bootstrap_state,
                 apply_block_stats,
+                block_lifecycle_tracer,
             )),
         )
     }
@@ -156,6 +157,15 @@ impl ChainCurrentHeadManager {
                                      "level" => new_head.level(),
                                      "result" => format!("{}", new_head_result)
             );
+            self.block_lifecycle_tracer.event(
+                new_head.block_hash(),
+                BlockLifecycleStage::HeadUpdate,
+                Some(format!(
+                    "level: {}, result: {}",
+                    new_head.level(),
+                    new_head_result
+                )),
+            );
 
             // notify other actors that new current head was changed
             // (this also notifies [mempool_prevalidator])
@@ -167,31 +177,41 @@ impl ChainCurrentHeadManager {
                 None,
             );
 
-            let mut is_bootstrapped = self.current_bootstrap_state.read()?.is_bootstrapped();
-
-            if !is_bootstrapped {
-                let chain_manager_current_level = new_head.level();
-
-                let remote_best_known_level = self
-                    .remote_current_head_state
-                    .read()?
-                    .as_ref()
-                    .map(|head| *head.level())
-                    .unwrap_or(0);
+            // notify indexers, that blocks from the old branch were rolled back
+            if let HeadResult::BranchSwitch(Some(reorg)) = &new_head_result {
+                if reorg.depth > 0 {
+                    info!(ctx.system.log(), "Chain reorganization";
+                        "old_head" => reorg.old_head.block_hash().to_base58_check(),
+                        "old_level" => reorg.old_head.level(),
+                        "new_head" => reorg.new_head.block_hash().to_base58_check(),
+                        "new_level" => reorg.new_head.level(),
+                        "common_ancestor" => reorg.common_ancestor.block_hash().to_base58_check(),
+                        "depth" => reorg.depth);
+                    self.shell_channel.tell(
+                        Publish {
+                            msg: ShellChannelMsg::Reorg(Arc::new(reorg.clone())),
+                            topic: ShellChannelTopic::ShellEvents.into(),
+                        },
+                        None,
+                    );
+                }
+            }
 
+            // update synchronization heuristic with our new head
+            let is_bootstrapped = {
                 let mut current_bootstrap_state = self.current_bootstrap_state.write()?;
-                is_bootstrapped = current_bootstrap_state.update_by_new_local_head(
-                    remote_best_known_level,
-                    *chain_manager_current_level,
-                );
+                let was_bootstrapped = current_bootstrap_state.is_bootstrapped();
+                let is_bootstrapped =
+                    current_bootstrap_state.update_by_new_local_head(block.header.timestamp());
 
-                if is_bootstrapped {
+                if !was_bootstrapped && is_bootstrapped {
                     info!(ctx.system.log(), "Bootstrapped (chain_current_head_manager)";
                        "num_of_peers_for_bootstrap_threshold" => current_bootstrap_state.num_of_peers_for_bootstrap_threshold(),
-                       "remote_best_known_level" => remote_best_known_level,
-                       "reached_on_level" => chain_manager_current_level);
+                       "sync_state" => current_bootstrap_state.sync_state().as_str(),
+                       "reached_on_level" => new_head.level());
                 }
-            }
+                is_bootstrapped
+            };
 
             // TODO: TE-369 - lazy feature, if multiple messages are waiting in queue, we just want to send the last one as first one and the other discard
 
@@ -200,7 +220,7 @@ impl ChainCurrentHeadManager {
             // e.g. if we just start to bootstrap from the scratch, we dont want to spam other nodes (with higher level)
             if is_bootstrapped {
                 match new_head_result {
-                    HeadResult::BranchSwitch => {
+                    HeadResult::BranchSwitch(_) => {
                         self.shell_channel.tell(
                             Publish {
                                 msg: ShellChannelMsg::AdvertiseToP2pNewCurrentBranch(
@@ -278,10 +298,10 @@ impl
         PersistentStorage,
         StorageInitInfo,
         CurrentHeadRef,
-        CurrentHeadRef,
         CurrentMempoolStateStorageRef,
         SynchronizationBootstrapStateRef,
         ApplyBlockStatsRef,
+        BlockLifecycleTracerRef,
     )> for ChainCurrentHeadManager
 {
     fn create_args(
@@ -290,19 +310,19 @@ impl
             persistent_storage,
             init_storage_data,
             local_current_head_state,
-            remote_current_head_state,
             current_mempool_state,
             current_bootstrap_state,
             apply_block_stats,
+            block_lifecycle_tracer,
         ): (
             ShellChannelRef,
             PersistentStorage,
             StorageInitInfo,
             CurrentHeadRef,
-            CurrentHeadRef,
             CurrentMempoolStateStorageRef,
             SynchronizationBootstrapStateRef,
             ApplyBlockStatsRef,
+            BlockLifecycleTracerRef,
         ),
     ) -> Self {
         ChainCurrentHeadManager {
@@ -316,8 +336,8 @@ impl
                 Arc::new(init_storage_data.genesis_block_header_hash),
             ),
             current_bootstrap_state,
-            remote_current_head_state,
             apply_block_stats,
+            block_lifecycle_tracer,
         }
     }
 }
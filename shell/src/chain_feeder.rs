@@ -5,8 +5,11 @@
 //! This actor is responsible for correct applying of blocks with Tezos protocol in context
 //! This actor is aslo responsible for correct initialization of genesis in storage.
 
+use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{channel, Receiver as QueueReceiver, Sender as QueueSender};
+use std::sync::mpsc::{
+    channel, Receiver as QueueReceiver, RecvTimeoutError, Sender as QueueSender,
+};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
@@ -17,29 +20,34 @@ use riker::actors::*;
 use slog::{debug, error, info, trace, warn, Logger};
 
 use crypto::hash::{BlockHash, ChainId, ContextHash};
+use ipc::IpcError;
+use storage::block_meta_storage::Meta;
 use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::context::{ContextApi, TezedgeContext};
 use storage::persistent::PersistentStorage;
 use storage::{
-    initialize_storage_with_genesis_block, store_applied_block_result, store_commit_genesis_result,
-    BlockMetaStorage, BlockMetaStorageReader, BlockStorage, BlockStorageReader, ChainMetaStorage,
-    OperationsMetaStorage, OperationsStorage, OperationsStorageReader, StorageError,
-    StorageInitInfo,
+    applied_block_additional_data, initialize_storage_with_genesis_block,
+    store_commit_genesis_result, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
+    BlockStorageReader, ChainMetaStorage, InvalidBlock, OperationsMetaStorage, OperationsStorage,
+    OperationsStorageReader, StorageError, StorageInitInfo,
 };
 use tezos_api::environment::TezosEnvironmentConfiguration;
-use tezos_api::ffi::ApplyBlockRequest;
+use tezos_api::ffi::{ApplyBlockRequest, ApplyBlockResponse};
 use tezos_wrapper::service::{
     handle_protocol_service_error, ProtocolController, ProtocolServiceError,
 };
-use tezos_wrapper::TezosApiConnectionPool;
+use tezos_wrapper::{ProtocolRunnerConnection, RunnerType, TezosApiConnectionPool};
 
 use crate::chain_current_head_manager::{ChainCurrentHeadManagerRef, ProcessValidatedBlock};
 use crate::chain_feeder_channel::{
     ChainFeederChannelMsg, ChainFeederChannelRef, ChainFeederChannelTopic,
 };
+use crate::chain_feeder_pipeline::{AppliedBlock, AppliedBlockWriter, BlockPrefetcher};
 use crate::peer_branch_bootstrapper::{BlockAlreadyApplied, PeerBranchBootstrapperRef};
 use crate::shell_channel::{ShellChannelMsg, ShellChannelRef};
 use crate::stats::apply_block_stats::BlockValidationTimer;
+use crate::stats::block_lifecycle::{BlockLifecycleStage, BlockLifecycleTracerRef};
+use crate::stats::write_runner_stats::{WriteRunnerRestartReason, WriteRunnerStatsRef};
 use crate::subscription::subscribe_to_shell_shutdown;
 use crate::utils::{dispatch_condvar_result, CondvarResult};
 use crate::validation;
@@ -110,6 +118,16 @@ struct ApplyBlock {
     envelope: ApplyCompletedBlock,
     chain_feeder: ChainFeederRef,
     request: ApplyBlockRequest,
+    /// When the request was added to the internal queue
+    queued_at: Instant,
+    /// How many times was the block sent to the protocol runner, which crashed meanwhile
+    attempts: usize,
+    /// How many times the protocol runner did not apply the block in timeout (e.g. protocol migration),
+    /// timeout is doubled for every next attempt, such block is never given up
+    timeouts: u32,
+    /// Request was prepared by the applier itself from the prefetched block and the result of its predecessor,
+    /// if it fails, block is just applied again the regular way (see [chain_feeder_pipeline](crate::chain_feeder_pipeline))
+    pipelined: bool,
 }
 
 impl ApplyBlock {
@@ -122,6 +140,10 @@ impl ApplyBlock {
             envelope,
             chain_feeder,
             request,
+            queued_at: Instant::now(),
+            attempts: 0,
+            timeouts: 0,
+            pipelined: false,
         }
     }
 }
@@ -168,6 +190,12 @@ impl ChainFeeder {
     /// This actor spawns a new thread in which it will periodically monitor [`persistent_storage`](PersistentStorage).
     /// Purpose of the monitoring thread is to detect whether it is possible to apply blocks received by the p2p layer.
     /// If the block can be applied, it is sent via IPC to the `protocol_runner`, where it is then applied by calling a tezos ffi.
+    ///
+    /// The (write) `protocol_runner` is supervised - if it dies or does not respond in `apply_block_timeout`,
+    /// it is restarted, context is re-initialized and not yet applied blocks are replayed, see [`write_runner_stats`](WriteRunnerStatsRef).
+    ///
+    /// Application of blocks is pipelined - up to `apply_block_look_ahead` next blocks are prefetched and results of applied blocks
+    /// are stored in the background, while the next block is applied (0 disables pipelining).
     pub fn actor(
         sys: &impl ActorRefFactory,
         chain_current_head_manager: ChainCurrentHeadManagerRef,
@@ -177,6 +205,10 @@ impl ChainFeeder {
         tezos_writeable_api: Arc<TezosApiConnectionPool>,
         init_storage_data: StorageInitInfo,
         tezos_env: TezosEnvironmentConfiguration,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        write_runner_stats: WriteRunnerStatsRef,
+        apply_block_timeout: Duration,
+        apply_block_look_ahead: usize,
         log: Logger,
     ) -> Result<ChainFeederRef, CreateError> {
         // spawn inner thread
@@ -188,6 +220,10 @@ impl ChainFeeder {
                 Arc::new(init_storage_data),
                 Arc::new(tezos_env),
                 tezos_writeable_api,
+                block_lifecycle_tracer,
+                write_runner_stats,
+                apply_block_timeout,
+                apply_block_look_ahead,
                 log,
             )
             .spawn_feeder_thread();
@@ -270,7 +306,12 @@ impl ChainFeeder {
         }
 
         // collect data
-        let request = self.prepare_apply_request(&msg.block_hash, msg.chain_id.as_ref().clone())?;
+        let request = prepare_apply_request(
+            self.block_storage.as_ref(),
+            self.operations_storage.as_ref(),
+            &msg.block_hash,
+            msg.chain_id.as_ref().clone(),
+        )?;
 
         // add request to queue
         let result_callback = msg.result_callback.clone();
@@ -293,50 +334,6 @@ impl ChainFeeder {
         }
     }
 
-    /// Collects complete data for applying block, if not complete, return None
-    fn prepare_apply_request(
-        &self,
-        block_hash: &BlockHash,
-        chain_id: ChainId,
-    ) -> Result<ApplyBlockRequest, StorageError> {
-        // get block header
-        let current_head = match self.block_storage.get(block_hash)? {
-            Some(block) => block,
-            None => return Err(StorageError::MissingKey),
-        };
-
-        // get operations
-        let operations = self.operations_storage.get_operations(block_hash)?;
-
-        // get predecessor metadata
-        let (
-            predecessor,
-            (
-                predecessor_block_metadata_hash,
-                predecessor_ops_metadata_hash,
-                predecessor_max_operations_ttl,
-            ),
-        ) = match self
-            .block_storage
-            .get_with_additional_data(&current_head.header.predecessor())?
-        {
-            Some((predecessor, predecessor_additional_data)) => {
-                (predecessor, predecessor_additional_data.into())
-            }
-            None => return Err(StorageError::MissingKey),
-        };
-
-        Ok(ApplyBlockRequest {
-            chain_id,
-            block_header: (&*current_head.header).clone(),
-            pred_header: (&*predecessor.header).clone(),
-            operations: ApplyBlockRequest::convert_operations(operations),
-            max_operations_ttl: predecessor_max_operations_ttl as i32,
-            predecessor_block_metadata_hash,
-            predecessor_ops_metadata_hash,
-        })
-    }
-
     fn check_blocks_for_apply(
         &self,
         msg: CheckBlocksForApply,
@@ -545,6 +542,32 @@ pub enum FeedChainError {
     StorageError { error: StorageError },
     #[fail(display = "Protocol service error error! Reason: {:?}", error)]
     ProtocolServiceError { error: ProtocolServiceError },
+    #[fail(display = "Protocol runner sub-process is not running")]
+    ProtocolRunnerNotRunning,
+    #[fail(display = "Applied block writer error, reason: {}", reason)]
+    AppliedBlockWriterError { reason: String },
+}
+
+impl FeedChainError {
+    /// Returns Some, if error was caused by failed (crashed/hung) protocol runner, which needs to be restarted
+    fn protocol_runner_failure(&self) -> Option<WriteRunnerRestartReason> {
+        match self {
+            FeedChainError::ProtocolRunnerNotRunning => Some(WriteRunnerRestartReason::Crash),
+            FeedChainError::ProtocolServiceError {
+                error:
+                    ProtocolServiceError::IpcError {
+                        reason: IpcError::ReceiveMessageTimeouted,
+                    },
+            } => Some(WriteRunnerRestartReason::Hang),
+            FeedChainError::ProtocolServiceError {
+                error: ProtocolServiceError::IpcError { .. },
+            } => Some(WriteRunnerRestartReason::Crash),
+            FeedChainError::ProtocolServiceError {
+                error: ProtocolServiceError::UnexpectedMessage { .. },
+            } => Some(WriteRunnerRestartReason::Crash),
+            _ => None,
+        }
+    }
 }
 
 impl From<StorageError> for FeedChainError {
@@ -568,6 +591,10 @@ pub(crate) struct BlockApplierThreadSpawner {
     init_storage_data: Arc<StorageInitInfo>,
     tezos_env: Arc<TezosEnvironmentConfiguration>,
     tezos_writeable_api: Arc<TezosApiConnectionPool>,
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
+    write_runner_stats: WriteRunnerStatsRef,
+    apply_block_timeout: Duration,
+    apply_block_look_ahead: usize,
     log: Logger,
 }
 
@@ -579,6 +606,10 @@ impl BlockApplierThreadSpawner {
         init_storage_data: Arc<StorageInitInfo>,
         tezos_env: Arc<TezosEnvironmentConfiguration>,
         tezos_writeable_api: Arc<TezosApiConnectionPool>,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        write_runner_stats: WriteRunnerStatsRef,
+        apply_block_timeout: Duration,
+        apply_block_look_ahead: usize,
         log: Logger,
     ) -> Self {
         Self {
@@ -588,6 +619,10 @@ impl BlockApplierThreadSpawner {
             tezos_writeable_api,
             init_storage_data,
             tezos_env,
+            block_lifecycle_tracer,
+            write_runner_stats,
+            apply_block_timeout,
+            apply_block_look_ahead,
             log,
         }
     }
@@ -611,6 +646,10 @@ impl BlockApplierThreadSpawner {
             let tezos_writeable_api = self.tezos_writeable_api.clone();
             let init_storage_data = self.init_storage_data.clone();
             let tezos_env = self.tezos_env.clone();
+            let block_lifecycle_tracer = self.block_lifecycle_tracer.clone();
+            let write_runner_stats = self.write_runner_stats.clone();
+            let apply_block_timeout = self.apply_block_timeout;
+            let apply_block_look_ahead = self.apply_block_look_ahead;
             let log = self.log.clone();
             let block_applier_run = block_applier_run.clone();
 
@@ -624,43 +663,121 @@ impl BlockApplierThreadSpawner {
                     persistent_storage.merkle(),
                 ));
 
+                // applied blocks are stored in the background, next blocks are prefetched (only if pipelining is enabled)
+                let applied_block_writer = AppliedBlockWriter::start(
+                    apply_block_look_ahead,
+                    &persistent_storage,
+                    chain_current_head_manager.clone(),
+                    chain_feeder_channel.clone(),
+                    block_applier_run.clone(),
+                    log.clone(),
+                );
+                let block_prefetcher = if apply_block_look_ahead > 0 {
+                    Some(BlockPrefetcher::start(
+                        apply_block_look_ahead,
+                        &persistent_storage,
+                        log.clone(),
+                    ))
+                } else {
+                    None
+                };
+
+                // blocks, which were sent to the failed protocol runner and needs to be replayed to the new one
+                let mut pending_blocks: VecDeque<ApplyBlock> = VecDeque::new();
+                let mut restart_delay = PROTOCOL_RUNNER_RESTART_DELAY.0;
+
                 block_applier_run.store(true, Ordering::Release);
                 info!(log, "Chain feeder started processing");
 
                 while block_applier_run.load(Ordering::Acquire) {
-                    match tezos_writeable_api.pool.get() {
-                        Ok(mut protocol_controller) => match feed_chain_to_protocol(
-                            &tezos_env,
-                            &init_storage_data,
-                            &block_applier_run,
-                            &chain_feeder_channel,
-                            &chain_current_head_manager,
-                            &block_storage,
-                            &block_meta_storage,
-                            &chain_meta_storage,
-                            &operations_meta_storage,
-                            &context,
-                            &protocol_controller.api,
-                            &mut block_applier_event_receiver,
-                            &log,
-                        ) {
-                            Ok(()) => {
-                                protocol_controller.set_release_on_return_to_pool();
-                                debug!(log, "Feed chain to protocol finished")
+                    match tezos_writeable_api.pool().get() {
+                        Ok(mut protocol_controller) => {
+                            let started_at = Instant::now();
+                            if let Ok(mut stats) = write_runner_stats.write() {
+                                stats.started += 1;
                             }
-                            Err(err) => {
-                                protocol_controller.set_release_on_return_to_pool();
-                                if block_applier_run.load(Ordering::Acquire) {
-                                    warn!(log, "Error while feeding chain to protocol"; "reason" => format!("{:?}", err));
+
+                            match feed_chain_to_protocol(
+                                &tezos_env,
+                                &init_storage_data,
+                                &block_applier_run,
+                                &chain_current_head_manager,
+                                &block_storage,
+                                &block_meta_storage,
+                                &chain_meta_storage,
+                                &operations_meta_storage,
+                                &context,
+                                &mut protocol_controller,
+                                &mut block_applier_event_receiver,
+                                &mut pending_blocks,
+                                &applied_block_writer,
+                                block_prefetcher.as_ref(),
+                                apply_block_timeout,
+                                &block_lifecycle_tracer,
+                                &write_runner_stats,
+                                &log,
+                            ) {
+                                Ok(()) => {
+                                    protocol_controller.set_release_on_return_to_pool();
+                                    debug!(log, "Feed chain to protocol finished")
+                                }
+                                Err(err) => {
+                                    protocol_controller.set_release_on_return_to_pool();
+                                    if block_applier_run.load(Ordering::Acquire) {
+                                        warn!(log, "Error while feeding chain to protocol"; "reason" => format!("{:?}", err));
+
+                                        if let Some(reason) = err.protocol_runner_failure() {
+                                            if reason == WriteRunnerRestartReason::Hang {
+                                                // hung runner does not react to graceful shutdown
+                                                protocol_controller.kill_subprocess();
+                                            }
+
+                                            // runner was running long enough, so it is not crash loop
+                                            if started_at.elapsed() > PROTOCOL_RUNNER_STABLE_UPTIME
+                                            {
+                                                restart_delay = PROTOCOL_RUNNER_RESTART_DELAY.0;
+                                            }
+
+                                            if let Ok(mut stats) = write_runner_stats.write() {
+                                                stats.record_restart(
+                                                    reason,
+                                                    format!("{}", err),
+                                                    chrono::Utc::now().timestamp(),
+                                                );
+                                            }
+                                            warn!(log, "Write protocol runner failed, restarting";
+                                                       "reason" => reason.as_str(),
+                                                       "pending_blocks" => pending_blocks.len(),
+                                                       "restart_delay" => format!("{:?}", restart_delay));
+
+                                            // wait a little bit (unparked on shutdown), to prevent fast crash loops
+                                            thread::park_timeout(restart_delay);
+                                            restart_delay = std::cmp::min(
+                                                restart_delay * 2,
+                                                PROTOCOL_RUNNER_RESTART_DELAY.1,
+                                            );
+                                        }
+                                    }
                                 }
                             }
-                        },
+                        }
                         Err(err) => {
                             warn!(log, "No connection from protocol runner"; "reason" => format!("{:?}", err))
                         }
                     }
                 }
 
+                // notify waiting callbacks of blocks, which were not replayed
+                for ApplyBlock { envelope, .. } in pending_blocks.drain(..) {
+                    if let Err(e) = dispatch_condvar_result(
+                        envelope.result_callback,
+                        || Err(format_err!("Chain feeder was shut down")),
+                        true,
+                    ) {
+                        warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+                    }
+                }
+
                 info!(log, "Chain feeder thread finished");
                 Ok(())
             })
@@ -677,15 +794,20 @@ fn feed_chain_to_protocol(
     tezos_env: &TezosEnvironmentConfiguration,
     init_storage_data: &StorageInitInfo,
     apply_block_run: &AtomicBool,
-    chain_feeder_channel: &ChainFeederChannelRef,
     chain_current_head_manager: &ChainCurrentHeadManagerRef,
     block_storage: &BlockStorage,
     block_meta_storage: &BlockMetaStorage,
     chain_meta_storage: &ChainMetaStorage,
     operations_meta_storage: &OperationsMetaStorage,
     context: &Box<dyn ContextApi>,
-    protocol_controller: &ProtocolController,
+    protocol_runner: &mut ProtocolRunnerConnection<RunnerType>,
     block_applier_event_receiver: &mut QueueReceiver<Event>,
+    pending_blocks: &mut VecDeque<ApplyBlock>,
+    applied_block_writer: &AppliedBlockWriter,
+    block_prefetcher: Option<&BlockPrefetcher>,
+    apply_block_timeout: Duration,
+    block_lifecycle_tracer: &BlockLifecycleTracerRef,
+    write_runner_stats: &WriteRunnerStatsRef,
     log: &Logger,
 ) -> Result<(), FeedChainError> {
     // at first we initialize protocol runtime and ffi context
@@ -697,243 +819,427 @@ fn feed_chain_to_protocol(
         chain_meta_storage,
         operations_meta_storage,
         context,
-        &protocol_controller,
+        &protocol_runner.api,
         &log,
         &tezos_env,
         &init_storage_data,
     )?;
 
     // now just check current head (at least genesis should be there)
-    if chain_meta_storage
-        .get_current_head(&init_storage_data.chain_id)?
-        .is_none()
-    {
-        // this should not happen here, we applied at least genesis before
-        return Err(FeedChainError::UnknownCurrentHeadError);
+    let current_head = match chain_meta_storage.get_current_head(&init_storage_data.chain_id)? {
+        Some(current_head) => current_head,
+        None => {
+            // this should not happen here, we applied at least genesis before
+            return Err(FeedChainError::UnknownCurrentHeadError);
+        }
     };
 
+    // check, that we have context of the last applied block (e.g. after restart of the protocol runner)
+    if let Some(current_head_header) = block_storage.get(current_head.block_hash())? {
+        match context.is_committed(current_head_header.header.context()) {
+            Ok(true) => (),
+            Ok(false) => {
+                error!(log, "Context of current head is not stored";
+                            "block" => current_head.block_hash().to_base58_check(),
+                            "level" => current_head.level(),
+                            "context" => current_head_header.header.context().to_base58_check())
+            }
+            Err(e) => {
+                warn!(log, "Failed to check context of current head"; "block" => current_head.block_hash().to_base58_check(), "reason" => format!("{}", e))
+            }
+        }
+    }
+    info!(log, "Protocol runner is ready to apply blocks";
+               "current_head" => current_head.block_hash().to_base58_check(),
+               "current_head_level" => current_head.level(),
+               "pending_blocks" => pending_blocks.len());
+
+    // blocks prepared by the applier itself from the prefetched blocks, see [chain_feeder_pipeline](crate::chain_feeder_pipeline)
+    let mut pipelined_blocks: VecDeque<ApplyBlock> = VecDeque::new();
+
     // now we can start applying block
     while apply_block_run.load(Ordering::Acquire) {
-        // let's handle event, if any
-        if let Ok(event) = block_applier_event_receiver.recv() {
-            match event {
-                Event::ApplyBlock(ApplyBlock {
-                    envelope:
-                        ApplyCompletedBlock {
-                            block_hash,
-                            result_callback,
-                            bootstrapper,
-                            roundtrip_timer,
-                            chain_id,
-                        },
-                    chain_feeder,
-                    request,
-                }) => {
-                    let block_hash = Arc::new(block_hash);
-                    let validated_at_timer = Instant::now();
-                    debug!(log, "Applying block"; "block_header_hash" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(), "sender" => sender_to_string(&bootstrapper));
-
-                    // check if block is already applied (not necessery here)
-                    let load_metadata_timer = Instant::now();
-                    let mut current_head_meta = match block_meta_storage.get(&block_hash)? {
-                        Some(meta) => {
-                            if meta.is_applied() {
-                                // block already applied - ok, doing nothing
-                                debug!(log, "Block is already applied (feeder)"; "block" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(), "sender" => sender_to_string(&bootstrapper));
-                                if let Err(e) = dispatch_condvar_result(
-                                    result_callback,
-                                    || Err(format_err!("Block is already applied")),
-                                    true,
-                                ) {
-                                    warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
-                                }
-
-                                // TODO: TE-369 - refactor pinging bootstrapper
-                                // if we have sender, we send him direct info
-                                if let Some(bootstrapper) = bootstrapper.as_ref() {
-                                    bootstrapper.tell(
-                                        BlockAlreadyApplied {
-                                            block_hash: block_hash.clone(),
-                                        },
-                                        None,
-                                    );
-                                }
+        // if some of the applied blocks was not stored, we need to start again
+        if let Some(e) = applied_block_writer.take_failure() {
+            return Err(e);
+        }
 
-                                continue;
-                            }
-                            meta
+        // at first replay blocks, which were not applied by previous (failed) protocol runner
+        let event = match pending_blocks.pop_front() {
+            Some(apply_block) => {
+                if let Ok(mut stats) = write_runner_stats.write() {
+                    stats.replayed_blocks += 1;
+                }
+                Event::ApplyBlock(apply_block)
+            }
+            None => match pipelined_blocks.pop_front() {
+                Some(apply_block) => Event::ApplyBlock(apply_block),
+                None => match block_applier_event_receiver
+                    .recv_timeout(PROTOCOL_RUNNER_LIVENESS_CHECK_INTERVAL)
+                {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => {
+                        // nothing to apply, so check, if protocol runner is still alive
+                        if !protocol_runner.is_running() {
+                            return Err(FeedChainError::ProtocolRunnerNotRunning);
                         }
-                        None => {
-                            warn!(log, "Block metadata not found (feeder)"; "block" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(),);
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+            },
+        };
+
+        match event {
+            Event::ApplyBlock(ApplyBlock {
+                envelope:
+                    ApplyCompletedBlock {
+                        block_hash,
+                        result_callback,
+                        bootstrapper,
+                        roundtrip_timer,
+                        chain_id,
+                    },
+                chain_feeder,
+                request,
+                queued_at,
+                attempts,
+                timeouts,
+                pipelined,
+            }) => {
+                let block_hash = Arc::new(block_hash);
+                let validated_at_timer = Instant::now();
+                block_lifecycle_tracer.record_elapsed(
+                    &block_hash,
+                    BlockLifecycleStage::QueuedForApply,
+                    queued_at.elapsed(),
+                    None,
+                );
+                debug!(log, "Applying block"; "block_header_hash" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(), "pipelined" => pipelined, "sender" => sender_to_string(&bootstrapper));
+
+                // check if block is already applied (not necessery here)
+                let load_metadata_timer = Instant::now();
+                if applied_block_writer.is_in_flight(&block_hash) {
+                    // block was already applied (pipelined), but it is not stored yet
+                    applied_block_writer.wait_until_written();
+                }
+                let current_head_meta = match block_meta_storage.get(&block_hash)? {
+                    Some(meta) => {
+                        if meta.is_applied() {
+                            // block already applied - ok, doing nothing
+                            debug!(log, "Block is already applied (feeder)"; "block" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(), "sender" => sender_to_string(&bootstrapper));
                             if let Err(e) = dispatch_condvar_result(
                                 result_callback,
-                                || Err(format_err!("Block metadata not found")),
+                                || Err(format_err!("Block is already applied")),
                                 true,
                             ) {
                                 warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
                             }
+
+                            // TODO: TE-369 - refactor pinging bootstrapper
+                            // if we have sender, we send him direct info
+                            if let Some(bootstrapper) = bootstrapper.as_ref() {
+                                bootstrapper.tell(
+                                    BlockAlreadyApplied {
+                                        block_hash: block_hash.clone(),
+                                    },
+                                    None,
+                                );
+                            }
+
                             continue;
                         }
-                    };
-                    let load_metadata_elapsed = load_metadata_timer.elapsed();
-
-                    // try apply block
-                    let protocol_call_timer = Instant::now();
-                    match protocol_controller.apply_block(request) {
-                        Ok(apply_block_result) => {
-                            let protocol_call_elapsed = protocol_call_timer.elapsed();
-                            debug!(log, "Block was applied";
+                        meta
+                    }
+                    None => {
+                        warn!(log, "Block metadata not found (feeder)"; "block" => block_hash.to_base58_check(), "chain_id" => chain_id.to_base58_check(),);
+                        if let Err(e) = dispatch_condvar_result(
+                            result_callback,
+                            || Err(format_err!("Block metadata not found")),
+                            true,
+                        ) {
+                            warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+                        }
+                        continue;
+                    }
+                };
+                let load_metadata_elapsed = load_metadata_timer.elapsed();
+
+                // successors can be prepared meanwhile
+                if let Some(block_prefetcher) = block_prefetcher {
+                    block_prefetcher.prefetch_successors(&block_hash, request.block_header.level());
+                }
+
+                // try apply block
+                let protocol_call_timer = Instant::now();
+                match protocol_runner.api.apply_block_with_timeout(
+                    request.clone(),
+                    apply_block_timeout_for_attempt(apply_block_timeout, timeouts),
+                ) {
+                    Ok(apply_block_result) => {
+                        let protocol_call_elapsed = protocol_call_timer.elapsed();
+                        block_lifecycle_tracer.record_elapsed(
+                            &block_hash,
+                            BlockLifecycleStage::ApplyBlock,
+                            protocol_call_elapsed,
+                            Some(apply_block_result.context_hash.to_base58_check()),
+                        );
+                        debug!(log, "Block was applied";
                                 "block_header_hash" => block_hash.to_base58_check(),
                                 "chain_id" => chain_id.to_base58_check(),
                                 "context_hash" => apply_block_result.context_hash.to_base58_check(),
                                 "validation_result_message" => &apply_block_result.validation_result_message,
                                 "sender" => sender_to_string(&bootstrapper));
 
-                            // we need to check and wait for context_hash to be 100% sure, that everything is ok
-                            let context_wait_timer = Instant::now();
-                            if let Err(e) =
-                                wait_for_context(context, &apply_block_result.context_hash)
-                            {
-                                error!(log,
-                                      "Failed to wait for context";
-                                      "block" => block_hash.to_base58_check(),
-                                      "chain_id" => chain_id.to_base58_check(),
-                                      "context" => apply_block_result.context_hash.to_base58_check(),
-                                      "reason" => format!("{}", e)
-                                );
-                                if let Err(e) = dispatch_condvar_result(
-                                    result_callback,
-                                    || {
-                                        Err(format_err!("Failed to wait for context, context_hash: {}, reason: {}", apply_block_result.context_hash.to_base58_check(), e)
-                                        )
-                                    },
-                                    true,
-                                ) {
-                                    warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+                        // successor (if prefetched) can be applied right away, we dont need to wait for storing of this block
+                        let next_block = match block_prefetcher {
+                            Some(block_prefetcher) => prepare_pipelined_block(
+                                block_prefetcher,
+                                &current_head_meta,
+                                &request,
+                                &apply_block_result,
+                                &chain_id,
+                                &bootstrapper,
+                                &chain_feeder,
+                            ),
+                            None => None,
+                        };
+
+                        // wait for context, store result and notify others (in the background)
+                        applied_block_writer.submit(AppliedBlock {
+                            block_hash,
+                            chain_id,
+                            roundtrip_timer,
+                            bootstrapper,
+                            result_callback,
+                            chain_feeder,
+                            block_meta: current_head_meta,
+                            apply_block_result,
+                            validated_at_timer,
+                            load_metadata_elapsed,
+                            protocol_call_elapsed,
+                        })?;
+
+                        match next_block {
+                            Some(next_block) => pipelined_blocks.push_back(next_block),
+                            None => {
+                                if block_prefetcher.is_none() {
+                                    // pipelining is disabled
+                                    applied_block_writer.wait_until_written();
                                 }
-                                return Err(FeedChainError::MissingContextError {
-                                    context_hash: apply_block_result.context_hash.to_base58_check(),
-                                });
                             }
-                            let context_wait_elapsed = context_wait_timer.elapsed();
-                            if context_wait_elapsed.gt(&CONTEXT_WAIT_DURATION_LONG_TO_LOG) {
-                                info!(log, "Block was applied with long context processing";
-                                           "block_header_hash" => block_hash.to_base58_check(),
-                                           "chain_id" => chain_id.to_base58_check(),
-                                           "context_hash" => apply_block_result.context_hash.to_base58_check(),
-                                           "context_wait_elapsed" => format!("{:?}", &context_wait_elapsed),
-                                           "sender" => sender_to_string(&bootstrapper));
+                        }
+                    }
+                    Err(pse) => {
+                        block_lifecycle_tracer.record_elapsed(
+                            &block_hash,
+                            BlockLifecycleStage::ApplyBlock,
+                            protocol_call_timer.elapsed(),
+                            Some(format!("failed: {}", pse)),
+                        );
+
+                        // protocol runner failed (not the block), so block will be replayed to the new protocol runner
+                        let protocol_runner_failed = matches!(
+                            pse,
+                            ProtocolServiceError::IpcError { .. }
+                                | ProtocolServiceError::UnexpectedMessage { .. }
+                        );
+                        // slow block is not a reason to give up, it is replayed with longer timeout
+                        let timed_out = matches!(
+                            pse,
+                            ProtocolServiceError::IpcError {
+                                reason: IpcError::ReceiveMessageTimeouted
                             }
+                        );
 
-                            // Lets mark header as applied and store result
-                            // store success result
-                            let store_result_timer = Instant::now();
-                            match store_applied_block_result(
-                                block_storage,
-                                block_meta_storage,
-                                &block_hash,
-                                apply_block_result,
-                                &mut current_head_meta,
-                            ) {
-                                Ok(_) => {
-                                    // now everythings stored, we are done
-
-                                    // notify condvar
-                                    if let Err(e) =
-                                        dispatch_condvar_result(result_callback, || Ok(()), true)
-                                    {
-                                        warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
-                                    }
-                                }
-                                Err(e) => {
-                                    if let Err(e) = dispatch_condvar_result(
-                                        result_callback,
-                                        || {
-                                            Err(format_err!(
-                                                "Failed to store applied result, reason: {}",
-                                                e
-                                            ))
-                                        },
-                                        true,
-                                    ) {
-                                        warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
-                                    }
-                                    return Err(e.into());
-                                }
-                            };
-                            let store_result_elapsed = store_result_timer.elapsed();
-
-                            // notify others
-                            if apply_block_run.load(Ordering::Acquire) {
-                                // now we want to parallelize and speed-up
-
-                                // 1. ping chain_feeder for successors check -> to queue
-                                let successors = current_head_meta.take_successors();
-                                if !successors.is_empty() {
-                                    chain_feeder.tell(
-                                        CheckBlocksForApply::new(
-                                            successors,
-                                            chain_id.clone(),
-                                            bootstrapper.clone(),
-                                            Some(block_hash.clone()),
-                                            Instant::now(),
-                                        ),
-                                        None,
-                                    );
-                                } else {
-                                    // TODO: TE-369 - refactor pinging bootstrapper
-                                    chain_feeder_channel.tell(
-                                        Publish {
-                                            msg: ChainFeederChannelMsg::BlockApplied(
-                                                block_hash.clone(),
-                                            ),
-                                            topic: ChainFeederChannelTopic::BlockApplied.into(),
-                                        },
-                                        None,
-                                    );
-                                }
+                        if pipelined {
+                            // block will be applied (and reported) the regular way, after its predecessor is stored
+                            debug!(log, "Pipelined apply of block failed, block will be applied again";
+                                        "block" => block_hash.to_base58_check(),
+                                        "reason" => format!("{}", pse));
+                            if let Some(block_prefetcher) = block_prefetcher {
+                                block_prefetcher.clear();
+                            }
+                            if protocol_runner_failed {
+                                return Err(pse.into());
+                            }
+                            continue;
+                        }
 
-                                // 2. ping chain current head manager
-                                chain_current_head_manager.tell(
-                                    ProcessValidatedBlock::new(
-                                        block_hash,
-                                        chain_id,
-                                        roundtrip_timer,
-                                        Arc::new(BlockValidationTimer::new(
-                                            validated_at_timer.elapsed(),
-                                            load_metadata_elapsed,
-                                            protocol_call_elapsed,
-                                            context_wait_elapsed,
-                                            store_result_elapsed,
-                                        )),
-                                    ),
-                                    None,
-                                );
+                        if timed_out
+                            || (protocol_runner_failed && attempts + 1 < MAX_APPLY_BLOCK_ATTEMPTS)
+                        {
+                            let (attempts, timeouts) = if timed_out {
+                                (attempts, timeouts + 1)
+                            } else {
+                                (attempts + 1, timeouts)
+                            };
+                            warn!(log, "Protocol runner failed to apply block, block will be replayed";
+                                       "block" => block_hash.to_base58_check(),
+                                       "attempts" => attempts,
+                                       "timeouts" => timeouts,
+                                       "next_timeout" => format!("{:?}", apply_block_timeout_for_attempt(apply_block_timeout, timeouts)),
+                                       "reason" => format!("{}", pse));
+                            pending_blocks.push_front(ApplyBlock {
+                                envelope: ApplyCompletedBlock {
+                                    block_hash: block_hash.as_ref().clone(),
+                                    chain_id,
+                                    roundtrip_timer,
+                                    bootstrapper,
+                                    result_callback,
+                                },
+                                chain_feeder,
+                                request,
+                                queued_at: Instant::now(),
+                                attempts,
+                                timeouts,
+                                pipelined,
+                            });
+                            return Err(pse.into());
+                        }
+                        if protocol_runner_failed {
+                            error!(log, "Giving up block apply, protocol runner failed too many times";
+                                        "block" => block_hash.to_base58_check(),
+                                        "attempts" => attempts + 1);
+                            if let Ok(mut stats) = write_runner_stats.write() {
+                                stats.abandoned_blocks += 1;
                             }
                         }
-                        Err(pse) => {
-                            if let Err(e) = dispatch_condvar_result(
-                                result_callback,
-                                || Err(format_err!("{}", pse)),
-                                true,
+
+                        // block was rejected by protocol, so remember it as invalid
+                        if let ProtocolServiceError::ProtocolError { .. } = pse {
+                            if let Err(e) = chain_meta_storage.add_invalid_block(
+                                &chain_id,
+                                InvalidBlock::new(
+                                    block_hash.as_ref().clone(),
+                                    request.block_header.level(),
+                                    format!("{}", pse),
+                                ),
                             ) {
-                                warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+                                warn!(log, "Failed to store invalid block"; "block" => block_hash.to_base58_check(), "reason" => format!("{}", e));
                             }
-                            handle_protocol_service_error(
-                                pse,
-                                |e| warn!(log, "Failed to apply block"; "block" => block_hash.to_base58_check(), "reason" => format!("{:?}", e)),
-                            )?;
                         }
+
+                        if let Err(e) = dispatch_condvar_result(
+                            result_callback,
+                            || Err(format_err!("{}", pse)),
+                            true,
+                        ) {
+                            warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+                        }
+                        handle_protocol_service_error(
+                            pse,
+                            |e| warn!(log, "Failed to apply block"; "block" => block_hash.to_base58_check(), "reason" => format!("{:?}", e)),
+                        )?;
                     }
                 }
-                Event::ShuttingDown => {
-                    apply_block_run.store(false, Ordering::Release);
-                }
+            }
+            Event::ShuttingDown => {
+                apply_block_run.store(false, Ordering::Release);
             }
         }
     }
 
-    Ok(())
+    // everything applied should be also stored
+    applied_block_writer.wait_until_written();
+    match applied_block_writer.take_failure() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Collects complete data for applying block from storage (predecessor must be already applied)
+fn prepare_apply_request(
+    block_storage: &dyn BlockStorageReader,
+    operations_storage: &dyn OperationsStorageReader,
+    block_hash: &BlockHash,
+    chain_id: ChainId,
+) -> Result<ApplyBlockRequest, StorageError> {
+    // get block header
+    let current_head = match block_storage.get(block_hash)? {
+        Some(block) => block,
+        None => return Err(StorageError::MissingKey),
+    };
+
+    // get operations
+    let operations = operations_storage.get_operations(block_hash)?;
+
+    // get predecessor metadata
+    let (
+        predecessor,
+        (
+            predecessor_block_metadata_hash,
+            predecessor_ops_metadata_hash,
+            predecessor_max_operations_ttl,
+        ),
+    ) = match block_storage.get_with_additional_data(&current_head.header.predecessor())? {
+        Some((predecessor, predecessor_additional_data)) => {
+            (predecessor, predecessor_additional_data.into())
+        }
+        None => return Err(StorageError::MissingKey),
+    };
+
+    Ok(ApplyBlockRequest {
+        chain_id,
+        block_header: (&*current_head.header).clone(),
+        pred_header: (&*predecessor.header).clone(),
+        operations: ApplyBlockRequest::convert_operations(operations),
+        max_operations_ttl: predecessor_max_operations_ttl as i32,
+        predecessor_block_metadata_hash,
+        predecessor_ops_metadata_hash,
+    })
+}
+
+/// Prepares request for the prefetched successor of the just applied block,
+/// data of the predecessor are taken directly from its result (which is not stored yet)
+fn prepare_pipelined_block(
+    block_prefetcher: &BlockPrefetcher,
+    applied_block_meta: &Meta,
+    applied_block_request: &ApplyBlockRequest,
+    applied_block_result: &ApplyBlockResponse,
+    chain_id: &Arc<ChainId>,
+    bootstrapper: &Option<PeerBranchBootstrapperRef>,
+    chain_feeder: &ChainFeederRef,
+) -> Option<ApplyBlock> {
+    let (block_hash, prefetched) =
+        applied_block_meta
+            .successors()
+            .iter()
+            .find_map(|successor| {
+                block_prefetcher
+                    .take(successor)
+                    .map(|prefetched| (successor.clone(), prefetched))
+            })?;
+
+    let (
+        predecessor_block_metadata_hash,
+        predecessor_ops_metadata_hash,
+        predecessor_max_operations_ttl,
+    ) = applied_block_additional_data(applied_block_result).into();
+
+    Some(ApplyBlock {
+        envelope: ApplyCompletedBlock::new(
+            block_hash,
+            chain_id.clone(),
+            None,
+            bootstrapper.clone(),
+            Instant::now(),
+        ),
+        chain_feeder: chain_feeder.clone(),
+        request: ApplyBlockRequest {
+            chain_id: applied_block_request.chain_id.clone(),
+            block_header: prefetched.block_header,
+            pred_header: applied_block_request.block_header.clone(),
+            operations: prefetched.operations,
+            max_operations_ttl: predecessor_max_operations_ttl as i32,
+            predecessor_block_metadata_hash,
+            predecessor_ops_metadata_hash,
+        },
+        queued_at: Instant::now(),
+        attempts: 0,
+        timeouts: 0,
+        pipelined: true,
+    })
 }
 
 /// This initializes ocaml runtime and protocol context,
@@ -1040,16 +1346,33 @@ pub(crate) fn initialize_protocol_context(
     Ok(())
 }
 
-fn sender_to_string(sender: &Option<PeerBranchBootstrapperRef>) -> String {
+/// Timeout for the next attempt to apply block, which was not applied `timeouts` times in timeout before
+/// (e.g. protocol migration block can take much longer, than regular block)
+fn apply_block_timeout_for_attempt(apply_block_timeout: Duration, timeouts: u32) -> Duration {
+    apply_block_timeout * 2u32.pow(std::cmp::min(timeouts, MAX_APPLY_BLOCK_TIMEOUT_DOUBLINGS))
+}
+
+pub(crate) fn sender_to_string(sender: &Option<PeerBranchBootstrapperRef>) -> String {
     match sender {
         Some(sender) => format!("{}-{}", sender.name(), sender.uri().to_string()),
         None => "--none--".to_string(),
     }
 }
 
+/// How many times we try to apply block with crashing protocol runner, before we give up (timeouts are not counted)
+const MAX_APPLY_BLOCK_ATTEMPTS: usize = 3;
+/// Max count of doubling of apply block timeout for block, which was not applied in timeout
+const MAX_APPLY_BLOCK_TIMEOUT_DOUBLINGS: u32 = 5;
+/// How often we check, if protocol runner is still alive, when there is nothing to apply
+const PROTOCOL_RUNNER_LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// Delay (min, max) before restart of failed protocol runner, delay is doubled with every restart
+const PROTOCOL_RUNNER_RESTART_DELAY: (Duration, Duration) =
+    (Duration::from_secs(1), Duration::from_secs(30));
+/// If protocol runner was running at least this time, restart delay is reset to min
+const PROTOCOL_RUNNER_STABLE_UPTIME: Duration = Duration::from_secs(300);
+
 const CONTEXT_WAIT_DURATION: (Duration, Duration) =
     (Duration::from_secs(300), Duration::from_millis(10));
-const CONTEXT_WAIT_DURATION_LONG_TO_LOG: Duration = Duration::from_secs(30);
 
 /// Context_listener is now asynchronous, so we need to make sure, that it is processed, so we wait a little bit
 pub fn wait_for_context(
@@ -1074,3 +1397,286 @@ pub fn wait_for_context(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use slog::Discard;
+
+    use storage::tests_common::TmpStorage;
+
+    use crate::chain_feeder_pipeline::tests::{
+        start_test_writer, store_test_chain, test_actor_system, test_applied_block, test_apply,
+        NoopChainFeeder,
+    };
+
+    use super::*;
+
+    type CheckedBlocks = Arc<Mutex<Vec<BlockHash>>>;
+
+    /// Stands for chain feeder, records blocks, which should be checked for apply
+    struct RecordingChainFeeder {
+        checked: CheckedBlocks,
+    }
+
+    impl ActorFactoryArgs<CheckedBlocks> for RecordingChainFeeder {
+        fn create_args(checked: CheckedBlocks) -> Self {
+            RecordingChainFeeder { checked }
+        }
+    }
+
+    impl Actor for RecordingChainFeeder {
+        type Msg = ChainFeederMsg;
+
+        fn recv(&mut self, _: &Context<Self::Msg>, msg: Self::Msg, _: Sender) {
+            if let ChainFeederMsg::CheckBlocksForApply(msg) = msg {
+                self.checked.lock().unwrap().extend(msg.blocks);
+            }
+        }
+    }
+
+    /// Successor is prefetched in the background, so we need to retry
+    fn wait_for_pipelined_block(
+        block_prefetcher: &BlockPrefetcher,
+        applied_block_meta: &Meta,
+        applied_block_request: &ApplyBlockRequest,
+        applied_block_result: &ApplyBlockResponse,
+        chain_id: &Arc<ChainId>,
+        chain_feeder: &ChainFeederRef,
+    ) -> ApplyBlock {
+        let started = Instant::now();
+        loop {
+            if let Some(next_block) = prepare_pipelined_block(
+                block_prefetcher,
+                applied_block_meta,
+                applied_block_request,
+                applied_block_result,
+                chain_id,
+                &None,
+                chain_feeder,
+            ) {
+                return next_block;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "Successor was not prefetched"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_pipelined_and_sequential_apply_give_same_results() -> Result<(), failure::Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let pipelined_storage = TmpStorage::create_to_out_dir("__test_pipelined_apply")?;
+        let sequential_storage = TmpStorage::create_to_out_dir("__test_sequential_apply")?;
+        let chain = store_test_chain(pipelined_storage.storage(), 10)?;
+        let _ = store_test_chain(sequential_storage.storage(), 10)?;
+        let chain_id = Arc::new(chain.chain_id.clone());
+
+        let sys = test_actor_system("test_pipelined_and_sequential_apply");
+        let chain_feeder: ChainFeederRef = sys
+            .actor_of::<NoopChainFeeder>("chain-feeder")
+            .expect("Failed to create chain feeder");
+
+        // pipelined - just the first block is prepared from storage, successors from the prefetched data and results
+        let mut pipelined = Vec::new();
+        {
+            let storage = pipelined_storage.storage();
+            let block_storage = BlockStorage::new(storage);
+            let block_meta_storage = BlockMetaStorage::new(storage);
+            let operations_storage = OperationsStorage::new(storage);
+            let block_prefetcher = BlockPrefetcher::start(2, storage, log.clone());
+            let applied_block_writer = start_test_writer(&sys, storage);
+
+            let mut next_block = Some((
+                chain.blocks[0].clone(),
+                prepare_apply_request(
+                    &block_storage,
+                    &operations_storage,
+                    &chain.blocks[0],
+                    chain.chain_id.clone(),
+                )?,
+            ));
+            while let Some((block_hash, request)) = next_block.take() {
+                let block_meta = block_meta_storage.get(&block_hash)?.expect("stored meta");
+                block_prefetcher.prefetch_successors(&block_hash, request.block_header.level());
+                let apply_block_result = test_apply(&request);
+
+                if !block_meta.successors().is_empty() {
+                    let ApplyBlock {
+                        envelope, request, ..
+                    } = wait_for_pipelined_block(
+                        &block_prefetcher,
+                        &block_meta,
+                        &request,
+                        &apply_block_result,
+                        &chain_id,
+                        &chain_feeder,
+                    );
+                    next_block = Some((envelope.block_hash, request));
+                }
+
+                let (applied_block, _) = test_applied_block(
+                    &block_hash,
+                    &chain.chain_id,
+                    &chain_feeder,
+                    block_meta,
+                    apply_block_result,
+                );
+                applied_block_writer.submit(applied_block)?;
+                pipelined.push((block_hash, request));
+            }
+            applied_block_writer.wait_until_written();
+            assert!(applied_block_writer.take_failure().is_none());
+        }
+
+        // sequential - every block is prepared from storage, after its predecessor is written
+        let mut sequential = Vec::new();
+        {
+            let storage = sequential_storage.storage();
+            let block_storage = BlockStorage::new(storage);
+            let block_meta_storage = BlockMetaStorage::new(storage);
+            let operations_storage = OperationsStorage::new(storage);
+            let applied_block_writer = start_test_writer(&sys, storage);
+
+            for block_hash in &chain.blocks {
+                let request = prepare_apply_request(
+                    &block_storage,
+                    &operations_storage,
+                    block_hash,
+                    chain.chain_id.clone(),
+                )?;
+                let (applied_block, _) = test_applied_block(
+                    block_hash,
+                    &chain.chain_id,
+                    &chain_feeder,
+                    block_meta_storage.get(block_hash)?.expect("stored meta"),
+                    test_apply(&request),
+                );
+                applied_block_writer.submit(applied_block)?;
+                applied_block_writer.wait_until_written();
+                sequential.push((block_hash.clone(), request));
+            }
+            assert!(applied_block_writer.take_failure().is_none());
+        }
+
+        assert_eq!(chain.blocks.len(), pipelined.len());
+        assert_eq!(sequential, pipelined);
+
+        // stored results are the same
+        let pipelined_block_storage = BlockStorage::new(pipelined_storage.storage());
+        let pipelined_block_meta_storage = BlockMetaStorage::new(pipelined_storage.storage());
+        let sequential_block_storage = BlockStorage::new(sequential_storage.storage());
+        let sequential_block_meta_storage = BlockMetaStorage::new(sequential_storage.storage());
+        for block_hash in &chain.blocks {
+            assert!(pipelined_block_meta_storage.is_applied(block_hash)?);
+            assert!(sequential_block_meta_storage.is_applied(block_hash)?);
+
+            let (_, pipelined_data) = pipelined_block_storage
+                .get_with_additional_data(block_hash)?
+                .expect("stored additional data");
+            let (_, sequential_data) = sequential_block_storage
+                .get_with_additional_data(block_hash)?
+                .expect("stored additional data");
+            let pipelined_data: (_, _, u16) = pipelined_data.into();
+            let sequential_data: (_, _, u16) = sequential_data.into();
+            assert_eq!(sequential_data, pipelined_data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_pipelined_block_is_applied_again_after_predecessor_is_written(
+    ) -> Result<(), failure::Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_failed_pipelined_block")?;
+        let storage = tmp_storage.storage();
+        let chain = store_test_chain(storage, 2)?;
+        let chain_id = Arc::new(chain.chain_id.clone());
+        let block_storage = BlockStorage::new(storage);
+        let block_meta_storage = BlockMetaStorage::new(storage);
+        let operations_storage = OperationsStorage::new(storage);
+
+        let sys = test_actor_system("test_failed_pipelined_block");
+        let checked: CheckedBlocks = Arc::new(Mutex::new(Vec::new()));
+        let chain_feeder: ChainFeederRef = sys
+            .actor_of_args::<RecordingChainFeeder, _>("chain-feeder", checked.clone())
+            .expect("Failed to create chain feeder");
+        let block_prefetcher = BlockPrefetcher::start(2, storage, log);
+        let applied_block_writer = start_test_writer(&sys, storage);
+
+        // block 1 is applied and block 2 is pipelined
+        let (block1, block2) = (&chain.blocks[0], &chain.blocks[1]);
+        let block1_request = prepare_apply_request(
+            &block_storage,
+            &operations_storage,
+            block1,
+            chain.chain_id.clone(),
+        )?;
+        let block1_meta = block_meta_storage.get(block1)?.expect("stored meta");
+        block_prefetcher.prefetch_successors(block1, block1_request.block_header.level());
+        let block1_result = test_apply(&block1_request);
+        let pipelined_block2 = wait_for_pipelined_block(
+            &block_prefetcher,
+            &block1_meta,
+            &block1_request,
+            &block1_result,
+            &chain_id,
+            &chain_feeder,
+        );
+        assert!(pipelined_block2.pipelined);
+        assert_eq!(block2, &pipelined_block2.envelope.block_hash);
+
+        let (applied_block1, _) = test_applied_block(
+            block1,
+            &chain.chain_id,
+            &chain_feeder,
+            block1_meta,
+            block1_result,
+        );
+        applied_block_writer.submit(applied_block1)?;
+
+        // pipelined apply of block 2 failed, so nothing prefetched is used anymore
+        block_prefetcher.clear();
+        assert!(block_prefetcher.take(block2).is_none());
+
+        // after block 1 is written, chain feeder is asked to check block 2 again
+        applied_block_writer.wait_until_written();
+        assert!(applied_block_writer.take_failure().is_none());
+        assert!(block_meta_storage.is_applied(block1)?);
+        assert!(!block_meta_storage.is_applied(block2)?);
+
+        let started = Instant::now();
+        while checked.lock().unwrap().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(vec![block2.clone()], *checked.lock().unwrap());
+
+        // and block 2 is applied the regular way, with the same request
+        let block2_request = prepare_apply_request(
+            &block_storage,
+            &operations_storage,
+            block2,
+            chain.chain_id.clone(),
+        )?;
+        assert_eq!(block2_request, pipelined_block2.request);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_block_timeout_is_doubled_for_timed_out_block() {
+        let timeout = Duration::from_secs(600);
+        assert_eq!(timeout, apply_block_timeout_for_attempt(timeout, 0));
+        assert_eq!(timeout * 2, apply_block_timeout_for_attempt(timeout, 1));
+        assert_eq!(timeout * 8, apply_block_timeout_for_attempt(timeout, 3));
+        assert_eq!(
+            timeout * 32,
+            apply_block_timeout_for_attempt(timeout, MAX_APPLY_BLOCK_TIMEOUT_DOUBLINGS)
+        );
+        // timeout stops growing, but block is still replayed
+        assert_eq!(timeout * 32, apply_block_timeout_for_attempt(timeout, 100));
+    }
+}
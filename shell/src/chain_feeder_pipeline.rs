@@ -0,0 +1,859 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Pipelining of block application for [chain feeder](crate::chain_feeder).
+//!
+//! Protocol runner can apply just one block at a time, so we try to keep it busy and move everything else out of the way:
+//! - [BlockPrefetcher] loads (and decodes) headers and operations of the next `look_ahead` blocks, while the current block is applied,
+//! - [AppliedBlockWriter] waits for context, stores results and notifies others about applied blocks in the background
+//!   (strictly in order of application), while the next block is applied.
+//!
+//! When pipelining is disabled (`look_ahead == 0`), nothing is prefetched and the applier waits for every block to be written.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{
+    channel, sync_channel, Receiver as QueueReceiver, Sender as QueueSender, SyncSender,
+};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use failure::format_err;
+use riker::actors::*;
+use slog::{debug, error, info, warn, Logger};
+
+use crypto::hash::{BlockHash, ChainId};
+use storage::block_meta_storage::Meta;
+use storage::context::{ContextApi, TezedgeContext};
+use storage::persistent::PersistentStorage;
+use storage::{
+    store_applied_block_result, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
+    BlockStorageReader, OperationsMetaStorage, OperationsStorage, OperationsStorageReader,
+    StorageError,
+};
+use tezos_api::ffi::{ApplyBlockRequest, ApplyBlockResponse};
+use tezos_messages::p2p::encoding::block_header::{BlockHeader, Level};
+use tezos_messages::p2p::encoding::operation::Operation;
+
+use crate::chain_current_head_manager::{ChainCurrentHeadManagerRef, ProcessValidatedBlock};
+use crate::chain_feeder::{
+    sender_to_string, wait_for_context, ChainFeederRef, CheckBlocksForApply, FeedChainError,
+};
+use crate::chain_feeder_channel::{
+    ChainFeederChannelMsg, ChainFeederChannelRef, ChainFeederChannelTopic,
+};
+use crate::peer_branch_bootstrapper::PeerBranchBootstrapperRef;
+use crate::stats::apply_block_stats::BlockValidationTimer;
+use crate::utils::{dispatch_condvar_result, CondvarResult};
+
+const CONTEXT_WAIT_DURATION_LONG_TO_LOG: Duration = Duration::from_secs(30);
+
+/// Block, which is ready to be applied, as soon as its predecessor is applied
+pub(crate) struct PrefetchedBlock {
+    pub(crate) block_header: BlockHeader,
+    pub(crate) operations: Vec<Vec<Operation>>,
+}
+
+/// Loads data of the successors of the block, which is being applied, in its own thread.
+///
+/// At most `look_ahead` blocks (and `look_ahead` levels) are kept, blocks which cannot be applied next are evicted.
+pub(crate) struct BlockPrefetcher {
+    prefetched: Arc<Mutex<HashMap<BlockHash, PrefetchedBlock>>>,
+    applying_sender: Option<QueueSender<(BlockHash, Level)>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BlockPrefetcher {
+    pub(crate) fn start(
+        look_ahead: usize,
+        persistent_storage: &PersistentStorage,
+        log: Logger,
+    ) -> Self {
+        let (applying_sender, applying_receiver) = channel();
+        let prefetched = Arc::new(Mutex::new(HashMap::new()));
+
+        let thread = {
+            let prefetched = prefetched.clone();
+            let block_storage = BlockStorage::new(persistent_storage);
+            let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+            let operations_storage = OperationsStorage::new(persistent_storage);
+            let operations_meta_storage = OperationsMetaStorage::new(persistent_storage);
+
+            thread::spawn(move || {
+                while let Ok(mut applying) = applying_receiver.recv() {
+                    // we are interested just in the last block, which is being applied
+                    while let Ok(newer) = applying_receiver.try_recv() {
+                        applying = newer;
+                    }
+                    if let Err(e) = prefetch_successors(
+                        applying,
+                        look_ahead,
+                        &prefetched,
+                        &block_storage,
+                        &block_meta_storage,
+                        &operations_storage,
+                        &operations_meta_storage,
+                    ) {
+                        warn!(log, "Failed to prefetch blocks for apply"; "reason" => format!("{}", e));
+                    }
+                }
+            })
+        };
+
+        BlockPrefetcher {
+            prefetched,
+            applying_sender: Some(applying_sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Block starts to be applied, so we can prepare its successors
+    pub(crate) fn prefetch_successors(&self, block_hash: &BlockHash, level: Level) {
+        if let Some(sender) = self.applying_sender.as_ref() {
+            let _ = sender.send((block_hash.clone(), level));
+        }
+    }
+
+    /// Returns prefetched block (if any), which is removed from the prefetcher
+    pub(crate) fn take(&self, block_hash: &BlockHash) -> Option<PrefetchedBlock> {
+        self.prefetched
+            .lock()
+            .ok()
+            .and_then(|mut prefetched| prefetched.remove(block_hash))
+    }
+
+    /// Forgets all prefetched blocks (e.g. when their predecessor failed to apply)
+    pub(crate) fn clear(&self) {
+        if let Ok(mut prefetched) = self.prefetched.lock() {
+            prefetched.clear();
+        }
+    }
+}
+
+impl Drop for BlockPrefetcher {
+    fn drop(&mut self) {
+        // closed channel stops the thread
+        drop(self.applying_sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Walks successors of the `applying` block (level by level) and loads the ones, which can be applied
+fn prefetch_successors(
+    (applying, applying_level): (BlockHash, Level),
+    look_ahead: usize,
+    prefetched: &Mutex<HashMap<BlockHash, PrefetchedBlock>>,
+    block_storage: &BlockStorage,
+    block_meta_storage: &BlockMetaStorage,
+    operations_storage: &OperationsStorage,
+    operations_meta_storage: &OperationsMetaStorage,
+) -> Result<(), StorageError> {
+    let prefetched_count = match prefetched.lock() {
+        Ok(mut prefetched) => {
+            // blocks on the same (or lower) level as applying block, will never be applied next
+            prefetched.retain(|_, block| block.block_header.level() > applying_level);
+            prefetched.len()
+        }
+        Err(_) => return Ok(()),
+    };
+    let mut free_slots = look_ahead.saturating_sub(prefetched_count);
+
+    let mut predecessors = vec![applying];
+    for _ in 0..look_ahead {
+        let mut next_predecessors = Vec::new();
+        for predecessor in predecessors {
+            let successors = match block_meta_storage.get(&predecessor)? {
+                Some(meta) => meta.take_successors(),
+                None => continue,
+            };
+            for successor in successors {
+                let already_prefetched = prefetched
+                    .lock()
+                    .map(|prefetched| prefetched.contains_key(&successor))
+                    .unwrap_or(false);
+                if already_prefetched {
+                    next_predecessors.push(successor);
+                    continue;
+                }
+                if free_slots == 0 {
+                    return Ok(());
+                }
+                if block_meta_storage.is_applied(&successor)?
+                    || !operations_meta_storage.is_complete(&successor)?
+                {
+                    continue;
+                }
+                let block_header = match block_storage.get(&successor)? {
+                    Some(block) => (&*block.header).clone(),
+                    None => continue,
+                };
+                let operations = ApplyBlockRequest::convert_operations(
+                    operations_storage.get_operations(&successor)?,
+                );
+
+                if let Ok(mut prefetched) = prefetched.lock() {
+                    prefetched.insert(
+                        successor.clone(),
+                        PrefetchedBlock {
+                            block_header,
+                            operations,
+                        },
+                    );
+                    free_slots -= 1;
+                }
+                next_predecessors.push(successor);
+            }
+        }
+        if next_predecessors.is_empty() {
+            break;
+        }
+        predecessors = next_predecessors;
+    }
+
+    Ok(())
+}
+
+/// Result of the block applied by protocol runner, which needs to be stored and announced
+pub(crate) struct AppliedBlock {
+    pub(crate) block_hash: Arc<BlockHash>,
+    pub(crate) chain_id: Arc<ChainId>,
+    pub(crate) roundtrip_timer: Arc<Instant>,
+    pub(crate) bootstrapper: Option<PeerBranchBootstrapperRef>,
+    pub(crate) result_callback: Option<CondvarResult<(), failure::Error>>,
+    pub(crate) chain_feeder: ChainFeederRef,
+    pub(crate) block_meta: Meta,
+    pub(crate) apply_block_result: ApplyBlockResponse,
+    pub(crate) validated_at_timer: Instant,
+    pub(crate) load_metadata_elapsed: Duration,
+    pub(crate) protocol_call_elapsed: Duration,
+}
+
+/// Blocks, which were applied, but not yet written
+type InFlightBlocks = Arc<(Mutex<HashSet<BlockHash>>, Condvar)>;
+
+/// Writes applied blocks in its own thread, at most `capacity` blocks can wait for write.
+///
+/// After the first failed write, following blocks are discarded (their callbacks get error), until the failure is taken.
+pub(crate) struct AppliedBlockWriter {
+    applied_sender: Option<SyncSender<AppliedBlock>>,
+    in_flight: InFlightBlocks,
+    failure: Arc<Mutex<Option<FeedChainError>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AppliedBlockWriter {
+    pub(crate) fn start(
+        capacity: usize,
+        persistent_storage: &PersistentStorage,
+        chain_current_head_manager: ChainCurrentHeadManagerRef,
+        chain_feeder_channel: ChainFeederChannelRef,
+        apply_block_run: Arc<AtomicBool>,
+        log: Logger,
+    ) -> Self {
+        let (applied_sender, applied_receiver) = sync_channel(capacity);
+        let in_flight: InFlightBlocks = Arc::new((Mutex::new(HashSet::new()), Condvar::new()));
+        let failure = Arc::new(Mutex::new(None));
+
+        let thread = {
+            let in_flight = in_flight.clone();
+            let failure = failure.clone();
+            let block_storage = BlockStorage::new(persistent_storage);
+            let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+            let merkle = persistent_storage.merkle();
+
+            thread::spawn(move || {
+                let context: Box<dyn ContextApi> =
+                    Box::new(TezedgeContext::new(block_storage.clone(), merkle));
+                write_applied_blocks(
+                    applied_receiver,
+                    &in_flight,
+                    &failure,
+                    &block_storage,
+                    &block_meta_storage,
+                    &context,
+                    &chain_current_head_manager,
+                    &chain_feeder_channel,
+                    &apply_block_run,
+                    &log,
+                );
+            })
+        };
+
+        AppliedBlockWriter {
+            applied_sender: Some(applied_sender),
+            in_flight,
+            failure,
+            thread: Some(thread),
+        }
+    }
+
+    /// Adds block to the write queue, blocks, if the queue is full
+    pub(crate) fn submit(&self, applied_block: AppliedBlock) -> Result<(), FeedChainError> {
+        let block_hash = applied_block.block_hash.as_ref().clone();
+        {
+            let (in_flight, _) = &*self.in_flight;
+            if let Ok(mut in_flight) = in_flight.lock() {
+                in_flight.insert(block_hash.clone());
+            }
+        }
+
+        let sent = match self.applied_sender.as_ref() {
+            Some(sender) => sender.send(applied_block).map_err(|e| e.0),
+            None => Err(applied_block),
+        };
+        match sent {
+            Ok(()) => Ok(()),
+            Err(applied_block) => {
+                self.written(&block_hash);
+                let _ = dispatch_condvar_result(
+                    applied_block.result_callback,
+                    || Err(format_err!("Applied block writer is not running")),
+                    true,
+                );
+                Err(FeedChainError::AppliedBlockWriterError {
+                    reason: "writer thread is not running".to_string(),
+                })
+            }
+        }
+    }
+
+    /// Returns true, if block was applied, but was not written yet
+    pub(crate) fn is_in_flight(&self, block_hash: &BlockHash) -> bool {
+        let (in_flight, _) = &*self.in_flight;
+        in_flight
+            .lock()
+            .map(|in_flight| in_flight.contains(block_hash))
+            .unwrap_or(false)
+    }
+
+    /// Blocks until all submitted blocks are written (or discarded)
+    pub(crate) fn wait_until_written(&self) {
+        let (in_flight, written) = &*self.in_flight;
+        if let Ok(mut in_flight) = in_flight.lock() {
+            while !in_flight.is_empty() {
+                in_flight = match written.wait(in_flight) {
+                    Ok(in_flight) => in_flight,
+                    Err(_) => return,
+                };
+            }
+        }
+    }
+
+    /// Returns failure of write (if any), following blocks will be written again
+    pub(crate) fn take_failure(&self) -> Option<FeedChainError> {
+        self.failure
+            .lock()
+            .ok()
+            .and_then(|mut failure| failure.take())
+    }
+
+    fn written(&self, block_hash: &BlockHash) {
+        mark_written(&self.in_flight, block_hash)
+    }
+}
+
+impl Drop for AppliedBlockWriter {
+    fn drop(&mut self) {
+        // closed channel stops the thread, after all queued blocks are written
+        drop(self.applied_sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn mark_written(in_flight: &InFlightBlocks, block_hash: &BlockHash) {
+    let (in_flight, written) = &**in_flight;
+    if let Ok(mut in_flight) = in_flight.lock() {
+        in_flight.remove(block_hash);
+    }
+    written.notify_all();
+}
+
+fn write_applied_blocks(
+    applied_receiver: QueueReceiver<AppliedBlock>,
+    in_flight: &InFlightBlocks,
+    failure: &Mutex<Option<FeedChainError>>,
+    block_storage: &BlockStorage,
+    block_meta_storage: &BlockMetaStorage,
+    context: &Box<dyn ContextApi>,
+    chain_current_head_manager: &ChainCurrentHeadManagerRef,
+    chain_feeder_channel: &ChainFeederChannelRef,
+    apply_block_run: &AtomicBool,
+    log: &Logger,
+) {
+    while let Ok(applied_block) = applied_receiver.recv() {
+        let block_hash = applied_block.block_hash.clone();
+        let failed = failure
+            .lock()
+            .map(|failure| failure.is_some())
+            .unwrap_or(true);
+
+        if failed {
+            debug!(log, "Discarding applied block, because previous block was not written"; "block" => block_hash.to_base58_check());
+            if let Err(e) = dispatch_condvar_result(
+                applied_block.result_callback,
+                || {
+                    Err(format_err!(
+                        "Block was not stored, because previous block failed to store"
+                    ))
+                },
+                true,
+            ) {
+                warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+            }
+        } else if let Err(e) = write_applied_block(
+            applied_block,
+            block_storage,
+            block_meta_storage,
+            context,
+            chain_current_head_manager,
+            chain_feeder_channel,
+            apply_block_run,
+            log,
+        ) {
+            if let Ok(mut failure) = failure.lock() {
+                *failure = Some(e);
+            }
+        }
+
+        mark_written(in_flight, &block_hash);
+    }
+}
+
+/// Waits for context, stores result of the applied block and notifies others
+fn write_applied_block(
+    AppliedBlock {
+        block_hash,
+        chain_id,
+        roundtrip_timer,
+        bootstrapper,
+        result_callback,
+        chain_feeder,
+        mut block_meta,
+        apply_block_result,
+        validated_at_timer,
+        load_metadata_elapsed,
+        protocol_call_elapsed,
+    }: AppliedBlock,
+    block_storage: &BlockStorage,
+    block_meta_storage: &BlockMetaStorage,
+    context: &Box<dyn ContextApi>,
+    chain_current_head_manager: &ChainCurrentHeadManagerRef,
+    chain_feeder_channel: &ChainFeederChannelRef,
+    apply_block_run: &AtomicBool,
+    log: &Logger,
+) -> Result<(), FeedChainError> {
+    // we need to check and wait for context_hash to be 100% sure, that everything is ok
+    let context_wait_timer = Instant::now();
+    if let Err(e) = wait_for_context(context, &apply_block_result.context_hash) {
+        error!(log,
+              "Failed to wait for context";
+              "block" => block_hash.to_base58_check(),
+              "chain_id" => chain_id.to_base58_check(),
+              "context" => apply_block_result.context_hash.to_base58_check(),
+              "reason" => format!("{}", e)
+        );
+        if let Err(e) = dispatch_condvar_result(
+            result_callback,
+            || {
+                Err(format_err!(
+                    "Failed to wait for context, context_hash: {}, reason: {}",
+                    apply_block_result.context_hash.to_base58_check(),
+                    e
+                ))
+            },
+            true,
+        ) {
+            warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+        }
+        return Err(FeedChainError::MissingContextError {
+            context_hash: apply_block_result.context_hash.to_base58_check(),
+        });
+    }
+    let context_wait_elapsed = context_wait_timer.elapsed();
+    if context_wait_elapsed.gt(&CONTEXT_WAIT_DURATION_LONG_TO_LOG) {
+        info!(log, "Block was applied with long context processing";
+                   "block_header_hash" => block_hash.to_base58_check(),
+                   "chain_id" => chain_id.to_base58_check(),
+                   "context_hash" => apply_block_result.context_hash.to_base58_check(),
+                   "context_wait_elapsed" => format!("{:?}", &context_wait_elapsed),
+                   "sender" => sender_to_string(&bootstrapper));
+    }
+
+    // Lets mark header as applied and store result
+    let store_result_timer = Instant::now();
+    match store_applied_block_result(
+        block_storage,
+        block_meta_storage,
+        &block_hash,
+        apply_block_result,
+        &mut block_meta,
+    ) {
+        Ok(_) => {
+            // now everythings stored, we are done
+            if let Err(e) = dispatch_condvar_result(result_callback, || Ok(()), true) {
+                warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+            }
+        }
+        Err(e) => {
+            if let Err(e) = dispatch_condvar_result(
+                result_callback,
+                || Err(format_err!("Failed to store applied result, reason: {}", e)),
+                true,
+            ) {
+                warn!(log, "Failed to dispatch result to condvar"; "reason" => format!("{}", e));
+            }
+            return Err(e.into());
+        }
+    };
+    let store_result_elapsed = store_result_timer.elapsed();
+
+    // notify others
+    if apply_block_run.load(Ordering::Acquire) {
+        // 1. ping chain_feeder for successors check -> to queue
+        let successors = block_meta.take_successors();
+        if !successors.is_empty() {
+            chain_feeder.tell(
+                CheckBlocksForApply::new(
+                    successors,
+                    chain_id.clone(),
+                    bootstrapper,
+                    Some(block_hash.clone()),
+                    Instant::now(),
+                ),
+                None,
+            );
+        } else {
+            // TODO: TE-369 - refactor pinging bootstrapper
+            chain_feeder_channel.tell(
+                Publish {
+                    msg: ChainFeederChannelMsg::BlockApplied(block_hash.clone()),
+                    topic: ChainFeederChannelTopic::BlockApplied.into(),
+                },
+                None,
+            );
+        }
+
+        // 2. ping chain current head manager
+        chain_current_head_manager.tell(
+            ProcessValidatedBlock::new(
+                block_hash,
+                chain_id,
+                roundtrip_timer,
+                Arc::new(BlockValidationTimer::new(
+                    validated_at_timer.elapsed(),
+                    load_metadata_elapsed,
+                    protocol_call_elapsed,
+                    context_wait_elapsed,
+                    store_result_elapsed,
+                )),
+            ),
+            None,
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::convert::{TryFrom, TryInto};
+
+    use riker::system::SystemBuilder;
+    use slog::Discard;
+
+    use crypto::hash::{BlockMetadataHash, ContextHash, OperationMetadataListListHash};
+    use storage::tests_common::TmpStorage;
+    use storage::{BlockAdditionalDataBuilder, BlockHeaderWithHash};
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use crate::chain_current_head_manager::ChainCurrentHeadManagerMsg;
+    use crate::chain_feeder::ChainFeederMsg;
+    use crate::chain_feeder_channel::ChainFeederChannel;
+
+    use super::*;
+
+    /// Stands for chain feeder, ignores all messages
+    #[derive(Default)]
+    pub(crate) struct NoopChainFeeder;
+
+    impl Actor for NoopChainFeeder {
+        type Msg = ChainFeederMsg;
+
+        fn recv(&mut self, _: &Context<Self::Msg>, _: Self::Msg, _: Sender) {}
+    }
+
+    /// Stands for chain current head manager, ignores all messages
+    #[derive(Default)]
+    pub(crate) struct NoopChainCurrentHeadManager;
+
+    impl Actor for NoopChainCurrentHeadManager {
+        type Msg = ChainCurrentHeadManagerMsg;
+
+        fn recv(&mut self, _: &Context<Self::Msg>, _: Self::Msg, _: Sender) {}
+    }
+
+    /// Stored chain: genesis (applied) - 1 - 2 - ... - `count` (not applied, but complete and with context)
+    pub(crate) struct TestChain {
+        pub(crate) chain_id: ChainId,
+        pub(crate) genesis: BlockHash,
+        pub(crate) blocks: Vec<BlockHash>,
+    }
+
+    pub(crate) fn store_test_chain(
+        storage: &PersistentStorage,
+        count: i32,
+    ) -> Result<TestChain, failure::Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let block_storage = BlockStorage::new(storage);
+        let block_meta_storage = BlockMetaStorage::new(storage);
+        let operations_meta_storage = OperationsMetaStorage::new(storage);
+        let chain_id: ChainId = vec![1, 2, 3, 4].try_into()?;
+
+        let genesis = test_block(0, &vec![0; 32].try_into()?)?;
+        block_storage.put_block_header(&genesis)?;
+        block_storage.assign_to_context(&genesis.hash, genesis.header.context())?;
+        block_storage.put_block_additional_data(
+            &genesis.hash,
+            BlockAdditionalDataBuilder::default()
+                .max_operations_ttl(0)
+                .last_allowed_fork_level(0)
+                .block_metadata_hash(None)
+                .ops_metadata_hash(None)
+                .ops_metadata_hashes(None)
+                .build()
+                .unwrap(),
+        )?;
+        block_meta_storage.put(
+            &genesis.hash,
+            &Meta::genesis_meta(&genesis.hash, &chain_id, true),
+        )?;
+
+        let mut blocks = Vec::new();
+        let mut predecessor = genesis.hash.clone();
+        for level in 1..=count {
+            let block = test_block(level, &predecessor)?;
+            block_storage.put_block_header(&block)?;
+            // context is committed by context listener, so successors can be applied
+            block_storage.assign_to_context(&block.hash, block.header.context())?;
+            block_meta_storage.put_block_header(&block, &chain_id, &log)?;
+            operations_meta_storage.put_block_header(&block, &chain_id)?;
+            predecessor = block.hash.clone();
+            blocks.push(block.hash);
+        }
+
+        Ok(TestChain {
+            chain_id,
+            genesis: genesis.hash,
+            blocks,
+        })
+    }
+
+    fn test_block(
+        level: i32,
+        predecessor: &BlockHash,
+    ) -> Result<BlockHeaderWithHash, failure::Error> {
+        Ok(BlockHeaderWithHash::new(
+            BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor.clone())
+                .timestamp(5_635_634 + level as i64)
+                .validation_pass(0)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc".try_into()?,
+                )
+                .fitness(vec![vec![0, level as u8]])
+                .context(ContextHash::try_from(vec![level as u8; 32])?)
+                .protocol_data(vec![0, 1, 2, 3, 4, 5, 6, 7, 8])
+                .build()
+                .unwrap(),
+        )?)
+    }
+
+    /// Deterministic result of the "protocol", which depends just on the request
+    pub(crate) fn test_apply(request: &ApplyBlockRequest) -> ApplyBlockResponse {
+        let level = request.block_header.level() as u8;
+        ApplyBlockResponse {
+            validation_result_message: format!("applied level {}", level),
+            context_hash: request.block_header.context().clone(),
+            block_header_proto_json: "{}".to_string(),
+            block_header_proto_metadata_json: "{}".to_string(),
+            operations_proto_metadata_json: "[]".to_string(),
+            max_operations_ttl: std::cmp::min(request.max_operations_ttl + 1, 60),
+            last_allowed_fork_level: 0,
+            forking_testchain: false,
+            forking_testchain_data: None,
+            block_metadata_hash: Some(BlockMetadataHash::try_from(vec![level; 32]).unwrap()),
+            ops_metadata_hashes: None,
+            ops_metadata_hash: Some(
+                OperationMetadataListListHash::try_from(vec![level; 32]).unwrap(),
+            ),
+        }
+    }
+
+    pub(crate) fn test_applied_block(
+        block_hash: &BlockHash,
+        chain_id: &ChainId,
+        chain_feeder: &ChainFeederRef,
+        block_meta: Meta,
+        apply_block_result: ApplyBlockResponse,
+    ) -> (AppliedBlock, CondvarResult<(), failure::Error>) {
+        let result_callback = Arc::new((Mutex::new(None), Condvar::new()));
+        let applied_block = AppliedBlock {
+            block_hash: Arc::new(block_hash.clone()),
+            chain_id: Arc::new(chain_id.clone()),
+            roundtrip_timer: Arc::new(Instant::now()),
+            bootstrapper: None,
+            result_callback: Some(result_callback.clone()),
+            chain_feeder: chain_feeder.clone(),
+            block_meta,
+            apply_block_result,
+            validated_at_timer: Instant::now(),
+            load_metadata_elapsed: Duration::from_secs(0),
+            protocol_call_elapsed: Duration::from_secs(0),
+        };
+        (applied_block, result_callback)
+    }
+
+    pub(crate) fn test_actor_system(name: &str) -> ActorSystem {
+        SystemBuilder::new()
+            .name(name)
+            .log(Logger::root(Discard, slog::o!()))
+            .create()
+            .expect("Failed to create actor system")
+    }
+
+    pub(crate) fn start_test_writer(
+        sys: &ActorSystem,
+        storage: &PersistentStorage,
+    ) -> AppliedBlockWriter {
+        AppliedBlockWriter::start(
+            4,
+            storage,
+            sys.actor_of::<NoopChainCurrentHeadManager>("chain-current-head-manager")
+                .expect("Failed to create chain current head manager"),
+            ChainFeederChannel::actor(sys).expect("Failed to create chain feeder channel"),
+            Arc::new(AtomicBool::new(true)),
+            Logger::root(Discard, slog::o!()),
+        )
+    }
+
+    fn take_result(
+        result_callback: &CondvarResult<(), failure::Error>,
+    ) -> Option<Result<(), failure::Error>> {
+        result_callback.0.lock().unwrap().take()
+    }
+
+    #[test]
+    fn test_block_prefetcher_prefetches_successors_up_to_look_ahead() -> Result<(), failure::Error>
+    {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_block_prefetcher")?;
+        let chain = store_test_chain(tmp_storage.storage(), 5)?;
+        let prefetcher =
+            BlockPrefetcher::start(2, tmp_storage.storage(), Logger::root(Discard, slog::o!()));
+
+        prefetcher.prefetch_successors(&chain.genesis, 0);
+        let started = Instant::now();
+        while prefetcher.prefetched.lock().unwrap().len() < 2 {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // just the next `look_ahead` blocks are prefetched
+        assert_eq!(2, prefetcher.prefetched.lock().unwrap().len());
+        assert!(prefetcher.take(&chain.blocks[2]).is_none());
+        let prefetched = prefetcher
+            .take(&chain.blocks[0])
+            .expect("block 1 prefetched");
+        assert_eq!(1, prefetched.block_header.level());
+        assert!(prefetched.operations.is_empty());
+
+        // e.g. block 1 failed to apply, so block 2 cannot be applied next
+        prefetcher.clear();
+        assert!(prefetcher.take(&chain.blocks[1]).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_applied_block_writer_discards_blocks_after_failure() -> Result<(), failure::Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_applied_block_writer_failure")?;
+        let storage = tmp_storage.storage();
+        let chain = store_test_chain(storage, 3)?;
+        let block_storage = BlockStorage::new(storage);
+        let block_meta_storage = BlockMetaStorage::new(storage);
+
+        let sys = test_actor_system("test_applied_block_writer_failure");
+        let chain_feeder: ChainFeederRef = sys
+            .actor_of::<NoopChainFeeder>("chain-feeder")
+            .expect("Failed to create chain feeder");
+        let writer = start_test_writer(&sys, storage);
+
+        let apply = |block_hash: &BlockHash, predecessor: &BlockHash| {
+            let block = block_storage.get(block_hash)?.expect("stored block");
+            let predecessor = block_storage.get(predecessor)?.expect("stored block");
+            let request = ApplyBlockRequest {
+                chain_id: chain.chain_id.clone(),
+                block_header: (&*block.header).clone(),
+                pred_header: (&*predecessor.header).clone(),
+                operations: vec![],
+                max_operations_ttl: 0,
+                predecessor_block_metadata_hash: None,
+                predecessor_ops_metadata_hash: None,
+            };
+            let meta = block_meta_storage.get(block_hash)?.expect("stored meta");
+            Ok::<_, failure::Error>(test_applied_block(
+                block_hash,
+                &chain.chain_id,
+                &chain_feeder,
+                meta,
+                test_apply(&request),
+            ))
+        };
+
+        // block, which is not stored, fails to be written (context is found, but header is missing)
+        let (mut unknown_block, unknown_callback) = apply(&chain.blocks[0], &chain.genesis)?;
+        unknown_block.block_hash = Arc::new(vec![9; 32].try_into()?);
+        writer.submit(unknown_block)?;
+
+        // following (pipelined) blocks are discarded
+        let (block1, block1_callback) = apply(&chain.blocks[0], &chain.genesis)?;
+        let (block2, block2_callback) = apply(&chain.blocks[1], &chain.blocks[0])?;
+        writer.submit(block1)?;
+        writer.submit(block2)?;
+        writer.wait_until_written();
+
+        assert!(matches!(take_result(&unknown_callback), Some(Err(_))));
+        assert!(matches!(take_result(&block1_callback), Some(Err(_))));
+        assert!(matches!(take_result(&block2_callback), Some(Err(_))));
+        assert!(!writer.is_in_flight(&chain.blocks[0]));
+        assert!(!block_meta_storage.is_applied(&chain.blocks[0])?);
+        assert!(!block_meta_storage.is_applied(&chain.blocks[1])?);
+        assert!(block_storage
+            .get_with_additional_data(&chain.blocks[0])?
+            .is_none());
+
+        // failure is taken just once, then blocks are written again
+        assert!(matches!(
+            writer.take_failure(),
+            Some(FeedChainError::StorageError { .. })
+        ));
+        assert!(writer.take_failure().is_none());
+
+        let (block1, block1_callback) = apply(&chain.blocks[0], &chain.genesis)?;
+        writer.submit(block1)?;
+        writer.wait_until_written();
+        assert!(matches!(take_result(&block1_callback), Some(Ok(()))));
+        assert!(block_meta_storage.is_applied(&chain.blocks[0])?);
+        assert!(!block_meta_storage.is_applied(&chain.blocks[1])?);
+        assert!(writer.take_failure().is_none());
+
+        Ok(())
+    }
+}
@@ -45,13 +45,15 @@ use crate::shell_channel::{
     ShellChannelMsg, ShellChannelRef, ShellChannelTopic,
 };
 use crate::state::block_state::{BlockAcceptanceResult, BlockchainState};
+use crate::state::clock_state::NodeClockRef;
 use crate::state::head_state::CurrentHeadRef;
 use crate::state::peer_state::{tell_peer, PeerState};
 use crate::state::synchronization_state::{
-    PeerBranchSynchronizationDone, SynchronizationBootstrapStateRef,
+    PeerBranchSynchronizationDone, SynchronizationBootstrapStateRef, UpdateIsBootstrapped,
 };
 use crate::state::StateError;
 use crate::stats::apply_block_stats::ApplyBlockStatsRef;
+use crate::stats::block_lifecycle::{BlockLifecycleStage, BlockLifecycleTracerRef};
 use crate::subscription::*;
 use crate::utils::{dispatch_condvar_result, CondvarResult};
 use crate::validation;
@@ -166,6 +168,8 @@ struct Stats {
 
     /// Shared statistics for applying blocks
     apply_block_stats: ApplyBlockStatsRef,
+    /// Tracer of the block lifecycle
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
 }
 
 /// Purpose of this actor is to perform chain synchronization.
@@ -244,6 +248,8 @@ impl ChainManager {
         current_mempool_state: CurrentMempoolStateStorageRef,
         current_bootstrap_state: SynchronizationBootstrapStateRef,
         apply_block_stats: ApplyBlockStatsRef,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        node_clock: NodeClockRef,
         p2p_disable_mempool: bool,
         identity: Arc<Identity>,
     ) -> Result<ChainManagerRef, CreateError> {
@@ -263,6 +269,8 @@ impl ChainManager {
                 current_mempool_state,
                 current_bootstrap_state,
                 apply_block_stats,
+                block_lifecycle_tracer,
+                node_clock,
                 p2p_disable_mempool,
                 identity.peer_id(),
             )),
@@ -344,6 +352,14 @@ impl ChainManager {
                                             message.current_branch().current_head().clone(),
                                         )?;
 
+                                        // update synchronization heuristic (just by accepted branch)
+                                        Self::update_bootstrap_state_by_peer_head(
+                                            &self.current_bootstrap_state,
+                                            peer,
+                                            &message_current_head.header,
+                                            &log,
+                                        )?;
+
                                         // update remote heads
                                         peer.update_current_head(&message_current_head);
                                         if let Err(e) =
@@ -407,6 +423,31 @@ impl ChainManager {
                                     };
 
                                     match was_queued {
+                                        true if chain_state.is_conflicting_with_checkpoint(
+                                            &block_header_with_hash,
+                                        ) =>
+                                        {
+                                            warn!(log, "Received block header conflicts with checkpoint - blacklisting peer";
+                                                       "block_header_hash" => block_header_with_hash.hash.to_base58_check(),
+                                                       "level" => block_header_with_hash.header.level());
+
+                                            // clear peer stuff immediatelly
+                                            peer.clear();
+
+                                            // blacklist peer
+                                            network_channel.tell(
+                                                Publish {
+                                                    msg: NetworkChannelMsg::BlacklistPeer(
+                                                        peer.peer_id.clone(),
+                                                        "block header conflicts with checkpoint"
+                                                            .to_string(),
+                                                    ),
+                                                    topic: NetworkChannelTopic::NetworkCommands
+                                                        .into(),
+                                                },
+                                                None,
+                                            );
+                                        }
                                         true => {
                                             // TODO: TE-369 - peers stats
                                             peer.block_response_last = Instant::now();
@@ -530,6 +571,11 @@ impl ChainManager {
                                                     // update stats
                                                     stats.unseen_block_operations_last =
                                                         Instant::now();
+                                                    stats.block_lifecycle_tracer.event(
+                                                        &block_hash,
+                                                        BlockLifecycleStage::OperationsReceived,
+                                                        Some(peer.peer_id.peer_address.to_string()),
+                                                    );
 
                                                     // TODO: TE-369 - is this necessery?
                                                     // notify others that new all operations for block were received
@@ -636,6 +682,14 @@ impl ChainManager {
                                         .map_err(StateError::from)?
                                         .is_bootstrapped()
                                     {
+                                        // head cannot be validated yet (we do not have its predecessor),
+                                        // so synchronization heuristic gets it as it is (heads from the future are ignored)
+                                        Self::update_bootstrap_state_by_peer_head(
+                                            &self.current_bootstrap_state,
+                                            peer,
+                                            message.current_block_header(),
+                                            &log,
+                                        )?;
                                         continue;
                                     }
 
@@ -643,13 +697,21 @@ impl ChainManager {
                                     match chain_state.can_accept_head(
                                         &message,
                                         &current_head.local,
-                                        &self.tezos_readonly_prevalidation_api.pool.get()?.api,
+                                        &self.tezos_readonly_prevalidation_api.pool().get()?.api,
                                     )? {
                                         BlockAcceptanceResult::AcceptBlock => {
                                             let message_current_head = BlockHeaderWithHash::new(
                                                 message.current_block_header().clone(),
                                             )?;
 
+                                            // update synchronization heuristic (just by validated head)
+                                            Self::update_bootstrap_state_by_peer_head(
+                                                &self.current_bootstrap_state,
+                                                peer,
+                                                &message_current_head.header,
+                                                &log,
+                                            )?;
+
                                             // update remote heads
                                             peer.update_current_head(&message_current_head);
                                             if let Err(e) = current_head
@@ -789,7 +851,7 @@ impl ChainManager {
                                                 &operation_hash,
                                                 &operation,
                                                 self.current_mempool_state.clone(),
-                                                &self.tezos_readonly_prevalidation_api.pool.get()?.api,
+                                                &self.tezos_readonly_prevalidation_api.pool().get()?.api,
                                                 block_storage,
                                                 block_meta_storage,
                                             ) {
@@ -953,8 +1015,12 @@ impl ChainManager {
                 });
             }
             ShellChannelMsg::PeerBranchSynchronizationDone(msg) => {
-                if let Err(e) = self.resolve_is_bootstrapped(&msg, &ctx.system.log()) {
-                    warn!(ctx.system.log(), "Failed to resolve is_bootstrapped for chain manager"; "msg" => format!("{:?}", msg), "reason" => format!("{:?}", e))
+                self.process_peer_branch_synchronization_done(&msg, &ctx.system.log());
+            }
+            ShellChannelMsg::P2pConfigurationReloaded(configuration) => {
+                if self.p2p_disable_mempool != configuration.disable_mempool {
+                    info!(ctx.system.log(), "Mempool propagation to p2p changed"; "disable_mempool" => configuration.disable_mempool);
+                    self.p2p_disable_mempool = configuration.disable_mempool;
                 }
             }
             ShellChannelMsg::ShuttingDown(_) => {
@@ -980,6 +1046,11 @@ impl ChainManager {
             // update stats for new header
             stats.unseen_block_last = Instant::now();
             stats.unseen_block_count += 1;
+            stats.block_lifecycle_tracer.event(
+                &received_block.hash,
+                BlockLifecycleStage::HeaderReceived,
+                Some(peer.peer_id.peer_address.to_string()),
+            );
 
             // notify others that new block was received
             shell_channel.tell(
@@ -1047,6 +1118,11 @@ impl ChainManager {
             // update stats
             self.stats.unseen_block_last = Instant::now();
             self.stats.unseen_block_count += 1;
+            self.stats.block_lifecycle_tracer.event(
+                &block_header_with_hash.hash,
+                BlockLifecycleStage::HeaderReceived,
+                Some("injected".to_string()),
+            );
 
             // notify others that new block (header) was received
             self.shell_channel.tell(
@@ -1142,6 +1218,11 @@ impl ChainManager {
                             if all_operations_received {
                                 // update stats
                                 self.stats.unseen_block_operations_last = Instant::now();
+                                self.stats.block_lifecycle_tracer.event(
+                                    &block_header_with_hash.hash,
+                                    BlockLifecycleStage::OperationsReceived,
+                                    Some("injected".to_string()),
+                                );
 
                                 // notify others that new all operations for block were received
                                 self.shell_channel.tell(
@@ -1239,66 +1320,113 @@ impl ChainManager {
         Ok(())
     }
 
-    /// Resolves if chain_manager is bootstrapped,
-    /// means that we have at_least <> boostrapped peers
-    ///
-    /// "bootstrapped peer" means, that peer.current_level <= chain_manager.current_level
-    fn resolve_is_bootstrapped(
+    /// Updates synchronization heuristic by the current head received from the peer
+    fn update_bootstrap_state_by_peer_head(
+        current_bootstrap_state: &SynchronizationBootstrapStateRef,
+        peer: &PeerState,
+        head: &BlockHeader,
+        log: &Logger,
+    ) -> Result<(), Error> {
+        let mut current_bootstrap_state =
+            current_bootstrap_state.write().map_err(StateError::from)?;
+        let was_bootstrapped = current_bootstrap_state.is_bootstrapped();
+
+        let is_bootstrapped = current_bootstrap_state.update_by_peer_head(
+            &peer.peer_id.peer_public_key_hash,
+            head.message_typed_hash()?,
+            head.timestamp(),
+        );
+
+        if !was_bootstrapped && is_bootstrapped {
+            info!(log, "Bootstrapped (chain_manager)";
+                   "num_of_peers_for_bootstrap_threshold" => current_bootstrap_state.num_of_peers_for_bootstrap_threshold(),
+                   "sync_state" => current_bootstrap_state.sync_state().as_str(),
+                   "peer_head_level" => head.level());
+        }
+
+        Ok(())
+    }
+
+    /// Peer's branch was downloaded and applied, so peer is marked as bootstrapped
+    fn process_peer_branch_synchronization_done(
         &mut self,
         msg: &PeerBranchSynchronizationDone,
         log: &Logger,
-    ) -> Result<(), StateError> {
-        if self.current_bootstrap_state.read()?.is_bootstrapped() {
-            // TODO: TE-386 - global queue for requested operations
-            if let Some(peer_state) = self.peers.get_mut(msg.peer().peer_ref.uri()) {
-                peer_state.missing_operations_for_blocks.clear();
+    ) {
+        if let Some(peer_state) = self.peers.get_mut(msg.peer().peer_ref.uri()) {
+            // branch was synchronized just to the checkpoint, so we continue with the rest of the peer's branch
+            if let Some(checkpoint) = self.chain_state.checkpoint() {
+                let is_above_checkpoint = peer_state
+                    .current_head_level
+                    .map_or(false, |level| level > *checkpoint.level());
+                if *msg.to_level().as_ref() == *checkpoint.level() && is_above_checkpoint {
+                    debug!(log, "Peer branch synchronization reached checkpoint, requesting rest of the branch";
+                                "checkpoint_level" => checkpoint.level(),
+                                "peer_id" => msg.peer().peer_id_marker.clone());
+                    tell_peer(
+                        GetCurrentBranchMessage::new(
+                            self.chain_state.get_chain_id().as_ref().clone(),
+                        )
+                        .into(),
+                        peer_state,
+                    );
+                    return;
+                }
             }
 
-            return Ok(());
+            peer_state.set_is_bootstrapped(true);
+
+            // TODO: TE-386 - global queue for requested operations
+            peer_state.missing_operations_for_blocks.clear();
+
+            debug!(log, "Peer branch synchronization done";
+                        "to_level" => msg.to_level().as_ref(),
+                        "peer_id" => msg.peer().peer_id_marker.clone());
         }
+    }
 
-        let chain_manager_current_level = self
+    /// Our local current head is also considered by synchronization heuristic
+    fn init_bootstrap_state_by_local_head(&self, log: &Logger) -> Result<(), Error> {
+        let local_head_timestamp = match self
             .current_head
             .local
-            .read()?
-            .as_ref()
-            .map(|head| *head.level())
-            .unwrap_or(0);
-
-        let remote_best_known_level = self
-            .current_head
-            .remote
-            .read()?
+            .read()
+            .map_err(StateError::from)?
             .as_ref()
-            .map(|head| *head.level())
-            .unwrap_or(0);
-
-        if let Some(peer_state) = self.peers.get_mut(msg.peer().peer_ref.uri()) {
-            self.current_bootstrap_state.write()?.update_by_peer_state(
-                msg,
-                peer_state,
-                remote_best_known_level,
-                chain_manager_current_level,
-            );
+        {
+            Some(head) => self
+                .block_storage
+                .get(head.block_hash())?
+                .map(|block| block.header.timestamp()),
+            None => None,
+        };
 
-            // TODO: TE-386 - global queue for requested operations
-            peer_state.missing_operations_for_blocks.clear();
+        let mut current_bootstrap_state = self
+            .current_bootstrap_state
+            .write()
+            .map_err(StateError::from)?;
+        if let Some(timestamp) = local_head_timestamp {
+            let _ = current_bootstrap_state.update_by_new_local_head(timestamp);
         }
 
-        {
-            // lock and log
-            let current_bootstrap_state = self.current_bootstrap_state.read()?;
-            if current_bootstrap_state.is_bootstrapped() {
-                info!(log, "Bootstrapped (chain_manager)";
-                       "num_of_peers_for_bootstrap_threshold" => current_bootstrap_state.num_of_peers_for_bootstrap_threshold(),
-                       "remote_best_known_level" => remote_best_known_level,
-                       "reached_on_level" => chain_manager_current_level);
-            }
+        if current_bootstrap_state.is_bootstrapped() {
+            info!(log, "Bootstrapped on startup (chain_manager)";
+                   "num_of_peers_for_bootstrap_threshold" => current_bootstrap_state.num_of_peers_for_bootstrap_threshold(),
+                   "sync_state" => current_bootstrap_state.sync_state().as_str());
         }
 
         Ok(())
     }
 
+    /// Forgets disconnected peer
+    fn remove_peer(&mut self, peer_uri: &ActorUri) {
+        if let Some(peer_state) = self.peers.remove(peer_uri) {
+            if let Ok(mut current_bootstrap_state) = self.current_bootstrap_state.write() {
+                current_bootstrap_state.remove_peer(&peer_state.peer_id.peer_public_key_hash);
+            }
+        }
+    }
+
     /// Send CurrentBranch message to the p2p
     fn advertise_current_branch_to_p2p(
         &self,
@@ -1442,6 +1570,8 @@ impl
         CurrentMempoolStateStorageRef,
         SynchronizationBootstrapStateRef,
         ApplyBlockStatsRef,
+        BlockLifecycleTracerRef,
+        NodeClockRef,
         bool,
         CryptoboxPublicKeyHash,
     )> for ChainManager
@@ -1461,6 +1591,8 @@ impl
             current_mempool_state,
             current_bootstrap_state,
             apply_block_stats,
+            block_lifecycle_tracer,
+            node_clock,
             p2p_disable_mempool,
             identity_peer_id,
         ): (
@@ -1477,6 +1609,8 @@ impl
             CurrentMempoolStateStorageRef,
             SynchronizationBootstrapStateRef,
             ApplyBlockStatsRef,
+            BlockLifecycleTracerRef,
+            NodeClockRef,
             bool,
             CryptoboxPublicKeyHash,
         ),
@@ -1494,8 +1628,11 @@ impl
                 block_applier,
                 shell_channel,
                 chain_feeder_channel,
+                block_lifecycle_tracer.clone(),
+                node_clock,
                 Arc::new(init_storage_data.chain_id),
                 Arc::new(init_storage_data.genesis_block_header_hash),
+                init_storage_data.checkpoint,
             ),
             peers: HashMap::new(),
             current_head: CurrentHead {
@@ -1508,6 +1645,7 @@ impl
                 unseen_block_last: Instant::now(),
                 unseen_block_operations_last: Instant::now(),
                 apply_block_stats,
+                block_lifecycle_tracer,
             },
             is_sandbox,
             identity_peer_id,
@@ -1528,6 +1666,7 @@ impl Actor for ChainManager {
         subscribe_to_network_events(&self.network_channel, ctx.myself());
         subscribe_to_shell_shutdown(&self.shell_channel, ctx.myself());
         subscribe_to_shell_commands(&self.shell_channel, ctx.myself());
+        subscribe_to_shell_configuration(&self.shell_channel, ctx.myself());
 
         ctx.schedule::<Self::Msg, _>(
             ASK_CURRENT_HEAD_INITIAL_DELAY,
@@ -1562,16 +1701,8 @@ impl Actor for ChainManager {
     }
 
     fn post_start(&mut self, ctx: &Context<Self::Msg>) {
-        match self.current_bootstrap_state.read() {
-            Ok(current_bootstrap_state) => {
-                if current_bootstrap_state.is_bootstrapped() {
-                    info!(ctx.system.log(), "Bootstrapped on startup (chain_manager)";
-                       "num_of_peers_for_bootstrap_threshold" => current_bootstrap_state.num_of_peers_for_bootstrap_threshold());
-                }
-            }
-            Err(e) => {
-                warn!(ctx.system.log(), "Failed to read current_bootstrap_state on startup"; "reason" => format!("{}", e))
-            }
+        if let Err(e) = self.init_bootstrap_state_by_local_head(&ctx.system.log()) {
+            warn!(ctx.system.log(), "Failed to init current_bootstrap_state on startup"; "reason" => format!("{}", e))
         }
     }
 
@@ -1601,7 +1732,7 @@ impl Receive<SystemEvent> for ChainManager {
         _sender: Option<BasicActorRef>,
     ) {
         if let SystemEvent::ActorTerminated(evt) = msg {
-            self.peers.remove(evt.actor.uri());
+            self.remove_peer(evt.actor.uri());
         }
     }
 }
@@ -1615,7 +1746,7 @@ impl Receive<DeadLetter> for ChainManager {
         msg: DeadLetter,
         _sender: Option<BasicActorRef>,
     ) {
-        self.peers.remove(msg.recipient.uri());
+        self.remove_peer(msg.recipient.uri());
     }
 }
 
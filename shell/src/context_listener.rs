@@ -8,7 +8,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use failure::Error;
 use riker::actors::*;
@@ -22,6 +22,7 @@ use tezos_context::channel::{ContextAction, ContextActionMessage};
 use tezos_wrapper::service::IpcEvtServer;
 
 use crate::shell_channel::{ShellChannelMsg, ShellChannelRef};
+use crate::stats::block_lifecycle::{BlockLifecycleStage, BlockLifecycleTracerRef};
 use crate::subscription::subscribe_to_shell_shutdown;
 
 type SharedJoinHandle = Arc<Mutex<Option<JoinHandle<Result<(), Error>>>>>;
@@ -55,6 +56,7 @@ impl ContextListener {
         persistent_storage: &PersistentStorage,
         action_store_backend: Vec<Box<dyn ActionRecorder + Send>>,
         mut event_server: IpcEvtServer,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
         log: Logger,
     ) -> Result<ContextListenerRef, CreateError> {
         let listener_run = Arc::new(AtomicBool::new(true));
@@ -77,6 +79,7 @@ impl ContextListener {
                         Self::IPC_ACCEPT_TIMEOUT,
                         &mut action_store_backend,
                         &mut context,
+                        &block_lifecycle_tracer,
                         &log,
                     ) {
                         Ok(()) => info!(log, "Context listener finished"),
@@ -169,6 +172,7 @@ fn listen_protocol_events(
     event_server_accept_timeout: Duration,
     action_store_backend: &mut Vec<Box<dyn ActionRecorder + Send>>,
     context: &mut Box<dyn ContextApi>,
+    block_lifecycle_tracer: &BlockLifecycleTracerRef,
     log: &Logger,
 ) -> Result<(), Error> {
     info!(
@@ -224,7 +228,7 @@ fn listen_protocol_events(
                 }
 
                 if msg.perform {
-                    perform_context_action(&msg.action, context)?;
+                    perform_context_action(&msg.action, context, block_lifecycle_tracer)?;
                 }
             }
             Err(err) => {
@@ -249,6 +253,7 @@ where
 fn perform_context_action(
     action: &ContextAction,
     context: &mut Box<dyn ContextApi>,
+    block_lifecycle_tracer: &BlockLifecycleTracerRef,
 ) -> Result<(), Error> {
     match action {
         ContextAction::Get { key, .. } => {
@@ -302,6 +307,7 @@ fn perform_context_action(
             let parent_context_hash = try_from_untyped_option(parent_context_hash)?;
             let block_hash = BlockHash::try_from(block_hash.clone())?;
             let new_context_hash = ContextHash::try_from(new_context_hash.clone())?;
+            let commit_timer = Instant::now();
             let hash = context.commit(
                 &block_hash,
                 &parent_context_hash,
@@ -309,6 +315,12 @@ fn perform_context_action(
                 message.to_string(),
                 *date,
             )?;
+            block_lifecycle_tracer.record_elapsed(
+                &block_hash,
+                BlockLifecycleStage::ContextCommit,
+                commit_timer.elapsed(),
+                Some(hash.to_base58_check()),
+            );
             assert_eq!(
                 &hash,
                 &new_context_hash,
@@ -9,11 +9,13 @@ use failure::Fail;
 pub mod chain_current_head_manager;
 pub mod chain_feeder;
 pub mod chain_feeder_channel;
+pub mod chain_feeder_pipeline;
 pub mod chain_manager;
 pub mod context_listener;
 pub mod mempool;
 pub mod peer_branch_bootstrapper;
 pub mod peer_manager;
+pub mod reload;
 pub mod shell_channel;
 pub mod state;
 pub mod stats;
@@ -31,7 +33,7 @@ pub const SUPPORTED_DISTRIBUTED_DB_VERSION: &[u16] = &[0];
 pub struct InvalidRangeError(String);
 
 /// Simple threshold, for representing integral ranges.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PeerConnectionThreshold {
     low: usize,
     high: usize,
@@ -65,7 +67,7 @@ impl PeerConnectionThreshold {
         })
     }
 
-    /// Threshold for minimal count of bootstrapped peers
+    /// Threshold for minimal count of peers with recent head, used by synchronization heuristic
     /// Ocaml counts it from (expected)connections: see [node_shared_arg.ml]
     pub fn num_of_peers_for_bootstrap_threshold(&self) -> usize {
         if let Some(sync_tresh) = self.peers_for_bootstrap_threshold {
@@ -74,7 +76,6 @@ impl PeerConnectionThreshold {
             sync_tresh
         } else {
             // calculate othervise
-            // TODO TE-244 - Implement the synchronization heuristic
             // NOTE: the calculation should never yield 0!
 
             // since we define the low and high bound, calculate the expected connections
@@ -190,6 +191,23 @@ pub mod subscription {
         );
     }
 
+    #[inline]
+    pub(crate) fn subscribe_to_shell_configuration<M, E>(
+        shell_channel: &ChannelRef<E>,
+        myself: ActorRef<M>,
+    ) where
+        M: Message,
+        E: Message + Into<M>,
+    {
+        shell_channel.tell(
+            Subscribe {
+                actor: Box::new(myself),
+                topic: ShellChannelTopic::ShellConfiguration.into(),
+            },
+            None,
+        );
+    }
+
     #[inline]
     pub fn subscribe_to_shell_shutdown<M, E>(shell_channel: &ChannelRef<E>, myself: ActorRef<M>)
     where
@@ -93,7 +93,7 @@ impl MempoolPrevalidator {
                 let mempool_storage = MempoolStorage::new(&persistent_storage);
 
                 while validator_run.load(Ordering::Acquire) {
-                    match tezos_readonly_api.pool.get() {
+                    match tezos_readonly_api.pool().get() {
                         Ok(mut protocol_controller) => match process_prevalidation(
                             &block_storage,
                             &chain_meta_storage,
@@ -221,6 +221,10 @@ impl Actor for MempoolPrevalidator {
 
     fn post_stop(&mut self) {
         self.validator_run.store(false, Ordering::Release);
+        // wake up validator thread waiting for event (actor can be stopped also without shutdown, e.g. mempool disabled at runtime)
+        if let Ok(validator_event_sender) = self.validator_event_sender.lock() {
+            let _ = validator_event_sender.send(Event::ShuttingDown);
+        }
 
         let join_handle = self
             .validator_thread
@@ -209,6 +209,10 @@ impl MempoolState {
     pub fn operations(&self) -> &HashMap<OperationHash, Operation> {
         &self.operations
     }
+
+    pub fn pending(&self) -> &HashSet<OperationHash> {
+        &self.pending
+    }
 }
 
 pub(crate) fn collect_mempool(applied: &Vec<Applied>, pending: &HashSet<OperationHash>) -> Mempool {
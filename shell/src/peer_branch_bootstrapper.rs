@@ -23,12 +23,16 @@ use crate::chain_feeder_channel::{
 };
 use crate::shell_channel::{ShellChannelMsg, ShellChannelRef, ShellChannelTopic};
 use crate::state::bootstrap_state::{BootstrapState, InnerBlockState};
+use crate::state::download_scheduler::{DownloadKind, DownloadSchedulerRef, Schedule};
 use crate::state::synchronization_state::PeerBranchSynchronizationDone;
 use crate::state::{MissingOperations, StateError};
+use crate::stats::block_lifecycle::{BlockLifecycleStage, BlockLifecycleTracerRef};
 use crate::subscription::subscribe_to_actor_terminated;
 
 const MAX_BOOTSTRAP_BRANCHES_PER_PEER: usize = 2;
 const MAX_QUEUED_ITEMS: usize = 10;
+/// How many intervals of the branch can be downloaded in parallel (intervals are distributed among peers by [DownloadScheduler](crate::state::download_scheduler::DownloadScheduler))
+const MAX_DOWNLOAD_AHEAD_INTERVALS: usize = 4;
 const MAX_TRIES_FOR_LOOPING_EXISTING_DATA_IN_ONE_RUN: usize = 5000;
 const SCHEDULE_ONE_TIMER_NO_DATA_DELAY: Duration = Duration::from_secs(5);
 const SCHEDULE_ONE_TIMER_DATA_DELAY: Duration = Duration::from_millis(10);
@@ -138,6 +142,7 @@ pub struct PeerBranchBootstrapper {
     operations_meta_storage: OperationsMetaStorage,
 
     block_applier: ChainFeederRef,
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
 
     bootstrap_state: Vec<BootstrapState>,
     queued_block_headers: Arc<Mutex<HashSet<Arc<BlockHash>>>>,
@@ -145,7 +150,8 @@ pub struct PeerBranchBootstrapper {
     queued_block_headers_for_apply: HashMap<Arc<BlockHash>, Instant>,
 
     empty_bootstrap_state: Option<Instant>,
-    // TODO: TE-369 - rate limiter
+    /// Chain-wide scheduler, which distributes requests among peers and limits requests in flight per peer
+    download_scheduler: DownloadSchedulerRef,
     // Indicates that we triggered check_chain_completeness
     // (means, waiting in actor's mailbox)
     // this is optimization
@@ -166,6 +172,8 @@ impl PeerBranchBootstrapper {
         block_applier: ChainFeederRef,
         block_meta_storage: BlockMetaStorage,
         operations_meta_storage: OperationsMetaStorage,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        download_scheduler: DownloadSchedulerRef,
     ) -> Result<PeerBranchBootstrapperRef, CreateError> {
         sys.actor_of_props::<PeerBranchBootstrapper>(
             &format!("{}-branch-bootstrap", &peer.peer_ref.name()),
@@ -178,6 +186,8 @@ impl PeerBranchBootstrapper {
                 block_applier,
                 block_meta_storage,
                 operations_meta_storage,
+                block_lifecycle_tracer,
+                download_scheduler,
             )),
         )
     }
@@ -195,29 +205,39 @@ impl PeerBranchBootstrapper {
             operations_meta_storage,
             queued_block_headers,
             queued_block_operations,
+            block_lifecycle_tracer,
+            download_scheduler,
             ..
         } = self;
 
         let mut was_scheduled = false;
         bootstrap_state.iter_mut().for_each(|bootstrap| {
-            // schedule next block downloading
-            was_scheduled |= schedule_block_downloading(
-                peer,
-                bootstrap,
-                queued_block_headers,
-                block_meta_storage,
-                operations_meta_storage,
-                log,
-            );
+            // intervals are downloaded in parallel, scheduler decides, which of them are downloaded from this peer
+            for interval in bootstrap.intervals_to_download(MAX_DOWNLOAD_AHEAD_INTERVALS) {
+                // schedule next block downloading
+                was_scheduled |= schedule_block_downloading(
+                    peer,
+                    bootstrap,
+                    &interval,
+                    queued_block_headers,
+                    block_meta_storage,
+                    operations_meta_storage,
+                    download_scheduler,
+                    log,
+                );
 
-            // schedule next block downloading
-            was_scheduled |= schedule_operations_downloading(
-                peer,
-                bootstrap,
-                queued_block_operations,
-                operations_meta_storage,
-                log,
-            );
+                // schedule next block downloading
+                was_scheduled |= schedule_operations_downloading(
+                    peer,
+                    bootstrap,
+                    &interval,
+                    queued_block_operations,
+                    operations_meta_storage,
+                    block_lifecycle_tracer,
+                    download_scheduler,
+                    log,
+                );
+            }
         });
 
         // if scheduled, lets ping quick, if no data, then wait
@@ -313,6 +333,8 @@ impl
         ChainFeederRef,
         BlockMetaStorage,
         OperationsMetaStorage,
+        BlockLifecycleTracerRef,
+        DownloadSchedulerRef,
     )> for PeerBranchBootstrapper
 {
     fn create_args(
@@ -325,6 +347,8 @@ impl
             block_applier,
             block_meta_storage,
             operations_meta_storage,
+            block_lifecycle_tracer,
+            download_scheduler,
         ): (
             Arc<PeerId>,
             Arc<Mutex<HashSet<Arc<BlockHash>>>>,
@@ -334,6 +358,8 @@ impl
             ChainFeederRef,
             BlockMetaStorage,
             OperationsMetaStorage,
+            BlockLifecycleTracerRef,
+            DownloadSchedulerRef,
         ),
     ) -> Self {
         PeerBranchBootstrapper {
@@ -347,7 +373,9 @@ impl
             block_applier,
             block_meta_storage,
             operations_meta_storage,
+            block_lifecycle_tracer,
             empty_bootstrap_state: None,
+            download_scheduler,
         }
     }
 }
@@ -379,6 +407,13 @@ impl Actor for PeerBranchBootstrapper {
         );
     }
 
+    fn post_stop(&mut self) {
+        // requests of this peer can be scheduled to other peers
+        if let Ok(mut download_scheduler) = self.download_scheduler.lock() {
+            download_scheduler.peer_disconnected(&self.peer.peer_address);
+        }
+    }
+
     fn recv(&mut self, ctx: &Context<Self::Msg>, msg: Self::Msg, sender: Sender) {
         self.receive(ctx, msg, sender);
     }
@@ -512,12 +547,22 @@ impl Receive<UpdateBlockState> for PeerBranchBootstrapper {
         _: Option<BasicActorRef>,
     ) {
         let PeerBranchBootstrapper {
+            peer,
             bootstrap_state,
             block_meta_storage,
             operations_meta_storage,
+            download_scheduler,
             ..
         } = self;
 
+        if let Ok(mut download_scheduler) = download_scheduler.lock() {
+            download_scheduler.received(
+                DownloadKind::BlockHeader,
+                peer.peer_address,
+                &msg.block_hash,
+            );
+        }
+
         bootstrap_state
             .iter_mut()
             .for_each(|bootstrap| {
@@ -547,11 +592,21 @@ impl Receive<UpdateOperationsState> for PeerBranchBootstrapper {
         _: Option<BasicActorRef>,
     ) {
         let PeerBranchBootstrapper {
+            peer,
             bootstrap_state,
             queued_block_operations,
+            download_scheduler,
             ..
         } = self;
 
+        if let Ok(mut download_scheduler) = download_scheduler.lock() {
+            download_scheduler.received(
+                DownloadKind::Operations,
+                peer.peer_address,
+                &msg.block_hash,
+            );
+        }
+
         // check pipelines
         bootstrap_state.iter_mut().for_each(|bootstrap| {
             bootstrap.block_operations_downloaded(&msg.block_hash);
@@ -675,16 +730,18 @@ fn update_block_state(
 fn schedule_block_downloading(
     peer: &mut Arc<PeerId>,
     bootstrap: &mut BootstrapState,
+    interval: &Arc<BlockHash>,
     queued_block_headers: &mut Arc<Mutex<HashSet<Arc<BlockHash>>>>,
     block_meta_storage: &mut BlockMetaStorage,
     operations_meta_storage: &mut OperationsMetaStorage,
+    download_scheduler: &DownloadSchedulerRef,
     log: &Logger,
 ) -> bool {
     // get first non downloaded header (check also storage)
     let mut max_tries = 0;
 
     let next_block_to_download = loop {
-        let block = bootstrap.next_block_to_download();
+        let block = bootstrap.next_block_to_download_in_interval(interval);
         max_tries += 1;
         // this means we are looping through existing data, which somebody downloaded before, so we wait for maybe applied
         if max_tries > MAX_TRIES_FOR_LOOPING_EXISTING_DATA_IN_ONE_RUN {
@@ -741,7 +798,16 @@ fn schedule_block_downloading(
                     }
                 }
             }
-            None => break None,
+            None => {
+                // all headers of the interval are downloaded
+                release_downloaded_interval(
+                    download_scheduler,
+                    DownloadKind::BlockHeader,
+                    interval,
+                    log,
+                );
+                break None;
+            }
         }
     };
 
@@ -749,7 +815,17 @@ fn schedule_block_downloading(
         let can_be_scheduled = {
             match queued_block_headers.lock() {
                 Ok(mut queued_block_headers) => {
-                    if queued_block_headers.len() < MAX_QUEUED_ITEMS {
+                    if queued_block_headers.len() < MAX_QUEUED_ITEMS
+                        && !queued_block_headers.contains(&block)
+                        && schedule_download(
+                            download_scheduler,
+                            DownloadKind::BlockHeader,
+                            peer,
+                            interval,
+                            &block,
+                            log,
+                        )
+                    {
                         queued_block_headers.insert(block.clone())
                     } else {
                         false
@@ -774,8 +850,11 @@ fn schedule_block_downloading(
 fn schedule_operations_downloading(
     peer: &mut Arc<PeerId>,
     bootstrap: &mut BootstrapState,
+    interval: &Arc<BlockHash>,
     queued_block_operations: &mut Arc<Mutex<HashMap<BlockHash, MissingOperations>>>,
     operations_meta_storage: &mut OperationsMetaStorage,
+    block_lifecycle_tracer: &BlockLifecycleTracerRef,
+    download_scheduler: &DownloadSchedulerRef,
     log: &Logger,
 ) -> bool {
     let mut max_tries = 0;
@@ -787,7 +866,7 @@ fn schedule_operations_downloading(
             break None;
         }
 
-        let block = bootstrap.next_block_operations_to_download();
+        let block = bootstrap.next_block_operations_to_download_in_interval(interval);
 
         match block {
             Some(block) => {
@@ -818,7 +897,16 @@ fn schedule_operations_downloading(
                     }
                 }
             }
-            None => break None,
+            None => {
+                // operations of all blocks of the interval are downloaded
+                release_downloaded_interval(
+                    download_scheduler,
+                    DownloadKind::Operations,
+                    interval,
+                    log,
+                );
+                break None;
+            }
         }
     };
 
@@ -828,7 +916,16 @@ fn schedule_operations_downloading(
                 Ok(mut queued_block_operations) => {
                     if queued_block_operations.len() < MAX_QUEUED_ITEMS {
                         // we dont want to reschedule the same
-                        if !queued_block_operations.contains_key(block.as_ref()) {
+                        if !queued_block_operations.contains_key(block.as_ref())
+                            && schedule_download(
+                                download_scheduler,
+                                DownloadKind::Operations,
+                                peer,
+                                interval,
+                                &block,
+                                log,
+                            )
+                        {
                             queued_block_operations
                                 .insert(
                                     block.as_ref().clone(),
@@ -854,6 +951,14 @@ fn schedule_operations_downloading(
             }
         };
         if can_be_scheduled {
+            block_lifecycle_tracer.event(
+                &block,
+                BlockLifecycleStage::OperationsRequested,
+                Some(format!(
+                    "{}, validation_passes: {:?}",
+                    peer.peer_address, missing_validation_passes
+                )),
+            );
             tell_peer(
                 GetOperationsForBlocksMessage::new(
                     missing_validation_passes
@@ -872,6 +977,52 @@ fn schedule_operations_downloading(
     false
 }
 
+/// Asks chain-wide scheduler, if the block can be requested from the peer now
+fn schedule_download(
+    download_scheduler: &DownloadSchedulerRef,
+    kind: DownloadKind,
+    peer: &PeerId,
+    interval: &Arc<BlockHash>,
+    block: &BlockHash,
+    log: &Logger,
+) -> bool {
+    let schedule = match download_scheduler.lock() {
+        Ok(mut download_scheduler) => {
+            download_scheduler.schedule(kind, peer.peer_address, interval, block)
+        }
+        Err(e) => {
+            error!(log, "Failed to lock download scheduler"; "reason" => format!("{}", e));
+            return false;
+        }
+    };
+
+    if let Schedule::Reassigned { stalled_peer } = &schedule {
+        debug!(log, "Re-assigned stalled download request";
+            "kind" => format!("{:?}", kind),
+            "block" => block.to_base58_check(),
+            "stalled_peer_ip" => stalled_peer.to_string(),
+            "peer_id" => peer.peer_id_marker.clone(), "peer_ip" => peer.peer_address.to_string(), "peer" => peer.peer_ref.name(), "peer_uri" => peer.peer_ref.uri().to_string(),
+        );
+    }
+
+    schedule.is_scheduled()
+}
+
+/// Notifies chain-wide scheduler, that the interval is downloaded, so it is not claimed by the peer anymore
+fn release_downloaded_interval(
+    download_scheduler: &DownloadSchedulerRef,
+    kind: DownloadKind,
+    interval: &Arc<BlockHash>,
+    log: &Logger,
+) {
+    match download_scheduler.lock() {
+        Ok(mut download_scheduler) => download_scheduler.interval_downloaded(kind, interval),
+        Err(e) => {
+            error!(log, "Failed to lock download scheduler"; "reason" => format!("{}", e));
+        }
+    }
+}
+
 fn schedule_block_applying(
     bootstrap: &mut BootstrapState,
     queued_block_headers_for_apply: &mut HashMap<Arc<BlockHash>, Instant>,
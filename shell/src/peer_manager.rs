@@ -31,7 +31,7 @@ use networking::{LocalPeerInfo, PeerId, ShellCompatibilityVersion};
 use tezos_identity::Identity;
 use tezos_messages::p2p::encoding::prelude::*;
 
-use crate::shell_channel::{ShellChannelMsg, ShellChannelRef};
+use crate::shell_channel::{P2pConfigurationReloaded, ShellChannelMsg, ShellChannelRef};
 use crate::subscription::*;
 use crate::PeerConnectionThreshold;
 
@@ -74,6 +74,8 @@ pub struct P2p {
     pub private_node: bool,
 
     pub peer_threshold: PeerConnectionThreshold,
+    /// Peer's head older than latency is not considered as recent by synchronization heuristic
+    pub synchronization_latency: Duration,
 
     /// Bootstrap lookup addresses disable/enable
     pub disable_bootstrap_lookup: bool,
@@ -119,6 +121,8 @@ pub struct PeerManager {
     potential_peers: HashSet<SocketAddr>,
     /// Bootstrap peer, which we try to connect all the the, if no other peers presents
     bootstrap_addresses: HashSet<(String, u16)>,
+    /// Configured bootstrap peers (part of bootstrap_addresses), can be changed by configuration reload
+    bootstrap_peers: Vec<SocketAddr>,
 
     /// Indicates that mempool should be disabled
     disable_mempool: bool,
@@ -136,6 +140,9 @@ pub struct PeerManager {
     rx_run: Arc<AtomicBool>,
     /// set of blacklisted IP addresses
     ip_blacklist: HashSet<IpAddr>,
+    /// set of blacklisted addresses (IP and port), private node blacklists just addresses,
+    /// because all peers can share the same IP (e.g. sandbox topology behind 127.0.0.1 proxies)
+    address_blacklist: HashSet<SocketAddr>,
     /// Last time we did DNS peer discovery
     discovery_last: Option<Instant>,
     /// Last time we checked peer count
@@ -219,7 +226,7 @@ impl PeerManager {
 
     fn calculate_count_of_required_peers(&mut self) -> usize {
         cmp::max(
-            (self.threshold.high + 3 * self.threshold.low) / 4 - self.peers.len(),
+            ((self.threshold.high + 3 * self.threshold.low) / 4).saturating_sub(self.peers.len()),
             self.threshold.low,
         )
     }
@@ -234,12 +241,21 @@ impl PeerManager {
         Peer::actor(sys, network_channel, tokio_executor, info)
     }
 
-    /// Check if given ip address is blacklisted to connect to
-    fn is_blacklisted(&self, ip_address: &IpAddr) -> bool {
-        self.ip_blacklist.contains(ip_address)
+    /// Check if given address (or its ip address) is blacklisted to connect to
+    fn is_blacklisted(&self, address: &SocketAddr) -> bool {
+        self.ip_blacklist.contains(&address.ip()) || self.address_blacklist.contains(address)
     }
 
     fn blacklist_address(&mut self, address: SocketAddr, reason: String, log: &Logger) {
+        if self.private_node {
+            info!(log, "Blacklisting address";
+                       "address" => format!("{}", address),
+                       "reason" => reason,
+            );
+            self.address_blacklist.insert(address);
+            return;
+        }
+
         info!(log, "Blacklisting IP";
                    "ip" => format!("{}", address.ip()),
                    "reason" => reason,
@@ -291,7 +307,7 @@ impl PeerManager {
     ) {
         let sock_addresses = potential_peers
             .into_iter()
-            .filter(|address: &SocketAddr| !self.is_blacklisted(&address.ip()))
+            .filter(|address: &SocketAddr| !self.is_blacklisted(address))
             .collect::<Vec<_>>();
 
         // we want to make sure, that we dont want to have unlimited potential peers (num_of_required_peers * 10)
@@ -342,6 +358,43 @@ impl PeerManager {
 
         self.check_peer_count_last = Some(Instant::now());
     }
+
+    /// Applies p2p configuration changed at runtime, new values are used with next peer count check or new connections
+    fn reload_configuration(
+        &mut self,
+        ctx: &Context<PeerManagerMsg>,
+        configuration: P2pConfigurationReloaded,
+    ) {
+        let P2pConfigurationReloaded {
+            peer_threshold,
+            bootstrap_peers,
+            disable_mempool,
+        } = configuration;
+
+        info!(ctx.system.log(), "Reloading p2p configuration";
+                                "peer_threshold" => format!("{:?}", &peer_threshold),
+                                "bootstrap_peers" => format!("{:?}", &bootstrap_peers),
+                                "disable_mempool" => disable_mempool);
+
+        // replace previous bootstrap peers
+        for addr in &self.bootstrap_peers {
+            self.bootstrap_addresses
+                .remove(&(addr.ip().to_string(), addr.port()));
+        }
+        self.bootstrap_addresses.extend(
+            bootstrap_peers
+                .iter()
+                .map(|addr| (addr.ip().to_string(), addr.port())),
+        );
+        self.bootstrap_peers = bootstrap_peers.clone();
+
+        self.threshold = peer_threshold;
+        // applies just to the new connections (already connected peers keep negotiated metadata)
+        self.disable_mempool = disable_mempool;
+
+        self.process_new_potential_peers(bootstrap_peers);
+        self.trigger_check_peer_count(ctx);
+    }
 }
 
 impl
@@ -390,6 +443,7 @@ impl
             shell_channel,
             tokio_executor,
             bootstrap_addresses,
+            bootstrap_peers: p2p_config.bootstrap_peers,
             threshold: p2p_config.peer_threshold,
             local_node_info: Arc::new(LocalPeerInfo::new(
                 p2p_config.listener_port,
@@ -402,6 +456,7 @@ impl
             potential_peers: HashSet::new(),
             peers: HashMap::new(),
             ip_blacklist: HashSet::new(),
+            address_blacklist: HashSet::new(),
             discovery_last: None,
             check_peer_count_last: None,
             shutting_down: false,
@@ -415,6 +470,7 @@ impl Actor for PeerManager {
     fn pre_start(&mut self, ctx: &Context<Self::Msg>) {
         subscribe_to_actor_terminated(ctx.system.sys_events(), ctx.myself());
         subscribe_to_shell_shutdown(&self.shell_channel, ctx.myself());
+        subscribe_to_shell_configuration(&self.shell_channel, ctx.myself());
         subscribe_to_dead_letters(ctx.system.dead_letters(), ctx.myself());
         subscribe_to_network_commands(&self.network_channel, ctx.myself());
 
@@ -491,10 +547,18 @@ impl Receive<ShellChannelMsg> for PeerManager {
     type Msg = PeerManagerMsg;
 
     fn receive(&mut self, ctx: &Context<Self::Msg>, msg: ShellChannelMsg, _sender: Sender) {
-        if let ShellChannelMsg::ShuttingDown(_) = msg {
-            unsubscribe_from_dead_letters(ctx.system.dead_letters(), ctx.myself());
-            self.shutting_down = true;
-            self.rx_run.store(false, Ordering::Release);
+        match msg {
+            ShellChannelMsg::ShuttingDown(_) => {
+                unsubscribe_from_dead_letters(ctx.system.dead_letters(), ctx.myself());
+                self.shutting_down = true;
+                self.rx_run.store(false, Ordering::Release);
+            }
+            ShellChannelMsg::P2pConfigurationReloaded(configuration) => {
+                if !self.shutting_down {
+                    self.reload_configuration(ctx, configuration);
+                }
+            }
+            _ => (),
         }
     }
 }
@@ -535,6 +599,10 @@ impl Receive<NetworkChannelMsg> for PeerManager {
 
     fn receive(&mut self, ctx: &Context<Self::Msg>, msg: NetworkChannelMsg, _sender: Sender) {
         match msg {
+            NetworkChannelMsg::ProcessAdvertisedPeers(peer, _) if self.private_node => {
+                // private node connects just to the configured peers
+                debug!(ctx.system.log(), "Ignoring advertise message in private mode"; "peer_id" => peer.peer_id_marker.clone());
+            }
             NetworkChannelMsg::ProcessAdvertisedPeers(peer, message) => {
                 // extract potential peers from the advertise message
                 info!(ctx.system.log(), "Received advertise message"; "peer_id" => peer.peer_id_marker.clone(), "peers" => format!("{:?}", message.id().join(", ")));
@@ -564,6 +632,10 @@ impl Receive<NetworkChannelMsg> for PeerManager {
             }) => {
                 // received message that bootstrap process failed for the peer
                 match potential_peers_to_connect {
+                    Some(_) if self.private_node => {
+                        // private node connects just to the configured peers
+                        self.trigger_check_peer_count(ctx);
+                    }
                     Some(peers) => {
                         self.process_new_potential_peers(
                             peers
@@ -606,6 +678,7 @@ impl Receive<WhitelistAllIpAddresses> for PeerManager {
     ) {
         info!(ctx.system.log(), "Whitelisting all IP addresses");
         self.ip_blacklist.clear();
+        self.address_blacklist.clear();
     }
 }
 
@@ -615,8 +688,8 @@ impl Receive<ConnectToPeer> for PeerManager {
     fn receive(&mut self, ctx: &Context<Self::Msg>, msg: ConnectToPeer, _sender: Sender) {
         // received message instructing this actor that it should open new p2p connection to the remote peer
 
-        if self.is_blacklisted(&msg.address.ip()) {
-            debug!(ctx.system.log(), "Peer is blacklisted - will not connect"; "ip" => format!("{}", msg.address.ip()));
+        if self.is_blacklisted(&msg.address) {
+            debug!(ctx.system.log(), "Peer is blacklisted - will not connect"; "address" => format!("{}", msg.address));
             return;
         }
 
@@ -663,8 +736,8 @@ impl Receive<AcceptPeer> for PeerManager {
     type Msg = PeerManagerMsg;
 
     fn receive(&mut self, ctx: &Context<Self::Msg>, msg: AcceptPeer, _sender: Sender) {
-        if self.is_blacklisted(&msg.address.ip()) {
-            warn!(ctx.system.log(), "Peer is blacklisted - will not accept connection"; "ip" => format!("{}", msg.address.ip()));
+        if self.is_blacklisted(&msg.address) {
+            warn!(ctx.system.log(), "Peer is blacklisted - will not accept connection"; "address" => format!("{}", msg.address));
         } else if self.peers.len() < self.threshold.high {
             debug!(ctx.system.log(), "Connection from"; "ip" => msg.address);
 
@@ -0,0 +1,51 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Runtime reload of the subset of node configuration (without node restart).
+//!
+//! Reload is triggered by SIGHUP or by rpc, implementation (re-read of configuration and applying changes to running actors/pools)
+//! is provided by the node, so the rpc server just calls [ConfigurationReloader].
+
+use std::sync::Arc;
+
+use failure::Fail;
+use serde::Serialize;
+
+/// Reference to [ConfigurationReloader]
+pub type ConfigurationReloaderRef = Arc<dyn ConfigurationReloader>;
+
+/// Reloads configuration and applies changes to the running node
+pub trait ConfigurationReloader: Send + Sync {
+    /// Re-reads and validates configuration, applies all changed reloadable settings and returns them.
+    /// If validation fails, nothing is applied.
+    fn reload(&self) -> Result<Vec<ConfigurationChange>, ConfigurationReloadError>;
+}
+
+/// One applied change of configuration
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfigurationChange {
+    pub setting: String,
+    pub previous_value: String,
+    pub new_value: String,
+}
+
+impl ConfigurationChange {
+    pub fn new(setting: &str, previous_value: String, new_value: String) -> Self {
+        Self {
+            setting: setting.to_string(),
+            previous_value,
+            new_value,
+        }
+    }
+}
+
+/// Possible errors for configuration reload
+#[derive(Debug, Fail)]
+pub enum ConfigurationReloadError {
+    #[fail(display = "Invalid configuration, reason: {}", reason)]
+    InvalidConfiguration { reason: String },
+    #[fail(display = "Failed to apply setting: {}, reason: {}", setting, reason)]
+    ApplyError { setting: String, reason: String },
+    #[fail(display = "Configuration reload is already running")]
+    AlreadyRunning,
+}
@@ -3,6 +3,7 @@
 
 //! Shell channel is used to transmit high level shell messages.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use riker::actors::*;
@@ -13,8 +14,10 @@ use storage::BlockHeaderWithHash;
 use tezos_messages::p2p::encoding::prelude::{Mempool, Operation, Path};
 use tezos_messages::Head;
 
+use crate::state::head_state::Reorg;
 use crate::state::synchronization_state::PeerBranchSynchronizationDone;
 use crate::utils::CondvarResult;
+use crate::PeerConnectionThreshold;
 
 /// Notify actors that system is about to shut down
 #[derive(Clone, Debug)]
@@ -46,6 +49,14 @@ pub struct MempoolOperationReceived {
     pub result_callback: Option<CondvarResult<(), failure::Error>>,
 }
 
+/// Notify actors that p2p configuration was reloaded at runtime
+#[derive(Clone, Debug)]
+pub struct P2pConfigurationReloaded {
+    pub peer_threshold: PeerConnectionThreshold,
+    pub bootstrap_peers: Vec<SocketAddr>,
+    pub disable_mempool: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct InjectBlock {
     pub chain_id: Arc<ChainId>,
@@ -64,6 +75,8 @@ pub enum ShellChannelMsg {
     BlockApplied(Arc<BlockHash>),
     AllBlockOperationsReceived(AllBlockOperationsReceived),
     MempoolOperationReceived(MempoolOperationReceived),
+    /// If current head was switched to the other branch and some blocks were rolled back
+    Reorg(Arc<Reorg>),
 
     /// Commands
     AdvertiseToP2pNewCurrentBranch(Arc<ChainId>, Arc<BlockHash>),
@@ -72,6 +85,7 @@ pub enum ShellChannelMsg {
     InjectBlock(InjectBlock, Option<CondvarResult<(), failure::Error>>),
     RequestCurrentHead(RequestCurrentHead),
     PeerBranchSynchronizationDone(PeerBranchSynchronizationDone),
+    P2pConfigurationReloaded(P2pConfigurationReloaded),
     ShuttingDown(ShuttingDown),
 }
 
@@ -99,6 +113,12 @@ impl From<ShuttingDown> for ShellChannelMsg {
     }
 }
 
+impl From<P2pConfigurationReloaded> for ShellChannelMsg {
+    fn from(msg: P2pConfigurationReloaded) -> Self {
+        ShellChannelMsg::P2pConfigurationReloaded(msg)
+    }
+}
+
 impl From<RequestCurrentHead> for ShellChannelMsg {
     fn from(msg: RequestCurrentHead) -> Self {
         ShellChannelMsg::RequestCurrentHead(msg)
@@ -119,6 +139,9 @@ pub enum ShellChannelTopic {
     /// Control event
     ShellCommands,
 
+    /// Runtime configuration changes
+    ShellConfiguration,
+
     /// Shutdown event
     ShellShutdown,
 }
@@ -130,6 +153,7 @@ impl From<ShellChannelTopic> for Topic {
             ShellChannelTopic::ShellNewCurrentHead => Topic::from("shell.new_current_head"),
             ShellChannelTopic::ShellBlockApplied => Topic::from("shell.block_applied"),
             ShellChannelTopic::ShellCommands => Topic::from("shell.commands"),
+            ShellChannelTopic::ShellConfiguration => Topic::from("shell.configuration"),
             ShellChannelTopic::ShellShutdown => Topic::from("shell.shutdown"),
         }
     }
@@ -16,8 +16,10 @@ use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::persistent::PersistentStorage;
 use storage::{
     BlockHeaderWithHash, BlockMetaStorage, BlockMetaStorageReader, BlockStorage,
-    BlockStorageReader, ChainMetaStorage, OperationsMetaStorage, OperationsStorage, StorageError,
+    BlockStorageReader, ChainMetaStorage, Checkpoint, OperationsMetaStorage, OperationsStorage,
+    StorageError,
 };
+use tezos_messages::p2p::binary_message::MessageHash;
 use tezos_messages::p2p::encoding::block_header::BlockHeader;
 use tezos_messages::p2p::encoding::current_branch::{CurrentBranchMessage, HISTORY_MAX_SIZE};
 use tezos_messages::p2p::encoding::prelude::{CurrentHeadMessage, OperationsForBlocksMessage};
@@ -31,9 +33,12 @@ use crate::peer_branch_bootstrapper::{
 };
 use crate::shell_channel::ShellChannelRef;
 use crate::state::bootstrap_state::InnerBlockState;
+use crate::state::clock_state::NodeClockRef;
+use crate::state::download_scheduler::{init_download_scheduler, DownloadSchedulerRef};
 use crate::state::head_state::CurrentHeadRef;
 use crate::state::peer_state::PeerState;
 use crate::state::StateError;
+use crate::stats::block_lifecycle::BlockLifecycleTracerRef;
 use crate::validation;
 
 pub enum BlockAcceptanceResult {
@@ -63,8 +68,20 @@ pub struct BlockchainState {
     shell_channel: ShellChannelRef,
     chain_feeder_channel: ChainFeederChannelRef,
 
+    /// Tracer of the block lifecycle (passed to bootstrappers)
+    block_lifecycle_tracer: BlockLifecycleTracerRef,
+
+    /// Node's notion of "now" (used to reject future blocks)
+    node_clock: NodeClockRef,
+
+    /// Chain-wide download scheduler (shared by all bootstrappers)
+    download_scheduler: DownloadSchedulerRef,
+
     chain_id: Arc<ChainId>,
     chain_genesis_block_hash: Arc<BlockHash>,
+
+    /// Trusted checkpoint, just branches with this block are accepted
+    checkpoint: Option<Checkpoint>,
 }
 
 impl BlockchainState {
@@ -73,8 +90,11 @@ impl BlockchainState {
         block_applier: ChainFeederRef,
         shell_channel: ShellChannelRef,
         chain_feeder_channel: ChainFeederChannelRef,
+        block_lifecycle_tracer: BlockLifecycleTracerRef,
+        node_clock: NodeClockRef,
         chain_id: Arc<ChainId>,
         chain_genesis_block_hash: Arc<BlockHash>,
+        checkpoint: Option<Checkpoint>,
     ) -> Self {
         BlockchainState {
             block_storage: BlockStorage::new(persistent_storage),
@@ -85,8 +105,12 @@ impl BlockchainState {
             block_applier,
             shell_channel,
             chain_feeder_channel,
+            block_lifecycle_tracer,
+            node_clock,
+            download_scheduler: init_download_scheduler(),
             chain_id,
             chain_genesis_block_hash,
+            checkpoint,
         }
     }
 
@@ -103,6 +127,9 @@ impl BlockchainState {
         if branch.current_branch().current_head().level() <= 0 {
             return Ok(false);
         }
+        if !self.can_reach_checkpoint(branch, current_head)? {
+            return Ok(false);
+        }
 
         if let Some(current_head) = current_head.read()?.as_ref() {
             // (only_if_fitness_increases) we can accept branch if increases fitness
@@ -119,6 +146,81 @@ impl BlockchainState {
         }
     }
 
+    /// Branch must contain checkpoint (if configured), until our current head is behind the checkpoint
+    ///
+    /// History of the branch is sparse, so here we just check, that branch is at least as high as the checkpoint
+    /// and does not have other block at the checkpoint level. The rest is checked, when headers of the branch above the checkpoint
+    /// are downloaded (backward from the branch head), see [is_conflicting_with_checkpoint].
+    ///
+    /// Until the checkpoint is applied, headers are downloaded backward from the checkpoint itself, see [checkpoint_to_reach].
+    fn can_reach_checkpoint(
+        &self,
+        branch: &CurrentBranchMessage,
+        current_head: &CurrentHeadRef,
+    ) -> Result<bool, StateError> {
+        let checkpoint = match &self.checkpoint {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(true),
+        };
+
+        if let Some(current_head) = current_head.read()?.as_ref() {
+            if current_head.level() >= checkpoint.level() {
+                // we are already behind the checkpoint, fitness decides
+                return Ok(true);
+            }
+        }
+
+        let branch_head = branch.current_branch().current_head();
+        if branch_head.level() < *checkpoint.level() {
+            return Ok(false);
+        }
+        if branch_head.level() == *checkpoint.level() {
+            let branch_head_hash: BlockHash =
+                branch_head
+                    .message_typed_hash()
+                    .map_err(|e| StateError::ProcessingError {
+                        reason: format!("{}", e),
+                    })?;
+            return Ok(checkpoint.block_hash().eq(&branch_head_hash));
+        }
+        Ok(true)
+    }
+
+    /// Returns true, if block is at the level of the checkpoint, but it is not the checkpoint,
+    /// which means, that block (and every its successor) is not on the trusted branch
+    pub fn is_conflicting_with_checkpoint(&self, block: &BlockHeaderWithHash) -> bool {
+        match &self.checkpoint {
+            Some(checkpoint) => checkpoint.conflicts_with(&block.hash, block.header.level()),
+            None => false,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Option<&Checkpoint> {
+        self.checkpoint.as_ref()
+    }
+
+    /// Returns checkpoint, if branch with this head should be bootstrapped just to the checkpoint (headers-first).
+    ///
+    /// Checkpoint header is requested directly (GetBlockHeaders) and headers are downloaded backward from it down to the last applied block,
+    /// so every downloaded header is linked to the checkpoint by its predecessor hash.
+    /// Blocks above the checkpoint are scheduled the regular way, when the checkpoint is applied.
+    fn checkpoint_to_reach(
+        &self,
+        branch_head: &BlockHeaderWithHash,
+    ) -> Result<Option<&Checkpoint>, StorageError> {
+        match &self.checkpoint {
+            Some(checkpoint)
+                if branch_head.header.level() > *checkpoint.level()
+                    && !self
+                        .block_meta_storage
+                        .is_applied(checkpoint.block_hash())? =>
+            {
+                Ok(Some(checkpoint))
+            }
+            _ => Ok(None),
+        }
+    }
+
     /// Validate if we can accept head
     pub fn can_accept_head(
         &self,
@@ -136,6 +238,20 @@ impl BlockchainState {
             return Ok(BlockAcceptanceResult::IgnoreBlock);
         }
 
+        // head at the checkpoint level must be the checkpoint
+        if let Some(checkpoint) = &self.checkpoint {
+            if validated_header.level() == *checkpoint.level() {
+                let validated_header_hash: BlockHash = validated_header
+                    .message_typed_hash()
+                    .map_err(|e| StateError::ProcessingError {
+                        reason: format!("{}", e),
+                    })?;
+                if checkpoint.block_hash().ne(&validated_header_hash) {
+                    return Ok(BlockAcceptanceResult::IgnoreBlock);
+                }
+            }
+        }
+
         // we need our current head at first
         if let Some(current_head) = current_head.read()?.as_ref() {
             // same header means only mempool operations were changed
@@ -144,7 +260,7 @@ impl BlockchainState {
             }
 
             // (future block)
-            if validation::is_future_block(&validated_header)? {
+            if validation::is_future_block(&validated_header, self.node_clock.now())? {
                 return Ok(BlockAcceptanceResult::IgnoreBlock);
             }
 
@@ -404,6 +520,16 @@ impl BlockchainState {
                 }
             };
 
+        // until the checkpoint is applied, we download (and link) headers backward from the checkpoint,
+        // the rest of the branch is scheduled after the checkpoint is reached (see [checkpoint_to_reach])
+        let (missing_history, to_level) = match self.checkpoint_to_reach(block_header)? {
+            Some(checkpoint) => (
+                vec![Arc::new(checkpoint.block_hash().clone())],
+                *checkpoint.level(),
+            ),
+            None => (missing_history, block_header.header.level()),
+        };
+
         // if we miss something, we will run "peer branch bootstrapper"
         if !missing_history.is_empty() {
             if peer.peer_branch_bootstrapper.is_none() {
@@ -418,6 +544,8 @@ impl BlockchainState {
                         self.block_applier.clone(),
                         self.block_meta_storage.clone(),
                         self.operations_meta_storage.clone(),
+                        self.block_lifecycle_tracer.clone(),
+                        self.download_scheduler.clone(),
                     )
                     .map_err(|e| StateError::ProcessingError {
                         reason: format!("{}", e),
@@ -432,7 +560,7 @@ impl BlockchainState {
                         self.chain_id.clone(),
                         last_applied_block,
                         missing_history,
-                        Arc::new(block_header.header.level()),
+                        Arc::new(to_level),
                     ),
                     None,
                 );
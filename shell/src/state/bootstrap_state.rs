@@ -54,69 +54,58 @@ impl BootstrapState {
 
     /// This finds block, which should be downloaded first.
     pub fn next_block_to_download(&self) -> Option<Arc<BlockHash>> {
-        let mut previous_interval_applied = true;
-
-        for interval in &self.intervals {
-            // Note: little slow down, we want to download next interval, only if the previos one was applied successfully
-            // to prevent downloading invalid rest of the chain
-            if !previous_interval_applied {
-                break;
-            }
-            previous_interval_applied = interval.all_block_applied;
-
-            // if interval is downloaded, just skip it
-            if interval.all_blocks_downloaded {
-                continue;
-            }
-
-            // get first non-downloaded block
-            for b in &interval.blocks {
-                // skip applied
-                if b.applied {
-                    continue;
-                }
-
-                if !b.block_downloaded {
-                    return Some(b.block_hash.clone());
-                }
-            }
-
-            // if we came here, it means, that interval is not closed, but all blocks are downloaded
-            // we mark all_blocks_downloaded only if predecessor matches first block
-            // so we need to return here first block after begining to continue
-            // to prevent stucking the pipeline
-            if let Some(first_block_after_begining) = interval.blocks.get(1) {
-                return Some(first_block_after_begining.block_hash.clone());
-            }
-        }
-        None
+        // Note: little slow down, we want to download next interval, only if the previos one was applied successfully
+        // to prevent downloading invalid rest of the chain (see [intervals_to_download] for parallel download)
+        self.intervals
+            .first()
+            .and_then(|interval| interval.next_block_to_download())
     }
 
     /// This finds block, for which we should download operations.
     pub fn next_block_operations_to_download(&self) -> Option<Arc<BlockHash>> {
-        let mut previous_interval_applied = true;
+        self.intervals
+            .first()
+            .and_then(|interval| interval.next_block_operations_to_download())
+    }
 
-        for interval in &self.intervals {
-            // Note: little slow down, we want to download next interval, only if the previos one was applied successfully
-            // to prevent downloading invalid rest of the chain
-            if !previous_interval_applied {
-                break;
-            }
-            previous_interval_applied = interval.all_block_applied;
+    /// Returns first `max_intervals` intervals, which are not applied yet, so they can be downloaded in parallel (by more peers).
+    ///
+    /// Interval is identified by its last block (the block from history), which does not change until interval is applied.
+    pub fn intervals_to_download(&self, max_intervals: usize) -> Vec<Arc<BlockHash>> {
+        self.intervals
+            .iter()
+            .take(max_intervals)
+            .filter_map(|interval| interval.blocks.last())
+            .map(|last_block| last_block.block_hash.clone())
+            .collect()
+    }
 
-            // get first non-downloaded block
-            for b in &interval.blocks {
-                // skip applied
-                if b.applied {
-                    continue;
-                }
+    /// This finds block, which should be downloaded first in the interval (identified by its last block).
+    pub fn next_block_to_download_in_interval(
+        &self,
+        interval_id: &BlockHash,
+    ) -> Option<Arc<BlockHash>> {
+        self.interval(interval_id)
+            .and_then(|interval| interval.next_block_to_download())
+    }
 
-                if !b.operations_downloaded {
-                    return Some(b.block_hash.clone());
-                }
-            }
-        }
-        None
+    /// This finds block in the interval (identified by its last block), for which we should download operations.
+    pub fn next_block_operations_to_download_in_interval(
+        &self,
+        interval_id: &BlockHash,
+    ) -> Option<Arc<BlockHash>> {
+        self.interval(interval_id)
+            .and_then(|interval| interval.next_block_operations_to_download())
+    }
+
+    fn interval(&self, interval_id: &BlockHash) -> Option<&BootstrapInterval> {
+        self.intervals.iter().find(|interval| {
+            interval
+                .blocks
+                .last()
+                .map(|last_block| last_block.block_hash.as_ref().eq(interval_id))
+                .unwrap_or(false)
+        })
     }
 
     /// This finds block, for which we should apply.
@@ -522,6 +511,44 @@ impl BootstrapInterval {
         }
     }
 
+    fn next_block_to_download(&self) -> Option<Arc<BlockHash>> {
+        // if interval is downloaded, just skip it
+        if self.all_blocks_downloaded {
+            return None;
+        }
+
+        // get first non-downloaded block
+        // (begining of the interval is skipped, it is applied or it is the end of the previous interval)
+        for b in self.blocks.iter().skip(1) {
+            // skip applied
+            if b.applied {
+                continue;
+            }
+
+            if !b.block_downloaded {
+                return Some(b.block_hash.clone());
+            }
+        }
+
+        // if we came here, it means, that interval is not closed, but all blocks are downloaded
+        // we mark all_blocks_downloaded only if predecessor matches first block
+        // so we need to return here first block after begining to continue
+        // to prevent stucking the pipeline
+        self.blocks
+            .get(1)
+            .map(|first_block_after_begining| first_block_after_begining.block_hash.clone())
+    }
+
+    fn next_block_operations_to_download(&self) -> Option<Arc<BlockHash>> {
+        // get first non-downloaded block (begining of the interval is skipped as well)
+        self.blocks
+            .iter()
+            .skip(1)
+            .filter(|b| !b.applied)
+            .find(|b| !b.operations_downloaded)
+            .map(|b| b.block_hash.clone())
+    }
+
     fn split(
         first_applied_block: Arc<BlockHash>,
         blocks: Vec<Arc<BlockHash>>,
@@ -957,6 +984,67 @@ mod tests {
         assert!(matches!(pipeline.next_block_to_apply(), None));
     }
 
+    #[test]
+    fn test_bootstrap_state_intervals_to_download() {
+        // genesis
+        let last_applied = block(0);
+        // history blocks
+        let history: Vec<Arc<BlockHash>> = vec![block(2), block(5), block(8)];
+        let chain_id = Arc::new(
+            ChainId::from_base58_check("NetXgtSLGNJvNye").expect("Failed to create chainId"),
+        );
+
+        // create
+        let mut pipeline = BootstrapState::new(chain_id, last_applied, history, Arc::new(8));
+        assert_eq!(pipeline.intervals.len(), 3);
+
+        // intervals are identified by the last block
+        let intervals = pipeline.intervals_to_download(2);
+        assert_eq!(intervals.len(), 2);
+        assert_eq!(intervals[0].as_ref(), block(2).as_ref());
+        assert_eq!(intervals[1].as_ref(), block(5).as_ref());
+        assert_eq!(pipeline.intervals_to_download(10).len(), 3);
+
+        // every interval has its own next block
+        assert!(
+            matches!(pipeline.next_block_to_download_in_interval(&block(5)), Some(x) if x.as_ref().eq(block(5).as_ref()))
+        );
+        assert!(
+            matches!(pipeline.next_block_operations_to_download_in_interval(&block(8)), Some(x) if x.as_ref().eq(block(8).as_ref()))
+        );
+        assert!(matches!(
+            pipeline.next_block_to_download_in_interval(&block(3)),
+            None
+        ));
+
+        // register downloaded block 5 with his predecessor 4, which continues the second interval
+        assert!(pipeline
+            .block_downloaded(
+                &block(5),
+                &InnerBlockState {
+                    block_downloaded: true,
+                    applied: false,
+                    operations_downloaded: false,
+                },
+                &block(4),
+                |_| {
+                    Ok(Some(InnerBlockState {
+                        block_downloaded: false,
+                        applied: false,
+                        operations_downloaded: false,
+                    }))
+                },
+            )
+            .is_ok());
+        assert!(
+            matches!(pipeline.next_block_to_download_in_interval(&block(5)), Some(x) if x.as_ref().eq(block(4).as_ref()))
+        );
+        // first interval is untouched
+        assert!(
+            matches!(pipeline.next_block_to_download(), Some(x) if x.as_ref().eq(block(2).as_ref()))
+        );
+    }
+
     fn assert_interval(
         tested: &BootstrapInterval,
         (expected_left, expected_right): (Arc<BlockHash>, Arc<BlockHash>),
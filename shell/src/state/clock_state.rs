@@ -0,0 +1,88 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! This module covers node's notion of "now", which is used e.g. to reject blocks from the future.
+//!
+//! It is the wall clock with an offset, the offset can be changed just in sandbox (by rpc),
+//! so tests can move through cycles and voting periods without waiting.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Clock shareable between threads/actors
+pub type NodeClockRef = Arc<NodeClock>;
+
+/// Inits clock without offset (wall clock)
+pub fn init_node_clock() -> NodeClockRef {
+    Arc::new(NodeClock {
+        offset_secs: AtomicI64::new(0),
+    })
+}
+
+/// Wall clock with configurable offset
+pub struct NodeClock {
+    offset_secs: AtomicI64,
+}
+
+impl NodeClock {
+    /// Returns node's "now"
+    pub fn now(&self) -> DateTime<Utc> {
+        Utc::now() + Duration::seconds(self.offset_secs())
+    }
+
+    /// Returns offset of the node's "now" from the wall clock
+    pub fn offset_secs(&self) -> i64 {
+        self.offset_secs.load(Ordering::Acquire)
+    }
+
+    /// Sets node's "now" to the timestamp (unix seconds), the clock keeps running from there
+    pub fn set_now(&self, timestamp: i64) {
+        self.offset_secs
+            .store(timestamp - Utc::now().timestamp(), Ordering::Release);
+    }
+
+    /// Moves node's "now" by seconds (negative value moves back)
+    pub fn advance(&self, secs: i64) {
+        self.offset_secs.fetch_add(secs, Ordering::AcqRel);
+    }
+
+    /// Moves node's "now" forward to the timestamp (unix seconds), if it is behind
+    pub fn advance_to(&self, timestamp: i64) {
+        let now = Utc::now().timestamp();
+        self.offset_secs
+            .fetch_max(timestamp - now, Ordering::AcqRel);
+    }
+
+    /// Resets clock back to the wall clock
+    pub fn reset(&self) {
+        self.offset_secs.store(0, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_clock() {
+        let clock = init_node_clock();
+        assert_eq!(0, clock.offset_secs());
+
+        clock.advance(3600);
+        assert_eq!(3600, clock.offset_secs());
+        assert!(clock.now() > Utc::now() + Duration::seconds(3500));
+
+        // advance_to never moves back
+        clock.advance_to(Utc::now().timestamp() + 60);
+        assert_eq!(3600, clock.offset_secs());
+
+        let future = Utc::now().timestamp() + 86400;
+        clock.set_now(future);
+        assert!((clock.now().timestamp() - future).abs() <= 1);
+
+        clock.reset();
+        assert_eq!(0, clock.offset_secs());
+    }
+}
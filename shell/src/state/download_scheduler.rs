@@ -0,0 +1,625 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Chain-wide scheduler of block headers/operations downloads from peers.
+//!
+//! Every peer's bootstrapper asks the scheduler before it requests anything from its peer, so that:
+//! - one block (header/operations) is requested just from one peer at a time,
+//! - one interval of the branch (see [BootstrapState](crate::state::bootstrap_state::BootstrapState)) is downloaded by one peer at a time,
+//!   other peers continue with the following intervals,
+//! - count of requests in flight is limited per peer according to its measured throughput (the fastest peer gets the most),
+//! - requests (and intervals), which are not answered in time (according to measured latency of the peer), are re-assigned to other peers,
+//! - interval is released, as soon as it is downloaded or its request timed out, so other peers do not wait for it.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crypto::hash::BlockHash;
+
+/// Scheduler shareable between peer's bootstrappers
+pub type DownloadSchedulerRef = Arc<Mutex<DownloadScheduler>>;
+
+pub fn init_download_scheduler() -> DownloadSchedulerRef {
+    Arc::new(Mutex::new(DownloadScheduler::default()))
+}
+
+/// What is requested from peer
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DownloadKind {
+    BlockHeader,
+    Operations,
+}
+
+/// Result of [DownloadScheduler::schedule]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    /// Block should not be requested from the peer now
+    Denied,
+    /// Block should be requested from the peer
+    Scheduled,
+    /// Block should be requested from the peer, because request of the other (stalled) peer was not answered in time
+    Reassigned { stalled_peer: SocketAddr },
+}
+
+impl Schedule {
+    pub fn is_scheduled(&self) -> bool {
+        !matches!(self, Schedule::Denied)
+    }
+}
+
+struct Request {
+    peer: SocketAddr,
+    /// Interval (identified by its last block), which the block belongs to
+    interval: Arc<BlockHash>,
+    requested_at: Instant,
+}
+
+struct IntervalClaim {
+    peer: SocketAddr,
+    last_progress: Instant,
+}
+
+#[derive(Default)]
+struct PeerDownloads {
+    in_flight: usize,
+    /// Receive times of the requests in the last [DownloadScheduler::THROUGHPUT_WINDOW]
+    recently_received: VecDeque<Instant>,
+    received: usize,
+    timed_out: usize,
+    /// Exponential moving average of the request/response latency
+    latency: Option<Duration>,
+}
+
+impl PeerDownloads {
+    fn throughput(&self, now: Instant) -> usize {
+        self.recently_received
+            .iter()
+            .filter(|received_at| {
+                now.duration_since(**received_at) <= DownloadScheduler::THROUGHPUT_WINDOW
+            })
+            .count()
+    }
+
+    fn stall_timeout(&self) -> Duration {
+        match self.latency {
+            Some(latency) => (latency * DownloadScheduler::STALL_LATENCY_FACTOR)
+                .max(DownloadScheduler::MIN_STALL_TIMEOUT)
+                .min(DownloadScheduler::MAX_STALL_TIMEOUT),
+            None => DownloadScheduler::MAX_STALL_TIMEOUT,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DownloadScheduler {
+    /// Requests in flight
+    requests: HashMap<(DownloadKind, BlockHash), Request>,
+    /// Intervals (identified by its last block) claimed by peers
+    intervals: HashMap<(DownloadKind, Arc<BlockHash>), IntervalClaim>,
+    peers: HashMap<SocketAddr, PeerDownloads>,
+    /// Count of requests re-assigned to other peers
+    reassigned: usize,
+}
+
+impl DownloadScheduler {
+    /// Peer, which does not respond, can have at least this count of requests in flight
+    pub const MIN_IN_FLIGHT: usize = 1;
+    /// The fastest peer can have this count of requests in flight
+    pub const MAX_IN_FLIGHT: usize = 10;
+    /// Peer, which was not measured yet, starts with this count of requests in flight
+    pub const DEFAULT_IN_FLIGHT: usize = 4;
+
+    /// Throughput is measured as count of responses in this window
+    const THROUGHPUT_WINDOW: Duration = Duration::from_secs(30);
+    /// Request is stalled, if it takes this times longer, than is the average latency of the peer
+    const STALL_LATENCY_FACTOR: u32 = 5;
+    const MIN_STALL_TIMEOUT: Duration = Duration::from_secs(5);
+    const MAX_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+    /// Requests, which nobody wants to re-assign (e.g. block was downloaded in other way), are forgotten after this timeout
+    const MAX_REQUEST_AGE: Duration = Duration::from_secs(120);
+
+    /// Decides, if `peer` should request the block (header/operations) now, if so, the request is registered as in flight.
+    ///
+    /// Returns [Schedule::Denied], if:
+    /// - block is already requested (and not stalled yet),
+    /// - interval is downloaded by another peer (which is not stalled yet),
+    /// - peer has reached its limit of requests in flight.
+    pub fn schedule(
+        &mut self,
+        kind: DownloadKind,
+        peer: SocketAddr,
+        interval: &Arc<BlockHash>,
+        block_hash: &BlockHash,
+    ) -> Schedule {
+        let now = Instant::now();
+        self.forget_old_requests(now);
+
+        let key = (kind, block_hash.clone());
+        if let Some(request) = self.requests.get(&key) {
+            if request.peer == peer
+                || now.duration_since(request.requested_at) < self.stall_timeout(&request.peer)
+            {
+                return Schedule::Denied;
+            }
+        }
+
+        if let Some(claim) = self.intervals.get(&(kind, interval.clone())) {
+            if claim.peer != peer
+                && now.duration_since(claim.last_progress) < self.stall_timeout(&claim.peer)
+            {
+                return Schedule::Denied;
+            }
+        }
+
+        if self.in_flight(&peer) >= self.max_in_flight(&peer, now) {
+            return Schedule::Denied;
+        }
+
+        // re-assign stalled request
+        let result = match self.requests.remove(&key) {
+            Some(stalled) => {
+                if let Some(stalled_peer) = self.peers.get_mut(&stalled.peer) {
+                    stalled_peer.in_flight = stalled_peer.in_flight.saturating_sub(1);
+                    stalled_peer.timed_out += 1;
+                }
+                self.reassigned += 1;
+                Schedule::Reassigned {
+                    stalled_peer: stalled.peer,
+                }
+            }
+            None => Schedule::Scheduled,
+        };
+
+        let claim = self
+            .intervals
+            .entry((kind, interval.clone()))
+            .or_insert_with(|| IntervalClaim {
+                peer,
+                last_progress: now,
+            });
+        if claim.peer != peer {
+            claim.peer = peer;
+            claim.last_progress = now;
+        }
+
+        self.requests.insert(
+            key,
+            Request {
+                peer,
+                interval: interval.clone(),
+                requested_at: now,
+            },
+        );
+        self.peers.entry(peer).or_default().in_flight += 1;
+        result
+    }
+
+    /// Notifies, that block (header/operations) was received from the peer
+    pub fn received(&mut self, kind: DownloadKind, peer: SocketAddr, block_hash: &BlockHash) {
+        let now = Instant::now();
+        let request = match self.requests.remove(&(kind, block_hash.clone())) {
+            Some(request) => request,
+            None => return,
+        };
+
+        // anyway, block is downloaded, so the slot of the requesting peer is released
+        if let Some(requested_peer) = self.peers.get_mut(&request.peer) {
+            requested_peer.in_flight = requested_peer.in_flight.saturating_sub(1);
+        }
+
+        // measure just peer's own responses
+        if request.peer != peer {
+            return;
+        }
+        if let Some(stats) = self.peers.get_mut(&peer) {
+            let latency = now.duration_since(request.requested_at);
+            stats.latency = Some(match stats.latency {
+                Some(average) => (average * 4 + latency) / 5,
+                None => latency,
+            });
+            stats.received += 1;
+            stats.recently_received.push_back(now);
+            while let Some(received_at) = stats.recently_received.front() {
+                if now.duration_since(*received_at) > Self::THROUGHPUT_WINDOW {
+                    stats.recently_received.pop_front();
+                } else {
+                    break;
+                }
+            }
+        }
+        if let Some(claim) = self.intervals.get_mut(&(kind, request.interval)) {
+            if claim.peer == peer {
+                claim.last_progress = now;
+            }
+        }
+    }
+
+    /// Notifies, that all blocks (headers/operations) of the interval are downloaded, so the interval is not claimed anymore
+    pub fn interval_downloaded(&mut self, kind: DownloadKind, interval: &Arc<BlockHash>) {
+        self.intervals.remove(&(kind, interval.clone()));
+    }
+
+    /// Forgets everything about the peer, so its requests and intervals can be scheduled to other peers immediately
+    pub fn peer_disconnected(&mut self, peer: &SocketAddr) {
+        self.requests.retain(|_, request| request.peer.ne(peer));
+        self.intervals.retain(|_, claim| claim.peer.ne(peer));
+        self.peers.remove(peer);
+    }
+
+    /// Returns current count of requests in flight for the peer
+    pub fn in_flight(&self, peer: &SocketAddr) -> usize {
+        self.peers.get(peer).map(|p| p.in_flight).unwrap_or(0)
+    }
+
+    /// Returns max count of requests in flight for the peer, which is proportional to its throughput compared to the fastest peer
+    pub fn max_in_flight(&self, peer: &SocketAddr, now: Instant) -> usize {
+        let stats = match self.peers.get(peer) {
+            Some(stats) if stats.received > 0 || stats.timed_out > 0 => stats,
+            _ => return Self::DEFAULT_IN_FLIGHT,
+        };
+
+        let best_throughput = self
+            .peers
+            .values()
+            .map(|p| p.throughput(now))
+            .max()
+            .unwrap_or(0);
+        if best_throughput == 0 {
+            return Self::MIN_IN_FLIGHT;
+        }
+
+        Self::MIN_IN_FLIGHT
+            + (Self::MAX_IN_FLIGHT - Self::MIN_IN_FLIGHT) * stats.throughput(now) / best_throughput
+    }
+
+    /// Returns count of requests, which were re-assigned to other peers
+    pub fn reassigned(&self) -> usize {
+        self.reassigned
+    }
+
+    fn stall_timeout(&self, peer: &SocketAddr) -> Duration {
+        self.peers
+            .get(peer)
+            .map(|p| p.stall_timeout())
+            .unwrap_or(Self::MAX_STALL_TIMEOUT)
+    }
+
+    fn forget_old_requests(&mut self, now: Instant) {
+        let peers = &mut self.peers;
+        let intervals = &mut self.intervals;
+        self.requests.retain(|(kind, _), request| {
+            if now.duration_since(request.requested_at) < Self::MAX_REQUEST_AGE {
+                true
+            } else {
+                if let Some(stats) = peers.get_mut(&request.peer) {
+                    stats.in_flight = stats.in_flight.saturating_sub(1);
+                    stats.timed_out += 1;
+                }
+                // timed out peer does not hold the interval anymore
+                let interval_key = (*kind, request.interval.clone());
+                if let Some(claim) = intervals.get(&interval_key) {
+                    if claim.peer == request.peer {
+                        intervals.remove(&interval_key);
+                    }
+                }
+                false
+            }
+        });
+
+        // intervals, which nobody continues with (e.g. interval was applied meanwhile), are forgotten as well
+        self.intervals
+            .retain(|_, claim| now.duration_since(claim.last_progress) < Self::MAX_REQUEST_AGE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn block(d: u8) -> BlockHash {
+        [d; 32]
+            .to_vec()
+            .try_into()
+            .expect("Failed to create BlockHash")
+    }
+
+    #[test]
+    fn test_schedule_block_just_once() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        // already in flight
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(1))
+            .is_scheduled());
+        // operations are scheduled separately
+        assert!(scheduler
+            .schedule(DownloadKind::Operations, peer(2), &interval, &block(1))
+            .is_scheduled());
+
+        assert_eq!(1, scheduler.in_flight(&peer(1)));
+        assert_eq!(1, scheduler.in_flight(&peer(2)));
+    }
+
+    #[test]
+    fn test_interval_is_claimed_by_one_peer() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval1 = Arc::new(block(100));
+        let interval2 = Arc::new(block(200));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval1, &block(1))
+            .is_scheduled());
+        // other peer continues with next interval
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval1, &block(2))
+            .is_scheduled());
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval2, &block(3))
+            .is_scheduled());
+
+        // owner continues with its interval
+        scheduler.received(DownloadKind::BlockHeader, peer(1), &block(1));
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval1, &block(2))
+            .is_scheduled());
+        assert_eq!(0, scheduler.reassigned());
+    }
+
+    #[test]
+    fn test_in_flight_limit_per_peer() {
+        let mut scheduler = DownloadScheduler::default();
+        let now = Instant::now();
+
+        // unmeasured peer
+        assert_eq!(
+            DownloadScheduler::DEFAULT_IN_FLIGHT,
+            scheduler.max_in_flight(&peer(1), now)
+        );
+        for i in 0..DownloadScheduler::DEFAULT_IN_FLIGHT {
+            let interval = Arc::new(block(100 + i as u8));
+            assert!(scheduler
+                .schedule(
+                    DownloadKind::BlockHeader,
+                    peer(1),
+                    &interval,
+                    &block(i as u8)
+                )
+                .is_scheduled());
+        }
+        let interval = Arc::new(block(99));
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(50))
+            .is_scheduled());
+
+        // response releases slot
+        scheduler.received(DownloadKind::BlockHeader, peer(1), &block(0));
+        assert_eq!(
+            DownloadScheduler::DEFAULT_IN_FLIGHT - 1,
+            scheduler.in_flight(&peer(1))
+        );
+        // the only measured peer is the fastest one
+        assert_eq!(
+            DownloadScheduler::MAX_IN_FLIGHT,
+            scheduler.max_in_flight(&peer(1), Instant::now())
+        );
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(50))
+            .is_scheduled());
+    }
+
+    #[test]
+    fn test_in_flight_limit_is_proportional_to_throughput() {
+        let mut scheduler = DownloadScheduler::default();
+
+        // peer1 responds twice more, than peer2
+        for i in 0..4 {
+            let interval = Arc::new(block(100 + i));
+            assert!(scheduler
+                .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(i))
+                .is_scheduled());
+            scheduler.received(DownloadKind::BlockHeader, peer(1), &block(i));
+        }
+        for i in 10..12 {
+            let interval = Arc::new(block(100 + i));
+            assert!(scheduler
+                .schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(i))
+                .is_scheduled());
+            scheduler.received(DownloadKind::BlockHeader, peer(2), &block(i));
+        }
+
+        let now = Instant::now();
+        assert_eq!(
+            DownloadScheduler::MAX_IN_FLIGHT,
+            scheduler.max_in_flight(&peer(1), now)
+        );
+        assert_eq!(
+            DownloadScheduler::MIN_IN_FLIGHT
+                + (DownloadScheduler::MAX_IN_FLIGHT - DownloadScheduler::MIN_IN_FLIGHT) / 2,
+            scheduler.max_in_flight(&peer(2), now)
+        );
+    }
+
+    #[test]
+    fn test_stalled_request_is_reassigned() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+
+        assert!(scheduler
+            .schedule(DownloadKind::Operations, peer(1), &interval, &block(1))
+            .is_scheduled());
+
+        // simulate stalled request (and interval)
+        let stalled_at = Instant::now() - DownloadScheduler::MAX_STALL_TIMEOUT;
+        scheduler
+            .requests
+            .values_mut()
+            .for_each(|r| r.requested_at = stalled_at);
+        scheduler
+            .intervals
+            .values_mut()
+            .for_each(|c| c.last_progress = stalled_at);
+
+        assert_eq!(
+            Schedule::Reassigned {
+                stalled_peer: peer(1)
+            },
+            scheduler.schedule(DownloadKind::Operations, peer(2), &interval, &block(1))
+        );
+        assert_eq!(1, scheduler.reassigned());
+        assert_eq!(0, scheduler.in_flight(&peer(1)));
+        assert_eq!(1, scheduler.in_flight(&peer(2)));
+
+        // stalled peer cannot continue with the interval
+        assert!(!scheduler
+            .schedule(DownloadKind::Operations, peer(1), &interval, &block(2))
+            .is_scheduled());
+
+        // late response from stalled peer does not break anything
+        scheduler.received(DownloadKind::Operations, peer(1), &block(1));
+        assert_eq!(0, scheduler.in_flight(&peer(2)));
+    }
+
+    #[test]
+    fn test_interval_is_released_when_downloaded() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        assert!(scheduler
+            .schedule(DownloadKind::Operations, peer(1), &interval, &block(1))
+            .is_scheduled());
+        scheduler.received(DownloadKind::BlockHeader, peer(1), &block(1));
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(2))
+            .is_scheduled());
+
+        // headers are downloaded, so other peer does not need to wait for the interval
+        scheduler.interval_downloaded(DownloadKind::BlockHeader, &interval);
+        assert_eq!(
+            Schedule::Scheduled,
+            scheduler.schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(2))
+        );
+
+        // operations are still claimed
+        assert!(!scheduler
+            .schedule(DownloadKind::Operations, peer(2), &interval, &block(2))
+            .is_scheduled());
+        assert_eq!(0, scheduler.reassigned());
+    }
+
+    #[test]
+    fn test_interval_is_released_when_request_timed_out() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+        let other_interval = Arc::new(block(200));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        assert!(scheduler
+            .schedule(
+                DownloadKind::BlockHeader,
+                peer(1),
+                &other_interval,
+                &block(3)
+            )
+            .is_scheduled());
+
+        // simulate request, which was never answered, but the interval is still progressing
+        let timed_out_at = Instant::now() - DownloadScheduler::MAX_REQUEST_AGE;
+        scheduler
+            .requests
+            .values_mut()
+            .filter(|r| r.interval == interval)
+            .for_each(|r| r.requested_at = timed_out_at);
+
+        // request is forgotten and interval released
+        assert_eq!(
+            Schedule::Scheduled,
+            scheduler.schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(2))
+        );
+        assert_eq!(1, scheduler.in_flight(&peer(1)));
+        assert_eq!(1, scheduler.in_flight(&peer(2)));
+
+        // other interval of the peer stays claimed
+        assert!(!scheduler
+            .schedule(
+                DownloadKind::BlockHeader,
+                peer(2),
+                &other_interval,
+                &block(4)
+            )
+            .is_scheduled());
+    }
+
+    #[test]
+    fn test_abandoned_interval_is_released() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+        let other_interval = Arc::new(block(200));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        scheduler.received(DownloadKind::BlockHeader, peer(1), &block(1));
+
+        // nobody continues with the interval (e.g. it was applied meanwhile)
+        let abandoned_at = Instant::now() - DownloadScheduler::MAX_REQUEST_AGE;
+        scheduler
+            .intervals
+            .values_mut()
+            .for_each(|c| c.last_progress = abandoned_at);
+
+        assert!(scheduler
+            .schedule(
+                DownloadKind::BlockHeader,
+                peer(1),
+                &other_interval,
+                &block(2)
+            )
+            .is_scheduled());
+        assert!(!scheduler
+            .intervals
+            .contains_key(&(DownloadKind::BlockHeader, interval)));
+        assert!(scheduler
+            .intervals
+            .contains_key(&(DownloadKind::BlockHeader, other_interval)));
+    }
+
+    #[test]
+    fn test_peer_disconnected() {
+        let mut scheduler = DownloadScheduler::default();
+        let interval = Arc::new(block(100));
+
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(1), &interval, &block(1))
+            .is_scheduled());
+        assert!(!scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(1))
+            .is_scheduled());
+
+        scheduler.peer_disconnected(&peer(1));
+        assert_eq!(0, scheduler.in_flight(&peer(1)));
+        assert!(scheduler
+            .schedule(DownloadKind::BlockHeader, peer(2), &interval, &block(1))
+            .is_scheduled());
+        assert_eq!(0, scheduler.reassigned());
+    }
+}
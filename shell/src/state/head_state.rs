@@ -9,12 +9,21 @@ use std::sync::{Arc, RwLock};
 use crypto::hash::{BlockHash, ChainId};
 use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::persistent::PersistentStorage;
-use storage::{BlockHeaderWithHash, ChainMetaStorage};
+use storage::{BlockHeaderWithHash, BlockStorage, BlockStorageReader, ChainMetaStorage};
+use tezos_messages::p2p::encoding::block_header::Level;
 use tezos_messages::Head;
 
 use crate::mempool::CurrentMempoolStateStorageRef;
 use crate::state::StateError;
 use crate::validation;
+use crate::validation::fitness_comparator::FitnessWrapper;
+
+/// Max number of remembered alternate heads, heads with the lowest fitness are forgotten
+const MAX_ALTERNATE_HEADS: usize = 64;
+
+/// Common ancestor is searched just this deep (on both branches), so deep branch switch does not block head processing,
+/// deeper reorgs are not resolved
+const MAX_REORG_DEPTH: Level = 120;
 
 /// In-memory synchronized struct for sharing current head between threads/actors
 pub type CurrentHeadRef = Arc<RwLock<Option<Head>>>;
@@ -24,8 +33,20 @@ pub fn init_current_head_state() -> CurrentHeadRef {
     Arc::new(RwLock::new(None))
 }
 
+/// Current head was switched to the other branch
+#[derive(Clone, Debug)]
+pub struct Reorg {
+    pub old_head: Head,
+    pub new_head: Head,
+    /// Last block, which is on both branches
+    pub common_ancestor: Head,
+    /// Number of blocks removed from the old branch
+    pub depth: Level,
+}
+
 pub enum HeadResult {
-    BranchSwitch,
+    /// New head is not a successor of the previous head, reorg is resolved, if the common ancestor was found
+    BranchSwitch(Option<Reorg>),
     HeadIncrement,
     GenesisInitialized,
 }
@@ -33,7 +54,7 @@ pub enum HeadResult {
 impl fmt::Display for HeadResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
-            HeadResult::BranchSwitch => write!(f, "BranchSwitch"),
+            HeadResult::BranchSwitch(_) => write!(f, "BranchSwitch"),
             HeadResult::HeadIncrement => write!(f, "HeadIncrement"),
             HeadResult::GenesisInitialized => write!(f, "GenesisInitialized"),
         }
@@ -43,6 +64,8 @@ impl fmt::Display for HeadResult {
 pub struct HeadState {
     ///persistent chain metadata storage
     chain_meta_storage: ChainMetaStorage,
+    /// Block storage
+    block_storage: BlockStorage,
 
     /// Current head information
     current_head_state: CurrentHeadRef,
@@ -63,6 +86,7 @@ impl HeadState {
     ) -> Self {
         HeadState {
             chain_meta_storage: ChainMetaStorage::new(persistent_storage),
+            block_storage: BlockStorage::new(persistent_storage),
             current_head_state,
             current_mempool_state,
             chain_id,
@@ -99,13 +123,34 @@ impl HeadState {
                 &current_head,
                 &current_context_fitness,
             ) {
-                // just ignore
+                // the same block can be applied more times, but current head is not an alternate head
+                if current_head.block_hash().eq(&potential_new_head.hash) {
+                    return Ok(None);
+                }
+
+                // just ignore, but remember it as alternate head
+                self.update_alternate_heads(
+                    potential_new_head,
+                    Some(Head::new(
+                        potential_new_head.hash.clone(),
+                        potential_new_head.header.level(),
+                        potential_new_head.header.fitness().clone(),
+                    )),
+                )?;
                 return Ok(None);
             }
         }
 
+        // this will be new head
+        let head = Head::new(
+            potential_new_head.hash.clone(),
+            potential_new_head.header.level(),
+            potential_new_head.header.fitness().clone(),
+        );
+
         // we need to check, if previous head is predecessor of new_head (for later use)
-        let head_result = match self.current_head_state.read()?.as_ref() {
+        let previous_head = self.current_head_state.read()?.clone();
+        let head_result = match previous_head.as_ref() {
             Some(previos_head) => {
                 if previos_head
                     .block_hash()
@@ -114,7 +159,15 @@ impl HeadState {
                     HeadResult::HeadIncrement
                 } else {
                     // if previous head is not predecesor of new head, means it could be new branch
-                    HeadResult::BranchSwitch
+                    let reorg = self
+                        .find_common_ancestor(previos_head, potential_new_head)?
+                        .map(|common_ancestor| Reorg {
+                            old_head: previos_head.clone(),
+                            new_head: head.clone(),
+                            depth: previos_head.level() - common_ancestor.level(),
+                            common_ancestor,
+                        });
+                    HeadResult::BranchSwitch(reorg)
                 }
             }
             None => {
@@ -131,12 +184,13 @@ impl HeadState {
             }
         };
 
-        // this will be new head
-        let head = Head::new(
-            potential_new_head.hash.clone(),
-            potential_new_head.header.level(),
-            potential_new_head.header.fitness().clone(),
-        );
+        // previous head is alternate head now, if we left its branch
+        match &head_result {
+            HeadResult::BranchSwitch(_) => {
+                self.update_alternate_heads(potential_new_head, previous_head)?
+            }
+            _ => self.update_alternate_heads(potential_new_head, None)?,
+        }
 
         // set new head to db
         self.chain_meta_storage
@@ -149,6 +203,101 @@ impl HeadState {
         Ok(Some((head, head_result)))
     }
 
+    /// Applied block and its predecessor are not alternate heads anymore,
+    /// `new_alternate_head` is either ignored applied block or the current head, which we switched from.
+    fn update_alternate_heads(
+        &self,
+        applied_block: &BlockHeaderWithHash,
+        new_alternate_head: Option<Head>,
+    ) -> Result<(), StateError> {
+        let mut alternate_heads = self
+            .chain_meta_storage
+            .get_alternate_heads(&self.chain_id)?;
+        let heads_count = alternate_heads.len();
+        alternate_heads.retain(|head| {
+            head.block_hash() != applied_block.header.predecessor()
+                && head.block_hash() != &applied_block.hash
+        });
+        let mut changed = heads_count != alternate_heads.len();
+
+        if let Some(new_alternate_head) = new_alternate_head {
+            if !alternate_heads
+                .iter()
+                .any(|head| head.block_hash() == new_alternate_head.block_hash())
+            {
+                alternate_heads.push(new_alternate_head);
+                changed = true;
+            }
+        }
+
+        if changed {
+            if alternate_heads.len() > MAX_ALTERNATE_HEADS {
+                // forget the weakest heads
+                alternate_heads.sort_by(|h1, h2| {
+                    FitnessWrapper::new(h2.fitness()).cmp(&FitnessWrapper::new(h1.fitness()))
+                });
+                alternate_heads.truncate(MAX_ALTERNATE_HEADS);
+            }
+            self.chain_meta_storage
+                .set_alternate_heads(&self.chain_id, alternate_heads)?;
+        }
+
+        Ok(())
+    }
+
+    /// Walks back both branches to the last block, which is on both of them,
+    /// returns None, if it is not found in [MAX_REORG_DEPTH]
+    fn find_common_ancestor(
+        &self,
+        old_head: &Head,
+        new_head: &BlockHeaderWithHash,
+    ) -> Result<Option<Head>, StateError> {
+        let mut old_block = match self.block_storage.get(old_head.block_hash())? {
+            Some(old_block) => old_block,
+            None => return Ok(None),
+        };
+        let mut new_block = new_head.clone();
+
+        while old_block.hash != new_block.hash {
+            // step back on the higher branch (genesis is its own predecessor, so we stop there)
+            let step_back_old = old_block.header.level() >= new_block.header.level();
+            let block_to_step_back = if step_back_old {
+                &old_block
+            } else {
+                &new_block
+            };
+            if block_to_step_back.header.level() <= 0 {
+                return Ok(None);
+            }
+            let depth = if step_back_old {
+                old_head.level() - old_block.header.level()
+            } else {
+                new_head.header.level() - new_block.header.level()
+            };
+            if depth >= MAX_REORG_DEPTH {
+                return Ok(None);
+            }
+            let predecessor = match self
+                .block_storage
+                .get(block_to_step_back.header.predecessor())?
+            {
+                Some(predecessor) => predecessor,
+                None => return Ok(None),
+            };
+            if step_back_old {
+                old_block = predecessor;
+            } else {
+                new_block = predecessor;
+            }
+        }
+
+        Ok(Some(Head::new(
+            old_block.hash,
+            old_block.header.level(),
+            old_block.header.fitness().clone(),
+        )))
+    }
+
     /// Tries to load last known current head from database
     pub(crate) fn load_current_head_state(&self) -> Result<Option<Head>, StateError> {
         match self.chain_meta_storage.get_current_head(&self.chain_id)? {
@@ -161,3 +310,217 @@ impl HeadState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use storage::tests_common::TmpStorage;
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use crate::mempool::init_mempool_state_storage;
+
+    use super::*;
+
+    fn block(
+        level: Level,
+        predecessor: &BlockHash,
+        fitness: u8,
+        branch: u8,
+    ) -> Result<BlockHeaderWithHash, failure::Error> {
+        Ok(BlockHeaderWithHash::new(
+            BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor.clone())
+                .timestamp(5_635_634 + level as i64)
+                .validation_pass(4)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc".try_into()?,
+                )
+                .fitness(vec![vec![0], vec![fitness]])
+                .context("CoV16kW8WgL51SpcftQKdeqc94D6ekghMgPMmEn7TSZzFA697PeE".try_into()?)
+                .protocol_data(vec![branch])
+                .build()
+                .unwrap(),
+        )?)
+    }
+
+    fn head_state(
+        tmp_storage: &TmpStorage,
+        chain_id: &ChainId,
+        genesis: &BlockHeaderWithHash,
+    ) -> HeadState {
+        HeadState::new(
+            tmp_storage.storage(),
+            init_current_head_state(),
+            init_mempool_state_storage(),
+            Arc::new(chain_id.clone()),
+            Arc::new(genesis.hash.clone()),
+        )
+    }
+
+    /// Stores blocks, which are linked one after another, to the block storage
+    fn store_branch(
+        block_storage: &BlockStorage,
+        from: &BlockHeaderWithHash,
+        count: Level,
+        branch: u8,
+        fitness_bonus: u8,
+    ) -> Result<Vec<BlockHeaderWithHash>, failure::Error> {
+        let mut blocks = Vec::new();
+        let mut predecessor = from.clone();
+        for _ in 0..count {
+            let level = predecessor.header.level() + 1;
+            let next = block(
+                level,
+                &predecessor.hash,
+                level as u8 + fitness_bonus,
+                branch,
+            )?;
+            block_storage.put_block_header(&next)?;
+            blocks.push(next.clone());
+            predecessor = next;
+        }
+        Ok(blocks)
+    }
+
+    fn alternate_heads(
+        tmp_storage: &TmpStorage,
+        chain_id: &ChainId,
+    ) -> Result<Vec<BlockHash>, failure::Error> {
+        Ok(ChainMetaStorage::new(tmp_storage.storage())
+            .get_alternate_heads(chain_id)?
+            .into_iter()
+            .map(|head| head.block_hash().clone())
+            .collect())
+    }
+
+    #[test]
+    fn test_branch_switch_resolves_reorg_and_alternate_heads() -> Result<(), failure::Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_head_state_branch_switch")?;
+        let block_storage = BlockStorage::new(tmp_storage.storage());
+        let chain_id: ChainId = "NetXgtSLGNJvNye".try_into()?;
+
+        /*
+         * Genesis - A1 - A2 - A3
+         *             \
+         *              B2 - B3 - B4
+         */
+        let genesis = block(0, &vec![0; 32].try_into()?, 0, 0)?;
+        block_storage.put_block_header(&genesis)?;
+        let a = store_branch(&block_storage, &genesis, 3, 1, 0)?;
+        let b = store_branch(&block_storage, &a[0], 3, 2, 0)?;
+        let head_state = head_state(&tmp_storage, &chain_id, &genesis);
+
+        assert!(matches!(
+            head_state.try_update_new_current_head(&genesis)?,
+            Some((_, HeadResult::GenesisInitialized))
+        ));
+        for block in &a {
+            assert!(matches!(
+                head_state.try_update_new_current_head(block)?,
+                Some((_, HeadResult::HeadIncrement))
+            ));
+        }
+
+        // lower (or the same) fitness is ignored, but remembered
+        assert!(head_state.try_update_new_current_head(&b[0])?.is_none());
+        assert_eq!(
+            vec![b[0].hash.clone()],
+            alternate_heads(&tmp_storage, &chain_id)?
+        );
+
+        // ignored successor replaces its predecessor
+        assert!(head_state.try_update_new_current_head(&b[1])?.is_none());
+        assert_eq!(
+            vec![b[1].hash.clone()],
+            alternate_heads(&tmp_storage, &chain_id)?
+        );
+
+        // higher fitness switches the branch
+        let reorg = match head_state.try_update_new_current_head(&b[2])? {
+            Some((_, HeadResult::BranchSwitch(Some(reorg)))) => reorg,
+            _ => panic!("Expected resolved branch switch"),
+        };
+        assert_eq!(&a[2].hash, reorg.old_head.block_hash());
+        assert_eq!(&b[2].hash, reorg.new_head.block_hash());
+        assert_eq!(&a[0].hash, reorg.common_ancestor.block_hash());
+        assert_eq!(1, *reorg.common_ancestor.level());
+        assert_eq!(2, reorg.depth);
+
+        // previous head is alternate head now, ignored branch is current one
+        assert_eq!(
+            vec![a[2].hash.clone()],
+            alternate_heads(&tmp_storage, &chain_id)?
+        );
+
+        // already known heads are not listed twice
+        assert!(head_state.try_update_new_current_head(&b[2])?.is_none());
+        assert!(head_state.try_update_new_current_head(&a[2])?.is_none());
+        assert_eq!(
+            vec![a[2].hash.clone()],
+            alternate_heads(&tmp_storage, &chain_id)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alternate_heads_are_truncated() -> Result<(), failure::Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_head_state_alternate_heads")?;
+        let block_storage = BlockStorage::new(tmp_storage.storage());
+        let chain_id: ChainId = "NetXgtSLGNJvNye".try_into()?;
+
+        let genesis = block(0, &vec![0; 32].try_into()?, 0, 0)?;
+        block_storage.put_block_header(&genesis)?;
+        let head_state = head_state(&tmp_storage, &chain_id, &genesis);
+        head_state.try_update_new_current_head(&genesis)?;
+        let current_head = block(1, &genesis.hash, u8::MAX, 0)?;
+        head_state.try_update_new_current_head(&current_head)?;
+
+        // every ignored block is on its own branch
+        let count = MAX_ALTERNATE_HEADS + 10;
+        for i in 1..=count {
+            let ignored = block(1, &genesis.hash, i as u8, i as u8)?;
+            assert!(head_state.try_update_new_current_head(&ignored)?.is_none());
+        }
+
+        // the weakest heads are forgotten
+        let alternate_heads =
+            ChainMetaStorage::new(tmp_storage.storage()).get_alternate_heads(&chain_id)?;
+        assert_eq!(MAX_ALTERNATE_HEADS, alternate_heads.len());
+        let weakest_kept = (count - MAX_ALTERNATE_HEADS + 1) as u8;
+        assert!(alternate_heads
+            .iter()
+            .all(|head| head.fitness()[1][0] >= weakest_kept));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deep_reorg_is_not_resolved() -> Result<(), failure::Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_head_state_deep_reorg")?;
+        let block_storage = BlockStorage::new(tmp_storage.storage());
+        let chain_id: ChainId = "NetXgtSLGNJvNye".try_into()?;
+
+        let genesis = block(0, &vec![0; 32].try_into()?, 0, 0)?;
+        block_storage.put_block_header(&genesis)?;
+        let a = store_branch(&block_storage, &genesis, MAX_REORG_DEPTH + 1, 1, 0)?;
+        let b = store_branch(&block_storage, &genesis, MAX_REORG_DEPTH + 1, 2, 1)?;
+        let head_state = head_state(&tmp_storage, &chain_id, &genesis);
+
+        head_state.try_update_new_current_head(&genesis)?;
+        for block in &a {
+            head_state.try_update_new_current_head(block)?;
+        }
+
+        // common ancestor (genesis) is too deep
+        assert!(matches!(
+            head_state.try_update_new_current_head(b.last().unwrap())?,
+            Some((_, HeadResult::BranchSwitch(None)))
+        ));
+
+        Ok(())
+    }
+}
@@ -13,6 +13,8 @@ use tezos_messages::p2p::encoding::prelude::OperationsForBlock;
 
 pub mod block_state;
 pub mod bootstrap_state;
+pub mod clock_state;
+pub mod download_scheduler;
 pub mod head_state;
 pub mod peer_state;
 pub mod synchronization_state;
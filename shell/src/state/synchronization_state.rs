@@ -1,21 +1,37 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-//! This module covers functionality to resolve if node is bootstrapped
-// TODO: TE-244 - reimplement
+//! This module covers functionality to resolve if node is bootstrapped.
+//!
+//! It is the synchronization heuristic (as in Ocaml node): we remember timestamp of the latest head from every peer
+//! and we take `threshold` most recent of them. If the oldest one is not older than `latency` (and our local head neither),
+//! node is [`SyncState::Synced`]. If they are older, but all the peers agree on the same head (and we have it),
+//! the chain is [`SyncState::Stuck`], otherwise node is [`SyncState::Unsynced`].
+//!
+//! Node is marked as bootstrapped, once it is synced or stuck, and this is never reverted,
+//! so heads from the future (see [`FUTURE_BLOCK_TOLERANCE`]) are not considered at all.
 
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use crypto::hash::CryptoboxPublicKeyHash;
+use serde::{Deserialize, Serialize};
+
+use crypto::hash::{BlockHash, CryptoboxPublicKeyHash};
 use networking::PeerId;
 use tezos_messages::p2p::encoding::block_header::Level;
 
+use crate::state::clock_state::NodeClockRef;
+use crate::validation::FUTURE_BLOCK_TOLERANCE;
+
 /// Type hold information if node is bootstrapped shareable between threads/actors
 /// Indicates that node/shell is bootstrapped, which means, that can broadcast stuff (new branch, new head) to the network
 type BootstrappedStatusRef = Arc<AtomicBool>;
 
+/// Default latency for synchronization heuristic, see [`SynchronizationBootstrapState`]
+pub const DEFAULT_SYNCHRONIZATION_LATENCY: Duration = Duration::from_secs(150);
+
 /// Trait for struct witch has updatable flag [`is_bootstrapped`]
 pub trait UpdateIsBootstrapped {
     fn set_is_bootstrapped(&mut self, new_status: bool);
@@ -37,43 +53,80 @@ impl PeerBranchSynchronizationDone {
         &self.peer
     }
 
-    fn to_level(&self) -> &Arc<Level> {
+    pub fn to_level(&self) -> &Arc<Level> {
         &self.to_level
     }
 }
 
+/// Result of the synchronization heuristic
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncState {
+    /// Peers and we have recent heads
+    Synced,
+    /// Not enough peers or heads are too old
+    Unsynced,
+    /// Peers agree on the same old head, so the chain does not move
+    Stuck,
+}
+
+impl SyncState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncState::Synced => "synced",
+            SyncState::Unsynced => "unsynced",
+            SyncState::Stuck => "stuck",
+        }
+    }
+}
+
+/// Latest head received from the peer
+struct PeerHead {
+    block_hash: BlockHash,
+    timestamp: i64,
+}
+
 pub type SynchronizationBootstrapStateRef = Arc<RwLock<SynchronizationBootstrapState>>;
 
-/// Inits empty mempool state storage
+/// Inits empty synchronization state storage
 pub fn init_synchronization_bootstrap_state_storage(
     num_of_peers_for_bootstrap_threshold: usize,
+    latency: Duration,
+    node_clock: NodeClockRef,
 ) -> SynchronizationBootstrapStateRef {
     Arc::new(RwLock::new(SynchronizationBootstrapState::new(
         num_of_peers_for_bootstrap_threshold,
+        latency,
         BootstrappedStatusRef::new(AtomicBool::new(false)),
+        node_clock,
     )))
 }
 
-/// Manages bootstrap status based on number of bootstrapped peers
+/// Manages bootstrap status based on the synchronization heuristic
 pub struct SynchronizationBootstrapState {
-    /// Indicates threshold for minimal count of bootstrapped peers to mark chain_manager as bootstrapped
+    /// Indicates threshold for minimal count of peers with recent heads to mark chain_manager as bootstrapped
     num_of_peers_for_bootstrap_threshold: usize,
+    /// Head older than latency is not considered as recent
+    latency: Duration,
 
     /// Holds bootstrapped state
     current_bootstrapped_status: BootstrappedStatusRef,
 
-    /// holder of bootstrapped peers with they highest level
-    state: HashMap<CryptoboxPublicKeyHash, Level>,
+    /// Node's "now"
+    node_clock: NodeClockRef,
+
+    /// Timestamp of our local current head
+    local_head_timestamp: Option<i64>,
+    /// Holder of the latest heads of peers
+    peer_heads: HashMap<CryptoboxPublicKeyHash, PeerHead>,
 }
 
 impl SynchronizationBootstrapState {
-    /// This constant is used for solving, if something is bootstrapped
-    /// This means, that the compared level, should be on at least 96%
-    const HIGH_LEVEL_MARGIN_PERCENTAGE: i32 = 98;
-
     pub fn new(
         num_of_peers_for_bootstrap_threshold: usize,
+        latency: Duration,
         current_bootstrapped_status: BootstrappedStatusRef,
+        node_clock: NodeClockRef,
     ) -> Self {
         // if no limit, just mark as bootstrapped
         if num_of_peers_for_bootstrap_threshold == 0 {
@@ -82,8 +135,11 @@ impl SynchronizationBootstrapState {
 
         Self {
             num_of_peers_for_bootstrap_threshold,
+            latency,
             current_bootstrapped_status,
-            state: HashMap::default(),
+            node_clock,
+            local_head_timestamp: None,
+            peer_heads: HashMap::default(),
         }
     }
 
@@ -91,133 +147,127 @@ impl SynchronizationBootstrapState {
         self.current_bootstrapped_status.load(Ordering::Acquire)
     }
 
-    fn consider_as_bootstrapped(
-        tested_level: Level,
-        target_level: Level,
-        percentage_margin: i32,
-    ) -> bool {
-        // only tested level is higher/equals percentage of target_level then HIGH_LEVEL_MARGIN_PERCENTAGE
-        if let Some(calculated_percentage) = tested_level
-            .checked_mul(100)
-            .map(|p| p.checked_div(target_level).unwrap_or(0))
-        {
-            if calculated_percentage >= percentage_margin {
-                return true;
-            }
-        }
-
-        false
+    /// Resolves synchronization state against node's "now"
+    pub fn sync_state(&self) -> SyncState {
+        self.sync_state_at(self.node_clock.now().timestamp())
     }
 
-    pub(crate) fn update_by_peer_state(
-        &mut self,
-        new_update: &PeerBranchSynchronizationDone,
-        target: &mut impl UpdateIsBootstrapped,
-        remote_best_known_level: Level,
-        local_best_known_level: Level,
-    ) {
-        // if already bootstrapped, do nothing
-        if self.is_bootstrapped() {
-            return;
+    fn sync_state_at(&self, now: i64) -> SyncState {
+        // if no limit, we are always synced
+        if self.num_of_peers_for_bootstrap_threshold == 0 {
+            return SyncState::Synced;
         }
 
-        // peer key for hashmap
-        let peer_key = &new_update.peer.peer_public_key_hash;
-
-        // lets update peer by public key hash
-        if let Some(peer_to_level) = self.state.get_mut(peer_key) {
-            let update_to_level = new_update.to_level().as_ref();
-            if *peer_to_level < *update_to_level {
-                *peer_to_level = *update_to_level;
-                target.set_is_bootstrapped(Self::consider_as_bootstrapped(
-                    *update_to_level,
-                    remote_best_known_level,
-                    Self::HIGH_LEVEL_MARGIN_PERCENTAGE,
-                ));
-            } else {
-                target.set_is_bootstrapped(Self::consider_as_bootstrapped(
-                    *peer_to_level,
-                    remote_best_known_level,
-                    Self::HIGH_LEVEL_MARGIN_PERCENTAGE,
-                ));
-            }
-        } else {
-            let update_to_level = new_update.to_level().as_ref();
-            self.state.insert(peer_key.clone(), *update_to_level);
-            target.set_is_bootstrapped(Self::consider_as_bootstrapped(
-                *update_to_level,
-                remote_best_known_level,
-                Self::HIGH_LEVEL_MARGIN_PERCENTAGE,
-            ));
+        // take the most recent peer heads
+        let mut candidates: Vec<&PeerHead> = self.peer_heads.values().collect();
+        if candidates.len() < self.num_of_peers_for_bootstrap_threshold {
+            return SyncState::Unsynced;
+        }
+        candidates.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        candidates.truncate(self.num_of_peers_for_bootstrap_threshold);
+
+        let local_head_timestamp = match self.local_head_timestamp {
+            Some(timestamp) => timestamp,
+            None => return SyncState::Unsynced,
         };
 
-        let _ = self.update_by_new_local_head(remote_best_known_level, local_best_known_level);
+        // the least recent of the most recent ones
+        let least_recent = match candidates.last() {
+            Some(least_recent) => least_recent,
+            None => return SyncState::Unsynced,
+        };
+        let recent_limit = now - self.latency.as_secs() as i64;
+
+        if least_recent.timestamp >= recent_limit && local_head_timestamp >= recent_limit {
+            SyncState::Synced
+        } else if least_recent.timestamp < recent_limit
+            && local_head_timestamp >= least_recent.timestamp
+            && candidates
+                .iter()
+                .all(|candidate| candidate.block_hash == least_recent.block_hash)
+        {
+            SyncState::Stuck
+        } else {
+            SyncState::Unsynced
+        }
     }
 
-    pub(crate) fn update_by_new_local_head(
+    /// Marks as bootstrapped, if synced or stuck, returns bootstrapped status
+    fn resolve_is_bootstrapped(&mut self) -> bool {
+        if !self.is_bootstrapped() && self.sync_state() != SyncState::Unsynced {
+            self.current_bootstrapped_status
+                .store(true, Ordering::Release);
+        }
+        self.is_bootstrapped()
+    }
+
+    /// Remembers the head received from the peer (only if newer than the last one and not from the future), returns bootstrapped status
+    pub(crate) fn update_by_peer_head(
         &mut self,
-        remote_best_known_level: Level,
-        local_best_known_level: Level,
+        peer: &CryptoboxPublicKeyHash,
+        block_hash: BlockHash,
+        timestamp: i64,
     ) -> bool {
-        // let resolve number of bootstrapped peer matching
-        let num_of_bootstrapped_peers = self.num_of_bootstrapped_peers(remote_best_known_level);
-
-        // global bootstrap status is considered as bootstrapped, only if number of bootstrapped peers is under threshold,
-        // and only if local level is considered as bootstrapped agains remove level
-        // so we can mark chain_manager as bootstrapped
-        if self.num_of_peers_for_bootstrap_threshold <= num_of_bootstrapped_peers {
-            if Self::consider_as_bootstrapped(
-                local_best_known_level,
-                remote_best_known_level,
-                Self::HIGH_LEVEL_MARGIN_PERCENTAGE,
-            ) {
-                self.current_bootstrapped_status
-                    .store(true, Ordering::Release);
-                self.state.clear();
+        // such head would stay as the latest head of the peer forever
+        if timestamp > self.node_clock.now().timestamp() + FUTURE_BLOCK_TOLERANCE.as_secs() as i64 {
+            return self.is_bootstrapped();
+        }
+
+        match self.peer_heads.get_mut(peer) {
+            Some(peer_head) => {
+                if peer_head.timestamp < timestamp {
+                    peer_head.block_hash = block_hash;
+                    peer_head.timestamp = timestamp;
+                }
+            }
+            None => {
+                self.peer_heads.insert(
+                    peer.clone(),
+                    PeerHead {
+                        block_hash,
+                        timestamp,
+                    },
+                );
             }
         }
 
-        self.is_bootstrapped()
+        self.resolve_is_bootstrapped()
     }
 
-    pub fn num_of_bootstrapped_peers(&self, best_known_level: Level) -> usize {
-        self.state
-            .values()
-            .filter(|&peer_to_level| {
-                Self::consider_as_bootstrapped(
-                    *peer_to_level,
-                    best_known_level,
-                    Self::HIGH_LEVEL_MARGIN_PERCENTAGE,
-                )
-            })
-            .count()
+    /// Remembers timestamp of our new local head, returns bootstrapped status
+    pub(crate) fn update_by_new_local_head(&mut self, timestamp: i64) -> bool {
+        self.local_head_timestamp = Some(timestamp);
+        self.resolve_is_bootstrapped()
+    }
+
+    /// Forgets the head of disconnected peer
+    pub(crate) fn remove_peer(&mut self, peer: &CryptoboxPublicKeyHash) {
+        self.peer_heads.remove(peer);
     }
 
     pub fn num_of_peers_for_bootstrap_threshold(&self) -> usize {
         self.num_of_peers_for_bootstrap_threshold
     }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Changes threshold at runtime, already bootstrapped state is not reverted
+    pub fn set_num_of_peers_for_bootstrap_threshold(
+        &mut self,
+        num_of_peers_for_bootstrap_threshold: usize,
+    ) {
+        self.num_of_peers_for_bootstrap_threshold = num_of_peers_for_bootstrap_threshold;
+        let _ = self.resolve_is_bootstrapped();
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use std::net::SocketAddr;
-    use std::sync::atomic::AtomicBool;
-    use std::sync::Arc;
-    use std::time::Duration;
+    use std::convert::TryFrom;
 
-    use futures::lock::Mutex as TokioMutex;
-    use riker::actors::*;
-    use slog::{Drain, Level, Logger};
-
-    use crypto::hash::CryptoboxPublicKeyHash;
-    use networking::p2p::network_channel::NetworkChannelRef;
-    use networking::p2p::peer::Peer;
-    use networking::p2p::{network_channel::NetworkChannel, peer::BootstrapOutput};
-    use networking::PeerId;
-    use tezos_identity::Identity;
-    use tezos_messages::p2p::encoding::prelude::{MetadataMessage, NetworkVersion};
-
-    use crate::state::peer_state::PeerState;
+    use crate::state::clock_state::init_node_clock;
 
     use super::*;
 
@@ -225,215 +275,210 @@ pub mod tests {
     fn test_resolve_is_bootstrapped_no_threshold() {
         // prepare empty states
         let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
-        let bootstrap_state = SynchronizationBootstrapState::new(0, bootstrap_status);
+        let bootstrap_state = SynchronizationBootstrapState::new(
+            0,
+            DEFAULT_SYNCHRONIZATION_LATENCY,
+            bootstrap_status,
+            init_node_clock(),
+        );
 
         // check
         assert!(bootstrap_state.is_bootstrapped());
+        assert_eq!(SyncState::Synced, bootstrap_state.sync_state());
     }
 
     #[test]
-    fn test_resolve_is_bootstrapped() {
-        // prerequizities
-        let log = create_logger(Level::Debug);
-        let tokio_runtime = create_tokio_runtime();
-        let actor_system = SystemBuilder::new()
-            .name("test_actors_apply_blocks_and_check_context")
-            .log(log.clone())
-            .create()
-            .expect("Failed to create actor system");
-        let network_channel =
-            NetworkChannel::actor(&actor_system).expect("Failed to create network channel");
-        let mut peer_state1 = peer(&actor_system, network_channel.clone(), &tokio_runtime);
-        let mut peer_state2 = peer(&actor_system, network_channel, &tokio_runtime);
-
-        let done_peer = |to_level, peer_state: &PeerState| -> PeerBranchSynchronizationDone {
-            PeerBranchSynchronizationDone::new(peer_state.peer_id.clone(), Arc::new(to_level))
-        };
+    fn test_resolve_is_bootstrapped() -> Result<(), failure::Error> {
+        let node_clock = init_node_clock();
+        node_clock.set_now(10_000);
 
-        // prepare empty states with threshold = 2
+        // prepare empty states with threshold = 2 and latency = 100s
         let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
-        let mut bootstrap_state = SynchronizationBootstrapState::new(2, bootstrap_status);
+        let mut bootstrap_state = SynchronizationBootstrapState::new(
+            2,
+            Duration::from_secs(100),
+            bootstrap_status,
+            node_clock.clone(),
+        );
+        let (peer1, peer2) = (peer_key(1)?, peer_key(2)?);
 
         // check
         assert!(!bootstrap_state.is_bootstrapped());
-        assert_eq!(0, bootstrap_state.num_of_bootstrapped_peers(100));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state());
 
-        // update with low level
-        bootstrap_state.update_by_peer_state(
-            &done_peer(10, &peer_state1),
-            &mut peer_state1,
-            100,
-            0,
-        );
-        bootstrap_state.update_by_peer_state(
-            &done_peer(15, &peer_state2),
-            &mut peer_state2,
-            100,
-            0,
-        );
-        assert_eq!(0, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(!peer_state1.is_bootstrapped());
-        assert!(!peer_state2.is_bootstrapped());
-        assert!(!bootstrap_state.is_bootstrapped());
+        // local head is recent, but not enough peers
+        assert!(!bootstrap_state.update_by_new_local_head(10_000));
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(1)?, 10_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state());
 
-        // update with one with higher level
-        bootstrap_state.update_by_peer_state(
-            &done_peer(98, &peer_state1),
-            &mut peer_state1,
-            100,
-            0,
-        );
-        bootstrap_state.update_by_peer_state(
-            &done_peer(15, &peer_state2),
-            &mut peer_state2,
-            100,
-            0,
-        );
-        assert_eq!(1, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(peer_state1.is_bootstrapped());
-        assert!(!peer_state2.is_bootstrapped());
-        assert!(!bootstrap_state.is_bootstrapped());
+        // second peer has too old head
+        assert!(!bootstrap_state.update_by_peer_head(&peer2, block_hash(2)?, 9_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state());
 
-        // update the same one with one more higher level
-        bootstrap_state.update_by_peer_state(
-            &done_peer(99, &peer_state1),
-            &mut peer_state1,
-            100,
-            0,
+        // older head does not replace the newer one
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(3)?, 8_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state());
+
+        // second peer has recent head now (within latency)
+        assert!(bootstrap_state.update_by_peer_head(&peer2, block_hash(4)?, 9_950));
+        assert_eq!(SyncState::Synced, bootstrap_state.sync_state());
+        assert!(bootstrap_state.is_bootstrapped());
+
+        // time goes on, heads are old, but bootstrapped is not reverted
+        node_clock.set_now(20_000);
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state());
+        assert!(bootstrap_state.is_bootstrapped());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_sync_state_local_head_behind() -> Result<(), failure::Error> {
+        let node_clock = init_node_clock();
+        node_clock.set_now(10_000);
+
+        let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
+        let mut bootstrap_state = SynchronizationBootstrapState::new(
+            2,
+            Duration::from_secs(100),
+            bootstrap_status,
+            node_clock,
         );
-        bootstrap_state.update_by_peer_state(
-            &done_peer(15, &peer_state2),
-            &mut peer_state2,
-            100,
-            0,
+
+        // peers are recent, but we do not have local head
+        assert!(!bootstrap_state.update_by_peer_head(&peer_key(1)?, block_hash(1)?, 10_000));
+        assert!(!bootstrap_state.update_by_peer_head(&peer_key(2)?, block_hash(2)?, 10_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state_at(10_000));
+
+        // peers are recent, but we are behind
+        assert!(!bootstrap_state.update_by_new_local_head(5_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state_at(10_000));
+
+        // we catched up
+        assert!(bootstrap_state.update_by_new_local_head(9_990));
+        assert_eq!(SyncState::Synced, bootstrap_state.sync_state_at(10_000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_sync_state_stuck() -> Result<(), failure::Error> {
+        let node_clock = init_node_clock();
+        node_clock.set_now(10_000);
+
+        let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
+        let mut bootstrap_state = SynchronizationBootstrapState::new(
+            2,
+            Duration::from_secs(100),
+            bootstrap_status,
+            node_clock,
         );
-        assert_eq!(1, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(peer_state1.is_bootstrapped());
-        assert!(!peer_state2.is_bootstrapped());
-        assert!(!bootstrap_state.is_bootstrapped());
+        let (peer1, peer2, peer3) = (peer_key(1)?, peer_key(2)?, peer_key(3)?);
+
+        // all peers are on the same old head, but we are behind
+        assert!(!bootstrap_state.update_by_new_local_head(4_000));
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(1)?, 5_000));
+        assert!(!bootstrap_state.update_by_peer_head(&peer2, block_hash(1)?, 5_000));
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state_at(10_000));
+
+        // peers disagree
+        assert!(!bootstrap_state.update_by_peer_head(&peer3, block_hash(2)?, 4_500));
+        bootstrap_state.remove_peer(&peer2);
+        assert_eq!(SyncState::Unsynced, bootstrap_state.sync_state_at(10_000));
+
+        // peers agree again and we have their head
+        assert!(!bootstrap_state.update_by_peer_head(&peer2, block_hash(1)?, 5_000));
+        assert!(bootstrap_state.update_by_new_local_head(5_000));
+        assert_eq!(SyncState::Stuck, bootstrap_state.sync_state_at(10_000));
+        assert!(bootstrap_state.is_bootstrapped());
+
+        // stuck only when old
+        assert_eq!(SyncState::Synced, bootstrap_state.sync_state_at(5_050));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_peer_head_from_future_is_ignored() -> Result<(), failure::Error> {
+        let node_clock = init_node_clock();
+        node_clock.set_now(10_000);
 
-        // update the other with higher (threshold is ok, but not local level)
-        bootstrap_state.update_by_peer_state(
-            &done_peer(99, &peer_state1),
-            &mut peer_state1,
-            100,
-            97,
+        let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
+        let mut bootstrap_state = SynchronizationBootstrapState::new(
+            1,
+            Duration::from_secs(100),
+            bootstrap_status,
+            node_clock.clone(),
         );
-        bootstrap_state.update_by_peer_state(
-            &done_peer(98, &peer_state2),
-            &mut peer_state2,
-            100,
-            97,
+        let peer1 = peer_key(1)?;
+        assert!(!bootstrap_state.update_by_new_local_head(5_000));
+
+        // head from the future is ignored
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(1)?, 1_000_000));
+        assert!(bootstrap_state.peer_heads.is_empty());
+
+        // head within tolerance is accepted
+        let within_tolerance = 10_000 + FUTURE_BLOCK_TOLERANCE.as_secs() as i64;
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(2)?, within_tolerance));
+        assert_eq!(
+            Some(within_tolerance),
+            bootstrap_state
+                .peer_heads
+                .get(&peer1)
+                .map(|head| head.timestamp)
         );
-        assert_eq!(2, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(peer_state1.is_bootstrapped());
-        assert!(peer_state2.is_bootstrapped());
-        assert!(!bootstrap_state.is_bootstrapped());
 
-        // update by new local head
-        assert!(!bootstrap_state.update_by_new_local_head(100, 97));
-        assert_eq!(2, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(peer_state1.is_bootstrapped());
-        assert!(peer_state2.is_bootstrapped());
-        assert!(!bootstrap_state.is_bootstrapped());
+        // head from the future does not replace the last head of the peer
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(3)?, 1_000_000));
+        assert_eq!(
+            Some(within_tolerance),
+            bootstrap_state
+                .peer_heads
+                .get(&peer1)
+                .map(|head| head.timestamp)
+        );
 
-        assert!(bootstrap_state.update_by_new_local_head(100, 98));
-        assert_eq!(0, bootstrap_state.num_of_bootstrapped_peers(100));
-        assert!(peer_state1.is_bootstrapped());
-        assert!(peer_state2.is_bootstrapped());
-        assert!(bootstrap_state.is_bootstrapped());
+        // honest peer head is replaced by newer one later
+        node_clock.set_now(20_000);
+        assert!(!bootstrap_state.update_by_peer_head(&peer1, block_hash(4)?, 19_990));
+        assert_eq!(
+            Some(19_990),
+            bootstrap_state
+                .peer_heads
+                .get(&peer1)
+                .map(|head| head.timestamp)
+        );
 
-        // shutdown actor system
-        let _ = tokio_runtime.block_on(async move {
-            tokio::time::timeout(Duration::from_secs(2), actor_system.shutdown()).await
-        });
+        Ok(())
     }
 
     #[test]
-    fn test_consider_as_bootstrapped() {
-        assert!(SynchronizationBootstrapState::consider_as_bootstrapped(
-            0, 0, 0,
-        ));
-        assert!(!SynchronizationBootstrapState::consider_as_bootstrapped(
-            0, 1, 1,
-        ));
-        assert!(!SynchronizationBootstrapState::consider_as_bootstrapped(
-            1, 0, 1,
-        ));
-        assert!(!SynchronizationBootstrapState::consider_as_bootstrapped(
-            97, 100, 98,
-        ));
-        assert!(SynchronizationBootstrapState::consider_as_bootstrapped(
-            98, 100, 98,
-        ));
-        assert!(SynchronizationBootstrapState::consider_as_bootstrapped(
-            99, 100, 98,
-        ));
-        assert!(SynchronizationBootstrapState::consider_as_bootstrapped(
-            100, 100, 98,
-        ));
-    }
+    fn test_set_num_of_peers_for_bootstrap_threshold() -> Result<(), failure::Error> {
+        let node_clock = init_node_clock();
+        node_clock.set_now(10_000);
+
+        let bootstrap_status = BootstrappedStatusRef::new(AtomicBool::new(false));
+        let mut bootstrap_state = SynchronizationBootstrapState::new(
+            2,
+            Duration::from_secs(100),
+            bootstrap_status,
+            node_clock,
+        );
+        assert!(!bootstrap_state.update_by_new_local_head(10_000));
+        assert!(!bootstrap_state.update_by_peer_head(&peer_key(1)?, block_hash(1)?, 10_000));
+
+        // lower threshold is enough
+        bootstrap_state.set_num_of_peers_for_bootstrap_threshold(1);
+        assert!(bootstrap_state.is_bootstrapped());
 
-    fn create_logger(level: Level) -> Logger {
-        let drain = slog_async::Async::new(
-            slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
-                .build()
-                .fuse(),
-        )
-        .build()
-        .filter_level(level)
-        .fuse();
-
-        Logger::root(drain, slog::o!())
+        Ok(())
     }
 
-    fn create_tokio_runtime() -> tokio::runtime::Runtime {
-        tokio::runtime::Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .expect("Failed to create tokio runtime")
+    fn peer_key(id: u8) -> Result<CryptoboxPublicKeyHash, failure::Error> {
+        Ok(CryptoboxPublicKeyHash::try_from(vec![id; 16])?)
     }
 
-    fn peer(
-        sys: &impl ActorRefFactory,
-        network_channel: NetworkChannelRef,
-        tokio_runtime: &tokio::runtime::Runtime,
-    ) -> PeerState {
-        let socket_address: SocketAddr = "127.0.0.1:3011"
-            .parse()
-            .expect("Expected valid ip:port address");
-
-        let node_identity = Arc::new(Identity::generate(0f64));
-        let peer_public_key_hash: CryptoboxPublicKeyHash =
-            node_identity.public_key.public_key_hash();
-        let peer_id_marker = peer_public_key_hash.to_base58_check();
-
-        let metadata = MetadataMessage::new(false, false);
-        let version = NetworkVersion::new("".to_owned(), 0, 0);
-        let peer_ref = Peer::actor(
-            sys,
-            network_channel,
-            tokio_runtime.handle().clone(),
-            BootstrapOutput(
-                Arc::new(TokioMutex::new(None)),
-                Arc::new(TokioMutex::new(None)),
-                peer_public_key_hash.clone(),
-                peer_id_marker.clone(),
-                metadata.clone(),
-                version,
-                socket_address,
-            ),
-        )
-        .unwrap();
-
-        PeerState::new(
-            Arc::new(PeerId::new(
-                peer_ref,
-                peer_public_key_hash,
-                peer_id_marker,
-                socket_address,
-            )),
-            &metadata,
-        )
+    fn block_hash(id: u8) -> Result<BlockHash, failure::Error> {
+        Ok(BlockHash::try_from(vec![id; 32])?)
     }
 }
@@ -9,6 +9,8 @@ use getset::Getters;
 
 use tezos_messages::p2p::encoding::block_header::Level;
 
+use crate::stats::metrics::{DurationHistogram, BLOCK_APPLICATION_BUCKETS};
+
 /// Shareabale type for stats
 pub type ApplyBlockStatsRef = Arc<RwLock<ApplyBlockStats>>;
 
@@ -21,7 +23,7 @@ pub fn init_empty_apply_block_stats() -> ApplyBlockStatsRef {
 #[derive(Getters)]
 pub struct ApplyBlockStats {
     /// ID of the last applied block
-    #[get = "pub(crate)"]
+    #[get = "pub"]
     applied_block_level: Option<Level>,
     /// Last time a block was applied
     #[get = "pub(crate)"]
@@ -36,6 +38,13 @@ pub struct ApplyBlockStats {
     /// Sum of durations of roundtrip: time of fired event for validation to received response
     #[get = "pub(crate)"]
     applied_block_lasts_sum_roundtrip_timer: Duration,
+
+    /// Count of all applied blocks from node start (never cleared)
+    #[get = "pub"]
+    applied_block_total_count: u64,
+    /// Histograms of block application durations from node start (never cleared)
+    #[get = "pub"]
+    applied_block_histograms: BlockApplicationHistograms,
 }
 
 impl Default for ApplyBlockStats {
@@ -46,6 +55,8 @@ impl Default for ApplyBlockStats {
             applied_block_lasts_count: 0,
             applied_block_lasts_sum_validation_timer: BlockValidationTimer::default(),
             applied_block_lasts_sum_roundtrip_timer: Duration::new(0, 0),
+            applied_block_total_count: 0,
+            applied_block_histograms: BlockApplicationHistograms::default(),
         }
     }
 }
@@ -62,12 +73,18 @@ impl ApplyBlockStats {
         roundtrip_timer: Arc<Instant>,
         validation_timer: Arc<BlockValidationTimer>,
     ) {
+        let roundtrip = roundtrip_timer.elapsed();
+
+        self.applied_block_total_count += 1;
+        self.applied_block_histograms
+            .observe(roundtrip, &validation_timer);
+
         self.applied_block_lasts_count += 1;
         self.applied_block_lasts_sum_validation_timer
             .add_assign(validation_timer);
         self.applied_block_lasts_sum_roundtrip_timer = match self
             .applied_block_lasts_sum_roundtrip_timer
-            .checked_add(roundtrip_timer.elapsed())
+            .checked_add(roundtrip)
         {
             Some(result) => result,
             None => self.applied_block_lasts_sum_roundtrip_timer,
@@ -80,6 +97,53 @@ impl ApplyBlockStats {
     }
 }
 
+/// Histograms of durations of the block application phases
+#[derive(Clone, Debug)]
+pub struct BlockApplicationHistograms {
+    roundtrip: DurationHistogram,
+    validation: DurationHistogram,
+    load_metadata: DurationHistogram,
+    protocol_call: DurationHistogram,
+    context_wait: DurationHistogram,
+    store_result: DurationHistogram,
+}
+
+impl Default for BlockApplicationHistograms {
+    fn default() -> Self {
+        Self {
+            roundtrip: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+            validation: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+            load_metadata: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+            protocol_call: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+            context_wait: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+            store_result: DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS),
+        }
+    }
+}
+
+impl BlockApplicationHistograms {
+    fn observe(&mut self, roundtrip: Duration, timer: &BlockValidationTimer) {
+        self.roundtrip.observe(roundtrip);
+        self.validation.observe(timer.validated_at);
+        self.load_metadata.observe(timer.load_metadata_elapsed);
+        self.protocol_call.observe(timer.protocol_call_elapsed);
+        self.context_wait.observe(timer.context_wait_elapsed);
+        self.store_result.observe(timer.store_result_elapsed);
+    }
+
+    /// Returns histograms labeled by the phase name
+    pub fn phases(&self) -> [(&'static str, &DurationHistogram); 6] {
+        [
+            ("roundtrip", &self.roundtrip),
+            ("validation", &self.validation),
+            ("load_metadata", &self.load_metadata),
+            ("protocol_call", &self.protocol_call),
+            ("context_wait", &self.context_wait),
+            ("store_result", &self.store_result),
+        ]
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BlockValidationTimer {
     validated_at: Duration,
@@ -0,0 +1,298 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Tracing of the block lifecycle across shell actors.
+//!
+//! Every block gets a correlation id (`trace_id`) with the first recorded span, and all the following spans of the block
+//! (from any actor/thread) are correlated with it, so the whole timeline of the block can be reconstructed
+//! (e.g. by rpc `/dev/blocks/:block_hash/timeline`). Spans can be also exported to the JSON-lines trace file.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use slog::{warn, Logger};
+
+use crypto::hash::BlockHash;
+
+/// Default count of the last traced blocks, which timelines are held in memory
+pub const BLOCK_TIMELINES_CAPACITY: usize = 4096;
+
+/// Shareable tracer of the block lifecycle
+pub type BlockLifecycleTracerRef = Arc<BlockLifecycleTracer>;
+
+/// Inits tracer, if `trace_file` is set, then all spans are appended also to this file (as JSON lines)
+pub fn init_block_lifecycle_tracer(
+    capacity: usize,
+    trace_file: Option<&Path>,
+    log: Logger,
+) -> Result<BlockLifecycleTracerRef, io::Error> {
+    let exporter = match trace_file {
+        Some(trace_file) => Some(Mutex::new(LineWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(trace_file)?,
+        ))),
+        None => None,
+    };
+
+    Ok(Arc::new(BlockLifecycleTracer {
+        capacity,
+        state: Mutex::new(TracerState::default()),
+        exporter,
+        log,
+    }))
+}
+
+/// Stages of the block lifecycle, from the header received to the new current head
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockLifecycleStage {
+    HeaderReceived,
+    OperationsRequested,
+    OperationsReceived,
+    QueuedForApply,
+    ApplyBlock,
+    ContextCommit,
+    HeadUpdate,
+}
+
+/// One timed stage of the block lifecycle
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockSpan {
+    pub trace_id: u64,
+    pub block_hash: String,
+    pub stage: BlockLifecycleStage,
+    /// Start of the span as unix timestamp in microseconds
+    pub start_micros: u64,
+    pub duration_micros: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// Span of the timeline relative to the first span of the block
+#[derive(Clone, Debug, Serialize)]
+pub struct TimelineSpan {
+    pub stage: BlockLifecycleStage,
+    /// Microseconds from the start of the first span of the block
+    pub offset_micros: u64,
+    pub duration_micros: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+/// Reconstructed timeline of the block
+#[derive(Clone, Debug, Serialize)]
+pub struct BlockTimeline {
+    pub trace_id: u64,
+    pub block_hash: String,
+    /// Start of the first span as unix timestamp in microseconds
+    pub start_micros: u64,
+    /// Microseconds from the start of the first span to the end of the last span
+    pub total_duration_micros: u64,
+    pub spans: Vec<TimelineSpan>,
+}
+
+#[derive(Default)]
+struct TracerState {
+    next_trace_id: u64,
+    timelines: HashMap<BlockHash, (u64, Vec<BlockSpan>)>,
+    /// Traced blocks in order of the first span, used to evict the oldest timelines
+    order: VecDeque<BlockHash>,
+}
+
+/// Collects spans of the block lifecycle, holds timelines of the last `capacity` blocks
+pub struct BlockLifecycleTracer {
+    capacity: usize,
+    state: Mutex<TracerState>,
+    exporter: Option<Mutex<LineWriter<File>>>,
+    log: Logger,
+}
+
+impl BlockLifecycleTracer {
+    /// Records instant stage (zero duration), which happened just now
+    pub fn event(
+        &self,
+        block_hash: &BlockHash,
+        stage: BlockLifecycleStage,
+        details: Option<String>,
+    ) {
+        self.record_elapsed(block_hash, stage, Duration::from_secs(0), details)
+    }
+
+    /// Records stage, which just finished and took `elapsed` time
+    pub fn record_elapsed(
+        &self,
+        block_hash: &BlockHash,
+        stage: BlockLifecycleStage,
+        elapsed: Duration,
+        details: Option<String>,
+    ) {
+        let now = SystemTime::now();
+        let start = now.checked_sub(elapsed).unwrap_or(now);
+        self.record(block_hash, stage, start, elapsed, details)
+    }
+
+    fn record(
+        &self,
+        block_hash: &BlockHash,
+        stage: BlockLifecycleStage,
+        start: SystemTime,
+        duration: Duration,
+        details: Option<String>,
+    ) {
+        let span = {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            let TracerState {
+                next_trace_id,
+                timelines,
+                order,
+            } = &mut *state;
+
+            if !timelines.contains_key(block_hash) {
+                *next_trace_id += 1;
+                timelines.insert(block_hash.clone(), (*next_trace_id, Vec::new()));
+                order.push_back(block_hash.clone());
+                while order.len() > self.capacity {
+                    if let Some(oldest) = order.pop_front() {
+                        timelines.remove(&oldest);
+                    }
+                }
+            }
+
+            match timelines.get_mut(block_hash) {
+                Some((trace_id, spans)) => {
+                    let span = BlockSpan {
+                        trace_id: *trace_id,
+                        block_hash: block_hash.to_base58_check(),
+                        stage,
+                        start_micros: unix_micros(start),
+                        duration_micros: duration.as_micros() as u64,
+                        details,
+                    };
+                    spans.push(span.clone());
+                    span
+                }
+                // capacity is zero, nothing is held
+                None => return,
+            }
+        };
+
+        if let Some(exporter) = self.exporter.as_ref() {
+            if let Err(e) = export(exporter, &span) {
+                warn!(self.log, "Failed to export block lifecycle span"; "block_hash" => &span.block_hash, "reason" => format!("{}", e));
+            }
+        }
+    }
+
+    /// Reconstructs timeline of the block, if the block is still held
+    pub fn timeline(&self, block_hash: &BlockHash) -> Option<BlockTimeline> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (trace_id, spans) = state.timelines.get(block_hash)?;
+
+        let mut spans = spans.clone();
+        spans.sort_by_key(|span| span.start_micros);
+
+        let start_micros = spans.first().map(|span| span.start_micros).unwrap_or(0);
+        let end_micros = spans
+            .iter()
+            .map(|span| span.start_micros + span.duration_micros)
+            .max()
+            .unwrap_or(start_micros);
+
+        Some(BlockTimeline {
+            trace_id: *trace_id,
+            block_hash: block_hash.to_base58_check(),
+            start_micros,
+            total_duration_micros: end_micros - start_micros,
+            spans: spans
+                .into_iter()
+                .map(|span| TimelineSpan {
+                    stage: span.stage,
+                    offset_micros: span.start_micros - start_micros,
+                    duration_micros: span.duration_micros,
+                    details: span.details,
+                })
+                .collect(),
+        })
+    }
+}
+
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+fn export(exporter: &Mutex<LineWriter<File>>, span: &BlockSpan) -> Result<(), io::Error> {
+    let line = serde_json::to_string(span)?;
+    let mut exporter = exporter.lock().unwrap_or_else(PoisonError::into_inner);
+    writeln!(exporter, "{}", line)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+
+    use slog::{o, Discard};
+
+    use super::*;
+
+    #[test]
+    fn test_block_timeline() -> Result<(), failure::Error> {
+        let trace_file = env::temp_dir().join("__block_lifecycle_tracer_test.jsonl");
+        let _ = fs::remove_file(&trace_file);
+
+        let tracer =
+            init_block_lifecycle_tracer(1, Some(&trace_file), Logger::root(Discard, o!()))?;
+        let block_1 = BlockHash::try_from("BKyQ9EofHrgaZKENioHyP4FZNsTmiSEcVmcghgzCC9cGhE7oCET")?;
+        let block_2 = BlockHash::try_from("BLaf78njreWdt2WigJjM9e3ecEdVKm5ehahUfYBKvcWvZ8vfTcJ")?;
+
+        tracer.event(
+            &block_1,
+            BlockLifecycleStage::HeaderReceived,
+            Some("127.0.0.1:9732".to_string()),
+        );
+        tracer.record_elapsed(
+            &block_1,
+            BlockLifecycleStage::ApplyBlock,
+            Duration::from_millis(5),
+            None,
+        );
+
+        let timeline = tracer.timeline(&block_1).expect("Timeline should exist");
+        assert_eq!(1, timeline.trace_id);
+        assert_eq!(2, timeline.spans.len());
+        assert!(timeline
+            .spans
+            .iter()
+            .any(|span| span.stage == BlockLifecycleStage::ApplyBlock
+                && span.duration_micros == 5000));
+        assert!(timeline.total_duration_micros >= 5000);
+
+        // capacity is one block, so the first one is evicted
+        tracer.event(&block_2, BlockLifecycleStage::HeaderReceived, None);
+        assert!(tracer.timeline(&block_1).is_none());
+        assert_eq!(2, tracer.timeline(&block_2).unwrap().trace_id);
+
+        // all spans are exported
+        let lines = BufReader::new(File::open(&trace_file)?)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(3, lines.len());
+        let span: serde_json::Value = serde_json::from_str(&lines[1])?;
+        assert_eq!("apply_block", span["stage"]);
+        assert_eq!(1, span["trace_id"]);
+
+        let _ = fs::remove_file(&trace_file);
+        Ok(())
+    }
+}
@@ -14,6 +14,8 @@ use serde::{Deserialize, Serialize};
 
 use lazy_static::lazy_static;
 
+use tezos_wrapper::stats::Statm;
+
 pub type MemoryStatsResult<T> = std::result::Result<T, MemoryStatsError>;
 
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
@@ -82,8 +84,8 @@ impl From<FromUtf8Error> for MemoryStatsError {
 }
 
 lazy_static! {
-    static ref LINUX_DATA: Regex = Regex::new(r"^(?P<size>\d+)\s+(?P<resident>\d+)\s+(?P<shared>\d+)\s+(?P<text>\d+)\s+(?P<lib>\d+)\s+(?P<data>\d+)\s+(?P<dt>\d+).*").expect("Invalid regex");
-    static ref MAC_DATA: Regex = Regex::new(r"(?P<mem>[0-9.]+)\s+(?P<resident>\d+)").expect("Invalid regex");
+    static ref MAC_DATA: Regex =
+        Regex::new(r"(?P<mem>[0-9.]+)\s+(?P<resident>\d+)").expect("Invalid regex");
 }
 
 #[derive(Default)]
@@ -192,25 +194,20 @@ impl Memory {
         }
     }
 
-    /// get LinuxData from statm String
-    /// all exept page_size must be strings convertable to i64
-    /// no need to check them because regular will match only numbers /(\d+)/
+    /// get LinuxData from statm String (parsed the same way as resident memory of protocol runners in pools)
     fn parse_linux_statm(&self, statm: String) -> MemoryStatsResult<MemoryData> {
-        if let Some(captures) = LINUX_DATA.captures(&statm) {
-            let data = LinuxData {
-                page_size: self.page_size,
-                size: captures["size"].to_string(),
-                resident: captures["resident"].to_string(),
-                shared: captures["shared"].to_string(),
-                text: captures["text"].to_string(),
-                lib: captures["lib"].to_string(),
-                data: captures["data"].to_string(),
-                dt: captures["dt"].to_string(),
-            };
-            Ok(MemoryData::from(data))
-        } else {
-            Err(MemoryStatsError::ParsingData)
-        }
+        let statm = Statm::parse(&statm).ok_or(MemoryStatsError::ParsingData)?;
+        let data = LinuxData {
+            page_size: self.page_size,
+            size: statm.size.to_string(),
+            resident: statm.resident.to_string(),
+            shared: statm.shared.to_string(),
+            text: statm.text.to_string(),
+            lib: statm.lib.to_string(),
+            data: statm.data.to_string(),
+            dt: statm.dt.to_string(),
+        };
+        Ok(MemoryData::from(data))
     }
 
     /// read lines of given file path and parse linux memory data
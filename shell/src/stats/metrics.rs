@@ -0,0 +1,135 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Shared snapshots of node internals, which are not owned by shell actors (e.g. p2p transfer and bootstrap progress
+//! collected by monitoring), so they can be exported by rpc `/metrics` endpoint.
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use networking::p2p::traffic::PeerTrafficStats;
+
+/// Upper bounds (in seconds) of histogram buckets used for block application durations
+pub const BLOCK_APPLICATION_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Histogram of durations with fixed buckets, values are never cleared
+#[derive(Clone, Debug)]
+pub struct DurationHistogram {
+    /// Upper bounds of buckets in seconds (ascending)
+    bounds: &'static [f64],
+    /// Non-cumulative counts per bucket, last item is for `+Inf` bucket
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl DurationHistogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let idx = self
+            .bounds
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or_else(|| self.bounds.len());
+        self.counts[idx] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    /// Returns pairs (upper bound, cumulative count), the last upper bound is `f64::INFINITY`
+    pub fn cumulative_buckets(&self) -> Vec<(f64, u64)> {
+        let mut cumulative = 0;
+        self.bounds
+            .iter()
+            .cloned()
+            .chain(std::iter::once(f64::INFINITY))
+            .zip(self.counts.iter())
+            .map(|(bound, count)| {
+                cumulative += count;
+                (bound, cumulative)
+            })
+            .collect()
+    }
+
+    /// Sum of all observed durations in seconds
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// Shareable type for monitoring metrics
+pub type MonitorMetricsRef = Arc<RwLock<MonitorMetrics>>;
+
+/// Inits empty monitoring metrics
+pub fn init_monitor_metrics() -> MonitorMetricsRef {
+    Arc::new(RwLock::new(MonitorMetrics::default()))
+}
+
+/// Last snapshot of statistics collected by monitoring
+#[derive(Clone, Debug, Default)]
+pub struct MonitorMetrics {
+    /// Transfer statistics of connected peers
+    pub peers: Vec<PeerTransferMetrics>,
+    /// Summed traffic of already disconnected peers
+    pub disconnected_peers_traffic: PeerTrafficStats,
+    pub bootstrap: BootstrapMetrics,
+}
+
+/// Transfer statistics of one connected peer
+#[derive(Clone, Debug)]
+pub struct PeerTransferMetrics {
+    pub peer_id: String,
+    pub address: String,
+    /// Socket level traffic of the peer connection
+    pub traffic: PeerTrafficStats,
+}
+
+/// Progress of the bootstrap pipeline, counts are collected from node start
+#[derive(Clone, Debug, Default)]
+pub struct BootstrapMetrics {
+    /// Highest level announced by peers
+    pub remote_level: u64,
+    pub received_headers: u64,
+    pub downloaded_operations: u64,
+    pub applied_blocks: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_histogram() {
+        let mut histogram = DurationHistogram::new(&BLOCK_APPLICATION_BUCKETS);
+        histogram.observe(Duration::from_millis(1));
+        histogram.observe(Duration::from_millis(10));
+        histogram.observe(Duration::from_millis(700));
+        histogram.observe(Duration::from_secs(60));
+
+        let buckets = histogram.cumulative_buckets();
+        assert_eq!(BLOCK_APPLICATION_BUCKETS.len() + 1, buckets.len());
+        assert_eq!((0.005, 1), buckets[0]);
+        assert_eq!((0.01, 2), buckets[1]);
+        assert_eq!((0.5, 2), buckets[6]);
+        assert_eq!((1.0, 3), buckets[7]);
+        assert_eq!((30.0, 3), buckets[11]);
+        assert_eq!((f64::INFINITY, 4), buckets[12]);
+        assert_eq!(4, histogram.count());
+        assert!((histogram.sum() - 60.711).abs() < 1e-9);
+    }
+}
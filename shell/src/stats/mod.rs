@@ -4,4 +4,7 @@
 //! This module contains all structs used to hold shell stats.
 
 pub mod apply_block_stats;
+pub mod block_lifecycle;
 pub mod memory;
+pub mod metrics;
+pub mod write_runner_stats;
@@ -0,0 +1,100 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Stats of the supervised (write) protocol runner, which applies blocks in chain feeder.
+
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+pub type WriteRunnerStatsRef = Arc<RwLock<WriteRunnerStats>>;
+
+pub fn init_write_runner_stats() -> WriteRunnerStatsRef {
+    Arc::new(RwLock::new(WriteRunnerStats::default()))
+}
+
+/// Reason, why write protocol runner was restarted
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteRunnerRestartReason {
+    /// Runner sub-process died or IPC failed
+    Crash,
+    /// Runner did not respond in configured timeout and was killed
+    Hang,
+}
+
+impl WriteRunnerRestartReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WriteRunnerRestartReason::Crash => "crash",
+            WriteRunnerRestartReason::Hang => "hang",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WriteRunnerRestart {
+    /// Unix timestamp (seconds)
+    pub timestamp: i64,
+    pub reason: WriteRunnerRestartReason,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct WriteRunnerStats {
+    /// How many times was write runner (re)initialized
+    pub started: u64,
+    /// Restarts caused by crash of the runner
+    pub crashes: u64,
+    /// Restarts caused by hung runner
+    pub hangs: u64,
+    /// Blocks, which were replayed to the new runner after restart
+    pub replayed_blocks: u64,
+    /// Blocks, which were given up after too many failed attempts
+    pub abandoned_blocks: u64,
+    pub last_restart: Option<WriteRunnerRestart>,
+}
+
+impl WriteRunnerStats {
+    /// Total count of restarts
+    pub fn restarts(&self) -> u64 {
+        self.crashes + self.hangs
+    }
+
+    pub fn record_restart(
+        &mut self,
+        reason: WriteRunnerRestartReason,
+        message: String,
+        timestamp: i64,
+    ) {
+        match reason {
+            WriteRunnerRestartReason::Crash => self.crashes += 1,
+            WriteRunnerRestartReason::Hang => self.hangs += 1,
+        }
+        self.last_restart = Some(WriteRunnerRestart {
+            timestamp,
+            reason,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_restart() {
+        let mut stats = WriteRunnerStats::default();
+        stats.record_restart(WriteRunnerRestartReason::Crash, "eof".to_string(), 1);
+        stats.record_restart(WriteRunnerRestartReason::Hang, "timeout".to_string(), 2);
+        stats.record_restart(WriteRunnerRestartReason::Crash, "eof".to_string(), 3);
+
+        assert_eq!(2, stats.crashes);
+        assert_eq!(1, stats.hangs);
+        assert_eq!(3, stats.restarts());
+        let last = stats.last_restart.expect("last restart should be set");
+        assert_eq!(3, last.timestamp);
+        assert_eq!(WriteRunnerRestartReason::Crash, last.reason);
+    }
+}
@@ -63,10 +63,15 @@ pub fn is_same_head(head: &Head, incoming_header: &BlockHeader) -> Result<bool,
     Ok(is_same)
 }
 
-/// Returns only true, if timestamp of header is not in the far future
-pub fn is_future_block(block_header: &BlockHeader) -> Result<bool, failure::Error> {
-    let future_margin =
-        chrono::offset::Utc::now() + chrono::Duration::from_std(Duration::from_secs(15))?;
+/// Block with timestamp later than node's `now` + tolerance is considered as block from the future
+pub const FUTURE_BLOCK_TOLERANCE: Duration = Duration::from_secs(15);
+
+/// Returns only true, if timestamp of header is in the far future (according to node's `now`)
+pub fn is_future_block(
+    block_header: &BlockHeader,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<bool, failure::Error> {
+    let future_margin = now + chrono::Duration::from_std(FUTURE_BLOCK_TOLERANCE)?;
     let block_timestamp = chrono::Utc.from_utc_datetime(&chrono::NaiveDateTime::from_timestamp(
         block_header.timestamp(),
         0,
@@ -3,11 +3,15 @@
 #![feature(test)]
 extern crate test;
 
-/// Big integration test for actors, covers two main use cases:
+/// Big integration test for actors, covers three main use cases:
 /// 1. test_scenario_for_apply_blocks_with_chain_feeder_and_check_context - see fn description
 /// 2. test_scenario_for_add_operations_to_mempool_and_check_state - see fn description
+/// 3. test_actors_apply_blocks_with_killed_write_protocol_runner - see fn description
 use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::process::Command;
 use std::sync::Arc;
+use std::thread;
 use std::time::{Duration, Instant, SystemTime};
 
 use riker::actors::*;
@@ -16,6 +20,7 @@ use slog::{info, Logger};
 use crypto::hash::{BlockHash, ChainId, ContextHash, OperationHash};
 use shell::chain_feeder::ApplyCompletedBlock;
 use shell::shell_channel::{MempoolOperationReceived, ShellChannelRef, ShellChannelTopic};
+use shell::stats::block_lifecycle::BlockLifecycleStage;
 use storage::chain_meta_storage::ChainMetaStorageReader;
 use storage::context::{ContextApi, TezedgeContext};
 use storage::mempool_storage::MempoolOperationType;
@@ -140,6 +145,206 @@ fn test_actors_apply_blocks_and_check_context_and_mempool() -> Result<(), failur
     Ok(())
 }
 
+/// Write protocol runner is killed while blocks are being applied,
+/// chain feeder should restart it and replay not yet applied blocks, so every block is applied exactly once.
+#[ignore]
+#[test]
+fn test_actors_apply_blocks_with_killed_write_protocol_runner() -> Result<(), failure::Error> {
+    // logger
+    let log_level = common::log_level();
+    let log = common::create_logger(log_level);
+
+    let (requests, operations, tezos_env) = samples::read_data_apply_block_request_until_1326();
+    let tezos_env: &TezosEnvironmentConfiguration = TEZOS_ENV
+        .get(&tezos_env)
+        .expect("no environment configuration");
+    let chain_id = tezos_env.main_chain_id().expect("invalid chain id");
+
+    // prepare storage paths
+    let storage_db_path =
+        common::prepare_empty_dir("__test_actors_apply_blocks_with_killed_write_protocol_runner");
+    let context_db_path = common::prepare_empty_dir(
+        "__test_actors_apply_blocks_with_killed_write_protocol_runner_context",
+    );
+    let node_name = "test_actors_apply_blocks_with_killed_write_protocol_runner";
+
+    // start/stop node to initialize genesis (see test_actors_apply_blocks_and_check_context_and_mempool)
+    let node = common::infra::NodeInfrastructure::start(
+        TmpStorage::initialize(&storage_db_path, true, false)?,
+        &context_db_path,
+        node_name,
+        &tezos_env,
+        None,
+        None,
+        tezos_identity::Identity::generate(0f64),
+        (log.clone(), log_level),
+    )?;
+    node.wait_for_new_current_head(
+        "genesis",
+        node.tezos_env.genesis_header_hash()?,
+        (Duration::from_secs(5), Duration::from_millis(250)),
+    )?;
+    drop(node);
+
+    let apply_to_level = 1324;
+    let kill_at_level = 100;
+    init_storage_data(
+        &log,
+        &requests,
+        &operations,
+        apply_to_level,
+        TmpStorage::initialize(&storage_db_path, false, false)?.storage(),
+        &chain_id,
+    )?;
+    let blocks = requests
+        .iter()
+        .take(apply_to_level as usize)
+        .map(|request| {
+            samples::from_captured_bytes(request)?
+                .block_header
+                .message_typed_hash()
+                .map_err(failure::Error::from)
+        })
+        .collect::<Result<Vec<BlockHash>, failure::Error>>()?;
+
+    let node = common::infra::NodeInfrastructure::start(
+        TmpStorage::initialize(storage_db_path, false, true)?,
+        &context_db_path,
+        node_name,
+        &tezos_env,
+        None,
+        None,
+        tezos_identity::Identity::generate(0f64),
+        (log, log_level),
+    )?;
+    node.block_applier.tell(
+        ApplyCompletedBlock::new(
+            tezos_env.genesis_header_hash()?,
+            Arc::new(chain_id.clone()),
+            None,
+            None,
+            Instant::now(),
+        ),
+        None,
+    );
+
+    // kill write protocol runner in the middle of the chain
+    let chain_meta_storage = ChainMetaStorage::new(node.tmp_storage.storage());
+    let started = Instant::now();
+    loop {
+        if let Some(head) = chain_meta_storage.get_current_head(&chain_id)? {
+            if *head.level() >= kill_at_level {
+                break;
+            }
+        }
+        assert!(
+            started.elapsed() < Duration::from_secs(300),
+            "Level {} was not applied in time",
+            kill_at_level
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    let killed_pid = kill_protocol_runner(&format!("{}_writeable_runner_pool", node_name))?;
+    let killed_at_level = chain_meta_storage
+        .get_current_head(&chain_id)?
+        .map(|head| *head.level())
+        .unwrap_or(0);
+    info!(node.log, "Write protocol runner killed"; "pid" => killed_pid, "level" => killed_at_level);
+    assert!(
+        killed_at_level < apply_to_level,
+        "Runner was killed after all blocks were applied, nothing to replay"
+    );
+
+    // all blocks should be applied by the restarted runner
+    node.wait_for_new_current_head(
+        "after_write_runner_restart",
+        blocks[(apply_to_level - 1) as usize].clone(),
+        (Duration::from_secs(600), Duration::from_millis(250)),
+    )?;
+
+    {
+        let stats = node
+            .write_runner_stats
+            .read()
+            .expect("Failed to lock write runner stats");
+        assert!(stats.restarts() >= 1, "{:?}", stats);
+        assert!(stats.started >= 2, "{:?}", stats);
+        assert_eq!(0, stats.abandoned_blocks, "{:?}", stats);
+    }
+
+    // nothing lost and nothing applied twice
+    let block_meta_storage = BlockMetaStorage::new(node.tmp_storage.storage());
+    for (idx, block_hash) in blocks.iter().enumerate() {
+        let level = idx + 1;
+        let meta = block_meta_storage
+            .get(block_hash)?
+            .unwrap_or_else(|| panic!("Missing metadata of block at level {}", level));
+        assert!(meta.is_applied(), "Block at level {} is not applied", level);
+
+        let timeline = node
+            .block_lifecycle_tracer
+            .timeline(block_hash)
+            .unwrap_or_else(|| panic!("Missing timeline of block at level {}", level));
+        let successful_applies = timeline
+            .spans
+            .iter()
+            .filter(|span| span.stage == BlockLifecycleStage::ApplyBlock)
+            .filter(|span| {
+                !span
+                    .details
+                    .as_ref()
+                    .map_or(false, |details| details.starts_with("failed"))
+            })
+            .count();
+        assert_eq!(
+            1, successful_applies,
+            "Block at level {} was applied {} times",
+            level, successful_applies
+        );
+    }
+
+    drop(node);
+
+    Ok(())
+}
+
+/// Kills (SIGKILL) protocol runner sub-process, which was started with endpoint name prefixed by `endpoint_prefix`
+fn kill_protocol_runner(endpoint_prefix: &str) -> Result<u32, failure::Error> {
+    for entry in fs::read_dir("/proc")? {
+        let entry = entry?;
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            Some(pid) => pid,
+            None => continue,
+        };
+        let cmdline = match fs::read(entry.path().join("cmdline")) {
+            Ok(cmdline) => cmdline,
+            Err(_) => continue,
+        };
+        let args: Vec<String> = cmdline
+            .split(|b| *b == 0)
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        if args
+            .windows(2)
+            .any(|arg| arg[0] == "--endpoint" && arg[1].starts_with(endpoint_prefix))
+        {
+            Command::new("kill")
+                .arg("-9")
+                .arg(pid.to_string())
+                .status()?;
+            return Ok(pid);
+        }
+    }
+    Err(failure::format_err!(
+        "Protocol runner with endpoint: {} not found",
+        endpoint_prefix
+    ))
+}
+
 fn check_context(
     expected_context_hash: ContextHash,
     persistent_storage: &PersistentStorage,
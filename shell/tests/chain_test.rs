@@ -18,6 +18,7 @@ use serial_test::serial;
 use crypto::hash::OperationHash;
 use networking::ShellCompatibilityVersion;
 use shell::peer_manager::P2p;
+use shell::state::synchronization_state::DEFAULT_SYNCHRONIZATION_LATENCY;
 use shell::PeerConnectionThreshold;
 use storage::tests_common::TmpStorage;
 use storage::{BlockMetaStorage, BlockMetaStorageReader};
@@ -42,6 +43,7 @@ lazy_static! {
             private_node: false,
             bootstrap_peers: vec![],
             peer_threshold: PeerConnectionThreshold::try_new(0, 10, Some(0)).expect("Invalid range"),
+            synchronization_latency: DEFAULT_SYNCHRONIZATION_LATENCY,
         },
         SHELL_COMPATIBILITY_VERSION.clone(),
     );
@@ -56,7 +56,7 @@ pub fn log_level() -> Level {
 
 pub fn protocol_runner_executable_path() -> PathBuf {
     let executable = env::var("PROTOCOL_RUNNER")
-        .unwrap_or_else(|_| panic!("This test requires environment parameter: 'PROTOCOL_RUNNER' to point to protocol_runner executable"));
+        .unwrap_or_else(|_| panic!("This test requires environment parameter: 'PROTOCOL_RUNNER' to point to protocol_runner (or mock-protocol-runner) executable"));
     PathBuf::from(executable)
 }
 
@@ -79,6 +79,7 @@ pub mod infra {
     use tokio::runtime::Runtime;
 
     use crypto::hash::{BlockHash, ContextHash, OperationHash};
+    use ipc::IpcTransport;
     use networking::p2p::network_channel::{NetworkChannel, NetworkChannelRef};
     use networking::ShellCompatibilityVersion;
     use shell::chain_current_head_manager::ChainCurrentHeadManager;
@@ -89,9 +90,16 @@ pub mod infra {
     use shell::mempool::{init_mempool_state_storage, CurrentMempoolStateStorageRef};
     use shell::peer_manager::{P2p, PeerManager, PeerManagerRef, WhitelistAllIpAddresses};
     use shell::shell_channel::{ShellChannel, ShellChannelRef, ShellChannelTopic, ShuttingDown};
+    use shell::state::clock_state::init_node_clock;
     use shell::state::head_state::init_current_head_state;
-    use shell::state::synchronization_state::init_synchronization_bootstrap_state_storage;
+    use shell::state::synchronization_state::{
+        init_synchronization_bootstrap_state_storage, DEFAULT_SYNCHRONIZATION_LATENCY,
+    };
     use shell::stats::apply_block_stats::init_empty_apply_block_stats;
+    use shell::stats::block_lifecycle::{
+        init_block_lifecycle_tracer, BlockLifecycleTracerRef, BLOCK_TIMELINES_CAPACITY,
+    };
+    use shell::stats::write_runner_stats::{init_write_runner_stats, WriteRunnerStatsRef};
     use shell::PeerConnectionThreshold;
     use storage::chain_meta_storage::ChainMetaStorageReader;
     use storage::context::{ContextApi, TezedgeContext};
@@ -102,7 +110,9 @@ pub mod infra {
     use tezos_identity::Identity;
     use tezos_wrapper::service::IpcEvtServer;
     use tezos_wrapper::ProtocolEndpointConfiguration;
-    use tezos_wrapper::{TezosApiConnectionPool, TezosApiConnectionPoolConfiguration};
+    use tezos_wrapper::{
+        ProtocolRunnerLocation, TezosApiConnectionPool, TezosApiConnectionPoolConfiguration,
+    };
 
     use crate::common;
     use crate::common::contains_all_keys;
@@ -121,6 +131,8 @@ pub mod infra {
         pub current_mempool_state_storage: CurrentMempoolStateStorageRef,
         pub tezos_env: TezosEnvironmentConfiguration,
         pub tokio_runtime: Runtime,
+        pub block_lifecycle_tracer: BlockLifecycleTracerRef,
+        pub write_runner_stats: WriteRunnerStatsRef,
     }
 
     impl NodeInfrastructure {
@@ -155,6 +167,7 @@ pub mod infra {
                 &tmp_storage.path(),
                 &context_db_path,
                 &patch_context,
+                &None,
                 &log,
             )
             .expect("Failed to resolve init storage chain data");
@@ -168,6 +181,8 @@ pub mod infra {
                     connection_timeout: Duration::from_secs(3),
                     max_lifetime: Duration::from_secs(60),
                     idle_timeout: Duration::from_secs(60),
+                    runners: vec![ProtocolRunnerLocation::Local],
+                    max_runner_memory: None,
                 },
                 ProtocolEndpointConfiguration::new(
                     TezosRuntimeConfiguration {
@@ -181,6 +196,9 @@ pub mod infra {
                     &common::protocol_runner_executable_path(),
                     log_level,
                     None,
+                    None,
+                    IpcTransport::Socket,
+                    None,
                 ),
                 log.clone(),
             )?);
@@ -195,6 +213,8 @@ pub mod infra {
                     connection_timeout: Duration::from_secs(3),
                     max_lifetime: Duration::from_secs(60),
                     idle_timeout: Duration::from_secs(60),
+                    runners: vec![ProtocolRunnerLocation::Local],
+                    max_runner_memory: None,
                 },
                 ProtocolEndpointConfiguration::new(
                     TezosRuntimeConfiguration {
@@ -208,6 +228,9 @@ pub mod infra {
                     &common::protocol_runner_executable_path(),
                     log_level,
                     Some(apply_protocol_events.server_path()),
+                    None,
+                    IpcTransport::Socket,
+                    None,
                 ),
                 log.clone(),
             )?);
@@ -215,10 +238,18 @@ pub mod infra {
             let local_current_head_state = init_current_head_state();
             let remote_current_head_state = init_current_head_state();
             let current_mempool_state_storage = init_mempool_state_storage();
+            let node_clock = init_node_clock();
             let bootstrap_state = init_synchronization_bootstrap_state_storage(
                 p2p_threshold.num_of_peers_for_bootstrap_threshold(),
+                DEFAULT_SYNCHRONIZATION_LATENCY,
+                node_clock.clone(),
             );
             let apply_block_stats = init_empty_apply_block_stats();
+            let block_lifecycle_tracer =
+                init_block_lifecycle_tracer(BLOCK_TIMELINES_CAPACITY, None, log.clone())
+                    .expect("Failed to create block lifecycle tracer");
+
+            let write_runner_stats = init_write_runner_stats();
 
             let tokio_runtime = create_tokio_runtime();
 
@@ -241,6 +272,7 @@ pub mod infra {
                 &persistent_storage,
                 vec![],
                 apply_protocol_events,
+                block_lifecycle_tracer.clone(),
                 log.clone(),
             )
             .expect("Failed to create context event listener");
@@ -250,10 +282,10 @@ pub mod infra {
                 persistent_storage.clone(),
                 init_storage_data.clone(),
                 local_current_head_state.clone(),
-                remote_current_head_state.clone(),
                 current_mempool_state_storage.clone(),
                 bootstrap_state.clone(),
                 apply_block_stats.clone(),
+                block_lifecycle_tracer.clone(),
             )
             .expect("Failed to create chain current head manager");
             let block_applier = ChainFeeder::actor(
@@ -265,6 +297,10 @@ pub mod infra {
                 tezos_writeable_api,
                 init_storage_data.clone(),
                 tezos_env.clone(),
+                block_lifecycle_tracer.clone(),
+                write_runner_stats.clone(),
+                Duration::from_secs(600),
+                4,
                 log.clone(),
             )
             .expect("Failed to create chain feeder");
@@ -283,6 +319,8 @@ pub mod infra {
                 current_mempool_state_storage.clone(),
                 bootstrap_state,
                 apply_block_stats,
+                block_lifecycle_tracer.clone(),
+                node_clock,
                 false,
                 identity.clone(),
             )
@@ -328,6 +366,8 @@ pub mod infra {
                 tmp_storage,
                 current_mempool_state_storage,
                 tezos_env: tezos_env.clone(),
+                block_lifecycle_tracer,
+                write_runner_stats,
             })
         }
 
@@ -12,12 +12,15 @@ use failure::format_err;
 use serial_test::serial;
 use slog::{error, info, o, warn, Level, Logger};
 
+use ipc::IpcTransport;
 use tezos_api::environment::{TezosEnvironmentConfiguration, TEZOS_ENV};
 use tezos_api::ffi::{InitProtocolContextResult, TezosRuntimeConfiguration};
 use tezos_wrapper::runner::{ExecutableProtocolRunner, ProtocolRunner};
 use tezos_wrapper::service::{IpcCmdServer, ProtocolRunnerEndpoint};
 use tezos_wrapper::ProtocolEndpointConfiguration;
-use tezos_wrapper::{TezosApiConnectionPool, TezosApiConnectionPoolConfiguration};
+use tezos_wrapper::{
+    ProtocolRunnerLocation, TezosApiConnectionPool, TezosApiConnectionPoolConfiguration,
+};
 
 mod common;
 
@@ -135,6 +138,9 @@ fn create_endpoint<Runner: ProtocolRunner + 'static>(
             &protocol_runner,
             log_level,
             None,
+            None,
+            IpcTransport::Socket,
+            None,
         ),
         log.new(o!("endpoint" => endpoint_name.clone())),
     )?;
@@ -204,6 +210,8 @@ fn test_readonly_protocol_runner_connection_pool() -> Result<(), failure::Error>
         connection_timeout: Duration::from_secs(1),
         max_lifetime: Duration::from_secs(1),
         idle_timeout: Duration::from_secs(1),
+        runners: vec![ProtocolRunnerLocation::Local],
+        max_runner_memory: None,
     };
 
     // cfg for protocol runner
@@ -219,6 +227,9 @@ fn test_readonly_protocol_runner_connection_pool() -> Result<(), failure::Error>
         &protocol_runner,
         log_level,
         None,
+        None,
+        IpcTransport::Socket,
+        None,
     );
 
     // create pool
@@ -231,7 +242,7 @@ fn test_readonly_protocol_runner_connection_pool() -> Result<(), failure::Error>
     )?);
 
     // create readonly pool pool
-    let pool = &pool_wrapper.pool;
+    let pool = pool_wrapper.pool();
     assert_eq!(0, pool.state().connections);
 
     // test pool
@@ -276,6 +287,18 @@ fn test_readonly_protocol_runner_connection_pool() -> Result<(), failure::Error>
     assert_eq!(3, pool.state().connections);
     assert_eq!(3, pool.state().idle_connections);
 
+    // check stats of runners
+    let runner_stats = pool_wrapper.runner_stats();
+    assert_eq!(3, runner_stats.len());
+    assert!(runner_stats.iter().all(|runner| runner.calls > 0
+        && runner.errors == 0
+        && runner.latency.is_some()
+        && runner.pid.is_some()));
+    // reused connection was pinged on checkout
+    assert!(runner_stats
+        .iter()
+        .any(|runner| runner.last_ping_ms.is_some()));
+
     {
         // acquire (reused and not release)
         let api = &pool.get()?.api;
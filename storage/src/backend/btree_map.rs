@@ -1,16 +1,19 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
 
-use crate::merkle_storage::{ContextValue, EntryHash};
-use crate::persistent::database::RocksDBStats;
-use crate::storage_backend::{StorageBackend as KVStoreTrait, StorageBackendError as KVStoreError};
+use crate::persistent::database::{Direction, RocksDBStats};
+use crate::storage_backend::{
+    apply_merge_operator, BackendIterator, BackendIteratorMode, BatchOperation, MergeOperator,
+    StorageBackend as KVStoreTrait, StorageBackendError as KVStoreError, WriteBatch,
+};
 
 /// In Memory Key Value Store implemented with [BTreeMap](std::collections::BTreeMap)
 #[derive(Debug)]
 pub struct KVStore<K: Ord, V> {
-    kv_map: BTreeMap<K, V>,
+    kv_map: RwLock<BTreeMap<K, V>>,
 }
 
 impl<K: Ord, V> Default for KVStore<K, V> {
@@ -22,49 +25,137 @@ impl<K: Ord, V> Default for KVStore<K, V> {
 impl<K: Ord, V> KVStore<K, V> {
     pub fn new() -> Self {
         Self {
-            kv_map: BTreeMap::new(),
+            kv_map: RwLock::new(BTreeMap::new()),
         }
     }
 }
 
-impl KVStoreTrait for KVStore<EntryHash, ContextValue> {
+/// Entries of all columns are stored in single map, keyed by (column, key)
+type ColumnKey = (&'static str, Vec<u8>);
+
+fn merge_value(
+    kv_map: &mut BTreeMap<ColumnKey, Vec<u8>>,
+    column: &'static str,
+    key: Vec<u8>,
+    value: &[u8],
+    operator: Option<MergeOperator>,
+) -> Result<(), KVStoreError> {
+    let column_key = (column, key);
+    let merged = apply_merge_operator(
+        column,
+        &column_key.1,
+        kv_map.get(&column_key).map(|v| v.as_slice()),
+        value,
+        operator,
+    )?;
+    kv_map.insert(column_key, merged);
+    Ok(())
+}
+
+impl KVStoreTrait for KVStore<ColumnKey, Vec<u8>> {
+    fn name(&self) -> &'static str {
+        "btree"
+    }
+
     fn is_persisted(&self) -> bool {
         false
     }
 
-    /// put kv in map if key doesn't exist. If it does then return false.
-    fn put(&mut self, key: &EntryHash, value: ContextValue) -> Result<bool, KVStoreError> {
-        match self.kv_map.entry(key.clone()) {
-            Entry::Vacant(entry) => {
-                entry.insert(value);
-                Ok(true)
-            }
-            _ => Ok(false),
-        }
+    fn get(&self, column: &'static str, key: &[u8]) -> Result<Option<Vec<u8>>, KVStoreError> {
+        let kv_map = self.kv_map.read()?;
+
+        Ok(kv_map.get(&(column, key.to_vec())).cloned())
     }
 
-    fn put_batch(&mut self, batch: Vec<(EntryHash, ContextValue)>) -> Result<(), KVStoreError> {
-        for (k, v) in batch.into_iter() {
-            self.put(&k, v)?;
-        }
+    fn put(&self, column: &'static str, key: &[u8], value: &[u8]) -> Result<(), KVStoreError> {
+        let mut kv_map = self.kv_map.write()?;
+
+        kv_map.insert((column, key.to_vec()), value.to_vec());
         Ok(())
     }
 
-    fn merge(&mut self, key: &EntryHash, value: ContextValue) -> Result<(), KVStoreError> {
-        self.kv_map.insert(key.clone(), value);
-        Ok(())
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
+    ) -> Result<(), KVStoreError> {
+        let mut kv_map = self.kv_map.write()?;
+
+        merge_value(&mut kv_map, column, key.to_vec(), value, operator)
     }
 
-    fn delete(&mut self, key: &EntryHash) -> Result<Option<ContextValue>, KVStoreError> {
-        Ok(self.kv_map.remove(key))
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), KVStoreError> {
+        let mut kv_map = self.kv_map.write()?;
+
+        kv_map.remove(&(column, key.to_vec()));
+        Ok(())
     }
 
-    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, KVStoreError> {
-        Ok(self.kv_map.get(key).cloned())
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), KVStoreError> {
+        let mut kv_map = self.kv_map.write()?;
+
+        for operation in batch.into_operations() {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    kv_map.insert((column, key), value);
+                }
+                BatchOperation::Merge {
+                    column,
+                    key,
+                    value,
+                    operator,
+                } => merge_value(&mut kv_map, column, key, &value, operator)?,
+                BatchOperation::Delete { column, key } => {
+                    kv_map.remove(&(column, key));
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn contains(&self, key: &EntryHash) -> Result<bool, KVStoreError> {
-        Ok(self.kv_map.contains_key(key))
+    /// Iterator works with a snapshot of the requested part of the column
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, KVStoreError> {
+        let kv_map = self.kv_map.read()?;
+
+        let to_entry = |((_, k), v): (&ColumnKey, &Vec<u8>)| {
+            (k.clone().into_boxed_slice(), v.clone().into_boxed_slice())
+        };
+        let column_start = (column, Vec::new());
+
+        let entries: Vec<(Box<[u8]>, Box<[u8]>)> = match mode {
+            BackendIteratorMode::Start => kv_map
+                .range(column_start..)
+                .take_while(|((c, _), _)| *c == column)
+                .map(to_entry)
+                .collect(),
+            BackendIteratorMode::End => {
+                let mut entries: Vec<_> = kv_map
+                    .range(column_start..)
+                    .take_while(|((c, _), _)| *c == column)
+                    .map(to_entry)
+                    .collect();
+                entries.reverse();
+                entries
+            }
+            BackendIteratorMode::From(key, Direction::Forward) => kv_map
+                .range((column, key.to_vec())..)
+                .take_while(|((c, _), _)| *c == column)
+                .map(to_entry)
+                .collect(),
+            BackendIteratorMode::From(key, Direction::Reverse) => kv_map
+                .range(column_start..=(column, key.to_vec()))
+                .rev()
+                .map(to_entry)
+                .collect(),
+        };
+
+        Ok(Box::new(entries.into_iter().map(Ok)))
     }
 
     fn get_mem_use_stats(&self) -> Result<RocksDBStats, KVStoreError> {
@@ -78,4 +169,4 @@ impl KVStoreTrait for KVStore<EntryHash, ContextValue> {
     }
 }
 
-pub type BTreeMapBackend = KVStore<EntryHash, ContextValue>;
+pub type BTreeMapBackend = KVStore<ColumnKey, Vec<u8>>;
@@ -4,99 +4,128 @@
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use crate::merkle_storage::{ContextValue, EntryHash};
-use crate::persistent::database::RocksDBStats;
-use crate::storage_backend::{StorageBackend, StorageBackendError, StorageBackendStats};
+use crate::persistent::database::{Direction, RocksDBStats};
+use crate::storage_backend::{
+    apply_merge_operator, BackendIterator, BackendIteratorMode, BatchOperation, MergeOperator,
+    StorageBackend, StorageBackendError, WriteBatch,
+};
 
+type Column = HashMap<Vec<u8>, Vec<u8>>;
+
+/// In memory backend implemented with [HashMap](std::collections::HashMap), one map per column.
+///
+/// Iterators work with a sorted snapshot of the whole column.
 #[derive(Default)]
 pub struct InMemoryBackend {
-    inner: Arc<RwLock<HashMap<EntryHash, ContextValue>>>,
-    stats: StorageBackendStats,
+    inner: Arc<RwLock<HashMap<&'static str, Column>>>,
 }
 
 impl InMemoryBackend {
     pub fn new() -> Self {
         InMemoryBackend {
             inner: Arc::new(RwLock::new(HashMap::new())),
-            stats: Default::default(),
         }
     }
 }
 
 impl StorageBackend for InMemoryBackend {
+    fn name(&self) -> &'static str {
+        "inmem"
+    }
+
     fn is_persisted(&self) -> bool {
         false
     }
 
-    fn put(&mut self, key: &EntryHash, value: ContextValue) -> Result<bool, StorageBackendError> {
-        let measurement = StorageBackendStats::from((key, &value));
-        let mut w = self
-            .inner
-            .write()
-            .map_err(|e| StorageBackendError::GuardPoison {
-                error: format!("{}", e),
-            })?;
+    fn get(
+        &self,
+        column: &'static str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageBackendError> {
+        let r = self.inner.read()?;
 
-        let was_added = w.insert(key.clone(), value).is_none();
+        Ok(r.get(column).and_then(|c| c.get(key)).cloned())
+    }
 
-        if was_added {
-            self.stats += measurement;
-        }
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), StorageBackendError> {
+        let mut w = self.inner.write()?;
 
-        Ok(was_added)
+        w.entry(column)
+            .or_default()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
     }
 
-    fn put_batch(
-        &mut self,
-        batch: Vec<(EntryHash, ContextValue)>,
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
     ) -> Result<(), StorageBackendError> {
-        for (k, v) in batch.into_iter() {
-            self.put(&k, v)?;
-        }
-        Ok(())
+        let mut w = self.inner.write()?;
+
+        merge_value(w.entry(column).or_default(), column, key, value, operator)
     }
 
-    fn merge(&mut self, key: &EntryHash, value: ContextValue) -> Result<(), StorageBackendError> {
-        let mut w = self
-            .inner
-            .write()
-            .map_err(|e| StorageBackendError::GuardPoison {
-                error: format!("{}", e),
-            })?;
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError> {
+        let mut w = self.inner.write()?;
 
-        w.insert(key.clone(), value);
+        if let Some(c) = w.get_mut(column) {
+            c.remove(key);
+        }
         Ok(())
     }
 
-    fn delete(&mut self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        let mut w = self
-            .inner
-            .write()
-            .map_err(|e| StorageBackendError::GuardPoison {
-                error: format!("{}", e),
-            })?;
+    fn contains(&self, column: &'static str, key: &[u8]) -> Result<bool, StorageBackendError> {
+        let r = self.inner.read()?;
 
-        Ok(w.remove(key))
+        Ok(r.get(column).map_or(false, |c| c.contains_key(key)))
     }
 
-    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        let db = self.inner.clone();
-        let r = db.read().map_err(|e| StorageBackendError::GuardPoison {
-            error: format!("{}", e),
-        })?;
-
-        match r.get(key) {
-            None => Ok(None),
-            Some(v) => Ok(Some(v.clone())),
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError> {
+        let mut w = self.inner.write()?;
+
+        for operation in batch.into_operations() {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    w.entry(column).or_default().insert(key, value);
+                }
+                BatchOperation::Merge {
+                    column,
+                    key,
+                    value,
+                    operator,
+                } => merge_value(w.entry(column).or_default(), column, &key, &value, operator)?,
+                BatchOperation::Delete { column, key } => {
+                    if let Some(c) = w.get_mut(column) {
+                        c.remove(&key);
+                    }
+                }
+            }
         }
+        Ok(())
     }
 
-    fn contains(&self, key: &EntryHash) -> Result<bool, StorageBackendError> {
-        let db = self.inner.clone();
-        let r = db.read().map_err(|e| StorageBackendError::GuardPoison {
-            error: format!("{}", e),
-        })?;
-        Ok(r.contains_key(key))
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let r = self.inner.read()?;
+
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = match r.get(column) {
+            Some(c) => c.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            None => Vec::new(),
+        };
+        entries.sort_unstable_by(|(k1, _), (k2, _)| k1.cmp(k2));
+
+        Ok(iterate_sorted(entries, mode))
     }
 
     fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError> {
@@ -109,3 +138,49 @@ impl StorageBackend for InMemoryBackend {
         })
     }
 }
+
+fn merge_value(
+    c: &mut Column,
+    column: &'static str,
+    key: &[u8],
+    value: &[u8],
+    operator: Option<MergeOperator>,
+) -> Result<(), StorageBackendError> {
+    let merged = apply_merge_operator(
+        column,
+        key,
+        c.get(key).map(|v| v.as_slice()),
+        value,
+        operator,
+    )?;
+    c.insert(key.to_vec(), merged);
+    Ok(())
+}
+
+/// Creates iterator over (ascending) sorted `entries` according to `mode`
+pub(crate) fn iterate_sorted(
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    mode: BackendIteratorMode<'_>,
+) -> BackendIterator<'static> {
+    let into_boxed = |(k, v): (Vec<u8>, Vec<u8>)| -> Result<_, StorageBackendError> {
+        Ok((k.into_boxed_slice(), v.into_boxed_slice()))
+    };
+
+    match mode {
+        BackendIteratorMode::Start => Box::new(entries.into_iter().map(into_boxed)),
+        BackendIteratorMode::End => Box::new(entries.into_iter().rev().map(into_boxed)),
+        BackendIteratorMode::From(key, Direction::Forward) => {
+            let start = match entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+                Ok(idx) | Err(idx) => idx,
+            };
+            Box::new(entries.into_iter().skip(start).map(into_boxed))
+        }
+        BackendIteratorMode::From(key, Direction::Reverse) => {
+            let end = match entries.binary_search_by(|(k, _)| k.as_slice().cmp(key)) {
+                Ok(idx) => idx + 1,
+                Err(idx) => idx,
+            };
+            Box::new(entries.into_iter().take(end).rev().map(into_boxed))
+        }
+    }
+}
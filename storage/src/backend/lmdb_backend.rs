@@ -0,0 +1,358 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::RwLock;
+
+use lmdb::{
+    Cursor, Database, DatabaseFlags, Environment, EnvironmentFlags, RoCursor, RwTransaction,
+    Transaction, WriteFlags,
+};
+use lmdb_sys as ffi;
+
+use crate::persistent::database::{Direction, RocksDBStats};
+use crate::storage_backend::{
+    apply_merge_operator, BackendIterator, BackendIteratorMode, BatchOperation, MergeOperator,
+    StorageBackend, StorageBackendError, WriteBatch,
+};
+
+/// Maximal number of columns (named databases) in one environment
+const LMDB_MAX_DBS: u32 = 64;
+
+/// Default maximal size of the memory map (database size limit), LMDB allocates disk space lazily
+pub const LMDB_DEFAULT_MAP_SIZE: usize = 1024 * 1024 * 1024 * 1024;
+
+/// How many entries are read in one read transaction by iterator
+const LMDB_ITERATOR_CHUNK_SIZE: usize = 1024;
+
+/// LMDB backend, every column is stored in separate named database of single environment.
+///
+/// Named databases are created lazily on first access.
+pub struct LmdbBackend {
+    env: Environment,
+    dbs: RwLock<HashMap<&'static str, Database>>,
+}
+
+impl LmdbBackend {
+    /// Open (or create) LMDB environment in directory `path`, `map_size` (in bytes) limits the size of the database
+    pub fn open<P: AsRef<Path>>(path: P, map_size: usize) -> Result<Self, StorageBackendError> {
+        fs::create_dir_all(path.as_ref())?;
+
+        let env = Environment::new()
+            // iterators open short read transactions, which are not bound to thread,
+            // commits are not synced to disk (the same as RocksDB writes), disk is synced by `flush`
+            .set_flags(EnvironmentFlags::NO_TLS | EnvironmentFlags::NO_SYNC)
+            .set_max_dbs(LMDB_MAX_DBS)
+            .set_map_size(map_size)
+            .open(path.as_ref())?;
+
+        Ok(Self {
+            env,
+            dbs: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn db(&self, column: &'static str) -> Result<Database, StorageBackendError> {
+        if let Some(db) = self.dbs.read()?.get(column) {
+            return Ok(*db);
+        }
+
+        let mut dbs = self.dbs.write()?;
+        match dbs.get(column) {
+            Some(db) => Ok(*db),
+            None => {
+                let db = self.env.create_db(Some(column), DatabaseFlags::empty())?;
+                dbs.insert(column, db);
+                Ok(db)
+            }
+        }
+    }
+}
+
+fn get_value<T: Transaction>(
+    txn: &T,
+    db: Database,
+    key: &[u8],
+) -> Result<Option<Vec<u8>>, StorageBackendError> {
+    match txn.get(db, &key) {
+        Ok(value) => Ok(Some(value.to_vec())),
+        Err(lmdb::Error::NotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn merge_value(
+    txn: &mut RwTransaction,
+    db: Database,
+    column: &'static str,
+    key: &[u8],
+    value: &[u8],
+    operator: Option<MergeOperator>,
+) -> Result<(), StorageBackendError> {
+    let existing = get_value(&*txn, db, key)?;
+    let merged = apply_merge_operator(column, key, existing.as_deref(), value, operator)?;
+    txn.put(db, &key, &merged, WriteFlags::empty())?;
+    Ok(())
+}
+
+fn delete_value(
+    txn: &mut RwTransaction,
+    db: Database,
+    key: &[u8],
+) -> Result<(), StorageBackendError> {
+    match txn.del(db, &key, None) {
+        Ok(()) | Err(lmdb::Error::NotFound) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl StorageBackend for LmdbBackend {
+    fn name(&self) -> &'static str {
+        "lmdb"
+    }
+
+    fn is_persisted(&self) -> bool {
+        true
+    }
+
+    fn get(
+        &self,
+        column: &'static str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageBackendError> {
+        let db = self.db(column)?;
+        let txn = self.env.begin_ro_txn()?;
+
+        get_value(&txn, db, key)
+    }
+
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), StorageBackendError> {
+        let db = self.db(column)?;
+        let mut txn = self.env.begin_rw_txn()?;
+        txn.put(db, &key, &value, WriteFlags::empty())?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
+    ) -> Result<(), StorageBackendError> {
+        let db = self.db(column)?;
+        // LMDB allows single write transaction only, so read-modify-write is atomic
+        let mut txn = self.env.begin_rw_txn()?;
+        merge_value(&mut txn, db, column, key, value, operator)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError> {
+        let db = self.db(column)?;
+        let mut txn = self.env.begin_rw_txn()?;
+        delete_value(&mut txn, db, key)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError> {
+        let operations = batch.into_operations();
+
+        // resolve all databases before write transaction is opened (database creation needs own transaction)
+        let mut dbs = HashMap::new();
+        for operation in &operations {
+            let column = match operation {
+                BatchOperation::Put { column, .. }
+                | BatchOperation::Merge { column, .. }
+                | BatchOperation::Delete { column, .. } => *column,
+            };
+            if !dbs.contains_key(column) {
+                dbs.insert(column, self.db(column)?);
+            }
+        }
+
+        let mut txn = self.env.begin_rw_txn()?;
+        for operation in operations {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    txn.put(dbs[column], &key, &value, WriteFlags::empty())?
+                }
+                BatchOperation::Merge {
+                    column,
+                    key,
+                    value,
+                    operator,
+                } => merge_value(&mut txn, dbs[column], column, &key, &value, operator)?,
+                BatchOperation::Delete { column, key } => {
+                    delete_value(&mut txn, dbs[column], &key)?
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let (seek, direction) = match mode {
+            BackendIteratorMode::Start => (Seek::First, Direction::Forward),
+            BackendIteratorMode::End => (Seek::Last, Direction::Reverse),
+            BackendIteratorMode::From(key, Direction::Forward) => {
+                (Seek::AtOrAfter(key.to_vec()), Direction::Forward)
+            }
+            BackendIteratorMode::From(key, Direction::Reverse) => {
+                (Seek::AtOrBefore(key.to_vec()), Direction::Reverse)
+            }
+        };
+
+        Ok(Box::new(LmdbIterator {
+            backend: self,
+            db: self.db(column)?,
+            direction,
+            seek: Some(seek),
+            chunk: VecDeque::new(),
+        }))
+    }
+
+    fn flush(&self) -> Result<(), StorageBackendError> {
+        self.env.sync(true)?;
+        Ok(())
+    }
+
+    fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError> {
+        //TODO TE-431 StorageBackent::get_mem_use_stats() should be implemented for all backends
+        Ok(RocksDBStats {
+            mem_table_total: 0,
+            mem_table_unflushed: 0,
+            mem_table_readers_total: 0,
+            cache_total: 0,
+        })
+    }
+}
+
+/// Position, where the next chunk of iterator starts
+enum Seek {
+    First,
+    Last,
+    AtOrAfter(Vec<u8>),
+    AtOrBefore(Vec<u8>),
+    After(Vec<u8>),
+    Before(Vec<u8>),
+}
+
+/// Iterator reading entries in chunks, every chunk is read in separate short read transaction,
+/// so long iterations do not block LMDB from reusing free pages.
+struct LmdbIterator<'a> {
+    backend: &'a LmdbBackend,
+    db: Database,
+    direction: Direction,
+    /// `None` when all entries were read
+    seek: Option<Seek>,
+    chunk: VecDeque<(Box<[u8]>, Box<[u8]>)>,
+}
+
+type CursorEntry<'txn> = (Option<&'txn [u8]>, &'txn [u8]);
+
+/// Positions cursor at the first key greater or equal to `key`, returned key is never `None`
+fn set_range<'txn>(
+    cursor: &RoCursor<'txn>,
+    key: &'txn [u8],
+) -> Result<CursorEntry<'txn>, lmdb::Error> {
+    cursor
+        .get(Some(key), None, ffi::MDB_SET_RANGE)
+        .map(|(found, value)| (Some(found.unwrap_or(key)), value))
+}
+
+impl<'a> LmdbIterator<'a> {
+    fn read_chunk(&mut self, seek: Seek) -> Result<(), lmdb::Error> {
+        let backend = self.backend;
+        let txn = backend.env.begin_ro_txn()?;
+        let cursor = txn.open_ro_cursor(self.db)?;
+
+        let (step, first): (_, Result<CursorEntry, lmdb::Error>) = match &seek {
+            Seek::First => (ffi::MDB_NEXT, cursor.get(None, None, ffi::MDB_FIRST)),
+            Seek::Last => (ffi::MDB_PREV, cursor.get(None, None, ffi::MDB_LAST)),
+            Seek::AtOrAfter(key) => (ffi::MDB_NEXT, set_range(&cursor, key)),
+            Seek::After(key) => (
+                ffi::MDB_NEXT,
+                match set_range(&cursor, key) {
+                    Ok((Some(found), _)) if found == key.as_slice() => {
+                        cursor.get(None, None, ffi::MDB_NEXT)
+                    }
+                    result => result,
+                },
+            ),
+            Seek::AtOrBefore(key) => (
+                ffi::MDB_PREV,
+                match set_range(&cursor, key) {
+                    Ok((Some(found), value)) if found == key.as_slice() => Ok((Some(found), value)),
+                    Ok(_) => cursor.get(None, None, ffi::MDB_PREV),
+                    Err(lmdb::Error::NotFound) => cursor.get(None, None, ffi::MDB_LAST),
+                    Err(e) => Err(e),
+                },
+            ),
+            Seek::Before(key) => (
+                ffi::MDB_PREV,
+                match set_range(&cursor, key) {
+                    Ok(_) => cursor.get(None, None, ffi::MDB_PREV),
+                    Err(lmdb::Error::NotFound) => cursor.get(None, None, ffi::MDB_LAST),
+                    Err(e) => Err(e),
+                },
+            ),
+        };
+
+        let mut entry: Result<CursorEntry, lmdb::Error> = first;
+        let mut last_key = None;
+        while self.chunk.len() < LMDB_ITERATOR_CHUNK_SIZE {
+            match entry {
+                Ok((Some(key), value)) => {
+                    self.chunk.push_back((key.into(), value.into()));
+                    last_key = Some(key.to_vec());
+                }
+                Ok((None, _)) | Err(lmdb::Error::NotFound) => {
+                    // end of database
+                    last_key = None;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+            entry = cursor.get(None, None, step);
+        }
+
+        let direction = self.direction;
+        self.seek = last_key.map(|key| match direction {
+            Direction::Forward => Seek::After(key),
+            Direction::Reverse => Seek::Before(key),
+        });
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for LmdbIterator<'a> {
+    type Item = Result<(Box<[u8]>, Box<[u8]>), StorageBackendError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.chunk.is_empty() {
+            let seek = self.seek.take()?;
+            // error is returned as the last item, iteration cannot continue without valid position
+            if let Err(e) = self.read_chunk(seek) {
+                self.chunk.clear();
+                self.seek = None;
+                return Some(Err(e.into()));
+            }
+        }
+        self.chunk.pop_front().map(Ok)
+    }
+}
@@ -3,10 +3,12 @@
 
 pub mod btree_map;
 pub mod in_memory_backend;
+pub mod lmdb_backend;
 pub mod rocksdb_backend;
 pub mod sled_backend;
 
 pub use btree_map::*;
 pub use in_memory_backend::*;
+pub use lmdb_backend::*;
 pub use rocksdb_backend::*;
 pub use sled_backend::*;
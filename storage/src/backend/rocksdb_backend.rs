@@ -1,38 +1,30 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crate::merkle_storage::{ContextValue, EntryHash};
-use crate::persistent::database::GetInMemStats;
-use crate::persistent::database::KeyValueStoreWithSchema;
-use crate::persistent::database::RocksDBStats;
-use crate::storage_backend::{StorageBackend, StorageBackendError};
-use crate::MerkleStorage;
-use rocksdb::WriteBatch;
-use rocksdb::{WriteOptions, DB};
+use std::path::Path;
+
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::{ColumnFamily, WriteOptions, DB};
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
-use std::sync::Arc;
 
-pub struct RocksDBBackend {
-    column_name: &'static str,
-    inner: Arc<DB>,
+use crate::persistent::database::{GetInMemStats, RocksDBStats};
+use crate::storage_backend::{
+    BackendIterator, BackendIteratorMode, BatchOperation, MergeOperator, StorageBackend,
+    StorageBackendError, WriteBatch,
+};
+
+fn cf_handle<'a>(
+    db: &'a DB,
+    column: &'static str,
+) -> Result<&'a ColumnFamily, StorageBackendError> {
+    db.cf_handle(column)
+        .ok_or(StorageBackendError::MissingColumnFamily { name: column })
 }
 
-impl RocksDBBackend {
-    pub fn new(db: Arc<DB>, column_name: &'static str) -> Self {
-        RocksDBBackend {
-            inner: db,
-            column_name,
-        }
-    }
-}
-
-impl RocksDBBackend {
-    fn default_write_options() -> WriteOptions {
-        let mut opts = WriteOptions::default();
-        opts.set_sync(false);
-        opts
-    }
+fn default_write_options() -> WriteOptions {
+    let mut opts = WriteOptions::default();
+    opts.set_sync(false);
+    opts
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -43,101 +35,130 @@ pub struct RocksDBBackendStats {
     cache_total: u64,
 }
 
-impl StorageBackend for RocksDBBackend {
+/// RocksDB backend, every column is stored in separate column family.
+/// Column families have to be registered when database is opened (see [open_kv](crate::persistent::open_kv)).
+impl StorageBackend for DB {
+    fn name(&self) -> &'static str {
+        "rocksdb"
+    }
+
     fn is_persisted(&self) -> bool {
         true
     }
 
-    fn put(&mut self, key: &EntryHash, value: ContextValue) -> Result<bool, StorageBackendError> {
-        let cf = self.inner.cf_handle(&self.column_name).ok_or(
-            StorageBackendError::MissingColumnFamily {
-                name: &self.column_name,
-            },
-        )?;
-
-        self.inner
-            .put_cf_opt(
-                cf,
-                &key.as_ref()[..],
-                &value,
-                &Self::default_write_options(),
-            )
+    fn get(
+        &self,
+        column: &'static str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageBackendError> {
+        let cf = cf_handle(self, column)?;
+
+        self.get_cf(cf, key).map_err(StorageBackendError::from)
+    }
+
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), StorageBackendError> {
+        let cf = cf_handle(self, column)?;
+
+        self.put_cf_opt(cf, key, value, &default_write_options())
             .map_err(StorageBackendError::from)
-            .map(|_| true)
     }
 
-    fn put_batch(
-        &mut self,
-        batch: Vec<(EntryHash, ContextValue)>,
+    /// RocksDB uses merge operator registered in column family descriptor, so `operator` is ignored
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        _operator: Option<MergeOperator>,
     ) -> Result<(), StorageBackendError> {
-        let mut rocksb_batch = WriteBatch::default(); // batch containing DB key values to persist
-
-        for (k, v) in batch.iter() {
-            (self.inner.deref() as &dyn KeyValueStoreWithSchema<MerkleStorage>).put_batch(
-                &mut rocksb_batch,
-                &k,
-                &v,
-            )?;
-        }
+        let cf = cf_handle(self, column)?;
 
-        (self.inner.deref() as &dyn KeyValueStoreWithSchema<MerkleStorage>)
-            .write_batch(rocksb_batch)?;
+        self.merge_cf_opt(cf, key, value, &default_write_options())
+            .map_err(StorageBackendError::from)
+    }
+
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError> {
+        let cf = cf_handle(self, column)?;
+
+        self.delete_cf_opt(cf, key, &default_write_options())
+            .map_err(StorageBackendError::from)
+    }
+
+    fn contains(&self, column: &'static str, key: &[u8]) -> Result<bool, StorageBackendError> {
+        let cf = cf_handle(self, column)?;
 
-        Ok(())
+        let val = self.get_pinned_cf(cf, key)?;
+        Ok(val.is_some())
     }
 
-    fn merge(&mut self, key: &EntryHash, value: ContextValue) -> Result<(), StorageBackendError> {
-        let cf = self.inner.cf_handle(&self.column_name).ok_or(
-            StorageBackendError::MissingColumnFamily {
-                name: &self.column_name,
-            },
-        )?;
-
-        self.inner
-            .merge_cf_opt(
-                cf,
-                &key.as_ref()[..],
-                &value,
-                &Self::default_write_options(),
-            )
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError> {
+        let mut rocksdb_batch = rocksdb::WriteBatch::default();
+
+        for operation in batch.into_operations() {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    rocksdb_batch.put_cf(cf_handle(self, column)?, key, value)
+                }
+                BatchOperation::Merge {
+                    column, key, value, ..
+                } => rocksdb_batch.merge_cf(cf_handle(self, column)?, key, value),
+                BatchOperation::Delete { column, key } => {
+                    rocksdb_batch.delete_cf(cf_handle(self, column)?, key)
+                }
+            }
+        }
+
+        self.write_opt(rocksdb_batch, &default_write_options())
             .map_err(StorageBackendError::from)
     }
 
-    /// Warning: always returns None.
-    fn delete(&mut self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        let cf = self.inner.cf_handle(&self.column_name).ok_or(
-            StorageBackendError::MissingColumnFamily {
-                name: &self.column_name,
-            },
-        )?;
-
-        self.inner
-            .delete_cf_opt(cf, &key.as_ref()[..], &Self::default_write_options())
-            .map_err(StorageBackendError::from)?;
-        Ok(None)
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let cf = cf_handle(self, column)?;
+
+        let iter = match mode {
+            BackendIteratorMode::Start => self.iterator_cf(cf, rocksdb::IteratorMode::Start),
+            BackendIteratorMode::End => self.iterator_cf(cf, rocksdb::IteratorMode::End),
+            BackendIteratorMode::From(key, direction) => {
+                self.iterator_cf(cf, rocksdb::IteratorMode::From(key, direction.into()))
+            }
+        };
+
+        Ok(Box::new(iter.map(Ok)))
+    }
+
+    /// RocksDB uses prefix extractor registered in column family descriptor, so `prefix_len` is ignored
+    fn prefix_iterator<'a>(
+        &'a self,
+        column: &'static str,
+        key: &[u8],
+        _prefix_len: usize,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let cf = cf_handle(self, column)?;
+
+        Ok(Box::new(self.prefix_iterator_cf(cf, key).map(Ok)))
     }
 
-    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        let cf = self.inner.cf_handle(&self.column_name).ok_or(
-            StorageBackendError::MissingColumnFamily {
-                name: &self.column_name,
-            },
-        )?;
-
-        let v = self
-            .inner
-            .get_cf(cf, &key.as_ref()[..])
-            .map_err(StorageBackendError::from)?;
-        Ok(v)
+    fn flush(&self) -> Result<(), StorageBackendError> {
+        DB::flush(self).map_err(StorageBackendError::from)
     }
 
-    fn contains(&self, key: &EntryHash) -> Result<bool, StorageBackendError> {
-        self.get(key).map(|v| v.is_some())
+    fn checkpoint(&self, path: &Path) -> Result<(), StorageBackendError> {
+        Checkpoint::new(self)?
+            .create_checkpoint(path)
+            .map_err(StorageBackendError::from)
     }
 
     fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError> {
-        self.inner
-            .get_stats()
+        self.get_stats()
             .map_err(|_| StorageBackendError::BackendError)
     }
 }
@@ -1,62 +1,160 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crate::merkle_storage::{ContextValue, EntryHash};
-use crate::persistent::database::RocksDBStats;
-use crate::storage_backend::{StorageBackend, StorageBackendError};
+use std::path::Path;
 
+use crate::persistent::database::{Direction, RocksDBStats};
+use crate::storage_backend::{
+    apply_merge_operator, BackendIterator, BackendIteratorMode, BatchOperation, MergeOperator,
+    StorageBackend, StorageBackendError, WriteBatch,
+};
+
+/// Sled backend, every column is stored in separate [sled::Tree]
 pub struct SledBackend {
-    inner: sled::Tree,
+    inner: sled::Db,
 }
 
 impl SledBackend {
-    pub fn new(db: sled::Tree) -> Self {
+    pub fn new(db: sled::Db) -> Self {
         SledBackend { inner: db }
     }
+
+    /// Open (or create) sled database at given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StorageBackendError> {
+        Ok(Self::new(sled::open(path)?))
+    }
+
+    fn tree(&self, column: &'static str) -> Result<sled::Tree, StorageBackendError> {
+        self.inner
+            .open_tree(column)
+            .map_err(StorageBackendError::from)
+    }
+}
+
+fn merge_value(
+    tree: &sled::Tree,
+    column: &'static str,
+    key: &[u8],
+    value: &[u8],
+    operator: Option<MergeOperator>,
+) -> Result<(), StorageBackendError> {
+    let mut merge_result = Ok(());
+    tree.fetch_and_update(key, |existing| {
+        match apply_merge_operator(column, key, existing, value, operator) {
+            Ok(merged) => {
+                merge_result = Ok(());
+                Some(merged)
+            }
+            Err(e) => {
+                // keep existing value untouched
+                merge_result = Err(e);
+                existing.map(|v| v.to_vec())
+            }
+        }
+    })?;
+    merge_result
+}
+
+/// Converts sled iterator to backend iterator
+fn into_backend_iterator<'a, I>(iter: I) -> BackendIterator<'a>
+where
+    I: Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>> + 'a,
+{
+    Box::new(iter.map(|item| {
+        item.map(|(k, v)| (k.to_vec().into_boxed_slice(), v.to_vec().into_boxed_slice()))
+            .map_err(StorageBackendError::from)
+    }))
 }
 
 impl StorageBackend for SledBackend {
+    fn name(&self) -> &'static str {
+        "sled"
+    }
+
     fn is_persisted(&self) -> bool {
         true
     }
 
-    fn put(&mut self, key: &EntryHash, value: ContextValue) -> Result<bool, StorageBackendError> {
-        Ok(self
-            .inner
-            .insert(&key.as_ref()[..], value)
-            .map(|v| v.is_none())?)
+    fn get(
+        &self,
+        column: &'static str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageBackendError> {
+        Ok(self.tree(column)?.get(key)?.map(|v| v.to_vec()))
     }
 
-    fn put_batch(
-        &mut self,
-        batch: Vec<(EntryHash, ContextValue)>,
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
     ) -> Result<(), StorageBackendError> {
-        for (k, v) in batch.into_iter() {
-            self.put(&k, v)?;
-        }
+        self.tree(column)?.insert(key, value)?;
         Ok(())
     }
 
-    fn merge(&mut self, key: &EntryHash, value: ContextValue) -> Result<(), StorageBackendError> {
-        self.inner.insert(&key.as_ref()[..], value)?;
-        Ok(())
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
+    ) -> Result<(), StorageBackendError> {
+        merge_value(&self.tree(column)?, column, key, value, operator)
     }
 
-    fn delete(&mut self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        Ok(self.inner.remove(&key.as_ref()[..])?.map(|v| v.to_vec()))
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError> {
+        self.tree(column)?.remove(key)?;
+        Ok(())
     }
 
-    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError> {
-        let r = self.inner.get(&key.as_ref()[..])?;
+    fn contains(&self, column: &'static str, key: &[u8]) -> Result<bool, StorageBackendError> {
+        Ok(self.tree(column)?.contains_key(key)?)
+    }
 
-        match r {
-            None => Err(StorageBackendError::BackendError),
-            Some(v) => Ok(Some(v.to_vec())),
+    /// Sled batches are atomic per tree, so batch spanning more columns is not written atomically
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError> {
+        for operation in batch.into_operations() {
+            match operation {
+                BatchOperation::Put { column, key, value } => {
+                    self.tree(column)?.insert(key, value)?;
+                }
+                BatchOperation::Merge {
+                    column,
+                    key,
+                    value,
+                    operator,
+                } => merge_value(&self.tree(column)?, column, &key, &value, operator)?,
+                BatchOperation::Delete { column, key } => {
+                    self.tree(column)?.remove(key)?;
+                }
+            }
         }
+        Ok(())
     }
 
-    fn contains(&self, key: &EntryHash) -> Result<bool, StorageBackendError> {
-        Ok(self.inner.contains_key(&key.as_ref()[..])?)
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let tree = self.tree(column)?;
+
+        Ok(match mode {
+            BackendIteratorMode::Start => into_backend_iterator(tree.iter()),
+            BackendIteratorMode::End => into_backend_iterator(tree.iter().rev()),
+            BackendIteratorMode::From(key, Direction::Forward) => {
+                into_backend_iterator(tree.range(key.to_vec()..))
+            }
+            BackendIteratorMode::From(key, Direction::Reverse) => {
+                into_backend_iterator(tree.range(..=key.to_vec()).rev())
+            }
+        })
+    }
+
+    fn flush(&self) -> Result<(), StorageBackendError> {
+        self.inner.flush()?;
+        Ok(())
     }
 
     fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError> {
@@ -0,0 +1,375 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Online backup and restore of the whole [PersistentStorage].
+//!
+//! Backup is created while node is running. All commit logs are frozen (appends are blocked) and flushed,
+//! offsets of their last records are remembered and RocksDB checkpoints are created for all databases
+//! (other storage backends do not support checkpoints).
+//! Because block data are always appended to commit log before they are indexed in database,
+//! database checkpoint cannot reference any record, which is not part of the backup.
+//! Commit logs are copied after appends are unblocked and truncated to remembered offsets.
+//!
+//! Backup directory has the same layout as storage directory of the node:
+//! - `db`, `context`, `context_actions` - RocksDB checkpoints
+//! - one directory for every commit log (e.g. `block_storage`)
+//! - `backup.manifest` - [BackupManifest]
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use failure::Fail;
+use rocksdb::{Options, DB};
+use serde::{Deserialize, Serialize};
+use slog::{info, Logger};
+
+use crypto::hash::ChainId;
+
+use crate::persistent::commit_log::truncate_commit_log;
+use crate::persistent::{
+    BincodeEncoded, CommitLogError, DBError, KeyValueSchema, PersistentStorage, SchemaError,
+    StorageType,
+};
+use crate::storage_backend::StorageBackend;
+use crate::system_storage::DbVersion;
+use crate::{StorageError, SystemStorage};
+
+/// Directory of the operational database in backup
+pub const BACKUP_DB_DIR: &str = "db";
+/// Directory of the context database in backup
+pub const BACKUP_CONTEXT_DIR: &str = "context";
+/// Directory of the context actions database in backup
+pub const BACKUP_CONTEXT_ACTIONS_DIR: &str = "context_actions";
+/// File with [BackupManifest]
+const BACKUP_MANIFEST_FILE: &str = "backup.manifest";
+
+/// Possible errors for backup/restore
+#[derive(Debug, Fail)]
+pub enum BackupError {
+    #[fail(display = "Database error: {}", error)]
+    DBError { error: DBError },
+    #[fail(display = "Commit log error: {}", error)]
+    CommitLogError { error: CommitLogError },
+    #[fail(display = "Storage error: {}", error)]
+    StorageError { error: StorageError },
+    #[fail(display = "I/O error: {}", error)]
+    IOError { error: io::Error },
+    #[fail(display = "Target directory is not empty: {}", path)]
+    TargetNotEmpty { path: String },
+    #[fail(display = "Invalid backup {}, reason: {}", path, reason)]
+    InvalidBackup { path: String, reason: String },
+    #[fail(
+        display = "Backup was created for another chain, expected: {}, found: {}",
+        expected, found
+    )]
+    ChainIdMismatch { expected: String, found: String },
+    #[fail(
+        display = "Backup database version {} is newer than supported version {}",
+        found, supported
+    )]
+    UnsupportedDbVersion {
+        found: DbVersion,
+        supported: DbVersion,
+    },
+}
+
+impl From<DBError> for BackupError {
+    fn from(error: DBError) -> Self {
+        BackupError::DBError { error }
+    }
+}
+
+impl From<rocksdb::Error> for BackupError {
+    fn from(error: rocksdb::Error) -> Self {
+        BackupError::DBError {
+            error: error.into(),
+        }
+    }
+}
+
+impl From<CommitLogError> for BackupError {
+    fn from(error: CommitLogError) -> Self {
+        BackupError::CommitLogError { error }
+    }
+}
+
+impl From<StorageError> for BackupError {
+    fn from(error: StorageError) -> Self {
+        BackupError::StorageError { error }
+    }
+}
+
+impl From<SchemaError> for BackupError {
+    fn from(error: SchemaError) -> Self {
+        BackupError::DBError {
+            error: error.into(),
+        }
+    }
+}
+
+impl From<io::Error> for BackupError {
+    fn from(error: io::Error) -> Self {
+        BackupError::IOError { error }
+    }
+}
+
+/// Describes content of the backup, stored together with the backup
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BackupManifest {
+    /// Chain id (base58) stored in [SystemStorage] at the time of backup
+    pub chain_id: Option<String>,
+    /// Database version stored in [SystemStorage] at the time of backup
+    pub db_version: Option<DbVersion>,
+    /// Commit logs included in backup with offsets of their last records (None, if commit log was empty)
+    pub commit_logs: BTreeMap<String, Option<u64>>,
+}
+
+impl BincodeEncoded for BackupManifest {}
+
+/// Creates consistent point-in-time copy of all stores to `target_dir` without stopping the node.
+///
+/// `target_dir` must not exist or must be empty.
+pub fn backup_storage<P: AsRef<Path>>(
+    persistent_storage: &PersistentStorage,
+    target_dir: P,
+    log: &Logger,
+) -> Result<BackupManifest, BackupError> {
+    let target_dir = target_dir.as_ref();
+    ensure_empty_dir(target_dir)?;
+    info!(log, "Storage backup started"; "target_dir" => target_dir.display().to_string());
+
+    let clog = persistent_storage.clog();
+    let commit_logs = clog.with_frozen(|offsets| {
+        // operational database goes first, contexts can only be ahead of it (never behind),
+        // which is fine, because they are referenced from operational database
+        for (storage_type, dir) in vec![
+            (StorageType::Database, BACKUP_DB_DIR),
+            (StorageType::Context, BACKUP_CONTEXT_DIR),
+            (StorageType::ContextAction, BACKUP_CONTEXT_ACTIONS_DIR),
+        ] {
+            persistent_storage
+                .kv(storage_type)
+                .checkpoint(&target_dir.join(dir))
+                .map_err(DBError::from)?;
+        }
+        Ok::<_, BackupError>(
+            offsets
+                .iter()
+                .map(|(name, offset)| (name.clone(), *offset))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    })??;
+
+    // commit logs are only appended, so we can copy them without blocking and truncate them afterwards
+    for (name, last_offset) in &commit_logs {
+        let commit_log_dir = target_dir.join(name);
+        copy_dir(&clog.base_path().join(name), &commit_log_dir)?;
+        truncate_commit_log(&commit_log_dir, *last_offset)?;
+    }
+
+    let system_storage = SystemStorage::new(persistent_storage.kv(StorageType::Database));
+    let manifest = BackupManifest {
+        chain_id: system_storage
+            .get_chain_id()?
+            .map(|chain_id| chain_id.to_base58_check()),
+        db_version: system_storage.get_db_version()?,
+        commit_logs,
+    };
+    fs::write(target_dir.join(BACKUP_MANIFEST_FILE), manifest.encode()?)?;
+
+    info!(log, "Storage backup finished";
+               "target_dir" => target_dir.display().to_string(),
+               "chain_id" => manifest.chain_id.as_ref().map_or("-none-", |c| c.as_str()),
+               "db_version" => manifest.db_version);
+    Ok(manifest)
+}
+
+/// Restores backup created by [backup_storage] to `target_dir` (storage directory of the node).
+///
+/// Before anything is copied, chain id and database version stored in backup's [SystemStorage] are checked.
+/// Older database version is accepted, because it is migrated on node startup.
+pub fn restore_storage<B: AsRef<Path>, T: AsRef<Path>>(
+    backup_dir: B,
+    target_dir: T,
+    expected_chain_id: &ChainId,
+    supported_db_version: DbVersion,
+    log: &Logger,
+) -> Result<BackupManifest, BackupError> {
+    let backup_dir = backup_dir.as_ref();
+    let target_dir = target_dir.as_ref();
+    let invalid_backup = |reason: &str| BackupError::InvalidBackup {
+        path: backup_dir.display().to_string(),
+        reason: reason.to_string(),
+    };
+
+    let manifest = fs::read(backup_dir.join(BACKUP_MANIFEST_FILE))
+        .map_err(|_| invalid_backup("missing manifest"))
+        .and_then(|bytes| {
+            BackupManifest::decode(&bytes).map_err(|_| invalid_backup("invalid manifest"))
+        })?;
+
+    // check system info directly in backup database
+    let (chain_id, db_version) = {
+        let db = Arc::new(DB::open_cf_for_read_only(
+            &Options::default(),
+            backup_dir.join(BACKUP_DB_DIR),
+            vec![SystemStorage::name()],
+            false,
+        )?);
+        let system_storage = SystemStorage::new(db);
+        (
+            system_storage.get_chain_id()?,
+            system_storage.get_db_version()?,
+        )
+    };
+    match chain_id {
+        Some(chain_id) if chain_id == *expected_chain_id => (),
+        Some(chain_id) => {
+            return Err(BackupError::ChainIdMismatch {
+                expected: expected_chain_id.to_base58_check(),
+                found: chain_id.to_base58_check(),
+            })
+        }
+        None => return Err(invalid_backup("chain id is not stored")),
+    }
+    match db_version {
+        Some(db_version) if db_version > supported_db_version => {
+            return Err(BackupError::UnsupportedDbVersion {
+                found: db_version,
+                supported: supported_db_version,
+            })
+        }
+        Some(_) => (),
+        None => return Err(invalid_backup("database version is not stored")),
+    }
+
+    ensure_empty_dir(target_dir)?;
+    info!(log, "Storage restore started";
+               "backup_dir" => backup_dir.display().to_string(),
+               "target_dir" => target_dir.display().to_string());
+
+    for dir in &[
+        BACKUP_DB_DIR,
+        BACKUP_CONTEXT_DIR,
+        BACKUP_CONTEXT_ACTIONS_DIR,
+    ] {
+        copy_dir(&backup_dir.join(dir), &target_dir.join(dir))?;
+    }
+    for commit_log in manifest.commit_logs.keys() {
+        copy_dir(&backup_dir.join(commit_log), &target_dir.join(commit_log))?;
+    }
+
+    info!(log, "Storage restore finished";
+               "target_dir" => target_dir.display().to_string(),
+               "db_version" => db_version);
+    Ok(manifest)
+}
+
+/// Creates directory (if needed) and checks, that it is empty
+fn ensure_empty_dir(path: &Path) -> Result<(), BackupError> {
+    fs::create_dir_all(path)?;
+    if fs::read_dir(path)?.next().is_some() {
+        return Err(BackupError::TargetNotEmpty {
+            path: path.display().to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), io::Error> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use failure::Error;
+    use slog::{Discard, Logger};
+    use tempfile::TempDir;
+
+    use tezos_messages::p2p::binary_message::BinaryMessage;
+    use tezos_messages::p2p::encoding::prelude::BlockHeader;
+
+    use crate::tests_common::TmpStorage;
+    use crate::{BlockHeaderWithHash, BlockStorage, BlockStorageReader};
+
+    use super::*;
+
+    #[test]
+    fn test_backup_and_restore() -> Result<(), Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let tmp_dir = TempDir::new()?;
+        let backup_dir = &tmp_dir.path().join("backup");
+        let restore_dir = &tmp_dir.path().join("target");
+        let chain_id: ChainId = vec![1, 2, 3, 4].try_into()?;
+        let block = make_test_block_header()?;
+
+        {
+            let tmp_storage = TmpStorage::create(tmp_dir.path().join("source"))?;
+            let storage = tmp_storage.storage();
+            let mut system_storage = SystemStorage::new(storage.kv(StorageType::Database));
+            system_storage.set_chain_id(&chain_id)?;
+            system_storage.set_db_version(17)?;
+            BlockStorage::new(storage).put_block_header(&block)?;
+
+            let manifest = backup_storage(storage, backup_dir, &log)?;
+            assert_eq!(Some(chain_id.to_base58_check()), manifest.chain_id);
+            assert_eq!(Some(17), manifest.db_version);
+            assert_eq!(Some(&Some(0)), manifest.commit_logs.get("block_storage"));
+
+            // backup cannot be created to non-empty directory
+            assert!(matches!(
+                backup_storage(storage, backup_dir, &log),
+                Err(BackupError::TargetNotEmpty { .. })
+            ));
+        }
+
+        // another chain
+        let other_chain_id: ChainId = vec![4, 3, 2, 1].try_into()?;
+        assert!(matches!(
+            restore_storage(backup_dir, restore_dir, &other_chain_id, 17, &log),
+            Err(BackupError::ChainIdMismatch { .. })
+        ));
+        // newer database version
+        assert!(matches!(
+            restore_storage(backup_dir, restore_dir, &chain_id, 16, &log),
+            Err(BackupError::UnsupportedDbVersion { .. })
+        ));
+
+        restore_storage(backup_dir, restore_dir, &chain_id, 17, &log)?;
+        {
+            let tmp_storage = TmpStorage::initialize(restore_dir, false, true)?;
+            let storage = tmp_storage.storage();
+            assert_eq!(
+                Some(block.clone()),
+                BlockStorage::new(storage).get(&block.hash)?
+            );
+            assert_eq!(
+                Some(chain_id.clone()),
+                SystemStorage::new(storage.kv(StorageType::Database)).get_chain_id()?
+            );
+        }
+
+        Ok(())
+    }
+
+    fn make_test_block_header() -> Result<BlockHeaderWithHash, Error> {
+        let message_bytes = hex::decode("00006d6e0102dd00defaf70c53e180ea148b349a6feb4795610b2abc7b07fe91ce50a90814000000005c1276780432bc1d3a28df9a67b363aa1638f807214bb8987e5f9c0abcbd69531facffd1c80000001100000001000000000800000000000c15ef15a6f54021cb353780e2847fb9c546f1d72c1dc17c3db510f45553ce501ce1de000000000003c762c7df00a856b8bfcaf0676f069f825ca75f37f2bee9fe55ba109cec3d1d041d8c03519626c0c0faa557e778cb09d2e0c729e8556ed6a7a518c84982d1f2682bc6aa753f")?;
+        let block_header = BlockHeaderWithHash::new(BlockHeader::from_bytes(message_bytes)?)?;
+        Ok(block_header)
+    }
+}
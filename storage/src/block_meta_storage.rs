@@ -18,6 +18,7 @@ use crate::persistent::{
     PersistentStorage, SchemaError,
 };
 use crate::predecessor_storage::{PredecessorKey, PredecessorStorage};
+use crate::storage_backend::MergeOperator;
 use crate::{num_from_slice, persistent::StorageType};
 use crate::{BlockHeaderWithHash, StorageError};
 
@@ -179,6 +180,21 @@ impl BlockMetaStorage {
         self.kv.merge(block_hash, meta).map_err(StorageError::from)
     }
 
+    /// Marks applied block as not applied, returns true, if block was applied.
+    ///
+    /// Flags cannot be cleared by [put](BlockMetaStorage::put) (merge), so metadata are overwritten here,
+    /// which is safe just when nobody else writes to the storage (e.g. offline storage repair).
+    pub fn reset_applied(&self, block_hash: &BlockHash) -> Result<bool, StorageError> {
+        match self.get(block_hash)? {
+            Some(mut meta) if meta.is_applied => {
+                meta.is_applied = false;
+                self.kv.put(block_hash, &meta).map_err(StorageError::from)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
     #[inline]
     pub fn get(&self, block_hash: &BlockHash) -> Result<Option<Meta>, StorageError> {
         self.kv.get(block_hash).map_err(StorageError::from)
@@ -522,63 +538,74 @@ impl KeyValueSchema for BlockMetaStorage {
     fn name() -> &'static str {
         "block_meta_storage"
     }
+
+    fn merge_operator() -> Option<MergeOperator> {
+        Some(merge_meta_operand)
+    }
 }
 
 fn merge_meta_value(
-    _new_key: &[u8],
+    new_key: &[u8],
     existing_val: Option<&[u8]>,
     operands: &mut MergeOperands,
 ) -> Option<Vec<u8>> {
-    if let Some(val) = existing_val {
-        if val.len() < LEN_FIXED_META {
-            return None;
-        }
-    }
-
     let mut result = existing_val.map(|v| v.to_vec());
 
     for op in operands {
-        match result {
-            Some(ref mut val) => {
-                if op.len() < LEN_FIXED_META {
-                    return None;
-                }
+        result = Some(merge_meta_operand(new_key, result.as_deref(), op)?);
+    }
 
-                let mask_val = val[IDX_MASK];
-                let mask_op = op[IDX_MASK];
+    result
+}
 
-                // merge `mask(1)`
-                val[IDX_MASK] = mask_val | mask_op;
+/// Merges single operand to the existing value (used directly by backends without native merge support)
+fn merge_meta_operand(_key: &[u8], existing_val: Option<&[u8]>, op: &[u8]) -> Option<Vec<u8>> {
+    let mut val = match existing_val {
+        Some(val) => val.to_vec(),
+        None => return Some(op.to_vec()),
+    };
 
-                // if op has predecessor and val has not, copy it from op to val
-                if has_predecessor!(mask_op) && !has_predecessor!(mask_val) {
-                    val.splice(
-                        IDX_PREDECESSOR..IDX_LEVEL,
-                        op[IDX_PREDECESSOR..IDX_LEVEL].iter().cloned(),
-                    );
-                }
+    if val.len() < LEN_FIXED_META || op.len() < LEN_FIXED_META {
+        return None;
+    }
 
-                // replace op (successors count + successors) to val
-                let val_successors_count = successors_count!(val);
-                let op_successors_count = successors_count!(op);
-                if (has_successor!(mask_op) && !has_successor!(mask_val))
-                    || (val_successors_count != op_successors_count)
-                {
-                    val.truncate(LEN_FIXED_META);
-                    val.splice(
-                        IDX_SUCCESSOR_COUNT..,
-                        op[IDX_SUCCESSOR_COUNT..].iter().cloned(),
-                    );
-                }
+    let mask_val = val[IDX_MASK];
+    let mask_op = op[IDX_MASK];
 
-                let total_len = total_len(op_successors_count);
-                debug_assert_eq!(total_len, val.len(), "Invalid length after merge operator was applied. Was expecting {} but found {}.", total_len, val.len());
-            }
-            None => result = Some(op.to_vec()),
-        }
+    // merge `mask(1)`
+    val[IDX_MASK] = mask_val | mask_op;
+
+    // if op has predecessor and val has not, copy it from op to val
+    if has_predecessor!(mask_op) && !has_predecessor!(mask_val) {
+        val.splice(
+            IDX_PREDECESSOR..IDX_LEVEL,
+            op[IDX_PREDECESSOR..IDX_LEVEL].iter().cloned(),
+        );
     }
 
-    result
+    // replace op (successors count + successors) to val
+    let val_successors_count = successors_count!(val);
+    let op_successors_count = successors_count!(op);
+    if (has_successor!(mask_op) && !has_successor!(mask_val))
+        || (val_successors_count != op_successors_count)
+    {
+        val.truncate(LEN_FIXED_META);
+        val.splice(
+            IDX_SUCCESSOR_COUNT..,
+            op[IDX_SUCCESSOR_COUNT..].iter().cloned(),
+        );
+    }
+
+    let total_len = total_len(op_successors_count);
+    debug_assert_eq!(
+        total_len,
+        val.len(),
+        "Invalid length after merge operator was applied. Was expecting {} but found {}.",
+        total_len,
+        val.len()
+    );
+
+    Some(val)
 }
 
 #[cfg(test)]
@@ -15,6 +15,7 @@ use crate::persistent::{
     BincodeEncoded, CommitLogSchema, CommitLogWithSchema, KeyValueSchema, KeyValueStoreWithSchema,
     Location, PersistentStorage, StorageType,
 };
+use crate::storage_backend::WriteBatch;
 use crate::{BlockHeaderWithHash, Direction, IteratorMode, StorageError};
 
 /// Store block header data in a key-value store and into commit log.
@@ -175,13 +176,7 @@ impl BlockStorage {
                     block_json_data: None,
                     block_additional_data: None,
                 };
-                self.primary_index
-                    .put(&block_header.hash, &location)
-                    .and(
-                        self.by_level_index
-                            .put(block_header.header.level(), &location),
-                    )
-                    .and(Ok(true))
+                self.put_indexes(block_header, &location).and(Ok(true))
             })
     }
 
@@ -203,12 +198,7 @@ impl BlockStorage {
         };
         let block_header = self.get_block_header_by_location(&updated_column_location)?;
         // update indexes
-        self.primary_index
-            .put(&block_header.hash, &updated_column_location)
-            .and(
-                self.by_level_index
-                    .put(block_header.header.level(), &updated_column_location),
-            )
+        self.put_indexes(&block_header, &updated_column_location)
     }
 
     pub fn put_block_additional_data(
@@ -229,12 +219,30 @@ impl BlockStorage {
         };
         let block_header = self.get_block_header_by_location(&updated_column_location)?;
         // update indexes
-        self.primary_index
-            .put(&block_header.hash, &updated_column_location)
-            .and(
-                self.by_level_index
-                    .put(block_header.header.level(), &updated_column_location),
-            )
+        self.put_indexes(&block_header, &updated_column_location)
+    }
+
+    /// Removes references to the results of block application (json data, additional data and context index),
+    /// block header is kept. Used, when applied block has to be applied again (e.g. storage repair).
+    pub fn remove_applied_data(&self, block_hash: &BlockHash) -> Result<(), StorageError> {
+        let mut location = match self.primary_index.get(block_hash)? {
+            Some(location) => location,
+            None => return Ok(()),
+        };
+        location.block_json_data = None;
+        location.block_additional_data = None;
+        let block_header = self.get_block_header_by_location(&location)?;
+
+        // context index is removed, only if it still points to this block
+        let context_hash = block_header.header.context();
+        if let Some(context_location) = self.by_context_hash_index.get(context_hash)? {
+            if context_location.block_header.0 == location.block_header.0 {
+                self.by_context_hash_index.delete(context_hash)?;
+            }
+        }
+
+        // update indexes
+        self.put_indexes(&block_header, &location)
     }
 
     pub fn assign_to_context(
@@ -248,6 +256,20 @@ impl BlockStorage {
         }
     }
 
+    /// Updates primary and level index in single batch
+    fn put_indexes(
+        &self,
+        block_header: &BlockHeaderWithHash,
+        location: &BlockStorageColumnsLocation,
+    ) -> Result<(), StorageError> {
+        let mut batch = WriteBatch::default();
+        self.primary_index
+            .put_batch(&mut batch, &block_header.hash, location)?;
+        self.by_level_index
+            .put_batch(&mut batch, block_header.header.level(), location)?;
+        self.primary_index.write_batch(batch)
+    }
+
     #[inline]
     fn get_block_header_by_location(
         &self,
@@ -488,16 +510,23 @@ impl BlockPrimaryIndex {
     }
 
     #[inline]
-    fn put(
+    fn put_batch(
         &self,
+        batch: &mut WriteBatch,
         block_hash: &BlockHash,
         location: &BlockStorageColumnsLocation,
     ) -> Result<(), StorageError> {
         self.kv
-            .put(block_hash, &location)
+            .put_batch(batch, block_hash, location)
             .map_err(StorageError::from)
     }
 
+    /// Writes batch (with writes to any column of the database) atomically
+    #[inline]
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageError> {
+        self.kv.write_batch(batch).map_err(StorageError::from)
+    }
+
     #[inline]
     fn get(
         &self,
@@ -536,12 +565,15 @@ impl BlockByLevelIndex {
         Self { kv }
     }
 
-    fn put(
+    fn put_batch(
         &self,
+        batch: &mut WriteBatch,
         level: BlockLevel,
         location: &BlockStorageColumnsLocation,
     ) -> Result<(), StorageError> {
-        self.kv.put(&level, location).map_err(StorageError::from)
+        self.kv
+            .put_batch(batch, &level, location)
+            .map_err(StorageError::from)
     }
 
     fn get_blocks(
@@ -577,7 +609,7 @@ impl BlockByLevelIndex {
     ) -> Result<Vec<BlockStorageColumnsLocation>, StorageError> {
         self.kv
             .iterator(IteratorMode::From(&from_level, Direction::Reverse))?
-            .filter(|(level, _)| *level.as_ref().unwrap() % every_nth == 0)
+            .filter(|(level, _)| level.as_ref().map_or(true, |level| *level % every_nth == 0))
             .take(limit)
             .map(|(_, location)| location.map_err(StorageError::from))
             .collect()
@@ -628,6 +660,10 @@ impl BlockByContextHashIndex {
     fn contains(&self, context_hash: &ContextHash) -> Result<bool, StorageError> {
         self.kv.contains(context_hash).map_err(StorageError::from)
     }
+
+    fn delete(&self, context_hash: &ContextHash) -> Result<(), StorageError> {
+        self.kv.delete(context_hash).map_err(StorageError::from)
+    }
 }
 
 impl KeyValueSchema for BlockByContextHashIndex {
@@ -670,8 +706,10 @@ mod tests {
             .unwrap();
             let index = BlockByLevelIndex::new(Arc::new(db));
 
+            let mut batch = WriteBatch::default();
             for i in [1161, 66441, 905, 66185, 649, 65929, 393, 65673].iter() {
-                index.put(
+                index.put_batch(
+                    &mut batch,
                     *i,
                     &BlockStorageColumnsLocation {
                         block_header: Location::new(*i as u64),
@@ -680,6 +718,7 @@ mod tests {
                     },
                 )?;
             }
+            index.kv.write_batch(batch)?;
 
             let res = index
                 .get_blocks(649, 2)?
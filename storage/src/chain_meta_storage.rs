@@ -1,14 +1,17 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::str::FromStr;
 use std::sync::Arc;
 
 use std::convert::TryFrom;
 
+use getset::Getters;
 use rocksdb::{Cache, ColumnFamilyDescriptor};
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{ChainId, HashType};
+use crypto::hash::{BlockHash, ChainId, HashType};
+use tezos_messages::p2p::encoding::block_header::Level;
 use tezos_messages::Head;
 
 use crate::persistent::{
@@ -17,6 +20,9 @@ use crate::persistent::{
 };
 use crate::StorageError;
 
+/// Max number of stored invalid blocks per chain
+pub const MAX_INVALID_BLOCKS: usize = 1000;
+
 pub type ChainMetaStorageKv = dyn KeyValueStoreWithSchema<ChainMetaStorage> + Sync + Send;
 
 pub trait ChainMetaStorageReader: Sync + Send {
@@ -30,8 +36,20 @@ pub trait ChainMetaStorageReader: Sync + Send {
     /// - caboose - so in particular it is the lowest block for which we have stored the context
     fn get_caboose(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
 
+    /// Load save_point for chain_id from dedicated storage (see [get_caboose])
+    fn get_savepoint(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
+
+    /// Load trusted checkpoint for chain_id from dedicated storage, every accepted branch must contain this block
+    fn get_checkpoint(&self, chain_id: &ChainId) -> Result<Option<Checkpoint>, StorageError>;
+
     /// Load genesis for chain_id from dedicated storage
     fn get_genesis(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError>;
+
+    /// Load known alternate heads (applied blocks without applied successor, except current head) for chain_id
+    fn get_alternate_heads(&self, chain_id: &ChainId) -> Result<Vec<Head>, StorageError>;
+
+    /// Load blocks, which were rejected by protocol, for chain_id
+    fn get_invalid_blocks(&self, chain_id: &ChainId) -> Result<Vec<InvalidBlock>, StorageError>;
 }
 
 /// Represents storage of the chain metadata (current_head, test_chain, ...).
@@ -83,6 +101,30 @@ impl ChainMetaStorage {
             .map_err(StorageError::from)
     }
 
+    #[inline]
+    pub fn set_savepoint(&self, chain_id: &ChainId, head: Head) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &MetaKey::key_savepoint(chain_id.clone()),
+                &MetadataValue::Head(head),
+            )
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    pub fn set_checkpoint(
+        &self,
+        chain_id: &ChainId,
+        checkpoint: Checkpoint,
+    ) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &MetaKey::key_checkpoint(chain_id.clone()),
+                &MetadataValue::Checkpoint(checkpoint),
+            )
+            .map_err(StorageError::from)
+    }
+
     #[inline]
     pub fn set_genesis(&self, chain_id: &ChainId, head: Head) -> Result<(), StorageError> {
         self.kv
@@ -93,6 +135,48 @@ impl ChainMetaStorage {
             .map_err(StorageError::from)
     }
 
+    #[inline]
+    pub fn set_alternate_heads(
+        &self,
+        chain_id: &ChainId,
+        heads: Vec<Head>,
+    ) -> Result<(), StorageError> {
+        self.kv
+            .put(
+                &MetaKey::key_alternate_heads(chain_id.clone()),
+                &MetadataValue::Heads(heads),
+            )
+            .map_err(StorageError::from)
+    }
+
+    /// Stores invalid block, just last [MAX_INVALID_BLOCKS] blocks are kept
+    pub fn add_invalid_block(
+        &self,
+        chain_id: &ChainId,
+        invalid_block: InvalidBlock,
+    ) -> Result<(), StorageError> {
+        let mut invalid_blocks = self.get_invalid_blocks(chain_id)?;
+        if invalid_blocks
+            .iter()
+            .any(|b| b.block_hash == invalid_block.block_hash)
+        {
+            return Ok(());
+        }
+
+        invalid_blocks.push(invalid_block);
+        if invalid_blocks.len() > MAX_INVALID_BLOCKS {
+            let overflow = invalid_blocks.len() - MAX_INVALID_BLOCKS;
+            invalid_blocks.drain(0..overflow);
+        }
+
+        self.kv
+            .put(
+                &MetaKey::key_invalid_blocks(chain_id.clone()),
+                &MetadataValue::InvalidBlocks(invalid_blocks),
+            )
+            .map_err(StorageError::from)
+    }
+
     #[inline]
     pub fn get_test_chain_id(&self, chain_id: &ChainId) -> Result<Option<ChainId>, StorageError> {
         self.kv
@@ -149,6 +233,28 @@ impl ChainMetaStorageReader for ChainMetaStorage {
             .map_err(StorageError::from)
     }
 
+    #[inline]
+    fn get_savepoint(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_savepoint(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::Head(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    fn get_checkpoint(&self, chain_id: &ChainId) -> Result<Option<Checkpoint>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_checkpoint(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::Checkpoint(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
+
     #[inline]
     fn get_genesis(&self, chain_id: &ChainId) -> Result<Option<Head>, StorageError> {
         self.kv
@@ -159,6 +265,28 @@ impl ChainMetaStorageReader for ChainMetaStorage {
             })
             .map_err(StorageError::from)
     }
+
+    #[inline]
+    fn get_alternate_heads(&self, chain_id: &ChainId) -> Result<Vec<Head>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_alternate_heads(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::Heads(value)) => value,
+                _ => Vec::new(),
+            })
+            .map_err(StorageError::from)
+    }
+
+    #[inline]
+    fn get_invalid_blocks(&self, chain_id: &ChainId) -> Result<Vec<InvalidBlock>, StorageError> {
+        self.kv
+            .get(&MetaKey::key_invalid_blocks(chain_id.clone()))
+            .map(|result| match result {
+                Some(MetadataValue::InvalidBlocks(value)) => value,
+                _ => Vec::new(),
+            })
+            .map_err(StorageError::from)
+    }
 }
 
 impl KeyValueSchema for ChainMetaStorage {
@@ -191,6 +319,10 @@ impl MetaKey {
     const KEY_CABOOSE: &'static str = "cbs";
     const KEY_GENESIS: &'static str = "gns";
     const KEY_TEST_CHAIN_ID: &'static str = "tcid";
+    const KEY_SAVEPOINT: &'static str = "svp";
+    const KEY_CHECKPOINT: &'static str = "chkp";
+    const KEY_ALTERNATE_HEADS: &'static str = "alth";
+    const KEY_INVALID_BLOCKS: &'static str = "invb";
 
     fn key_current_head(chain_id: ChainId) -> MetaKey {
         MetaKey {
@@ -206,6 +338,34 @@ impl MetaKey {
         }
     }
 
+    fn key_savepoint(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_SAVEPOINT.to_string(),
+        }
+    }
+
+    fn key_checkpoint(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_CHECKPOINT.to_string(),
+        }
+    }
+
+    fn key_alternate_heads(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_ALTERNATE_HEADS.to_string(),
+        }
+    }
+
+    fn key_invalid_blocks(chain_id: ChainId) -> MetaKey {
+        MetaKey {
+            chain_id,
+            key: Self::KEY_INVALID_BLOCKS.to_string(),
+        }
+    }
+
     fn key_genesis(chain_id: ChainId) -> MetaKey {
         MetaKey {
             chain_id,
@@ -250,6 +410,71 @@ impl Decoder for MetaKey {
 pub enum MetadataValue {
     Head(Head),
     TestChainId(ChainId),
+    Checkpoint(Checkpoint),
+    Heads(Vec<Head>),
+    InvalidBlocks(Vec<InvalidBlock>),
+}
+
+/// Trusted block (configured with `--checkpoint <block_hash>,<level>`), node accepts just branches, which contain this block
+#[derive(Clone, Debug, PartialEq, Getters, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[get = "pub"]
+    block_hash: BlockHash,
+    #[get = "pub"]
+    level: Level,
+}
+
+impl Checkpoint {
+    pub fn new(block_hash: BlockHash, level: Level) -> Self {
+        Self { block_hash, level }
+    }
+
+    /// Returns true, if block (hash, level) is at the level of checkpoint, but it is not the checkpoint
+    pub fn conflicts_with(&self, block_hash: &BlockHash, level: Level) -> bool {
+        self.level == level && self.block_hash.ne(block_hash)
+    }
+}
+
+impl FromStr for Checkpoint {
+    type Err = String;
+
+    /// Parses `<block_hash>,<level>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (block_hash, level) = match s.split(',').collect::<Vec<_>>().as_slice() {
+            [block_hash, level] => (block_hash.trim(), level.trim()),
+            _ => return Err(format!("Expected <block_hash>,<level>, but was '{}'", s)),
+        };
+        let block_hash = BlockHash::from_base58_check(block_hash)
+            .map_err(|e| format!("Invalid block hash '{}': {}", block_hash, e))?;
+        let level = level
+            .parse::<Level>()
+            .map_err(|e| format!("Invalid level '{}': {}", level, e))?;
+        if level < 0 {
+            return Err(format!("Invalid level '{}': must not be negative", level));
+        }
+        Ok(Checkpoint::new(block_hash, level))
+    }
+}
+
+/// Block, which was rejected by protocol
+#[derive(Clone, Debug, PartialEq, Getters, Serialize, Deserialize)]
+pub struct InvalidBlock {
+    #[get = "pub"]
+    block_hash: BlockHash,
+    #[get = "pub"]
+    level: Level,
+    #[get = "pub"]
+    error: String,
+}
+
+impl InvalidBlock {
+    pub fn new(block_hash: BlockHash, level: Level, error: String) -> Self {
+        Self {
+            block_hash,
+            level,
+            error,
+        }
+    }
 }
 
 impl BincodeEncoded for MetadataValue {}
@@ -388,6 +613,123 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_savepoint_and_checkpoint() -> Result<(), Error> {
+        let tmp_storage = TmpStorage::create_to_out_dir("__test_savepoint_and_checkpoint")?;
+        let index = ChainMetaStorage::new(tmp_storage.storage());
+
+        let chain_id1 = "NetXgtSLGNJvNye".try_into()?;
+        let chain_id2 = "NetXjD3HPJJjmcd".try_into()?;
+        let block_1 = Head::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?,
+            1,
+            vec![],
+        );
+        let checkpoint = Checkpoint::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7".try_into()?,
+            2,
+        );
+
+        // nothing stored
+        assert!(index.get_savepoint(&chain_id1)?.is_none());
+        assert!(index.get_checkpoint(&chain_id1)?.is_none());
+
+        // set for chain_id1
+        index.set_savepoint(&chain_id1, block_1.clone())?;
+        index.set_checkpoint(&chain_id1, checkpoint.clone())?;
+        assert_eq!(
+            index.get_savepoint(&chain_id1)?.unwrap().block_hash(),
+            block_1.block_hash()
+        );
+        assert_eq!(index.get_checkpoint(&chain_id1)?, Some(checkpoint));
+        assert!(index.get_savepoint(&chain_id2)?.is_none());
+        assert!(index.get_checkpoint(&chain_id2)?.is_none());
+
+        // checkpoint is not mixed with heads
+        assert!(index.get_caboose(&chain_id1)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_alternate_heads_and_invalid_blocks() -> Result<(), Error> {
+        let tmp_storage =
+            TmpStorage::create_to_out_dir("__test_alternate_heads_and_invalid_blocks")?;
+        let index = ChainMetaStorage::new(tmp_storage.storage());
+
+        let chain_id1 = "NetXgtSLGNJvNye".try_into()?;
+        let chain_id2 = "NetXjD3HPJJjmcd".try_into()?;
+        let block_1 = Head::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe".try_into()?,
+            1,
+            vec![],
+        );
+        let invalid_block = InvalidBlock::new(
+            "BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7".try_into()?,
+            2,
+            "Protocol error".to_string(),
+        );
+
+        // nothing stored
+        assert!(index.get_alternate_heads(&chain_id1)?.is_empty());
+        assert!(index.get_invalid_blocks(&chain_id1)?.is_empty());
+
+        // set for chain_id1
+        index.set_alternate_heads(&chain_id1, vec![block_1.clone()])?;
+        index.add_invalid_block(&chain_id1, invalid_block.clone())?;
+        let alternate_heads = index.get_alternate_heads(&chain_id1)?;
+        assert_eq!(1, alternate_heads.len());
+        assert_eq!(alternate_heads[0].block_hash(), block_1.block_hash());
+        assert_eq!(
+            index.get_invalid_blocks(&chain_id1)?,
+            vec![invalid_block.clone()]
+        );
+        assert!(index.get_alternate_heads(&chain_id2)?.is_empty());
+        assert!(index.get_invalid_blocks(&chain_id2)?.is_empty());
+
+        // invalid block is stored just once
+        index.add_invalid_block(&chain_id1, invalid_block)?;
+        assert_eq!(1, index.get_invalid_blocks(&chain_id1)?.len());
+
+        // clear alternate heads
+        index.set_alternate_heads(&chain_id1, vec![])?;
+        assert!(index.get_alternate_heads(&chain_id1)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_from_str() -> Result<(), Error> {
+        let checkpoint: Checkpoint = "BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7, 1234"
+            .parse()
+            .expect("Failed to parse checkpoint");
+        assert_eq!(
+            checkpoint.block_hash(),
+            &BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7")?
+        );
+        assert_eq!(*checkpoint.level(), 1234);
+
+        assert!(checkpoint.conflicts_with(
+            &BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe")?,
+            1234
+        ));
+        assert!(!checkpoint.conflicts_with(checkpoint.block_hash(), 1234));
+        assert!(!checkpoint.conflicts_with(
+            &BlockHash::from_base58_check("BLockGenesisGenesisGenesisGenesisGenesisb83baZgbyZe")?,
+            1233
+        ));
+
+        assert!("BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7"
+            .parse::<Checkpoint>()
+            .is_err());
+        assert!("BLockGenesisGenesisGenesisGenesisGenesisd6f5afWyME7,abc"
+            .parse::<Checkpoint>()
+            .is_err());
+        assert!("abc,1".parse::<Checkpoint>().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_genesis() -> Result<(), Error> {
         let tmp_storage = TmpStorage::create("__test_genesis")?;
@@ -24,6 +24,7 @@ use crate::persistent::{
     default_table_options, ActionRecorder, BincodeEncoded, Decoder, Encoder, KeyValueSchema,
     KeyValueStoreWithSchema, PersistentStorage, SchemaError,
 };
+use crate::storage_backend::WriteBatch;
 use crate::StorageError;
 use crate::{num_from_slice, persistent::StorageType};
 
@@ -95,24 +96,29 @@ impl ContextActionStorage {
         // generate ID
         let id = self.generator.next()?;
         let action = ContextActionRecordValue::new(action, id);
-        // Store action
-        self.kv.put(&id, &action)?;
-        // Populate indexes
-        self.context_by_block_index
-            .put(&ContextActionByBlockHashKey::new(block_hash, id))?;
+        // Store action and populate indexes in single batch
+        let mut batch = WriteBatch::default();
+        self.kv.put_batch(&mut batch, &id, &action)?;
+        self.context_by_block_index.put_batch(
+            &mut batch,
+            &ContextActionByBlockHashKey::new(block_hash, id),
+        )?;
 
         if let Some(action_type) = ContextActionType::extract_type(action.action()) {
-            self.context_by_type_index
-                .put(&ContextActionByTypeIndexKey::new(action_type, id))?;
+            self.context_by_type_index.put_batch(
+                &mut batch,
+                &ContextActionByTypeIndexKey::new(action_type, id),
+            )?;
         }
 
-        extract_contract_addresses(&action)
-            .iter()
-            .map(|contract_address| {
-                self.context_by_contract_index
-                    .put(&ContextActionByContractIndexKey::new(contract_address, id))
-            })
-            .collect::<Result<(), _>>()
+        for contract_address in extract_contract_addresses(&action) {
+            self.context_by_contract_index.put_batch(
+                &mut batch,
+                &ContextActionByContractIndexKey::new(&contract_address, id),
+            )?;
+        }
+
+        self.kv.write_batch(batch).map_err(StorageError::from)
     }
 
     #[inline]
@@ -389,8 +395,14 @@ impl ContextActionByBlockHashIndex {
     }
 
     #[inline]
-    fn put(&mut self, key: &ContextActionByBlockHashKey) -> Result<(), StorageError> {
-        self.kv.put(key, &()).map_err(StorageError::from)
+    fn put_batch(
+        &self,
+        batch: &mut WriteBatch,
+        key: &ContextActionByBlockHashKey,
+    ) -> Result<(), StorageError> {
+        self.kv
+            .put_batch(batch, key, &())
+            .map_err(StorageError::from)
     }
 
     #[inline]
@@ -434,6 +446,10 @@ impl KeyValueSchema for ContextActionByBlockHashIndex {
     fn name() -> &'static str {
         "context_action_block_hash_index"
     }
+
+    fn prefix_len() -> Option<usize> {
+        Some(ContextActionByBlockHashKey::LEN_BLOCK_HASH)
+    }
 }
 
 /// Key for a specific action stored in a database.
@@ -519,8 +535,14 @@ impl ContextActionByContractIndex {
     }
 
     #[inline]
-    fn put(&mut self, key: &ContextActionByContractIndexKey) -> Result<(), StorageError> {
-        self.kv.put(key, &()).map_err(StorageError::from)
+    fn put_batch(
+        &self,
+        batch: &mut WriteBatch,
+        key: &ContextActionByContractIndexKey,
+    ) -> Result<(), StorageError> {
+        self.kv
+            .put_batch(batch, key, &())
+            .map_err(StorageError::from)
     }
 
     #[inline]
@@ -571,6 +593,10 @@ impl KeyValueSchema for ContextActionByContractIndex {
     fn name() -> &'static str {
         "context_by_contract_storage"
     }
+
+    fn prefix_len() -> Option<usize> {
+        Some(ContextActionByContractIndexKey::LEN_CONTRACT_ADDRESS)
+    }
 }
 
 /// Key for a specific action stored in a database.
@@ -739,8 +765,14 @@ impl ContextActionByTypeIndex {
     }
 
     #[inline]
-    fn put(&self, key: &ContextActionByTypeIndexKey) -> Result<(), StorageError> {
-        self.kv.put(key, &()).map_err(StorageError::from)
+    fn put_batch(
+        &self,
+        batch: &mut WriteBatch,
+        key: &ContextActionByTypeIndexKey,
+    ) -> Result<(), StorageError> {
+        self.kv
+            .put_batch(batch, key, &())
+            .map_err(StorageError::from)
     }
 
     #[inline]
@@ -793,6 +825,10 @@ impl KeyValueSchema for ContextActionByTypeIndex {
     fn name() -> &'static str {
         "context_by_type_storage"
     }
+
+    fn prefix_len() -> Option<usize> {
+        Some(mem::size_of::<ContextActionType>())
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -0,0 +1,613 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Offline storage integrity checker.
+//!
+//! Crashes in the middle of block application can leave storage in a state, where different
+//! parts of the storage disagree with each other, e.g. block is marked as applied in [BlockMetaStorage],
+//! but its additional data were never written to [BlockStorage] or its context was never committed.
+//!
+//! [check_storage_integrity] walks all stored block metadata, cross-checks them with block storage (commit log),
+//! operations metadata and merkle (context) storage and collects all found inconsistencies to [IntegrityReport].
+//! [repair_current_head] can be used afterwards to move current head back to the last consistent block
+//! and to reset all blocks above it to not applied, so the node re-applies them on the next start.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use failure::Fail;
+use slog::{info, warn, Logger};
+
+use crypto::hash::{BlockHash, ChainId, ContextHash};
+use tezos_messages::Head;
+
+use crate::chain_meta_storage::ChainMetaStorageReader;
+use crate::merkle_storage::{EntryHash, MerkleError};
+use crate::persistent::PersistentStorage;
+use crate::{
+    BlockMetaStorage, BlockMetaStorageReader, BlockStorage, BlockStorageReader, ChainMetaStorage,
+    IteratorMode, OperationsMetaStorage, StorageError,
+};
+
+/// How often (in checked blocks) is integrity check progress logged
+const INTEGRITY_LOG_INTERVAL: usize = 100_000;
+
+/// Possible errors for integrity check/repair
+#[derive(Debug, Fail)]
+pub enum IntegrityError {
+    #[fail(display = "Storage error: {}", error)]
+    StorageError { error: StorageError },
+    #[fail(display = "Merkle storage error: {}", error)]
+    MerkleError { error: MerkleError },
+    #[fail(display = "Current head is not stored for chain: {}", chain_id)]
+    MissingCurrentHead { chain_id: String },
+    #[fail(
+        display = "No consistent applied block found below current head: {}",
+        current_head
+    )]
+    NoConsistentBlock { current_head: String },
+}
+
+impl From<StorageError> for IntegrityError {
+    fn from(error: StorageError) -> Self {
+        IntegrityError::StorageError { error }
+    }
+}
+
+impl From<MerkleError> for IntegrityError {
+    fn from(error: MerkleError) -> Self {
+        IntegrityError::MerkleError { error }
+    }
+}
+
+/// Inconsistency found by [check_storage_integrity]
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegrityIssue {
+    /// Block metadata are stored, but block header is not
+    MissingBlockHeader { block_hash: BlockHash },
+    /// Block data cannot be read from commit log
+    UnreadableBlockData {
+        block_hash: BlockHash,
+        reason: String,
+    },
+    /// Block is marked as applied, but [BlockAdditionalData](crate::BlockAdditionalData) are not stored
+    MissingAdditionalData { block_hash: BlockHash },
+    /// Block is marked as applied, but not all its operations are stored
+    IncompleteOperations { block_hash: BlockHash },
+    /// Block is marked as applied, but its resulting context is not stored in merkle storage
+    MissingContext {
+        block_hash: BlockHash,
+        context_hash: ContextHash,
+    },
+    /// Block metadata reference successor, which has no metadata stored
+    DanglingSuccessor {
+        block_hash: BlockHash,
+        successor: BlockHash,
+    },
+}
+
+impl IntegrityIssue {
+    /// Block, which is affected by this issue
+    pub fn block_hash(&self) -> &BlockHash {
+        match self {
+            IntegrityIssue::MissingBlockHeader { block_hash }
+            | IntegrityIssue::UnreadableBlockData { block_hash, .. }
+            | IntegrityIssue::MissingAdditionalData { block_hash }
+            | IntegrityIssue::IncompleteOperations { block_hash }
+            | IntegrityIssue::MissingContext { block_hash, .. }
+            | IntegrityIssue::DanglingSuccessor { block_hash, .. } => block_hash,
+        }
+    }
+}
+
+impl fmt::Display for IntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityIssue::MissingBlockHeader { block_hash } => write!(
+                f,
+                "block {} has metadata, but block header is missing",
+                block_hash.to_base58_check()
+            ),
+            IntegrityIssue::UnreadableBlockData { block_hash, reason } => write!(
+                f,
+                "block {} data cannot be read, reason: {}",
+                block_hash.to_base58_check(),
+                reason
+            ),
+            IntegrityIssue::MissingAdditionalData { block_hash } => write!(
+                f,
+                "block {} is applied, but block additional data are missing",
+                block_hash.to_base58_check()
+            ),
+            IntegrityIssue::IncompleteOperations { block_hash } => write!(
+                f,
+                "block {} is applied, but operations are not complete",
+                block_hash.to_base58_check()
+            ),
+            IntegrityIssue::MissingContext {
+                block_hash,
+                context_hash,
+            } => write!(
+                f,
+                "block {} is applied, but context {} is missing",
+                block_hash.to_base58_check(),
+                context_hash.to_base58_check()
+            ),
+            IntegrityIssue::DanglingSuccessor {
+                block_hash,
+                successor,
+            } => write!(
+                f,
+                "block {} references successor {}, which is not stored",
+                block_hash.to_base58_check(),
+                successor.to_base58_check()
+            ),
+        }
+    }
+}
+
+/// Result of [check_storage_integrity]
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// Count of checked blocks (with stored metadata)
+    pub checked_blocks: usize,
+    /// Count of checked blocks marked as applied
+    pub applied_blocks: usize,
+    /// False, if merkle storage is not persistent and contexts could not be checked
+    pub contexts_checked: bool,
+    /// All found inconsistencies
+    pub issues: Vec<IntegrityIssue>,
+    inconsistent_blocks: HashSet<BlockHash>,
+}
+
+impl IntegrityReport {
+    fn add_issue(&mut self, issue: IntegrityIssue) {
+        self.inconsistent_blocks.insert(issue.block_hash().clone());
+        self.issues.push(issue);
+    }
+
+    /// Returns true, if no inconsistency was found
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns true, if no inconsistency was found for the block
+    pub fn is_block_consistent(&self, block_hash: &BlockHash) -> bool {
+        !self.inconsistent_blocks.contains(block_hash)
+    }
+}
+
+/// Walks all block metadata and checks, that related data are stored in all other storages.
+pub fn check_storage_integrity(
+    persistent_storage: &PersistentStorage,
+    log: &Logger,
+) -> Result<IntegrityReport, IntegrityError> {
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+    let operations_meta_storage = OperationsMetaStorage::new(persistent_storage);
+    let merkle = persistent_storage.merkle();
+    let merkle = merkle.read().expect("lock poisoning");
+
+    let mut report = IntegrityReport {
+        contexts_checked: merkle.has_persistent_backend(),
+        ..Default::default()
+    };
+    if !report.contexts_checked {
+        warn!(
+            log,
+            "Merkle storage is not persistent, contexts of applied blocks will not be checked"
+        );
+    }
+
+    info!(log, "Storage integrity check started");
+    for (block_hash, meta) in block_meta_storage.iter(IteratorMode::Start)? {
+        let block_hash = block_hash.map_err(StorageError::from)?;
+        let meta = meta.map_err(StorageError::from)?;
+        report.checked_blocks += 1;
+        if report.checked_blocks % INTEGRITY_LOG_INTERVAL == 0 {
+            info!(log, "Storage integrity check in progress";
+                       "checked_blocks" => report.checked_blocks,
+                       "issues" => report.issues.len());
+        }
+
+        // every successor should have its own metadata
+        for successor in meta.successors() {
+            if !block_meta_storage.contains(successor)? {
+                report.add_issue(IntegrityIssue::DanglingSuccessor {
+                    block_hash: block_hash.clone(),
+                    successor: successor.clone(),
+                });
+            }
+        }
+
+        if block_storage.get_location(&block_hash)?.is_none() {
+            report.add_issue(IntegrityIssue::MissingBlockHeader { block_hash });
+            continue;
+        }
+
+        if !meta.is_applied() {
+            continue;
+        }
+        report.applied_blocks += 1;
+
+        // applied block should have stored all results of application
+        let block = match block_storage.get_with_additional_data(&block_hash) {
+            Ok(Some((block, _))) => block,
+            Ok(None) => {
+                report.add_issue(IntegrityIssue::MissingAdditionalData { block_hash });
+                continue;
+            }
+            Err(e) => {
+                report.add_issue(IntegrityIssue::UnreadableBlockData {
+                    block_hash,
+                    reason: format!("{}", e),
+                });
+                continue;
+            }
+        };
+
+        if !operations_meta_storage.is_complete(&block_hash)? {
+            report.add_issue(IntegrityIssue::IncompleteOperations {
+                block_hash: block_hash.clone(),
+            });
+        }
+
+        if report.contexts_checked {
+            let context_hash = block.header.context();
+            let context_stored = match EntryHash::try_from(context_hash.as_ref().as_slice()) {
+                Ok(entry_hash) => merkle.contains_commit(&entry_hash)?,
+                Err(_) => false,
+            };
+            if !context_stored {
+                report.add_issue(IntegrityIssue::MissingContext {
+                    block_hash,
+                    context_hash: context_hash.clone(),
+                });
+            }
+        }
+    }
+
+    info!(log, "Storage integrity check finished";
+               "checked_blocks" => report.checked_blocks,
+               "applied_blocks" => report.applied_blocks,
+               "issues" => report.issues.len());
+    Ok(report)
+}
+
+/// Moves current head back (through predecessors) to the last applied block without any inconsistency.
+/// All blocks above the new current head are marked as not applied and their application results are removed.
+///
+/// Returns new current head, or None if current head is already consistent.
+pub fn repair_current_head(
+    persistent_storage: &PersistentStorage,
+    chain_id: &ChainId,
+    report: &IntegrityReport,
+    log: &Logger,
+) -> Result<Option<Head>, IntegrityError> {
+    let block_storage = BlockStorage::new(persistent_storage);
+    let block_meta_storage = BlockMetaStorage::new(persistent_storage);
+    let chain_meta_storage = ChainMetaStorage::new(persistent_storage);
+
+    let current_head = match chain_meta_storage.get_current_head(chain_id)? {
+        Some(current_head) => current_head,
+        None => {
+            return Err(IntegrityError::MissingCurrentHead {
+                chain_id: chain_id.to_base58_check(),
+            })
+        }
+    };
+
+    let mut block_hash = current_head.block_hash().clone();
+    loop {
+        let meta = block_meta_storage.get(&block_hash)?;
+        let is_applied = meta.as_ref().map(|meta| meta.is_applied()).unwrap_or(false);
+
+        if is_applied && report.is_block_consistent(&block_hash) {
+            if block_hash == *current_head.block_hash() {
+                info!(log, "Current head is consistent, nothing to repair";
+                           "current_head" => block_hash.to_base58_check());
+                return Ok(None);
+            }
+            if let Some(block) = block_storage.get(&block_hash)? {
+                let new_head = Head::new(
+                    block.hash,
+                    block.header.level(),
+                    block.header.fitness().clone(),
+                );
+                chain_meta_storage.set_current_head(chain_id, new_head.clone())?;
+                let reset_blocks =
+                    reset_applied_successors(&block_storage, &block_meta_storage, &block_hash)?;
+                info!(log, "Current head was moved to the last consistent block";
+                           "previous_head" => current_head.block_hash().to_base58_check(),
+                           "previous_level" => current_head.level(),
+                           "new_head" => new_head.block_hash().to_base58_check(),
+                           "new_level" => new_head.level(),
+                           "reset_applied_blocks" => reset_blocks);
+                return Ok(Some(new_head));
+            }
+        }
+
+        // continue with predecessor, genesis is predecessor of itself
+        match meta.and_then(|meta| meta.predecessor().clone()) {
+            Some(predecessor) if predecessor != block_hash => block_hash = predecessor,
+            _ => {
+                return Err(IntegrityError::NoConsistentBlock {
+                    current_head: current_head.block_hash().to_base58_check(),
+                })
+            }
+        }
+    }
+}
+
+/// Marks all (transitive) successors of the block as not applied and removes their application results,
+/// so they are fed to the protocol again. Returns count of reset blocks.
+fn reset_applied_successors(
+    block_storage: &BlockStorage,
+    block_meta_storage: &BlockMetaStorage,
+    block_hash: &BlockHash,
+) -> Result<usize, IntegrityError> {
+    let mut reset_blocks = 0;
+    let mut visited = HashSet::new();
+    visited.insert(block_hash.clone());
+    let mut to_visit = vec![block_hash.clone()];
+    while let Some(block_hash) = to_visit.pop() {
+        let meta = match block_meta_storage.get(&block_hash)? {
+            Some(meta) => meta,
+            None => continue,
+        };
+        for successor in meta.successors() {
+            if visited.insert(successor.clone()) {
+                if block_meta_storage.reset_applied(successor)? {
+                    reset_blocks += 1;
+                }
+                block_storage.remove_applied_data(successor)?;
+                to_visit.push(successor.clone());
+            }
+        }
+    }
+    Ok(reset_blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use failure::Error;
+    use slog::{Discard, Logger};
+
+    use tezos_messages::p2p::encoding::prelude::BlockHeaderBuilder;
+
+    use crate::block_meta_storage::Meta;
+    use crate::persistent::StorageType;
+    use crate::tests_common::TmpStorage;
+    use crate::{BlockAdditionalDataBuilder, BlockHeaderWithHash, BlockMetaStorageKV};
+
+    use super::*;
+
+    fn block(
+        level: i32,
+        predecessor: &BlockHash,
+        context: &ContextHash,
+    ) -> Result<BlockHeaderWithHash, Error> {
+        Ok(BlockHeaderWithHash::new(
+            BlockHeaderBuilder::default()
+                .level(level)
+                .proto(1)
+                .predecessor(predecessor.clone())
+                .timestamp(5_635_634 + level as i64)
+                .validation_pass(0)
+                .operations_hash(
+                    "LLoaGLRPRx3Zf8kB4ACtgku8F4feeBiskeb41J1ciwfcXB3KzHKXc".try_into()?,
+                )
+                .fitness(vec![vec![0, level as u8]])
+                .context(context.clone())
+                .protocol_data(vec![0, 1, 2, 3, 4, 5, 6, 7, 8])
+                .build()
+                .unwrap(),
+        )?)
+    }
+
+    fn store_block(
+        storage: &PersistentStorage,
+        block: &BlockHeaderWithHash,
+        chain_id: &ChainId,
+        applied: bool,
+        with_additional_data: bool,
+        log: &Logger,
+    ) -> Result<(), Error> {
+        let block_storage = BlockStorage::new(storage);
+        let block_meta_storage = BlockMetaStorage::new(storage);
+        let operations_meta_storage = OperationsMetaStorage::new(storage);
+
+        block_storage.put_block_header(block)?;
+        let mut meta = block_meta_storage.put_block_header(block, chain_id, log)?;
+        operations_meta_storage.put_block_header(block, chain_id)?;
+        if with_additional_data {
+            block_storage.put_block_additional_data(
+                &block.hash,
+                BlockAdditionalDataBuilder::default()
+                    .max_operations_ttl(60)
+                    .last_allowed_fork_level(0)
+                    .block_metadata_hash(None)
+                    .ops_metadata_hash(None)
+                    .ops_metadata_hashes(None)
+                    .build()
+                    .unwrap(),
+            )?;
+        }
+        if applied {
+            meta.set_is_applied(true);
+            block_meta_storage.put(&block.hash, &meta)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_and_repair_storage_integrity() -> Result<(), Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let tmp_storage = TmpStorage::create_to_out_dir("__integrity_check_and_repair")?;
+        let storage = tmp_storage.storage();
+        let chain_id: ChainId = vec![1, 2, 3, 4].try_into()?;
+
+        // commit context for the first block
+        let committed_context: ContextHash = {
+            let merkle = storage.merkle();
+            let mut merkle = merkle.write().expect("lock poisoning");
+            merkle.set(&vec!["data".to_string(), "a".to_string()], &vec![1, 2, 3])?;
+            merkle
+                .commit(0, "Tezos".to_string(), "Block 1".to_string())?
+                .to_vec()
+                .try_into()?
+        };
+        let missing_context: ContextHash =
+            "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd".try_into()?;
+
+        let genesis_hash: BlockHash =
+            "BKyQ9EofHrgaZKENioHyP4FZNsTmiSEcVmcghgzCC9cGhE7oCET".try_into()?;
+        let block1 = block(1, &genesis_hash, &committed_context)?;
+        let block2 = block(2, &block1.hash, &missing_context)?;
+        let block3 = block(3, &block2.hash, &missing_context)?;
+        let block4 = block(4, &block3.hash, &missing_context)?;
+
+        store_block(storage, &block1, &chain_id, true, true, &log)?;
+        // crash after block was marked as applied, but before additional data were stored
+        store_block(storage, &block2, &chain_id, true, false, &log)?;
+        // crash before context was committed
+        store_block(storage, &block3, &chain_id, true, true, &log)?;
+        // not applied yet
+        store_block(storage, &block4, &chain_id, false, false, &log)?;
+
+        // genesis has metadata, but block header was never stored
+        let block_meta_storage = BlockMetaStorage::new(storage);
+        block_meta_storage.put(
+            &genesis_hash,
+            &Meta::genesis_meta(&genesis_hash, &chain_id, true),
+        )?;
+
+        let report = check_storage_integrity(storage, &log)?;
+        assert!(report.contexts_checked);
+        assert_eq!(5, report.checked_blocks);
+        assert_eq!(3, report.applied_blocks);
+        assert!(!report.is_consistent());
+        assert!(report.is_block_consistent(&block1.hash));
+        assert!(!report.is_block_consistent(&block2.hash));
+        assert!(!report.is_block_consistent(&block3.hash));
+        assert!(report.is_block_consistent(&block4.hash));
+        assert_eq!(3, report.issues.len());
+        assert!(report.issues.contains(&IntegrityIssue::MissingBlockHeader {
+            block_hash: genesis_hash.clone()
+        }));
+        assert!(report
+            .issues
+            .contains(&IntegrityIssue::MissingAdditionalData {
+                block_hash: block2.hash.clone()
+            }));
+        assert!(report.issues.contains(&IntegrityIssue::MissingContext {
+            block_hash: block3.hash.clone(),
+            context_hash: missing_context.clone(),
+        }));
+
+        // current head points to inconsistent block
+        let chain_meta_storage = ChainMetaStorage::new(storage);
+        chain_meta_storage.set_current_head(
+            &chain_id,
+            Head::new(block3.hash.clone(), 3, block3.header.fitness().clone()),
+        )?;
+
+        let new_head = repair_current_head(storage, &chain_id, &report, &log)?;
+        assert_eq!(
+            Some(&block1.hash),
+            new_head.as_ref().map(|head| head.block_hash())
+        );
+        assert_eq!(
+            Some(block1.hash.clone()),
+            chain_meta_storage
+                .get_current_head(&chain_id)?
+                .map(|head| head.block_hash().clone())
+        );
+
+        // blocks above the new current head are not applied anymore and their results are removed
+        let block_storage = BlockStorage::new(storage);
+        let is_applied = |block_hash: &BlockHash| -> Result<bool, Error> {
+            Ok(block_meta_storage
+                .get(block_hash)?
+                .map(|meta| meta.is_applied())
+                .unwrap_or(false))
+        };
+        assert!(is_applied(&block1.hash)?);
+        assert!(!is_applied(&block2.hash)?);
+        assert!(!is_applied(&block3.hash)?);
+        assert!(!is_applied(&block4.hash)?);
+        assert!(block_storage
+            .get_with_additional_data(&block1.hash)?
+            .is_some());
+        assert!(block_storage
+            .get_with_additional_data(&block3.hash)?
+            .is_none());
+        assert!(block_storage.get(&block3.hash)?.is_some());
+
+        // so they are fed again - block2 is the next block to apply (applied predecessor, complete operations)
+        assert_eq!(
+            Some(&block1.hash),
+            block_meta_storage
+                .get(&block2.hash)?
+                .and_then(|meta| meta.predecessor().clone())
+                .as_ref()
+        );
+        assert!(OperationsMetaStorage::new(storage).is_complete(&block2.hash)?);
+
+        // and storage is consistent except of genesis header
+        let report = check_storage_integrity(storage, &log)?;
+        assert_eq!(1, report.applied_blocks);
+        assert_eq!(
+            vec![IntegrityIssue::MissingBlockHeader {
+                block_hash: genesis_hash.clone()
+            }],
+            report.issues
+        );
+
+        // current head is consistent now, so nothing is changed
+        assert!(repair_current_head(storage, &chain_id, &report, &log)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_storage_integrity_dangling_successor() -> Result<(), Error> {
+        let log = Logger::root(Discard, slog::o!());
+        let tmp_storage = TmpStorage::create_to_out_dir("__integrity_dangling_successor")?;
+        let storage = tmp_storage.storage();
+        let chain_id: ChainId = vec![1, 2, 3, 4].try_into()?;
+
+        let genesis_hash: BlockHash =
+            "BKyQ9EofHrgaZKENioHyP4FZNsTmiSEcVmcghgzCC9cGhE7oCET".try_into()?;
+        let context: ContextHash =
+            "CoVmAcMV64uAQo8XvfLr9VDuz7HVZLT4cgK1w1qYmTjQNbGwQwDd".try_into()?;
+        let block1 = block(1, &genesis_hash, &context)?;
+        let block2 = block(2, &block1.hash, &context)?;
+        store_block(storage, &block1, &chain_id, false, false, &log)?;
+        store_block(storage, &block2, &chain_id, false, false, &log)?;
+
+        // block2 is still registered as successor of block1
+        BlockMetaStorageKV::delete(storage.kv(StorageType::Database).as_ref(), &block2.hash)?;
+
+        // genesis metadata (without header) are stored together with block1
+        let report = check_storage_integrity(storage, &log)?;
+        assert_eq!(2, report.checked_blocks);
+        assert_eq!(
+            vec![
+                IntegrityIssue::MissingBlockHeader {
+                    block_hash: genesis_hash.clone()
+                },
+                IntegrityIssue::DanglingSuccessor {
+                    block_hash: block1.hash.clone(),
+                    successor: block2.hash.clone(),
+                }
+            ],
+            report.issues
+        );
+
+        Ok(())
+    }
+}
@@ -30,7 +30,8 @@ pub use crate::block_storage::{
     BlockAdditionalData, BlockAdditionalDataBuilder, BlockJsonData, BlockJsonDataBuilder,
     BlockStorage, BlockStorageReader,
 };
-pub use crate::chain_meta_storage::ChainMetaStorage;
+use crate::chain_meta_storage::ChainMetaStorageReader;
+pub use crate::chain_meta_storage::{ChainMetaStorage, Checkpoint, InvalidBlock};
 pub use crate::context_action_storage::{
     ContextActionByBlockHashKey, ContextActionRecordValue, ContextActionStorage,
 };
@@ -45,19 +46,22 @@ use crate::persistent::sequence::SequenceError;
 use crate::persistent::ActionRecordError;
 use crate::persistent::{CommitLogError, DBError, Decoder, Encoder, SchemaError};
 pub use crate::predecessor_storage::PredecessorStorage;
-pub use crate::system_storage::SystemStorage;
+pub use crate::system_storage::{SystemStorage, SystemStorageKv};
 pub use action_file_storage::ActionFileStorage;
 
 pub mod action_file;
 pub mod action_file_storage;
 pub mod backend;
+pub mod backup;
 pub mod block_meta_storage;
 pub mod block_storage;
 pub mod chain_meta_storage;
 pub mod context;
 pub mod context_action_storage;
+pub mod integrity;
 pub mod mempool_storage;
 pub mod merkle_storage;
+pub mod migration;
 pub mod operations_meta_storage;
 pub mod operations_storage;
 pub mod persistent;
@@ -221,6 +225,8 @@ pub struct StorageInitInfo {
     pub chain_id: ChainId,
     pub genesis_block_header_hash: BlockHash,
     pub patch_context: Option<PatchContext>,
+    /// Trusted checkpoint (configured or stored), see [resolve_checkpoint]
+    pub checkpoint: Option<Checkpoint>,
 }
 
 /// Resolve main chain id and genesis header from configuration
@@ -229,12 +235,14 @@ pub fn resolve_storage_init_chain_data(
     storage_db_path: &Path,
     context_db_path: &Path,
     patch_context: &Option<PatchContext>,
+    checkpoint: &Option<Checkpoint>,
     log: &Logger,
 ) -> Result<StorageInitInfo, StorageError> {
     let init_data = StorageInitInfo {
         chain_id: tezos_env.main_chain_id()?,
         genesis_block_header_hash: tezos_env.genesis_header_hash()?,
         patch_context: patch_context.clone(),
+        checkpoint: checkpoint.clone(),
     };
 
     info!(
@@ -249,10 +257,49 @@ pub fn resolve_storage_init_chain_data(
                 Some(pc) => format!("{:?}", pc),
                 None => "-none-".to_string()
         },
+        "checkpoint" => match checkpoint {
+                Some(checkpoint) => format!("{}, level: {}", checkpoint.block_hash().to_base58_check(), checkpoint.level()),
+                None => "-none-".to_string()
+        },
     );
     Ok(init_data)
 }
 
+/// Configured checkpoint is stored to the chain metadata (so it is enforced also after restart without configuration),
+/// if not configured, the stored one is used.
+///
+/// Note: blocks are still applied from the last applied block (genesis for empty storage), headers are just downloaded
+/// from the checkpoint first. Import of a context at the checkpoint is not supported, so savepoint and caboose stay at genesis.
+pub fn resolve_checkpoint(
+    chain_meta_storage: &ChainMetaStorage,
+    init_storage_data: &mut StorageInitInfo,
+    log: &Logger,
+) -> Result<(), StorageError> {
+    let chain_id = &init_storage_data.chain_id;
+    let stored_checkpoint = chain_meta_storage.get_checkpoint(chain_id)?;
+
+    match &init_storage_data.checkpoint {
+        Some(checkpoint) => {
+            if let Some(stored_checkpoint) = &stored_checkpoint {
+                if stored_checkpoint.ne(checkpoint) {
+                    info!(log, "Stored checkpoint is replaced by configured one";
+                               "stored_checkpoint" => stored_checkpoint.block_hash().to_base58_check(),
+                               "stored_checkpoint_level" => stored_checkpoint.level());
+                }
+            }
+            chain_meta_storage.set_checkpoint(chain_id, checkpoint.clone())?;
+        }
+        None => init_storage_data.checkpoint = stored_checkpoint,
+    }
+
+    if let Some(checkpoint) = &init_storage_data.checkpoint {
+        info!(log, "Node accepts just branches with checkpoint";
+                   "checkpoint" => checkpoint.block_hash().to_base58_check(),
+                   "level" => checkpoint.level());
+    }
+    Ok(())
+}
+
 /// Stores apply result to storage and mark block as applied, if everythnig is ok.
 pub fn store_applied_block_result(
     block_storage: &BlockStorage,
@@ -261,6 +308,9 @@ pub fn store_applied_block_result(
     block_result: ApplyBlockResponse,
     block_metadata: &mut block_meta_storage::Meta,
 ) -> Result<(BlockJsonData, BlockAdditionalData), StorageError> {
+    // additional data are taken before json data are moved out of the result
+    let block_additional_data = applied_block_additional_data(&block_result);
+
     // store result data - json and additional data
     let block_json_data = BlockJsonDataBuilder::default()
         .block_header_proto_json(block_result.block_header_proto_json)
@@ -271,10 +321,26 @@ pub fn store_applied_block_result(
     block_storage.put_block_json_data(&block_hash, block_json_data.clone())?;
 
     // store additional data
-    let block_additional_data = BlockAdditionalDataBuilder::default()
+    block_storage.put_block_additional_data(&block_hash, block_additional_data.clone())?;
+
+    // TODO: check context checksum or context_hash
+
+    // if everything is stored and ok, we can considere this block as applied
+    // mark current head as applied
+    block_metadata.set_is_applied(true);
+    block_meta_storage.put(&block_hash, &block_metadata)?;
+    // populate predecessor storage
+    block_meta_storage.store_predecessors(&block_hash, &block_metadata)?;
+
+    Ok((block_json_data, block_additional_data))
+}
+
+/// Additional data of the applied block, which are stored and which are needed for applying of its successors
+pub fn applied_block_additional_data(block_result: &ApplyBlockResponse) -> BlockAdditionalData {
+    BlockAdditionalDataBuilder::default()
         .max_operations_ttl(block_result.max_operations_ttl.try_into().unwrap())
         .last_allowed_fork_level(block_result.last_allowed_fork_level)
-        .block_metadata_hash(block_result.block_metadata_hash)
+        .block_metadata_hash(block_result.block_metadata_hash.clone())
         .ops_metadata_hash({
             // Note: Ocaml introduces this two attributes (block_metadata_hash, ops_metadata_hash) in 008 edo
             //       So, we need to add the same handling, because this attributes contributes to context_hash
@@ -284,27 +350,15 @@ pub fn store_applied_block_result(
                     if hashes.is_empty() {
                         None
                     } else {
-                        block_result.ops_metadata_hash
+                        block_result.ops_metadata_hash.clone()
                     }
                 }
                 None => None,
             }
         })
-        .ops_metadata_hashes(block_result.ops_metadata_hashes)
+        .ops_metadata_hashes(block_result.ops_metadata_hashes.clone())
         .build()
-        .unwrap();
-    block_storage.put_block_additional_data(&block_hash, block_additional_data.clone())?;
-
-    // TODO: check context checksum or context_hash
-
-    // if everything is stored and ok, we can considere this block as applied
-    // mark current head as applied
-    block_metadata.set_is_applied(true);
-    block_meta_storage.put(&block_hash, &block_metadata)?;
-    // populate predecessor storage
-    block_meta_storage.store_predecessors(&block_hash, &block_metadata)?;
-
-    Ok((block_json_data, block_additional_data))
+        .unwrap()
 }
 
 /// Stores commit_genesis result to storage and mark genesis block as applied, if everythnig is ok.
@@ -354,6 +408,8 @@ pub fn store_commit_genesis_result(
             // init chain data
             chain_meta_storage.set_genesis(&chain_id, head.clone())?;
             chain_meta_storage.set_caboose(&chain_id, head.clone())?;
+            // every block is applied from genesis, so savepoint (the lowest block with metadata) is genesis
+            chain_meta_storage.set_savepoint(&chain_id, head.clone())?;
             chain_meta_storage.set_current_head(&chain_id, head)?;
 
             Ok(block_json_data)
@@ -414,21 +470,31 @@ pub fn initialize_storage_with_genesis_block(
 }
 
 pub fn check_database_compatibility(
-    db: Arc<rocksdb::DB>,
+    db: Arc<SystemStorageKv>,
     expected_database_version: i64,
     tezos_env: &TezosEnvironmentConfiguration,
     log: &Logger,
 ) -> Result<bool, StorageError> {
     let mut system_info = SystemStorage::new(db);
-    let db_version_ok = match system_info.get_db_version()? {
-        Some(db_version) => db_version == expected_database_version,
+    let (db_version_ok, found_db_version) = match system_info.get_db_version()? {
+        Some(db_version) => (db_version == expected_database_version, db_version),
         None => {
             system_info.set_db_version(expected_database_version)?;
-            true
+            (true, expected_database_version)
         }
     };
     if !db_version_ok {
-        error!(log, "Incompatible database version found. Please re-sync your node to empty storage - see configuration!");
+        error!(log, "Incompatible database version found and no migration is available. Please re-sync your node to empty storage - see configuration!";
+                    "expected_db_version" => expected_database_version,
+                    "db_version" => found_db_version
+        );
+    }
+    if let Some(progress) = system_info.get_migration_progress()? {
+        error!(log, "Database contains unfinished migration, which will be resumed on next start";
+                    "migration_version" => progress.version,
+                    "migration_step" => progress.step
+        );
+        return Ok(false);
     }
 
     let tezos_env_main_chain_id = tezos_env
@@ -469,9 +535,14 @@ pub fn check_database_compatibility(
     Ok(db_version_ok && chain_id_ok)
 }
 
+/// Storage backend used for all key-value databases (see [StorageBackend](storage_backend::StorageBackend))
 #[derive(PartialEq, Debug, Clone)]
 pub enum KeyValueStoreBackend {
     RocksDB,
+    /// `map_size` - maximal size of the database in bytes (see [LmdbBackend::open](backend::LmdbBackend::open))
+    Lmdb {
+        map_size: usize,
+    },
     InMem,
     Sled,
     BTreeMap,
@@ -514,6 +585,20 @@ pub mod tests_common {
             path: P,
             remove_if_exists: bool,
             remove_on_destroy: bool,
+        ) -> Result<Self, Error> {
+            Self::initialize_with_backend(
+                path,
+                KeyValueStoreBackend::RocksDB,
+                remove_if_exists,
+                remove_on_destroy,
+            )
+        }
+
+        pub fn initialize_with_backend<P: AsRef<Path>>(
+            path: P,
+            backend: KeyValueStoreBackend,
+            remove_if_exists: bool,
+            remove_on_destroy: bool,
         ) -> Result<Self, Error> {
             let path = path.as_ref().to_path_buf();
             // remove previous data if exists
@@ -526,7 +611,8 @@ pub mod tests_common {
             // create common RocksDB block cache to be shared among column families
             let cache = Cache::new_lru_cache(128 * 1024 * 1024)?; // 128 MB
 
-            let kv = open_kv(
+            let kv = open_kv_store(
+                &backend,
                 path.join("db"),
                 vec![
                     block_storage::BlockPrimaryIndex::descriptor(&cache),
@@ -547,13 +633,15 @@ pub mod tests_common {
                 &cfg,
             )?;
 
-            let kv_context = open_kv(
+            let kv_context = open_kv_store(
+                &backend,
                 path.join("context"),
                 vec![MerkleStorage::descriptor(&cache)],
                 &cfg,
             )?;
 
-            let kv_context_action = open_kv(
+            let kv_context_action = open_kv_store(
+                &backend,
                 path.join("context_actions"),
                 vec![
                     ContextActionStorage::descriptor(&cache),
@@ -571,7 +659,6 @@ pub mod tests_common {
                     Arc::new(kv_context),
                     Arc::new(kv_context_action),
                     Arc::new(clog),
-                    KeyValueStoreBackend::RocksDB,
                 ),
                 path,
                 remove_on_destroy,
@@ -63,7 +63,7 @@ use crate::persistent;
 use crate::persistent::database::RocksDBStats;
 use crate::persistent::BincodeEncoded;
 use crate::persistent::{default_table_options, KeyValueSchema};
-use crate::storage_backend::{StorageBackend, StorageBackendError};
+use crate::storage_backend::{StorageBackend, StorageBackendError, WriteBatch};
 
 const HASH_LEN: usize = 32;
 
@@ -135,7 +135,7 @@ pub struct MerkleStorage {
     /// tree with current staging area (currently checked out context)
     current_stage_tree: Option<Tree>,
     current_stage_tree_hash: Option<EntryHash>,
-    db: Box<MerkleStorageKV>,
+    db: Arc<MerkleStorageKV>,
     /// all entries in current staging area
     staged: Vec<(EntryHash, RefCnt, Entry)>,
     /// HashMap for looking up entry index in self.staged by hash
@@ -229,6 +229,13 @@ pub struct OperationLatencies {
     pub op_exec_time_max: f64,
 }
 
+impl OperationLatencies {
+    /// Total time spent in operation
+    pub fn cumul_op_exec_time(&self) -> f64 {
+        self.cumul_op_exec_time
+    }
+}
+
 impl Default for OperationLatencies {
     fn default() -> Self {
         OperationLatencies {
@@ -360,7 +367,7 @@ fn hash_commit(commit: &Commit) -> Result<EntryHash, MerkleError> {
 }
 
 impl MerkleStorage {
-    pub fn new(db: Box<MerkleStorageKV>) -> Self {
+    pub fn new(db: Arc<MerkleStorageKV>) -> Self {
         MerkleStorage {
             db,
             staged: Vec::new(),
@@ -691,6 +698,15 @@ impl MerkleStorage {
         Ok(())
     }
 
+    /// Check if commit for context hash is stored (in staging area or in DB)
+    pub fn contains_commit(&self, context_hash: &EntryHash) -> Result<bool, MerkleError> {
+        match self.get_commit(context_hash) {
+            Ok(_) => Ok(true),
+            Err(MerkleError::EntryNotFound { .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Take the current changes in the staging area, create a commit and persist all changes
     /// to database under the new commit. Return last commit if there are no changes, that is
     /// empty commits are not allowed.
@@ -1157,13 +1173,17 @@ impl MerkleStorage {
 
     /// Persists an entry and its descendants from staged area to database on disk.
     fn persist_staged_entry_to_db(&mut self, entry: &Entry) -> Result<(), MerkleError> {
-        let mut batch: Vec<(EntryHash, ContextValue)> = Vec::new();
+        let mut entries: Vec<(EntryHash, ContextValue)> = Vec::new();
 
         // build list of entries to be persisted
-        self.get_entries_recursively(entry, &mut batch)?;
+        self.get_entries_recursively(entry, &mut entries)?;
 
-        // write all entries at once (depends on backend)
-        self.db.put_batch(batch)?;
+        // write all entries at once
+        let mut batch = WriteBatch::default();
+        for (hash, value) in entries {
+            batch.put(Self::name(), hash.to_vec(), value);
+        }
+        self.db.write_batch(batch)?;
 
         Ok(())
     }
@@ -1239,7 +1259,7 @@ impl MerkleStorage {
     }
 
     fn get_entry_db(&self, hash: &EntryHash) -> Result<Entry, MerkleError> {
-        let entry_bytes = self.db.get(hash)?;
+        let entry_bytes = self.db.get(Self::name(), hash)?;
         match entry_bytes {
             None => Err(MerkleError::EntryNotFound {
                 hash: HashType::ContextHash.hash_to_b58check(hash)?,
@@ -1251,7 +1271,7 @@ impl MerkleStorage {
     fn get_entry(&self, hash: &EntryHash) -> Result<Entry, MerkleError> {
         match self.staged_get(hash) {
             None => {
-                let entry_bytes = self.db.get(hash)?;
+                let entry_bytes = self.db.get(Self::name(), hash)?;
                 match entry_bytes {
                     None => Err(MerkleError::EntryNotFound {
                         hash: HashType::ContextHash.hash_to_b58check(hash)?,
@@ -1414,10 +1434,11 @@ impl MerkleStorage {
 #[cfg(test)]
 #[allow(unused_must_use)]
 mod tests {
-    use crate::backend::{BTreeMapBackend, InMemoryBackend, RocksDBBackend, SledBackend};
+    use crate::backend::{
+        BTreeMapBackend, InMemoryBackend, LmdbBackend, SledBackend, LMDB_DEFAULT_MAP_SIZE,
+    };
     use assert_json_diff::assert_json_eq;
     use rocksdb::{Options, DB};
-    use std::ops::Deref;
     use std::path::{Path, PathBuf};
     use std::{env, fs};
 
@@ -1441,22 +1462,26 @@ mod tests {
         out_dir_path(db_name)
     }
 
+    fn get_lmdb_name(db_name: &str) -> PathBuf {
+        out_dir_path(&format!("{}_lmdb", db_name))
+    }
+
     fn get_db(db_name: &str, cache: &Cache) -> DB {
         open_db(get_db_name(db_name), &cache)
     }
 
     fn get_storage(backend: &str, db_name: &str, cache: &Cache) -> MerkleStorage {
         match backend {
-            "rocksdb" => MerkleStorage::new(Box::new(RocksDBBackend::new(
-                Arc::new(get_db(db_name, &cache)),
-                MerkleStorage::name(),
-            ))),
+            "rocksdb" => MerkleStorage::new(Arc::new(get_db(db_name, &cache))),
+            "lmdb" => MerkleStorage::new(Arc::new(
+                LmdbBackend::open(get_lmdb_name(db_name), LMDB_DEFAULT_MAP_SIZE).unwrap(),
+            )),
             "sled" => {
                 let sled = sled::Config::new().path(db_name).open().unwrap();
-                MerkleStorage::new(Box::new(SledBackend::new(sled.deref().clone())))
+                MerkleStorage::new(Arc::new(SledBackend::new(sled)))
             }
-            "btree" => MerkleStorage::new(Box::new(BTreeMapBackend::new())),
-            "inmem" => MerkleStorage::new(Box::new(InMemoryBackend::new())),
+            "btree" => MerkleStorage::new(Arc::new(BTreeMapBackend::new())),
+            "inmem" => MerkleStorage::new(Arc::new(InMemoryBackend::new())),
             _ => {
                 panic!("unknown backend set")
             }
@@ -1466,6 +1491,7 @@ mod tests {
     fn clean_db(db_name: &str) {
         let _ = DB::destroy(&Options::default(), get_db_name(db_name));
         let _ = fs::remove_dir_all(get_db_name(db_name));
+        let _ = fs::remove_dir_all(get_lmdb_name(db_name));
     }
 
     fn test_duplicate_entry_in_staging(backend: &str) {
@@ -2046,17 +2072,11 @@ mod tests {
         {
             clean_db(db_name);
             let cache = Cache::new_lru_cache(32 * 1024 * 1024).unwrap();
-            MerkleStorage::new(Box::new(RocksDBBackend::new(
-                Arc::new(get_db(db_name, &cache)),
-                MerkleStorage::name(),
-            )));
+            MerkleStorage::new(Arc::new(get_db(db_name, &cache)));
         }
 
         let db = DB::open_for_read_only(&Options::default(), get_db_name(db_name), true).unwrap();
-        let mut storage = MerkleStorage::new(Box::new(RocksDBBackend::new(
-            Arc::new(db),
-            MerkleStorage::name(),
-        )));
+        let mut storage = MerkleStorage::new(Arc::new(db));
         storage.set(&vec!["a".to_string()], &vec![1u8]);
         let res = storage.commit(0, "".to_string(), "".to_string());
         println!("{:?}", res);
@@ -2287,6 +2307,7 @@ mod tests {
     }
 
     tests_with_storage!(rocksdb_tests, "rocksdb");
+    tests_with_storage!(lmdb_tests, "lmdb");
     tests_with_storage!(sled_tests, "sled");
     tests_with_storage!(btree_tests, "btree");
     tests_with_storage!(inmem_tests, "inmem");
@@ -0,0 +1,913 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Database schema versioning with in-place migrations.
+//!
+//! Every RocksDB database (db, context, context_actions) stores its schema version in [SystemStorage].
+//! When node starts with newer schema version, than the one stored in database, all registered migrations
+//! between those two versions are applied one by one (see [migrate_database]).
+//!
+//! Migration consists of [MigrationStep]s, which are processed in batches. Every batch is written
+//! atomically together with [MigrationProgress], so when node crashes in the middle of migration,
+//! next start continues from the last written key.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use failure::Fail;
+use rocksdb::{ColumnFamilyDescriptor, Options, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use slog::{info, warn, Logger};
+
+use crate::persistent::{
+    default_kv_options, BincodeEncoded, DBError, DbConfiguration, Encoder, KeyValueSchema,
+    SchemaError, StorageType,
+};
+use crate::system_storage::{DbVersion, SystemStorageKv, SystemValue};
+use crate::SystemStorage;
+
+/// How many entries are written to database in one (atomic) batch
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// How often (in processed entries) is migration progress logged
+const MIGRATION_LOG_INTERVAL: u64 = 500_000;
+
+/// Computes new key for migrated entry, arguments are: (key, value)
+pub type RekeyFn = Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, SchemaError> + Send + Sync>;
+
+/// Computes new value for migrated entry, arguments are: (key, value)
+pub type ReencodeFn = Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<u8>, SchemaError> + Send + Sync>;
+
+/// Computes index entries (key, value) for source entry, arguments are: (key, value)
+pub type IndexFn =
+    Box<dyn Fn(&[u8], &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, SchemaError> + Send + Sync>;
+
+/// Possible errors for migrations
+#[derive(Debug, Fail)]
+pub enum MigrationError {
+    #[fail(display = "Database error: {}", error)]
+    DBError { error: DBError },
+    #[fail(
+        display = "No migration is registered for database version {} (migrating from: {}, to: {})",
+        missing, from, to
+    )]
+    MissingMigration {
+        missing: DbVersion,
+        from: DbVersion,
+        to: DbVersion,
+    },
+    #[fail(
+        display = "Database version {} is newer than supported version {}",
+        found, supported
+    )]
+    UnsupportedDowngrade {
+        found: DbVersion,
+        supported: DbVersion,
+    },
+    #[fail(
+        display = "Migration to version {} was interrupted, but resume is not possible, reason: {}",
+        version, reason
+    )]
+    InvalidProgress { version: DbVersion, reason: String },
+}
+
+impl From<DBError> for MigrationError {
+    fn from(error: DBError) -> Self {
+        MigrationError::DBError { error }
+    }
+}
+
+impl From<rocksdb::Error> for MigrationError {
+    fn from(error: rocksdb::Error) -> Self {
+        MigrationError::DBError {
+            error: error.into(),
+        }
+    }
+}
+
+impl From<SchemaError> for MigrationError {
+    fn from(error: SchemaError) -> Self {
+        MigrationError::DBError {
+            error: error.into(),
+        }
+    }
+}
+
+/// Building blocks of migrations, every step processes all entries of one source column family.
+pub enum MigrationStep {
+    /// Moves all entries from column family `from` to column family `to`, `from` is dropped afterwards
+    RenameColumnFamily {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Moves all entries from column family `from` to column family `to` under the new key, `from` is dropped afterwards.
+    /// (For rekeying of column family in place, rekey to temporary column family and rename it back with next step.)
+    RekeyColumnFamily {
+        from: &'static str,
+        to: &'static str,
+        rekey: RekeyFn,
+    },
+    /// Rewrites all values of column family `cf` (e.g. changed value encoding)
+    ReencodeValues {
+        cf: &'static str,
+        reencode: ReencodeFn,
+    },
+    /// Fills (new) index column family `index` from all entries of column family `source`
+    BuildIndex {
+        source: &'static str,
+        index: &'static str,
+        index_fn: IndexFn,
+    },
+}
+
+impl MigrationStep {
+    fn source(&self) -> &'static str {
+        match self {
+            MigrationStep::RenameColumnFamily { from, .. } => from,
+            MigrationStep::RekeyColumnFamily { from, .. } => from,
+            MigrationStep::ReencodeValues { cf, .. } => cf,
+            MigrationStep::BuildIndex { source, .. } => source,
+        }
+    }
+
+    fn target(&self) -> &'static str {
+        match self {
+            MigrationStep::RenameColumnFamily { to, .. } => to,
+            MigrationStep::RekeyColumnFamily { to, .. } => to,
+            MigrationStep::ReencodeValues { cf, .. } => cf,
+            MigrationStep::BuildIndex { index, .. } => index,
+        }
+    }
+
+    /// Source column family is removed, when all entries are migrated
+    fn drops_source(&self) -> bool {
+        matches!(
+            self,
+            MigrationStep::RenameColumnFamily { .. } | MigrationStep::RekeyColumnFamily { .. }
+        )
+    }
+
+    /// Returns entries (key, value), which should be written to target column family for source entry
+    fn transform(&self, key: &[u8], value: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, SchemaError> {
+        match self {
+            MigrationStep::RenameColumnFamily { .. } => Ok(vec![(key.to_vec(), value.to_vec())]),
+            MigrationStep::RekeyColumnFamily { rekey, .. } => {
+                Ok(vec![(rekey(key, value)?, value.to_vec())])
+            }
+            MigrationStep::ReencodeValues { reencode, .. } => {
+                Ok(vec![(key.to_vec(), reencode(key, value)?)])
+            }
+            MigrationStep::BuildIndex { index_fn, .. } => index_fn(key, value),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            MigrationStep::RenameColumnFamily { from, to } => {
+                format!("rename '{}' -> '{}'", from, to)
+            }
+            MigrationStep::RekeyColumnFamily { from, to, .. } => {
+                format!("rekey '{}' -> '{}'", from, to)
+            }
+            MigrationStep::ReencodeValues { cf, .. } => format!("reencode values of '{}'", cf),
+            MigrationStep::BuildIndex { source, index, .. } => {
+                format!("build index '{}' from '{}'", index, source)
+            }
+        }
+    }
+}
+
+/// Migration of database from version `version - 1` to `version`.
+pub struct Migration {
+    version: DbVersion,
+    description: &'static str,
+    steps: Vec<MigrationStep>,
+}
+
+impl Migration {
+    pub fn new(version: DbVersion, description: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Appends step to migration, steps are processed in order as they were added
+    pub fn step(mut self, step: MigrationStep) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    pub fn version(&self) -> DbVersion {
+        self.version
+    }
+
+    pub fn description(&self) -> &'static str {
+        self.description
+    }
+}
+
+/// Registry of all known migrations for one database
+#[derive(Default)]
+pub struct MigrationRegistry {
+    migrations: BTreeMap<DbVersion, Migration>,
+}
+
+impl MigrationRegistry {
+    /// Returns all registered migrations for selected database.
+    ///
+    /// New migrations are registered here, when `expected_db_version` of the database is increased.
+    pub fn for_storage(storage: StorageType) -> Self {
+        match storage {
+            StorageType::Database => MigrationRegistry::default(),
+            StorageType::Context => MigrationRegistry::default(),
+            StorageType::ContextAction => MigrationRegistry::default(),
+        }
+    }
+
+    /// Registers migration, migration with the same version is replaced
+    pub fn register(mut self, migration: Migration) -> Self {
+        self.migrations.insert(migration.version, migration);
+        self
+    }
+
+    /// Returns ordered migrations, which are needed to migrate database from `from` to `to` version
+    pub fn pending(
+        &self,
+        from: DbVersion,
+        to: DbVersion,
+    ) -> Result<Vec<&Migration>, MigrationError> {
+        if from > to {
+            return Err(MigrationError::UnsupportedDowngrade {
+                found: from,
+                supported: to,
+            });
+        }
+        ((from + 1)..=to)
+            .map(|version| {
+                self.migrations
+                    .get(&version)
+                    .ok_or(MigrationError::MissingMigration {
+                        missing: version,
+                        from,
+                        to,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Stored state of running migration, which allows to resume migration after crash
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MigrationProgress {
+    /// Target version of running migration
+    pub version: DbVersion,
+    /// Index of running step
+    pub step: usize,
+    /// Last source key, which was already migrated in running step
+    pub last_key: Option<Vec<u8>>,
+    /// All entries of running step are migrated, only finalization (e.g. drop of source column family) is left
+    pub entries_migrated: bool,
+}
+
+impl BincodeEncoded for MigrationProgress {}
+
+impl MigrationProgress {
+    fn start(version: DbVersion) -> Self {
+        Self {
+            version,
+            step: 0,
+            last_key: None,
+            entries_migrated: false,
+        }
+    }
+
+    fn next_step(&mut self) {
+        self.step += 1;
+        self.last_key = None;
+        self.entries_migrated = false;
+    }
+}
+
+/// Result of one (applied or dry-run) migration
+#[derive(Debug, Clone)]
+pub struct MigrationSummary {
+    pub version: DbVersion,
+    pub description: &'static str,
+    /// Count of processed source entries
+    pub processed: u64,
+}
+
+/// Applies all pending migrations to the database at `path`.
+///
+/// Database is opened with column families of the actual schema `cfs` (name, descriptor), so merge operators
+/// and other column family options are the same as in the running node. All other already existing column families
+/// (e.g. renamed/removed ones, which are not part of the actual schema) are opened with default options.
+/// Not existing database is considered as up to date.
+///
+/// In `dry_run` mode all pending migrations are just validated (every entry is transformed, but nothing is written).
+///
+/// Returns summary of processed migrations.
+pub fn migrate_database<P, I>(
+    path: P,
+    cfs: I,
+    cfg: &DbConfiguration,
+    registry: &MigrationRegistry,
+    expected_version: DbVersion,
+    dry_run: bool,
+    log: &Logger,
+) -> Result<Vec<MigrationSummary>, MigrationError>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = (&'static str, ColumnFamilyDescriptor)>,
+{
+    let path = path.as_ref();
+    let existing_cfs = match DB::list_cf(&Options::default(), path) {
+        Ok(existing_cfs) => existing_cfs,
+        // database does not exist yet, so there is nothing to migrate
+        Err(_) => return Ok(Vec::new()),
+    };
+    // nothing is created in dry-run mode, so just already existing column families of the schema are opened
+    let (known_names, mut descriptors): (Vec<_>, Vec<_>) = cfs
+        .into_iter()
+        .filter(|(name, _)| !dry_run || existing_cfs.iter().any(|existing| existing == name))
+        .unzip();
+    descriptors.extend(
+        existing_cfs
+            .into_iter()
+            .filter(|name| !known_names.iter().any(|known| known == name))
+            .map(|name| ColumnFamilyDescriptor::new(name, Options::default())),
+    );
+    let mut db = DB::open_cf_descriptors(&default_kv_options(cfg), path, descriptors)
+        .map_err(DBError::from)?;
+
+    let current_version = match db_version(&db)? {
+        Some(version) => version,
+        // version was never stored, database is initialized later by check_database_compatibility
+        None => return Ok(Vec::new()),
+    };
+    if current_version == expected_version {
+        return Ok(Vec::new());
+    }
+
+    let migrations = registry.pending(current_version, expected_version)?;
+    info!(log, "Database migration is required";
+               "path" => path.display().to_string(),
+               "db_version" => current_version,
+               "expected_db_version" => expected_version,
+               "pending_migrations" => migrations.len(),
+               "dry_run" => dry_run);
+
+    let mut summaries = Vec::with_capacity(migrations.len());
+    for migration in migrations {
+        summaries.push(apply_migration(&mut db, migration, dry_run, log)?);
+    }
+
+    if dry_run {
+        info!(log, "Database migration dry-run finished, database was not modified";
+                   "path" => path.display().to_string());
+    } else {
+        db.flush().map_err(DBError::from)?;
+        info!(log, "Database migrated successfully";
+                   "path" => path.display().to_string(),
+                   "db_version" => expected_version);
+    }
+
+    Ok(summaries)
+}
+
+fn apply_migration(
+    db: &mut DB,
+    migration: &Migration,
+    dry_run: bool,
+    log: &Logger,
+) -> Result<MigrationSummary, MigrationError> {
+    let mut progress = match migration_progress(db)? {
+        Some(progress) if progress.version == migration.version => {
+            if progress.step > migration.steps.len() {
+                return Err(MigrationError::InvalidProgress {
+                    version: migration.version,
+                    reason: format!(
+                        "stored step {} is out of migration steps (count: {})",
+                        progress.step,
+                        migration.steps.len()
+                    ),
+                });
+            }
+            info!(log, "Resuming interrupted migration";
+                       "version" => migration.version,
+                       "step" => progress.step,
+                       "entries_migrated" => progress.entries_migrated);
+            progress
+        }
+        Some(progress) => {
+            return Err(MigrationError::InvalidProgress {
+                version: migration.version,
+                reason: format!(
+                    "stored progress belongs to migration of version {}",
+                    progress.version
+                ),
+            });
+        }
+        None => MigrationProgress::start(migration.version),
+    };
+
+    info!(log, "Applying migration";
+               "version" => migration.version,
+               "description" => migration.description,
+               "steps" => migration.steps.len(),
+               "dry_run" => dry_run);
+
+    let mut processed = 0;
+    while progress.step < migration.steps.len() {
+        let step = &migration.steps[progress.step];
+        info!(log, "Migration step started";
+                   "version" => migration.version,
+                   "step" => progress.step,
+                   "description" => step.description());
+
+        if !progress.entries_migrated {
+            processed += migrate_entries(db, migration.version, step, &mut progress, dry_run, log)?;
+        }
+
+        if dry_run {
+            // nothing is written in dry-run mode, so we just continue with next step
+            progress.next_step();
+            continue;
+        }
+
+        // finalize step - drop of missing column family is ok, because finalization could be already
+        // done before crash (but progress was not stored)
+        if step.drops_source() && db.cf_handle(step.source()).is_some() {
+            db.drop_cf(step.source()).map_err(DBError::from)?;
+        }
+
+        progress.next_step();
+        let mut batch = WriteBatch::default();
+        put_system_value(
+            db,
+            &mut batch,
+            SystemStorage::MIGRATION_PROGRESS,
+            &SystemValue::MigrationProgress(progress.clone()),
+        )?;
+        db.write(batch).map_err(DBError::from)?;
+    }
+
+    if !dry_run {
+        // mark migration as finished - set new version and remove progress atomically
+        let mut batch = WriteBatch::default();
+        put_system_value(
+            db,
+            &mut batch,
+            SystemStorage::DB_VERSION,
+            &SystemValue::Integer(migration.version),
+        )?;
+        let cf = db
+            .cf_handle(SystemStorage::name())
+            .ok_or(DBError::MissingColumnFamily {
+                name: SystemStorage::name(),
+            })?;
+        batch.delete_cf(cf, SystemStorage::MIGRATION_PROGRESS.as_bytes());
+        db.write(batch).map_err(DBError::from)?;
+    }
+
+    info!(log, "Migration finished";
+               "version" => migration.version,
+               "processed_entries" => processed,
+               "dry_run" => dry_run);
+
+    Ok(MigrationSummary {
+        version: migration.version,
+        description: migration.description,
+        processed,
+    })
+}
+
+/// Migrates all source entries of the step (starting after `progress.last_key`), returns count of processed entries
+fn migrate_entries(
+    db: &mut DB,
+    version: DbVersion,
+    step: &MigrationStep,
+    progress: &mut MigrationProgress,
+    dry_run: bool,
+    log: &Logger,
+) -> Result<u64, MigrationError> {
+    if !dry_run && db.cf_handle(step.target()).is_none() {
+        db.create_cf(step.target(), &Options::default())
+            .map_err(DBError::from)?;
+    }
+
+    let db: &DB = db;
+    let source = match db.cf_handle(step.source()) {
+        Some(source) => source,
+        None if dry_run => {
+            // source could be created by one of the previous steps, which were not written in dry-run mode
+            warn!(log, "Source column family does not exist, step cannot be validated";
+                       "version" => version,
+                       "step" => progress.step,
+                       "column_family" => step.source());
+            return Ok(0);
+        }
+        None => {
+            return Err(DBError::MissingColumnFamily {
+                name: step.source(),
+            }
+            .into())
+        }
+    };
+    // in dry-run mode target does not need to exist yet (just validation)
+    let target = db.cf_handle(step.target());
+    let estimated_count = db
+        .property_int_value_cf(source, "rocksdb.estimate-num-keys")
+        .unwrap_or(None);
+
+    let iterator = match &progress.last_key {
+        Some(last_key) => db.iterator_cf(
+            source,
+            rocksdb::IteratorMode::From(last_key.as_slice(), rocksdb::Direction::Forward),
+        ),
+        None => db.iterator_cf(source, rocksdb::IteratorMode::Start),
+    };
+
+    let mut processed: u64 = 0;
+    let mut batch = WriteBatch::default();
+    let mut batch_entries = 0;
+    for (key, value) in iterator {
+        // iterator starts at the last migrated key (inclusive)
+        if progress.last_key.as_deref() == Some(&*key) {
+            continue;
+        }
+
+        let entries = step.transform(&key, &value)?;
+        if let Some(target) = target {
+            if !dry_run {
+                entries
+                    .iter()
+                    .for_each(|(key, value)| batch.put_cf(target, key, value));
+            }
+        }
+        progress.last_key = Some(key.to_vec());
+        processed += 1;
+        batch_entries += 1;
+
+        if batch_entries >= MIGRATION_BATCH_SIZE {
+            if !dry_run {
+                write_batch_with_progress(db, batch, progress)?;
+            }
+            batch = WriteBatch::default();
+            batch_entries = 0;
+        }
+
+        if processed % MIGRATION_LOG_INTERVAL == 0 {
+            info!(log, "Migration step in progress";
+                       "version" => version,
+                       "step" => progress.step,
+                       "processed_entries" => processed,
+                       "estimated_entries" => estimated_count.map_or("-unknown-".to_string(), |count| count.to_string()));
+        }
+    }
+
+    progress.entries_migrated = true;
+    if !dry_run {
+        write_batch_with_progress(db, batch, progress)?;
+    }
+
+    info!(log, "Migration step entries processed";
+               "version" => version,
+               "step" => progress.step,
+               "processed_entries" => processed);
+
+    Ok(processed)
+}
+
+/// Writes migrated entries together with actual progress atomically
+fn write_batch_with_progress(
+    db: &DB,
+    mut batch: WriteBatch,
+    progress: &MigrationProgress,
+) -> Result<(), MigrationError> {
+    put_system_value(
+        db,
+        &mut batch,
+        SystemStorage::MIGRATION_PROGRESS,
+        &SystemValue::MigrationProgress(progress.clone()),
+    )?;
+    db.write(batch)
+        .map_err(DBError::from)
+        .map_err(MigrationError::from)
+}
+
+/// Adds system value to the RocksDB write batch (migrations work directly with RocksDB)
+fn put_system_value(
+    db: &DB,
+    batch: &mut WriteBatch,
+    key: &str,
+    value: &SystemValue,
+) -> Result<(), MigrationError> {
+    let cf = db
+        .cf_handle(SystemStorage::name())
+        .ok_or(DBError::MissingColumnFamily {
+            name: SystemStorage::name(),
+        })?;
+    batch.put_cf(cf, key.to_string().encode()?, Encoder::encode(value)?);
+    Ok(())
+}
+
+#[inline]
+fn system_kv(db: &DB) -> &SystemStorageKv {
+    db
+}
+
+fn db_version(db: &DB) -> Result<Option<DbVersion>, MigrationError> {
+    match system_kv(db).get(&SystemStorage::DB_VERSION.to_string())? {
+        Some(SystemValue::Integer(version)) => Ok(Some(version)),
+        _ => Ok(None),
+    }
+}
+
+fn migration_progress(db: &DB) -> Result<Option<MigrationProgress>, MigrationError> {
+    match system_kv(db).get(&SystemStorage::MIGRATION_PROGRESS.to_string())? {
+        Some(SystemValue::MigrationProgress(progress)) => Ok(Some(progress)),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, fs};
+
+    use failure::Error;
+    use slog::{Drain, Level};
+
+    use crate::persistent::open_kv;
+
+    use super::*;
+
+    const OLD_CF: &str = "migration_test_old";
+    const NEW_CF: &str = "migration_test_new";
+    const INDEX_CF: &str = "migration_test_index";
+
+    fn test_path(name: &str) -> PathBuf {
+        let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not defined - check build.rs");
+        let path = Path::new(out_dir.as_str()).join(name);
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        path
+    }
+
+    fn test_logger() -> Logger {
+        let drain = slog_async::Async::new(
+            slog_term::FullFormat::new(slog_term::TermDecorator::new().build())
+                .build()
+                .fuse(),
+        )
+        .build()
+        .filter_level(Level::Info)
+        .fuse();
+        Logger::root(drain, slog::o!())
+    }
+
+    /// Creates database with version 1 and `count` entries in [OLD_CF]
+    fn prepare_db(path: &Path, count: u32) -> Result<(), Error> {
+        let db = open_kv(
+            path,
+            vec![
+                ColumnFamilyDescriptor::new(SystemStorage::name(), Options::default()),
+                ColumnFamilyDescriptor::new(OLD_CF, Options::default()),
+            ],
+            &DbConfiguration::default(),
+        )?;
+        system_kv(&db).put(
+            &SystemStorage::DB_VERSION.to_string(),
+            &SystemValue::Integer(1),
+        )?;
+        let cf = db.cf_handle(OLD_CF).unwrap();
+        for i in 0..count {
+            db.put_cf(cf, i.to_be_bytes(), (i * 2).to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn schema_v2() -> Vec<(&'static str, ColumnFamilyDescriptor)> {
+        vec![SystemStorage::name(), NEW_CF, INDEX_CF]
+            .into_iter()
+            .map(|name| (name, ColumnFamilyDescriptor::new(name, Options::default())))
+            .collect()
+    }
+
+    fn open_v2(path: &Path) -> Result<DB, DBError> {
+        open_kv(
+            path,
+            schema_v2().into_iter().map(|(_, descriptor)| descriptor),
+            &DbConfiguration::default(),
+        )
+    }
+
+    fn registry_v2() -> MigrationRegistry {
+        registry_v2_interrupted_at(None)
+    }
+
+    /// Migration fails, when value of entry with key `interrupt_at` should be reencoded (simulates crash of the node)
+    fn registry_v2_interrupted_at(interrupt_at: Option<u32>) -> MigrationRegistry {
+        let interrupt_at = interrupt_at.map(|key| key.to_be_bytes().to_vec());
+        MigrationRegistry::default().register(
+            Migration::new(2, "test migration")
+                .step(MigrationStep::RenameColumnFamily {
+                    from: OLD_CF,
+                    to: NEW_CF,
+                })
+                .step(MigrationStep::ReencodeValues {
+                    cf: NEW_CF,
+                    reencode: Box::new(move |key, value| {
+                        if interrupt_at.as_deref() == Some(key) {
+                            return Err(SchemaError::EncodeError);
+                        }
+                        Ok([value, &[0xffu8][..]].concat())
+                    }),
+                })
+                .step(MigrationStep::BuildIndex {
+                    source: NEW_CF,
+                    index: INDEX_CF,
+                    index_fn: Box::new(|key, value| Ok(vec![(value[0..4].to_vec(), key.to_vec())])),
+                }),
+        )
+    }
+
+    #[test]
+    fn test_pending_migrations() {
+        let registry = MigrationRegistry::default()
+            .register(Migration::new(2, "v2"))
+            .register(Migration::new(3, "v3"));
+
+        assert_eq!(0, registry.pending(3, 3).unwrap().len());
+        assert_eq!(
+            vec![2, 3],
+            registry
+                .pending(1, 3)
+                .unwrap()
+                .iter()
+                .map(|m| m.version())
+                .collect::<Vec<_>>()
+        );
+        assert!(matches!(
+            registry.pending(0, 3),
+            Err(MigrationError::MissingMigration { missing: 1, .. })
+        ));
+        assert!(matches!(
+            registry.pending(4, 3),
+            Err(MigrationError::UnsupportedDowngrade { .. })
+        ));
+    }
+
+    #[test]
+    fn test_migrate_database() -> Result<(), Error> {
+        let path = test_path("__migration_test_migrate_database");
+        prepare_db(&path, 25_000)?;
+
+        let summaries = migrate_database(
+            &path,
+            schema_v2(),
+            &DbConfiguration::default(),
+            &registry_v2(),
+            2,
+            false,
+            &test_logger(),
+        )?;
+        assert_eq!(1, summaries.len());
+        assert_eq!(3 * 25_000, summaries[0].processed);
+
+        // old column family is dropped
+        assert!(!DB::list_cf(&Options::default(), &path)?.contains(&OLD_CF.to_string()));
+
+        let db = open_v2(&path)?;
+        assert_eq!(Some(2), db_version(&db)?);
+        assert_eq!(None, migration_progress(&db)?);
+
+        let new_cf = db.cf_handle(NEW_CF).unwrap();
+        let index_cf = db.cf_handle(INDEX_CF).unwrap();
+        assert_eq!(
+            Some([14u32.to_be_bytes().to_vec(), vec![0xff]].concat()),
+            db.get_cf(new_cf, 7u32.to_be_bytes())?
+        );
+        assert_eq!(
+            Some(7u32.to_be_bytes().to_vec()),
+            db.get_cf(index_cf, 14u32.to_be_bytes())?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_database_dry_run() -> Result<(), Error> {
+        let path = test_path("__migration_test_migrate_database_dry_run");
+        prepare_db(&path, 100)?;
+
+        let summaries = migrate_database(
+            &path,
+            schema_v2(),
+            &DbConfiguration::default(),
+            &registry_v2(),
+            2,
+            true,
+            &test_logger(),
+        )?;
+        assert_eq!(1, summaries.len());
+        // dry-run does not rename, so next steps cannot be validated
+        assert_eq!(100, summaries[0].processed);
+
+        let db = open_kv(
+            &path,
+            vec![
+                ColumnFamilyDescriptor::new(SystemStorage::name(), Options::default()),
+                ColumnFamilyDescriptor::new(OLD_CF, Options::default()),
+                ColumnFamilyDescriptor::new(NEW_CF, Options::default()),
+                ColumnFamilyDescriptor::new(INDEX_CF, Options::default()),
+            ],
+            &DbConfiguration::default(),
+        )?;
+        assert_eq!(Some(1), db_version(&db)?);
+        assert_eq!(None, migration_progress(&db)?);
+        assert_eq!(
+            100,
+            db.iterator_cf(db.cf_handle(OLD_CF).unwrap(), rocksdb::IteratorMode::Start)
+                .count()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_database_resume() -> Result<(), Error> {
+        let path = test_path("__migration_test_migrate_database_resume");
+        prepare_db(&path, 25_000)?;
+
+        // node crashes during the second step, after the first step and the first batch of the second step were written
+        let result = migrate_database(
+            &path,
+            schema_v2(),
+            &DbConfiguration::default(),
+            &registry_v2_interrupted_at(Some(15_000)),
+            2,
+            false,
+            &test_logger(),
+        );
+        assert!(result.is_err());
+        {
+            let db = open_v2(&path)?;
+            assert_eq!(Some(1), db_version(&db)?);
+            assert_eq!(
+                Some(MigrationProgress {
+                    version: 2,
+                    step: 1,
+                    last_key: Some((MIGRATION_BATCH_SIZE as u32 - 1).to_be_bytes().to_vec()),
+                    entries_migrated: false,
+                }),
+                migration_progress(&db)?
+            );
+        }
+
+        // next start resumes the second step after the last written batch
+        let summaries = migrate_database(
+            &path,
+            schema_v2(),
+            &DbConfiguration::default(),
+            &registry_v2(),
+            2,
+            false,
+            &test_logger(),
+        )?;
+        assert_eq!(
+            (25_000 - MIGRATION_BATCH_SIZE as u64) + 25_000,
+            summaries[0].processed
+        );
+        assert!(!DB::list_cf(&Options::default(), &path)?.contains(&OLD_CF.to_string()));
+
+        // all records are kept and every value is reencoded exactly once
+        let db = open_v2(&path)?;
+        assert_eq!(Some(2), db_version(&db)?);
+        assert_eq!(None, migration_progress(&db)?);
+        let new_cf = db.cf_handle(NEW_CF).unwrap();
+        let index_cf = db.cf_handle(INDEX_CF).unwrap();
+        assert_eq!(
+            25_000,
+            db.iterator_cf(new_cf, rocksdb::IteratorMode::Start).count()
+        );
+        assert_eq!(
+            25_000,
+            db.iterator_cf(index_cf, rocksdb::IteratorMode::Start)
+                .count()
+        );
+        for i in 0..25_000u32 {
+            assert_eq!(
+                Some([(i * 2).to_be_bytes().to_vec(), vec![0xff]].concat()),
+                db.get_cf(new_cf, i.to_be_bytes())?
+            );
+            assert_eq!(
+                Some(i.to_be_bytes().to_vec()),
+                db.get_cf(index_cf, (i * 2).to_be_bytes())?
+            );
+        }
+        Ok(())
+    }
+}
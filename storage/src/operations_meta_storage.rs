@@ -15,6 +15,7 @@ use crate::persistent::{
     default_table_options, Decoder, Encoder, KeyValueSchema, KeyValueStoreWithSchema,
     PersistentStorage, SchemaError,
 };
+use crate::storage_backend::MergeOperator;
 use crate::{num_from_slice, persistent::StorageType};
 use crate::{BlockHeaderWithHash, StorageError};
 
@@ -139,48 +140,59 @@ impl KeyValueSchema for OperationsMetaStorage {
     fn name() -> &'static str {
         "operations_meta_storage"
     }
+
+    fn merge_operator() -> Option<MergeOperator> {
+        Some(merge_meta_operand)
+    }
 }
 
 fn merge_meta_value(
-    _new_key: &[u8],
+    new_key: &[u8],
     existing_val: Option<&[u8]>,
     operands: &mut MergeOperands,
 ) -> Option<Vec<u8>> {
     let mut result = existing_val.map(|v| v.to_vec());
 
     for op in operands {
-        match result {
-            Some(ref mut val) => {
-                debug_assert_eq!(
-                    val.len(),
-                    op.len(),
-                    "Value length is fixed. expected={}, found={}",
-                    val.len(),
-                    op.len()
-                );
-                debug_assert_ne!(0, val.len(), "Value cannot have zero size");
-                debug_assert_eq!(val[0], op[0], "Value of validation passes cannot change");
-                // in case of inconsistency, return `None`
-                if val.len() == 0 || val.len() != op.len() || val[0] != op[0] {
-                    return None;
-                }
-
-                let validation_passes = val[0] as usize;
-                // merge `is_validation_pass_present`
-                for i in 1..=validation_passes {
-                    val[i] |= op[i]
-                }
-                // merge `is_complete`
-                let is_complete_idx = validation_passes + 1;
-                val[is_complete_idx] |= op[is_complete_idx];
-            }
-            None => result = Some(op.to_vec()),
-        }
+        result = Some(merge_meta_operand(new_key, result.as_deref(), op)?);
     }
 
     result
 }
 
+/// Merges single operand to the existing value (used directly by backends without native merge support)
+fn merge_meta_operand(_key: &[u8], existing_val: Option<&[u8]>, op: &[u8]) -> Option<Vec<u8>> {
+    let mut val = match existing_val {
+        Some(val) => val.to_vec(),
+        None => return Some(op.to_vec()),
+    };
+
+    debug_assert_eq!(
+        val.len(),
+        op.len(),
+        "Value length is fixed. expected={}, found={}",
+        val.len(),
+        op.len()
+    );
+    debug_assert_ne!(0, val.len(), "Value cannot have zero size");
+    debug_assert_eq!(val[0], op[0], "Value of validation passes cannot change");
+    // in case of inconsistency, return `None`
+    if val.len() == 0 || val.len() != op.len() || val[0] != op[0] {
+        return None;
+    }
+
+    let validation_passes = val[0] as usize;
+    // merge `is_validation_pass_present`
+    for i in 1..=validation_passes {
+        val[i] |= op[i]
+    }
+    // merge `is_complete`
+    let is_complete_idx = validation_passes + 1;
+    val[is_complete_idx] |= op[is_complete_idx];
+
+    Some(val)
+}
+
 /// Block operations metadata
 #[derive(PartialEq, Debug)]
 pub struct Meta {
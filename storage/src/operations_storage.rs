@@ -102,6 +102,10 @@ impl KeyValueSchema for OperationsStorage {
     fn name() -> &'static str {
         "operations_storage"
     }
+
+    fn prefix_len() -> Option<usize> {
+        Some(HashType::BlockHash.size())
+    }
 }
 
 #[derive(Debug, PartialEq)]
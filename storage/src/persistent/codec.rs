@@ -18,6 +18,8 @@ pub enum SchemaError {
     DecodeError,
     #[fail(display = "Failed to decode value: {}", 0)]
     DecodeValidationError(String),
+    #[fail(display = "Failed to read value from storage: {}", 0)]
+    ReadError(String),
 }
 
 impl From<crypto::hash::FromBytesError> for SchemaError {
@@ -219,10 +219,7 @@ impl CommitLogs {
             std::fs::create_dir_all(&path)?;
         }
 
-        let mut opts = LogOptions::new(&path);
-        // TODO: TE-396 - rework
-        opts.message_max_bytes(15_000_000);
-        let log = CommitLog::new(opts)?;
+        let log = open_commit_log(&path)?;
 
         let mut commit_log_map = self.commit_log_map.write().unwrap();
         commit_log_map.insert(name.into(), Arc::new(RwLock::new(log)));
@@ -249,6 +246,62 @@ impl CommitLogs {
     }
 }
 
+impl CommitLogs {
+    /// Directory, where all registered commit logs are stored (every commit log in its own sub-directory).
+    #[inline]
+    pub(crate) fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
+    /// Blocks appends to all registered commit logs, flushes them and calls `f` with offsets of last stored records.
+    ///
+    /// Appends are blocked until `f` returns, so it can be used to take a snapshot of other stores,
+    /// which is consistent with the commit logs.
+    pub(crate) fn with_frozen<F, R>(&self, f: F) -> Result<R, CommitLogError>
+    where
+        F: FnOnce(&HashMap<String, Option<Offset>>) -> R,
+    {
+        let commit_log_map = self.commit_log_map.read().unwrap();
+        let mut frozen = Vec::with_capacity(commit_log_map.len());
+        let mut offsets = HashMap::with_capacity(commit_log_map.len());
+        for (name, commit_log) in commit_log_map.iter() {
+            let mut commit_log = commit_log.write().expect("Write lock failed");
+            commit_log.flush()?;
+            offsets.insert(name.clone(), commit_log.last_offset());
+            frozen.push(commit_log);
+        }
+
+        Ok(f(&offsets))
+    }
+}
+
+/// Truncates (copy of) commit log stored in `path`, so it contains only records up to `last_offset` (inclusive).
+pub(crate) fn truncate_commit_log<P: AsRef<Path>>(
+    path: P,
+    last_offset: Option<Offset>,
+) -> Result<(), CommitLogError> {
+    match last_offset {
+        Some(last_offset) => {
+            let mut log = open_commit_log(path)?;
+            log.truncate(last_offset)?;
+            log.flush()?;
+        }
+        None => {
+            // commit log was empty
+            std::fs::remove_dir_all(&path)?;
+            std::fs::create_dir_all(&path)?;
+        }
+    }
+    Ok(())
+}
+
+fn open_commit_log<P: AsRef<Path>>(path: P) -> Result<CommitLog, CommitLogError> {
+    let mut opts = LogOptions::new(path);
+    // TODO: TE-396 - rework
+    opts.message_max_bytes(15_000_000);
+    CommitLog::new(opts).map_err(CommitLogError::from)
+}
+
 impl Drop for CommitLogs {
     fn drop(&mut self) {
         let _ = self.flush().expect("Failed to flush commit logs");
@@ -2,13 +2,18 @@
 // SPDX-License-Identifier: MIT
 
 use std::marker::PhantomData;
+use std::path::Path;
 
 use failure::Fail;
-use rocksdb::{DBIterator, Error, WriteBatch, WriteOptions, DB};
+use rocksdb::{Error, DB};
 use serde::Serialize;
 
 use crate::persistent::codec::{Decoder, Encoder, SchemaError};
 use crate::persistent::schema::KeyValueSchema;
+use crate::storage_backend::{
+    BackendIterator, BackendIteratorMode, MergeOperator, StorageBackend, StorageBackendError,
+    WriteBatch,
+};
 
 #[derive(Serialize, Debug, Clone)]
 pub struct RocksDBStats {
@@ -29,6 +34,8 @@ pub enum DBError {
     MissingColumnFamily { name: &'static str },
     #[fail(display = "Database incompatibility {}", name)]
     DatabaseIncompatibility { name: String },
+    #[fail(display = "Storage backend error: {}", error)]
+    StorageBackendError { error: StorageBackendError },
 }
 
 impl From<SchemaError> for DBError {
@@ -43,6 +50,18 @@ impl From<Error> for DBError {
     }
 }
 
+impl From<StorageBackendError> for DBError {
+    fn from(error: StorageBackendError) -> Self {
+        match error {
+            StorageBackendError::RocksDBError { error } => DBError::RocksDBError { error },
+            StorageBackendError::MissingColumnFamily { name } => {
+                DBError::MissingColumnFamily { name }
+            }
+            error => DBError::StorageBackendError { error },
+        }
+    }
+}
+
 impl slog::Value for DBError {
     fn serialize(
         &self,
@@ -54,7 +73,7 @@ impl slog::Value for DBError {
     }
 }
 
-/// Custom trait extending RocksDB to better handle and enforce database schema
+/// Custom trait extending [StorageBackend] to better handle and enforce database schema
 pub trait KeyValueStoreWithSchema<S: KeyValueSchema> {
     /// Insert new key value pair into the database. If key already exists, method will fail
     ///
@@ -85,11 +104,11 @@ pub trait KeyValueStoreWithSchema<S: KeyValueSchema> {
     /// Read all entries in database.
     ///
     /// # Arguments
-    /// * `mode` - Reading mode, specified by RocksDB, From start to end, from end to start, or from
+    /// * `mode` - Reading mode, From start to end, from end to start, or from
     /// arbitrary position to end.
     fn iterator(&self, mode: IteratorMode<S>) -> Result<IteratorWithSchema<S>, DBError>;
 
-    /// Starting from given key, read all entries to the end.
+    /// Starting from given key, read all entries with the same key prefix (see [KeyValueSchema::prefix_len]).
     ///
     /// # Arguments
     /// * `key` - Key (specified by schema), from which to start reading entries
@@ -136,46 +155,32 @@ impl GetInMemStats for DB {
     }
 }
 
-impl<S: KeyValueSchema> KeyValueStoreWithSchema<S> for DB {
+impl<S: KeyValueSchema, B: StorageBackend> KeyValueStoreWithSchema<S> for B {
     fn put(&self, key: &S::Key, value: &S::Value) -> Result<(), DBError> {
         let key = key.encode()?;
         let value = value.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
-        self.put_cf_opt(cf, &key, &value, &default_write_options())
-            .map_err(DBError::from)
+        StorageBackend::put(self, S::name(), &key, &value).map_err(DBError::from)
     }
 
     fn delete(&self, key: &S::Key) -> Result<(), DBError> {
         let key = key.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
-        self.delete_cf_opt(cf, &key, &default_write_options())
-            .map_err(DBError::from)
+        StorageBackend::delete(self, S::name(), &key).map_err(DBError::from)
     }
 
     fn merge(&self, key: &S::Key, value: &S::Value) -> Result<(), DBError> {
         let key = key.encode()?;
         let value = value.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
-        self.merge_cf_opt(cf, &key, &value, &default_write_options())
+        StorageBackend::merge(self, S::name(), &key, &value, S::merge_operator())
             .map_err(DBError::from)
     }
 
     fn get(&self, key: &S::Key) -> Result<Option<S::Value>, DBError> {
         let key = key.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
-        self.get_cf(cf, &key)
+        StorageBackend::get(self, S::name(), &key)
             .map_err(DBError::from)?
             .map(|value| S::Value::decode(&value))
             .transpose()
@@ -183,42 +188,37 @@ impl<S: KeyValueSchema> KeyValueStoreWithSchema<S> for DB {
     }
 
     fn iterator(&self, mode: IteratorMode<S>) -> Result<IteratorWithSchema<S>, DBError> {
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
-
         let iter = match mode {
-            IteratorMode::Start => self.iterator_cf(cf, rocksdb::IteratorMode::Start),
-            IteratorMode::End => self.iterator_cf(cf, rocksdb::IteratorMode::End),
-            IteratorMode::From(key, direction) => self.iterator_cf(
-                cf,
-                rocksdb::IteratorMode::From(&key.encode()?, direction.into()),
+            IteratorMode::Start => {
+                StorageBackend::iterator(self, S::name(), BackendIteratorMode::Start)
+            }
+            IteratorMode::End => {
+                StorageBackend::iterator(self, S::name(), BackendIteratorMode::End)
+            }
+            IteratorMode::From(key, direction) => StorageBackend::iterator(
+                self,
+                S::name(),
+                BackendIteratorMode::From(&key.encode()?, direction),
             ),
-        };
+        }?;
 
         Ok(IteratorWithSchema(iter, PhantomData))
     }
 
     fn prefix_iterator(&self, key: &S::Key) -> Result<IteratorWithSchema<S>, DBError> {
         let key = key.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
+        let prefix_len = S::prefix_len().unwrap_or_else(|| key.len());
 
         Ok(IteratorWithSchema(
-            self.prefix_iterator_cf(cf, key),
+            StorageBackend::prefix_iterator(self, S::name(), &key, prefix_len)?,
             PhantomData,
         ))
     }
 
     fn contains(&self, key: &S::Key) -> Result<bool, DBError> {
         let key = key.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
 
-        let val = self.get_pinned_cf(cf, &key)?;
-        Ok(val.is_some())
+        StorageBackend::contains(self, S::name(), &key).map_err(DBError::from)
     }
 
     fn put_batch(
@@ -227,44 +227,127 @@ impl<S: KeyValueSchema> KeyValueStoreWithSchema<S> for DB {
         key: &S::Key,
         value: &S::Value,
     ) -> Result<(), DBError> {
-        let key = key.encode()?;
-        let value = value.encode()?;
-        let cf = self
-            .cf_handle(S::name())
-            .ok_or(DBError::MissingColumnFamily { name: S::name() })?;
-
-        batch.put_cf(cf, &key, &value);
+        batch.put(S::name(), key.encode()?, value.encode()?);
 
         Ok(())
     }
 
     fn write_batch(&self, batch: WriteBatch) -> Result<(), DBError> {
-        self.write_opt(batch, &default_write_options())?;
-        Ok(())
+        StorageBackend::write_batch(self, batch).map_err(DBError::from)
     }
 }
 
-fn default_write_options() -> WriteOptions {
-    let mut opts = WriteOptions::default();
-    opts.set_sync(false);
-    opts
+/// Database shared by storages, backed by any [StorageBackend] (selected by [KeyValueStoreBackend](crate::KeyValueStoreBackend))
+pub struct KeyValueStore {
+    backend: Box<dyn StorageBackend>,
+}
+
+impl KeyValueStore {
+    pub fn new<B: StorageBackend + 'static>(backend: B) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+}
+
+impl StorageBackend for KeyValueStore {
+    fn name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    fn is_persisted(&self) -> bool {
+        self.backend.is_persisted()
+    }
+
+    fn get(
+        &self,
+        column: &'static str,
+        key: &[u8],
+    ) -> Result<Option<Vec<u8>>, StorageBackendError> {
+        self.backend.get(column, key)
+    }
+
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), StorageBackendError> {
+        self.backend.put(column, key, value)
+    }
+
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
+    ) -> Result<(), StorageBackendError> {
+        self.backend.merge(column, key, value, operator)
+    }
+
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError> {
+        self.backend.delete(column, key)
+    }
+
+    fn contains(&self, column: &'static str, key: &[u8]) -> Result<bool, StorageBackendError> {
+        self.backend.contains(column, key)
+    }
+
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError> {
+        self.backend.write_batch(batch)
+    }
+
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        self.backend.iterator(column, mode)
+    }
+
+    fn prefix_iterator<'a>(
+        &'a self,
+        column: &'static str,
+        key: &[u8],
+        prefix_len: usize,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        self.backend.prefix_iterator(column, key, prefix_len)
+    }
+
+    fn flush(&self) -> Result<(), StorageBackendError> {
+        self.backend.flush()
+    }
+
+    fn checkpoint(&self, path: &Path) -> Result<(), StorageBackendError> {
+        self.backend.checkpoint(path)
+    }
+
+    fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError> {
+        self.backend.get_mem_use_stats()
+    }
 }
 
 /// Database iterator extended by specific schema
-pub struct IteratorWithSchema<'a, S: KeyValueSchema>(DBIterator<'a>, PhantomData<S>);
+pub struct IteratorWithSchema<'a, S: KeyValueSchema>(BackendIterator<'a>, PhantomData<S>);
 
 impl<'a, S: KeyValueSchema> Iterator for IteratorWithSchema<'a, S> {
     type Item = (Result<S::Key, SchemaError>, Result<S::Value, SchemaError>);
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        self.0
-            .next()
-            .map(|(k, v)| (S::Key::decode(&k), S::Value::decode(&v)))
+        self.0.next().map(|item| match item {
+            Ok((k, v)) => (S::Key::decode(&k), S::Value::decode(&v)),
+            Err(e) => (
+                Err(SchemaError::ReadError(e.to_string())),
+                Err(SchemaError::ReadError(e.to_string())),
+            ),
+        })
     }
 }
 
 /// Database iterator direction
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     Forward,
     Reverse,
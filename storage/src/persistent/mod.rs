@@ -2,7 +2,6 @@
 // SPDX-License-Identifier: MIT
 
 use failure::Fail;
-use std::ops::Deref;
 use std::path::Path;
 use std::sync::{Arc, RwLock};
 
@@ -14,15 +13,16 @@ use rocksdb::{BlockBasedOptions, Cache, ColumnFamilyDescriptor, Options, DB};
 
 pub use codec::{BincodeEncoded, Codec, Decoder, Encoder, SchemaError};
 pub use commit_log::{CommitLogError, CommitLogRef, CommitLogWithSchema, CommitLogs, Location};
-pub use database::{DBError, KeyValueStoreWithSchema};
+pub use database::{DBError, KeyValueStore, KeyValueStoreWithSchema};
 pub use schema::{CommitLogDescriptor, CommitLogSchema, KeyValueSchema};
 
 use crate::backend::btree_map::BTreeMapBackend;
 use crate::backend::in_memory_backend::InMemoryBackend;
-use crate::backend::rocksdb_backend::RocksDBBackend;
+use crate::backend::lmdb_backend::LmdbBackend;
 use crate::backend::sled_backend::SledBackend;
 use crate::merkle_storage::MerkleStorage;
 use crate::persistent::sequence::Sequences;
+use crate::storage_backend::StorageBackend;
 use tezos_context::channel::ContextActionMessage;
 
 pub mod codec;
@@ -58,9 +58,38 @@ where
     DB::open_cf_descriptors(&default_kv_options(cfg), path, cfs).map_err(DBError::from)
 }
 
+/// Open key-value store at given path with selected backend
+///
+/// # Arguments
+/// * `backend` - Storage backend to use
+/// * `path` - Path to open database (not used by in-memory backends)
+/// * `cfs` - Iterator of Column Family descriptors (used by RocksDB only, other backends create columns on demand)
+pub fn open_kv_store<P, I>(
+    backend: &KeyValueStoreBackend,
+    path: P,
+    cfs: I,
+    cfg: &DbConfiguration,
+) -> Result<KeyValueStore, DBError>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = ColumnFamilyDescriptor>,
+{
+    match backend {
+        KeyValueStoreBackend::RocksDB => open_kv(path, cfs, cfg).map(KeyValueStore::new),
+        KeyValueStoreBackend::Lmdb { map_size } => LmdbBackend::open(path, *map_size)
+            .map(KeyValueStore::new)
+            .map_err(DBError::from),
+        KeyValueStoreBackend::Sled => SledBackend::open(path)
+            .map(KeyValueStore::new)
+            .map_err(DBError::from),
+        KeyValueStoreBackend::InMem => Ok(KeyValueStore::new(InMemoryBackend::new())),
+        KeyValueStoreBackend::BTreeMap => Ok(KeyValueStore::new(BTreeMapBackend::new())),
+    }
+}
+
 /// Create default database configuration options,
 /// based on recommended setting: https://github.com/facebook/rocksdb/wiki/Setup-Options-and-Basic-Tuning#other-general-options
-fn default_kv_options(cfg: &DbConfiguration) -> Options {
+pub(crate) fn default_kv_options(cfg: &DbConfiguration) -> Options {
     // default db options
     let mut db_opts = Options::default();
     db_opts.create_missing_column_families(true);
@@ -126,11 +155,11 @@ where
 #[derive(Clone)]
 pub struct PersistentStorage {
     /// key-value store for operational database
-    db: Arc<DB>,
+    db: Arc<KeyValueStore>,
     /// key-value store for context (used by merkle)
-    db_context: Arc<DB>,
+    db_context: Arc<KeyValueStore>,
     /// context actions store
-    db_context_actions: Arc<DB>,
+    db_context_actions: Arc<KeyValueStore>,
     /// commit log store for storing plain block header data
     clog: Arc<CommitLogs>,
     /// autoincrement  id generators
@@ -147,30 +176,17 @@ pub enum StorageType {
 
 impl PersistentStorage {
     pub fn new(
-        db: Arc<DB>,
-        db_context: Arc<DB>,
-        db_context_actions: Arc<DB>,
+        db: Arc<KeyValueStore>,
+        db_context: Arc<KeyValueStore>,
+        db_context_actions: Arc<KeyValueStore>,
         clog: Arc<CommitLogs>,
-        merkle_backend: KeyValueStoreBackend,
     ) -> Self {
-        let merkle = match merkle_backend {
-            KeyValueStoreBackend::RocksDB => MerkleStorage::new(Box::new(RocksDBBackend::new(
-                db_context.clone(),
-                MerkleStorage::name(),
-            ))),
-            KeyValueStoreBackend::InMem => MerkleStorage::new(Box::new(InMemoryBackend::new())),
-            KeyValueStoreBackend::Sled => {
-                let sled = sled::Config::new().temporary(true).open().unwrap();
-                MerkleStorage::new(Box::new(SledBackend::new(sled.deref().clone())))
-            }
-            KeyValueStoreBackend::BTreeMap => MerkleStorage::new(Box::new(BTreeMapBackend::new())),
-        };
-
+        let merkle = MerkleStorage::new(db_context.clone());
         let seq = Arc::new(Sequences::new(db.clone(), 1000));
         Self {
             clog,
             db,
-            db_context: db_context.clone(),
+            db_context,
             db_context_actions,
             seq,
             merkle: Arc::new(RwLock::new(merkle)),
@@ -178,7 +194,7 @@ impl PersistentStorage {
     }
 
     #[inline]
-    pub fn kv(&self, storage: StorageType) -> Arc<DB> {
+    pub fn kv(&self, storage: StorageType) -> Arc<KeyValueStore> {
         match storage {
             StorageType::Context => self.db_context.clone(),
             StorageType::ContextAction => self.db_context_actions.clone(),
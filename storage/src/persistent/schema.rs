@@ -5,6 +5,7 @@ use rocksdb::{Cache, ColumnFamilyDescriptor};
 
 use crate::persistent::codec::Codec;
 use crate::persistent::default_table_options;
+use crate::storage_backend::MergeOperator;
 
 /// This trait extends basic column family by introducing Codec types safety and enforcement
 pub trait KeyValueSchema {
@@ -16,6 +17,23 @@ pub trait KeyValueSchema {
     }
 
     fn name() -> &'static str;
+
+    /// Column family descriptor together with its name (name is not accessible from [ColumnFamilyDescriptor])
+    fn named_descriptor(cache: &Cache) -> (&'static str, ColumnFamilyDescriptor) {
+        (Self::name(), Self::descriptor(cache))
+    }
+
+    /// Merge operator used by backends without native merge support
+    /// (RocksDB uses merge operator registered in [descriptor](KeyValueSchema::descriptor)).
+    fn merge_operator() -> Option<MergeOperator> {
+        None
+    }
+
+    /// Length of the key prefix used by prefix iterator, `None` means whole key
+    /// (RocksDB uses prefix extractor registered in [descriptor](KeyValueSchema::descriptor)).
+    fn prefix_len() -> Option<usize> {
+        None
+    }
 }
 
 pub struct CommitLogDescriptor {
@@ -160,6 +160,10 @@ impl KeyValueSchema for ListValue {
     fn name() -> &'static str {
         "skip_list_values"
     }
+
+    fn prefix_len() -> Option<usize> {
+        Some(ListValueKey::LEN_ID)
+    }
 }
 
 impl<'a, K, V> TryExtend<(&'a K, &'a V)> for ListValue
@@ -9,7 +9,9 @@ use std::sync::Arc;
 use serde::{Deserialize, Serialize};
 
 use crate::persistent::sequence::SequenceGenerator;
-use crate::persistent::{BincodeEncoded, Codec, KeyValueSchema, KeyValueStoreWithSchema};
+use crate::persistent::{
+    BincodeEncoded, Codec, KeyValueSchema, KeyValueStore, KeyValueStoreWithSchema,
+};
 use crate::skip_list::content::{ListValueDatabase, NodeHeader, SkipListId};
 use crate::skip_list::lane::{Lane, LaneDatabase, TypedLane};
 use crate::skip_list::{SkipListError, TryExtend, LEVEL_BASE};
@@ -41,7 +43,7 @@ impl DatabaseBackedSkipList {
     /// Create new list in given database
     pub fn new(
         list_id: SkipListId,
-        db: Arc<rocksdb::DB>,
+        db: Arc<KeyValueStore>,
         sequence_gen: Arc<SequenceGenerator>,
     ) -> Result<Self, SkipListError> {
         let value_db: Arc<ListValueDatabase> = db.clone();
@@ -1,13 +1,15 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use crate::persistent::database::{DBError, RocksDBStats};
-use failure::Fail;
-use serde::Serialize;
 use std::collections::HashSet;
 use std::mem;
+use std::path::Path;
+
+use failure::Fail;
+use serde::Serialize;
 
 use crate::merkle_storage::{ContextValue, EntryHash};
+use crate::persistent::database::{Direction, RocksDBStats};
 
 pub fn size_of_vec<T>(v: &Vec<T>) -> usize {
     mem::size_of::<Vec<T>>() + mem::size_of::<T>() * v.capacity()
@@ -23,12 +25,24 @@ pub enum StorageBackendError {
     BackendError,
     #[fail(display = "SledDB error: {}", error)]
     SledDBError { error: sled::Error },
+    #[fail(display = "LMDB error: {}", error)]
+    LmdbError { error: lmdb::Error },
+    #[fail(display = "I/O error: {}", error)]
+    IOError { error: std::io::Error },
     #[fail(display = "Guard Poison {} ", error)]
     GuardPoison { error: String },
     #[fail(display = "Serialization error: {:?}", error)]
     SerializationError { error: bincode::Error },
-    #[fail(display = "DBError error: {:?}", error)]
-    DBError { error: DBError },
+    #[fail(display = "Merge operator failed for column {}", column)]
+    MergeError { column: &'static str },
+    #[fail(
+        display = "Operation {} is not supported by {} backend",
+        operation, backend
+    )]
+    UnsupportedOperation {
+        operation: &'static str,
+        backend: &'static str,
+    },
 }
 
 impl From<rocksdb::Error> for StorageBackendError {
@@ -43,9 +57,15 @@ impl From<sled::Error> for StorageBackendError {
     }
 }
 
-impl From<DBError> for StorageBackendError {
-    fn from(error: DBError) -> Self {
-        StorageBackendError::DBError { error }
+impl From<lmdb::Error> for StorageBackendError {
+    fn from(error: lmdb::Error) -> Self {
+        StorageBackendError::LmdbError { error }
+    }
+}
+
+impl From<std::io::Error> for StorageBackendError {
+    fn from(error: std::io::Error) -> Self {
+        StorageBackendError::IOError { error }
     }
 }
 
@@ -55,6 +75,14 @@ impl From<bincode::Error> for StorageBackendError {
     }
 }
 
+impl<T> From<std::sync::PoisonError<T>> for StorageBackendError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        StorageBackendError::GuardPoison {
+            error: format!("{}", error),
+        }
+    }
+}
+
 impl slog::Value for StorageBackendError {
     fn serialize(
         &self,
@@ -66,18 +94,177 @@ impl slog::Value for StorageBackendError {
     }
 }
 
-//TODO TE-432 - create single abstraction for StorageBackend and KeyValueWithSchema
+/// Merge operator used by backends without native merge support, arguments are: (key, existing value, operand).
+///
+/// Returns `None` when values cannot be merged.
+pub type MergeOperator = fn(&[u8], Option<&[u8]>, &[u8]) -> Option<Vec<u8>>;
+
+/// Iterator over raw (key, value) pairs of single column, read errors are returned as items
+pub type BackendIterator<'a> =
+    Box<dyn Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), StorageBackendError>> + 'a>;
+
+/// Backend iterator mode, from start to end, from end to start or from specific (encoded) key to end/start
+pub enum BackendIteratorMode<'a> {
+    Start,
+    End,
+    From(&'a [u8], Direction),
+}
+
+/// Single write operation stored in [WriteBatch]
+pub enum BatchOperation {
+    Put {
+        column: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Merge {
+        column: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        operator: Option<MergeOperator>,
+    },
+    Delete {
+        column: &'static str,
+        key: Vec<u8>,
+    },
+}
+
+/// Backend independent batch of writes, which is written by [StorageBackend::write_batch]
+#[derive(Default)]
+pub struct WriteBatch {
+    operations: Vec<BatchOperation>,
+}
+
+impl WriteBatch {
+    pub fn put(&mut self, column: &'static str, key: Vec<u8>, value: Vec<u8>) {
+        self.operations
+            .push(BatchOperation::Put { column, key, value });
+    }
+
+    pub fn merge(
+        &mut self,
+        column: &'static str,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        operator: Option<MergeOperator>,
+    ) {
+        self.operations.push(BatchOperation::Merge {
+            column,
+            key,
+            value,
+            operator,
+        });
+    }
+
+    pub fn delete(&mut self, column: &'static str, key: Vec<u8>) {
+        self.operations.push(BatchOperation::Delete { column, key });
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    pub fn into_operations(self) -> Vec<BatchOperation> {
+        self.operations
+    }
+}
+
+/// Applies merge `operator` (if any) to the `existing` value, without operator the operand overrides existing value
+pub(crate) fn apply_merge_operator(
+    column: &'static str,
+    key: &[u8],
+    existing: Option<&[u8]>,
+    operand: &[u8],
+    operator: Option<MergeOperator>,
+) -> Result<Vec<u8>, StorageBackendError> {
+    match operator {
+        Some(operator) => {
+            operator(key, existing, operand).ok_or(StorageBackendError::MergeError { column })
+        }
+        None => Ok(operand.to_vec()),
+    }
+}
+
+/// Key-value storage engine used by all column families ([KeyValueStoreWithSchema](crate::persistent::KeyValueStoreWithSchema))
+/// and by [MerkleStorage](crate::MerkleStorage).
+///
+/// Backend works with raw bytes only, encoding is done by schema. Every column (`KeyValueSchema::name`)
+/// is stored separately, so column families, sled trees or named LMDB databases are used depending on backend.
 pub trait StorageBackend: Send + Sync {
+    /// Name of backend, used for logging and errors
+    fn name(&self) -> &'static str;
+
     fn is_persisted(&self) -> bool;
-    fn get(&self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError>;
-    fn put(&mut self, key: &EntryHash, value: ContextValue) -> Result<bool, StorageBackendError>;
-    fn put_batch(
-        &mut self,
-        batch: Vec<(EntryHash, ContextValue)>,
+
+    fn get(&self, column: &'static str, key: &[u8])
+        -> Result<Option<Vec<u8>>, StorageBackendError>;
+
+    fn put(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
     ) -> Result<(), StorageBackendError>;
-    fn merge(&mut self, key: &EntryHash, value: ContextValue) -> Result<(), StorageBackendError>;
-    fn delete(&mut self, key: &EntryHash) -> Result<Option<ContextValue>, StorageBackendError>;
-    fn contains(&self, key: &EntryHash) -> Result<bool, StorageBackendError>;
+
+    /// Merges `value` to the existing value. RocksDB uses merge operator registered with column family
+    /// descriptor, other backends apply `operator` atomically (read-modify-write).
+    fn merge(
+        &self,
+        column: &'static str,
+        key: &[u8],
+        value: &[u8],
+        operator: Option<MergeOperator>,
+    ) -> Result<(), StorageBackendError>;
+
+    fn delete(&self, column: &'static str, key: &[u8]) -> Result<(), StorageBackendError>;
+
+    fn contains(&self, column: &'static str, key: &[u8]) -> Result<bool, StorageBackendError> {
+        self.get(column, key).map(|value| value.is_some())
+    }
+
+    /// Write all operations of the batch atomically
+    fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageBackendError>;
+
+    fn iterator<'a>(
+        &'a self,
+        column: &'static str,
+        mode: BackendIteratorMode<'_>,
+    ) -> Result<BackendIterator<'a>, StorageBackendError>;
+
+    /// Iterates from `key` while keys share the same prefix of length `prefix_len`.
+    /// RocksDB uses prefix extractor of the column family instead of `prefix_len`.
+    fn prefix_iterator<'a>(
+        &'a self,
+        column: &'static str,
+        key: &[u8],
+        prefix_len: usize,
+    ) -> Result<BackendIterator<'a>, StorageBackendError> {
+        let prefix = key[..std::cmp::min(prefix_len, key.len())].to_vec();
+        let iter = self.iterator(column, BackendIteratorMode::From(key, Direction::Forward))?;
+        Ok(Box::new(iter.take_while(move |item| match item {
+            Ok((key, _)) => key.starts_with(&prefix),
+            // error is passed to the caller
+            Err(_) => true,
+        })))
+    }
+
+    /// Flush all pending writes to disk
+    fn flush(&self) -> Result<(), StorageBackendError> {
+        Ok(())
+    }
+
+    /// Create consistent point-in-time copy of the database in (not existing) directory `path`
+    fn checkpoint(&self, _path: &Path) -> Result<(), StorageBackendError> {
+        Err(StorageBackendError::UnsupportedOperation {
+            operation: "checkpoint",
+            backend: self.name(),
+        })
+    }
+
     fn get_mem_use_stats(&self) -> Result<RocksDBStats, StorageBackendError>;
 }
 
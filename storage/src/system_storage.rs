@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use crypto::hash::ChainId;
 
+use crate::migration::MigrationProgress;
 use crate::persistent::{
     default_table_options, BincodeEncoded, KeyValueSchema, KeyValueStoreWithSchema,
 };
@@ -27,8 +28,9 @@ pub struct SystemStorage {
 
 impl SystemStorage {
     const CHAIN_ID: &'static str = "chain_id";
-    const DB_VERSION: &'static str = "db_version";
+    pub(crate) const DB_VERSION: &'static str = "db_version";
     const CHAIN_NAME: &'static str = "chain_name";
+    pub(crate) const MIGRATION_PROGRESS: &'static str = "migration_progress";
 
     pub fn new(kv: Arc<SystemStorageKv>) -> Self {
         SystemStorage { kv }
@@ -106,6 +108,18 @@ impl SystemStorage {
             )
             .map_err(StorageError::from)
     }
+
+    /// Returns progress of interrupted database migration, if any
+    #[inline]
+    pub fn get_migration_progress(&self) -> Result<Option<MigrationProgress>, StorageError> {
+        self.kv
+            .get(&Self::MIGRATION_PROGRESS.to_string())
+            .map(|result| match result {
+                Some(SystemValue::MigrationProgress(value)) => Some(value),
+                _ => None,
+            })
+            .map_err(StorageError::from)
+    }
 }
 
 impl KeyValueSchema for SystemStorage {
@@ -128,6 +142,7 @@ pub enum SystemValue {
     String(String),
     Integer(i64),
     Hash(Vec<u8>),
+    MigrationProgress(MigrationProgress),
 }
 
 impl BincodeEncoded for SystemValue {}
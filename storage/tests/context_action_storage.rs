@@ -5,6 +5,7 @@ use std::convert::TryInto;
 
 use failure::Error;
 
+use storage::storage_backend::StorageBackend;
 use storage::tests_common::TmpStorage;
 use storage::*;
 use tezos_context::channel::ContextAction;
@@ -0,0 +1,121 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+use std::convert::{TryFrom, TryInto};
+use std::env;
+use std::path::PathBuf;
+
+use failure::Error;
+
+use crypto::hash::{BlockHash, ChainId};
+use storage::block_meta_storage::Meta;
+use storage::tests_common::TmpStorage;
+use storage::*;
+use tezos_messages::p2p::encoding::prelude::*;
+
+const BACKENDS: [KeyValueStoreBackend; 5] = [
+    KeyValueStoreBackend::RocksDB,
+    KeyValueStoreBackend::Lmdb {
+        map_size: backend::LMDB_DEFAULT_MAP_SIZE,
+    },
+    KeyValueStoreBackend::Sled,
+    KeyValueStoreBackend::InMem,
+    KeyValueStoreBackend::BTreeMap,
+];
+
+fn out_dir_path(dir_name: &str, backend: &KeyValueStoreBackend) -> PathBuf {
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR is not defined - check build.rs");
+    PathBuf::from(out_dir).join(format!("{}_{:?}", dir_name, backend))
+}
+
+#[test]
+fn test_block_meta_merge_all_backends() -> Result<(), Error> {
+    for backend in BACKENDS.iter() {
+        let tmp_storage = TmpStorage::initialize_with_backend(
+            out_dir_path("__storage_backend_block_meta", backend),
+            backend.clone(),
+            true,
+            true,
+        )?;
+        let storage = BlockMetaStorage::new(tmp_storage.storage());
+
+        let chain_id: ChainId = vec![44; 4].try_into()?;
+        let block_hash =
+            BlockHash::try_from("BKyQ9EofHrgaZKENioHyP4FZNsTmiSEcVmcghgzCC9cGhE7oCET")?;
+        let predecessor =
+            BlockHash::try_from("BLaf78njreWdt2WigJjM9e3ecEdVKm5ehahUfYBKvcWvZ8vfTcJ")?;
+
+        // first write without predecessor
+        storage.put(&block_hash, &Meta::new(false, None, 5, chain_id.clone()))?;
+        // merge predecessor and applied flag
+        storage.put(
+            &block_hash,
+            &Meta::new(true, Some(predecessor.clone()), 5, chain_id.clone()),
+        )?;
+        // applied flag cannot be reset by merge
+        storage.put(&block_hash, &Meta::new(false, None, 5, chain_id.clone()))?;
+
+        let meta = storage.get(&block_hash)?.expect("Meta should be stored");
+        assert!(meta.is_applied(), "backend: {:?}", backend);
+        assert_eq!(
+            Some(&predecessor),
+            meta.predecessor().as_ref(),
+            "backend: {:?}",
+            backend
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_operations_prefix_iterator_all_backends() -> Result<(), Error> {
+    for backend in BACKENDS.iter() {
+        let tmp_storage = TmpStorage::initialize_with_backend(
+            out_dir_path("__storage_backend_operations", backend),
+            backend.clone(),
+            true,
+            true,
+        )?;
+        let storage = OperationsStorage::new(tmp_storage.storage());
+
+        let block_hash_1 =
+            BlockHash::try_from("BKyQ9EofHrgaZKENioHyP4FZNsTmiSEcVmcghgzCC9cGhE7oCET")?;
+        let block_hash_2 =
+            BlockHash::try_from("BLaf78njreWdt2WigJjM9e3ecEdVKm5ehahUfYBKvcWvZ8vfTcJ")?;
+
+        for (block_hash, validation_pass) in &[
+            (&block_hash_1, 2),
+            (&block_hash_2, 0),
+            (&block_hash_1, 0),
+            (&block_hash_1, 1),
+        ] {
+            let message = OperationsForBlocksMessage::new(
+                OperationsForBlock::new((*block_hash).clone(), *validation_pass),
+                Path::op(),
+                vec![],
+            );
+            storage.put_operations(&message)?;
+        }
+
+        let operations = storage.get_operations(&block_hash_1)?;
+        assert_eq!(3, operations.len(), "backend: {:?}", backend);
+        for (i, operation) in operations.iter().enumerate() {
+            assert_eq!(
+                i as i8,
+                operation.operations_for_block().validation_pass(),
+                "backend: {:?}",
+                backend
+            );
+            assert_eq!(&block_hash_1, operation.operations_for_block().hash());
+        }
+        assert_eq!(
+            1,
+            storage.get_operations(&block_hash_2)?.len(),
+            "backend: {:?}",
+            backend
+        );
+    }
+
+    Ok(())
+}
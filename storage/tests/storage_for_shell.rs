@@ -55,8 +55,14 @@ fn test_storage() -> Result<(), Error> {
     };
 
     // initialize empty storage
-    let init_data =
-        resolve_storage_init_chain_data(&tezos_env, &tmp_storage_dir, &context_dir, &None, &log);
+    let init_data = resolve_storage_init_chain_data(
+        &tezos_env,
+        &tmp_storage_dir,
+        &context_dir,
+        &None,
+        &None,
+        &log,
+    );
     assert!(init_data.is_ok());
 
     let init_data = init_data.unwrap();
@@ -120,6 +126,15 @@ fn test_storage() -> Result<(), Error> {
         &init_data.genesis_block_header_hash
     );
 
+    // check savepoint is on genesis
+    assert_eq!(
+        chain_meta_storage
+            .get_savepoint(&init_data.chain_id)?
+            .expect("Savepoint should be set")
+            .block_hash(),
+        &init_data.genesis_block_header_hash
+    );
+
     // check current head is on genesis
     assert_eq!(
         chain_meta_storage
@@ -1,6 +1,7 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
+use std::path::Path;
 use std::str::FromStr;
 use std::{
     collections::HashMap,
@@ -42,6 +43,8 @@ pub enum TezosEnvironment {
     Mainnet,
     Zeronet,
     Sandbox,
+    /// Network loaded from JSON network descriptor, has no hard-coded configuration in [TEZOS_ENV]
+    Custom,
 }
 
 #[derive(Debug, Clone)]
@@ -287,6 +290,8 @@ pub enum TezosEnvironmentError {
     },
     #[fail(display = "Invalid time: {}, reason: {:?}", time, error)]
     InvalidTime { time: String, error: ParseError },
+    #[fail(display = "Invalid network descriptor, reason: {}", reason)]
+    InvalidNetworkDescriptor { reason: String },
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -373,6 +378,119 @@ impl TezosEnvironmentConfiguration {
             last_allowed_fork_level: 0,
         }
     }
+
+    /// Loads configuration from Octez-style network descriptor file (see node_config_file.ml)
+    pub fn try_from_json_file(path: &Path) -> Result<Self, TezosEnvironmentError> {
+        let json = std::fs::read_to_string(path).map_err(|e| {
+            TezosEnvironmentError::InvalidNetworkDescriptor {
+                reason: format!("failed to read file: {:?}, reason: {}", path, e),
+            }
+        })?;
+        Self::try_from_json(&json)
+    }
+
+    /// Parses configuration from Octez-style network descriptor, which can be either bare network object
+    /// or Octez node config with the descriptor under "network" field
+    pub fn try_from_json(json: &str) -> Result<Self, TezosEnvironmentError> {
+        let descriptor = match serde_json::from_str::<NetworkDescriptorFile>(json).map_err(|e| {
+            TezosEnvironmentError::InvalidNetworkDescriptor {
+                reason: format!("{}", e),
+            }
+        })? {
+            NetworkDescriptorFile::NodeConfig { network } => network,
+            NetworkDescriptorFile::Network(network) => network,
+        };
+
+        let configuration = TezosEnvironmentConfiguration {
+            genesis: GenesisChain {
+                time: descriptor.genesis.timestamp,
+                block: descriptor.genesis.block,
+                protocol: descriptor.genesis.protocol,
+            },
+            bootstrap_lookup_addresses: descriptor.default_bootstrap_peers,
+            version: descriptor.chain_name,
+            protocol_overrides: ProtocolOverrides {
+                user_activated_upgrades: descriptor
+                    .user_activated_upgrades
+                    .into_iter()
+                    .map(|upgrade| (upgrade.level, upgrade.replacement_protocol))
+                    .collect(),
+                user_activated_protocol_overrides: descriptor
+                    .user_activated_protocol_overrides
+                    .into_iter()
+                    .map(|o| (o.replaced_protocol, o.replacement_protocol))
+                    .collect(),
+            },
+            enable_testchain: false,
+            patch_context_genesis_parameters: descriptor.genesis_parameters.map(|parameters| {
+                PatchContext {
+                    key: parameters
+                        .context_key
+                        .unwrap_or_else(|| "sandbox_parameter".to_string()),
+                    json: parameters.values.to_string(),
+                }
+            }),
+        };
+
+        // validate genesis, so we fail early on startup
+        let _ = configuration.genesis_header_hash()?;
+        let _ = configuration.genesis_protocol()?;
+        let _ = configuration.genesis_time()?;
+
+        Ok(configuration)
+    }
+}
+
+/// Octez-style network descriptor - see node_config_file.ml (blockchain_network_encoding)
+#[derive(Deserialize, Debug)]
+struct NetworkDescriptor {
+    chain_name: String,
+    genesis: NetworkDescriptorGenesis,
+    #[serde(default)]
+    genesis_parameters: Option<NetworkDescriptorGenesisParameters>,
+    /// accepted for compatibility, sandboxed chain is not supported for custom networks
+    #[serde(default)]
+    #[allow(dead_code)]
+    sandboxed_chain_name: Option<String>,
+    #[serde(default)]
+    default_bootstrap_peers: Vec<String>,
+    #[serde(default)]
+    user_activated_upgrades: Vec<NetworkDescriptorUpgrade>,
+    #[serde(default)]
+    user_activated_protocol_overrides: Vec<NetworkDescriptorProtocolOverride>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkDescriptorGenesis {
+    timestamp: String,
+    block: String,
+    protocol: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkDescriptorGenesisParameters {
+    #[serde(default)]
+    context_key: Option<String>,
+    values: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkDescriptorUpgrade {
+    level: i32,
+    replacement_protocol: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct NetworkDescriptorProtocolOverride {
+    replaced_protocol: String,
+    replacement_protocol: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum NetworkDescriptorFile {
+    NodeConfig { network: NetworkDescriptor },
+    Network(NetworkDescriptor),
 }
 
 fn parse_from_rfc3339(time: &str) -> Result<i64, TezosEnvironmentError> {
@@ -399,4 +517,87 @@ mod tests {
         assert_eq!(expected, decoded);
         Ok(())
     }
+
+    #[test]
+    fn test_network_descriptor_from_json() -> Result<(), failure::Error> {
+        let json = r#"{
+            "network": {
+                "chain_name": "TEZOS_EDONET_2020-11-30T12:00:00Z",
+                "genesis": {
+                    "timestamp": "2020-11-30T12:00:00Z",
+                    "block": "BLockGenesisGenesisGenesisGenesisGenesis2431bbUwV2a",
+                    "protocol": "PtYuensgYBb3G3x1hLLbCmcav8ue8Kyd2khADcL5LsT5R1hcXex"
+                },
+                "genesis_parameters": {
+                    "values": {
+                        "genesis_pubkey": "edpkugeDwmwuwyyD3Q5enapgEYDxZLtEUFFSrvVwXASQMVEqsvTqWu"
+                    }
+                },
+                "sandboxed_chain_name": "SANDBOXED_TEZOS",
+                "default_bootstrap_peers": [ "edonet.tezos.co.il", "edonet.boot.tezostaquito.io" ],
+                "user_activated_upgrades": [
+                    { "level": 8191, "replacement_protocol": "PtEdo2ZkT9oKpimTah6x2embF25oss54njMuPzkJTEi5RqfdZFA" }
+                ],
+                "user_activated_protocol_overrides": [
+                    {
+                        "replaced_protocol": "PtEdoTezd3RHSC31mpxxo1npxFjoWWcFgQtxapi51Z8TLu6v6Uq",
+                        "replacement_protocol": "PtEdo2ZkT9oKpimTah6x2embF25oss54njMuPzkJTEi5RqfdZFA"
+                    }
+                ]
+            }
+        }"#;
+
+        let env = TezosEnvironmentConfiguration::try_from_json(json)?;
+        assert_eq!("TEZOS_EDONET_2020-11-30T12:00:00Z", env.version);
+        assert_eq!(
+            "BLockGenesisGenesisGenesisGenesisGenesis2431bbUwV2a",
+            env.genesis.block
+        );
+        assert_eq!(2, env.bootstrap_lookup_addresses.len());
+        assert_eq!(
+            vec![(
+                8191,
+                "PtEdo2ZkT9oKpimTah6x2embF25oss54njMuPzkJTEi5RqfdZFA".to_string()
+            )],
+            env.protocol_overrides.user_activated_upgrades
+        );
+        assert_eq!(
+            1,
+            env.protocol_overrides
+                .user_activated_protocol_overrides
+                .len()
+        );
+        let patch_context = env
+            .patch_context_genesis_parameters
+            .expect("expected genesis parameters");
+        assert_eq!("sandbox_parameter", patch_context.key);
+        assert!(patch_context.json.contains("genesis_pubkey"));
+
+        // bare network object without "network" wrapper
+        let json = r#"{
+            "chain_name": "TEZOS_CUSTOM",
+            "genesis": {
+                "timestamp": "2020-11-30T12:00:00Z",
+                "block": "BLockGenesisGenesisGenesisGenesisGenesis2431bbUwV2a",
+                "protocol": "PtYuensgYBb3G3x1hLLbCmcav8ue8Kyd2khADcL5LsT5R1hcXex"
+            }
+        }"#;
+        let env = TezosEnvironmentConfiguration::try_from_json(json)?;
+        assert_eq!("TEZOS_CUSTOM", env.version);
+        assert!(env.bootstrap_lookup_addresses.is_empty());
+        assert!(env.patch_context_genesis_parameters.is_none());
+
+        // invalid genesis block hash
+        let json = r#"{
+            "chain_name": "TEZOS_CUSTOM",
+            "genesis": {
+                "timestamp": "2020-11-30T12:00:00Z",
+                "block": "invalid",
+                "protocol": "PtYuensgYBb3G3x1hLLbCmcav8ue8Kyd2khADcL5LsT5R1hcXex"
+            }
+        }"#;
+        assert!(TezosEnvironmentConfiguration::try_from_json(json).is_err());
+
+        Ok(())
+    }
 }
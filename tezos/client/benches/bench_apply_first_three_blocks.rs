@@ -3,18 +3,23 @@
 #![feature(test)]
 extern crate test;
 
+use std::sync::mpsc::sync_channel;
+use std::thread;
 use std::time::Instant;
 use test::Bencher;
 
 use crypto::hash::ChainId;
+use failure::format_err;
 use tezos_api::environment::{
     get_empty_operation_list_list_hash, TezosEnvironmentConfiguration, TEZOS_ENV,
 };
-use tezos_api::ffi::{ApplyBlockRequest, InitProtocolContextResult, TezosRuntimeConfiguration};
+use tezos_api::ffi::{
+    ApplyBlockRequest, ApplyBlockResponse, InitProtocolContextResult, TezosRuntimeConfiguration,
+};
 use tezos_client::client;
 use tezos_interop::ffi;
 use tezos_messages::p2p::binary_message::BinaryMessage;
-use tezos_messages::p2p::encoding::prelude::BlockHeader;
+use tezos_messages::p2p::encoding::prelude::{BlockHeader, Operation};
 
 // not a real bench, just for approximatelly measurement of applying first three blocks
 // because this is very hard to use with b.iter
@@ -54,6 +59,45 @@ fn bench_apply_first_three_block(_: &mut Bencher) {
     );
 }
 
+// the same as above, but next blocks are decoded and results are checked in other threads, while block is applied,
+// like pipelined applier in chain_feeder does, to compare with sequential apply
+// (both benches initialize protocol context, so run them separately)
+// cargo bench --bench bench_apply_first_three_blocks -- --nocapture bench_apply_first_three_block$
+// cargo bench --bench bench_apply_first_three_blocks -- --nocapture pipelined
+#[bench]
+fn bench_apply_first_three_blocks_pipelined(_: &mut Bencher) {
+    ffi::change_runtime_configuration(TezosRuntimeConfiguration {
+        log_enabled: common::is_ocaml_log_enabled(),
+        debug_mode: false,
+        compute_context_action_tree_hashes: false,
+    })
+    .unwrap();
+
+    let now = Instant::now();
+
+    // init empty context for test (not measuring)
+    let storage_clocks = Instant::now();
+    let (chain_id, genesis_block_header, ..) = init_test_protocol_context(&format!(
+        "bootstrap_test_storage_bench_pipelined_{}",
+        now.elapsed().as_nanos()
+    ));
+    let storage_clocks = storage_clocks.elapsed();
+
+    // apply
+    let apply_clocks = Instant::now();
+    let result: Result<Vec<String>, failure::Error> =
+        apply_first_three_blocks_pipelined(chain_id, genesis_block_header, 2);
+    let apply_clocks = apply_clocks.elapsed();
+    assert!(result.is_ok());
+
+    println!(
+        "\nApply first three blocks (pipelined) done in {:?} (storage init in {:?}) \n{}!",
+        apply_clocks,
+        storage_clocks,
+        result.ok().unwrap().join("\n")
+    );
+}
+
 fn apply_first_three_blocks(
     chain_id: ChainId,
     genesis_block_header: BlockHeader,
@@ -133,6 +177,102 @@ fn apply_first_three_blocks(
     Ok(perf_log)
 }
 
+fn apply_first_three_blocks_pipelined(
+    chain_id: ChainId,
+    genesis_block_header: BlockHeader,
+    look_ahead: usize,
+) -> Result<Vec<String>, failure::Error> {
+    let mut perf_log = vec![];
+
+    // prefetcher - decodes next blocks, while block is applied
+    let (prefetched_sender, prefetched_receiver) = sync_channel(look_ahead);
+    let prefetcher = thread::spawn(move || {
+        for (header, block_hash, operations) in test_data::blocks() {
+            let clocks = Instant::now();
+            let prefetched: Result<(BlockHeader, Vec<Vec<Operation>>), failure::Error> =
+                hex::decode(header)
+                    .map_err(|e| format_err!("{}", e))
+                    .and_then(|bytes| BlockHeader::from_bytes(bytes).map_err(|e| e.into()))
+                    .map(|block_header| {
+                        (
+                            block_header,
+                            ApplyBlockRequest::convert_operations(
+                                test_data::block_operations_from_hex(block_hash, operations),
+                            ),
+                        )
+                    });
+            if prefetched_sender
+                .send((prefetched, clocks.elapsed()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    // writer - checks results of applied blocks, while next block is applied
+    let (applied_sender, applied_receiver) = sync_channel::<(i32, ApplyBlockResponse)>(look_ahead);
+    let writer = thread::spawn(move || {
+        for (level, apply_block_result) in applied_receiver {
+            match level {
+                1 => assert_eq!(
+                    test_data::context_hash(test_data::BLOCK_HEADER_LEVEL_1_CONTEXT_HASH),
+                    apply_block_result.context_hash
+                ),
+                2 => assert_eq!(
+                    "lvl 2, fit 2, prio 5, 0 ops",
+                    apply_block_result.validation_result_message
+                ),
+                3 => assert_eq!(
+                    "lvl 3, fit 5, prio 12, 1 ops",
+                    apply_block_result.validation_result_message
+                ),
+                _ => panic!("Unexpected level: {}", level),
+            }
+        }
+    });
+
+    let mut pred_header = genesis_block_header;
+    let mut max_operations_ttl = 0;
+    for (prefetched, prefetch_elapsed) in prefetched_receiver {
+        let (block_header, operations) = prefetched?;
+        let level = block_header.level();
+
+        let clocks = Instant::now();
+        let apply_block_result = client::apply_block(ApplyBlockRequest {
+            chain_id: chain_id.clone(),
+            block_header: block_header.clone(),
+            pred_header,
+            operations,
+            max_operations_ttl,
+            predecessor_block_metadata_hash: None,
+            predecessor_ops_metadata_hash: None,
+        })?;
+        perf_log.push(format!(
+            "- {}. apply: {:?} (prefetched in {:?})",
+            level,
+            clocks.elapsed(),
+            prefetch_elapsed
+        ));
+
+        max_operations_ttl = apply_block_result.max_operations_ttl;
+        pred_header = block_header;
+        applied_sender
+            .send((level, apply_block_result))
+            .map_err(|e| format_err!("Failed to send applied block, reason: {}", e))?;
+    }
+    drop(applied_sender);
+
+    prefetcher
+        .join()
+        .map_err(|_| format_err!("Prefetcher thread failed"))?;
+    writer
+        .join()
+        .map_err(|_| format_err!("Writer thread failed (unexpected apply result)"))?;
+
+    Ok(perf_log)
+}
+
 fn init_test_protocol_context(dir_name: &str) -> (ChainId, BlockHeader, InitProtocolContextResult) {
     let tezos_env: &TezosEnvironmentConfiguration = TEZOS_ENV
         .get(&test_data::TEZOS_NETWORK)
@@ -215,6 +355,27 @@ mod test_data {
         ]
     }
 
+    /// (header, block hash, operations) of the first three blocks
+    pub fn blocks() -> Vec<(&'static str, &'static str, Vec<Vec<String>>)> {
+        vec![
+            (
+                BLOCK_HEADER_LEVEL_1,
+                BLOCK_HEADER_HASH_LEVEL_1,
+                block_header_level1_operations(),
+            ),
+            (
+                BLOCK_HEADER_LEVEL_2,
+                BLOCK_HEADER_HASH_LEVEL_2,
+                block_header_level2_operations(),
+            ),
+            (
+                BLOCK_HEADER_LEVEL_3,
+                BLOCK_HEADER_HASH_LEVEL_3,
+                block_header_level3_operations(),
+            ),
+        ]
+    }
+
     pub fn block_operations_from_hex(
         block_hash: &str,
         hex_operations: Vec<Vec<String>>,
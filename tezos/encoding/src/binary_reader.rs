@@ -135,11 +135,7 @@ impl BinaryReader {
     ) -> Result<Value, BinaryReaderError> {
         let mut buf = buf.as_ref();
 
-        let result = match encoding {
-            Encoding::Obj(schema) => self.decode_record(&mut buf, schema),
-            Encoding::Tup(encodings) => self.decode_tuple(&mut buf, encodings),
-            _ => self.decode_value(&mut buf, encoding),
-        }?;
+        let result = self.read_from(&mut buf, encoding)?;
 
         if buf.remaining() == 0 {
             Ok(result)
@@ -150,6 +146,20 @@ impl BinaryReader {
         }
     }
 
+    /// Convert Tezos binary data at the beginning of `buf` into [intermadiate form](Value).
+    /// Unlike [read](BinaryReader::read), remaining bytes are left in `buf` and are not considered an error.
+    pub fn read_from(
+        &self,
+        buf: &mut dyn Buf,
+        encoding: &Encoding,
+    ) -> Result<Value, BinaryReaderError> {
+        match encoding {
+            Encoding::Obj(schema) => self.decode_record(buf, schema),
+            Encoding::Tup(encodings) => self.decode_tuple(buf, encodings),
+            _ => self.decode_value(buf, encoding),
+        }
+    }
+
     fn decode_record(
         &self,
         buf: &mut dyn Buf,
@@ -0,0 +1,860 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Direct conversion between rust types and Tezos binary form.
+//!
+//! Unlike [BinaryReader] and [binary_writer], which go through the [intermediate form](crate::types::Value),
+//! types implementing [BinaryRead] and [BinaryWrite] are read from/written to bytes directly.
+//! Both traits are usually generated by `#[derive(BinaryEncoding)]`, binary form of a field
+//! is described by `#[encoding(...)]` attribute, which mirrors [Encoding]. The derive implements
+//! [HasEncoding](crate::encoding::HasEncoding) from the same attributes, so the schema used
+//! by [BinaryReader] and [binary_writer] does not have to be written by hand:
+//!
+//! | attribute                     | encoding                      | rust type                    |
+//! |-------------------------------|-------------------------------|------------------------------|
+//! | (none)                        | type's own binary form        | primitives, hashes, derived  |
+//! | `uint8`, `int8`               | `Uint8`, `Int8`               | `u8`, `i8`                   |
+//! | `uint16`, `int16`             | `Uint16`, `Int16`             | `u16`, `i16`                 |
+//! | `int31`, `int32`              | `Int31`, `Int32`              | `i32`                        |
+//! | `int64`, `timestamp`          | `Int64`, `Timestamp`          | `i64`                        |
+//! | `float`, `bool`, `string`     | `Float`, `Bool`, `String`     | `f64`, `bool`, `String`      |
+//! | `bounded_string(MAX)`         | `BoundedString(MAX)`          | `String`                     |
+//! | `hash`                        | `Hash(..)`                    | hashes from `crypto::hash`   |
+//! | `bytes`                       | `Bytes`                       | `Vec<u8>`                    |
+//! | `dynamic(inner)`              | `Dynamic(inner)`              | type of `inner`              |
+//! | `bounded_dynamic(MAX, inner)` | `BoundedDynamic(MAX, inner)`  | type of `inner`              |
+//! | `sized(SIZE, inner)`          | `Sized(SIZE, inner)`          | type of `inner`              |
+//! | `bounded(MAX, inner)`         | `Bounded(MAX, inner)`         | type of `inner`              |
+//! | `greedy(inner)`               | `Greedy(inner)`               | type of `inner`              |
+//! | `list(inner)`                 | `List(inner)`                 | `Vec<_>`                     |
+//! | `bounded_list(MAX, inner)`    | `BoundedList(MAX, inner)`     | `Vec<_>`                     |
+//! | `option(inner)`               | `Option(inner)`               | `Option<_>`                  |
+//! | `option_field(inner)`         | `OptionalField(inner)`        | `Option<_>`                  |
+//! | `unit`                        | `Unit`                        | `Default` is used on read    |
+//! | `split(json, binary)`         | `Split(..)`                   | type of `binary`             |
+//! | `skip`                        | field is not encoded          | `Default` is used on read    |
+//!
+//! `inner` can be omitted, type's own binary form is used then (e.g. `dynamic(list)`). Schema of `list(uint8)`
+//! and `bounded_list(MAX, uint8)` is split into `Bytes` for JSON and list of `Uint8` for binary.
+//! `MAX` and `SIZE` are integer literals or constants.
+//!
+//! Struct attribute (e.g. `#[encoding(bounded(MAX))]`) wraps the whole struct. Enums are encoded
+//! as [Encoding::Tags], size of the tag is set by `#[encoding(tags(u8))]` or `#[encoding(tags(u16))]`
+//! and each variant (unit or with a single unnamed field) requires `#[encoding(tag = ID)]`.
+//!
+//! # Examples:
+//!
+//! ```
+//! use tezos_encoding::direct::{self, BinaryEncoding};
+//!
+//! #[derive(BinaryEncoding, Debug, PartialEq)]
+//! struct Version {
+//!    name: String,
+//!    major: u16,
+//!    minor: u16,
+//! }
+//!
+//! #[derive(BinaryEncoding, Debug, PartialEq)]
+//! struct Versions {
+//!     #[encoding(dynamic(list))]
+//!     versions: Vec<Version>,
+//!     #[encoding(option(bounded_string(16)))]
+//!     comment: Option<String>,
+//! }
+//!
+//! let versions = Versions {
+//!     versions: vec![Version { name: "v1.0".into(), major: 1, minor: 0 }],
+//!     comment: None,
+//! };
+//!
+//! let binary = direct::to_bytes(&versions).unwrap();
+//! assert_eq!(binary, hex::decode("0000000c0000000476312e300001000000").unwrap());
+//! assert_eq!(versions, direct::from_bytes(&binary).unwrap());
+//! ```
+
+use std::cmp;
+use std::convert::TryFrom;
+use std::mem::size_of;
+
+use bytes::{Buf, BufMut};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::ser::{Error as _, Serialize};
+
+use crypto::hash::*;
+
+use crate::binary_reader::{BinaryReader, BinaryReaderError};
+use crate::binary_writer;
+use crate::de;
+use crate::encoding::Encoding;
+use crate::ser::Error;
+use crate::types;
+
+pub use tezos_encoding_derive::BinaryEncoding;
+
+/// Type, which can be read directly from Tezos binary form.
+pub trait BinaryRead: Sized {
+    /// Reads value from the beginning of `buf` and advances `buf` by the number of consumed bytes.
+    fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError>;
+}
+
+/// Type, which can be written directly into Tezos binary form.
+pub trait BinaryWrite {
+    /// Appends binary form of the value to `data`.
+    fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+/// Reads value from `bytes`, all bytes have to be consumed (same as [BinaryReader::read]).
+pub fn from_bytes<T: BinaryRead>(bytes: &[u8]) -> Result<T, BinaryReaderError> {
+    let mut buf = bytes;
+    let value = T::read_binary(&mut buf)?;
+    if buf.is_empty() {
+        Ok(value)
+    } else {
+        Err(BinaryReaderError::Overflow { bytes: buf.len() })
+    }
+}
+
+/// Writes value into Tezos binary form (same as [binary_writer::write]).
+pub fn to_bytes<T: BinaryWrite + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::with_capacity(512);
+    value.write_binary(&mut data)?;
+    Ok(data)
+}
+
+#[inline]
+fn ensure(buf: &[u8], size: usize) -> Result<(), BinaryReaderError> {
+    if buf.len() >= size {
+        Ok(())
+    } else {
+        Err(BinaryReaderError::Underflow {
+            bytes: size - buf.len(),
+        })
+    }
+}
+
+#[inline]
+fn read_size(buf: &mut &[u8]) -> Result<usize, BinaryReaderError> {
+    ensure(*buf, size_of::<u32>())?;
+    Ok(buf.get_u32() as usize)
+}
+
+/// Reads `size` bytes from the beginning of `buf`
+#[inline]
+fn take<'a>(buf: &mut &'a [u8], size: usize) -> Result<&'a [u8], BinaryReaderError> {
+    ensure(*buf, size)?;
+    let whole: &'a [u8] = *buf;
+    let (bytes, rest) = whole.split_at(size);
+    *buf = rest;
+    Ok(bytes)
+}
+
+/// Reads value from first `size` bytes of `buf`. Like with [BinaryReader], bytes not consumed by `read`
+/// are left in `buf`.
+#[inline]
+fn read_slice<'a, T, F>(buf: &mut &'a [u8], size: usize, read: F) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let whole: &'a [u8] = *buf;
+    let mut slice = &whole[..size];
+    let value = read(&mut slice)?;
+    *buf = &whole[size - slice.len()..];
+    Ok(value)
+}
+
+macro_rules! primitive_binary_encoding {
+    ($t:ty, $get:ident, $put:ident) => {
+        impl BinaryRead for $t {
+            #[inline]
+            fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError> {
+                ensure(*buf, size_of::<$t>())?;
+                Ok(buf.$get())
+            }
+        }
+
+        impl BinaryWrite for $t {
+            #[inline]
+            fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+                data.$put(*self);
+                Ok(())
+            }
+        }
+    };
+}
+
+primitive_binary_encoding!(u8, get_u8, put_u8);
+primitive_binary_encoding!(i8, get_i8, put_i8);
+primitive_binary_encoding!(u16, get_u16, put_u16);
+primitive_binary_encoding!(i16, get_i16, put_i16);
+primitive_binary_encoding!(i32, get_i32, put_i32);
+primitive_binary_encoding!(i64, get_i64, put_i64);
+primitive_binary_encoding!(f64, get_f64, put_f64);
+
+impl BinaryRead for bool {
+    #[inline]
+    fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError> {
+        match u8::read_binary(buf)? {
+            types::BYTE_VAL_TRUE => Ok(true),
+            types::BYTE_VAL_FALSE => Ok(false),
+            b => Err(de::Error::custom(format!(
+                "Vas expecting 0xFF or 0x00 but instead got {:X}",
+                b
+            ))
+            .into()),
+        }
+    }
+}
+
+impl BinaryWrite for bool {
+    #[inline]
+    fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        if *self {
+            data.put_u8(types::BYTE_VAL_TRUE)
+        } else {
+            data.put_u8(types::BYTE_VAL_FALSE)
+        };
+        Ok(())
+    }
+}
+
+impl BinaryRead for String {
+    #[inline]
+    fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError> {
+        let size = read_size(buf)?;
+        Ok(String::from_utf8(take(buf, size)?.to_vec())?)
+    }
+}
+
+impl BinaryWrite for String {
+    #[inline]
+    fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        data.put_u32(self.len() as u32);
+        data.put_slice(self.as_bytes());
+        Ok(())
+    }
+}
+
+macro_rules! hash_binary_encoding {
+    ($($name:ident),*) => {
+        $(
+            impl BinaryRead for $name {
+                #[inline]
+                fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError> {
+                    Ok($name(take(buf, HashType::$name.size())?.to_vec()))
+                }
+            }
+
+            impl BinaryWrite for $name {
+                #[inline]
+                fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+                    if self.0.len() == HashType::$name.size() {
+                        data.put_slice(&self.0);
+                        Ok(())
+                    } else {
+                        Err(Error::custom(format!(
+                            "Was expecting {} bytes but got {}",
+                            HashType::$name.size(),
+                            self.0.len()
+                        )))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+hash_binary_encoding!(
+    ChainId,
+    BlockHash,
+    BlockMetadataHash,
+    OperationHash,
+    OperationListListHash,
+    OperationMetadataHash,
+    OperationMetadataListListHash,
+    ContextHash,
+    ProtocolHash,
+    ContractKt1Hash,
+    ContractTz1Hash,
+    ContractTz2Hash,
+    ContractTz3Hash,
+    CryptoboxPublicKeyHash,
+    PublicKeyEd25519,
+    PublicKeySecp256k1,
+    PublicKeyP256,
+    SeedEd25519,
+    SignatureEd25519,
+    NonceHash
+);
+
+/// Reads [Encoding::BoundedString].
+pub fn read_bounded_string(max: usize, buf: &mut &[u8]) -> Result<String, BinaryReaderError> {
+    let size = read_size(buf)?;
+    if size > max {
+        return Err(BinaryReaderError::EncodingBoundaryExceeded {
+            name: "Encoding::BoundedString".to_string(),
+            boundary: max,
+        });
+    }
+    Ok(String::from_utf8(take(buf, size)?.to_vec())?)
+}
+
+/// Reads [Encoding::Bytes], i.e. all remaining bytes.
+pub fn read_bytes(buf: &mut &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+    let size = buf.len();
+    Ok(take(buf, size)?.to_vec())
+}
+
+/// Reads [Encoding::BoundedList] of [Encoding::Uint8].
+pub fn read_bounded_bytes(max: usize, buf: &mut &[u8]) -> Result<Vec<u8>, BinaryReaderError> {
+    if buf.len() > max {
+        return Err(BinaryReaderError::EncodingBoundaryExceeded {
+            name: "Encoding::List".to_string(),
+            boundary: max,
+        });
+    }
+    read_bytes(buf)
+}
+
+/// Reads [Encoding::Dynamic].
+pub fn read_dynamic<'a, T, F>(buf: &mut &'a [u8], read: F) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let size = read_size(buf)?;
+    ensure(*buf, size)?;
+    read_slice(buf, size, read)
+}
+
+/// Reads [Encoding::BoundedDynamic].
+pub fn read_bounded_dynamic<'a, T, F>(
+    max: usize,
+    buf: &mut &'a [u8],
+    read: F,
+) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let size = read_size(buf)?;
+    if size > max {
+        return Err(BinaryReaderError::EncodingBoundaryExceeded {
+            name: "Encoding::BoundedDynamic".to_string(),
+            boundary: max,
+        });
+    }
+    ensure(*buf, size)?;
+    read_slice(buf, size, read)
+}
+
+/// Reads [Encoding::Sized].
+pub fn read_sized<'a, T, F>(
+    size: usize,
+    buf: &mut &'a [u8],
+    read: F,
+) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    ensure(*buf, size)?;
+    read_slice(buf, size, read)
+}
+
+/// Reads [Encoding::Bounded].
+pub fn read_bounded<'a, T, F>(
+    max: usize,
+    buf: &mut &'a [u8],
+    read: F,
+) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let upper = cmp::min(max, buf.len());
+    match read_slice(buf, upper, read) {
+        Err(BinaryReaderError::Underflow { .. }) => {
+            Err(BinaryReaderError::EncodingBoundaryExceeded {
+                name: "Encoding::Bounded".to_string(),
+                boundary: max,
+            })
+        }
+        r => r,
+    }
+}
+
+/// Reads [Encoding::Greedy].
+pub fn read_greedy<'a, T, F>(buf: &mut &'a [u8], read: F) -> Result<T, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    read(buf)
+}
+
+/// Reads [Encoding::List], i.e. items are read until `buf` is empty.
+pub fn read_list<'a, T, F>(buf: &mut &'a [u8], mut read: F) -> Result<Vec<T>, BinaryReaderError>
+where
+    F: FnMut(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let mut values = vec![];
+    while !buf.is_empty() {
+        values.push(read(buf)?);
+    }
+    Ok(values)
+}
+
+/// Reads [Encoding::BoundedList].
+pub fn read_bounded_list<'a, T, F>(
+    max: usize,
+    buf: &mut &'a [u8],
+    mut read: F,
+) -> Result<Vec<T>, BinaryReaderError>
+where
+    F: FnMut(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    let mut values = vec![];
+    while !buf.is_empty() {
+        if values.len() >= max {
+            return Err(BinaryReaderError::EncodingBoundaryExceeded {
+                name: "Encoding::List".to_string(),
+                boundary: max,
+            });
+        }
+        values.push(read(buf)?);
+    }
+    Ok(values)
+}
+
+/// Reads [Encoding::Option].
+pub fn read_option<'a, T, F>(buf: &mut &'a [u8], read: F) -> Result<Option<T>, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    match u8::read_binary(buf)? {
+        types::BYTE_VAL_SOME => read(buf).map(Some),
+        types::BYTE_VAL_NONE => Ok(None),
+        b => Err(de::Error::custom(format!("Unexpected option value {:X}", b)).into()),
+    }
+}
+
+/// Reads [Encoding::OptionalField].
+pub fn read_option_field<'a, T, F>(
+    buf: &mut &'a [u8],
+    read: F,
+) -> Result<Option<T>, BinaryReaderError>
+where
+    F: FnOnce(&mut &'a [u8]) -> Result<T, BinaryReaderError>,
+{
+    match u8::read_binary(buf)? {
+        types::BYTE_FIELD_SOME => read(buf).map(Some),
+        types::BYTE_FIELD_NONE => Ok(None),
+        b => Err(de::Error::custom(format!("Unexpected option value {:X}", b)).into()),
+    }
+}
+
+fn bound_error(name: &str, max: usize, act: usize) -> Error {
+    Error::custom(format!("{} maximum size {} exceeded: {}", name, max, act))
+}
+
+/// Writes [Encoding::Int31].
+pub fn write_int31(data: &mut Vec<u8>, value: &i32) -> Result<(), Error> {
+    if (*value & 0x7FFF_FFFF) == *value {
+        data.put_i32(*value);
+        Ok(())
+    } else {
+        Err(Error::custom("Value is outside of Int31 range"))
+    }
+}
+
+/// Writes [Encoding::BoundedString].
+pub fn write_bounded_string(max: usize, data: &mut Vec<u8>, value: &str) -> Result<(), Error> {
+    if value.len() > max {
+        return Err(bound_error("Encoding::BoundedString", max, value.len()));
+    }
+    data.put_u32(value.len() as u32);
+    data.put_slice(value.as_bytes());
+    Ok(())
+}
+
+/// Writes [Encoding::Bytes].
+pub fn write_bytes(data: &mut Vec<u8>, value: &[u8]) -> Result<(), Error> {
+    data.put_slice(value);
+    Ok(())
+}
+
+/// Writes [Encoding::BoundedList] of [Encoding::Uint8].
+pub fn write_bounded_bytes(max: usize, data: &mut Vec<u8>, value: &[u8]) -> Result<(), Error> {
+    if value.len() > max {
+        return Err(bound_error("Encoding::BoundedList", max, value.len()));
+    }
+    write_bytes(data, value)
+}
+
+/// Writes value prefixed by its size (as u32)
+fn write_size_prefixed<T, F>(
+    max: Option<usize>,
+    data: &mut Vec<u8>,
+    value: &T,
+    write: F,
+) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    let size_position = data.len();
+    // put 0 as a placeholder
+    data.put_u32(0);
+
+    write(data, value)?;
+
+    let size = data.len() - size_position - size_of::<u32>();
+    if let Some(max) = max {
+        if size > max {
+            return Err(bound_error("Encoding::BoundedDynamic", max, size));
+        }
+    }
+    let size = u32::try_from(size).map_err(|_| {
+        Error::custom("Encoded message size overflow while encoding a dynamic value")
+    })?;
+    data[size_position..size_position + size_of::<u32>()].copy_from_slice(&size.to_be_bytes());
+    Ok(())
+}
+
+/// Writes [Encoding::Dynamic].
+pub fn write_dynamic<T, F>(data: &mut Vec<u8>, value: &T, write: F) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    write_size_prefixed(None, data, value, write)
+}
+
+/// Writes [Encoding::BoundedDynamic].
+pub fn write_bounded_dynamic<T, F>(
+    max: usize,
+    data: &mut Vec<u8>,
+    value: &T,
+    write: F,
+) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    write_size_prefixed(Some(max), data, value, write)
+}
+
+/// Writes [Encoding::Sized].
+pub fn write_sized<T, F>(size: usize, data: &mut Vec<u8>, value: &T, write: F) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    let data_len_before_write = data.len();
+    write(data, value)?;
+    let bytes_sz = data.len() - data_len_before_write;
+    if bytes_sz == size {
+        Ok(())
+    } else {
+        Err(Error::custom(format!(
+            "Was expecting {} bytes but got {}",
+            bytes_sz, size
+        )))
+    }
+}
+
+/// Writes [Encoding::Bounded].
+pub fn write_bounded<T, F>(max: usize, data: &mut Vec<u8>, value: &T, write: F) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    let data_len_before_write = data.len();
+    write(data, value)?;
+    let bytes_sz = data.len() - data_len_before_write;
+    if bytes_sz <= max {
+        Ok(())
+    } else {
+        Err(bound_error("Encoding::Bounded", max, bytes_sz))
+    }
+}
+
+/// Writes [Encoding::Greedy].
+pub fn write_greedy<T, F>(data: &mut Vec<u8>, value: &T, write: F) -> Result<(), Error>
+where
+    T: ?Sized,
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    write(data, value)
+}
+
+/// Writes [Encoding::List].
+pub fn write_list<T, F>(data: &mut Vec<u8>, value: &[T], mut write: F) -> Result<(), Error>
+where
+    F: FnMut(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    for item in value {
+        write(data, item)?;
+    }
+    Ok(())
+}
+
+/// Writes [Encoding::BoundedList].
+pub fn write_bounded_list<T, F>(
+    max: usize,
+    data: &mut Vec<u8>,
+    value: &[T],
+    write: F,
+) -> Result<(), Error>
+where
+    F: FnMut(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    if value.len() > max {
+        return Err(bound_error("Encoding::BoundedList", max, value.len()));
+    }
+    write_list(data, value, write)
+}
+
+/// Writes [Encoding::Option].
+pub fn write_option<T, F>(data: &mut Vec<u8>, value: &Option<T>, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    match value {
+        Some(value) => {
+            data.put_u8(types::BYTE_VAL_SOME);
+            write(data, value)
+        }
+        None => {
+            data.put_u8(types::BYTE_VAL_NONE);
+            Ok(())
+        }
+    }
+}
+
+/// Writes [Encoding::OptionalField].
+pub fn write_option_field<T, F>(
+    data: &mut Vec<u8>,
+    value: &Option<T>,
+    write: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut Vec<u8>, &T) -> Result<(), Error>,
+{
+    match value {
+        Some(value) => {
+            data.put_u8(types::BYTE_FIELD_SOME);
+            write(data, value)
+        }
+        None => {
+            data.put_u8(types::BYTE_FIELD_NONE);
+            Ok(())
+        }
+    }
+}
+
+/// Reads value through the [intermediate form](crate::types::Value), see [binary_encoding_by_value].
+pub fn read_by_value<T: DeserializeOwned>(
+    buf: &mut &[u8],
+    encoding: &Encoding,
+) -> Result<T, BinaryReaderError> {
+    let value = BinaryReader::new().read_from(buf, encoding)?;
+    Ok(de::from_value(&value)?)
+}
+
+/// Writes value through the [intermediate form](crate::types::Value), see [binary_encoding_by_value].
+pub fn write_by_value<T: Serialize + ?Sized>(
+    data: &mut Vec<u8>,
+    value: &T,
+    encoding: &Encoding,
+) -> Result<(), Error> {
+    data.extend_from_slice(&binary_writer::write(value, encoding)?);
+    Ok(())
+}
+
+/// Implements [BinaryRead] and [BinaryWrite] for type with [HasEncoding](crate::encoding::HasEncoding)
+/// through the [intermediate form](crate::types::Value). This is meant for types,
+/// whose encoding can not be described by `#[derive(BinaryEncoding)]` (e.g. uses [Encoding::Z] or [Encoding::Custom]).
+#[macro_export]
+macro_rules! binary_encoding_by_value {
+    ($struct_name:ident) => {
+        impl $crate::direct::BinaryRead for $struct_name {
+            #[inline]
+            fn read_binary(
+                buf: &mut &[u8],
+            ) -> Result<Self, $crate::binary_reader::BinaryReaderError> {
+                $crate::direct::read_by_value(
+                    buf,
+                    &<Self as $crate::encoding::HasEncoding>::encoding(),
+                )
+            }
+        }
+
+        impl $crate::direct::BinaryWrite for $struct_name {
+            #[inline]
+            fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), $crate::ser::Error> {
+                $crate::direct::write_by_value(
+                    data,
+                    self,
+                    &<Self as $crate::encoding::HasEncoding>::encoding(),
+                )
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::encoding::{Field, HasEncoding, Tag, TagMap};
+
+    use super::*;
+
+    #[derive(BinaryEncoding, Serialize, Deserialize, Debug, PartialEq, Clone)]
+    #[encoding(tags(u16))]
+    enum Message {
+        #[encoding(tag = 0x01)]
+        Empty,
+        #[encoding(tag = 0x10)]
+        Record(Record),
+        #[encoding(tag = 0xFF)]
+        Level(#[encoding(int31)] i32),
+    }
+
+    fn message_encoding() -> Encoding {
+        Encoding::Tags(
+            size_of::<u16>(),
+            TagMap::new(vec![
+                Tag::new(0x01, "Empty", Encoding::Unit),
+                Tag::new(0x10, "Record", record_encoding()),
+                Tag::new(0xFF, "Level", Encoding::Int31),
+            ]),
+        )
+    }
+
+    #[derive(BinaryEncoding, Serialize, Deserialize, Debug, PartialEq, Clone)]
+    #[encoding(bounded(100))]
+    struct Record {
+        flag: bool,
+        #[encoding(sized(4, bytes))]
+        magic: Vec<u8>,
+        block: BlockHash,
+        #[encoding(dynamic(bounded_list(2, bounded_string(4))))]
+        names: Vec<String>,
+        #[encoding(option_field(timestamp))]
+        timestamp: Option<i64>,
+        #[encoding(bounded_dynamic(8, list(uint8)))]
+        tail: Vec<u8>,
+    }
+
+    fn record_encoding() -> Encoding {
+        Encoding::bounded(
+            100,
+            Encoding::Obj(vec![
+                Field::new("flag", Encoding::Bool),
+                Field::new("magic", Encoding::sized(4, Encoding::Bytes)),
+                Field::new("block", Encoding::Hash(HashType::BlockHash)),
+                Field::new(
+                    "names",
+                    Encoding::dynamic(Encoding::bounded_list(2, Encoding::BoundedString(4))),
+                ),
+                Field::new(
+                    "timestamp",
+                    Encoding::OptionalField(Box::new(Encoding::Timestamp)),
+                ),
+                Field::new(
+                    "tail",
+                    Encoding::bounded_dynamic(8, Encoding::list(Encoding::Uint8)),
+                ),
+            ]),
+        )
+    }
+
+    fn record() -> Record {
+        Record {
+            flag: true,
+            magic: vec![1, 2, 3, 4],
+            block: BlockHash(vec![7; HashType::BlockHash.size()]),
+            names: vec!["a".to_string(), "abcd".to_string()],
+            timestamp: Some(1_600_000_000),
+            tail: vec![0xCA, 0xFE],
+        }
+    }
+
+    #[test]
+    fn can_write_same_as_binary_writer() {
+        let messages = vec![
+            Message::Empty,
+            Message::Record(record()),
+            Message::Record(Record {
+                names: vec![],
+                timestamp: None,
+                tail: vec![],
+                ..record()
+            }),
+            Message::Level(0x7FFF_FFFF),
+        ];
+
+        for message in messages {
+            let expected = binary_writer::write(&message, &message_encoding()).unwrap();
+            let bytes = to_bytes(&message).unwrap();
+            assert_eq!(expected, bytes);
+            assert_eq!(
+                expected,
+                binary_writer::write(&message, Message::encoding()).unwrap()
+            );
+            assert_eq!(message, from_bytes::<Message>(&bytes).unwrap());
+        }
+    }
+
+    #[test]
+    fn can_check_bounds_on_write() {
+        let too_many_names = Record {
+            names: vec!["a".to_string(); 3],
+            ..record()
+        };
+        assert!(to_bytes(&too_many_names).is_err());
+
+        let too_long_name = Record {
+            names: vec!["abcde".to_string()],
+            ..record()
+        };
+        assert!(to_bytes(&too_long_name).is_err());
+
+        let wrong_magic = Record {
+            magic: vec![1, 2, 3],
+            ..record()
+        };
+        assert!(to_bytes(&wrong_magic).is_err());
+
+        let too_long_tail = Record {
+            tail: vec![0; 9],
+            ..record()
+        };
+        assert!(to_bytes(&too_long_tail).is_err());
+
+        assert!(to_bytes(&Message::Level(-1)).is_err());
+    }
+
+    #[test]
+    fn can_fail_read_same_as_binary_reader() {
+        let bytes = to_bytes(&Message::Record(record())).unwrap();
+
+        // every truncated input has to fail in the same way
+        for size in 0..bytes.len() {
+            let expected = BinaryReader::new()
+                .read(&bytes[..size], &message_encoding())
+                .unwrap_err();
+            let error = from_bytes::<Message>(&bytes[..size]).unwrap_err();
+            assert_eq!(format!("{:?}", expected), format!("{:?}", error));
+        }
+
+        let mut overflow = bytes;
+        overflow.push(0);
+        assert!(matches!(
+            from_bytes::<Message>(&overflow),
+            Err(BinaryReaderError::Overflow { bytes: 1 })
+        ));
+
+        assert!(matches!(
+            from_bytes::<Message>(&[0x00, 0x02]),
+            Err(BinaryReaderError::UnsupportedTag { tag: 0x02 })
+        ));
+    }
+}
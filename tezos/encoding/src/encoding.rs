@@ -354,7 +354,7 @@ pub trait HasEncoding {
 #[macro_export]
 macro_rules! has_encoding {
     ($struct_name:ident, $enc_ref_name:ident, $code:block) => {
-        lazy_static::lazy_static! {
+        $crate::lazy_static::lazy_static! {
             static ref $enc_ref_name: Encoding = {
                 $code
             };
@@ -368,6 +368,60 @@ macro_rules! has_encoding {
     };
 }
 
+macro_rules! static_has_encoding {
+    ($($t:ty => $encoding:expr),* $(,)?) => {
+        $(
+            impl HasEncoding for $t {
+                fn encoding() -> &'static Encoding {
+                    static ENCODING: Encoding = $encoding;
+                    &ENCODING
+                }
+            }
+        )*
+    };
+}
+
+static_has_encoding!(
+    u8 => Encoding::Uint8,
+    i8 => Encoding::Int8,
+    u16 => Encoding::Uint16,
+    i16 => Encoding::Int16,
+    i32 => Encoding::Int32,
+    i64 => Encoding::Int64,
+    f64 => Encoding::Float,
+    bool => Encoding::Bool,
+    String => Encoding::String,
+);
+
+macro_rules! hash_has_encoding {
+    ($($name:ident),*) => {
+        static_has_encoding!($(crypto::hash::$name => Encoding::Hash(HashType::$name)),*);
+    };
+}
+
+hash_has_encoding!(
+    ChainId,
+    BlockHash,
+    BlockMetadataHash,
+    OperationHash,
+    OperationListListHash,
+    OperationMetadataHash,
+    OperationMetadataListListHash,
+    ContextHash,
+    ProtocolHash,
+    ContractKt1Hash,
+    ContractTz1Hash,
+    ContractTz2Hash,
+    ContractTz3Hash,
+    CryptoboxPublicKeyHash,
+    PublicKeyEd25519,
+    PublicKeySecp256k1,
+    PublicKeyP256,
+    SeedEd25519,
+    SignatureEd25519,
+    NonceHash
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,12 +4,20 @@
 
 //! This crate provides serialization and deserialization functionality for the data types used by the Tezos shell.
 
+// allows `#[derive(BinaryEncoding)]` to be used inside of this crate
+extern crate self as tezos_encoding;
+
 mod bit_utils;
 pub mod types;
 
 pub mod binary_reader;
 pub mod binary_writer;
 pub mod de;
+pub mod direct;
 pub mod encoding;
 pub mod json_writer;
 pub mod ser;
+
+// used by `has_encoding!` and `#[derive(BinaryEncoding)]`
+#[doc(hidden)]
+pub use lazy_static;
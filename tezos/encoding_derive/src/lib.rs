@@ -0,0 +1,672 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+#![forbid(unsafe_code)]
+
+//! This crate provides `#[derive(BinaryEncoding)]`, which generates direct binary reader and writer
+//! (`tezos_encoding::direct::BinaryRead` and `tezos_encoding::direct::BinaryWrite`) for a struct or an enum,
+//! together with its schema (`tezos_encoding::encoding::HasEncoding`).
+//!
+//! Binary form of a field is described by `#[encoding(...)]` attribute, supported annotations
+//! are documented in `tezos_encoding::direct` module. Both the direct reader/writer and the schema
+//! are generated from the same attributes, so they can not diverge.
+
+extern crate proc_macro;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DataEnum, DeriveInput, Error, Fields, GenericArgument,
+    Ident, Lit, LitInt, Meta, NestedMeta, PathArguments, Result, Type, Variant,
+};
+
+#[proc_macro_derive(BinaryEncoding, attributes(encoding))]
+pub fn derive_binary_encoding(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Binary form of a field (or of a whole struct), mirrors `tezos_encoding::encoding::Encoding`.
+enum Encoding {
+    /// Type's own `BinaryRead`/`BinaryWrite` implementation (or struct body, when used as container encoding)
+    Natural,
+    /// Encoded as given primitive type (rust type, `Encoding` variant)
+    Primitive(&'static str, &'static str),
+    Int31,
+    /// Encoded as nothing, `Default` is used on read
+    Unit,
+    BoundedString(TokenStream),
+    Bytes,
+    /// Same as `list(uint8)`, but read/written at once
+    ByteList,
+    /// Same as `bounded_list(max, uint8)`, but read/written at once
+    BoundedBytes(TokenStream),
+    Dynamic(Box<Encoding>),
+    BoundedDynamic(TokenStream, Box<Encoding>),
+    Sized(TokenStream, Box<Encoding>),
+    Bounded(TokenStream, Box<Encoding>),
+    Greedy(Box<Encoding>),
+    List(Box<Encoding>),
+    BoundedList(TokenStream, Box<Encoding>),
+    Option(Box<Encoding>),
+    OptionField(Box<Encoding>),
+    /// Different schema for JSON (first) and binary (second), binary one is read/written
+    Split(Box<Encoding>, Box<Encoding>),
+}
+
+impl Encoding {
+    /// Encodings, which can wrap the whole struct
+    fn is_container(&self) -> bool {
+        match self {
+            Encoding::Natural => true,
+            Encoding::Dynamic(inner)
+            | Encoding::BoundedDynamic(_, inner)
+            | Encoding::Sized(_, inner)
+            | Encoding::Bounded(_, inner)
+            | Encoding::Greedy(inner) => inner.is_container(),
+            _ => false,
+        }
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream> {
+    let (read_body, write_body, schema) = match &input.data {
+        Data::Struct(data) => expand_struct(input, &data.fields)?,
+        Data::Enum(data) => expand_enum(input, data)?,
+        Data::Union(_) => {
+            return Err(Error::new(
+                input.ident.span(),
+                "BinaryEncoding can not be derived for union",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::tezos_encoding::direct::BinaryRead for #name #ty_generics #where_clause {
+            #[inline]
+            fn read_binary(
+                buf: &mut &[u8],
+            ) -> ::std::result::Result<Self, ::tezos_encoding::binary_reader::BinaryReaderError> {
+                #read_body
+            }
+        }
+
+        impl #impl_generics ::tezos_encoding::direct::BinaryWrite for #name #ty_generics #where_clause {
+            #[inline]
+            fn write_binary(
+                &self,
+                data: &mut ::std::vec::Vec<u8>,
+            ) -> ::std::result::Result<(), ::tezos_encoding::ser::Error> {
+                #write_body
+            }
+        }
+
+        impl #impl_generics ::tezos_encoding::encoding::HasEncoding for #name #ty_generics #where_clause {
+            fn encoding() -> &'static ::tezos_encoding::encoding::Encoding {
+                ::tezos_encoding::lazy_static::lazy_static! {
+                    static ref ENCODING: ::tezos_encoding::encoding::Encoding = #schema;
+                }
+                &ENCODING
+            }
+        }
+    })
+}
+
+fn expand_struct(
+    input: &DeriveInput,
+    fields: &Fields,
+) -> Result<(TokenStream, TokenStream, TokenStream)> {
+    let fields = match fields {
+        Fields::Named(fields) => &fields.named,
+        _ => {
+            return Err(Error::new(
+                fields.span(),
+                "BinaryEncoding can be derived only for struct with named fields",
+            ))
+        }
+    };
+
+    let mut names = Vec::with_capacity(fields.len());
+    let mut locals = Vec::with_capacity(fields.len());
+    let mut reads = Vec::with_capacity(fields.len());
+    let mut writes = Vec::with_capacity(fields.len());
+    let mut schemas = Vec::with_capacity(fields.len());
+    for field in fields {
+        let name = field.ident.as_ref().expect("named field");
+        let local = Ident::new(&format!("__{}", name), name.span());
+        match field_encoding(&field.attrs)? {
+            Some(encoding) => {
+                let read = read_expr(&encoding, &natural_read());
+                reads.push(quote!(let #local = #read?;));
+                writes.push(write_expr(&encoding, &quote!(&value.#name), &natural_write));
+                let schema = schema_expr(&encoding, Some(&field.ty), &|ty| {
+                    natural_schema(ty, field.span())
+                })?;
+                let name = name.to_string();
+                schemas.push(quote!(::tezos_encoding::encoding::Field::new(#name, #schema)));
+            }
+            None => reads.push(quote!(let #local = ::std::default::Default::default();)),
+        }
+        names.push(name);
+        locals.push(local);
+    }
+
+    let container = match encoding_attribute(&input.attrs)? {
+        Some(meta) => {
+            let encoding = parse_encoding(&meta)?;
+            if !encoding.is_container() {
+                return Err(Error::new(
+                    meta.span(),
+                    "struct can be wrapped only by `dynamic`, `bounded_dynamic`, `sized`, `bounded` or `greedy` encoding",
+                ));
+            }
+            encoding
+        }
+        None => Encoding::Natural,
+    };
+
+    let body_read = quote!({
+        #( #reads )*
+        ::std::result::Result::Ok(Self { #( #names: #locals ),* })
+    });
+    let body_write = |value: &TokenStream| {
+        quote!({
+            let value: &Self = #value;
+            #( #writes?; )*
+            ::std::result::Result::Ok(())
+        })
+    };
+
+    let body_schema = quote!(::tezos_encoding::encoding::Encoding::Obj(
+        vec![ #( #schemas ),* ]
+    ));
+
+    Ok((
+        read_expr(&container, &body_read),
+        write_expr(&container, &quote!(self), &body_write),
+        schema_expr(&container, None, &|_| Ok(body_schema.clone()))?,
+    ))
+}
+
+fn expand_enum(
+    input: &DeriveInput,
+    data: &DataEnum,
+) -> Result<(TokenStream, TokenStream, TokenStream)> {
+    let tag_type = enum_tag_type(input)?;
+    let direct = quote!(::tezos_encoding::direct);
+    let encoding_mod = quote!(::tezos_encoding::encoding);
+
+    let mut tags = Vec::with_capacity(data.variants.len());
+    let mut read_arms = Vec::with_capacity(data.variants.len());
+    let mut write_arms = Vec::with_capacity(data.variants.len());
+    let mut schemas = Vec::with_capacity(data.variants.len());
+    for variant in &data.variants {
+        let (tag, span) = variant_tag(variant)?;
+        if tag_type == "u8" && tag > u16::from(u8::MAX) {
+            return Err(Error::new(span, "tag does not fit into `u8`"));
+        }
+        if tags.contains(&tag) {
+            return Err(Error::new(span, format!("duplicate tag {}", tag)));
+        }
+        tags.push(tag);
+
+        let tag = LitInt::new(&tag.to_string(), span);
+        let tag_ty = Ident::new(tag_type, Span::call_site());
+        let write_tag = quote!(<#tag_ty as #direct::BinaryWrite>::write_binary(&#tag, data));
+        let ident = &variant.ident;
+        let variant_name = ident.to_string();
+        match &variant.fields {
+            Fields::Unit => {
+                read_arms.push(quote!(#tag => ::std::result::Result::Ok(Self::#ident),));
+                write_arms.push(quote!(Self::#ident => #write_tag,));
+                let unit = quote!(#encoding_mod::Encoding::Unit);
+                schemas.push(quote!(#encoding_mod::Tag::new(#tag, #variant_name, #unit)));
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let field = &fields.unnamed[0];
+                let encoding = field_encoding(&field.attrs)?.ok_or_else(|| {
+                    Error::new(field.span(), "variant field can not be skipped")
+                })?;
+                let read = read_expr(&encoding, &natural_read());
+                let write = write_expr(&encoding, &quote!(value), &natural_write);
+                let schema = schema_expr(&encoding, Some(&field.ty), &|ty| {
+                    natural_schema(ty, field.span())
+                })?;
+                schemas.push(quote!(#encoding_mod::Tag::new(#tag, #variant_name, #schema)));
+                read_arms.push(quote!(#tag => ::std::result::Result::Ok(Self::#ident(#read?)),));
+                write_arms.push(quote!(Self::#ident(value) => {
+                    #write_tag?;
+                    #write
+                }));
+            }
+            fields => {
+                return Err(Error::new(
+                    fields.span(),
+                    "BinaryEncoding supports only unit variants and variants with a single unnamed field",
+                ))
+            }
+        }
+    }
+
+    let read_tag = if tag_type == "u8" {
+        quote!(u16::from(<u8 as #direct::BinaryRead>::read_binary(buf)?))
+    } else {
+        quote!(<u16 as #direct::BinaryRead>::read_binary(buf)?)
+    };
+    let tag_ty = Ident::new(tag_type, Span::call_site());
+
+    Ok((
+        quote! {
+            match #read_tag {
+                #( #read_arms )*
+                tag => ::std::result::Result::Err(
+                    ::tezos_encoding::binary_reader::BinaryReaderError::UnsupportedTag { tag },
+                ),
+            }
+        },
+        quote! {
+            match self {
+                #( #write_arms )*
+            }
+        },
+        quote! {
+            #encoding_mod::Encoding::Tags(
+                ::std::mem::size_of::<#tag_ty>(),
+                #encoding_mod::TagMap::new(vec![ #( #schemas ),* ]),
+            )
+        },
+    ))
+}
+
+/// Reads `#[encoding(tags(u8))]` or `#[encoding(tags(u16))]` enum attribute
+fn enum_tag_type(input: &DeriveInput) -> Result<&'static str> {
+    const EXPECTED: &str = "enum requires `#[encoding(tags(u8))]` or `#[encoding(tags(u16))]`";
+
+    match encoding_attribute(&input.attrs)? {
+        Some(NestedMeta::Meta(Meta::List(list)))
+            if list.path.is_ident("tags") && list.nested.len() == 1 =>
+        {
+            match &list.nested[0] {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("u8") => Ok("u8"),
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("u16") => Ok("u16"),
+                other => Err(Error::new(other.span(), EXPECTED)),
+            }
+        }
+        Some(other) => Err(Error::new(other.span(), EXPECTED)),
+        None => Err(Error::new(input.ident.span(), EXPECTED)),
+    }
+}
+
+/// Reads `#[encoding(tag = ...)]` variant attribute
+fn variant_tag(variant: &Variant) -> Result<(u16, Span)> {
+    match encoding_attribute(&variant.attrs)? {
+        Some(NestedMeta::Meta(Meta::NameValue(name_value))) if name_value.path.is_ident("tag") => {
+            match &name_value.lit {
+                Lit::Int(tag) => Ok((tag.base10_parse()?, tag.span())),
+                other => Err(Error::new(other.span(), "expected integer tag")),
+            }
+        }
+        _ => Err(Error::new(
+            variant.ident.span(),
+            "variant requires `#[encoding(tag = ...)]`",
+        )),
+    }
+}
+
+/// Returns content of the (only) `#[encoding(...)]` attribute
+fn encoding_attribute(attrs: &[Attribute]) -> Result<Option<NestedMeta>> {
+    let mut result = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("encoding")) {
+        if result.is_some() {
+            return Err(Error::new(attr.span(), "duplicate `encoding` attribute"));
+        }
+        match attr.parse_meta()? {
+            Meta::List(list) if list.nested.len() == 1 => result = list.nested.into_iter().next(),
+            other => {
+                return Err(Error::new(
+                    other.span(),
+                    "expected `#[encoding(...)]` with a single encoding",
+                ))
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Returns encoding of a field, `None` means, that field is skipped
+fn field_encoding(attrs: &[Attribute]) -> Result<Option<Encoding>> {
+    match encoding_attribute(attrs)? {
+        None => Ok(Some(Encoding::Natural)),
+        Some(NestedMeta::Meta(Meta::Path(ref path))) if path.is_ident("skip") => Ok(None),
+        Some(meta) => parse_encoding(&meta).map(Some),
+    }
+}
+
+fn parse_encoding(meta: &NestedMeta) -> Result<Encoding> {
+    let (path, args) = match meta {
+        NestedMeta::Meta(Meta::Path(path)) => (path, vec![]),
+        NestedMeta::Meta(Meta::List(list)) => (&list.path, list.nested.iter().collect()),
+        _ => {
+            return Err(Error::new(
+                meta.span(),
+                "expected encoding, e.g. `dynamic(list(string))`",
+            ))
+        }
+    };
+    let name = path
+        .get_ident()
+        .map(|ident| ident.to_string())
+        .ok_or_else(|| Error::new(path.span(), "expected encoding name"))?;
+
+    match (name.as_str(), args.as_slice()) {
+        ("uint8", []) => Ok(Encoding::Primitive("u8", "Uint8")),
+        ("int8", []) => Ok(Encoding::Primitive("i8", "Int8")),
+        ("uint16", []) => Ok(Encoding::Primitive("u16", "Uint16")),
+        ("int16", []) => Ok(Encoding::Primitive("i16", "Int16")),
+        ("int32", []) => Ok(Encoding::Primitive("i32", "Int32")),
+        ("int64", []) => Ok(Encoding::Primitive("i64", "Int64")),
+        ("timestamp", []) => Ok(Encoding::Primitive("i64", "Timestamp")),
+        ("float", []) => Ok(Encoding::Primitive("f64", "Float")),
+        ("bool", []) => Ok(Encoding::Primitive("bool", "Bool")),
+        ("string", []) => Ok(Encoding::Primitive("String", "String")),
+        ("hash", []) => Ok(Encoding::Natural),
+        ("int31", []) => Ok(Encoding::Int31),
+        ("unit", []) => Ok(Encoding::Unit),
+        ("bytes", []) => Ok(Encoding::Bytes),
+        ("bounded_string", [max]) => Ok(Encoding::BoundedString(parse_size(max)?)),
+        ("dynamic", inner) if inner.len() <= 1 => Ok(Encoding::Dynamic(parse_inner(inner)?)),
+        ("greedy", inner) if inner.len() <= 1 => Ok(Encoding::Greedy(parse_inner(inner)?)),
+        ("option", inner) if inner.len() <= 1 => Ok(Encoding::Option(parse_inner(inner)?)),
+        ("option_field", inner) if inner.len() <= 1 => {
+            Ok(Encoding::OptionField(parse_inner(inner)?))
+        }
+        ("list", inner) if inner.len() <= 1 => match *parse_inner(inner)? {
+            Encoding::Primitive("u8", _) => Ok(Encoding::ByteList),
+            inner => Ok(Encoding::List(Box::new(inner))),
+        },
+        ("bounded_list", [max, inner @ ..]) if inner.len() <= 1 => {
+            let max = parse_size(max)?;
+            match *parse_inner(inner)? {
+                Encoding::Primitive("u8", _) => Ok(Encoding::BoundedBytes(max)),
+                inner => Ok(Encoding::BoundedList(max, Box::new(inner))),
+            }
+        }
+        ("bounded_dynamic", [max, inner @ ..]) if inner.len() <= 1 => Ok(Encoding::BoundedDynamic(
+            parse_size(max)?,
+            parse_inner(inner)?,
+        )),
+        ("sized", [size, inner @ ..]) if inner.len() <= 1 => {
+            Ok(Encoding::Sized(parse_size(size)?, parse_inner(inner)?))
+        }
+        ("bounded", [max, inner @ ..]) if inner.len() <= 1 => {
+            Ok(Encoding::Bounded(parse_size(max)?, parse_inner(inner)?))
+        }
+        ("split", [json, binary]) => Ok(Encoding::Split(
+            Box::new(parse_encoding(json)?),
+            Box::new(parse_encoding(binary)?),
+        )),
+        _ => Err(Error::new(
+            meta.span(),
+            format!("unsupported encoding `{}`", name),
+        )),
+    }
+}
+
+/// Parses optional inner encoding of a combinator, type's own encoding is used, when omitted
+fn parse_inner(args: &[&NestedMeta]) -> Result<Box<Encoding>> {
+    match args {
+        [inner] => parse_encoding(inner).map(Box::new),
+        _ => Ok(Box::new(Encoding::Natural)),
+    }
+}
+
+/// Parses size argument, which can be an integer literal or a constant
+fn parse_size(meta: &NestedMeta) -> Result<TokenStream> {
+    match meta {
+        NestedMeta::Lit(Lit::Int(size)) => Ok(quote!(#size)),
+        NestedMeta::Meta(Meta::Path(path)) => Ok(quote!(#path)),
+        _ => Err(Error::new(
+            meta.span(),
+            "expected size, integer literal or constant",
+        )),
+    }
+}
+
+fn natural_read() -> TokenStream {
+    quote!(::tezos_encoding::direct::BinaryRead::read_binary(buf))
+}
+
+fn natural_write(value: &TokenStream) -> TokenStream {
+    quote!(::tezos_encoding::direct::BinaryWrite::write_binary(#value, data))
+}
+
+fn primitive_type(name: &str) -> TokenStream {
+    if name == "String" {
+        quote!(::std::string::String)
+    } else {
+        let ident = Ident::new(name, Span::call_site());
+        quote!(#ident)
+    }
+}
+
+/// Generates expression, which reads value from `buf: &mut &[u8]`
+fn read_expr(encoding: &Encoding, natural: &TokenStream) -> TokenStream {
+    let direct = quote!(::tezos_encoding::direct);
+    let inner = |inner: &Encoding| read_expr(inner, natural);
+    match encoding {
+        Encoding::Natural => natural.clone(),
+        Encoding::Primitive(name, _) => {
+            let ty = primitive_type(name);
+            quote!(<#ty as #direct::BinaryRead>::read_binary(buf))
+        }
+        Encoding::Int31 => quote!(<i32 as #direct::BinaryRead>::read_binary(buf)),
+        Encoding::Unit => quote!(::std::result::Result::<
+            _,
+            ::tezos_encoding::binary_reader::BinaryReaderError,
+        >::Ok(::std::default::Default::default())),
+        Encoding::BoundedString(max) => quote!(#direct::read_bounded_string(#max, buf)),
+        Encoding::Bytes | Encoding::ByteList => quote!(#direct::read_bytes(buf)),
+        Encoding::BoundedBytes(max) => quote!(#direct::read_bounded_bytes(#max, buf)),
+        Encoding::Dynamic(encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_dynamic(buf, |buf| #read))
+        }
+        Encoding::BoundedDynamic(max, encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_bounded_dynamic(#max, buf, |buf| #read))
+        }
+        Encoding::Sized(size, encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_sized(#size, buf, |buf| #read))
+        }
+        Encoding::Bounded(max, encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_bounded(#max, buf, |buf| #read))
+        }
+        Encoding::Greedy(encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_greedy(buf, |buf| #read))
+        }
+        Encoding::List(encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_list(buf, |buf| #read))
+        }
+        Encoding::BoundedList(max, encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_bounded_list(#max, buf, |buf| #read))
+        }
+        Encoding::Option(encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_option(buf, |buf| #read))
+        }
+        Encoding::OptionField(encoding) => {
+            let read = inner(encoding);
+            quote!(#direct::read_option_field(buf, |buf| #read))
+        }
+        Encoding::Split(_, binary) => read_expr(binary, natural),
+    }
+}
+
+/// Generates expression, which writes `value` to `data: &mut Vec<u8>`
+fn write_expr(
+    encoding: &Encoding,
+    value: &TokenStream,
+    natural: &dyn Fn(&TokenStream) -> TokenStream,
+) -> TokenStream {
+    let direct = quote!(::tezos_encoding::direct);
+    let inner = |inner: &Encoding| write_expr(inner, &quote!(value), natural);
+    match encoding {
+        Encoding::Natural => natural(value),
+        Encoding::Primitive(name, _) => {
+            let ty = primitive_type(name);
+            quote!(<#ty as #direct::BinaryWrite>::write_binary(#value, data))
+        }
+        Encoding::Int31 => quote!(#direct::write_int31(data, #value)),
+        Encoding::Unit => quote!({
+            let _ = #value;
+            ::std::result::Result::<(), ::tezos_encoding::ser::Error>::Ok(())
+        }),
+        Encoding::BoundedString(max) => quote!(#direct::write_bounded_string(#max, data, #value)),
+        Encoding::Bytes | Encoding::ByteList => quote!(#direct::write_bytes(data, #value)),
+        Encoding::BoundedBytes(max) => quote!(#direct::write_bounded_bytes(#max, data, #value)),
+        Encoding::Dynamic(encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_dynamic(data, #value, |data, value| #write))
+        }
+        Encoding::BoundedDynamic(max, encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_bounded_dynamic(#max, data, #value, |data, value| #write))
+        }
+        Encoding::Sized(size, encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_sized(#size, data, #value, |data, value| #write))
+        }
+        Encoding::Bounded(max, encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_bounded(#max, data, #value, |data, value| #write))
+        }
+        Encoding::Greedy(encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_greedy(data, #value, |data, value| #write))
+        }
+        Encoding::List(encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_list(data, #value, |data, value| #write))
+        }
+        Encoding::BoundedList(max, encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_bounded_list(#max, data, #value, |data, value| #write))
+        }
+        Encoding::Option(encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_option(data, #value, |data, value| #write))
+        }
+        Encoding::OptionField(encoding) => {
+            let write = inner(encoding);
+            quote!(#direct::write_option_field(data, #value, |data, value| #write))
+        }
+        Encoding::Split(_, binary) => write_expr(binary, value, natural),
+    }
+}
+
+/// Schema of a type with own encoding, i.e. its `HasEncoding` implementation
+fn natural_schema(ty: Option<&Type>, span: Span) -> Result<TokenStream> {
+    match ty {
+        Some(ty) => Ok(quote!(
+            <#ty as ::tezos_encoding::encoding::HasEncoding>::encoding().clone()
+        )),
+        None => Err(Error::new(
+            span,
+            "can not infer type of the inner encoding, specify it explicitly",
+        )),
+    }
+}
+
+/// Returns `T` for `Vec<T>`, `Option<T>` and alike
+fn element_type(ty: Option<&Type>) -> Option<&Type> {
+    match ty? {
+        Type::Path(path) => match &path.path.segments.last()?.arguments {
+            PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Generates expression, which builds `tezos_encoding::encoding::Encoding` schema of value of type `ty`
+fn schema_expr(
+    encoding: &Encoding,
+    ty: Option<&Type>,
+    natural: &dyn Fn(Option<&Type>) -> Result<TokenStream>,
+) -> Result<TokenStream> {
+    let enc = quote!(::tezos_encoding::encoding::Encoding);
+    let same = |inner: &Encoding| schema_expr(inner, ty, natural);
+    let element = |inner: &Encoding| schema_expr(inner, element_type(ty), natural);
+    let split = |json: TokenStream, binary: TokenStream| {
+        let schema_type = quote!(::tezos_encoding::encoding::SchemaType);
+        quote!(#enc::Split(::std::sync::Arc::new(|schema_type| match schema_type {
+            #schema_type::Json => #json,
+            #schema_type::Binary => #binary,
+        })))
+    };
+    Ok(match encoding {
+        Encoding::Natural => natural(ty)?,
+        Encoding::Primitive(_, name) => {
+            let variant = Ident::new(name, Span::call_site());
+            quote!(#enc::#variant)
+        }
+        Encoding::Int31 => quote!(#enc::Int31),
+        Encoding::Unit => quote!(#enc::Unit),
+        Encoding::BoundedString(max) => quote!(#enc::BoundedString(#max)),
+        Encoding::Bytes => quote!(#enc::Bytes),
+        Encoding::ByteList => split(quote!(#enc::Bytes), quote!(#enc::list(#enc::Uint8))),
+        Encoding::BoundedBytes(max) => split(
+            quote!(#enc::Bytes),
+            quote!(#enc::bounded_list(#max, #enc::Uint8)),
+        ),
+        Encoding::Dynamic(encoding) => {
+            let schema = same(encoding)?;
+            quote!(#enc::dynamic(#schema))
+        }
+        Encoding::BoundedDynamic(max, encoding) => {
+            let schema = same(encoding)?;
+            quote!(#enc::bounded_dynamic(#max, #schema))
+        }
+        Encoding::Sized(size, encoding) => {
+            let schema = same(encoding)?;
+            quote!(#enc::sized(#size, #schema))
+        }
+        Encoding::Bounded(max, encoding) => {
+            let schema = same(encoding)?;
+            quote!(#enc::bounded(#max, #schema))
+        }
+        Encoding::Greedy(encoding) => {
+            let schema = same(encoding)?;
+            quote!(#enc::greedy(#schema))
+        }
+        Encoding::List(encoding) => {
+            let schema = element(encoding)?;
+            quote!(#enc::list(#schema))
+        }
+        Encoding::BoundedList(max, encoding) => {
+            let schema = element(encoding)?;
+            quote!(#enc::bounded_list(#max, #schema))
+        }
+        Encoding::Option(encoding) => {
+            let schema = element(encoding)?;
+            quote!(#enc::option(#schema))
+        }
+        Encoding::OptionField(encoding) => {
+            let schema = element(encoding)?;
+            quote!(#enc::option_field(#schema))
+        }
+        Encoding::Split(json, binary) => split(same(json)?, same(binary)?),
+    })
+}
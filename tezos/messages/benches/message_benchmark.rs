@@ -20,6 +20,7 @@ use crypto::{
 use tezos_encoding::{
     binary_reader::BinaryReader,
     de::from_value as deserialize_from_value,
+    direct,
     encoding::{Encoding, HasEncoding},
 };
 use tezos_messages::p2p::{
@@ -27,7 +28,7 @@ use tezos_messages::p2p::{
     binary_message::{BinaryChunk, BinaryChunkError, BinaryMessage, CONTENT_LENGTH_FIELD_BYTES},
     encoding::metadata::MetadataMessage,
     encoding::operation::Operation,
-    encoding::peer::PeerMessageResponse,
+    encoding::peer::{PeerMessage, PeerMessageResponse},
     encoding::prelude::ConnectionMessage,
 };
 
@@ -80,6 +81,9 @@ pub fn deserialize_benchmark(c: &mut Criterion) {
     c.bench_function("operation_from_bytes", |b| {
         b.iter(|| Operation::from_bytes(black_box(message_bytes.clone())))
     });
+    c.bench_function("from_bytes_direct", |b| {
+        b.iter(|| direct::from_bytes::<Operation>(black_box(&message_bytes)).unwrap())
+    });
     c.bench_function("from_bytes_reader", |b| {
         b.iter(|| {
             BinaryReader::new()
@@ -217,6 +221,44 @@ pub fn decode_stream(c: &mut Criterion) {
             }
         })
     });
+
+    // the same stream decoded through the intermediate form, for comparison
+    for message in &decrypted_messages {
+        let decoded: PeerMessageResponse = direct::from_bytes(message).unwrap();
+        let value = BinaryReader::new()
+            .read(message, PeerMessageResponse::encoding())
+            .unwrap();
+        let value_decoded = deserialize_from_value::<PeerMessageResponse>(&value);
+
+        // tag (0x51) name in the intermediate form does not match the variant name, so it cannot be compared
+        if decoded
+            .messages()
+            .iter()
+            .any(|m| matches!(m, PeerMessage::OperationHashesForBlock(_)))
+        {
+            assert!(
+                value_decoded.is_err(),
+                "intermediate form decoding of operation hashes for block should fail"
+            );
+            continue;
+        }
+
+        assert_eq!(
+            direct::to_bytes(&decoded).unwrap(),
+            direct::to_bytes(&value_decoded.expect("intermediate form decoding failed")).unwrap(),
+            "direct and intermediate form decoding should match"
+        );
+    }
+    c.bench_function("decode_stream_value", |b| {
+        b.iter(|| {
+            for message in decrypted_messages.to_owned() {
+                let value = BinaryReader::new()
+                    .read(message, PeerMessageResponse::encoding())
+                    .unwrap();
+                let _ = deserialize_from_value::<PeerMessageResponse>(&value);
+            }
+        })
+    });
 }
 
 /// simulate_bootstrap_crypto benchmark: creation of PrecomputedKey, generate nonces, decrypt first message, which happens in real communication between two nodes
@@ -4,14 +4,12 @@
 use bytes::{Buf, BufMut};
 use failure::Fail;
 use failure::_core::convert::TryFrom;
-use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crypto::blake2b;
 use crypto::hash::Hash;
-use tezos_encoding::binary_reader::{BinaryReader, BinaryReaderError};
-use tezos_encoding::binary_writer;
-use tezos_encoding::de::from_value as deserialize_from_value;
+use tezos_encoding::binary_reader::BinaryReaderError;
+use tezos_encoding::direct::{self, BinaryRead, BinaryWrite};
 use tezos_encoding::encoding::HasEncoding;
 use tezos_encoding::json_writer::JsonWriter;
 use tezos_encoding::ser;
@@ -187,6 +185,8 @@ pub mod cache {
 ///
 /// Binary messages could be written by a [`MessageWriter`](super::stream::MessageWriter).
 /// To read binary encoding use  [`MessageReader`](super::stream::MessageReader).
+///
+/// Messages are encoded directly (see [tezos_encoding::direct]), without the intermediate form.
 pub trait BinaryMessage: Sized {
     /// Produce bytes from the struct.
     fn as_bytes(&self) -> Result<Vec<u8>, ser::Error>;
@@ -197,7 +197,7 @@ pub trait BinaryMessage: Sized {
 
 impl<T> BinaryMessage for T
 where
-    T: HasEncoding + cache::CachedData + DeserializeOwned + Serialize + Sized,
+    T: cache::CachedData + BinaryRead + BinaryWrite + Sized,
 {
     #[inline]
     fn as_bytes(&self) -> Result<Vec<u8>, ser::Error> {
@@ -209,15 +209,14 @@ where
         }
 
         // if cache not configured or empty, resolve by encoding
-        binary_writer::write(self, &Self::encoding())
+        direct::to_bytes(self)
     }
 
     #[inline]
     fn from_bytes<B: AsRef<[u8]>>(bytes: B) -> Result<Self, BinaryReaderError> {
         let bytes = bytes.as_ref();
 
-        let value = BinaryReader::new().read(bytes, &Self::encoding())?;
-        let mut myself: Self = deserialize_from_value(&value)?;
+        let mut myself: Self = direct::from_bytes(bytes)?;
         if let Some(cache_writer) = myself.cache_writer() {
             cache_writer.put(bytes);
         }
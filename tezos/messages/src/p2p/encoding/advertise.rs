@@ -6,19 +6,20 @@ use std::net::SocketAddr;
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use tezos_encoding::direct::BinaryEncoding;
 
 use super::limits::*;
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct AdvertiseMessage {
     #[get = "pub"]
+    #[encoding(bounded_list(ADVERTISE_ID_LIST_MAX_LENGTH, bounded_string(P2P_POINT_MAX_LENGTH)))]
     id: Vec<String>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -35,12 +36,3 @@ impl AdvertiseMessage {
 }
 
 cached_data!(AdvertiseMessage, body);
-has_encoding!(AdvertiseMessage, ADVERTISE_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "id",
-        Encoding::bounded_list(
-            ADVERTISE_ID_LIST_MAX_LENGTH,
-            Encoding::BoundedString(P2P_POINT_MAX_LENGTH),
-        ),
-    )])
-});
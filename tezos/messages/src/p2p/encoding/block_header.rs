@@ -1,15 +1,12 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::sync::Arc;
-
 use derive_builder::Builder;
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{BlockHash, ContextHash, HashType, OperationListListHash};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding, SchemaType};
-use tezos_encoding::has_encoding;
+use crypto::hash::{BlockHash, ContextHash, OperationListListHash};
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
@@ -19,15 +16,6 @@ use super::limits::{BLOCK_HEADER_MAX_SIZE, GET_BLOCK_HEADERS_MAX_LENGTH};
 pub type Fitness = Vec<Vec<u8>>;
 pub type Level = i32;
 
-pub fn fitness_encoding() -> Encoding {
-    Encoding::Split(Arc::new(|schema_type| match schema_type {
-        SchemaType::Json => Encoding::dynamic(Encoding::list(Encoding::Bytes)),
-        SchemaType::Binary => Encoding::dynamic(Encoding::list(Encoding::dynamic(Encoding::list(
-            Encoding::Uint8,
-        )))),
-    }))
-}
-
 pub fn display_fitness(fitness: &Fitness) -> String {
     fitness
         .iter()
@@ -36,22 +24,17 @@ pub fn display_fitness(fitness: &Fitness) -> String {
         .join("::")
 }
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct BlockHeaderMessage {
     #[get = "pub"]
     block_header: BlockHeader,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(BlockHeaderMessage, body);
-has_encoding!(BlockHeaderMessage, BLOCK_HEADER_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "block_header",
-        BlockHeader::encoding().clone(),
-    )])
-});
 
 impl From<BlockHeader> for BlockHeaderMessage {
     fn from(block_header: BlockHeader) -> Self {
@@ -69,12 +52,14 @@ impl From<BlockHeaderMessage> for BlockHeader {
 }
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct GetBlockHeadersMessage {
     #[get = "pub"]
+    #[encoding(dynamic(bounded_list(GET_BLOCK_HEADERS_MAX_LENGTH)))]
     get_block_headers: Vec<BlockHash>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -88,22 +73,12 @@ impl GetBlockHeadersMessage {
 }
 
 cached_data!(GetBlockHeadersMessage, body);
-has_encoding!(
-    GetBlockHeadersMessage,
-    GET_BLOCK_HEADERS_MESSAGE_ENCODING,
-    {
-        Encoding::Obj(vec![Field::new(
-            "get_block_headers",
-            Encoding::dynamic(Encoding::bounded_list(
-                GET_BLOCK_HEADERS_MAX_LENGTH,
-                Encoding::Hash(HashType::BlockHash),
-            )),
-        )])
-    }
-);
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Builder, Getters, CopyGetters)]
+#[derive(
+    Serialize, Deserialize, BinaryEncoding, PartialEq, Debug, Clone, Builder, Getters, CopyGetters,
+)]
+#[encoding(bounded(BLOCK_HEADER_MAX_SIZE))]
 pub struct BlockHeader {
     #[get_copy = "pub"]
     level: Level,
@@ -112,47 +87,26 @@ pub struct BlockHeader {
     #[get = "pub"]
     predecessor: BlockHash,
     #[get_copy = "pub"]
+    #[encoding(timestamp)]
     timestamp: i64,
     #[get_copy = "pub"]
     validation_pass: u8,
     #[get = "pub"]
     operations_hash: OperationListListHash,
     #[get = "pub"]
+    #[encoding(dynamic(list(dynamic(list(uint8)))))]
     fitness: Fitness,
     #[get = "pub"]
     context: ContextHash,
 
     #[get = "pub"]
+    #[encoding(list(uint8))]
     protocol_data: Vec<u8>,
 
     #[serde(skip_serializing)]
     #[builder(default)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(BlockHeader, body);
-has_encoding!(BlockHeader, BLOCK_HEADER_ENCODING, {
-    Encoding::bounded(
-        BLOCK_HEADER_MAX_SIZE,
-        Encoding::Obj(vec![
-            Field::new("level", Encoding::Int32),
-            Field::new("proto", Encoding::Uint8),
-            Field::new("predecessor", Encoding::Hash(HashType::BlockHash)),
-            Field::new("timestamp", Encoding::Timestamp),
-            Field::new("validation_pass", Encoding::Uint8),
-            Field::new(
-                "operations_hash",
-                Encoding::Hash(HashType::OperationListListHash),
-            ),
-            Field::new("fitness", fitness_encoding()),
-            Field::new("context", Encoding::Hash(HashType::ContextHash)),
-            Field::new(
-                "protocol_data",
-                Encoding::Split(Arc::new(|schema_type| match schema_type {
-                    SchemaType::Json => Encoding::Bytes,
-                    SchemaType::Binary => Encoding::list(Encoding::Uint8),
-                })),
-            ),
-        ]),
-    )
-});
@@ -4,9 +4,8 @@
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{ChainId, HashType};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use crypto::hash::ChainId;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
@@ -15,15 +14,17 @@ use super::block_header::BlockHeader;
 use super::limits::BLOCK_HEADER_MAX_SIZE;
 use super::mempool::Mempool;
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct CurrentHeadMessage {
     #[get = "pub"]
     chain_id: ChainId,
     #[get = "pub"]
+    #[encoding(bounded_dynamic(BLOCK_HEADER_MAX_SIZE))]
     current_block_header: BlockHeader,
     #[get = "pub"]
     current_mempool: Mempool,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -43,24 +44,15 @@ impl CurrentHeadMessage {
 }
 
 cached_data!(CurrentHeadMessage, body);
-has_encoding!(CurrentHeadMessage, CURRENT_HEAD_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("chain_id", Encoding::Hash(HashType::ChainId)),
-        Field::new(
-            "current_block_header",
-            Encoding::bounded_dynamic(BLOCK_HEADER_MAX_SIZE, BlockHeader::encoding().clone()),
-        ),
-        Field::new("current_mempool", Mempool::encoding().clone()),
-    ])
-});
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct GetCurrentHeadMessage {
     #[get = "pub"]
     chain_id: ChainId,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -74,9 +66,3 @@ impl GetCurrentHeadMessage {
 }
 
 cached_data!(GetCurrentHeadMessage, body);
-has_encoding!(GetCurrentHeadMessage, GET_CURRENT_HEAD_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "chain_id",
-        Encoding::Hash(HashType::ChainId),
-    )])
-});
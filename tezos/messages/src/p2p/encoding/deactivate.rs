@@ -4,19 +4,19 @@
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{ChainId, HashType};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use crypto::hash::ChainId;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct DeactivateMessage {
     #[get = "pub"]
     deactivate: ChainId,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -30,9 +30,3 @@ impl DeactivateMessage {
 }
 
 cached_data!(DeactivateMessage, body);
-has_encoding!(DeactivateMessage, DEACTIVATE_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "deactivate",
-        Encoding::Hash(HashType::ChainId),
-    )])
-});
@@ -4,22 +4,25 @@
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{HashType, OperationHash};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use crypto::hash::OperationHash;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
 use super::limits::MEMPOOL_MAX_SIZE;
 
-#[derive(Clone, Serialize, Deserialize, Debug, Default, Getters)]
+#[derive(Clone, Serialize, Deserialize, BinaryEncoding, Debug, Default, Getters)]
+#[encoding(bounded(MEMPOOL_MAX_SIZE))]
 pub struct Mempool {
     #[get = "pub"]
+    #[encoding(dynamic(list))]
     known_valid: Vec<OperationHash>,
     #[get = "pub"]
+    #[encoding(dynamic(dynamic(list)))]
     pending: Vec<OperationHash>,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -38,20 +41,3 @@ impl Mempool {
 }
 
 cached_data!(Mempool, body);
-has_encoding!(Mempool, MEMPOOL_ENCODING, {
-    Encoding::bounded(
-        MEMPOOL_MAX_SIZE,
-        Encoding::Obj(vec![
-            Field::new(
-                "known_valid",
-                Encoding::dynamic(Encoding::list(Encoding::Hash(HashType::OperationHash))),
-            ),
-            Field::new(
-                "pending",
-                Encoding::dynamic(Encoding::dynamic(Encoding::list(Encoding::Hash(
-                    HashType::OperationHash,
-                )))),
-            ),
-        ]),
-    )
-});
@@ -1,7 +1,7 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::{convert::TryFrom, sync::Arc};
+use std::convert::TryFrom;
 
 use failure::Fail;
 use getset::Getters;
@@ -10,29 +10,26 @@ use serde::{Deserialize, Serialize};
 
 use crypto::{
     base58::FromBase58CheckError,
-    hash::{BlockHash, HashType, OperationHash},
+    hash::{BlockHash, OperationHash},
 };
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding, SchemaType};
-use tezos_encoding::has_encoding;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
 use super::limits::{GET_OPERATIONS_MAX_LENGTH, OPERATION_MAX_SIZE};
 
-#[derive(Serialize, Deserialize, PartialEq, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, PartialEq, Debug, Getters, Clone)]
 pub struct OperationMessage {
     #[get = "pub"]
     operation: Operation,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(OperationMessage, body);
-has_encoding!(OperationMessage, OPERATION_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new("operation", Operation::encoding().clone())])
-});
 
 impl From<Operation> for OperationMessage {
     fn from(operation: Operation) -> Self {
@@ -50,12 +47,14 @@ impl From<OperationMessage> for Operation {
 }
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Clone, Serialize, Deserialize, BinaryEncoding, PartialEq, Debug)]
 pub struct Operation {
     branch: BlockHash,
+    #[encoding(bounded_list(OPERATION_MAX_SIZE, uint8))]
     data: Vec<u8>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -101,18 +100,6 @@ impl TryFrom<DecodedOperation> for Operation {
 }
 
 cached_data!(Operation, body);
-has_encoding!(Operation, OPERATION_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("branch", Encoding::Hash(HashType::BlockHash)),
-        Field::new(
-            "data",
-            Encoding::Split(Arc::new(|schema_type| match schema_type {
-                SchemaType::Json => Encoding::Bytes,
-                SchemaType::Binary => Encoding::bounded_list(OPERATION_MAX_SIZE, Encoding::Uint8),
-            })),
-        ),
-    ])
-});
 
 // -----------------------------------------------------------------------------------------------
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -131,12 +118,14 @@ impl From<Operation> for DecodedOperation {
 }
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct GetOperationsMessage {
     #[get = "pub"]
+    #[encoding(dynamic(bounded_list(GET_OPERATIONS_MAX_LENGTH)))]
     get_operations: Vec<OperationHash>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -150,12 +139,3 @@ impl GetOperationsMessage {
 }
 
 cached_data!(GetOperationsMessage, body);
-has_encoding!(GetOperationsMessage, GET_OPERATION_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "get_operations",
-        Encoding::dynamic(Encoding::bounded_list(
-            GET_OPERATIONS_MAX_LENGTH,
-            Encoding::Hash(HashType::OperationHash),
-        )),
-    )])
-});
@@ -4,20 +4,21 @@
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{BlockHash, HashType, OperationHash};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use crypto::hash::{BlockHash, OperationHash};
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 use crate::p2p::encoding::prelude::Path;
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct GetOperationHashesForBlocksMessage {
     #[get = "pub"]
+    #[encoding(dynamic(list))]
     get_operation_hashes_for_blocks: Vec<OperationHashesForBlock>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -31,28 +32,20 @@ impl GetOperationHashesForBlocksMessage {
 }
 
 cached_data!(GetOperationHashesForBlocksMessage, body);
-has_encoding!(
-    GetOperationHashesForBlocksMessage,
-    GET_OPERATION_HASHES_FOR_BLOCKS_MESSAGE_ENCODING,
-    {
-        Encoding::Obj(vec![Field::new(
-            "get_operation_hashes_for_blocks",
-            Encoding::dynamic(Encoding::list(OperationHashesForBlock::encoding().clone())),
-        )])
-    }
-);
 
 // ------------------ Response ------------------ //
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct OperationHashesForBlocksMessage {
     #[get = "pub"]
     operation_hashes_for_block: OperationHashesForBlock,
     #[get = "pub"]
     operation_hashes_path: Path,
     #[get = "pub"]
+    #[encoding(list(dynamic))]
     operation_hashes: Vec<OperationHash>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -72,29 +65,9 @@ impl OperationHashesForBlocksMessage {
 }
 
 cached_data!(OperationHashesForBlocksMessage, body);
-has_encoding!(
-    OperationHashesForBlocksMessage,
-    OPERATION_HASHES_FOR_BLOCKS_MESSAGE_ENCODING,
-    {
-        Encoding::Obj(vec![
-            Field::new(
-                "operation_hashes_for_block",
-                OperationHashesForBlock::encoding().clone(),
-            ),
-            Field::new(
-                "operation_hashes_path",
-                super::operations_for_blocks::PathCodec::get_encoding(),
-            ),
-            Field::new(
-                "operation_hashes",
-                Encoding::list(Encoding::dynamic(Encoding::list(Encoding::Uint8))),
-            ),
-        ])
-    }
-);
 
 // ------------------ Inner message for operation hashes message ------------------ //
-#[derive(Serialize, Deserialize, Debug, Getters, CopyGetters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, CopyGetters, Clone)]
 pub struct OperationHashesForBlock {
     #[get = "pub"]
     hash: BlockHash,
@@ -102,6 +75,7 @@ pub struct OperationHashesForBlock {
     validation_pass: i8,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -116,13 +90,3 @@ impl OperationHashesForBlock {
 }
 
 cached_data!(OperationHashesForBlock, body);
-has_encoding!(
-    OperationHashesForBlock,
-    OPERATION_HASHES_FOR_BLOCK_ENCODING,
-    {
-        Encoding::Obj(vec![
-            Field::new("hash", Encoding::Hash(HashType::BlockHash)),
-            Field::new("validation_pass", Encoding::Int8),
-        ])
-    }
-);
@@ -9,9 +9,10 @@ use bytes::Buf;
 use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{BlockHash, Hash, HashType};
+use crypto::hash::{BlockHash, Hash, HashType, OperationListListHash};
 use tezos_encoding::binary_reader::BinaryReaderError;
-use tezos_encoding::encoding::{CustomCodec, Encoding, Field, HasEncoding};
+use tezos_encoding::direct::{BinaryEncoding, BinaryRead, BinaryWrite};
+use tezos_encoding::encoding::{CustomCodec, Encoding, HasEncoding};
 use tezos_encoding::ser::Error;
 use tezos_encoding::types::Value;
 use tezos_encoding::{has_encoding, safe};
@@ -36,13 +37,14 @@ use super::limits::{GET_OPERATIONS_FOR_BLOCKS_MAX_LENGTH, OPERATION_LIST_MAX_SIZ
 /// TODO: Implement mechanism for updating this, when Tezos implements this.
 pub const MAX_PASS_MERKLE_DEPTH: Option<usize> = Some(3);
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, CopyGetters, Getters)]
+#[derive(Clone, Serialize, Deserialize, BinaryEncoding, PartialEq, Debug, CopyGetters, Getters)]
 pub struct OperationsForBlock {
     #[get = "pub"]
     hash: BlockHash,
     #[get_copy = "pub"]
     validation_pass: i8,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -63,22 +65,18 @@ impl OperationsForBlock {
 }
 
 cached_data!(OperationsForBlock, body);
-has_encoding!(OperationsForBlock, OPERATIONS_FOR_BLOCK_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("hash", Encoding::Hash(HashType::BlockHash)),
-        Field::new("validation_pass", Encoding::Int8),
-    ])
-});
 // -----------------------------------------------------------------------------------------------
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug, Getters)]
+#[derive(Clone, Serialize, Deserialize, BinaryEncoding, PartialEq, Debug, Getters)]
 pub struct OperationsForBlocksMessage {
     #[get = "pub"]
     operations_for_block: OperationsForBlock,
     #[get = "pub"]
     operation_hashes_path: Path,
     #[get = "pub"]
+    #[encoding(bounded(OPERATION_LIST_MAX_SIZE, list(dynamic)))]
     operations: Vec<Operation>,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -98,26 +96,6 @@ impl OperationsForBlocksMessage {
 }
 
 cached_data!(OperationsForBlocksMessage, body);
-has_encoding!(
-    OperationsForBlocksMessage,
-    OPERATIONS_FOR_BLOCKS_MESSAGE_ENCODING,
-    {
-        Encoding::Obj(vec![
-            Field::new(
-                "operations_for_block",
-                OperationsForBlock::encoding().clone(),
-            ),
-            Field::new("operation_hashes_path", PathCodec::get_encoding()),
-            Field::new(
-                "operations",
-                Encoding::bounded(
-                    OPERATION_LIST_MAX_SIZE,
-                    Encoding::list(Encoding::dynamic(Operation::encoding().clone())),
-                ),
-            ),
-        ])
-    }
-);
 
 impl From<OperationsForBlocksMessage> for Vec<Operation> {
     fn from(msg: OperationsForBlocksMessage) -> Self {
@@ -185,6 +163,8 @@ impl Path {
     }
 }
 
+has_encoding!(Path, PATH_ENCODING, { PathCodec::get_encoding() });
+
 /// Manual serializization ensures that path depth does not exceed max value
 impl Serialize for Path {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -213,11 +193,97 @@ impl Serialize for Path {
     }
 }
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+/// Direct binary form of [Path], same as produced by [PathCodec]
+impl BinaryRead for Path {
+    fn read_binary(buf: &mut &[u8]) -> Result<Self, BinaryReaderError> {
+        enum PathNode {
+            Left,
+            Right(Hash),
+        }
+
+        let mut nodes = Vec::new();
+        loop {
+            match u8::read_binary(buf)? {
+                0xF0 => {
+                    nodes.push(PathNode::Left);
+                }
+                0x0F => {
+                    nodes.push(PathNode::Right(OperationListListHash::read_binary(buf)?.0));
+                }
+                0x00 => {
+                    let mut result = Vec::with_capacity(nodes.len());
+                    for node in nodes.into_iter().rev() {
+                        match node {
+                            PathNode::Left => {
+                                result.push(PathItem::left(
+                                    OperationListListHash::read_binary(buf)?.0,
+                                ));
+                            }
+                            PathNode::Right(hash) => {
+                                result.push(PathItem::right(hash));
+                            }
+                        }
+                    }
+                    result.reverse();
+                    return Ok(Path(result));
+                }
+                t => {
+                    return Err(BinaryReaderError::UnsupportedTag { tag: t as u16 });
+                }
+            }
+
+            if let Some(max) = MAX_PASS_MERKLE_DEPTH {
+                if nodes.len() > max {
+                    return Err(BinaryReaderError::EncodingBoundaryExceeded {
+                        name: "Path".to_string(),
+                        boundary: max,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl BinaryWrite for Path {
+    fn write_binary(&self, data: &mut Vec<u8>) -> Result<(), Error> {
+        if let Some(max) = MAX_PASS_MERKLE_DEPTH {
+            if self.0.len() > max {
+                use serde::ser::Error;
+                return Err(Error::custom(format!(
+                    "Path size exceedes its boundary {} for encoding",
+                    max
+                )));
+            }
+        }
+
+        let mut tails = VecDeque::new();
+        for path_item in &self.0 {
+            match path_item {
+                PathItem::Left(left) => {
+                    data.push(0xF0);
+                    tails.push_front(left.right());
+                }
+                PathItem::Right(right) => {
+                    data.push(0x0F);
+                    data.extend_from_slice(right.left());
+                }
+            }
+        }
+        data.push(0x00);
+        tails
+            .into_iter()
+            .for_each(|hash| data.extend_from_slice(hash));
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct GetOperationsForBlocksMessage {
     #[get = "pub"]
+    #[encoding(dynamic(bounded_list(GET_OPERATIONS_FOR_BLOCKS_MAX_LENGTH)))]
     get_operations_for_blocks: Vec<OperationsForBlock>,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -231,19 +297,6 @@ impl GetOperationsForBlocksMessage {
 }
 
 cached_data!(GetOperationsForBlocksMessage, body);
-has_encoding!(
-    GetOperationsForBlocksMessage,
-    GET_OPERATIONS_FOR_BLOCKS_MESSAGE_ENCODING,
-    {
-        Encoding::Obj(vec![Field::new(
-            "get_operations_for_blocks",
-            Encoding::dynamic(Encoding::bounded_list(
-                GET_OPERATIONS_FOR_BLOCKS_MAX_LENGTH,
-                OperationsForBlock::encoding().clone(),
-            )),
-        )])
-    }
-);
 
 // ---------------------------------------
 
@@ -3,55 +3,49 @@
 
 use serde::{Deserialize, Serialize};
 
-use crypto::hash::{HashType, ProtocolHash};
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use crypto::hash::ProtocolHash;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
 use super::limits::{GET_PROTOCOLS_MAX_LENGTH, PROTOCOL_COMPONENT_MAX_SIZE};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Clone)]
 pub struct ProtocolMessage {
     protocol: Protocol,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(ProtocolMessage, body);
-has_encoding!(ProtocolMessage, PROTOCOL_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new("protocol", Protocol::encoding().clone())])
-});
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Clone)]
 pub struct Component {
     name: String,
+    #[encoding(option_field)]
     interface: Option<String>,
     implementation: String,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(Component, body);
-has_encoding!(Component, COMPONENT_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("name", Encoding::String),
-        Field::new("interface", Encoding::option_field(Encoding::String)),
-        Field::new("implementation", Encoding::String),
-    ])
-});
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Clone)]
 pub struct Protocol {
     expected_env_version: i16,
+    #[encoding(bounded_dynamic(PROTOCOL_COMPONENT_MAX_SIZE, list))]
     components: Vec<Component>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -66,35 +60,16 @@ impl Protocol {
 }
 
 cached_data!(Protocol, body);
-has_encoding!(Protocol, PROTOCOL_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("expected_env_version", Encoding::Int16),
-        Field::new(
-            "components",
-            Encoding::bounded_dynamic(
-                PROTOCOL_COMPONENT_MAX_SIZE,
-                Encoding::list(Component::encoding().clone()),
-            ),
-        ),
-    ])
-});
 
 // -----------------------------------------------------------------------------------------------
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Clone)]
 pub struct GetProtocolsMessage {
+    #[encoding(dynamic(bounded_list(GET_PROTOCOLS_MAX_LENGTH)))]
     get_protocols: Vec<ProtocolHash>,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
 cached_data!(GetProtocolsMessage, body);
-has_encoding!(GetProtocolsMessage, GET_PROTOCOLS_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![Field::new(
-        "get_protocols",
-        Encoding::dynamic(Encoding::bounded_list(
-            GET_PROTOCOLS_MAX_LENGTH,
-            Encoding::Hash(HashType::ProtocolHash),
-        )),
-    )])
-});
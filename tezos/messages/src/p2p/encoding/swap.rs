@@ -4,22 +4,24 @@
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
 use super::limits::{P2P_POINT_MAX_LENGTH, PEER_ID_LENGTH};
 
-#[derive(Serialize, Deserialize, Debug, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Debug, Getters, Clone)]
 pub struct SwapMessage {
     #[get = "pub"]
+    #[encoding(bounded_string(P2P_POINT_MAX_LENGTH))]
     point: String,
     #[get = "pub"]
+    #[encoding(bounded_string(PEER_ID_LENGTH))]
     peer_id: String,
 
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -34,9 +36,3 @@ impl SwapMessage {
 }
 
 cached_data!(SwapMessage, body);
-has_encoding!(SwapMessage, SWAP_MESSAGE_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("point", Encoding::BoundedString(P2P_POINT_MAX_LENGTH)),
-        Field::new("peer_id", Encoding::BoundedString(PEER_ID_LENGTH)),
-    ])
-});
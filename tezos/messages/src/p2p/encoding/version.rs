@@ -6,14 +6,13 @@ use std::fmt;
 use getset::Getters;
 use serde::{Deserialize, Serialize};
 
-use tezos_encoding::encoding::{Encoding, Field, HasEncoding};
-use tezos_encoding::has_encoding;
+use tezos_encoding::direct::BinaryEncoding;
 
 use crate::cached_data;
 use crate::p2p::binary_message::cache::BinaryDataCache;
 
 /// Holds informations about chain compatibility, features compatibility...
-#[derive(Serialize, Deserialize, Getters, Clone)]
+#[derive(Serialize, Deserialize, BinaryEncoding, Getters, Clone)]
 pub struct NetworkVersion {
     #[get = "pub"]
     chain_name: String,
@@ -22,6 +21,7 @@ pub struct NetworkVersion {
     #[get = "pub"]
     p2p_version: u16,
     #[serde(skip_serializing)]
+    #[encoding(skip)]
     body: BinaryDataCache,
 }
 
@@ -51,13 +51,6 @@ impl NetworkVersion {
 }
 
 cached_data!(NetworkVersion, body);
-has_encoding!(NetworkVersion, NETWORK_VERSION_ENCODING, {
-    Encoding::Obj(vec![
-        Field::new("chain_name", Encoding::String),
-        Field::new("distributed_db_version", Encoding::Uint16),
-        Field::new("p2p_version", Encoding::Uint16),
-    ])
-});
 
 impl Eq for NetworkVersion {}
 
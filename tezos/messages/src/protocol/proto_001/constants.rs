@@ -6,6 +6,7 @@ use getset::{CopyGetters, Getters};
 use serde::{Deserialize, Serialize};
 
 use tezos_encoding::{
+    binary_encoding_by_value,
     encoding::{Encoding, Field, HasEncoding},
     has_encoding,
     types::BigInt,
@@ -263,6 +264,7 @@ impl ToRpcJsonMap for ParametricConstants {
 }
 
 non_cached_data!(ParametricConstants);
+binary_encoding_by_value!(ParametricConstants);
 has_encoding!(ParametricConstants, PARAMETRIC_CONSTANTS_ENCODING, {
     Encoding::Obj(vec![
         Field::new("preserved_cycles", Encoding::option_field(Encoding::Uint8)),
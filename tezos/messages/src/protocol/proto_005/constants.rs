@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
 use tezos_encoding::{
+    binary_encoding_by_value,
     encoding::{Encoding, Field, HasEncoding},
     has_encoding,
     types::BigInt,
@@ -191,6 +192,7 @@ impl ToRpcJsonMap for ParametricConstants {
 }
 
 non_cached_data!(ParametricConstants);
+binary_encoding_by_value!(ParametricConstants);
 has_encoding!(ParametricConstants, PARAMETRIC_CONSTANTS_ENCODING, {
     Encoding::Obj(vec![
         Field::new("preserved_cycles", Encoding::Uint8),
@@ -5,6 +5,7 @@ use getset::Getters;
 use serde::{Deserialize, Serialize};
 
 use tezos_encoding::{
+    binary_encoding_by_value,
     encoding::{Encoding, Field, HasEncoding},
     has_encoding,
     types::BigInt,
@@ -25,6 +26,7 @@ impl Counter {
 }
 
 non_cached_data!(Counter);
+binary_encoding_by_value!(Counter);
 has_encoding!(Counter, COUNTER_ENCODING, {
     Encoding::Obj(vec![Field::new("counter", Encoding::Z)])
 });
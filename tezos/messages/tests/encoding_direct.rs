@@ -0,0 +1,423 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Checks that direct binary encoding (`#[derive(BinaryEncoding)]`) produces the same bytes
+//! as encoding through the intermediate form, on randomly generated messages.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use failure::Error;
+use rand::distributions::Alphanumeric;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crypto::hash::{BlockHash, ChainId, ContextHash, OperationHash, OperationListListHash};
+use tezos_encoding::{
+    binary_reader::BinaryReader,
+    binary_writer,
+    de::from_value as deserialize_from_value,
+    direct::{self, BinaryRead, BinaryWrite},
+    encoding::HasEncoding,
+};
+use tezos_messages::p2p::binary_message::BinaryMessage;
+use tezos_messages::p2p::encoding::ack::{NackInfo, NackMotive};
+use tezos_messages::p2p::encoding::block_header::Fitness;
+use tezos_messages::p2p::encoding::limits::*;
+use tezos_messages::p2p::encoding::operation_hashes_for_blocks::OperationHashesForBlock;
+use tezos_messages::p2p::encoding::operations_for_blocks::PathItem;
+use tezos_messages::p2p::encoding::prelude::*;
+
+const ITERATIONS: usize = 100;
+
+/// Env variable, which sets the seed of random generator, so the failed test can be reproduced
+const SEED_ENV_VAR: &str = "ENCODING_DIRECT_SEED";
+
+/// Prints the seed, if the test panics
+struct SeedReporter(u64);
+
+impl Drop for SeedReporter {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("Test failed, reproduce it with {}={}", SEED_ENV_VAR, self.0);
+        }
+    }
+}
+
+/// Runs the test with seeded random generator (random seed, if not set by [SEED_ENV_VAR]), seed is printed on failure
+fn run_seeded<F>(test: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut StdRng) -> Result<(), Error>,
+{
+    let seed = match std::env::var(SEED_ENV_VAR) {
+        Ok(seed) => seed.parse()?,
+        Err(_) => thread_rng().gen(),
+    };
+    let _reporter = SeedReporter(seed);
+    test(&mut StdRng::seed_from_u64(seed)).map_err(|e| {
+        eprintln!("Test failed, reproduce it with {}={}", SEED_ENV_VAR, seed);
+        e
+    })
+}
+
+/// Asserts that both encodings produce the same bytes and that both decoders accept them.
+fn assert_same_encoding<T>(message: &T) -> Result<(), Error>
+where
+    T: BinaryRead + BinaryWrite + HasEncoding + Serialize + DeserializeOwned,
+{
+    let direct_bytes = direct::to_bytes(message)?;
+    let value_bytes = binary_writer::write(message, T::encoding())?;
+    assert_eq!(direct_bytes, value_bytes);
+
+    let direct_decoded: T = direct::from_bytes(&direct_bytes)?;
+    assert_eq!(direct::to_bytes(&direct_decoded)?, direct_bytes);
+
+    let value = BinaryReader::new().read(&value_bytes, T::encoding())?;
+    let value_decoded: T = deserialize_from_value(&value)?;
+    assert_eq!(direct::to_bytes(&value_decoded)?, direct_bytes);
+
+    Ok(())
+}
+
+fn random_bytes(rng: &mut StdRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+fn random_string(rng: &mut StdRng, max_len: usize) -> String {
+    let len = rng.gen_range(0, max_len + 1);
+    rng.sample_iter(&Alphanumeric).take(len).collect()
+}
+
+fn random_chain_id(rng: &mut StdRng) -> ChainId {
+    ChainId(random_bytes(rng, 4))
+}
+
+fn random_block_hash(rng: &mut StdRng) -> BlockHash {
+    BlockHash(random_bytes(rng, 32))
+}
+
+fn random_operation_hashes(rng: &mut StdRng, max_len: usize) -> Vec<OperationHash> {
+    let len = rng.gen_range(0, max_len + 1);
+    (0..len)
+        .map(|_| OperationHash(random_bytes(rng, 32)))
+        .collect()
+}
+
+fn random_block_hashes(rng: &mut StdRng, max_len: usize) -> Vec<BlockHash> {
+    let len = rng.gen_range(0, max_len + 1);
+    (0..len).map(|_| random_block_hash(rng)).collect()
+}
+
+fn random_block_header(rng: &mut StdRng) -> Result<BlockHeader, Error> {
+    let fitness: Fitness = (0..rng.gen_range(0, 4))
+        .map(|_| {
+            let len = rng.gen_range(0, 9);
+            random_bytes(rng, len)
+        })
+        .collect();
+    let protocol_data_len = rng.gen_range(0, 256);
+    BlockHeaderBuilder::default()
+        .level(rng.gen())
+        .proto(rng.gen())
+        .predecessor(random_block_hash(rng))
+        .timestamp(rng.gen_range(0, i32::MAX as i64))
+        .validation_pass(rng.gen())
+        .operations_hash(OperationListListHash(random_bytes(rng, 32)))
+        .fitness(fitness)
+        .context(ContextHash(random_bytes(rng, 32)))
+        .protocol_data(random_bytes(rng, protocol_data_len))
+        .build()
+        .map_err(failure::err_msg)
+}
+
+fn random_mempool(rng: &mut StdRng) -> Mempool {
+    Mempool::new(
+        random_operation_hashes(rng, 10),
+        random_operation_hashes(rng, 10),
+    )
+}
+
+fn random_operation(rng: &mut StdRng) -> Result<Operation, Error> {
+    let data_len = rng.gen_range(0, 256);
+    let mut bytes = random_bytes(rng, 32);
+    bytes.extend(random_bytes(rng, data_len));
+    Ok(Operation::from_bytes(bytes)?)
+}
+
+fn random_path(rng: &mut StdRng) -> Path {
+    random_path_with_depth(rng, MAX_PASS_MERKLE_DEPTH.unwrap_or(8))
+}
+
+fn random_path_with_depth(rng: &mut StdRng, max_depth: usize) -> Path {
+    let depth = rng.gen_range(0, max_depth + 1);
+    Path(
+        (0..depth)
+            .map(|_| {
+                let hash = random_bytes(rng, 32);
+                if rng.gen() {
+                    PathItem::left(hash)
+                } else {
+                    PathItem::right(hash)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Random bytes resembling binary form of [Path], valid or not
+fn random_path_bytes(rng: &mut StdRng) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut lefts = 0;
+    for _ in 0..rng.gen_range(0, MAX_PASS_MERKLE_DEPTH.unwrap_or(8) + 3) {
+        match rng.gen_range(0, 8) {
+            0 => bytes.push(rng.gen()),
+            1..=3 => {
+                bytes.push(0xF0);
+                lefts += 1;
+            }
+            _ => {
+                bytes.push(0x0F);
+                bytes.extend(random_bytes(rng, 32));
+            }
+        }
+    }
+    if rng.gen_range(0, 8) > 0 {
+        bytes.push(0x00);
+        for _ in 0..rng.gen_range(0, lefts + 2) {
+            bytes.extend(random_bytes(rng, 32));
+        }
+    }
+    if rng.gen_range(0, 8) == 0 && !bytes.is_empty() {
+        let len = rng.gen_range(0, bytes.len());
+        bytes.truncate(len);
+    }
+    bytes
+}
+
+fn put_string(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend(&(value.len() as u32).to_be_bytes());
+    bytes.extend(value.as_bytes());
+}
+
+fn random_protocol_bytes(rng: &mut StdRng) -> Vec<u8> {
+    let mut components = vec![];
+    for _ in 0..rng.gen_range(0, 4) {
+        put_string(&mut components, &random_string(rng, 16));
+        if rng.gen() {
+            components.push(0xFF);
+            put_string(&mut components, &random_string(rng, 64));
+        } else {
+            components.push(0x00);
+        }
+        put_string(&mut components, &random_string(rng, 64));
+    }
+    let mut bytes = rng.gen::<i16>().to_be_bytes().to_vec();
+    bytes.extend(&(components.len() as u32).to_be_bytes());
+    bytes.extend(components);
+    bytes
+}
+
+fn random_get_protocols_bytes(rng: &mut StdRng) -> Vec<u8> {
+    let len = rng.gen_range(0, GET_PROTOCOLS_MAX_LENGTH + 1);
+    let mut bytes = ((len * 32) as u32).to_be_bytes().to_vec();
+    bytes.extend(random_bytes(rng, len * 32));
+    bytes
+}
+
+fn random_peer_message(rng: &mut StdRng) -> Result<PeerMessage, Error> {
+    let message = match rng.gen_range(0, 20) {
+        0 => PeerMessage::Disconnect,
+        1 => PeerMessage::Bootstrap,
+        2 => {
+            let addresses = (0..rng.gen_range(0, 10))
+                .map(|_| SocketAddr::new(IpAddr::V4(Ipv4Addr::from(rng.gen::<u32>())), rng.gen()))
+                .collect::<Vec<_>>();
+            AdvertiseMessage::new(&addresses).into()
+        }
+        3 => PeerMessage::SwapRequest(SwapMessage::new(
+            random_string(rng, P2P_POINT_MAX_LENGTH),
+            random_string(rng, PEER_ID_LENGTH),
+        )),
+        4 => PeerMessage::SwapAck(SwapMessage::new(
+            random_string(rng, P2P_POINT_MAX_LENGTH),
+            random_string(rng, PEER_ID_LENGTH),
+        )),
+        5 => GetCurrentBranchMessage::new(random_chain_id(rng)).into(),
+        6 => {
+            let current_branch =
+                CurrentBranch::new(random_block_header(rng)?, random_block_hashes(rng, 20));
+            CurrentBranchMessage::new(random_chain_id(rng), current_branch).into()
+        }
+        7 => PeerMessage::Deactivate(DeactivateMessage::new(random_chain_id(rng))),
+        8 => GetCurrentHeadMessage::new(random_chain_id(rng)).into(),
+        9 => CurrentHeadMessage::new(
+            random_chain_id(rng),
+            random_block_header(rng)?,
+            random_mempool(rng),
+        )
+        .into(),
+        10 => GetBlockHeadersMessage::new(random_block_hashes(rng, GET_BLOCK_HEADERS_MAX_LENGTH))
+            .into(),
+        11 => BlockHeaderMessage::from(random_block_header(rng)?).into(),
+        12 => GetOperationsMessage::new(random_operation_hashes(rng, GET_OPERATIONS_MAX_LENGTH))
+            .into(),
+        13 => OperationMessage::from(random_operation(rng)?).into(),
+        14 => PeerMessage::GetProtocols(GetProtocolsMessage::from_bytes(
+            random_get_protocols_bytes(rng),
+        )?),
+        15 => PeerMessage::Protocol(ProtocolMessage::from_bytes(random_protocol_bytes(rng))?),
+        16 => {
+            let blocks = (0..rng.gen_range(0, 10))
+                .map(|_| OperationHashesForBlock::new(random_block_hash(rng), rng.gen_range(0, 4)))
+                .collect();
+            PeerMessage::GetOperationHashesForBlocks(GetOperationHashesForBlocksMessage::new(
+                blocks,
+            ))
+        }
+        17 => {
+            let blocks = (0..rng.gen_range(0, GET_OPERATIONS_FOR_BLOCKS_MAX_LENGTH + 1))
+                .map(|_| OperationsForBlock::new(random_block_hash(rng), rng.gen_range(0, 4)))
+                .collect();
+            GetOperationsForBlocksMessage::new(blocks).into()
+        }
+        18 => PeerMessage::OperationHashesForBlock(random_operation_hashes_for_blocks(rng)),
+        _ => {
+            let operations = (0..rng.gen_range(0, 4))
+                .map(|_| random_operation(rng))
+                .collect::<Result<Vec<_>, _>>()?;
+            OperationsForBlocksMessage::new(
+                OperationsForBlock::new(random_block_hash(rng), rng.gen_range(0, 4)),
+                random_path(rng),
+                operations,
+            )
+            .into()
+        }
+    };
+    Ok(message)
+}
+
+fn random_operation_hashes_for_blocks(rng: &mut StdRng) -> OperationHashesForBlocksMessage {
+    OperationHashesForBlocksMessage::new(
+        OperationHashesForBlock::new(random_block_hash(rng), rng.gen_range(0, 4)),
+        random_path(rng),
+        random_operation_hashes(rng, 10),
+    )
+}
+
+#[test]
+fn can_encode_connection_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS {
+            let mut bytes = rng.gen::<u16>().to_be_bytes().to_vec();
+            bytes.extend(random_bytes(rng, 32 + 24 + 24));
+            put_string(&mut bytes, &random_string(rng, 32));
+            bytes.extend(&rng.gen::<u16>().to_be_bytes());
+            bytes.extend(&rng.gen::<u16>().to_be_bytes());
+            assert_same_encoding(&ConnectionMessage::from_bytes(bytes)?)?;
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_encode_metadata_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS {
+            assert_same_encoding(&MetadataMessage::new(rng.gen(), rng.gen()))?;
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_encode_ack_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        assert_same_encoding(&AckMessage::Ack)?;
+        assert_same_encoding(&AckMessage::NackV0)?;
+        for _ in 0..ITERATIONS {
+            let motive = match rng.gen_range(0, 6) {
+                0 => NackMotive::NoMotive,
+                1 => NackMotive::TooManyConnections,
+                2 => NackMotive::UnknownChainName,
+                3 => NackMotive::DeprecatedP2pVersion,
+                4 => NackMotive::DeprecatedDistributedDbVersion,
+                _ => NackMotive::AlreadyConnected,
+            };
+            let peers = (0..rng.gen_range(0, 10))
+                .map(|_| random_string(rng, P2P_POINT_MAX_LENGTH))
+                .collect::<Vec<_>>();
+            assert_same_encoding(&AckMessage::Nack(NackInfo::new(motive, &peers)))?;
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_encode_peer_messages_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS {
+            let messages = (0..rng.gen_range(1, 4))
+                .map(|_| random_peer_message(rng))
+                .collect::<Result<Vec<_>, _>>()?;
+            for message in messages {
+                assert_same_encoding(&PeerMessageResponse::from(message))?;
+            }
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_encode_operation_hashes_for_blocks_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS {
+            assert_same_encoding(&random_operation_hashes_for_blocks(rng))?;
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_encode_path_directly() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS {
+            assert_same_encoding(&random_path(rng))?;
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_reject_too_deep_path_same_as_path_codec() -> Result<(), Error> {
+    run_seeded(|rng| {
+        let max_depth = MAX_PASS_MERKLE_DEPTH.unwrap_or(8);
+        for _ in 0..ITERATIONS {
+            let path = random_path_with_depth(rng, max_depth + 2);
+            let direct_bytes = direct::to_bytes(&path);
+            let value_bytes = binary_writer::write(&path, Path::encoding());
+            assert_eq!(direct_bytes.is_ok(), path.0.len() <= max_depth);
+            assert_eq!(direct_bytes.ok(), value_bytes.ok());
+        }
+        Ok(())
+    })
+}
+
+#[test]
+fn can_decode_path_same_as_path_codec() -> Result<(), Error> {
+    run_seeded(|rng| {
+        for _ in 0..ITERATIONS * 10 {
+            let bytes = random_path_bytes(rng);
+            let direct_decoded = direct::from_bytes::<Path>(&bytes).ok();
+            let value_decoded = BinaryReader::new()
+                .read(&bytes, Path::encoding())
+                .ok()
+                .and_then(|value| deserialize_from_value::<Path>(&value).ok());
+            assert_eq!(
+                direct_decoded,
+                value_decoded,
+                "bytes: {}",
+                hex::encode(&bytes)
+            );
+        }
+        Ok(())
+    })
+}
@@ -4,7 +4,11 @@
 
 //! This crate provides core implementation for a protocol runner (both IPC server and client parts).
 
+use std::fmt;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
 use std::time::Duration;
 
 use failure::Fail;
@@ -12,22 +16,31 @@ use getset::{CopyGetters, Getters};
 use r2d2::{CustomizeConnection, Pool};
 use slog::{Level, Logger};
 
+use crypto::crypto_box::PrecomputedKey;
+use ipc::IpcTransport;
 use tezos_api::environment::TezosEnvironmentConfiguration;
 use tezos_api::ffi::TezosRuntimeConfiguration;
 
 use crate::pool::{
     InitReadonlyContextProtocolRunnerConnectionCustomizer, NoopProtocolRunnerConnectionCustomizer,
-    PoolError, ProtocolRunnerConnection, ProtocolRunnerManager, SlogErrorHandler,
+    PoolError, ProtocolRunnerManager, SlogErrorHandler,
 };
+use crate::recorder::IpcRecorderRef;
 use crate::runner::ExecutableProtocolRunner;
+use crate::stats::{init_runner_registry, ProtocolRunnerStats, RunnerRegistryRef};
+
+pub use crate::pool::ProtocolRunnerConnection;
 
 mod pool;
 pub mod protocol;
+pub mod recorder;
+pub mod remote;
 pub mod runner;
 pub mod service;
+pub mod stats;
 
 /// Configuration for tezos api pool
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TezosApiConnectionPoolConfiguration {
     /// Nuber of connections to create on startup of pool (can be 0, so the connections are created on demand)
     pub min_connections: u8,
@@ -39,18 +52,72 @@ pub struct TezosApiConnectionPoolConfiguration {
     pub max_lifetime: Duration,
     /// if protocol_runner is not used 'idle_timeout', than is closed
     pub idle_timeout: Duration,
+
+    /// Where protocol runners are started, new connections are distributed round-robin between locations
+    /// (if runner cannot be started at one location, the next one is tried)
+    pub runners: Vec<ProtocolRunnerLocation>,
+
+    /// Max resident memory (in bytes) of one local protocol_runner, runner which exceeds it is evicted from pool (checked on checkout)
+    pub max_runner_memory: Option<u64>,
+}
+
+/// Location of protocol runner
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolRunnerLocation {
+    /// Runner is started as sub-process of the node
+    Local,
+    /// Runner is started on remote host by `protocol-runner-server` and connected over TCP
+    Remote(SocketAddr),
+}
+
+impl FromStr for ProtocolRunnerLocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(ProtocolRunnerLocation::Local),
+            address => address
+                .parse::<SocketAddr>()
+                .map(ProtocolRunnerLocation::Remote)
+                .map_err(|e| format!("Invalid protocol runner location: {}, reason: {}", s, e)),
+        }
+    }
+}
+
+impl fmt::Display for ProtocolRunnerLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolRunnerLocation::Local => write!(f, "local"),
+            ProtocolRunnerLocation::Remote(address) => write!(f, "{}", address),
+        }
+    }
 }
 
 /// This pool is "hard-coded" for ExecutableProtocolRunner, but it is easily extended as [TezosApiConnectionPool<Runner: ProtocolRunner + 'static>] if needed
 pub type RunnerType = ExecutableProtocolRunner;
 
+/// r2d2 pool of protocol runner connections
+pub type ProtocolRunnerPool = Pool<ProtocolRunnerManager<RunnerType>>;
+
+type ConnectionInitializer =
+    Box<dyn CustomizeConnection<ProtocolRunnerConnection<RunnerType>, PoolError>>;
+
 /// Wrapper for r2d2 pool with managed protocol_runner "connections", protocol runners sub-processes are now managed and started by the pool.
 /// Automatically refreshes old protocol_runner sub-processes [idle_timeout][max_lifetime]
 ///
-/// One connection means one protocol_runner sub-process and one IPC
+/// One connection means one protocol_runner sub-process and one IPC, sub-process can run locally or on remote host (see [ProtocolRunnerLocation])
+///
+/// Pool can be reconfigured at runtime (see [TezosApiConnectionPool::reconfigure]), so r2d2 pool should be always accessed through [TezosApiConnectionPool::pool]
 pub struct TezosApiConnectionPool {
-    pub pool: Pool<ProtocolRunnerManager<RunnerType>>,
+    pool: RwLock<ProtocolRunnerPool>,
+    pool_cfg: RwLock<TezosApiConnectionPoolConfiguration>,
     pub pool_name: String,
+    /// Stats of alive protocol runners (shared by all pools created by reconfiguration)
+    registry: RunnerRegistryRef,
+
+    endpoint_cfg: ProtocolEndpointConfiguration,
+    initializer: fn() -> ConnectionInitializer,
+    log: Logger,
 }
 
 /// Errors for connection pool
@@ -59,6 +126,9 @@ pub enum TezosApiConnectionPoolError {
     /// Initialization error
     #[fail(display = "Initialization error: {:?}", source)]
     InitializationError { source: r2d2::Error },
+    /// Invalid pool configuration
+    #[fail(display = "Invalid pool configuration: {}", reason)]
+    InvalidConfiguration { reason: String },
 }
 
 impl From<r2d2::Error> for TezosApiConnectionPoolError {
@@ -75,13 +145,9 @@ impl TezosApiConnectionPool {
         endpoint_cfg: ProtocolEndpointConfiguration,
         log: Logger,
     ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
-        Self::new(
-            pool_name,
-            pool_cfg,
-            endpoint_cfg,
-            log,
-            Box::new(InitReadonlyContextProtocolRunnerConnectionCustomizer),
-        )
+        Self::new(pool_name, pool_cfg, endpoint_cfg, log, || {
+            Box::new(InitReadonlyContextProtocolRunnerConnectionCustomizer)
+        })
     }
 
     /// Pool without ffi initialized context
@@ -92,13 +158,9 @@ impl TezosApiConnectionPool {
         endpoint_cfg: ProtocolEndpointConfiguration,
         log: Logger,
     ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
-        Self::new(
-            pool_name,
-            pool_cfg,
-            endpoint_cfg,
-            log,
-            Box::new(NoopProtocolRunnerConnectionCustomizer),
-        )
+        Self::new(pool_name, pool_cfg, endpoint_cfg, log, || {
+            Box::new(NoopProtocolRunnerConnectionCustomizer)
+        })
     }
 
     fn new(
@@ -106,13 +168,45 @@ impl TezosApiConnectionPool {
         pool_cfg: TezosApiConnectionPoolConfiguration,
         endpoint_cfg: ProtocolEndpointConfiguration,
         log: Logger,
-        initializer: Box<dyn CustomizeConnection<ProtocolRunnerConnection<RunnerType>, PoolError>>,
+        initializer: fn() -> ConnectionInitializer,
     ) -> Result<TezosApiConnectionPool, TezosApiConnectionPoolError> {
+        let registry = init_runner_registry();
+        let pool = Self::build_pool(
+            &pool_name,
+            &pool_cfg,
+            &endpoint_cfg,
+            &registry,
+            initializer,
+            &log,
+        )?;
+
+        Ok(TezosApiConnectionPool {
+            pool: RwLock::new(pool),
+            pool_cfg: RwLock::new(pool_cfg),
+            pool_name,
+            registry,
+            endpoint_cfg,
+            initializer,
+            log,
+        })
+    }
+
+    fn build_pool(
+        pool_name: &str,
+        pool_cfg: &TezosApiConnectionPoolConfiguration,
+        endpoint_cfg: &ProtocolEndpointConfiguration,
+        registry: &RunnerRegistryRef,
+        initializer: fn() -> ConnectionInitializer,
+        log: &Logger,
+    ) -> Result<ProtocolRunnerPool, TezosApiConnectionPoolError> {
         // create manager
         let manager = ProtocolRunnerManager::<RunnerType>::new(
-            pool_name.clone(),
+            pool_name.to_string(),
             pool_cfg.connection_timeout,
-            endpoint_cfg,
+            pool_cfg.runners.clone(),
+            pool_cfg.max_runner_memory,
+            registry.clone(),
+            endpoint_cfg.clone(),
             log.clone(),
         );
 
@@ -123,11 +217,87 @@ impl TezosApiConnectionPool {
             .connection_timeout(pool_cfg.connection_timeout)
             .max_lifetime(Some(pool_cfg.max_lifetime))
             .idle_timeout(Some(pool_cfg.idle_timeout))
-            .connection_customizer(initializer)
-            .error_handler(Box::new(SlogErrorHandler::new(log, pool_name.clone())))
+            .connection_customizer(initializer())
+            .error_handler(Box::new(SlogErrorHandler::new(
+                log.clone(),
+                pool_name.to_string(),
+            )))
             .build(manager)?;
 
-        Ok(TezosApiConnectionPool { pool, pool_name })
+        Ok(pool)
+    }
+
+    /// Returns current r2d2 pool (cheap clone of shared pool)
+    pub fn pool(&self) -> ProtocolRunnerPool {
+        match self.pool.read() {
+            Ok(pool) => pool.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Returns stats of all alive protocol runners of this pool (age, calls, errors, latencies, memory)
+    pub fn runner_stats(&self) -> Vec<ProtocolRunnerStats> {
+        match self.registry.read() {
+            Ok(registry) => registry.iter().map(|runner| runner.stats()).collect(),
+            Err(poisoned) => poisoned
+                .into_inner()
+                .iter()
+                .map(|runner| runner.stats())
+                .collect(),
+        }
+    }
+
+    /// Returns current pool configuration
+    pub fn pool_cfg(&self) -> TezosApiConnectionPoolConfiguration {
+        match self.pool_cfg.read() {
+            Ok(pool_cfg) => pool_cfg.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replaces r2d2 pool with the new one created with new configuration (r2d2 does not support resizing).
+    /// Connections borrowed from the previous pool are finished normally, protocol runners of previous pool are closed,
+    /// when the last borrowed connection is returned.
+    ///
+    /// Note: pool with writeable context cannot be reconfigured, because two protocol runners would write to the same context.
+    pub fn reconfigure(
+        &self,
+        pool_cfg: TezosApiConnectionPoolConfiguration,
+    ) -> Result<(), TezosApiConnectionPoolError> {
+        if pool_cfg.min_connections > pool_cfg.max_connections || pool_cfg.max_connections == 0 {
+            return Err(TezosApiConnectionPoolError::InvalidConfiguration {
+                reason: format!(
+                    "min_connections: {} must be less than or equal to max_connections: {} (and max_connections must be greater than 0)",
+                    pool_cfg.min_connections, pool_cfg.max_connections
+                ),
+            });
+        }
+        if pool_cfg.runners.is_empty() {
+            return Err(TezosApiConnectionPoolError::InvalidConfiguration {
+                reason: "at least one protocol runner location must be configured".to_string(),
+            });
+        }
+
+        let pool = Self::build_pool(
+            &self.pool_name,
+            &pool_cfg,
+            &self.endpoint_cfg,
+            &self.registry,
+            self.initializer,
+            &self.log,
+        )?;
+
+        let previous_pool = match self.pool.write() {
+            Ok(mut current) => std::mem::replace(&mut *current, pool),
+            Err(poisoned) => std::mem::replace(&mut *poisoned.into_inner(), pool),
+        };
+        match self.pool_cfg.write() {
+            Ok(mut current) => *current = pool_cfg,
+            Err(poisoned) => *poisoned.into_inner() = pool_cfg,
+        };
+        drop(previous_pool);
+
+        Ok(())
     }
 }
 
@@ -153,6 +323,15 @@ pub struct ProtocolEndpointConfiguration {
     #[get = "pub"]
     log_level: Level,
     event_server_path: Option<PathBuf>,
+    /// If set, all IPC calls to protocol runners are recorded
+    #[get = "pub"]
+    ipc_recorder: Option<IpcRecorderRef>,
+    /// Transport offered to protocol runners for commands (runner can fall back to socket)
+    #[get_copy = "pub"]
+    ipc_transport: IpcTransport,
+    /// Pre-shared key for authentication of remote protocol runners (see [ProtocolRunnerLocation::Remote])
+    #[get = "pub"]
+    remote_runner_key: Option<PrecomputedKey>,
 }
 
 impl ProtocolEndpointConfiguration {
@@ -164,6 +343,9 @@ impl ProtocolEndpointConfiguration {
         executable_path: P,
         log_level: Level,
         event_server_path: Option<PathBuf>,
+        ipc_recorder: Option<IpcRecorderRef>,
+        ipc_transport: IpcTransport,
+        remote_runner_key: Option<PrecomputedKey>,
     ) -> Self {
         ProtocolEndpointConfiguration {
             runtime_configuration,
@@ -173,6 +355,9 @@ impl ProtocolEndpointConfiguration {
             executable_path: executable_path.as_ref().into(),
             log_level,
             event_server_path,
+            ipc_recorder,
+            ipc_transport,
+            remote_runner_key,
         }
     }
 }
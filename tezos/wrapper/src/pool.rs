@@ -2,8 +2,10 @@
 // SPDX-License-Identifier: MIT
 
 use std::fmt::Formatter;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{error, fmt};
 
 use failure::_core::marker::PhantomData;
@@ -14,7 +16,8 @@ use ipc::IpcError;
 
 use crate::runner::{ProtocolRunner, ProtocolRunnerError};
 use crate::service::{ProtocolController, ProtocolRunnerEndpoint, ProtocolServiceError};
-use crate::ProtocolEndpointConfiguration;
+use crate::stats::{resident_memory, RegisteredRunner, RunnerRegistryRef};
+use crate::{ProtocolEndpointConfiguration, ProtocolRunnerLocation};
 
 /// Possible errors for storage
 #[derive(Debug)]
@@ -30,6 +33,12 @@ pub enum PoolError {
         message: String,
         error: ProtocolServiceError,
     },
+    RemoteRunnerError {
+        reason: String,
+    },
+    UnhealthyRunnerError {
+        reason: String,
+    },
 }
 
 impl std::error::Error for PoolError {}
@@ -40,24 +49,66 @@ impl fmt::Display for PoolError {
             PoolError::SpawnRunnerError { ref error } => write!(f, "Create pool connection error - fail to spawn sub-process, reason: {:?}", error),
             PoolError::IpcError { ref reason, ref error } => write!(f, "Create pool connection IPC error - {}, error: {:?}", reason, error),
             PoolError::InitContextError { ref message, ref error } => write!(f, "Create pool connection error - fail to initialize context, message: {}, reason: {:?}", message, error),
+            PoolError::RemoteRunnerError { ref reason } => write!(f, "Create pool connection error - remote protocol runner, reason: {}", reason),
+            PoolError::UnhealthyRunnerError { ref reason } => write!(f, "Pool connection health check failed, reason: {}", reason),
         }
     }
 }
 
+/// Protocol runner, which serves the connection
+enum RunnerInstance<Subprocess> {
+    /// Sub-process of the node
+    Local(Subprocess),
+    /// Runner on remote host, sub-process is managed by `protocol-runner-server` (and terminated, when connection is closed)
+    Remote(SocketAddr),
+}
+
 /// Protocol runner sub-process wrapper which acts as connection
 pub struct ProtocolRunnerConnection<Runner: ProtocolRunner> {
     pub api: ProtocolController,
-    subprocess: Runner::Subprocess,
+    runner: RunnerInstance<Runner::Subprocess>,
     log: Logger,
     pub name: String,
 
     /// Indicates that we want to release this connection on return to pool (used for gracefull shutdown)
     release_on_return_to_pool: bool,
+    /// Runner with higher resident memory is evicted from pool (checked on checkout)
+    max_memory: Option<u64>,
+    /// Stats of the pool, where this connection is registered
+    registry: RunnerRegistryRef,
 }
 
 impl<Runner: ProtocolRunner + 'static> ProtocolRunnerConnection<Runner> {
+    /// Health check on checkout from pool - runner must respond to ping and must not exceed memory limit
     pub fn is_valid(&mut self) -> Result<(), PoolError> {
-        // TODO: check if unix socket is connected?
+        let latency = self
+            .api
+            .ping()
+            .map_err(|e| PoolError::UnhealthyRunnerError {
+                reason: format!("ping failed: {}", e),
+            })?;
+
+        if let Some(max_memory) = self.max_memory {
+            let resident = match &self.runner {
+                RunnerInstance::Local(subprocess) => {
+                    Runner::pid(subprocess).and_then(resident_memory)
+                }
+                RunnerInstance::Remote(_) => None,
+            };
+            if let Some(resident) = resident {
+                if resident > max_memory {
+                    warn!(self.log, "Protocol runner exceeded memory limit, so it is evicted from pool"; "resident_memory" => resident, "max_memory" => max_memory);
+                    return Err(PoolError::UnhealthyRunnerError {
+                        reason: format!(
+                            "resident memory: {} exceeds limit: {}",
+                            resident, max_memory
+                        ),
+                    });
+                }
+            }
+        }
+
+        debug!(self.log, "Protocol runner is healthy"; "ping_latency_ms" => latency.as_millis() as u64);
         Ok(())
     }
 
@@ -65,8 +116,7 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerConnection<Runner> {
         if self.release_on_return_to_pool {
             return true;
         }
-        let is_subprocess_running = Runner::is_running(&mut self.subprocess);
-        !is_subprocess_running
+        !self.is_running()
     }
 
     pub fn terminate_subprocess(&mut self) {
@@ -76,11 +126,12 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerConnection<Runner> {
         };
 
         // try terminate sub-process (if running)
-        if let Err(e) = Runner::wait_and_terminate_ref(
-            &mut self.subprocess,
-            Runner::PROCESS_TERMINATE_WAIT_TIMEOUT,
-        ) {
-            warn!(self.log, "Failed to terminate/kill protocol runner"; "reason" => e);
+        if let RunnerInstance::Local(subprocess) = &mut self.runner {
+            if let Err(e) =
+                Runner::wait_and_terminate_ref(subprocess, Runner::PROCESS_TERMINATE_WAIT_TIMEOUT)
+            {
+                warn!(self.log, "Failed to terminate/kill protocol runner"; "reason" => e);
+            }
         }
     }
 
@@ -88,15 +139,66 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerConnection<Runner> {
     pub fn set_release_on_return_to_pool(&mut self) {
         self.release_on_return_to_pool = true;
     }
+
+    /// Checks if sub-process is still running and IPC channel is usable
+    /// (remote runner is considered as running, until IPC fails)
+    pub fn is_running(&mut self) -> bool {
+        if self.api.has_ipc_failed() {
+            return false;
+        }
+        match &mut self.runner {
+            RunnerInstance::Local(subprocess) => Runner::is_running(subprocess),
+            RunnerInstance::Remote(_) => true,
+        }
+    }
+
+    /// Kills hung sub-process (without graceful shutdown) and marks connection for release
+    /// (remote runner is killed by `protocol-runner-server`, when connection is closed)
+    pub fn kill_subprocess(&mut self) {
+        if let RunnerInstance::Local(subprocess) = &mut self.runner {
+            if let Err(e) = Runner::kill(subprocess) {
+                warn!(self.log, "Failed to kill protocol runner"; "reason" => e);
+            }
+        }
+        self.release_on_return_to_pool = true;
+    }
+
+    /// Returns location of the protocol runner
+    pub fn location(&self) -> ProtocolRunnerLocation {
+        match &self.runner {
+            RunnerInstance::Local(_) => ProtocolRunnerLocation::Local,
+            RunnerInstance::Remote(address) => ProtocolRunnerLocation::Remote(*address),
+        }
+    }
+}
+
+impl<Runner: ProtocolRunner> Drop for ProtocolRunnerConnection<Runner> {
+    fn drop(&mut self) {
+        // connection is closed, so it is not in stats anymore
+        let call_stats = self.api.call_stats();
+        let is_other =
+            |registered: &RegisteredRunner| !Arc::ptr_eq(&registered.call_stats, &call_stats);
+        match self.registry.write() {
+            Ok(mut registry) => registry.retain(is_other),
+            Err(poisoned) => poisoned.into_inner().retain(is_other),
+        };
+    }
 }
 
 /// Connection manager, which creates new connections:
 /// - runs new sub-process
 /// - starts IPC accept
+///
+/// or connects to the remote protocol runner
 pub struct ProtocolRunnerManager<Runner: ProtocolRunner> {
     pool_name: String,
     pool_name_counter: AtomicUsize,
     pool_connection_timeout: Duration,
+    /// Locations of runners, which are used round-robin
+    runners: Vec<ProtocolRunnerLocation>,
+    next_runner: AtomicUsize,
+    max_runner_memory: Option<u64>,
+    registry: RunnerRegistryRef,
 
     pub endpoint_cfg: ProtocolEndpointConfiguration,
     pub log: Logger,
@@ -106,16 +208,29 @@ pub struct ProtocolRunnerManager<Runner: ProtocolRunner> {
 impl<Runner: ProtocolRunner + 'static> ProtocolRunnerManager<Runner> {
     const MIN_ACCEPT_TIMEOUT: Duration = Duration::from_secs(3);
 
-    pub fn new(
+    pub(crate) fn new(
         pool_name: String,
         pool_connection_timeout: Duration,
+        runners: Vec<ProtocolRunnerLocation>,
+        max_runner_memory: Option<u64>,
+        registry: RunnerRegistryRef,
         endpoint_cfg: ProtocolEndpointConfiguration,
         log: Logger,
     ) -> Self {
+        // local runner is default
+        let runners = if runners.is_empty() {
+            vec![ProtocolRunnerLocation::Local]
+        } else {
+            runners
+        };
         Self {
             pool_name,
             pool_name_counter: AtomicUsize::new(1),
             pool_connection_timeout,
+            runners,
+            next_runner: AtomicUsize::new(0),
+            max_runner_memory,
+            registry,
             endpoint_cfg,
             log,
             _phantom: PhantomData,
@@ -129,6 +244,79 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerManager<Runner> {
             self.pool_name_counter.fetch_add(1, Ordering::SeqCst)
         );
 
+        // round-robin, if runner cannot be created at one location, try the next one
+        let first_runner = self.next_runner.fetch_add(1, Ordering::SeqCst);
+        let mut last_error = None;
+        for idx in 0..self.runners.len() {
+            let location = &self.runners[(first_runner + idx) % self.runners.len()];
+            let result = match location {
+                ProtocolRunnerLocation::Local => self.create_local_connection(&endpoint_name),
+                ProtocolRunnerLocation::Remote(address) => {
+                    self.create_remote_connection(&endpoint_name, *address)
+                }
+            };
+            match result {
+                Ok(connection) => return Ok(connection),
+                Err(e) => {
+                    if self.runners.len() > 1 {
+                        warn!(self.log, "Failed to create connection for protocol runner, trying next location"; "endpoint" => endpoint_name.clone(), "location" => location.to_string(), "reason" => format!("{}", &e));
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| PoolError::RemoteRunnerError {
+            reason: "no protocol runner location configured".to_string(),
+        }))
+    }
+
+    /// Count accept_timeout according to connection_timeout (so there is time to try next runner location)
+    fn accept_timeout(&self) -> Duration {
+        match self.pool_connection_timeout.checked_div(3) {
+            Some(conn_timeout) => std::cmp::max(Self::MIN_ACCEPT_TIMEOUT, conn_timeout),
+            None => Self::MIN_ACCEPT_TIMEOUT,
+        }
+    }
+
+    /// Connects to the protocol runner, which is started on remote host by `protocol-runner-server`
+    fn create_remote_connection(
+        &self,
+        endpoint_name: &str,
+        address: SocketAddr,
+    ) -> Result<ProtocolRunnerConnection<Runner>, PoolError> {
+        let api = match ProtocolController::try_connect_remote(
+            address,
+            self.endpoint_cfg.clone(),
+            self.accept_timeout(),
+        ) {
+            Ok(api) => api,
+            Err(e) => {
+                error!(self.log, "Failed to connect to remote protocol runner"; "endpoint" => endpoint_name, "address" => address.to_string(), "reason" => format!("{}", &e));
+                return Err(PoolError::IpcError {
+                    reason: format!("fail to connect remote protocol runner: {}", address),
+                    error: e,
+                });
+            }
+        };
+
+        debug!(self.log, "Connection for remote protocol runner was created successfully"; "endpoint" => endpoint_name, "address" => address.to_string());
+        Ok(self.register_connection(
+            api,
+            RunnerInstance::Remote(address),
+            self.log
+                .new(o!("endpoint" => endpoint_name.to_string(), "remote" => address.to_string())),
+            endpoint_name.to_string(),
+        ))
+    }
+
+    /// Spawns new protocol runner sub-process and accepts its IPC connection
+    fn create_local_connection(
+        &self,
+        endpoint_name: &str,
+    ) -> Result<ProtocolRunnerConnection<Runner>, PoolError> {
+        let endpoint_name = endpoint_name.to_string();
+
         // crate protocol runner endpoint
         let protocol_endpoint = ProtocolRunnerEndpoint::<Runner>::try_new(
             &endpoint_name,
@@ -152,14 +340,8 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerManager<Runner> {
             }
         };
 
-        // count accept_timeout according to connection_timeout
-        let accept_timeout = match self.pool_connection_timeout.checked_div(3) {
-            Some(conn_timeout) => std::cmp::max(Self::MIN_ACCEPT_TIMEOUT, conn_timeout),
-            None => Self::MIN_ACCEPT_TIMEOUT,
-        };
-
         // start IPC connection
-        let api = match protocol_commands.try_accept(accept_timeout) {
+        let api = match protocol_commands.try_accept(self.accept_timeout()) {
             Ok(controller) => controller,
             Err(e) => {
                 error!(self.log, "Failed to accept IPC connection on sub-process (so terminate sub-process)"; "endpoint" => endpoint_name, "reason" => format!("{:?}", &e));
@@ -178,13 +360,49 @@ impl<Runner: ProtocolRunner + 'static> ProtocolRunnerManager<Runner> {
         };
 
         debug!(self.log, "Connection for protocol runner was created successfully"; "endpoint" => endpoint_name.clone());
-        Ok(ProtocolRunnerConnection {
+        Ok(self.register_connection(
             api,
-            subprocess,
-            log: self.log.new(o!("endpoint" => endpoint_name.clone())),
-            name: endpoint_name,
+            RunnerInstance::Local(subprocess),
+            self.log.new(o!("endpoint" => endpoint_name.clone())),
+            endpoint_name,
+        ))
+    }
+
+    /// Creates connection and registers it to the pool's stats
+    fn register_connection(
+        &self,
+        api: ProtocolController,
+        runner: RunnerInstance<Runner::Subprocess>,
+        log: Logger,
+        name: String,
+    ) -> ProtocolRunnerConnection<Runner> {
+        let registered = RegisteredRunner {
+            name: name.clone(),
+            location: match &runner {
+                RunnerInstance::Local(_) => ProtocolRunnerLocation::Local,
+                RunnerInstance::Remote(address) => ProtocolRunnerLocation::Remote(*address),
+            },
+            pid: match &runner {
+                RunnerInstance::Local(subprocess) => Runner::pid(subprocess),
+                RunnerInstance::Remote(_) => None,
+            },
+            created_at: Instant::now(),
+            call_stats: api.call_stats(),
+        };
+        match self.registry.write() {
+            Ok(mut registry) => registry.push(registered),
+            Err(poisoned) => poisoned.into_inner().push(registered),
+        };
+
+        ProtocolRunnerConnection {
+            api,
+            runner,
+            log,
+            name,
             release_on_return_to_pool: false,
-        })
+            max_memory: self.max_runner_memory,
+            registry: self.registry.clone(),
+        }
     }
 }
 
@@ -0,0 +1,477 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Recording of the IPC calls to the protocol runners and replay of the recordings.
+//!
+//! Recorder appends every command sent to the protocol runner together with its response and timing to the recording file.
+//! Replay feeds the recording to fresh protocol runners (one for every recorded connection) and compares responses,
+//! so misbehaviour of the OCaml side can be reproduced and regressions (e.g. after OCaml upgrade) can be caught offline.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process::Child;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use slog::{debug, info, warn, Level, Logger};
+
+use ipc::{temp_sock, IpcError, IpcReceiver, IpcSender, IpcServer};
+
+use crate::runner::{ExecutableProtocolRunner, ProtocolRunner, ProtocolRunnerError};
+use crate::service::{IpcCmdServer, NodeMessage, ProtocolMessage};
+
+/// Shareable recorder of the IPC calls, can be shared by more pools/connections
+pub type IpcRecorderRef = Arc<IpcRecorder>;
+
+/// One recorded call to the protocol runner
+#[derive(Deserialize, Debug)]
+pub(crate) struct IpcCallRecord {
+    /// Global order of the call in the recording
+    sequence: u64,
+    /// Identifies protocol runner connection, which performed the call
+    connection_id: u64,
+    /// Start of the call as unix timestamp in microseconds
+    started_at_micros: u64,
+    duration_micros: u64,
+    request: ProtocolMessage,
+    /// Response or IPC error, if the call failed
+    response: Result<NodeMessage, String>,
+}
+
+/// Borrowed variant of [IpcCallRecord] used for writing (serialized in the same way)
+#[derive(Serialize)]
+struct IpcCallRecordRef<'a> {
+    sequence: u64,
+    connection_id: u64,
+    started_at_micros: u64,
+    duration_micros: u64,
+    request: &'a ProtocolMessage,
+    response: Result<&'a NodeMessage, String>,
+}
+
+struct RecordingWriter {
+    next_sequence: u64,
+    writer: BufWriter<File>,
+}
+
+/// Appends records of the IPC calls to the recording file (bincode encoded records)
+pub struct IpcRecorder {
+    writer: Mutex<RecordingWriter>,
+    next_connection_id: AtomicU64,
+    log: Logger,
+}
+
+impl IpcRecorder {
+    /// Creates new recording file (existing file is truncated)
+    pub fn create<P: AsRef<Path>>(path: P, log: Logger) -> Result<IpcRecorderRef, io::Error> {
+        Ok(Arc::new(IpcRecorder {
+            writer: Mutex::new(RecordingWriter {
+                next_sequence: 0,
+                writer: BufWriter::new(File::create(path)?),
+            }),
+            next_connection_id: AtomicU64::new(0),
+            log,
+        }))
+    }
+
+    fn write(
+        &self,
+        connection_id: u64,
+        request: &ProtocolMessage,
+        response: &Result<NodeMessage, IpcError>,
+        started_at: SystemTime,
+        duration: Duration,
+    ) -> Result<(), bincode::Error> {
+        let mut recording = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let record = IpcCallRecordRef {
+            sequence: recording.next_sequence,
+            connection_id,
+            started_at_micros: started_at
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_micros() as u64)
+                .unwrap_or(0),
+            duration_micros: duration.as_micros() as u64,
+            request,
+            response: response.as_ref().map_err(|e| format!("{}", e)),
+        };
+        bincode::serialize_into(&mut recording.writer, &record)?;
+        // flush every record, because we want to have the last calls also, when the node crashes
+        recording.writer.flush()?;
+        recording.next_sequence += 1;
+        Ok(())
+    }
+}
+
+/// Records calls of one protocol runner connection
+pub(crate) struct ConnectionRecorder {
+    recorder: IpcRecorderRef,
+    connection_id: u64,
+}
+
+impl ConnectionRecorder {
+    pub(crate) fn new(recorder: IpcRecorderRef) -> Self {
+        let connection_id = recorder.next_connection_id.fetch_add(1, Ordering::SeqCst);
+        ConnectionRecorder {
+            recorder,
+            connection_id,
+        }
+    }
+
+    pub(crate) fn record(
+        &self,
+        request: &ProtocolMessage,
+        response: &Result<NodeMessage, IpcError>,
+        started_at: SystemTime,
+        duration: Duration,
+    ) {
+        if let Err(e) =
+            self.recorder
+                .write(self.connection_id, request, response, started_at, duration)
+        {
+            let call: &'static str = request.into();
+            warn!(self.recorder.log, "Failed to record protocol runner call"; "call" => call, "reason" => format!("{}", e));
+        }
+    }
+}
+
+/// Reads records from the recording file, the last incomplete record (e.g. after crash) is ignored
+struct IpcRecordingReader {
+    reader: BufReader<File>,
+}
+
+impl IpcRecordingReader {
+    fn open<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Ok(IpcRecordingReader {
+            reader: BufReader::new(File::open(path)?),
+        })
+    }
+}
+
+impl Iterator for IpcRecordingReader {
+    type Item = Result<IpcCallRecord, IpcReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match bincode::deserialize_from(&mut self.reader) {
+            Ok(record) => Some(Ok(record)),
+            Err(e) => match e.as_ref() {
+                bincode::ErrorKind::Io(io_error)
+                    if io_error.kind() == io::ErrorKind::UnexpectedEof =>
+                {
+                    None
+                }
+                _ => Some(Err(IpcReplayError::InvalidRecordingError { reason: e })),
+            },
+        }
+    }
+}
+
+/// Errors of the replay
+#[derive(Debug, Fail)]
+pub enum IpcReplayError {
+    #[fail(display = "Failed to read recording, reason: {}", reason)]
+    IoError { reason: io::Error },
+    #[fail(display = "Invalid recording, reason: {}", reason)]
+    InvalidRecordingError { reason: bincode::Error },
+    #[fail(display = "Failed to spawn protocol runner, reason: {}", reason)]
+    SpawnRunnerError { reason: ProtocolRunnerError },
+    #[fail(display = "Protocol runner IPC error, reason: {}", reason)]
+    IpcError { reason: IpcError },
+}
+
+impl From<io::Error> for IpcReplayError {
+    fn from(reason: io::Error) -> Self {
+        IpcReplayError::IoError { reason }
+    }
+}
+
+impl From<ProtocolRunnerError> for IpcReplayError {
+    fn from(reason: ProtocolRunnerError) -> Self {
+        IpcReplayError::SpawnRunnerError { reason }
+    }
+}
+
+impl From<IpcError> for IpcReplayError {
+    fn from(reason: IpcError) -> Self {
+        IpcReplayError::IpcError { reason }
+    }
+}
+
+/// Replayed call, which response differs from the recorded one
+#[derive(Debug)]
+pub struct ReplayMismatch {
+    pub sequence: u64,
+    pub connection_id: u64,
+    pub call: &'static str,
+    pub recorded: String,
+    pub replayed: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub replayed_calls: usize,
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+/// Fresh protocol runner, which replays calls of one recorded connection
+struct ReplayConnection {
+    connection_id: u64,
+    rx: IpcReceiver<NodeMessage>,
+    tx: IpcSender<ProtocolMessage>,
+    subprocess: Child,
+    /// If set, connection failed and remaining calls are not replayed
+    broken: Option<String>,
+}
+
+impl ReplayConnection {
+    const ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+    const CALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+    fn spawn(
+        connection_id: u64,
+        protocol_runner: &Path,
+        log_level: Level,
+    ) -> Result<ReplayConnection, IpcReplayError> {
+        let mut server: IpcServer<NodeMessage, ProtocolMessage> =
+            IpcServer::bind_path(&temp_sock())?;
+        let runner = ExecutableProtocolRunner::new_without_events(
+            protocol_runner,
+            server.client().path(),
+            format!("replay_{}", connection_id),
+            log_level,
+        );
+        let mut subprocess = runner.spawn()?;
+
+        match server.try_accept(Self::ACCEPT_TIMEOUT) {
+            Ok((rx, tx)) => Ok(ReplayConnection {
+                connection_id,
+                rx,
+                tx,
+                subprocess,
+                broken: None,
+            }),
+            Err(e) => {
+                let _ = ExecutableProtocolRunner::wait_and_terminate_ref(
+                    &mut subprocess,
+                    ExecutableProtocolRunner::PROCESS_TERMINATE_WAIT_TIMEOUT,
+                );
+                Err(e.into())
+            }
+        }
+    }
+
+    fn call(&mut self, request: &ProtocolMessage) -> Result<NodeMessage, IpcError> {
+        self.tx.send(request)?;
+        self.rx
+            .try_receive(Some(Self::CALL_TIMEOUT), Some(IpcCmdServer::IO_TIMEOUT))
+    }
+
+    fn shutdown(mut self, log: &Logger) {
+        if self.broken.is_none() {
+            if let Err(e) = self.call(&ProtocolMessage::ShutdownCall) {
+                warn!(log, "Failed to shutdown replay protocol runner gracefully"; "connection_id" => self.connection_id, "reason" => format!("{}", e));
+            }
+        }
+        if let Err(e) = ExecutableProtocolRunner::wait_and_terminate_ref(
+            &mut self.subprocess,
+            ExecutableProtocolRunner::PROCESS_TERMINATE_WAIT_TIMEOUT,
+        ) {
+            warn!(log, "Failed to terminate/kill replay protocol runner"; "connection_id" => self.connection_id, "reason" => e);
+        }
+    }
+}
+
+/// Compares recorded and replayed response, returns None if they are the same
+fn compare_responses(
+    recorded: &Result<NodeMessage, String>,
+    replayed: &Result<NodeMessage, String>,
+) -> Option<(String, String)> {
+    match (recorded, replayed) {
+        (Ok(recorded), Ok(replayed)) => {
+            // messages does not implement PartialEq, so we compare encoded messages
+            match (bincode::serialize(recorded), bincode::serialize(replayed)) {
+                (Ok(recorded_bytes), Ok(replayed_bytes)) if recorded_bytes == replayed_bytes => {
+                    None
+                }
+                _ => Some((format!("{:?}", recorded), format!("{:?}", replayed))),
+            }
+        }
+        // both calls failed on IPC level (e.g. protocol runner crashed)
+        (Err(_), Err(_)) => None,
+        (recorded, replayed) => Some((format!("{:?}", recorded), format!("{:?}", replayed))),
+    }
+}
+
+/// Replays recording with fresh protocol runners (`protocol_runner` executable) and compares responses with the recorded ones.
+///
+/// Every recorded connection is replayed by its own protocol runner, calls are replayed in the recorded order.
+/// Context is initialized in `data_dir` (instead of the recorded one), so the original node's data are not touched.
+pub fn replay_recording<P: AsRef<Path>>(
+    recording: P,
+    protocol_runner: &Path,
+    runner_log_level: Level,
+    data_dir: &Path,
+    log: &Logger,
+) -> Result<ReplayReport, IpcReplayError> {
+    let mut report = ReplayReport::default();
+    let mut connections: Vec<ReplayConnection> = Vec::new();
+
+    let result = replay_records(
+        IpcRecordingReader::open(recording)?,
+        protocol_runner,
+        runner_log_level,
+        data_dir,
+        &mut connections,
+        &mut report,
+        log,
+    );
+
+    // shutdown all runners, also if replay failed
+    for connection in connections {
+        connection.shutdown(log);
+    }
+
+    result.map(|()| report)
+}
+
+fn replay_records(
+    records: IpcRecordingReader,
+    protocol_runner: &Path,
+    runner_log_level: Level,
+    data_dir: &Path,
+    connections: &mut Vec<ReplayConnection>,
+    report: &mut ReplayReport,
+    log: &Logger,
+) -> Result<(), IpcReplayError> {
+    for record in records {
+        let IpcCallRecord {
+            sequence,
+            connection_id,
+            started_at_micros,
+            duration_micros,
+            mut request,
+            response,
+        } = record?;
+
+        // shutdown is not replayed, all runners are shut down at the end
+        if let ProtocolMessage::ShutdownCall = request {
+            continue;
+        }
+        if let ProtocolMessage::InitProtocolContextCall(params) = &mut request {
+            params.storage_data_dir = data_dir.to_string_lossy().to_string();
+        }
+        let call: &'static str = (&request).into();
+
+        let connection = match connections
+            .iter()
+            .position(|connection| connection.connection_id == connection_id)
+        {
+            Some(index) => &mut connections[index],
+            None => {
+                info!(log, "Spawning protocol runner for recorded connection"; "connection_id" => connection_id);
+                connections.push(ReplayConnection::spawn(
+                    connection_id,
+                    protocol_runner,
+                    runner_log_level,
+                )?);
+                connections.last_mut().unwrap()
+            }
+        };
+
+        let replayed = match connection.broken.clone() {
+            Some(reason) => Err(format!("Connection is broken, reason: {}", reason)),
+            None => match connection.call(&request) {
+                Ok(message) => Ok(message),
+                Err(e) => {
+                    let reason = format!("{}", e);
+                    connection.broken = Some(reason.clone());
+                    Err(reason)
+                }
+            },
+        };
+        report.replayed_calls += 1;
+
+        debug!(log, "Replayed protocol runner call"; "sequence" => sequence, "connection_id" => connection_id, "call" => call, "recorded_at_micros" => started_at_micros, "recorded_duration_micros" => duration_micros);
+        if let Some((recorded, replayed)) = compare_responses(&response, &replayed) {
+            warn!(log, "Replayed response differs from recorded one"; "sequence" => sequence, "connection_id" => connection_id, "call" => call);
+            report.mismatches.push(ReplayMismatch {
+                sequence,
+                connection_id,
+                call,
+                recorded,
+                replayed,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use slog::{o, Discard};
+
+    use tezos_api::ffi::TezosRuntimeConfiguration;
+
+    use super::*;
+
+    #[test]
+    fn test_record_and_read_recording() -> Result<(), failure::Error> {
+        let recording = env::temp_dir().join("__ipc_recorder_test.bin");
+        let _ = fs::remove_file(&recording);
+
+        let recorder = IpcRecorder::create(&recording, Logger::root(Discard, o!()))?;
+        let connection_1 = ConnectionRecorder::new(recorder.clone());
+        let connection_2 = ConnectionRecorder::new(recorder);
+
+        let request = ProtocolMessage::ChangeRuntimeConfigurationCall(TezosRuntimeConfiguration {
+            log_enabled: false,
+            debug_mode: false,
+            compute_context_action_tree_hashes: false,
+        });
+        connection_1.record(
+            &request,
+            &Ok(NodeMessage::ChangeRuntimeConfigurationResult(Ok(()))),
+            SystemTime::now(),
+            Duration::from_millis(3),
+        );
+        connection_2.record(
+            &ProtocolMessage::ShutdownCall,
+            &Err(IpcError::ReceiveMessageTimeouted),
+            SystemTime::now(),
+            Duration::from_millis(10),
+        );
+
+        let records = IpcRecordingReader::open(&recording)?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(2, records.len());
+
+        assert_eq!(0, records[0].sequence);
+        assert_eq!(0, records[0].connection_id);
+        assert_eq!(3000, records[0].duration_micros);
+        assert!(matches!(
+            records[0].request,
+            ProtocolMessage::ChangeRuntimeConfigurationCall(_)
+        ));
+        assert!(compare_responses(
+            &records[0].response,
+            &Ok(NodeMessage::ChangeRuntimeConfigurationResult(Ok(())))
+        )
+        .is_none());
+
+        assert_eq!(1, records[1].sequence);
+        assert_eq!(1, records[1].connection_id);
+        assert!(matches!(records[1].request, ProtocolMessage::ShutdownCall));
+        assert!(records[1].response.is_err());
+        assert!(
+            compare_responses(&records[1].response, &Ok(NodeMessage::ShutdownResult)).is_some()
+        );
+
+        let _ = fs::remove_file(&recording);
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Server for protocol runners on remote host (see `protocol-runner-server`).
+//!
+//! Server listens on TCP and accepts connections from the node's pools (authenticated with pre-shared key),
+//! for every connection a fresh protocol runner sub-process is spawned and commands are forwarded to it,
+//! so one TCP connection behaves exactly as one local protocol runner. When node closes connection, runner is terminated.
+//!
+//! Remote runners are supposed to be used for readonly pools, so initialization of writeable context is refused.
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use failure::Fail;
+use slog::{debug, info, o, warn, Level, Logger};
+
+use crypto::crypto_box::PrecomputedKey;
+use ipc::{temp_sock, IpcError, IpcReceiver, IpcSender, IpcServer, TcpIpcServer};
+
+use crate::runner::{ExecutableProtocolRunner, ProtocolRunner, ProtocolRunnerError};
+use crate::service::{IpcCmdServer, NodeMessage, ProtocolMessage};
+
+#[derive(Fail, Debug)]
+enum RemoteConnectionError {
+    #[fail(display = "Failed to spawn protocol runner, reason: {}", reason)]
+    SpawnRunnerError { reason: ProtocolRunnerError },
+    #[fail(display = "Protocol runner IPC error, reason: {}", reason)]
+    IpcError { reason: IpcError },
+    #[fail(display = "Refused call: {}", reason)]
+    RefusedCall { reason: String },
+}
+
+impl From<ProtocolRunnerError> for RemoteConnectionError {
+    fn from(reason: ProtocolRunnerError) -> Self {
+        RemoteConnectionError::SpawnRunnerError { reason }
+    }
+}
+
+impl From<IpcError> for RemoteConnectionError {
+    fn from(reason: IpcError) -> Self {
+        RemoteConnectionError::IpcError { reason }
+    }
+}
+
+/// Configuration of the remote protocol runners server
+#[derive(Clone)]
+pub struct RemoteRunnerServerConfiguration {
+    /// Path to the protocol runner executable
+    pub protocol_runner: PathBuf,
+    pub runner_log_level: Level,
+    /// If set, context is initialized in this dir (instead of the node's dir)
+    pub data_dir: Option<PathBuf>,
+    /// Max count of concurrently running protocol runners (connections)
+    pub max_runners: usize,
+}
+
+/// Protocol runner sub-process, which serves one remote connection
+struct RemoteConnection {
+    connection_id: u64,
+    rx: IpcReceiver<NodeMessage>,
+    tx: IpcSender<ProtocolMessage>,
+    subprocess: Child,
+}
+
+impl RemoteConnection {
+    const ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+    /// Node has own timeouts for every call, this is just the longest one (apply block)
+    const CALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+    fn spawn(
+        connection_id: u64,
+        configuration: &RemoteRunnerServerConfiguration,
+    ) -> Result<RemoteConnection, RemoteConnectionError> {
+        let mut server: IpcServer<NodeMessage, ProtocolMessage> =
+            IpcServer::bind_path(&temp_sock())?;
+        let runner = ExecutableProtocolRunner::new_without_events(
+            &configuration.protocol_runner,
+            server.client().path(),
+            format!("remote_{}", connection_id),
+            configuration.runner_log_level,
+        );
+        let mut subprocess = runner.spawn()?;
+
+        match server.try_accept(Self::ACCEPT_TIMEOUT) {
+            Ok((rx, tx)) => Ok(RemoteConnection {
+                connection_id,
+                rx,
+                tx,
+                subprocess,
+            }),
+            Err(e) => {
+                let _ = ExecutableProtocolRunner::wait_and_terminate_ref(
+                    &mut subprocess,
+                    ExecutableProtocolRunner::PROCESS_TERMINATE_WAIT_TIMEOUT,
+                );
+                Err(e.into())
+            }
+        }
+    }
+
+    fn call(&mut self, request: &ProtocolMessage) -> Result<NodeMessage, IpcError> {
+        self.tx.send(request)?;
+        self.rx
+            .try_receive(Some(Self::CALL_TIMEOUT), Some(IpcCmdServer::IO_TIMEOUT))
+    }
+
+    fn terminate(mut self, shutdown: bool, log: &Logger) {
+        if shutdown {
+            if let Err(e) = self.call(&ProtocolMessage::ShutdownCall) {
+                warn!(log, "Failed to shutdown protocol runner gracefully"; "connection_id" => self.connection_id, "reason" => format!("{}", e));
+            }
+        }
+        if let Err(e) = ExecutableProtocolRunner::wait_and_terminate_ref(
+            &mut self.subprocess,
+            ExecutableProtocolRunner::PROCESS_TERMINATE_WAIT_TIMEOUT,
+        ) {
+            warn!(log, "Failed to terminate/kill protocol runner"; "connection_id" => self.connection_id, "reason" => e);
+        }
+    }
+}
+
+/// Decrements count of running runners, when connection thread finishes
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Listens on `listen_address` and serves connections from the node's pools (blocks current thread)
+///
+/// Returns error only, if server cannot listen, failed connections are just logged.
+pub fn serve_remote_runners(
+    listen_address: SocketAddr,
+    key: PrecomputedKey,
+    configuration: RemoteRunnerServerConfiguration,
+    log: &Logger,
+) -> Result<(), IpcError> {
+    const ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    let mut server: TcpIpcServer<ProtocolMessage, NodeMessage> =
+        TcpIpcServer::bind(listen_address, key)?;
+    info!(log, "Remote protocol runner server is listening"; "address" => listen_address.to_string(), "max_runners" => configuration.max_runners);
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let next_connection_id = AtomicU64::new(1);
+
+    loop {
+        let (stream, peer_address) = match server.try_accept(ACCEPT_TIMEOUT) {
+            Ok(connection) => connection,
+            Err(IpcError::AcceptTimeout { .. }) => continue,
+            Err(e) => {
+                // e.g. connection reset by client before accept, server can still accept other connections
+                warn!(log, "Failed to accept connection"; "reason" => format!("{}", e));
+                continue;
+            }
+        };
+
+        if running.fetch_add(1, Ordering::SeqCst) >= configuration.max_runners {
+            running.fetch_sub(1, Ordering::SeqCst);
+            warn!(log, "Refused connection, max count of protocol runners is reached"; "peer" => peer_address.to_string(), "max_runners" => configuration.max_runners);
+            continue;
+        }
+
+        let connection_id = next_connection_id.fetch_add(1, Ordering::SeqCst);
+        let connection_log =
+            log.new(o!("connection_id" => connection_id, "peer" => peer_address.to_string()));
+        let guard = RunningGuard(running.clone());
+        let configuration = configuration.clone();
+        let key = server.key().clone();
+        let spawned = thread::Builder::new()
+            .name(format!("remote-runner-{}", connection_id))
+            .spawn(move || {
+                let _guard = guard;
+
+                // handshake is done here, so slow/unauthenticated client does not block other connections
+                let (rx, tx) = match TcpIpcServer::<ProtocolMessage, NodeMessage>::handshake(
+                    stream,
+                    peer_address,
+                    &key,
+                ) {
+                    Ok(channel) => channel,
+                    Err(e) => {
+                        warn!(connection_log, "Refused connection"; "reason" => format!("{}", e));
+                        return;
+                    }
+                };
+
+                info!(connection_log, "Serving connection");
+                match serve_connection(connection_id, rx, tx, &configuration, &connection_log) {
+                    Ok(()) => info!(connection_log, "Connection finished"),
+                    Err(e) => {
+                        warn!(connection_log, "Connection failed"; "reason" => format!("{}", e))
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!(log, "Failed to spawn thread for connection"; "connection_id" => connection_id, "reason" => format!("{}", e));
+        }
+    }
+}
+
+/// Forwards calls from the node to the fresh protocol runner, until node closes connection or shuts down runner
+fn serve_connection(
+    connection_id: u64,
+    mut remote_rx: IpcReceiver<ProtocolMessage>,
+    mut remote_tx: IpcSender<NodeMessage>,
+    configuration: &RemoteRunnerServerConfiguration,
+    log: &Logger,
+) -> Result<(), RemoteConnectionError> {
+    let mut connection = RemoteConnection::spawn(connection_id, configuration)?;
+    let result = forward_calls(
+        &mut connection,
+        &mut remote_rx,
+        &mut remote_tx,
+        configuration.data_dir.as_deref(),
+        log,
+    );
+
+    // runner was already shut down by the node, if forwarding finished successfully
+    connection.terminate(result.is_err(), log);
+    result
+}
+
+fn forward_calls(
+    connection: &mut RemoteConnection,
+    remote_rx: &mut IpcReceiver<ProtocolMessage>,
+    remote_tx: &mut IpcSender<NodeMessage>,
+    data_dir: Option<&Path>,
+    log: &Logger,
+) -> Result<(), RemoteConnectionError> {
+    // node can be idle for a long time, so we wait for commands without timeout
+    remote_rx
+        .set_read_timeout(None)
+        .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+
+    loop {
+        let mut request = remote_rx.receive()?;
+
+        if let ProtocolMessage::InitProtocolContextCall(params) = &mut request {
+            if !params.readonly {
+                return Err(RemoteConnectionError::RefusedCall {
+                    reason: "writeable context cannot be initialized by remote protocol runner"
+                        .to_string(),
+                });
+            }
+            if let Some(data_dir) = data_dir {
+                params.storage_data_dir = data_dir.to_string_lossy().to_string();
+            }
+        }
+        let is_shutdown = matches!(request, ProtocolMessage::ShutdownCall);
+        let call: &'static str = (&request).into();
+
+        let response = connection.call(&request)?;
+        remote_tx.send(&response)?;
+        debug!(log, "Forwarded protocol runner call"; "call" => call);
+
+        if is_shutdown {
+            return Ok(());
+        }
+    }
+}
@@ -52,6 +52,22 @@ pub struct ExecutableProtocolRunner {
 }
 
 impl ExecutableProtocolRunner {
+    /// Creates runner without event channel, so context actions are not sent back to the node (e.g. used for replay)
+    pub fn new_without_events(
+        executable_path: &Path,
+        sock_cmd_path: &Path,
+        endpoint_name: String,
+        log_level: Level,
+    ) -> Self {
+        ExecutableProtocolRunner {
+            sock_cmd_path: sock_cmd_path.to_path_buf(),
+            sock_evt_path: None,
+            executable_path: executable_path.to_path_buf(),
+            endpoint_name,
+            log_level,
+        }
+    }
+
     /// Send SIGINT signal to the sub-process, which is cheking for this ctrl-c signal and shuts down gracefully if recieved
     fn terminate_or_kill(process: &mut Child, reason: String) -> Result<(), ProtocolRunnerError> {
         // try to send SIGINT (ctrl-c)
@@ -140,6 +156,26 @@ impl ProtocolRunner for ExecutableProtocolRunner {
     fn is_running(process: &mut Self::Subprocess) -> bool {
         matches!(process.try_wait(), Ok(None))
     }
+
+    fn pid(process: &Self::Subprocess) -> Option<u32> {
+        Some(process.id())
+    }
+
+    fn kill(process: &mut Self::Subprocess) -> Result<(), ProtocolRunnerError> {
+        match process.kill() {
+            // process already exited
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => Ok(()),
+            Err(e) => Err(ProtocolRunnerError::TerminateError {
+                reason: format!("Failed to kill sub-process, reason: {}", e),
+            }),
+            Ok(_) => process
+                .wait()
+                .map(|_| ())
+                .map_err(|e| ProtocolRunnerError::TerminateError {
+                    reason: format!("Failed to wait for killed sub-process, reason: {}", e),
+                }),
+        }
+    }
 }
 
 pub trait ProtocolRunner: Clone + Send + Sync {
@@ -162,4 +198,10 @@ pub trait ProtocolRunner: Clone + Send + Sync {
 
     /// Checks if process is running
     fn is_running(process: &mut Self::Subprocess) -> bool;
+
+    /// Returns OS process id (if any), e.g. for memory stats
+    fn pid(process: &Self::Subprocess) -> Option<u32>;
+
+    /// Kills process immediately (SIGKILL), used for hung processes, which do not react to IPC/SIGINT
+    fn kill(process: &mut Self::Subprocess) -> Result<(), ProtocolRunnerError>;
 }
@@ -1,11 +1,12 @@
 // Copyright (c) SimpleStaking and Tezedge Contributors
 // SPDX-License-Identifier: MIT
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::convert::AsRef;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 
 use failure::Fail;
 use lazy_static::lazy_static;
@@ -20,7 +21,9 @@ use tezos_api::ffi::*;
 use tezos_context::channel::{context_receive, context_send, ContextAction, ContextActionMessage};
 
 use crate::protocol::*;
+use crate::recorder::ConnectionRecorder;
 use crate::runner::{ProtocolRunner, ProtocolRunnerError};
+use crate::stats::{CallStats, CallStatsRef};
 use crate::ProtocolEndpointConfiguration;
 
 lazy_static! {
@@ -39,7 +42,7 @@ lazy_static! {
 
 /// This command message is generated by tezedge node and is received by the protocol runner.
 #[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
-enum ProtocolMessage {
+pub(crate) enum ProtocolMessage {
     ApplyBlockCall(ApplyBlockRequest),
     AssertEncodingForProtocolDataCall(ProtocolHash, RustBytes),
     BeginApplicationCall(BeginApplicationRequest),
@@ -52,23 +55,25 @@ enum ProtocolMessage {
     ChangeRuntimeConfigurationCall(TezosRuntimeConfiguration),
     InitProtocolContextCall(InitProtocolContextParams),
     GenesisResultDataCall(GenesisResultDataParams),
+    /// Liveness check of the protocol runner (does not touch OCaml runtime)
+    PingCall,
     ShutdownCall,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct InitProtocolContextParams {
-    storage_data_dir: String,
+pub(crate) struct InitProtocolContextParams {
+    pub(crate) storage_data_dir: String,
     genesis: GenesisChain,
     genesis_max_operations_ttl: u16,
     protocol_overrides: ProtocolOverrides,
     commit_genesis: bool,
     enable_testchain: bool,
-    readonly: bool,
+    pub(crate) readonly: bool,
     patch_context: Option<PatchContext>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct GenesisResultDataParams {
+pub(crate) struct GenesisResultDataParams {
     genesis_context_hash: ContextHash,
     chain_id: ChainId,
     genesis_protocol_hash: ProtocolHash,
@@ -77,7 +82,7 @@ struct GenesisResultDataParams {
 
 /// This event message is generated as a response to the `ProtocolMessage` command.
 #[derive(Serialize, Deserialize, Debug, IntoStaticStr)]
-enum NodeMessage {
+pub(crate) enum NodeMessage {
     ApplyBlockResult(Result<ApplyBlockResponse, ApplyBlockError>),
     AssertEncodingForProtocolDataResult(Result<(), ProtocolDataError>),
     BeginApplicationResult(Result<BeginApplicationResponse, BeginApplicationError>),
@@ -89,6 +94,7 @@ enum NodeMessage {
     InitProtocolContextResult(Result<InitProtocolContextResult, TezosStorageInitError>),
     CommitGenesisResultData(Result<CommitGenesisResult, GetDataError>),
     ComputePathResponse(Result<ComputePathResponse, ComputePathError>),
+    PingResult,
     ShutdownResult,
 }
 
@@ -181,6 +187,9 @@ pub fn process_protocol_commands<Proto: ProtocolApi, P: AsRef<Path>, SDC: Fn(&Lo
                 );
                 tx.send(&NodeMessage::CommitGenesisResultData(res))?;
             }
+            ProtocolMessage::PingCall => {
+                tx.send(&NodeMessage::PingResult)?;
+            }
             ProtocolMessage::ShutdownCall => {
                 // send shutdown event to context listener, that we dont need it anymore
                 if let Err(e) = context_send(ContextActionMessage {
@@ -321,12 +330,12 @@ pub struct IpcCmdServer(
 /// * `IpcCmdServer` is used to create IPC channel over which commands from node are transferred to the protocol runner.
 /// * `IpcEvtServer` is used to create IPC channel over which events are transmitted from protocol runner to the tezedge node.
 impl IpcCmdServer {
-    const IO_TIMEOUT: Duration = Duration::from_secs(10);
+    pub(crate) const IO_TIMEOUT: Duration = Duration::from_secs(10);
 
     /// Create new IPC endpoint
     pub fn try_new(configuration: ProtocolEndpointConfiguration) -> Result<Self, IpcError> {
         Ok(IpcCmdServer(
-            IpcServer::bind_path(&temp_sock())?,
+            IpcServer::bind_path(&temp_sock())?.with_transport(configuration.ipc_transport()),
             configuration,
         ))
     }
@@ -337,16 +346,7 @@ impl IpcCmdServer {
     /// This is a blocking operation.
     pub fn try_accept(&mut self, timeout: Duration) -> Result<ProtocolController, IpcError> {
         let (rx, tx) = self.0.try_accept(timeout)?;
-        // configure default IO timeouts
-        rx.set_read_timeout(Some(Self::IO_TIMEOUT))
-            .and(tx.set_write_timeout(Some(Self::IO_TIMEOUT)))
-            .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
-
-        Ok(ProtocolController {
-            io: RefCell::new(IpcIO { rx, tx }),
-            configuration: self.1.clone(),
-            shutting_down: false,
-        })
+        ProtocolController::new(rx, tx, self.1.clone())
     }
 }
 
@@ -384,9 +384,15 @@ struct IpcIO {
 /// Encapsulate IPC communication.
 pub struct ProtocolController {
     io: RefCell<IpcIO>,
+    /// Records all calls of this controller (if configured)
+    recorder: Option<ConnectionRecorder>,
     configuration: ProtocolEndpointConfiguration,
     /// Indicates that was triggered shutting down
     shutting_down: bool,
+    /// Indicates, that IPC call failed, so channel cannot be used anymore (e.g. response can come later)
+    ipc_failed: Cell<bool>,
+    /// Count of calls, errors and latencies (see [crate::stats])
+    call_stats: CallStatsRef,
 }
 
 /// Provides convenience methods for IPC communication.
@@ -402,20 +408,112 @@ impl ProtocolController {
     const CALL_PROTOCOL_RPC_TIMEOUT: Duration = Duration::from_secs(30);
     const COMPUTE_PATH_TIMEOUT: Duration = Duration::from_secs(30);
     const ASSERT_ENCODING_FOR_PROTOCOL_DATA_TIMEOUT: Duration = Duration::from_secs(15);
+    const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn new(
+        rx: IpcReceiver<NodeMessage>,
+        tx: IpcSender<ProtocolMessage>,
+        configuration: ProtocolEndpointConfiguration,
+    ) -> Result<Self, IpcError> {
+        // configure default IO timeouts
+        rx.set_read_timeout(Some(IpcCmdServer::IO_TIMEOUT))
+            .and(tx.set_write_timeout(Some(IpcCmdServer::IO_TIMEOUT)))
+            .map_err(|err| IpcError::SocketConfigurationError { reason: err })?;
+
+        Ok(ProtocolController {
+            io: RefCell::new(IpcIO { rx, tx }),
+            recorder: configuration
+                .ipc_recorder()
+                .as_ref()
+                .map(|recorder| ConnectionRecorder::new(recorder.clone())),
+            configuration,
+            shutting_down: false,
+            ipc_failed: Cell::new(false),
+            call_stats: Arc::new(Mutex::new(CallStats::default())),
+        })
+    }
+
+    /// Connects to protocol runner on remote host (started by `protocol-runner-server`), which is authenticated with pre-shared key.
+    ///
+    /// `timeout` - timeout for connect and handshake
+    pub fn try_connect_remote(
+        address: SocketAddr,
+        configuration: ProtocolEndpointConfiguration,
+        timeout: Duration,
+    ) -> Result<ProtocolController, IpcError> {
+        let key = configuration.remote_runner_key().as_ref().ok_or_else(|| {
+            IpcError::PresharedKeyError {
+                reason: format!(
+                    "pre-shared key is not configured for remote protocol runner: {}",
+                    address
+                ),
+            }
+        })?;
+        let (rx, tx) = TcpIpcClient::new(address).connect(key, timeout)?;
+        Self::new(rx, tx, configuration)
+    }
+
+    /// Returns true, if any IPC call failed (so connection should not be used anymore)
+    pub fn has_ipc_failed(&self) -> bool {
+        self.ipc_failed.get()
+    }
+
+    /// Returns shared stats of calls of this controller
+    pub fn call_stats(&self) -> CallStatsRef {
+        self.call_stats.clone()
+    }
+
+    /// Sends command to protocol runner and waits for the response, the whole call is recorded (if configured)
+    fn call(
+        &self,
+        request: ProtocolMessage,
+        timeout: Duration,
+    ) -> Result<NodeMessage, ProtocolServiceError> {
+        let mut io = self.io.borrow_mut();
+        let started_at = SystemTime::now();
+        let timer = Instant::now();
+
+        let response = match io.tx.send(&request) {
+            Ok(()) => io
+                .rx
+                .try_receive(Some(timeout), Some(IpcCmdServer::IO_TIMEOUT)),
+            Err(e) => Err(e),
+        };
+
+        let elapsed = timer.elapsed();
+
+        // pings are just health checks, so they are not recorded/counted as calls
+        if !matches!(request, ProtocolMessage::PingCall) {
+            if let Some(recorder) = self.recorder.as_ref() {
+                recorder.record(&request, &response, started_at, elapsed);
+            }
+            if let Ok(mut call_stats) = self.call_stats.lock() {
+                call_stats.record_call(elapsed, response.is_err());
+            }
+        }
+        if response.is_err() {
+            self.ipc_failed.set(true);
+        }
+
+        response.map_err(ProtocolServiceError::from)
+    }
 
     /// Apply block
     pub fn apply_block(
         &self,
         request: ApplyBlockRequest,
     ) -> Result<ApplyBlockResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ProtocolMessage::ApplyBlockCall(request))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::APPLY_BLOCK_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
-        )? {
+        self.apply_block_with_timeout(request, Self::APPLY_BLOCK_TIMEOUT)
+    }
+
+    /// Apply block, if the response is not received in `timeout`, [IpcError::ReceiveMessageTimeouted] is returned
+    pub fn apply_block_with_timeout(
+        &self,
+        request: ApplyBlockRequest,
+        timeout: Duration,
+    ) -> Result<ApplyBlockResponse, ProtocolServiceError> {
+        match self.call(ProtocolMessage::ApplyBlockCall(request), timeout)? {
             NodeMessage::ApplyBlockResult(result) => {
                 result.map_err(|err| ProtocolError::ApplyBlockError { reason: err }.into())
             }
@@ -431,17 +529,10 @@ impl ProtocolController {
         protocol_hash: ProtocolHash,
         protocol_data: RustBytes,
     ) -> Result<(), ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ProtocolMessage::AssertEncodingForProtocolDataCall(
-                protocol_hash,
-                protocol_data,
-            ))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::ASSERT_ENCODING_FOR_PROTOCOL_DATA_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::AssertEncodingForProtocolDataCall(protocol_hash, protocol_data),
+            Self::ASSERT_ENCODING_FOR_PROTOCOL_DATA_TIMEOUT,
         )? {
             NodeMessage::AssertEncodingForProtocolDataResult(result) => result.map_err(|err| {
                 ProtocolError::AssertEncodingForProtocolDataError { reason: err }.into()
@@ -457,14 +548,10 @@ impl ProtocolController {
         &self,
         request: BeginApplicationRequest,
     ) -> Result<BeginApplicationResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ProtocolMessage::BeginApplicationCall(request))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::BEGIN_APPLICATION_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::BeginApplicationCall(request),
+            Self::BEGIN_APPLICATION_TIMEOUT,
         )? {
             NodeMessage::BeginApplicationResult(result) => {
                 result.map_err(|err| ProtocolError::BeginApplicationError { reason: err }.into())
@@ -480,14 +567,10 @@ impl ProtocolController {
         &self,
         request: BeginConstructionRequest,
     ) -> Result<PrevalidatorWrapper, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ProtocolMessage::BeginConstructionCall(request))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::BEGIN_CONSTRUCTION_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::BeginConstructionCall(request),
+            Self::BEGIN_CONSTRUCTION_TIMEOUT,
         )? {
             NodeMessage::BeginConstructionResult(result) => {
                 result.map_err(|err| ProtocolError::BeginConstructionError { reason: err }.into())
@@ -503,14 +586,10 @@ impl ProtocolController {
         &self,
         request: ValidateOperationRequest,
     ) -> Result<ValidateOperationResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ProtocolMessage::ValidateOperationCall(request))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::VALIDATE_OPERATION_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::ValidateOperationCall(request),
+            Self::VALIDATE_OPERATION_TIMEOUT,
         )? {
             NodeMessage::ValidateOperationResponse(result) => {
                 result.map_err(|err| ProtocolError::ValidateOperationError { reason: err }.into())
@@ -526,13 +605,10 @@ impl ProtocolController {
         &self,
         request: ComputePathRequest,
     ) -> Result<ComputePathResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ProtocolMessage::ComputePathCall(request))?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::COMPUTE_PATH_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::ComputePathCall(request),
+            Self::COMPUTE_PATH_TIMEOUT,
         )? {
             NodeMessage::ComputePathResponse(result) => {
                 result.map_err(|err| ProtocolError::ComputePathError { reason: err }.into())
@@ -548,14 +624,8 @@ impl ProtocolController {
         &self,
         msg: ProtocolMessage,
     ) -> Result<ProtocolRpcResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&msg)?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::CALL_PROTOCOL_RPC_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
-        )? {
+        match self.call(msg, Self::CALL_PROTOCOL_RPC_TIMEOUT)? {
             NodeMessage::RpcResponse(result) => {
                 result.map_err(|err| ProtocolError::ProtocolRpcError { reason: err }.into())
             }
@@ -578,14 +648,8 @@ impl ProtocolController {
         &self,
         msg: ProtocolMessage,
     ) -> Result<HelpersPreapplyResponse, ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&msg)?;
-
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::CALL_PROTOCOL_RPC_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
-        )? {
+        match self.call(msg, Self::CALL_PROTOCOL_RPC_TIMEOUT)? {
             NodeMessage::HelpersPreapplyResponse(result) => {
                 result.map_err(|err| ProtocolError::HelpersPreapplyError { reason: err }.into())
             }
@@ -616,13 +680,9 @@ impl ProtocolController {
         &self,
         settings: TezosRuntimeConfiguration,
     ) -> Result<(), ProtocolServiceError> {
-        let mut io = self.io.borrow_mut();
-        io.tx
-            .send(&ProtocolMessage::ChangeRuntimeConfigurationCall(settings))?;
-
-        match io.rx.try_receive(
-            Some(IpcCmdServer::IO_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+        match self.call(
+            ProtocolMessage::ChangeRuntimeConfigurationCall(settings),
+            IpcCmdServer::IO_TIMEOUT,
         )? {
             NodeMessage::ChangeRuntimeConfigurationResult(result) => result.map_err(|err| {
                 ProtocolError::TezosRuntimeConfigurationError { reason: err }.into()
@@ -663,28 +723,22 @@ impl ProtocolController {
         }
 
         // call init
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ProtocolMessage::InitProtocolContextCall(
-            InitProtocolContextParams {
-                storage_data_dir,
-                genesis: tezos_environment.genesis.clone(),
-                genesis_max_operations_ttl: tezos_environment
-                    .genesis_additional_data()
-                    .max_operations_ttl,
-                protocol_overrides: tezos_environment.protocol_overrides.clone(),
-                commit_genesis,
-                enable_testchain,
-                readonly,
-                patch_context,
-            },
-        ))?;
+        let request = ProtocolMessage::InitProtocolContextCall(InitProtocolContextParams {
+            storage_data_dir,
+            genesis: tezos_environment.genesis.clone(),
+            genesis_max_operations_ttl: tezos_environment
+                .genesis_additional_data()
+                .max_operations_ttl,
+            protocol_overrides: tezos_environment.protocol_overrides.clone(),
+            commit_genesis,
+            enable_testchain,
+            readonly,
+            patch_context,
+        });
 
         // wait for response
         // this might take a while, so we will use unusually long timeout
-        match io.rx.try_receive(
-            Some(Self::INIT_PROTOCOL_CONTEXT_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
-        )? {
+        match self.call(request, Self::INIT_PROTOCOL_CONTEXT_TIMEOUT)? {
             NodeMessage::InitProtocolContextResult(result) => {
                 if result.is_ok() {
                     // if context is initialized, and is not readonly, means is write, for wich we wait
@@ -713,6 +767,23 @@ impl ProtocolController {
         }
     }
 
+    /// Checks, if protocol runner responds, returns round-trip latency
+    pub fn ping(&self) -> Result<Duration, ProtocolServiceError> {
+        let timer = Instant::now();
+        match self.call(ProtocolMessage::PingCall, Self::PING_TIMEOUT)? {
+            NodeMessage::PingResult => {
+                let latency = timer.elapsed();
+                if let Ok(mut call_stats) = self.call_stats.lock() {
+                    call_stats.record_ping(latency);
+                }
+                Ok(latency)
+            }
+            message => Err(ProtocolServiceError::UnexpectedMessage {
+                message: message.into(),
+            }),
+        }
+    }
+
     /// Gracefully shutdown protocol runner
     pub fn shutdown(&mut self) -> Result<(), ProtocolServiceError> {
         if self.shutting_down {
@@ -721,13 +792,7 @@ impl ProtocolController {
         }
         self.shutting_down = true;
 
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ProtocolMessage::ShutdownCall)?;
-
-        match io.rx.try_receive(
-            Some(IpcCmdServer::IO_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
-        )? {
+        match self.call(ProtocolMessage::ShutdownCall, IpcCmdServer::IO_TIMEOUT)? {
             NodeMessage::ShutdownResult => Ok(()),
             message => Err(ProtocolServiceError::UnexpectedMessage {
                 message: message.into(),
@@ -796,21 +861,16 @@ impl ProtocolController {
             }
         })?;
 
-        let mut io = self.io.borrow_mut();
-        io.tx.send(&ProtocolMessage::GenesisResultDataCall(
-            GenesisResultDataParams {
+        match self.call(
+            ProtocolMessage::GenesisResultDataCall(GenesisResultDataParams {
                 genesis_context_hash: genesis_context_hash.clone(),
                 chain_id: main_chain_id,
                 genesis_protocol_hash: protocol_hash,
                 genesis_max_operations_ttl: tezos_environment
                     .genesis_additional_data()
                     .max_operations_ttl,
-            },
-        ))?;
-
-        match io.rx.try_receive(
-            Some(IpcCmdServer::IO_TIMEOUT),
-            Some(IpcCmdServer::IO_TIMEOUT),
+            }),
+            IpcCmdServer::IO_TIMEOUT,
         )? {
             NodeMessage::CommitGenesisResultData(result) => {
                 result.map_err(|err| ProtocolError::GenesisResultDataError { reason: err }.into())
@@ -0,0 +1,258 @@
+// Copyright (c) SimpleStaking and Tezedge Contributors
+// SPDX-License-Identifier: MIT
+
+//! Health and usage stats of protocol runners, which are managed by pools (see [TezosApiConnectionPool::runner_stats](crate::TezosApiConnectionPool::runner_stats)).
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::ProtocolRunnerLocation;
+
+pub type CallStatsRef = Arc<Mutex<CallStats>>;
+
+/// Runners of one pool (also across reconfigurations, so endpoint names are not unique)
+pub(crate) type RunnerRegistryRef = Arc<RwLock<Vec<RegisteredRunner>>>;
+
+pub(crate) fn init_runner_registry() -> RunnerRegistryRef {
+    Arc::new(RwLock::new(Vec::new()))
+}
+
+/// Calls of one protocol runner connection, recorded by [ProtocolController](crate::service::ProtocolController)
+#[derive(Debug, Default)]
+pub struct CallStats {
+    calls: u64,
+    errors: u64,
+    /// Latencies of the last [CallStats::LATENCY_SAMPLES] calls
+    latencies: VecDeque<Duration>,
+    last_ping: Option<Duration>,
+}
+
+impl CallStats {
+    pub const LATENCY_SAMPLES: usize = 1000;
+
+    /// Records finished call, `failed` means, that call failed on IPC level (error of protocol is still a valid response)
+    pub fn record_call(&mut self, latency: Duration, failed: bool) {
+        self.calls += 1;
+        if failed {
+            self.errors += 1;
+        }
+        if self.latencies.len() >= Self::LATENCY_SAMPLES {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(latency);
+    }
+
+    /// Pings are not counted as calls, we keep just round-trip latency of the last one
+    pub fn record_ping(&mut self, latency: Duration) {
+        self.last_ping = Some(latency);
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub fn last_ping(&self) -> Option<Duration> {
+        self.last_ping
+    }
+
+    /// Returns None, if there was no call yet
+    pub fn latency_percentiles(&self) -> Option<LatencyPercentiles> {
+        let mut sorted: Vec<Duration> = self.latencies.iter().copied().collect();
+        sorted.sort();
+
+        let percentile = |p: usize| -> Option<f64> {
+            let idx = (sorted.len() * p / 100).min(sorted.len().checked_sub(1)?);
+            Some(as_millis(sorted[idx]))
+        };
+
+        Some(LatencyPercentiles {
+            p50_ms: percentile(50)?,
+            p90_ms: percentile(90)?,
+            p99_ms: percentile(99)?,
+            max_ms: as_millis(*sorted.last()?),
+        })
+    }
+}
+
+/// Latencies of calls (from the last [CallStats::LATENCY_SAMPLES] calls)
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LatencyPercentiles {
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+/// Connection of the pool, which is alive (registered on create, unregistered on drop)
+pub(crate) struct RegisteredRunner {
+    pub(crate) name: String,
+    pub(crate) location: ProtocolRunnerLocation,
+    /// Pid of local sub-process
+    pub(crate) pid: Option<u32>,
+    pub(crate) created_at: Instant,
+    pub(crate) call_stats: CallStatsRef,
+}
+
+impl RegisteredRunner {
+    pub(crate) fn stats(&self) -> ProtocolRunnerStats {
+        let (calls, errors, last_ping, latency) = match self.call_stats.lock() {
+            Ok(stats) => (
+                stats.calls(),
+                stats.errors(),
+                stats.last_ping(),
+                stats.latency_percentiles(),
+            ),
+            Err(_) => (0, 0, None, None),
+        };
+        ProtocolRunnerStats {
+            name: self.name.clone(),
+            location: self.location.to_string(),
+            pid: self.pid,
+            age_secs: self.created_at.elapsed().as_secs(),
+            calls,
+            errors,
+            resident_memory: self.pid.and_then(resident_memory),
+            last_ping_ms: last_ping.map(as_millis),
+            latency,
+        }
+    }
+}
+
+/// Snapshot of stats of one protocol runner
+#[derive(Clone, Debug, Serialize)]
+pub struct ProtocolRunnerStats {
+    /// Endpoint name of the connection
+    pub name: String,
+    pub location: String,
+    pub pid: Option<u32>,
+    pub age_secs: u64,
+    pub calls: u64,
+    /// Calls, which failed on IPC level (e.g. timeout)
+    pub errors: u64,
+    /// Resident set size in bytes (only for local runners)
+    pub resident_memory: Option<u64>,
+    pub last_ping_ms: Option<f64>,
+    pub latency: Option<LatencyPercentiles>,
+}
+
+/// Memory usage of the process (in pages), as reported by linux in `/proc/<pid>/statm`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Statm {
+    pub size: u64,
+    pub resident: u64,
+    pub shared: u64,
+    pub text: u64,
+    pub lib: u64,
+    pub data: u64,
+    pub dt: u64,
+}
+
+impl Statm {
+    /// Parses content of the statm file, returns None, if it does not start with seven numbers
+    pub fn parse(statm: &str) -> Option<Statm> {
+        let mut fields = statm
+            .split_whitespace()
+            .map(|field| field.parse::<u64>().ok());
+        let mut next = || fields.next().flatten();
+        Some(Statm {
+            size: next()?,
+            resident: next()?,
+            shared: next()?,
+            text: next()?,
+            lib: next()?,
+            data: next()?,
+            dt: next()?,
+        })
+    }
+}
+
+/// Returns resident set size (in bytes) of the process, same data as in `/stats/memory/protocol_runners` (supported only on linux)
+pub(crate) fn resident_memory(pid: u32) -> Option<u64> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let statm = std::fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let resident_pages = Statm::parse(&statm)?.resident;
+    let page_size = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()?;
+    resident_pages.checked_mul(page_size as u64)
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut stats = CallStats::default();
+        assert!(stats.latency_percentiles().is_none());
+
+        for ms in 1..=100 {
+            stats.record_call(Duration::from_millis(ms), ms % 10 == 0);
+        }
+        stats.record_ping(Duration::from_millis(3));
+
+        assert_eq!(100, stats.calls());
+        assert_eq!(10, stats.errors());
+        assert_eq!(Some(Duration::from_millis(3)), stats.last_ping());
+
+        let percentiles = stats.latency_percentiles().expect("percentiles");
+        assert_eq!(51.0, percentiles.p50_ms);
+        assert_eq!(91.0, percentiles.p90_ms);
+        assert_eq!(100.0, percentiles.p99_ms);
+        assert_eq!(100.0, percentiles.max_ms);
+    }
+
+    #[test]
+    fn test_latency_samples_are_limited() {
+        let mut stats = CallStats::default();
+        for _ in 0..CallStats::LATENCY_SAMPLES {
+            stats.record_call(Duration::from_millis(500), false);
+        }
+        for _ in 0..CallStats::LATENCY_SAMPLES {
+            stats.record_call(Duration::from_millis(1), false);
+        }
+
+        assert_eq!(2 * CallStats::LATENCY_SAMPLES as u64, stats.calls());
+        let percentiles = stats.latency_percentiles().expect("percentiles");
+        assert_eq!(1.0, percentiles.max_ms);
+    }
+
+    #[test]
+    fn test_parse_statm() {
+        assert_eq!(
+            Some(Statm {
+                size: 218428,
+                resident: 10272,
+                shared: 6459,
+                text: 7780,
+                lib: 0,
+                data: 22424,
+                dt: 0,
+            }),
+            Statm::parse("218428 10272 6459 7780 0 22424 0\n")
+        );
+        assert_eq!(None, Statm::parse("218428 10272 6459"));
+        assert_eq!(None, Statm::parse("218428 x 6459 7780 0 22424 0"));
+    }
+
+    #[test]
+    fn test_resident_memory_of_current_process() {
+        if cfg!(target_os = "linux") {
+            let resident = resident_memory(std::process::id()).expect("resident memory");
+            assert!(resident > 0);
+        }
+    }
+}